@@ -8,6 +8,7 @@
 //
 
 use std::ffi::CString;
+use std::rc::Rc;
 use std::{
     cell::{Cell, RefCell},
     collections::HashMap,
@@ -16,12 +17,17 @@ use std::{
 };
 
 use pest::{
+    error::InputLocation,
     iterators::{Pair, Pairs},
     pratt_parser::PrattParser,
-    Parser,
+    Parser, Position,
 };
 
-use crate::program::{AwkRule, Constant, Function, OpCode, Pattern, Program, SpecialVar, VarId};
+use crate::program::{
+    AwkRule, BuiltinFunction, Constant, Function, GetlineSource, LineTable, OpCode, Output,
+    Pattern, Program, SpecialVar, VarId,
+};
+use crate::regex::Regex;
 
 lazy_static::lazy_static! {
     static ref PRATT_PARSER: PrattParser<Rule> = {
@@ -35,6 +41,7 @@ lazy_static::lazy_static! {
         .op(Op::infix(Rule::match_op, Assoc::Left)
             | Op::infix(Rule::not_match, Assoc::Left))
         .op(Op::infix(Rule::comp_op, Assoc::Left))
+        .op(Op::postfix(Rule::getline_command))
         .op(Op::infix(Rule::concat, Assoc::Left))
         .op(Op::infix(Rule::add, Assoc::Left)
             | Op::infix(Rule::binary_sub, Assoc::Left))
@@ -75,15 +82,22 @@ fn is_octal_digit(c: char) -> bool {
     ('0'..='7').contains(&c)
 }
 
-fn escape_string(s: &str) -> Result<String, String> {
+/// Replace the escape sequences of `s`, the text of a string literal or
+/// the value of a command line assignment, with the characters they stand
+/// for. The escape sequences POSIX does not define, like `\q`, stand for the
+/// character after the backslash.
+pub fn escape_string(s: &str) -> Result<String, String> {
     let mut result = String::new();
-    let s = s.trim_matches('"');
     let mut chars = s.chars();
     while let Some(c) = chars.next() {
         match c {
             '\\' => {
-                // there has to be a next character, otherwise the input is invalid
-                match chars.next().unwrap() {
+                let Some(escaped) = chars.next() else {
+                    // a trailing backslash stands for itself
+                    result.push('\\');
+                    break;
+                };
+                match escaped {
                     '"' => result.push('"'),
                     '/' => result.push('/'),
                     'a' => result.push('\x07'),
@@ -113,7 +127,7 @@ fn escape_string(s: &str) -> Result<String, String> {
                             result.push(first_char);
                         }
                     }
-                    other => return Err(format!("invalid escape sequence: \\{}", other)),
+                    other => result.push(other),
                 }
             }
             other => result.push(other),
@@ -122,6 +136,174 @@ fn escape_string(s: &str) -> Result<String, String> {
     Ok(result)
 }
 
+// the escape sequences of the text of a string literal whose meaning POSIX
+// leaves undefined, like \q
+fn non_portable_string_escapes(s: &str) -> Vec<char> {
+    let mut result = Vec::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            continue;
+        }
+        match chars.next() {
+            Some(escaped) if !is_octal_digit(escaped) && !"\"/abfnrtv\\".contains(escaped) => {
+                result.push(escaped)
+            }
+            _ => {}
+        }
+    }
+    result
+}
+
+// the escape sequences of an ERE token whose meaning POSIX leaves undefined,
+// like \d
+fn non_portable_ere_escapes(ere: &str) -> Vec<char> {
+    let mut result = Vec::new();
+    let mut chars = ere[1..ere.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            continue;
+        }
+        match chars.next() {
+            Some(escaped)
+                if !is_octal_digit(escaped) && !"/\"abfnrtv\\^$.[]|()*+?{}".contains(escaped) =>
+            {
+                result.push(escaped)
+            }
+            _ => {}
+        }
+    }
+    result
+}
+
+// the text of an ERE token, without its delimiters, with the escape
+// sequences of awk replaced by the characters they represent
+fn escape_ere(ere: &str) -> String {
+    let mut result = String::new();
+    let mut chars = ere[1..ere.len() - 1].chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        let escaped = match chars.next() {
+            Some('/') => '/',
+            Some('"') => '"',
+            Some('a') => '\x07',
+            Some('b') => '\x08',
+            Some('f') => '\x0C',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('v') => '\x0B',
+            Some(n) if is_octal_digit(n) => {
+                let mut char_code = n.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => {
+                            char_code = char_code * 8 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                let c = char::from_u32(char_code).unwrap_or('\0');
+                // the character stands for itself, even if it is special
+                if "\\^$.[]|()*+?{}".contains(c) {
+                    result.push('\\');
+                }
+                c
+            }
+            // other sequences, like \. or \\, are handled by the regex itself
+            Some(other) => {
+                result.push('\\');
+                other
+            }
+            None => '\\',
+        };
+        result.push(escaped);
+    }
+    result
+}
+
+fn builtin_function(function: Rule) -> BuiltinFunction {
+    match function {
+        Rule::atan2 => BuiltinFunction::Atan2,
+        Rule::cos => BuiltinFunction::Cos,
+        Rule::sin => BuiltinFunction::Sin,
+        Rule::exp => BuiltinFunction::Exp,
+        Rule::log => BuiltinFunction::Log,
+        Rule::sqrt => BuiltinFunction::Sqrt,
+        Rule::int => BuiltinFunction::Int,
+        Rule::rand => BuiltinFunction::Rand,
+        Rule::srand => BuiltinFunction::Srand,
+        Rule::gsub => BuiltinFunction::Gsub,
+        Rule::index => BuiltinFunction::Index,
+        Rule::length => BuiltinFunction::Length,
+        Rule::match_fn => BuiltinFunction::Match,
+        Rule::split => BuiltinFunction::Split,
+        Rule::sprintf => BuiltinFunction::Sprintf,
+        Rule::sub => BuiltinFunction::Sub,
+        Rule::substr => BuiltinFunction::Substr,
+        Rule::tolower => BuiltinFunction::ToLower,
+        Rule::toupper => BuiltinFunction::ToUpper,
+        Rule::close => BuiltinFunction::Close,
+        Rule::system => BuiltinFunction::System,
+        _ => unreachable!(
+            "encountered {:?} while compiling builtin function",
+            function
+        ),
+    }
+}
+
+// the minimum and maximum number of arguments of a builtin function
+fn builtin_arity(function: BuiltinFunction) -> (u16, u16) {
+    match function {
+        BuiltinFunction::Atan2 => (2, 2),
+        BuiltinFunction::Cos
+        | BuiltinFunction::Sin
+        | BuiltinFunction::Exp
+        | BuiltinFunction::Log
+        | BuiltinFunction::Sqrt
+        | BuiltinFunction::Int
+        | BuiltinFunction::ToLower
+        | BuiltinFunction::ToUpper
+        | BuiltinFunction::Close
+        | BuiltinFunction::System => (1, 1),
+        BuiltinFunction::Index | BuiltinFunction::Match => (2, 2),
+        BuiltinFunction::Length => (0, 1),
+        BuiltinFunction::Substr => (2, 3),
+        BuiltinFunction::Sprintf => (1, u16::MAX),
+        BuiltinFunction::Split => (2, 3),
+        BuiltinFunction::Sub | BuiltinFunction::Gsub => (2, 3),
+        BuiltinFunction::Rand => (0, 0),
+        BuiltinFunction::Srand => (0, 1),
+    }
+}
+
+// whether `instruction` pushes a reference to a variable, field or array
+// element
+fn is_reference(instruction: &OpCode) -> bool {
+    matches!(
+        instruction,
+        OpCode::VarRef(_)
+            | OpCode::LocalVarRef(_)
+            | OpCode::ArrayRef(_)
+            | OpCode::LocalArrayRef(_)
+            | OpCode::FieldRef
+    )
+}
+
+// the reference to the whole array named by an expression that is only a
+// variable name
+fn array_reference(instructions: &[OpCode]) -> Option<OpCode> {
+    match instructions {
+        [OpCode::VarRef(id)] => Some(OpCode::ArrayRef(*id)),
+        [OpCode::LocalVarRef(id)] => Some(OpCode::LocalArrayRef(*id)),
+        _ => None,
+    }
+}
+
 fn post_increment(val: &Cell<u32>) -> u32 {
     let result = val.get();
     val.set(result + 1);
@@ -165,12 +347,32 @@ pub enum GlobalName {
 type NameMap = HashMap<String, GlobalName>;
 type LocalMap = HashMap<String, VarId>;
 
+/// The break and continue jumps of a loop, which are set once the loop is
+/// compiled.
+#[derive(Default)]
+struct LoopJumps {
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
 struct Compiler {
     constants: RefCell<Vec<Constant>>,
     names: RefCell<NameMap>,
     last_global_var_id: Cell<u32>,
     last_global_function_id: Cell<u32>,
     in_function: bool,
+    // whether the action of a rule, run for each record, is being compiled
+    in_rule: bool,
+    // whether a BEGIN or an END action is being compiled
+    in_begin: bool,
+    in_end: bool,
+    loops: Vec<LoopJumps>,
+    // the portability problems found in the program, with their line
+    warnings: RefCell<Vec<(u32, String)>>,
+    // the offset of the start of each line of the program text
+    line_starts: Vec<usize>,
+    // the source lines of the instructions being compiled
+    lines: LineTable,
 }
 
 impl Default for Compiler {
@@ -200,6 +402,10 @@ impl Default for Compiler {
                 "FNR".to_string(),
                 GlobalName::SpecialVar(SpecialVar::Fnr as u32),
             ),
+            (
+                "FS".to_string(),
+                GlobalName::SpecialVar(SpecialVar::Fs as u32),
+            ),
             (
                 "NF".to_string(),
                 GlobalName::SpecialVar(SpecialVar::Nf as u32),
@@ -220,6 +426,10 @@ impl Default for Compiler {
                 "ORS".to_string(),
                 GlobalName::SpecialVar(SpecialVar::Ors as u32),
             ),
+            (
+                "RLENGTH".to_string(),
+                GlobalName::SpecialVar(SpecialVar::Rlength as u32),
+            ),
             (
                 "RS".to_string(),
                 GlobalName::SpecialVar(SpecialVar::Rs as u32),
@@ -239,11 +449,72 @@ impl Default for Compiler {
             last_global_var_id: Cell::new(SpecialVar::Count as u32),
             last_global_function_id: Cell::new(0),
             in_function: false,
+            in_rule: false,
+            in_begin: false,
+            in_end: false,
+            loops: Vec::new(),
+            warnings: RefCell::new(Vec::new()),
+            line_starts: vec![0],
+            lines: LineTable::default(),
         }
     }
 }
 
 impl Compiler {
+    // the line of the program text at `offset`, starting from 1
+    fn line(&self, offset: usize) -> u32 {
+        self.line_starts.partition_point(|&start| start <= offset) as u32
+    }
+
+    fn warn(&self, span: pest::Span, message: String) {
+        let line = self.line(span.start());
+        self.warnings.borrow_mut().push((line, message));
+    }
+
+    // warn about assignments to special variables that do not do what
+    // they seem to
+    fn check_assignment(&self, lvalue: &Pair<Rule>) {
+        let name = match lvalue.as_rule() {
+            Rule::name => lvalue.as_str(),
+            Rule::array_element => lvalue.clone().into_inner().next().unwrap().as_str(),
+            _ => return,
+        };
+        let message = match (lvalue.as_rule(), name) {
+            (Rule::name, "FILENAME") => "FILENAME is set by awk when it opens an input file",
+            (Rule::name, "FNR") if self.in_begin => {
+                "FNR is set to 0 by awk when it opens an input file"
+            }
+            (Rule::name, "NR") if self.in_end => {
+                "assigning NR in END does not change the number of records read"
+            }
+            (Rule::name, "FNR") if self.in_end => {
+                "assigning FNR in END does not change the number of records read"
+            }
+            (Rule::name, "NF") if self.in_begin => {
+                "assigning NF in BEGIN, before any record is read, creates empty fields"
+            }
+            (Rule::name, "FS") if self.in_rule => {
+                "assigning FS in a rule only affects the records after the current one"
+            }
+            (Rule::array_element, "ENVIRON") => {
+                "assigning to ENVIRON does not change the environment of commands"
+            }
+            _ => return,
+        };
+        self.warn(lvalue.as_span(), message.to_string());
+    }
+
+    // set the break and continue jumps of the innermost loop
+    fn end_loop(&mut self, instructions: &mut [OpCode], continue_target: usize, end: usize) {
+        let jumps = self.loops.pop().expect("no loop to end");
+        for index in jumps.breaks {
+            instructions[index] = OpCode::Jump(distance(index, end));
+        }
+        for index in jumps.continues {
+            instructions[index] = OpCode::Jump(distance(index, continue_target));
+        }
+    }
+
     fn push_constant(&self, constant: Constant) -> u32 {
         let index = self.constants.borrow().len() as u32;
         self.constants.borrow_mut().push(constant);
@@ -287,8 +558,21 @@ impl Compiler {
                 self.compile_expr(primary, &mut instructions, locals)?;
                 Ok(Expr::new(ExprKind::Number, instructions))
             }
+            Rule::index_list => {
+                let mut instructions = Vec::new();
+                self.compile_index(primary.into_inner(), &mut instructions, locals)?;
+                Ok(Expr::new(ExprKind::String, instructions))
+            }
             Rule::ere => {
-                let index = self.push_constant(Constant::Regex(primary.as_str().to_string()));
+                for escaped in non_portable_ere_escapes(primary.as_str()) {
+                    self.warn(
+                        primary.as_span(),
+                        format!("non-portable escape sequence '\\{}' in regex", escaped),
+                    );
+                }
+                let regex = Regex::new(&escape_ere(primary.as_str()))
+                    .map_err(|e| pest_error_from_span(primary.as_span(), e))?;
+                let index = self.push_constant(Constant::Regex(Rc::new(regex)));
                 Ok(Expr::new(
                     ExprKind::Regex,
                     vec![OpCode::PushConstant(index)],
@@ -306,9 +590,15 @@ impl Compiler {
                 ))
             }
             Rule::string => {
+                let text = &primary.as_str()[1..primary.as_str().len() - 1];
+                for escaped in non_portable_string_escapes(text) {
+                    self.warn(
+                        primary.as_span(),
+                        format!("non-portable escape sequence '\\{}' in string", escaped),
+                    );
+                }
                 let index = self.push_constant(Constant::String(
-                    escape_string(primary.as_str())
-                        .map_err(|e| pest_error_from_span(primary.as_span(), e))?,
+                    escape_string(text).map_err(|e| pest_error_from_span(primary.as_span(), e))?,
                 ));
                 Ok(Expr::new(
                     ExprKind::String,
@@ -324,39 +614,11 @@ impl Compiler {
                 let span = primary.as_span();
                 let mut inner = primary.into_inner();
                 let name = inner.next().unwrap().as_str();
-                let mut instructions = Vec::new();
-                let mut argc = 0;
-                for arg in inner {
-                    self.compile_expr(arg, &mut instructions, locals)?;
-                    argc += 1;
-                    if argc > u16::MAX {
-                        return Err(pest_error_from_span(
-                            span,
-                            "function call with too many arguments".to_string(),
-                        ));
-                    }
-                }
-                match self.names.borrow().get(name) {
+                let (id, parameter_count) = match self.names.borrow().get(name) {
                     Some(GlobalName::Function {
                         id,
                         parameter_count,
-                    }) => {
-                        if argc > *parameter_count as u16 {
-                            // TODO: other implementations simply issue a warning
-                            return Err(pest_error_from_span(
-                                span,
-                                format!("function '{}' called with too many arguments", name),
-                            ));
-                        } else if argc < *parameter_count as u16 {
-                            for _ in argc..*parameter_count as u16 {
-                                instructions.push(OpCode::PushUninitialized);
-                            }
-                        }
-                        instructions.push(OpCode::Call {
-                            id: *id,
-                            argc: *parameter_count as u16,
-                        });
-                    }
+                    }) => (*id, *parameter_count as usize),
                     Some(_) => {
                         return Err(pest_error_from_span(
                             span,
@@ -369,11 +631,119 @@ impl Compiler {
                             format!("call to undefined function '{}'", name),
                         ))
                     }
+                };
+                let mut instructions = Vec::new();
+                let mut argc = 0;
+                for arg in inner {
+                    self.compile_expr(arg, &mut instructions, locals)?;
+                    argc += 1;
+                }
+                if argc > parameter_count {
+                    // the extra arguments are evaluated and pushed with the
+                    // others. Call drops them when it sets up the frame
+                    self.warn(
+                        span,
+                        format!(
+                            "function '{}' called with more arguments than parameters",
+                            name
+                        ),
+                    );
                 }
+                for _ in argc..parameter_count {
+                    instructions.push(OpCode::PushUninitialized);
+                }
+                let argc = u16::try_from(argc.max(parameter_count)).map_err(|_| {
+                    pest_error_from_span(span, "function call with too many arguments".to_string())
+                })?;
+                instructions.push(OpCode::Call { id, argc });
                 Ok(Expr::new(ExprKind::Number, instructions))
             }
-            Rule::builtin_func => {
-                todo!();
+            Rule::builtin_function_call => {
+                let span = primary.as_span();
+                let mut inner = primary.into_inner();
+                let name = inner.next().unwrap();
+                let function = builtin_function(first_child(name.clone()).as_rule());
+                let mut instructions = Vec::new();
+                let mut argc = 0;
+                for arg in inner {
+                    if function == BuiltinFunction::Split && argc == 1 {
+                        // the array to split into
+                        let span = arg.as_span();
+                        let mut arg_instructions = Vec::new();
+                        self.compile_expr(arg, &mut arg_instructions, locals)?;
+                        let array = array_reference(&arg_instructions).ok_or_else(|| {
+                            pest_error_from_span(
+                                span,
+                                "second argument of split should be an array identifier"
+                                    .to_string(),
+                            )
+                        })?;
+                        instructions.push(array);
+                    } else if matches!(function, BuiltinFunction::Sub | BuiltinFunction::Gsub)
+                        && argc == 2
+                    {
+                        // the variable, field or array element to modify
+                        let span = arg.as_span();
+                        let start = instructions.len();
+                        self.compile_expr(arg, &mut instructions, locals)?;
+                        if !instructions[start..].last().is_some_and(is_reference) {
+                            return Err(pest_error_from_span(
+                                span,
+                                format!("third argument of {} should be an lvalue", name.as_str()),
+                            ));
+                        }
+                    } else {
+                        self.compile_expr(arg, &mut instructions, locals)?;
+                    }
+                    argc += 1;
+                }
+                if matches!(function, BuiltinFunction::Sub | BuiltinFunction::Gsub) && argc == 2 {
+                    // the default target is the record
+                    let zero = self.push_constant(Constant::Number(0.0));
+                    instructions.push(OpCode::PushConstant(zero));
+                    instructions.push(OpCode::FieldRef);
+                    argc += 1;
+                }
+                let (min_argc, max_argc) = builtin_arity(function);
+                if argc < min_argc || argc > max_argc {
+                    return Err(pest_error_from_span(
+                        span,
+                        format!(
+                            "function '{}' called with the wrong number of arguments",
+                            name.as_str()
+                        ),
+                    ));
+                }
+                instructions.push(OpCode::CallBuiltin { function, argc });
+                Ok(Expr::new(ExprKind::Number, instructions))
+            }
+            Rule::simple_get => {
+                let mut instructions = Vec::new();
+                let mut assign = false;
+                if let Some(lvalue) = primary.into_inner().next() {
+                    self.compile_lvalue(lvalue, &mut instructions, locals)?;
+                    assign = true;
+                }
+                instructions.push(OpCode::Getline {
+                    source: GetlineSource::Main,
+                    assign,
+                });
+                Ok(Expr::new(ExprKind::Number, instructions))
+            }
+            Rule::getline_file => {
+                let mut instructions = Vec::new();
+                let mut inner = primary.into_inner();
+                let assign = inner.len() == 2;
+                if assign {
+                    self.compile_lvalue(inner.next().unwrap(), &mut instructions, locals)?;
+                }
+                let file = self.map_primary(inner.next().unwrap(), locals)?;
+                instructions.extend(file.instructions);
+                instructions.push(OpCode::Getline {
+                    source: GetlineSource::File,
+                    assign,
+                });
+                Ok(Expr::new(ExprKind::Number, instructions))
             }
             _ => unreachable!(),
         }
@@ -385,7 +755,7 @@ impl Compiler {
         match op.as_rule() {
             Rule::dollarsign => {
                 instructions.push(OpCode::FieldRef);
-                Ok(Expr::new(ExprKind::Number, instructions))
+                Ok(Expr::new(ExprKind::LValue, instructions))
             }
             Rule::negate => {
                 instructions.push(OpCode::Negate);
@@ -417,7 +787,22 @@ impl Compiler {
         }
     }
 
-    fn map_postfix(&self, lhs: Expr, op: Pair<Rule>) -> Result<Expr, PestError> {
+    fn map_postfix(&self, lhs: Expr, op: Pair<Rule>, locals: &LocalMap) -> Result<Expr, PestError> {
+        if op.as_rule() == Rule::getline_command {
+            // the reference to the variable goes below the command
+            let mut instructions = Vec::new();
+            let mut assign = false;
+            if let Some(lvalue) = op.into_inner().next() {
+                self.compile_lvalue(lvalue, &mut instructions, locals)?;
+                assign = true;
+            }
+            instructions.extend(lhs.instructions);
+            instructions.push(OpCode::Getline {
+                source: GetlineSource::Command,
+                assign,
+            });
+            return Ok(Expr::new(ExprKind::Number, instructions));
+        }
         assert!(op.as_rule() == Rule::post_inc || op.as_rule() == Rule::post_dec);
         let kind = lhs.kind;
         let mut instructions = lhs.instructions;
@@ -441,30 +826,30 @@ impl Compiler {
         let mut instructions = lhs.instructions;
 
         match op.as_rule() {
+            // the value is 1 or 0, `!!rhs` when the right-hand side decides it
             Rule::and => {
-                instructions.push(OpCode::JumpIfFalse(rhs.instructions.len() as i32 + 1));
+                instructions.push(OpCode::JumpIfFalse(rhs.instructions.len() as i32 + 4));
                 instructions.extend(rhs.instructions);
+                instructions.extend([OpCode::Not, OpCode::Not, OpCode::Jump(3)]);
+                instructions.extend([OpCode::PushOne, OpCode::Not]);
                 return Ok(Expr::new(ExprKind::Number, instructions));
             }
             Rule::or => {
-                instructions.push(OpCode::JumpIfTrue(rhs.instructions.len() as i32 + 2));
+                instructions.push(OpCode::JumpIfTrue(rhs.instructions.len() as i32 + 4));
                 instructions.extend(rhs.instructions);
-                instructions.push(OpCode::Jump(1));
+                instructions.extend([OpCode::Not, OpCode::Not, OpCode::Jump(2)]);
                 instructions.push(OpCode::PushOne);
                 return Ok(Expr::new(ExprKind::Number, instructions));
             }
             Rule::in_op => {
-                match &rhs.instructions[..] {
-                    [OpCode::VarRef(id)] => instructions.push(OpCode::ArrayRef(*id)),
-                    [OpCode::LocalVarRef(id)] => instructions.push(OpCode::LocalArrayRef(*id)),
-                    _ => {
-                        return Err(pest_error_from_span(
-                            op.as_span(),
-                            "right-hand side of 'in' operator should be an array identifier"
-                                .to_string(),
-                        ));
-                    }
-                }
+                let array = array_reference(&rhs.instructions).ok_or_else(|| {
+                    pest_error_from_span(
+                        op.as_span(),
+                        "right-hand side of 'in' operator should be an array identifier"
+                            .to_string(),
+                    )
+                })?;
+                instructions.push(array);
                 instructions.push(OpCode::In);
                 return Ok(Expr::new(ExprKind::Number, instructions));
             }
@@ -540,11 +925,42 @@ impl Compiler {
         PRATT_PARSER
             .map_primary(|primary| self.map_primary(primary, locals))
             .map_prefix(|op, rhs| self.map_prefix(op, rhs?))
-            .map_postfix(|lhs, op| self.map_postfix(lhs?, op))
+            .map_postfix(|lhs, op| self.map_postfix(lhs?, op, locals))
             .map_infix(|lhs, op, rhs| self.map_infix(lhs?, op, rhs?))
             .parse(expr)
     }
 
+    // the instruction pushing a reference to the array `name`
+    fn array_ref(&self, name: Pair<Rule>, locals: &LocalMap) -> Result<OpCode, PestError> {
+        self.get_var(
+            name.as_str(),
+            locals,
+            OpCode::LocalArrayRef,
+            OpCode::ArrayRef,
+        )
+        .map_err(|msg| pest_error_from_span(name.as_span(), msg))
+    }
+
+    // compile the expressions of an array index, joined by SUBSEP
+    fn compile_index(
+        &self,
+        exprs: Pairs<Rule>,
+        instructions: &mut Vec<OpCode>,
+        locals: &LocalMap,
+    ) -> Result<(), PestError> {
+        for (i, expr) in exprs.enumerate() {
+            if i > 0 {
+                instructions.push(OpCode::VarRef(SpecialVar::Subsep as u32));
+                instructions.push(OpCode::Concat);
+            }
+            self.compile_expr(expr, instructions, locals)?;
+            if i > 0 {
+                instructions.push(OpCode::Concat);
+            }
+        }
+        Ok(())
+    }
+
     fn compile_lvalue(
         &self,
         lvalue: Pair<Rule>,
@@ -561,9 +977,7 @@ impl Compiler {
             Rule::array_element => {
                 let mut inner = lvalue.into_inner();
                 let name = inner.next().unwrap();
-                // FIXME: only supports expression lists of one element
-                let index = inner.next().unwrap();
-                self.compile_expr(index, instructions, locals)?;
+                self.compile_index(inner, instructions, locals)?;
                 let get_instruction = self
                     .get_var(
                         name.as_str(),
@@ -574,6 +988,11 @@ impl Compiler {
                     .map_err(|msg| pest_error_from_span(name.as_span(), msg))?;
                 instructions.push(get_instruction);
             }
+            Rule::field => {
+                let index = self.compile_binary_expr(first_child(lvalue).into_inner(), locals)?;
+                instructions.extend(index.instructions);
+                instructions.push(OpCode::FieldRef);
+            }
             _ => unreachable!(),
         }
         Ok(())
@@ -589,7 +1008,9 @@ impl Compiler {
         match expr.as_rule() {
             Rule::assignment => {
                 let mut inner = expr.into_inner();
-                self.compile_lvalue(inner.next().unwrap(), instructions, locals)?;
+                let lvalue = inner.next().unwrap();
+                self.check_assignment(&lvalue);
+                self.compile_lvalue(lvalue, instructions, locals)?;
                 let assignment_op = first_child(inner.next().unwrap());
                 if assignment_op.as_rule() != Rule::assign {
                     instructions.push(OpCode::Dup);
@@ -628,7 +1049,6 @@ impl Compiler {
                 let expr = self.compile_binary_expr(expr.into_inner(), locals)?;
                 instructions.extend(expr.instructions);
             }
-            Rule::input_function => todo!(),
             _ => unreachable!(
                 "encountered {:?} while compiling expression",
                 expr.as_rule()
@@ -648,9 +1068,14 @@ impl Compiler {
             Rule::delete_element => {
                 let mut inner = stmt.into_inner();
                 let name = inner.next().unwrap();
-                let index = inner.next().unwrap();
-                self.compile_expr(index, instructions, locals)?;
-                instructions.push(OpCode::Delete(0));
+                self.compile_index(inner, instructions, locals)?;
+                instructions.push(self.array_ref(name, locals)?);
+                instructions.push(OpCode::Delete);
+            }
+            Rule::delete_array => {
+                let name = first_child(stmt);
+                instructions.push(self.array_ref(name, locals)?);
+                instructions.push(OpCode::ClearArray);
             }
             Rule::expr => {
                 self.compile_expr(stmt, instructions, locals)?;
@@ -659,29 +1084,44 @@ impl Compiler {
             Rule::print_stmt => {
                 let mut inner = stmt.into_inner();
                 let print = inner.next().unwrap();
-                match print.as_rule() {
-                    Rule::simple_print | Rule::print_call => {
-                        let mut expressions = print.into_inner();
-                        if expressions.len() == 0 {
-                            todo!()
-                        } else {
-                            self.compile_expr(expressions.next().unwrap(), instructions, locals)?;
-                            if expressions.len() > 0 {
-                                self.compile_expr(
-                                    expressions.next().unwrap(),
-                                    instructions,
-                                    locals,
-                                )?;
-                                instructions.push(OpCode::Concat);
-                                for expr in expressions {
-                                    self.compile_expr(expr, instructions, locals)?;
-                                    instructions.push(OpCode::Concat);
-                                }
-                            }
-                        }
-                        instructions.push(OpCode::Print);
+                let is_printf = matches!(print.as_rule(), Rule::simple_printf | Rule::printf_call);
+                let span = print.as_span();
+                let mut argc = 0;
+                for expr in print.into_inner() {
+                    self.compile_expr(expr, instructions, locals)?;
+                    argc += 1;
+                }
+                if argc == 0 {
+                    if is_printf {
+                        return Err(pest_error_from_span(
+                            span,
+                            "printf requires a format".to_string(),
+                        ));
                     }
-                    _ => unreachable!(),
+                    // print without arguments prints the record
+                    let zero = self.push_constant(Constant::Number(0.0));
+                    instructions.push(OpCode::PushConstant(zero));
+                    instructions.push(OpCode::FieldRef);
+                    argc = 1;
+                }
+                let output = match inner.next() {
+                    Some(redirection) => {
+                        let mut redirection = redirection.into_inner();
+                        let output = match redirection.next().unwrap().as_rule() {
+                            Rule::truncate => Output::Truncate,
+                            Rule::append => Output::Append,
+                            Rule::pipe => Output::Pipe,
+                            _ => unreachable!(),
+                        };
+                        self.compile_expr(redirection.next().unwrap(), instructions, locals)?;
+                        output
+                    }
+                    None => Output::Stdout,
+                };
+                if is_printf {
+                    instructions.push(OpCode::Printf { argc, output });
+                } else {
+                    instructions.push(OpCode::Print { argc, output });
                 }
             }
             _ => unreachable!(
@@ -702,14 +1142,20 @@ impl Compiler {
         let start_index = instructions.len();
 
         let body = inner.next().unwrap();
+        self.loops.push(LoopJumps::default());
         self.compile_stmt(body, instructions, locals)?;
 
+        let condition_start = instructions.len();
         let condition = inner.next().unwrap();
+        self.lines
+            .add(condition_start, self.line(condition.as_span().start()));
         self.compile_expr(condition, instructions, locals)?;
         instructions.push(OpCode::JumpIfTrue(distance(
             instructions.len(),
             start_index,
         )));
+        let end = instructions.len();
+        self.end_loop(instructions, condition_start, end);
 
         Ok(())
     }
@@ -720,7 +1166,30 @@ impl Compiler {
         instructions: &mut Vec<OpCode>,
         locals: &LocalMap,
     ) -> Result<(), PestError> {
-        todo!();
+        let mut inner = for_each_stmt.into_inner();
+        let key = inner.next().unwrap();
+        // skip the `in` keyword
+        inner.next();
+        let array = inner.next().unwrap();
+        let body = inner.next().unwrap();
+
+        instructions.push(self.array_ref(array, locals)?);
+        instructions.push(OpCode::BeginForIn);
+        let loop_start = instructions.len();
+        self.compile_lvalue(key, instructions, locals)?;
+        let next_index = instructions.len();
+        instructions.push(OpCode::Invalid);
+
+        self.loops.push(LoopJumps::default());
+        self.compile_stmt(body, instructions, locals)?;
+        instructions.push(OpCode::Jump(distance(instructions.len(), loop_start)));
+        let end = instructions.len();
+        instructions[next_index] = OpCode::ForInNext(distance(next_index, end));
+        // pop the iterator
+        instructions.push(OpCode::Pop);
+        self.end_loop(instructions, loop_start, end);
+
+        Ok(())
     }
 
     fn compile_for(
@@ -742,11 +1211,17 @@ impl Compiler {
 
         let update = inner.next().unwrap();
         let body = inner.next().unwrap();
+        self.loops.push(LoopJumps::default());
         self.compile_stmt(body, instructions, locals)?;
+        let update_start = instructions.len();
+        self.lines
+            .add(update_start, self.line(update.as_span().start()));
         self.compile_simple_statement(update, instructions, locals)?;
         instructions.push(OpCode::Jump(distance(instructions.len(), condition_start)));
         instructions[for_jump_index] =
             OpCode::JumpIfFalse(distance(for_jump_index, instructions.len()));
+        let end = instructions.len();
+        self.end_loop(instructions, update_start, end);
 
         Ok(())
     }
@@ -766,11 +1241,14 @@ impl Compiler {
         instructions.push(OpCode::Invalid);
 
         let body = inner.next().unwrap();
+        self.loops.push(LoopJumps::default());
         self.compile_stmt(body, instructions, locals)?;
         instructions.push(OpCode::Jump(distance(instructions.len(), condition_start)));
 
         instructions[while_jump_index] =
             OpCode::JumpIfFalse(distance(while_jump_index, instructions.len()));
+        let end = instructions.len();
+        self.end_loop(instructions, condition_start, end);
 
         Ok(())
     }
@@ -826,6 +1304,10 @@ impl Compiler {
         instructions: &mut Vec<OpCode>,
         locals: &LocalMap,
     ) -> Result<(), PestError> {
+        if stmt.as_rule() != Rule::action {
+            let line = self.line(stmt.as_span().start());
+            self.lines.add(instructions.len(), line);
+        }
         match stmt.as_rule() {
             Rule::action => self.compile_action(stmt, instructions, locals),
             Rule::t_if => self.compile_if(stmt, instructions, locals),
@@ -841,11 +1323,26 @@ impl Compiler {
                 instructions.push(OpCode::Next);
                 Ok(())
             }
-            Rule::break_stmt => todo!(),
-            Rule::continue_stmt => todo!(),
+            Rule::break_stmt | Rule::continue_stmt => {
+                let Some(jumps) = self.loops.last_mut() else {
+                    return Err(pest_error_from_span(
+                        stmt.as_span(),
+                        format!("'{}' used outside of a loop", stmt.as_str()),
+                    ));
+                };
+                if stmt.as_rule() == Rule::break_stmt {
+                    jumps.breaks.push(instructions.len());
+                } else {
+                    jumps.continues.push(instructions.len());
+                }
+                instructions.push(OpCode::Invalid);
+                Ok(())
+            }
             Rule::exit_stmt => {
                 if let Some(expr) = stmt.into_inner().next() {
                     self.compile_expr(expr, instructions, locals)?;
+                } else {
+                    instructions.push(OpCode::PushUninitializedScalar);
                 }
                 instructions.push(OpCode::Exit);
                 Ok(())
@@ -902,6 +1399,7 @@ impl Compiler {
     }
 
     fn compile_rule(&mut self, rule: Pair<Rule>) -> Result<AwkRule, PestError> {
+        let line = self.line(rule.as_span().start());
         let rule = first_child(rule);
         match rule.as_rule() {
             Rule::action => {
@@ -910,6 +1408,8 @@ impl Compiler {
                 Ok(AwkRule {
                     pattern: Pattern::All,
                     instructions,
+                    line,
+                    lines: std::mem::take(&mut self.lines),
                 })
             }
             Rule::pattern_and_action => {
@@ -922,15 +1422,66 @@ impl Compiler {
                 Ok(AwkRule {
                     pattern,
                     instructions,
+                    line,
+                    lines: std::mem::take(&mut self.lines),
                 })
             }
             Rule::normal_pattern => {
-                todo!("needs to be implemented after printing and fields are implemented");
+                // a pattern without an action prints the matching records
+                let pattern = self.compile_normal_pattern(rule)?;
+                let zero = self.push_constant(Constant::Number(0.0));
+                Ok(AwkRule {
+                    pattern,
+                    instructions: vec![
+                        OpCode::PushConstant(zero),
+                        OpCode::FieldRef,
+                        OpCode::Print {
+                            argc: 1,
+                            output: Output::Stdout,
+                        },
+                    ],
+                    line,
+                    lines: LineTable(vec![(0, line)]),
+                })
             }
             _ => unreachable!("encountered {:?} while compiling rule", rule.as_rule()),
         }
     }
 
+    // add the function to the global names, so that it can be called before
+    // its definition and from its own body
+    fn declare_function(&mut self, function: Pair<Rule>) -> Result<(), PestError> {
+        let mut inner = function.into_inner();
+        let name = inner.next().unwrap();
+        let parameter_count = match inner.next().unwrap() {
+            param_list if param_list.as_rule() == Rule::param_list => {
+                param_list.into_inner().count() as u32
+            }
+            _ => 0,
+        };
+        match self.names.get_mut().get(name.as_str()) {
+            Some(GlobalName::Function { .. }) => Err(pest_error_from_span(
+                name.as_span(),
+                format!("function '{}' redefined", name.as_str()),
+            )),
+            Some(_) => Err(pest_error_from_span(
+                name.as_span(),
+                format!("cannot use '{}' as a function name", name.as_str()),
+            )),
+            None => {
+                let id = post_increment(&self.last_global_function_id);
+                self.names.get_mut().insert(
+                    name.as_str().to_string(),
+                    GlobalName::Function {
+                        id,
+                        parameter_count,
+                    },
+                );
+                Ok(())
+            }
+        }
+    }
+
     fn compile_function_definition(&mut self, function: Pair<Rule>) -> Result<Function, PestError> {
         let mut inner = function.into_inner();
         let name = inner.next().unwrap().as_str();
@@ -967,48 +1518,65 @@ impl Compiler {
             instructions.push(OpCode::Return);
         }
 
-        let id = post_increment(&self.last_global_function_id);
-        self.names.get_mut().insert(
-            name.to_string(),
-            GlobalName::Function {
-                id,
-                parameter_count: parameters_count as u32,
-            },
-        );
         Ok(Function {
+            name: name.to_string(),
             parameters_count,
             instructions,
+            lines: std::mem::take(&mut self.lines),
         })
     }
 }
 
 pub fn compile_program(text: &str) -> Result<Program, PestError> {
     let mut begin_instructions = Vec::new();
+    let mut begin_lines = LineTable::default();
     let mut rules = Vec::new();
     let mut end_instructions = Vec::new();
+    let mut end_lines = LineTable::default();
     let mut functions = Vec::new();
 
-    let mut compiler = Compiler::default();
+    let mut compiler = Compiler {
+        line_starts: std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect(),
+        ..Default::default()
+    };
     let program = AwkParser::parse(Rule::program, text)?.next().unwrap();
 
+    for item in program.clone().into_inner() {
+        if item.as_rule() == Rule::function_definition {
+            compiler.declare_function(item)?;
+        }
+    }
+
     for item in program.into_inner() {
         match item.as_rule() {
             Rule::begin_action => {
+                std::mem::swap(&mut compiler.lines, &mut begin_lines);
+                compiler.in_begin = true;
                 compiler.compile_action(
                     first_child(item),
                     &mut begin_instructions,
                     &HashMap::new(),
                 )?;
+                compiler.in_begin = false;
+                std::mem::swap(&mut compiler.lines, &mut begin_lines);
             }
             Rule::end_action => {
+                std::mem::swap(&mut compiler.lines, &mut end_lines);
+                compiler.in_end = true;
                 compiler.compile_action(
                     first_child(item),
                     &mut end_instructions,
                     &HashMap::new(),
                 )?;
+                compiler.in_end = false;
+                std::mem::swap(&mut compiler.lines, &mut end_lines);
             }
             Rule::rule => {
+                compiler.in_rule = true;
                 rules.push(compiler.compile_rule(item)?);
+                compiler.in_rule = false;
             }
             Rule::function_definition => {
                 functions.push(compiler.compile_function_definition(item)?);
@@ -1018,16 +1586,67 @@ pub fn compile_program(text: &str) -> Result<Program, PestError> {
         }
     }
 
+    let global_names = compiler
+        .names
+        .into_inner()
+        .into_iter()
+        .filter_map(|(name, global)| match global {
+            GlobalName::Variable(id) | GlobalName::SpecialVar(id) => Some((name, id)),
+            GlobalName::Function { .. } => None,
+        })
+        .collect();
     Ok(Program {
         constants: compiler.constants.into_inner(),
         begin_instructions,
+        begin_lines,
         rules,
         end_instructions,
+        end_lines,
         functions,
         globals_count: compiler.last_global_var_id.get() as usize,
+        global_names,
+        source_files: Vec::new(),
+        warnings: compiler.warnings.into_inner(),
     })
 }
 
+/// A file of the program, given with the -f option.
+pub struct SourceFile {
+    pub filename: String,
+    pub contents: String,
+}
+
+/// Compile the program made of `files`, one after the other. Errors are
+/// located in the file they come from.
+pub fn compile_program_files(files: &[SourceFile]) -> Result<Program, PestError> {
+    let mut text = String::new();
+    let mut file_starts = Vec::with_capacity(files.len());
+    for file in files {
+        file_starts.push(text.len());
+        text.push_str(&file.contents);
+        text.push('\n');
+    }
+    let mut program = compile_program(&text).map_err(|error| {
+        let start = match error.location {
+            InputLocation::Pos(start) | InputLocation::Span((start, _)) => start,
+        };
+        let index = file_starts.partition_point(|&file_start| file_start <= start) - 1;
+        let file = &files[index];
+        let offset = (start - file_starts[index]).min(file.contents.len());
+        let position = Position::new(&file.contents, offset).expect("offset is in the file");
+        PestError::new_from_pos(error.variant, position).with_path(&file.filename)
+    })?;
+    program.source_files = files
+        .iter()
+        .zip(file_starts)
+        .map(|(file, start)| {
+            let line = text[..start].matches('\n').count() as u32 + 1;
+            (file.filename.clone(), line)
+        })
+        .collect();
+    Ok(program)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -1125,6 +1744,36 @@ mod test {
         assert_eq!(constants, vec![Constant::Number(5.34)]);
     }
 
+    #[test]
+    fn test_compile_program_files() {
+        let file = |filename: &str, contents: &str| SourceFile {
+            filename: filename.to_string(),
+            contents: contents.to_string(),
+        };
+        let program = compile_program_files(&[
+            file("lib.awk", "function f(x) { return x }"),
+            file("main.awk", "BEGIN { f(1) }"),
+        ])
+        .expect("error compiling program");
+        assert_eq!(program.functions.len(), 1);
+
+        let error = compile_program_files(&[
+            file("lib.awk", "function f(x) {\n  return x\n}\n"),
+            file("main.awk", "BEGIN {\n  x = = 1\n}"),
+        ])
+        .expect_err("expected error compiling program");
+        assert_eq!(error.line_col, pest::error::LineColLocation::Pos((2, 7)));
+        assert!(error.to_string().contains("main.awk:2:7"));
+    }
+
+    #[test]
+    fn test_escape_string() {
+        assert_eq!(escape_string(r"a\tb\101"), Ok("a\tbA".to_string()));
+        assert_eq!(escape_string(r"\\\/"), Ok("\\/".to_string()));
+        assert_eq!(escape_string("end\\"), Ok("end\\".to_string()));
+        assert_eq!(escape_string(r"\q\."), Ok("q.".to_string()));
+    }
+
     #[test]
     fn test_compile_string() {
         let (_, constants) = compile_expr(r#""hello""#);
@@ -1136,6 +1785,9 @@ mod test {
             vec![Constant::String("hello\nworld".to_string())]
         );
 
+        let (_, constants) = compile_expr(r#""\"quoted\"""#);
+        assert_eq!(constants, vec![Constant::String("\"quoted\"".to_string())]);
+
         let (_, constants) = compile_expr(r#""hello\tworld""#);
         assert_eq!(
             constants,
@@ -1204,6 +1856,12 @@ mod test {
             instructions,
             vec![OpCode::VarRef(FIRST_GLOBAL_VAR), OpCode::PostDec]
         );
+
+        let (instructions, _) = compile_expr("$1++");
+        assert_eq!(
+            instructions,
+            vec![OpCode::PushConstant(0), OpCode::FieldRef, OpCode::PostInc]
+        );
     }
 
     #[test]
@@ -1552,6 +2210,37 @@ mod test {
         assert_eq!(constants, vec![Constant::String("a".to_string())]);
     }
 
+    #[test]
+    fn test_compile_multidimensional_index() {
+        let (instructions, _) = compile_expr("(1, 2) in map");
+        assert_eq!(
+            instructions,
+            vec![
+                OpCode::PushConstant(0),
+                OpCode::VarRef(SpecialVar::Subsep as u32),
+                OpCode::Concat,
+                OpCode::PushConstant(1),
+                OpCode::Concat,
+                OpCode::ArrayRef(FIRST_GLOBAL_VAR),
+                OpCode::In
+            ]
+        );
+
+        let (instructions, _) = compile_stmt("delete a[1, 2];");
+        assert_eq!(
+            instructions,
+            vec![
+                OpCode::PushConstant(0),
+                OpCode::VarRef(SpecialVar::Subsep as u32),
+                OpCode::Concat,
+                OpCode::PushConstant(1),
+                OpCode::Concat,
+                OpCode::ArrayRef(FIRST_GLOBAL_VAR),
+                OpCode::Delete,
+            ]
+        );
+    }
+
     #[test]
     fn test_compile_and() {
         let (instructions, constants) = compile_expr("1 && 2");
@@ -1559,8 +2248,13 @@ mod test {
             instructions,
             vec![
                 OpCode::PushConstant(0),
-                OpCode::JumpIfFalse(2),
+                OpCode::JumpIfFalse(5),
                 OpCode::PushConstant(1),
+                OpCode::Not,
+                OpCode::Not,
+                OpCode::Jump(3),
+                OpCode::PushOne,
+                OpCode::Not,
             ]
         );
         assert_eq!(
@@ -1575,10 +2269,15 @@ mod test {
                 OpCode::PushConstant(0),
                 OpCode::PushConstant(1),
                 OpCode::Lt,
-                OpCode::JumpIfFalse(4),
+                OpCode::JumpIfFalse(7),
                 OpCode::PushConstant(2),
                 OpCode::PushConstant(3),
                 OpCode::Ge,
+                OpCode::Not,
+                OpCode::Not,
+                OpCode::Jump(3),
+                OpCode::PushOne,
+                OpCode::Not,
             ]
         );
         assert_eq!(
@@ -1599,9 +2298,11 @@ mod test {
             instructions,
             vec![
                 OpCode::PushConstant(0),
-                OpCode::JumpIfTrue(3),
+                OpCode::JumpIfTrue(5),
                 OpCode::PushConstant(1),
-                OpCode::Jump(1),
+                OpCode::Not,
+                OpCode::Not,
+                OpCode::Jump(2),
                 OpCode::PushOne,
             ]
         );
@@ -1617,11 +2318,13 @@ mod test {
                 OpCode::PushConstant(0),
                 OpCode::PushConstant(1),
                 OpCode::Lt,
-                OpCode::JumpIfTrue(5),
+                OpCode::JumpIfTrue(7),
                 OpCode::PushConstant(2),
                 OpCode::PushConstant(3),
                 OpCode::Ge,
-                OpCode::Jump(1),
+                OpCode::Not,
+                OpCode::Not,
+                OpCode::Jump(2),
                 OpCode::PushOne,
             ]
         );
@@ -1933,7 +2636,10 @@ mod test {
     #[test]
     fn test_compile_exit() {
         let (instructions, _) = compile_stmt("exit;");
-        assert_eq!(instructions, vec![OpCode::Exit]);
+        assert_eq!(
+            instructions,
+            vec![OpCode::PushUninitializedScalar, OpCode::Exit]
+        );
 
         let (instructions, constant) = compile_stmt("exit 1;");
         assert_eq!(instructions, vec![OpCode::PushConstant(0), OpCode::Exit]);
@@ -1959,14 +2665,67 @@ mod test {
         let (instructions, constant) = compile_stmt("delete a[1];");
         assert_eq!(
             instructions,
-            vec![OpCode::PushConstant(0), OpCode::Delete(0),]
+            vec![
+                OpCode::PushConstant(0),
+                OpCode::ArrayRef(FIRST_GLOBAL_VAR),
+                OpCode::Delete,
+            ]
+        );
+
+        let (instructions, _) = compile_stmt("delete a;");
+        assert_eq!(
+            instructions,
+            vec![OpCode::ArrayRef(FIRST_GLOBAL_VAR), OpCode::ClearArray]
+        );
+    }
+
+    #[test]
+    fn test_compile_for_each() {
+        let (instructions, _) = compile_stmt("for (k in a) continue;");
+        assert_eq!(
+            instructions,
+            vec![
+                OpCode::ArrayRef(FIRST_GLOBAL_VAR),
+                OpCode::BeginForIn,
+                OpCode::VarRef(FIRST_GLOBAL_VAR + 1),
+                OpCode::ForInNext(3),
+                OpCode::Jump(-2),
+                OpCode::Jump(-3),
+                OpCode::Pop,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compile_break() {
+        let (instructions, _) = compile_stmt("while (1) break;");
+        assert_eq!(
+            instructions,
+            vec![
+                OpCode::PushConstant(0),
+                OpCode::JumpIfFalse(3),
+                OpCode::Jump(2),
+                OpCode::Jump(-3),
+            ]
         );
+
+        assert!(compile_program("BEGIN { break; }").is_err());
+        assert!(compile_program("BEGIN { if (1) continue; }").is_err());
     }
 
     #[test]
     fn test_compile_simple_print() {
         let (instructions, constant) = compile_stmt("print 1;");
-        assert_eq!(instructions, vec![OpCode::PushConstant(0), OpCode::Print,]);
+        assert_eq!(
+            instructions,
+            vec![
+                OpCode::PushConstant(0),
+                OpCode::Print {
+                    argc: 1,
+                    output: Output::Stdout
+                },
+            ]
+        );
         assert_eq!(constant, vec![Constant::Number(1.0),]);
 
         let (instructions, constant) = compile_stmt("print 1, 2;");
@@ -1975,8 +2734,10 @@ mod test {
             vec![
                 OpCode::PushConstant(0),
                 OpCode::PushConstant(1),
-                OpCode::Concat,
-                OpCode::Print,
+                OpCode::Print {
+                    argc: 2,
+                    output: Output::Stdout
+                },
             ]
         );
         assert_eq!(constant, vec![Constant::Number(1.0), Constant::Number(2.0)]);
@@ -1987,14 +2748,13 @@ mod test {
             vec![
                 OpCode::PushConstant(0),
                 OpCode::PushConstant(1),
-                OpCode::Concat,
                 OpCode::PushConstant(2),
-                OpCode::Concat,
                 OpCode::PushConstant(3),
-                OpCode::Concat,
                 OpCode::PushConstant(4),
-                OpCode::Concat,
-                OpCode::Print,
+                OpCode::Print {
+                    argc: 5,
+                    output: Output::Stdout
+                },
             ]
         );
         assert_eq!(
@@ -2012,7 +2772,16 @@ mod test {
     #[test]
     fn test_compile_print_call() {
         let (instructions, constant) = compile_stmt("print (\"hello\");");
-        assert_eq!(instructions, vec![OpCode::PushConstant(0), OpCode::Print,]);
+        assert_eq!(
+            instructions,
+            vec![
+                OpCode::PushConstant(0),
+                OpCode::Print {
+                    argc: 1,
+                    output: Output::Stdout
+                },
+            ]
+        );
         assert_eq!(constant, vec![Constant::String("hello".to_string())]);
 
         let (instructions, constants) = compile_stmt("print (\"hello\", 1);");
@@ -2021,8 +2790,10 @@ mod test {
             vec![
                 OpCode::PushConstant(0),
                 OpCode::PushConstant(1),
-                OpCode::Concat,
-                OpCode::Print,
+                OpCode::Print {
+                    argc: 2,
+                    output: Output::Stdout
+                },
             ]
         );
         assert_eq!(
@@ -2036,14 +2807,13 @@ mod test {
             vec![
                 OpCode::PushConstant(0),
                 OpCode::PushConstant(1),
-                OpCode::Concat,
                 OpCode::PushConstant(2),
-                OpCode::Concat,
                 OpCode::PushConstant(3),
-                OpCode::Concat,
                 OpCode::PushConstant(4),
-                OpCode::Concat,
-                OpCode::Print,
+                OpCode::Print {
+                    argc: 5,
+                    output: Output::Stdout
+                },
             ]
         );
         assert_eq!(
@@ -2058,6 +2828,197 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_compile_printf() {
+        let (instructions, _) = compile_stmt(r#"printf "%d\n", 1 > "file";"#);
+        assert_eq!(
+            instructions,
+            vec![
+                OpCode::PushConstant(0),
+                OpCode::PushConstant(1),
+                OpCode::PushConstant(2),
+                OpCode::Printf {
+                    argc: 2,
+                    output: Output::Truncate
+                },
+            ]
+        );
+
+        let (instructions, _) = compile_stmt(r#"printf("%s", "a");"#);
+        assert_eq!(
+            instructions.last(),
+            Some(&OpCode::Printf {
+                argc: 2,
+                output: Output::Stdout
+            })
+        );
+
+        assert!(compile_program("BEGIN { printf }").is_err());
+    }
+
+    #[test]
+    fn test_compile_print_redirection() {
+        let (instructions, constants) = compile_stmt(r#"print 1 > "file";"#);
+        assert_eq!(
+            instructions,
+            vec![
+                OpCode::PushConstant(0),
+                OpCode::PushConstant(1),
+                OpCode::Print {
+                    argc: 1,
+                    output: Output::Truncate
+                },
+            ]
+        );
+        assert_eq!(
+            constants,
+            vec![Constant::Number(1.0), Constant::String("file".to_string())]
+        );
+
+        let (instructions, _) = compile_stmt(r#"print 1, 2 >> "file";"#);
+        assert_eq!(
+            instructions.last(),
+            Some(&OpCode::Print {
+                argc: 2,
+                output: Output::Append
+            })
+        );
+
+        let (instructions, _) = compile_stmt(r#"print | "cat";"#);
+        assert_eq!(
+            instructions,
+            vec![
+                OpCode::PushConstant(0),
+                OpCode::FieldRef,
+                OpCode::PushConstant(1),
+                OpCode::Print {
+                    argc: 1,
+                    output: Output::Pipe
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compile_getline() {
+        let (instructions, _) = compile_expr("getline");
+        assert_eq!(
+            instructions,
+            vec![OpCode::Getline {
+                source: GetlineSource::Main,
+                assign: false
+            }]
+        );
+
+        let (instructions, _) = compile_expr("getline a");
+        assert_eq!(
+            instructions,
+            vec![
+                OpCode::VarRef(FIRST_GLOBAL_VAR),
+                OpCode::Getline {
+                    source: GetlineSource::Main,
+                    assign: true
+                }
+            ]
+        );
+
+        let (instructions, _) = compile_expr(r#"getline a < "file""#);
+        assert_eq!(
+            instructions,
+            vec![
+                OpCode::VarRef(FIRST_GLOBAL_VAR),
+                OpCode::PushConstant(0),
+                OpCode::Getline {
+                    source: GetlineSource::File,
+                    assign: true
+                }
+            ]
+        );
+
+        let (instructions, _) = compile_expr(r#""cmd" | getline"#);
+        assert_eq!(
+            instructions,
+            vec![
+                OpCode::PushConstant(0),
+                OpCode::Getline {
+                    source: GetlineSource::Command,
+                    assign: false
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compile_builtin_function_call() {
+        let (instructions, _) = compile_expr(r#"close("file")"#);
+        assert_eq!(
+            instructions,
+            vec![
+                OpCode::PushConstant(0),
+                OpCode::CallBuiltin {
+                    function: BuiltinFunction::Close,
+                    argc: 1
+                }
+            ]
+        );
+
+        assert!(compile_program("BEGIN { close() }").is_err());
+        assert!(compile_program(r#"BEGIN { close("a", "b") }"#).is_err());
+    }
+
+    #[test]
+    fn test_compile_split() {
+        let (instructions, _) = compile_expr(r#"split("a b", arr)"#);
+        assert_eq!(
+            instructions,
+            vec![
+                OpCode::PushConstant(0),
+                OpCode::ArrayRef(FIRST_GLOBAL_VAR),
+                OpCode::CallBuiltin {
+                    function: BuiltinFunction::Split,
+                    argc: 2
+                }
+            ]
+        );
+
+        assert!(compile_program(r#"BEGIN { split("a", arr[1]) }"#).is_err());
+        assert!(compile_program(r#"BEGIN { split("a", 1, 2) }"#).is_err());
+    }
+
+    #[test]
+    fn test_compile_sub() {
+        let (instructions, _) = compile_expr(r#"sub("a", "b")"#);
+        assert_eq!(
+            instructions,
+            vec![
+                OpCode::PushConstant(0),
+                OpCode::PushConstant(1),
+                OpCode::PushConstant(2),
+                OpCode::FieldRef,
+                OpCode::CallBuiltin {
+                    function: BuiltinFunction::Sub,
+                    argc: 3
+                }
+            ]
+        );
+
+        let (instructions, _) = compile_expr(r#"gsub("a", "b", x)"#);
+        assert_eq!(
+            instructions,
+            vec![
+                OpCode::PushConstant(0),
+                OpCode::PushConstant(1),
+                OpCode::VarRef(FIRST_GLOBAL_VAR),
+                OpCode::CallBuiltin {
+                    function: BuiltinFunction::Gsub,
+                    argc: 3
+                }
+            ]
+        );
+
+        assert!(compile_program(r#"BEGIN { sub("a", "b", x + 1) }"#).is_err());
+    }
+
     #[test]
     fn test_compile_empty_function() {
         let program = compile_correct_program(
@@ -2167,6 +3128,128 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_compile_line_table() {
+        let program = compile_correct_program(
+            "BEGIN {\n  x = 1\n  if (x)\n    y = 2\n}\nfunction f(a) {\n  return a\n}\n{\n  print\n}",
+        );
+        assert_eq!(program.begin_lines, LineTable(vec![(0, 2), (4, 3), (6, 4)]));
+        assert_eq!(program.functions[0].name, "f");
+        assert_eq!(program.functions[0].lines, LineTable(vec![(0, 7)]));
+        assert_eq!(program.rules[0].line, 9);
+        assert_eq!(program.rules[0].lines, LineTable(vec![(0, 10)]));
+        assert_eq!(program.begin_lines.line(5), Some(3));
+        assert_eq!(program.end_lines.line(0), None);
+    }
+
+    #[test]
+    fn test_compile_call_to_function_defined_later() {
+        let program = compile_correct_program(
+            r#"
+            function first() { return second(); }
+            function second() { return first(); }
+            BEGIN { second() }
+            "#,
+        );
+        assert_eq!(
+            program.functions[0].instructions,
+            vec![OpCode::Call { id: 1, argc: 0 }, OpCode::Return]
+        );
+        assert_eq!(
+            program.functions[1].instructions,
+            vec![OpCode::Call { id: 0, argc: 0 }, OpCode::Return]
+        );
+        assert_eq!(
+            program.begin_instructions,
+            vec![OpCode::Call { id: 1, argc: 0 }, OpCode::Pop]
+        );
+    }
+
+    #[test]
+    fn test_compile_function_redefinition_fails() {
+        does_not_compile("function f() {} function f(a) {}");
+        does_not_compile("function NR() {}");
+        does_not_compile("function f(f) {}");
+    }
+
+    #[test]
+    fn test_compile_function_call_with_too_many_arguments() {
+        let program = compile_correct_program("function f(a) {}\nBEGIN { f(1, 2) }");
+        assert_eq!(
+            program.begin_instructions,
+            vec![
+                OpCode::PushConstant(0),
+                OpCode::PushConstant(1),
+                OpCode::Call { id: 0, argc: 2 },
+                OpCode::Pop,
+            ]
+        );
+        assert_eq!(
+            program.warnings,
+            vec![(
+                2,
+                "function 'f' called with more arguments than parameters".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_compile_warnings() {
+        let program = compile_correct_program(
+            r#"BEGIN { FS = ":"; FILENAME = "file" }
+            { FS = ","; ENVIRON["HOME"] = "/" }
+            /\d\.\// { x = 1 }
+            function f() { FS = "," }
+            BEGIN { NR = 10; FNR = 1; NF = 2; x = "\q\." }
+            { NR = 1; NF = 3 }
+            END { NR = 1; FNR = 1; NF = 1 }"#,
+        );
+        assert_eq!(
+            program.warnings,
+            vec![
+                (
+                    1,
+                    "FILENAME is set by awk when it opens an input file".to_string()
+                ),
+                (
+                    2,
+                    "assigning FS in a rule only affects the records after the current one"
+                        .to_string()
+                ),
+                (
+                    2,
+                    "assigning to ENVIRON does not change the environment of commands".to_string()
+                ),
+                (3, "non-portable escape sequence '\\d' in regex".to_string()),
+                (
+                    5,
+                    "FNR is set to 0 by awk when it opens an input file".to_string()
+                ),
+                (
+                    5,
+                    "assigning NF in BEGIN, before any record is read, creates empty fields"
+                        .to_string()
+                ),
+                (
+                    5,
+                    "non-portable escape sequence '\\q' in string".to_string()
+                ),
+                (
+                    5,
+                    "non-portable escape sequence '\\.' in string".to_string()
+                ),
+                (
+                    7,
+                    "assigning NR in END does not change the number of records read".to_string()
+                ),
+                (
+                    7,
+                    "assigning FNR in END does not change the number of records read".to_string()
+                ),
+            ]
+        );
+    }
+
     #[test]
     fn test_compile_function_call_with_too_few_arguments() {
         let program = compile_correct_program(
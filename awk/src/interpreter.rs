@@ -8,8 +8,17 @@
 //
 
 use std::collections::HashMap;
-
-use crate::program::{Constant, Function, OpCode, Program, SpecialVar};
+use std::ffi::CString;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::compiler::escape_string;
+use crate::program::{
+    source_location, AwkRule, BuiltinFunction, Constant, Function, GetlineSource, LineTable,
+    OpCode, Output, Pattern, Program, SpecialVar,
+};
+use crate::regex::{Regex, RegexCache};
+use crate::streams::{RecordReader, RecordSeparator, Streams};
 
 fn get_or_insert(array: &mut HashMap<String, ScalarValue>, key: String) -> &mut ScalarValue {
     array.entry(key).or_insert(ScalarValue::Uninitialized)
@@ -19,6 +28,9 @@ fn get_or_insert(array: &mut HashMap<String, ScalarValue>, key: String) -> &mut
 enum ScalarValue {
     Number(f64),
     String(String),
+    /// A string that comes from the input, like a field, which compares as
+    /// a number when it looks like one.
+    StrNum(String),
     Uninitialized,
 }
 
@@ -27,32 +39,327 @@ impl From<Constant> for ScalarValue {
         match value {
             Constant::Number(n) => ScalarValue::Number(n),
             Constant::String(s) => ScalarValue::String(s),
-            _ => todo!(),
+            Constant::Regex(_) => unreachable!("regex constants are not scalar values"),
+        }
+    }
+}
+
+// the output of `snprintf`, which is called with a buffer and its size
+// until the buffer is large enough
+fn c_format(snprintf: impl Fn(*mut libc::c_char, usize) -> libc::c_int) -> String {
+    let mut buffer = vec![0u8; 32];
+    loop {
+        let len = snprintf(buffer.as_mut_ptr() as *mut libc::c_char, buffer.len()).max(0) as usize;
+        if len < buffer.len() {
+            buffer.truncate(len);
+            return String::from_utf8_lossy(&buffer).into_owned();
+        }
+        buffer.resize(len + 1, 0);
+    }
+}
+
+// `n` formatted by the C library, according to a format with a single
+// floating point conversion
+fn format_float(format: &str, n: f64) -> String {
+    let format = CString::new(format).expect("format without nul characters");
+    c_format(|buffer, size| unsafe { libc::snprintf(buffer, size, format.as_ptr(), n) })
+}
+
+// `n` formatted by the C library, according to a format with a single
+// `long long` conversion
+fn format_integer(format: &str, n: i64) -> String {
+    let format = CString::new(format).expect("format without nul characters");
+    c_format(|buffer, size| unsafe {
+        libc::snprintf(buffer, size, format.as_ptr(), n as libc::c_longlong)
+    })
+}
+
+// `s` padded with spaces to `width` characters
+fn pad(s: &str, width: usize, left_justify: bool) -> String {
+    let padding = " ".repeat(width.saturating_sub(s.chars().count()));
+    if left_justify {
+        format!("{}{}", s, padding)
+    } else {
+        format!("{}{}", padding, s)
+    }
+}
+
+// `replacement` with `&` replaced by `matched`. A backslash escapes `&`
+// and itself, and is kept before other characters
+fn expand_replacement(replacement: &str, matched: &str) -> String {
+    let mut result = String::new();
+    let mut chars = replacement.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '&' => result.push_str(matched),
+            '\\' => match chars.peek() {
+                Some(&escaped @ ('&' | '\\')) => {
+                    result.push(escaped);
+                    chars.next();
+                }
+                _ => result.push('\\'),
+            },
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+// `s` with the first match of `regex`, or all of them if `global` is set,
+// replaced by `replacement`, and the number of replacements
+fn substitute(regex: &Regex, replacement: &str, s: &str, global: bool) -> (String, usize) {
+    let mut result = String::new();
+    let mut count = 0;
+    let mut position = 0;
+    // an empty match right after a match is not replaced
+    let mut last_match_end = None;
+    while let Some((start, end)) = regex.find_at(s, position) {
+        result.push_str(&s[position..start]);
+        if start < end || last_match_end != Some(start) {
+            result.push_str(&expand_replacement(replacement, &s[start..end]));
+            count += 1;
+        }
+        position = end;
+        last_match_end = Some(end);
+        if start == end {
+            match s[end..].chars().next() {
+                Some(c) => {
+                    result.push(c);
+                    position += c.len_utf8();
+                }
+                None => break,
+            }
+        }
+        if !global {
+            break;
         }
     }
+    result.push_str(&s[position..]);
+    (result, count)
+}
+
+/// Format `args` according to the printf-style `format`. It is an error
+/// for the format to have more conversions than there are arguments.
+fn sprintf(format: &str, args: &[ScalarValue]) -> Result<String, String> {
+    let mut args = args.iter();
+    let mut next_arg = || {
+        args.next()
+            .cloned()
+            .ok_or_else(|| format!("not enough arguments for format '{}'", format))
+    };
+    let bytes = format.as_bytes();
+    let mut result = String::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let Some(offset) = format[i..].find('%') else {
+            result.push_str(&format[i..]);
+            break;
+        };
+        result.push_str(&format[i..i + offset]);
+        let start = i + offset;
+        i = start + 1;
+        if bytes.get(i) == Some(&b'%') {
+            result.push('%');
+            i += 1;
+            continue;
+        }
+
+        let mut flags = String::new();
+        while let Some(&c) = bytes.get(i).filter(|c| b"-+ #0".contains(c)) {
+            flags.push(c as char);
+            i += 1;
+        }
+        let mut width = 0;
+        if bytes.get(i) == Some(&b'*') {
+            let value = next_arg()?.as_f64_or_err()? as i64;
+            if value < 0 {
+                flags.push('-');
+            }
+            width = value.unsigned_abs() as usize;
+            i += 1;
+        } else {
+            while let Some(digit) = bytes.get(i).filter(|c| c.is_ascii_digit()) {
+                width = width * 10 + (digit - b'0') as usize;
+                i += 1;
+            }
+        }
+        let mut precision = None;
+        if bytes.get(i) == Some(&b'.') {
+            i += 1;
+            if bytes.get(i) == Some(&b'*') {
+                // a negative precision is taken as if it were omitted
+                let value = next_arg()?.as_f64_or_err()? as i64;
+                precision = usize::try_from(value).ok();
+                i += 1;
+            } else {
+                let mut value = 0;
+                while let Some(digit) = bytes.get(i).filter(|c| c.is_ascii_digit()) {
+                    value = value * 10 + (digit - b'0') as usize;
+                    i += 1;
+                }
+                precision = Some(value);
+            }
+        }
+
+        let Some(conversion) = format[i..].chars().next() else {
+            return Err(format!(
+                "unterminated format specification '{}'",
+                &format[start..]
+            ));
+        };
+        i += conversion.len_utf8();
+        let left_justify = flags.contains('-');
+        let mut spec = format!("%{}", flags);
+        if width > 0 {
+            spec.push_str(&width.to_string());
+        }
+        if let Some(precision) = precision {
+            spec.push_str(&format!(".{}", precision));
+        }
+        match conversion {
+            'd' | 'i' => {
+                let n = next_arg()?.as_f64_or_err()?;
+                result.push_str(&format_integer(&format!("{}lld", spec), n as i64));
+            }
+            'o' | 'u' | 'x' | 'X' => {
+                let n = next_arg()?.as_f64_or_err()?;
+                result.push_str(&format_integer(
+                    &format!("{}ll{}", spec, conversion),
+                    n as i64,
+                ));
+            }
+            'e' | 'E' | 'f' | 'F' | 'g' | 'G' => {
+                let n = next_arg()?.as_f64_or_err()?;
+                result.push_str(&format_float(&format!("{}{}", spec, conversion), n));
+            }
+            'c' => {
+                // the character with the code of a number, or the first
+                // character of a string
+                let c = match next_arg()? {
+                    ScalarValue::Number(n) => char::from_u32(n as u32),
+                    value => value.to_string().chars().next(),
+                };
+                let s = c.map(String::from).unwrap_or_default();
+                result.push_str(&pad(&s, width, left_justify));
+            }
+            's' => {
+                let mut s = next_arg()?.to_string();
+                if let Some(precision) = precision {
+                    s = s.chars().take(precision).collect();
+                }
+                result.push_str(&pad(&s, width, left_justify));
+            }
+            _ => {
+                return Err(format!(
+                    "invalid format specification '{}'",
+                    &format[start..i]
+                ))
+            }
+        }
+    }
+    Ok(result)
+}
+
+// the default value of CONVFMT and OFMT
+const DEFAULT_NUMBER_FORMAT: &str = "%.6g";
+
+// the string value of a number: integers are written as such, other
+// numbers with `format`, the value of CONVFMT or OFMT
+fn number_to_string(n: f64, format: &str) -> String {
+    if n.is_nan() {
+        String::from(if n.is_sign_negative() { "-nan" } else { "nan" })
+    } else if n.is_infinite() {
+        String::from(if n < 0.0 { "-inf" } else { "inf" })
+    } else if n == n.trunc() && n.abs() < 1e16 {
+        (n as i64).to_string()
+    } else {
+        sprintf(format, &[ScalarValue::Number(n)])
+            .unwrap_or_else(|_| format_float(DEFAULT_NUMBER_FORMAT, n))
+    }
+}
+
+// the numeric value of the longest prefix of `s`, after leading blanks,
+// that is a decimal number, 0 if there is none. Unlike strtod, hexadecimal
+// numbers, infinity and NaN are not recognized
+fn string_to_number(s: &str) -> f64 {
+    let s = s.trim_start_matches([' ', '\t', '\n']);
+    let bytes = s.as_bytes();
+    let skip_digits = |mut i: usize| {
+        while bytes.get(i).is_some_and(u8::is_ascii_digit) {
+            i += 1;
+        }
+        i
+    };
+
+    let sign_end = usize::from(matches!(bytes.first(), Some(b'+' | b'-')));
+    let int_end = skip_digits(sign_end);
+    let mut end = int_end;
+    if bytes.get(int_end) == Some(&b'.') {
+        end = skip_digits(int_end + 1);
+    }
+    // there must be a digit before or after the decimal point
+    if int_end == sign_end && end <= int_end + 1 {
+        return 0.0;
+    }
+    if matches!(bytes.get(end), Some(b'e' | b'E')) {
+        let mut exponent_start = end + 1;
+        if matches!(bytes.get(exponent_start), Some(b'+' | b'-')) {
+            exponent_start += 1;
+        }
+        let exponent_end = skip_digits(exponent_start);
+        if exponent_end > exponent_start {
+            end = exponent_end;
+        }
+    }
+    s[..end].parse().unwrap_or(0.0)
+}
+
+// the value of `s` if it looks like a number, with blanks around it
+fn numeric_string_value(s: &str) -> Option<f64> {
+    let s = s.trim_matches([' ', '\t', '\n']);
+    // leaves out the "inf" and "nan" that parse() accepts
+    if !s.starts_with(|c: char| c.is_ascii_digit() || "+-.".contains(c))
+        || !s.chars().all(|c| c.is_ascii_digit() || "+-.eE".contains(c))
+    {
+        return None;
+    }
+    s.parse().ok()
+}
+
+// the number of characters of `s` before the byte offset `offset`
+fn char_count(s: &str, offset: usize) -> usize {
+    s[..offset].chars().count()
 }
 
 impl ScalarValue {
     fn as_f64_or_err(&self) -> Result<f64, String> {
         match self {
             ScalarValue::Number(n) => Ok(*n),
-            ScalarValue::String(s) => s.parse().map_err(|e| todo!()),
+            ScalarValue::String(s) | ScalarValue::StrNum(s) => Ok(string_to_number(s)),
             ScalarValue::Uninitialized => Ok(0.0),
         }
     }
 
-    fn as_f64_or_none(&self) -> Option<f64> {
+    /// The number the value is compared as, `None` if it is compared as a
+    /// string.
+    fn numeric_value(&self) -> Option<f64> {
         match self {
             ScalarValue::Number(n) => Some(*n),
-            ScalarValue::String(s) => s.parse().ok(),
+            ScalarValue::String(_) => None,
+            ScalarValue::StrNum(s) => numeric_string_value(s),
             ScalarValue::Uninitialized => Some(0.0),
         }
     }
 
     fn to_string(&self) -> String {
+        self.to_string_with_format(DEFAULT_NUMBER_FORMAT)
+    }
+
+    // the string value, with non integer numbers written with `format`
+    fn to_string_with_format(&self, format: &str) -> String {
         match self {
-            ScalarValue::Number(n) => n.to_string(),
-            ScalarValue::String(s) => s.clone(),
+            ScalarValue::Number(n) => number_to_string(*n, format),
+            ScalarValue::String(s) | ScalarValue::StrNum(s) => s.clone(),
             ScalarValue::Uninitialized => String::new(),
         }
     }
@@ -61,6 +368,10 @@ impl ScalarValue {
         match self {
             ScalarValue::Number(n) => *n != 0.0,
             ScalarValue::String(s) => !s.is_empty(),
+            ScalarValue::StrNum(s) => match numeric_string_value(s) {
+                Some(n) => n != 0.0,
+                None => !s.is_empty(),
+            },
             ScalarValue::Uninitialized => false,
         }
     }
@@ -94,6 +405,11 @@ enum StackValue {
     Scalar(ScalarValue),
     Reference(Reference),
     Uninitialized,
+    // an ERE token, which is the regex itself where one is expected, and
+    // otherwise whether it matches the current record
+    Regex(Rc<Regex>),
+    // the keys of an array left to go through in a for-in loop
+    ArrayIterator { array: Reference, keys: Vec<String> },
 }
 
 impl From<Constant> for StackValue {
@@ -116,9 +432,106 @@ impl From<Reference> for StackValue {
 
 struct CallFrame<'i> {
     ip: usize,
+    // the base pointer of the caller
     bp: usize,
     last_temp_array: usize,
     instructions: &'i [OpCode],
+    // the function of the caller, if it is not the main code
+    function: Option<usize>,
+}
+
+/// The instruction being run, in the main code or in a function.
+#[derive(Debug, Default, Clone, Copy)]
+struct ExecutionPosition {
+    function: Option<usize>,
+    ip: usize,
+}
+
+/// An error raised by an instruction of the program.
+#[derive(Debug)]
+struct RuntimeError {
+    message: String,
+    position: ExecutionPosition,
+}
+
+enum ExecutionResult {
+    Completed,
+    Next,
+    Exit,
+}
+
+enum CurrentInput {
+    Stdin,
+    File(RecordReader),
+}
+
+/// Where the main loop, and plain getline, are in the operands of ARGV.
+struct MainInput {
+    next_operand: usize,
+    // whether a file operand was found, otherwise the standard input is read
+    read_file: bool,
+    current: Option<CurrentInput>,
+}
+
+impl Default for MainInput {
+    fn default() -> Self {
+        MainInput {
+            // ARGV[0] is the name of the program
+            next_operand: 1,
+            read_file: false,
+            current: None,
+        }
+    }
+}
+
+/// The pseudo-random number generator of rand(), a SplitMix64.
+struct RandomGenerator {
+    state: u64,
+}
+
+impl RandomGenerator {
+    /// A generator seeded with the integer part of `seed`.
+    fn new(seed: f64) -> Self {
+        RandomGenerator {
+            state: seed as i64 as u64,
+        }
+    }
+
+    /// The next number, between 0 included and 1 excluded.
+    fn next(&mut self) -> f64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^= z >> 31;
+        // the 53 high bits, as many as a f64 has
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// the name and value of a `var=value` operand, if `operand` is one
+fn command_line_assignment(operand: &str) -> Option<(&str, &str)> {
+    let (name, value) = operand.split_once('=')?;
+    let mut chars = name.chars();
+    let first = chars.next()?;
+    if (first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        Some((name, value))
+    } else {
+        None
+    }
+}
+
+/// What has to be done for $0 and the fields to agree, after one of them
+/// was modified in place.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RecordState {
+    UpToDate,
+    /// A field was assigned, so $0 has to be rebuilt from the fields.
+    FieldsChanged,
+    /// $0 was assigned, so it has to be split into fields again.
+    RecordChanged,
 }
 
 struct Interpreter {
@@ -126,8 +539,25 @@ struct Interpreter {
     constants: Vec<Constant>,
     stack: Vec<StackValue>,
     fields: Vec<ScalarValue>,
-    temp_arrays: Vec<HashMap<String, ScalarValue>>,
+    record_state: RecordState,
+    // the arrays local to functions, and the uninitialized local variables
+    // passed to functions, which the callee may turn into arrays
+    temp_arrays: Vec<GlobalValue>,
     bp: usize,
+    streams: Streams,
+    main_input: MainInput,
+    // the ids of the global variables of the program, by name
+    global_names: HashMap<String, u32>,
+    // the files the program was read from, with the line each starts at
+    source_files: Vec<(String, u32)>,
+    // whether to warn about the use of uninitialized variables
+    lint: bool,
+    random: RandomGenerator,
+    // the last seed given to srand()
+    random_seed: f64,
+    // the status of the last exit statement with an expression
+    exit_status: i32,
+    regex_cache: RegexCache,
 }
 
 macro_rules! numeric_op {
@@ -138,27 +568,28 @@ macro_rules! numeric_op {
     };
 }
 
+macro_rules! division_op {
+    ($s:ident, $op:tt) => {
+        let rhs = $s.pop_scalar()?.as_f64_or_err()?;
+        let lhs = $s.pop_scalar()?.as_f64_or_err()?;
+        if rhs == 0.0 {
+            return Err("division by zero".to_string());
+        }
+        $s.push(ScalarValue::Number(lhs $op rhs));
+    };
+}
+
+// values are compared as numbers when both are numbers, uninitialized or
+// numeric strings, and as strings otherwise
 macro_rules! compare_op {
     ($s:ident, $op:tt) => {
         let rhs = $s.pop_scalar()?;
         let lhs = $s.pop_scalar()?;
-        match (lhs, rhs) {
-            (ScalarValue::Number(lhs), ScalarValue::Number(rhs)) => {
-                $s.push(ScalarValue::Number((lhs $op rhs) as i32 as f64));
-            }
-            (ScalarValue::String(lhs), ScalarValue::String(rhs)) => {
-                $s.push(ScalarValue::Number((lhs $op rhs) as i32 as f64));
-            }
-            (lhs, rhs) => {
-                let lhs_num = lhs.as_f64_or_none();
-                let rhs_num = rhs.as_f64_or_none();
-                if let (Some(lhs), Some(rhs)) = (lhs_num, rhs_num) {
-                    $s.push(ScalarValue::Number((lhs $op rhs) as i32 as f64));
-                } else {
-                    $s.push(ScalarValue::Number((lhs.to_string() $op rhs.to_string()) as i32 as f64));
-                }
-            }
-        }
+        let result = match (lhs.numeric_value(), rhs.numeric_value()) {
+            (Some(lhs), Some(rhs)) => lhs $op rhs,
+            _ => $s.convert_to_string(&lhs) $op $s.convert_to_string(&rhs),
+        };
+        $s.push(ScalarValue::Number(result as i32 as f64));
     };
 }
 
@@ -180,7 +611,7 @@ impl Interpreter {
     }
 
     fn get_array_element(&mut self, global_index: usize) -> Result<ScalarValue, String> {
-        let key = self.pop_scalar()?.to_string();
+        let key = self.pop_string()?;
         match &mut self.globals[global_index] {
             GlobalValue::Array(map) => Ok(get_or_insert(map, key).clone()),
             global @ GlobalValue::Uninitialized => {
@@ -193,7 +624,7 @@ impl Interpreter {
     }
 
     fn get_array_element_mut(&mut self, global_index: usize) -> Result<&mut ScalarValue, String> {
-        let key = self.pop_scalar()?.to_string();
+        let key = self.pop_string()?;
         match &mut self.globals[global_index] {
             GlobalValue::Array(map) => Ok(get_or_insert(map, key)),
             global @ GlobalValue::Uninitialized => {
@@ -207,11 +638,117 @@ impl Interpreter {
         }
     }
 
+    // the array `reference` refers to, which is created if it is
+    // uninitialized
+    fn array_mut(
+        &mut self,
+        reference: Reference,
+    ) -> Result<&mut HashMap<String, ScalarValue>, String> {
+        let value = match reference {
+            Reference::GlobalArrayRef(index) => &mut self.globals[index],
+            Reference::TempArray(index) => &mut self.temp_arrays[index],
+            Reference::LocalArrayRef(index) => match self.get_from_stack(index).clone() {
+                StackValue::Reference(
+                    reference @ (Reference::GlobalArrayRef(_) | Reference::TempArray(_)),
+                ) => return self.array_mut(reference),
+                StackValue::Uninitialized => {
+                    let temp_index = self.new_temp_array();
+                    *self.get_from_stack_mut(index) = Reference::TempArray(temp_index).into();
+                    &mut self.temp_arrays[temp_index]
+                }
+                _ => return Err("scalar used in array context".to_string()),
+            },
+            _ => return Err("scalar used in array context".to_string()),
+        };
+        match value {
+            GlobalValue::Array(map) => Ok(map),
+            value @ GlobalValue::Uninitialized => {
+                *value = GlobalValue::Array(HashMap::new());
+                match value {
+                    GlobalValue::Array(map) => Ok(map),
+                    _ => unreachable!(),
+                }
+            }
+            _ => Err("scalar used in array context".to_string()),
+        }
+    }
+
+    fn new_temp_array(&mut self) -> usize {
+        self.temp_arrays.push(GlobalValue::Uninitialized);
+        self.temp_arrays.len() - 1
+    }
+
+    // the local scalar variable at `index`. A local that refers to an array
+    // which is still uninitialized becomes a scalar local to the function
+    fn local_scalar_mut(&mut self, index: usize) -> Result<&mut ScalarValue, String> {
+        let untyped = match self.get_from_stack(index) {
+            StackValue::Uninitialized => true,
+            StackValue::Reference(Reference::GlobalArrayRef(global_index)) => {
+                matches!(self.globals[*global_index], GlobalValue::Uninitialized)
+            }
+            StackValue::Reference(Reference::TempArray(temp_index)) => {
+                matches!(self.temp_arrays[*temp_index], GlobalValue::Uninitialized)
+            }
+            _ => false,
+        };
+        if untyped {
+            *self.get_from_stack_mut(index) = ScalarValue::Uninitialized.into();
+        }
+        match self.get_from_stack_mut(index) {
+            StackValue::Scalar(scalar) => Ok(scalar),
+            _ => Err("array used in scalar context".to_string()),
+        }
+    }
+
+    // the value passed to a function for the argument on top of the stack.
+    // Scalars are passed by value, while arrays and uninitialized variables,
+    // which could be used as arrays by the function, are passed by reference
+    fn pop_argument(&mut self) -> Result<StackValue, String> {
+        match self.pop() {
+            StackValue::Reference(Reference::GlobalVarRef(index))
+                if match self.globals[index] {
+                    GlobalValue::Array(_) => true,
+                    GlobalValue::Uninitialized => index >= SpecialVar::Count as usize,
+                    GlobalValue::Scalar(_) => false,
+                } =>
+            {
+                Ok(Reference::GlobalArrayRef(index).into())
+            }
+            StackValue::Reference(Reference::LocalVarRef(index)) => {
+                match self.get_from_stack(index).clone() {
+                    StackValue::Uninitialized => {
+                        let temp_index = self.new_temp_array();
+                        let reference = Reference::TempArray(temp_index);
+                        *self.get_from_stack_mut(index) = reference.clone().into();
+                        Ok(reference.into())
+                    }
+                    StackValue::Reference(reference) => Ok(reference.into()),
+                    value => Ok(value),
+                }
+            }
+            StackValue::Uninitialized => Ok(StackValue::Uninitialized),
+            value => Ok(self.stack_value_to_scalar(value)?.into()),
+        }
+    }
+
     fn deref(&mut self, reference: Reference) -> Result<ScalarValue, String> {
         match reference {
+            Reference::GlobalVarRef(idx) if idx == SpecialVar::Nf as usize => {
+                self.update_record()?;
+                Ok(self.special_value(SpecialVar::Nf))
+            }
             Reference::GlobalVarRef(idx) => match &self.globals[idx] {
                 GlobalValue::Scalar(scalar) => Ok(scalar.clone()),
                 GlobalValue::Uninitialized => {
+                    if self.lint {
+                        let name = self
+                            .global_names
+                            .iter()
+                            .find(|(_, id)| **id as usize == idx);
+                        if let Some((name, _)) = name {
+                            eprintln!("awk: warning: variable '{}' used before it is set", name);
+                        }
+                    }
                     self.globals[idx] = ScalarValue::Uninitialized.into();
                     Ok(ScalarValue::Uninitialized)
                 }
@@ -219,46 +756,17 @@ impl Interpreter {
             },
             Reference::GlobalArrayRef(idx) => self.get_array_element(idx),
             Reference::FieldRef(index) => {
-                if let Some(value) = self.fields.get(index) {
-                    return Ok(value.clone());
-                } else {
-                    self.fields.resize(index + 1, ScalarValue::Uninitialized);
-                    self.globals[SpecialVar::Nf as usize] =
-                        ScalarValue::Number(index as f64 + 1.0).into();
-                    Ok(ScalarValue::Uninitialized)
-                }
+                self.update_record()?;
+                Ok(self
+                    .fields
+                    .get(index)
+                    .cloned()
+                    .unwrap_or(ScalarValue::Uninitialized))
             }
-            Reference::LocalVarRef(idx) => match self.get_from_stack_mut(idx) {
-                StackValue::Scalar(scalar) => Ok(scalar.clone()),
-                value @ StackValue::Uninitialized => {
-                    *value = ScalarValue::Uninitialized.into();
-                    Ok(ScalarValue::Uninitialized)
-                }
-                _ => Err("array used in scalar context".to_string()),
-            },
-            Reference::LocalArrayRef(idx) => match &mut self.stack[idx + self.bp] {
-                StackValue::Reference(Reference::GlobalArrayRef(global_index)) => {
-                    let global_index = *global_index;
-                    self.get_array_element(global_index)
-                }
-                StackValue::Reference(Reference::TempArray(temp_idx)) => {
-                    let temp_idx = *temp_idx;
-                    let key = self.pop_scalar()?.to_string();
-                    Ok(get_or_insert(&mut self.temp_arrays[temp_idx], key).clone())
-                }
-                value @ StackValue::Uninitialized => {
-                    let index = self.temp_arrays.len();
-                    *value = Reference::TempArray(index).into();
-                    let key = self.pop_scalar()?.to_string();
-                    self.temp_arrays
-                        .push(HashMap::from([(key, ScalarValue::Uninitialized)]));
-                    Ok(ScalarValue::Uninitialized)
-                }
-                _ => Err("scalar used in array context".to_string()),
-            },
-            Reference::TempArray(idx) => {
-                let key = self.pop_scalar()?.to_string();
-                Ok(get_or_insert(&mut self.temp_arrays[idx], key).clone())
+            Reference::LocalVarRef(idx) => self.local_scalar_mut(idx).cloned(),
+            Reference::LocalArrayRef(_) | Reference::TempArray(_) => {
+                let key = self.pop_string()?;
+                Ok(get_or_insert(self.array_mut(reference)?, key).clone())
             }
         }
     }
@@ -268,9 +776,503 @@ impl Interpreter {
             StackValue::Scalar(val) => Ok(val),
             StackValue::Reference(reference) => self.deref(reference),
             StackValue::Uninitialized => Ok(ScalarValue::Uninitialized),
+            StackValue::Regex(regex) => {
+                let record = self.deref(Reference::FieldRef(0))?.to_string();
+                let matches = regex.matches(&record);
+                Ok(ScalarValue::Number(matches as i32 as f64))
+            }
+            StackValue::ArrayIterator { .. } => unreachable!("array iterator used as a value"),
+        }
+    }
+
+    fn pop_array_ref(&mut self) -> Reference {
+        match self.pop() {
+            StackValue::Reference(reference) => reference,
+            _ => panic!("array reference expected"),
+        }
+    }
+
+    // the next key of the iterator on top of the stack that is still in
+    // the array
+    fn next_key(&mut self) -> Result<Option<String>, String> {
+        loop {
+            let Some(StackValue::ArrayIterator { array, keys }) = self.stack.last_mut() else {
+                panic!("array iterator expected");
+            };
+            let Some(key) = keys.pop() else {
+                return Ok(None);
+            };
+            let array = array.clone();
+            if self.array_mut(array)?.contains_key(&key) {
+                return Ok(Some(key));
+            }
+        }
+    }
+
+    fn pop_regex(&mut self) -> Result<Rc<Regex>, String> {
+        match self.pop() {
+            StackValue::Regex(regex) => Ok(regex),
+            value => {
+                let pattern = self.stack_value_to_scalar(value)?.to_string();
+                self.regex_cache.get(&pattern)
+            }
         }
     }
 
+    // assign `value` to the reference on top of the stack
+    fn assign(&mut self, value: ScalarValue) -> Result<(), String> {
+        if let Some(StackValue::Reference(Reference::FieldRef(0))) = self.stack.last() {
+            self.pop();
+            return self.set_record(value.to_string());
+        }
+        *self.pop_ref()? = value;
+        Ok(())
+    }
+
+    fn special_value(&self, var: SpecialVar) -> ScalarValue {
+        match &self.globals[var as usize] {
+            GlobalValue::Scalar(value) => value.clone(),
+            _ => ScalarValue::Uninitialized,
+        }
+    }
+
+    // the string value of `value`, with numbers converted using CONVFMT
+    fn convert_to_string(&self, value: &ScalarValue) -> String {
+        match value {
+            ScalarValue::Number(_) => {
+                value.to_string_with_format(&self.special_string(SpecialVar::Convfmt))
+            }
+            _ => value.to_string(),
+        }
+    }
+
+    fn pop_string(&mut self) -> Result<String, String> {
+        let value = self.pop_scalar()?;
+        Ok(self.convert_to_string(&value))
+    }
+
+    fn special_string(&self, var: SpecialVar) -> String {
+        match &self.globals[var as usize] {
+            GlobalValue::Scalar(value) => value.to_string(),
+            _ => String::new(),
+        }
+    }
+
+    fn set_special_number(&mut self, var: SpecialVar, value: f64) {
+        self.globals[var as usize] = ScalarValue::Number(value).into();
+    }
+
+    fn increment_special(&mut self, var: SpecialVar) {
+        let value = match &self.globals[var as usize] {
+            GlobalValue::Scalar(value) => value.as_f64_or_err().unwrap_or(0.0),
+            _ => 0.0,
+        };
+        self.set_special_number(var, value + 1.0);
+    }
+
+    fn record_separator(&mut self) -> Result<RecordSeparator, String> {
+        let rs = self.special_string(SpecialVar::Rs);
+        let separator = match rs.as_bytes() {
+            [] => RecordSeparator::Paragraph,
+            [c] => RecordSeparator::Char(*c),
+            _ => RecordSeparator::Regex(self.regex_cache.get(&rs)?),
+        };
+        Ok(separator)
+    }
+
+    // the fields of `text`, as separated by `fs`
+    fn split_fields(&mut self, text: &str, fs: &str) -> Result<Vec<String>, String> {
+        let fields = if text.is_empty() {
+            Vec::new()
+        } else if fs == " " {
+            text.split([' ', '\t', '\n'])
+                .filter(|field| !field.is_empty())
+                .map(String::from)
+                .collect()
+        } else if fs.chars().count() == 1 && fs != "\\" {
+            text.split(fs).map(String::from).collect()
+        } else {
+            self.regex_cache
+                .get(fs)?
+                .split(text)
+                .into_iter()
+                .map(String::from)
+                .collect()
+        };
+        Ok(fields)
+    }
+
+    /// Make `record` the current record, splitting it into fields with FS.
+    fn set_record(&mut self, record: String) -> Result<(), String> {
+        let fs = self.special_string(SpecialVar::Fs);
+        let fields = if self.special_string(SpecialVar::Rs).is_empty() {
+            // newlines separate fields in paragraph mode, whatever FS is
+            let mut fields = Vec::new();
+            for line in record.split('\n') {
+                fields.extend(self.split_fields(line, &fs)?);
+            }
+            fields
+        } else {
+            self.split_fields(&record, &fs)?
+        };
+        self.set_special_number(SpecialVar::Nf, fields.len() as f64);
+        self.fields.clear();
+        self.fields.push(ScalarValue::StrNum(record));
+        self.fields
+            .extend(fields.into_iter().map(ScalarValue::StrNum));
+        self.record_state = RecordState::UpToDate;
+        Ok(())
+    }
+
+    /// Make $0, the fields and NF agree after some of them were assigned:
+    /// the fields are added or removed to match NF, and $0 is rebuilt by
+    /// joining them with OFS.
+    fn update_record(&mut self) -> Result<(), String> {
+        if self.record_state == RecordState::RecordChanged {
+            let record = self.fields[0].to_string();
+            return self.set_record(record);
+        }
+        let nf = match &self.globals[SpecialVar::Nf as usize] {
+            GlobalValue::Scalar(value) => value.as_f64_or_err()?,
+            _ => 0.0,
+        };
+        if nf < 0.0 {
+            return Err("NF set to a negative value".to_string());
+        }
+        let nf = nf as usize;
+        if self.fields.is_empty() {
+            self.fields.push(ScalarValue::Uninitialized);
+        }
+        if nf != self.fields.len() - 1 {
+            self.fields.resize(nf + 1, ScalarValue::Uninitialized);
+            self.record_state = RecordState::FieldsChanged;
+        }
+        if self.record_state == RecordState::FieldsChanged {
+            let ofs = self.special_string(SpecialVar::Ofs);
+            let fields: Vec<String> = self.fields[1..]
+                .iter()
+                .map(|field| self.convert_to_string(field))
+                .collect();
+            self.fields[0] = ScalarValue::StrNum(fields.join(&ofs));
+            self.record_state = RecordState::UpToDate;
+        }
+        Ok(())
+    }
+
+    // the next non-empty element of ARGV below ARGC, as they are when the
+    // operand is reached
+    fn next_operand(&mut self) -> Result<Option<String>, String> {
+        loop {
+            let argc = match &self.globals[SpecialVar::Argc as usize] {
+                GlobalValue::Scalar(value) => value.as_f64_or_err()?,
+                _ => 0.0,
+            };
+            let index = self.main_input.next_operand;
+            if index as f64 >= argc {
+                return Ok(None);
+            }
+            self.main_input.next_operand += 1;
+            let operand = match &self.globals[SpecialVar::Argv as usize] {
+                GlobalValue::Array(argv) => argv.get(&index.to_string()).map(|v| v.to_string()),
+                _ => None,
+            };
+            match operand {
+                Some(operand) if !operand.is_empty() => return Ok(Some(operand)),
+                _ => {}
+            }
+        }
+    }
+
+    fn assign_command_line_var(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let Some(&id) = self.global_names.get(name) else {
+            // the program does not use the variable
+            return Ok(());
+        };
+        if let GlobalValue::Array(_) = self.globals[id as usize] {
+            return Err(format!("can't assign to '{}', it is an array", name));
+        }
+        self.globals[id as usize] = ScalarValue::StrNum(escape_string(value)?).into();
+        Ok(())
+    }
+
+    // open the next input file of the operands, doing the assignments that
+    // come before it. Returns `false` once there are no files left
+    fn open_next_main_input(&mut self) -> Result<bool, String> {
+        let name = loop {
+            match self.next_operand()? {
+                Some(operand) => {
+                    if let Some((name, value)) = command_line_assignment(&operand) {
+                        self.assign_command_line_var(name, value)?;
+                    } else {
+                        break operand;
+                    }
+                }
+                None if self.main_input.read_file => return Ok(false),
+                None => break "-".to_string(),
+            }
+        };
+        self.main_input.read_file = true;
+        let input = if name == "-" {
+            CurrentInput::Stdin
+        } else {
+            let reader = RecordReader::open(&name)
+                .map_err(|e| format!("can't open file {}: {}", name, e))?;
+            CurrentInput::File(reader)
+        };
+        self.main_input.current = Some(input);
+        self.globals[SpecialVar::Filename as usize] = ScalarValue::String(name).into();
+        self.set_special_number(SpecialVar::Fnr, 0.0);
+        Ok(true)
+    }
+
+    /// Read the next record of the main input, going through the files of
+    /// ARGV in turn. Returns `None` once all of them are read.
+    fn read_main_record(&mut self) -> Result<Option<String>, String> {
+        loop {
+            if self.main_input.current.is_none() && !self.open_next_main_input()? {
+                return Ok(None);
+            }
+
+            let separator = self.record_separator()?;
+            let record = match self.main_input.current.as_mut().unwrap() {
+                CurrentInput::Stdin => self.streams.stdin().read_record(&separator),
+                CurrentInput::File(reader) => reader.read_record(&separator),
+            }
+            .map_err(|e| format!("error reading input: {}", e))?;
+            match record {
+                Some(record) => {
+                    self.increment_special(SpecialVar::Nr);
+                    self.increment_special(SpecialVar::Fnr);
+                    return Ok(Some(record));
+                }
+                None => self.main_input.current = None,
+            }
+        }
+    }
+
+    fn getline(&mut self, source: GetlineSource, assign: bool) -> Result<(), String> {
+        let record = match source {
+            GetlineSource::Main => Ok(self.read_main_record()?),
+            GetlineSource::File | GetlineSource::Command => {
+                let name = self.pop_string()?;
+                let separator = self.record_separator()?;
+                let record = self.streams.read_record(source, &name, &separator);
+                if source == GetlineSource::Command && matches!(record, Ok(Some(_))) {
+                    self.increment_special(SpecialVar::Nr);
+                }
+                record
+            }
+        };
+        let result = match record {
+            Ok(Some(record)) => {
+                if assign {
+                    self.assign(ScalarValue::StrNum(record))?;
+                } else {
+                    self.set_record(record)?;
+                }
+                1.0
+            }
+            Ok(None) => 0.0,
+            Err(_) => -1.0,
+        };
+        if assign && result != 1.0 {
+            // the reference to the variable that was not assigned
+            self.pop();
+        }
+        self.push(ScalarValue::Number(result));
+        Ok(())
+    }
+
+    fn pop_args(&mut self, argc: u16) -> Result<Vec<ScalarValue>, String> {
+        let mut args = Vec::with_capacity(argc as usize);
+        for _ in 0..argc {
+            args.push(self.pop_scalar()?);
+        }
+        args.reverse();
+        Ok(args)
+    }
+
+    // the name of the file or command to write to, if any, and the values
+    // to write
+    fn pop_output_args(
+        &mut self,
+        argc: u16,
+        output: Output,
+    ) -> Result<(String, Vec<ScalarValue>), String> {
+        let name = if output == Output::Stdout {
+            String::new()
+        } else {
+            self.pop_string()?
+        };
+        Ok((name, self.pop_args(argc)?))
+    }
+
+    fn print(&mut self, argc: u16, output: Output) -> Result<(), String> {
+        let (name, values) = self.pop_output_args(argc, output)?;
+        let ofmt = self.special_string(SpecialVar::Ofmt);
+        let values: Vec<String> = values
+            .iter()
+            .map(|value| value.to_string_with_format(&ofmt))
+            .collect();
+        let mut line = values.join(&self.special_string(SpecialVar::Ofs));
+        line.push_str(&self.special_string(SpecialVar::Ors));
+        self.streams.write(output, &name, &line)
+    }
+
+    fn printf(&mut self, argc: u16, output: Output) -> Result<(), String> {
+        let (name, values) = self.pop_output_args(argc, output)?;
+        let text = sprintf(&values[0].to_string(), &values[1..])?;
+        self.streams.write(output, &name, &text)
+    }
+
+    fn call_builtin(&mut self, function: BuiltinFunction, argc: u16) -> Result<(), String> {
+        if function == BuiltinFunction::Match {
+            let regex = self.pop_regex()?;
+            let s = self.pop_string()?;
+            let (start, length) = match regex.find(&s) {
+                Some((start, end)) => (
+                    char_count(&s, start) as f64 + 1.0,
+                    s[start..end].chars().count() as f64,
+                ),
+                None => (0.0, -1.0),
+            };
+            self.set_special_number(SpecialVar::Rstart, start);
+            self.set_special_number(SpecialVar::Rlength, length);
+            self.push(ScalarValue::Number(start));
+            return Ok(());
+        }
+        if function == BuiltinFunction::Sub || function == BuiltinFunction::Gsub {
+            // the target is a reference, preceded by the index of an array
+            // element
+            let target = self.pop();
+            let index = match target {
+                StackValue::Reference(
+                    Reference::GlobalArrayRef(_) | Reference::LocalArrayRef(_),
+                ) => Some(self.pop_scalar()?),
+                _ => None,
+            };
+            let replacement = self.pop_string()?;
+            let regex = self.pop_regex()?;
+
+            self.stack.extend(index.clone().map(StackValue::from));
+            self.push(target.clone());
+            let value = self.pop_string()?;
+            let global = function == BuiltinFunction::Gsub;
+            let (result, count) = substitute(&regex, &replacement, &value, global);
+            if count > 0 {
+                self.stack.extend(index.map(StackValue::from));
+                self.push(target);
+                self.assign(ScalarValue::String(result))?;
+            }
+            self.push(ScalarValue::Number(count as f64));
+            return Ok(());
+        }
+        if function == BuiltinFunction::Split {
+            // the separator is FS if it is omitted
+            let separator = if argc == 3 {
+                self.pop()
+            } else {
+                ScalarValue::String(self.special_string(SpecialVar::Fs)).into()
+            };
+            let StackValue::Reference(array) = self.pop() else {
+                unreachable!("split called without an array")
+            };
+            let s = self.pop_string()?;
+            let fields: Vec<String> = match separator {
+                StackValue::Regex(regex) => regex.split(&s).into_iter().map(String::from).collect(),
+                value => {
+                    let fs = self.stack_value_to_scalar(value)?.to_string();
+                    self.split_fields(&s, &fs)?
+                }
+            };
+            let count = fields.len();
+            let array = self.array_mut(array)?;
+            array.clear();
+            for (i, field) in fields.into_iter().enumerate() {
+                array.insert((i + 1).to_string(), ScalarValue::StrNum(field));
+            }
+            self.push(ScalarValue::Number(count as f64));
+            return Ok(());
+        }
+
+        let args = self.pop_args(argc)?;
+        let number = |i: usize| args[i].as_f64_or_err();
+        let result = match function {
+            BuiltinFunction::Atan2 => ScalarValue::Number(number(0)?.atan2(number(1)?)),
+            BuiltinFunction::Cos => ScalarValue::Number(number(0)?.cos()),
+            BuiltinFunction::Sin => ScalarValue::Number(number(0)?.sin()),
+            BuiltinFunction::Exp => ScalarValue::Number(number(0)?.exp()),
+            BuiltinFunction::Log => ScalarValue::Number(number(0)?.ln()),
+            BuiltinFunction::Sqrt => ScalarValue::Number(number(0)?.sqrt()),
+            BuiltinFunction::Int => ScalarValue::Number(number(0)?.trunc()),
+            BuiltinFunction::Index => {
+                let s = self.convert_to_string(&args[0]);
+                let t = self.convert_to_string(&args[1]);
+                let position = s.find(&t).map_or(0, |i| char_count(&s, i) + 1);
+                ScalarValue::Number(position as f64)
+            }
+            BuiltinFunction::Length => {
+                let s = match args.first() {
+                    Some(arg) => self.convert_to_string(arg),
+                    None => self.deref(Reference::FieldRef(0))?.to_string(),
+                };
+                ScalarValue::Number(s.chars().count() as f64)
+            }
+            BuiltinFunction::Substr => {
+                let s = self.convert_to_string(&args[0]);
+                let len = s.chars().count() as f64;
+                // the characters from position m to m + n - 1, both rounded
+                let start = number(1)?.round();
+                let end = match args.get(2) {
+                    Some(n) => start + n.as_f64_or_err()?.round(),
+                    None => f64::INFINITY,
+                };
+                let start = start.max(1.0);
+                let end = end.min(len + 1.0);
+                let substring = if start < end {
+                    s.chars()
+                        .skip(start as usize - 1)
+                        .take((end - start) as usize)
+                        .collect()
+                } else {
+                    String::new()
+                };
+                ScalarValue::String(substring)
+            }
+            BuiltinFunction::ToLower => {
+                ScalarValue::String(self.convert_to_string(&args[0]).to_lowercase())
+            }
+            BuiltinFunction::ToUpper => {
+                ScalarValue::String(self.convert_to_string(&args[0]).to_uppercase())
+            }
+            BuiltinFunction::Close => {
+                let name = args[0].to_string();
+                ScalarValue::Number(self.streams.close(&name) as f64)
+            }
+            BuiltinFunction::System => {
+                let command = args[0].to_string();
+                ScalarValue::Number(self.streams.system(&command) as f64)
+            }
+            BuiltinFunction::Sprintf => {
+                ScalarValue::String(sprintf(&args[0].to_string(), &args[1..])?)
+            }
+            BuiltinFunction::Rand => ScalarValue::Number(self.random.next()),
+            BuiltinFunction::Srand => {
+                let seed = match args.first() {
+                    Some(seed) => seed.as_f64_or_err()?,
+                    None => SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |time| time.as_secs()) as f64,
+                };
+                self.random = RandomGenerator::new(seed);
+                ScalarValue::Number(std::mem::replace(&mut self.random_seed, seed))
+            }
+            other => unreachable!("{:?} is not supported", other),
+        };
+        self.push(result);
+        Ok(())
+    }
+
     fn pop_scalar(&mut self) -> Result<ScalarValue, String> {
         let value = self.pop();
         self.stack_value_to_scalar(value)
@@ -291,29 +1293,24 @@ impl Interpreter {
                     _ => Err("array used in scalar context".to_string()),
                 },
                 Reference::GlobalArrayRef(idx) => self.get_array_element_mut(idx),
-                Reference::LocalVarRef(idx) => match &mut self.stack[idx] {
-                    StackValue::Scalar(scalar) => Ok(scalar),
-                    _ => Err("array used in scalar context".to_string()),
-                },
-                Reference::LocalArrayRef(idx) => match self.stack[idx] {
-                    StackValue::Reference(Reference::GlobalArrayRef(global_index)) => {
-                        self.get_array_element_mut(global_index)
-                    }
-                    StackValue::Reference(Reference::TempArray(temp_idx)) => {
-                        let key = self.pop_scalar()?.to_string();
-                        Ok(get_or_insert(&mut self.temp_arrays[temp_idx], key))
-                    }
-                    _ => Err("scalar used in array context".to_string()),
-                },
+                Reference::LocalVarRef(idx) => self.local_scalar_mut(idx),
+                Reference::LocalArrayRef(_) => {
+                    let key = self.pop_string()?;
+                    Ok(get_or_insert(self.array_mut(reference)?, key))
+                }
                 Reference::FieldRef(idx) => {
-                    if self.fields.len() > idx {
-                        Ok(&mut self.fields[idx])
-                    } else {
+                    self.update_record()?;
+                    if idx >= self.fields.len() {
+                        // the fields in between are empty
                         self.fields.resize(idx + 1, ScalarValue::Uninitialized);
-                        self.globals[SpecialVar::Nf as usize] =
-                            ScalarValue::Number(idx as f64 + 1.0).into();
-                        Ok(&mut self.fields[idx])
+                        self.set_special_number(SpecialVar::Nf, idx as f64);
                     }
+                    self.record_state = if idx == 0 {
+                        RecordState::RecordChanged
+                    } else {
+                        RecordState::FieldsChanged
+                    };
+                    Ok(&mut self.fields[idx])
                 }
                 Reference::TempArray(_) => {
                     unreachable!("temp arrays should only be accessed through LocalArrayRef")
@@ -330,43 +1327,9 @@ impl Interpreter {
             panic!("array reference expected");
         };
 
-        let key = self.pop_scalar()?.to_string();
-        match array_ref {
-            Reference::GlobalArrayRef(id) => match &mut self.globals[id] {
-                GlobalValue::Array(map) => {
-                    let value = map.contains_key(&key) as i32 as f64;
-                    self.push(ScalarValue::Number(value))
-                }
-                global @ GlobalValue::Uninitialized => {
-                    *global = GlobalValue::Array(HashMap::new());
-                    self.push(ScalarValue::Number(0.0));
-                }
-                _ => return Err("scalar used in array context".to_string()),
-            },
-            Reference::LocalArrayRef(id) => match self.stack[self.bp + id] {
-                StackValue::Reference(Reference::GlobalArrayRef(global_index)) => {
-                    let value = match &mut self.globals[global_index] {
-                        GlobalValue::Array(map) => map.contains_key(&key),
-                        global @ GlobalValue::Uninitialized => {
-                            *global = GlobalValue::Array(HashMap::new());
-                            false
-                        }
-                        _ => return Err("scalar used in array context".to_string()),
-                    };
-                    self.push(ScalarValue::Number(value as i32 as f64));
-                }
-                StackValue::Reference(Reference::TempArray(temp_idx)) => {
-                    let value = self.temp_arrays[temp_idx].contains_key(&key);
-                    self.push(ScalarValue::Number(value as i32 as f64));
-                }
-                _ => return Err("scalar used in array context".to_string()),
-            },
-            Reference::TempArray(_) => {
-                unreachable!("temp arrays should only be accessed through LocalArrayRef")
-            }
-            _ => return Err("scalar used in array context".to_string()),
-        }
-
+        let key = self.pop_string()?;
+        let value = self.array_mut(array_ref)?.contains_key(&key);
+        self.push(ScalarValue::Number(value as i32 as f64));
         Ok(())
     }
 
@@ -374,18 +1337,28 @@ impl Interpreter {
         &mut self,
         main: &[OpCode],
         functions: &[Function],
-        record: &[String],
-    ) -> Result<(), String> {
-        self.globals[SpecialVar::Nf as usize] = ScalarValue::Number(record.len() as f64).into();
-        self.fields.resize(record.len(), ScalarValue::Uninitialized);
-        for (i, field) in record.iter().enumerate() {
-            self.fields[i] = ScalarValue::String(field.clone());
-        }
+    ) -> Result<ExecutionResult, RuntimeError> {
+        let mut position = ExecutionPosition::default();
+        self.execute(main, functions, &mut position)
+            .map_err(|message| RuntimeError { message, position })
+    }
 
+    // run `main`, keeping `position` at the instruction being run
+    fn execute(
+        &mut self,
+        main: &[OpCode],
+        functions: &[Function],
+        position: &mut ExecutionPosition,
+    ) -> Result<ExecutionResult, String> {
         let mut ip = 0i64;
         let mut instructions = main;
+        let mut current_function = None;
         let mut call_frames = vec![];
         while (ip as usize) < instructions.len() {
+            *position = ExecutionPosition {
+                function: current_function,
+                ip: ip as usize,
+            };
             let mut ip_increment = 1i64;
             match instructions[ip as usize] {
                 OpCode::Add => {
@@ -398,10 +1371,10 @@ impl Interpreter {
                     numeric_op!(self, *);
                 }
                 OpCode::Div => {
-                    numeric_op!(self, /);
+                    division_op!(self, /);
                 }
                 OpCode::Mod => {
-                    numeric_op!(self, %);
+                    division_op!(self, %);
                 }
                 OpCode::Pow => {
                     let rhs = self.pop_scalar()?.as_f64_or_err()?;
@@ -426,11 +1399,16 @@ impl Interpreter {
                 OpCode::Ne => {
                     compare_op!(self, !=);
                 }
-                OpCode::Match => todo!(),
-                OpCode::NotMatch => todo!(),
+                OpCode::Match | OpCode::NotMatch => {
+                    let regex = self.pop_regex()?;
+                    let value = self.pop_string()?;
+                    let result =
+                        regex.matches(&value) == (instructions[ip as usize] == OpCode::Match);
+                    self.push(ScalarValue::Number(result as i32 as f64));
+                }
                 OpCode::Concat => {
-                    let rhs = self.pop_scalar()?.to_string();
-                    let lhs = self.pop_scalar()?.to_string();
+                    let rhs = self.pop_string()?;
+                    let lhs = self.pop_string()?;
                     self.push(ScalarValue::String(lhs + &rhs));
                 }
                 OpCode::In => self.in_op()?,
@@ -438,6 +1416,10 @@ impl Interpreter {
                     let value = self.pop_scalar()?.as_f64_or_err()?;
                     self.push(ScalarValue::Number(-value));
                 }
+                OpCode::AsNumber => {
+                    let value = self.pop_scalar()?.as_f64_or_err()?;
+                    self.push(ScalarValue::Number(value));
+                }
                 OpCode::Not => {
                     let value = !self.pop_scalar()?.is_true();
                     self.push(ScalarValue::Number(value as i32 as f64));
@@ -466,8 +1448,25 @@ impl Interpreter {
                     *reference = ScalarValue::Number(num);
                     self.push(ScalarValue::Number(num));
                 }
+                OpCode::Dup => {
+                    let top = self.stack.last().expect("stack underflow").clone();
+                    // a reference to an array element is preceded by its
+                    // index, which is duplicated along with it
+                    if let StackValue::Reference(
+                        Reference::GlobalArrayRef(_) | Reference::LocalArrayRef(_),
+                    ) = top
+                    {
+                        let index = self.stack[self.stack.len() - 2].clone();
+                        self.push(index);
+                    }
+                    self.push(top);
+                }
                 OpCode::Pop => {
-                    self.stack.pop();
+                    // a reference is still evaluated, which creates array
+                    // elements and consumes their index
+                    if let StackValue::Reference(reference) = self.pop() {
+                        self.deref(reference)?;
+                    }
                 }
                 OpCode::ArrayRef(id) => {
                     self.push(StackValue::Reference(Reference::GlobalArrayRef(
@@ -486,8 +1485,7 @@ impl Interpreter {
                 }
                 OpCode::Assign => {
                     let value = self.pop_scalar()?;
-                    let reference = self.pop_ref()?;
-                    *reference = value.clone();
+                    self.assign(value.clone())?;
                     self.push(value);
                 }
                 OpCode::LocalVarRef(idx) => {
@@ -496,13 +1494,28 @@ impl Interpreter {
                 OpCode::LocalArrayRef(idx) => {
                     self.push(Reference::LocalArrayRef(idx as usize));
                 }
-                OpCode::Delete(id) => {
-                    let key = self.pop_scalar()?.to_string();
-                    match &mut self.globals[id as usize] {
-                        GlobalValue::Array(map) => {
-                            map.remove(&key);
+                OpCode::Delete => {
+                    let array = self.pop_array_ref();
+                    let key = self.pop_string()?;
+                    self.array_mut(array)?.remove(&key);
+                }
+                OpCode::ClearArray => {
+                    let array = self.pop_array_ref();
+                    self.array_mut(array)?.clear();
+                }
+                OpCode::BeginForIn => {
+                    let array = self.pop_array_ref();
+                    let keys = self.array_mut(array.clone())?.keys().cloned().collect();
+                    self.push(StackValue::ArrayIterator { array, keys });
+                }
+                OpCode::ForInNext(offset) => {
+                    let key_ref = self.pop();
+                    match self.next_key()? {
+                        Some(key) => {
+                            self.push(key_ref);
+                            self.assign(ScalarValue::String(key))?;
                         }
-                        _ => return Err("scalar used in array context".to_string()),
+                        None => ip_increment = offset as i64,
                     }
                 }
                 OpCode::JumpIfFalse(offset) => {
@@ -520,50 +1533,177 @@ impl Interpreter {
                 }
                 OpCode::Call { id, argc } => {
                     let function = &functions[id as usize];
-                    self.bp = self.stack.len() - argc as usize;
+                    let mut arguments = Vec::with_capacity(argc as usize);
+                    for _ in 0..argc {
+                        arguments.push(self.pop_argument()?);
+                    }
+                    // the arguments after the parameters of the function
+                    // are dropped
+                    arguments.reverse();
+                    arguments.truncate(function.parameters_count);
                     call_frames.push(CallFrame {
                         ip: ip as usize,
                         bp: self.bp,
                         last_temp_array: self.temp_arrays.len(),
                         instructions,
+                        function: current_function,
                     });
+                    self.bp = self.stack.len();
+                    self.stack.extend(arguments);
                     instructions = &function.instructions;
+                    current_function = Some(id as usize);
                     ip = 0;
                     ip_increment = 0;
                 }
-                OpCode::PushConstant(idx) => {
-                    self.push(self.constants[idx as usize].clone());
-                }
+                OpCode::CallBuiltin { function, argc } => self.call_builtin(function, argc)?,
+                OpCode::PushConstant(idx) => match &self.constants[idx as usize] {
+                    Constant::Regex(regex) => self.push(StackValue::Regex(regex.clone())),
+                    constant => self.push(constant.clone()),
+                },
                 OpCode::PushOne => {
                     self.push(ScalarValue::Number(1.0));
                 }
                 OpCode::PushUninitialized => {
                     self.push(StackValue::Uninitialized);
                 }
-                OpCode::Return => {
-                    let return_value = self.pop_scalar()?;
-                    let frame = call_frames.pop().expect("return outside of function");
-                    self.bp = frame.bp;
-                    self.stack.truncate(self.bp);
-                    self.temp_arrays.truncate(frame.last_temp_array);
-                    self.push(return_value);
-                    instructions = frame.instructions;
-                    ip = frame.ip as i64;
+                OpCode::PushUninitializedScalar => {
+                    self.push(ScalarValue::Uninitialized);
+                }
+                OpCode::Print { argc, output } => self.print(argc, output)?,
+                OpCode::Printf { argc, output } => self.printf(argc, output)?,
+                OpCode::Getline { source, assign } => self.getline(source, assign)?,
+                OpCode::Next => {
+                    self.reset_stack();
+                    return Ok(ExecutionResult::Next);
+                }
+                OpCode::Exit => {
+                    // `exit` without an expression pushes an uninitialized
+                    // value, and keeps the status of an earlier exit
+                    let status = self.pop_scalar()?;
+                    if status != ScalarValue::Uninitialized {
+                        self.exit_status = status.as_f64_or_err()? as i32;
+                    }
+                    self.reset_stack();
+                    return Ok(ExecutionResult::Exit);
+                }
+                OpCode::Return => {
+                    let return_value = self.pop_scalar()?;
+                    let frame = call_frames.pop().expect("return outside of function");
+                    self.stack.truncate(self.bp);
+                    self.bp = frame.bp;
+                    self.temp_arrays.truncate(frame.last_temp_array);
+                    self.push(return_value);
+                    instructions = frame.instructions;
+                    current_function = frame.function;
+                    ip = frame.ip as i64;
+                }
+                OpCode::Invalid => panic!("invalid opcode"),
+            }
+            ip += ip_increment;
+        }
+        Ok(ExecutionResult::Completed)
+    }
+
+    // leave the functions being run, if any
+    fn reset_stack(&mut self) {
+        self.stack.clear();
+        self.temp_arrays.clear();
+        self.bp = 0;
+    }
+
+    // the message of `error`, preceded by the line, and the function, of the
+    // instruction that raised it. `lines` are the lines of the main code
+    fn locate_error(
+        &self,
+        error: RuntimeError,
+        lines: &LineTable,
+        functions: &[Function],
+    ) -> String {
+        let (lines, function) = match error.position.function {
+            Some(id) => (&functions[id].lines, Some(&functions[id].name)),
+            None => (lines, None),
+        };
+        match (lines.line(error.position.ip), function) {
+            (Some(line), Some(function)) => format!(
+                "{}: in function {}: {}",
+                source_location(&self.source_files, line),
+                function,
+                error.message
+            ),
+            (Some(line), None) => format!(
+                "{}: {}",
+                source_location(&self.source_files, line),
+                error.message
+            ),
+            (None, _) => error.message,
+        }
+    }
+
+    fn pattern_matches(
+        &mut self,
+        instructions: &[OpCode],
+        line: u32,
+        functions: &[Function],
+    ) -> Result<bool, String> {
+        let pattern_lines = || LineTable(vec![(0, line)]);
+        self.run(instructions, functions)
+            .map_err(|error| self.locate_error(error, &pattern_lines(), functions))?;
+        let value = self.pop_scalar().map_err(|message| {
+            let position = ExecutionPosition::default();
+            self.locate_error(
+                RuntimeError { message, position },
+                &pattern_lines(),
+                functions,
+            )
+        })?;
+        Ok(value.is_true())
+    }
+
+    /// Run the rules for each record of the main input, until the input
+    /// ends or the program exits.
+    fn run_rules(
+        &mut self,
+        rules: &[AwkRule],
+        functions: &[Function],
+    ) -> Result<ExecutionResult, String> {
+        let mut in_range = vec![false; rules.len()];
+        while let Some(record) = self.read_main_record()? {
+            self.set_record(record)?;
+            for (i, rule) in rules.iter().enumerate() {
+                let matches = match &rule.pattern {
+                    Pattern::All => true,
+                    Pattern::Expr(pattern) => {
+                        self.pattern_matches(pattern, rule.line, functions)?
+                    }
+                    Pattern::Range { start, end } => {
+                        if !in_range[i] && self.pattern_matches(start, rule.line, functions)? {
+                            in_range[i] = true;
+                        }
+                        if in_range[i] && self.pattern_matches(end, rule.line, functions)? {
+                            in_range[i] = false;
+                            true
+                        } else {
+                            in_range[i]
+                        }
+                    }
+                };
+                if !matches {
+                    continue;
+                }
+                let result = self
+                    .run(&rule.instructions, functions)
+                    .map_err(|error| self.locate_error(error, &rule.lines, functions))?;
+                match result {
+                    ExecutionResult::Completed => {}
+                    ExecutionResult::Next => break,
+                    ExecutionResult::Exit => return Ok(ExecutionResult::Exit),
                 }
-                OpCode::Invalid => panic!("invalid opcode"),
-                other => todo!("{:?}", other),
             }
-            ip += ip_increment;
         }
-        Ok(())
+        Ok(ExecutionResult::Completed)
     }
 
-    fn new(
-        args: HashMap<String, String>,
-        env: HashMap<String, String>,
-        constants: Vec<Constant>,
-        program_globals: usize,
-    ) -> Self {
+    fn new(env: HashMap<String, String>, constants: Vec<Constant>, program_globals: usize) -> Self {
         let mut globals =
             vec![GlobalValue::Uninitialized; SpecialVar::Count as usize + program_globals];
 
@@ -571,9 +1711,12 @@ impl Interpreter {
         globals[SpecialVar::Argv as usize] = GlobalValue::Array(HashMap::new());
         globals[SpecialVar::Convfmt as usize] =
             GlobalValue::Scalar(ScalarValue::String("%.6g".to_string()));
-        globals[SpecialVar::Environ as usize] = GlobalValue::Array(HashMap::new());
-        globals[SpecialVar::Filename as usize] =
-            GlobalValue::Scalar(ScalarValue::String("-".to_string()));
+        globals[SpecialVar::Environ as usize] = GlobalValue::Array(
+            env.into_iter()
+                .map(|(name, value)| (name, ScalarValue::StrNum(value)))
+                .collect(),
+        );
+        globals[SpecialVar::Filename as usize] = GlobalValue::Scalar(ScalarValue::Uninitialized);
         globals[SpecialVar::Fnr as usize] = GlobalValue::Scalar(ScalarValue::Number(0.0));
         globals[SpecialVar::Fs as usize] =
             GlobalValue::Scalar(ScalarValue::String(" ".to_string()));
@@ -590,7 +1733,7 @@ impl Interpreter {
             GlobalValue::Scalar(ScalarValue::String("\n".to_string()));
         globals[SpecialVar::Rstart as usize] = GlobalValue::Scalar(ScalarValue::Number(0.0));
         globals[SpecialVar::Subsep as usize] =
-            GlobalValue::Scalar(ScalarValue::String("\034".to_string()));
+            GlobalValue::Scalar(ScalarValue::String("\x1c".to_string()));
 
         Self {
             globals,
@@ -598,13 +1741,91 @@ impl Interpreter {
             bp: 0,
             stack: vec![],
             fields: vec![],
+            record_state: RecordState::UpToDate,
             temp_arrays: vec![],
+            streams: Streams::default(),
+            main_input: MainInput::default(),
+            global_names: HashMap::new(),
+            source_files: Vec::new(),
+            lint: false,
+            random: RandomGenerator::new(0.0),
+            random_seed: 0.0,
+            exit_status: 0,
+            regex_cache: RegexCache::default(),
         }
     }
 }
 
-pub fn interpret(program: Program, files: Vec<String>) -> Result<(), String> {
-    todo!();
+/// Run `program` with the operands `arguments`, the input files and
+/// assignments that make up ARGV, after doing the `var=value` assignments
+/// of the -v option. Returns the exit status of the program.
+pub fn interpret(
+    program: Program,
+    assignments: Vec<String>,
+    arguments: Vec<String>,
+    field_separator: Option<String>,
+    lint: bool,
+) -> Result<i32, String> {
+    let Program {
+        constants,
+        globals_count,
+        begin_instructions,
+        begin_lines,
+        rules,
+        end_instructions,
+        end_lines,
+        functions,
+        global_names,
+        source_files,
+        warnings: _,
+    } = program;
+    let env = std::env::vars_os()
+        .map(|(name, value)| {
+            (
+                name.to_string_lossy().into_owned(),
+                value.to_string_lossy().into_owned(),
+            )
+        })
+        .collect();
+    let mut interpreter = Interpreter::new(env, constants, globals_count);
+    if let Some(fs) = field_separator {
+        interpreter.globals[SpecialVar::Fs as usize] = ScalarValue::String(fs).into();
+    }
+    interpreter.global_names = global_names;
+    interpreter.source_files = source_files;
+    interpreter.lint = lint;
+    for assignment in &assignments {
+        let (name, value) = command_line_assignment(assignment)
+            .ok_or_else(|| format!("invalid variable assignment '{}'", assignment))?;
+        interpreter.assign_command_line_var(name, value)?;
+    }
+    interpreter.set_special_number(SpecialVar::Argc, (arguments.len() + 1) as f64);
+    interpreter.globals[SpecialVar::Argv as usize] = GlobalValue::Array(
+        std::iter::once("awk".to_string())
+            .chain(arguments)
+            .enumerate()
+            .map(|(i, arg)| (i.to_string(), ScalarValue::StrNum(arg)))
+            .collect(),
+    );
+
+    let result = (|| {
+        // exiting from BEGIN or from a rule skips the rest of the input, but
+        // not the END actions
+        let begin_result = interpreter
+            .run(&begin_instructions, &functions)
+            .map_err(|error| interpreter.locate_error(error, &begin_lines, &functions))?;
+        // the input is only read if there is something to do with it
+        let read_input = !rules.is_empty() || !end_instructions.is_empty();
+        if !matches!(begin_result, ExecutionResult::Exit) && read_input {
+            interpreter.run_rules(&rules, &functions)?;
+        }
+        interpreter
+            .run(&end_instructions, &functions)
+            .map_err(|error| interpreter.locate_error(error, &end_lines, &functions))?;
+        Ok(interpreter.exit_status)
+    })();
+    interpreter.streams.close_all();
+    result
 }
 
 #[cfg(test)]
@@ -618,10 +1839,9 @@ mod tests {
         constants: Vec<Constant>,
         global_count: usize,
     ) -> ScalarValue {
-        let mut interpreter =
-            Interpreter::new(HashMap::new(), HashMap::new(), constants, global_count);
+        let mut interpreter = Interpreter::new(HashMap::new(), constants, global_count);
         interpreter
-            .run(&instructions, &[], &[])
+            .run(&instructions, &[])
             .expect("error running test");
         interpreter.pop_scalar().unwrap()
     }
@@ -632,18 +1852,19 @@ mod tests {
         global_count: usize,
         record: Vec<String>,
     ) -> ScalarValue {
-        let mut interpreter =
-            Interpreter::new(HashMap::new(), HashMap::new(), constants, global_count);
+        let mut interpreter = Interpreter::new(HashMap::new(), constants, global_count);
+        interpreter.set_special_number(SpecialVar::Nf, record.len() as f64 - 1.0);
+        interpreter.fields = record.into_iter().map(ScalarValue::String).collect();
         interpreter
-            .run(&instructions, &[], &record)
+            .run(&instructions, &[])
             .expect("error running test");
         interpreter.pop_scalar().unwrap()
     }
 
     fn test_global(instructions: Vec<OpCode>, constants: Vec<Constant>) -> GlobalValue {
-        let mut interpreter = Interpreter::new(HashMap::new(), HashMap::new(), constants, 1);
+        let mut interpreter = Interpreter::new(HashMap::new(), constants, 1);
         interpreter
-            .run(&instructions, &[], &[])
+            .run(&instructions, &[])
             .expect("error running test");
         interpreter.globals[FIRST_GLOBAL_VAR as usize].clone()
     }
@@ -654,10 +1875,9 @@ mod tests {
         global_count: usize,
         functions: Vec<Function>,
     ) -> ScalarValue {
-        let mut interpreter =
-            Interpreter::new(HashMap::new(), HashMap::new(), constants, global_count);
+        let mut interpreter = Interpreter::new(HashMap::new(), constants, global_count);
         interpreter
-            .run(&main, &functions, &[])
+            .run(&main, &functions)
             .expect("error running test");
         interpreter.pop_scalar().unwrap()
     }
@@ -852,6 +2072,74 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_compare_numeric_strings() {
+        let compare_fields = |record: &str, lhs: f64, rhs: f64, op: OpCode| {
+            let constants = vec![Constant::Number(lhs), Constant::Number(rhs)];
+            let mut interpreter = Interpreter::new(HashMap::new(), constants, 0);
+            interpreter.set_record(record.to_string()).unwrap();
+            let instructions = vec![
+                OpCode::PushConstant(0),
+                OpCode::FieldRef,
+                OpCode::PushConstant(1),
+                OpCode::FieldRef,
+                op,
+            ];
+            interpreter
+                .run(&instructions, &[])
+                .expect("error running test");
+            interpreter.pop_scalar().unwrap()
+        };
+        assert_eq!(
+            compare_fields("10 10.0", 1.0, 2.0, OpCode::Eq),
+            ScalarValue::Number(1.0)
+        );
+        assert_eq!(
+            compare_fields("10 9", 1.0, 2.0, OpCode::Gt),
+            ScalarValue::Number(1.0)
+        );
+        assert_eq!(
+            compare_fields("10 abc", 1.0, 2.0, OpCode::Lt),
+            ScalarValue::Number(1.0)
+        );
+
+        // a string constant makes the comparison a string comparison
+        let instructions = vec![
+            OpCode::PushConstant(0),
+            OpCode::FieldRef,
+            OpCode::PushConstant(1),
+            OpCode::Eq,
+        ];
+        let constants = vec![Constant::Number(1.0), Constant::String("10".to_string())];
+        assert_eq!(
+            interpret_expr_with_record(instructions, constants, 0, vec!["10.0".to_string()]),
+            ScalarValue::Number(0.0)
+        );
+    }
+
+    #[test]
+    fn test_compare_uninitialized() {
+        for constant in [Constant::Number(0.0), Constant::String(String::new())] {
+            let instructions = vec![
+                OpCode::VarRef(FIRST_GLOBAL_VAR),
+                OpCode::PushConstant(0),
+                OpCode::Eq,
+            ];
+            assert_eq!(
+                interpret_expr(instructions, vec![constant], 1),
+                ScalarValue::Number(1.0)
+            );
+        }
+    }
+
+    #[test]
+    fn test_numeric_string_is_true() {
+        assert!(!ScalarValue::StrNum(" 0.0 ".to_string()).is_true());
+        assert!(ScalarValue::StrNum("0x".to_string()).is_true());
+        assert!(ScalarValue::String("0".to_string()).is_true());
+        assert!(!ScalarValue::StrNum(String::new()).is_true());
+    }
+
     #[test]
     fn test_interpret_in_for_global_array() {
         let instructions = vec![
@@ -923,6 +2211,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_as_number() {
+        let instructions = vec![OpCode::PushConstant(0), OpCode::AsNumber];
+        let constant = vec![Constant::String("3x".to_string())];
+        assert_eq!(
+            interpret_expr(instructions, constant, 0),
+            ScalarValue::Number(3.0)
+        );
+    }
+
     #[test]
     fn test_not() {
         let instructions = vec![OpCode::PushConstant(0), OpCode::Not];
@@ -1001,6 +2299,87 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_compound_assign_to_array_element() {
+        let instructions = vec![
+            OpCode::PushConstant(0),
+            OpCode::ArrayRef(FIRST_GLOBAL_VAR),
+            OpCode::PushConstant(1),
+            OpCode::Assign,
+            OpCode::Pop,
+            OpCode::PushConstant(0),
+            OpCode::ArrayRef(FIRST_GLOBAL_VAR),
+            OpCode::Dup,
+            OpCode::PushConstant(1),
+            OpCode::Add,
+            OpCode::Assign,
+        ];
+        let constant = vec![Constant::String("key".to_string()), Constant::Number(2.0)];
+        assert_eq!(
+            test_global(instructions, constant),
+            GlobalValue::Array(HashMap::from([(
+                "key".to_string(),
+                ScalarValue::Number(4.0)
+            )]))
+        );
+    }
+
+    #[test]
+    fn test_random_generator() {
+        let mut generator = RandomGenerator::new(7.0);
+        let numbers: Vec<f64> = (0..1000).map(|_| generator.next()).collect();
+        assert!(numbers.iter().all(|n| (0.0..1.0).contains(n)));
+        let mut generator = RandomGenerator::new(7.9);
+        assert!(numbers.iter().all(|&n| n == generator.next()));
+        assert_ne!(RandomGenerator::new(8.0).next(), numbers[0]);
+    }
+
+    #[test]
+    fn test_command_line_assignment() {
+        assert_eq!(command_line_assignment("a=1"), Some(("a", "1")));
+        assert_eq!(command_line_assignment("_x2=a=b"), Some(("_x2", "a=b")));
+        assert_eq!(command_line_assignment("v="), Some(("v", "")));
+        assert_eq!(command_line_assignment("2a=1"), None);
+        assert_eq!(command_line_assignment("=1"), None);
+        assert_eq!(command_line_assignment("./a=1"), None);
+        assert_eq!(command_line_assignment("file"), None);
+    }
+
+    #[test]
+    fn test_environ_holds_the_environment() {
+        let env = HashMap::from([("HOME".to_string(), "/home/user".to_string())]);
+        let constants = vec![Constant::String("HOME".to_string())];
+        let mut interpreter = Interpreter::new(env, constants, 0);
+        let instructions = vec![
+            OpCode::PushConstant(0),
+            OpCode::ArrayRef(SpecialVar::Environ as u32),
+        ];
+        interpreter
+            .run(&instructions, &[])
+            .expect("error running test");
+        assert_eq!(
+            interpreter.pop_scalar().unwrap(),
+            ScalarValue::StrNum("/home/user".to_string())
+        );
+    }
+
+    #[test]
+    fn test_pop_array_element_creates_it() {
+        let instructions = vec![
+            OpCode::PushConstant(0),
+            OpCode::ArrayRef(FIRST_GLOBAL_VAR),
+            OpCode::Pop,
+        ];
+        let constant = vec![Constant::String("key".to_string())];
+        assert_eq!(
+            test_global(instructions, constant),
+            GlobalValue::Array(HashMap::from([(
+                "key".to_string(),
+                ScalarValue::Uninitialized
+            )]))
+        );
+    }
+
     #[test]
     fn test_delete_array_element_after_insertion() {
         let instructions = vec![
@@ -1009,7 +2388,8 @@ mod tests {
             OpCode::PushConstant(1),
             OpCode::Assign,
             OpCode::PushConstant(0),
-            OpCode::Delete(FIRST_GLOBAL_VAR),
+            OpCode::ArrayRef(FIRST_GLOBAL_VAR),
+            OpCode::Delete,
         ];
         let constant = vec![Constant::String("key".to_string()), Constant::Number(123.0)];
         assert_eq!(
@@ -1113,6 +2493,7 @@ mod tests {
         let functions = vec![Function {
             parameters_count: 0,
             instructions: vec![OpCode::PushConstant(0), OpCode::Return],
+            ..Default::default()
         }];
         let constant = vec![Constant::String("test".to_string())];
         assert_eq!(
@@ -1127,6 +2508,7 @@ mod tests {
         let functions = vec![Function {
             parameters_count: 1,
             instructions: vec![OpCode::LocalVarRef(0), OpCode::Return],
+            ..Default::default()
         }];
         assert_eq!(
             interpret_with_functions(main, vec![], 1, functions),
@@ -1144,6 +2526,7 @@ mod tests {
                 OpCode::LocalArrayRef(0),
                 OpCode::Return,
             ],
+            ..Default::default()
         }];
         let constant = vec![Constant::String("key".to_string())];
         assert_eq!(
@@ -1158,6 +2541,7 @@ mod tests {
         let functions = vec![Function {
             parameters_count: 1,
             instructions: vec![OpCode::LocalVarRef(0), OpCode::PushOne, OpCode::Add],
+            ..Default::default()
         }];
         let constant = vec![Constant::Number(0.0)];
         assert_eq!(
@@ -1169,7 +2553,7 @@ mod tests {
     #[test]
     fn test_call_function_with_array_argument() {
         let main = vec![
-            OpCode::ArrayRef(FIRST_GLOBAL_VAR),
+            OpCode::VarRef(FIRST_GLOBAL_VAR),
             OpCode::Call { id: 0, argc: 1 },
         ];
         let functions = vec![Function {
@@ -1180,6 +2564,7 @@ mod tests {
                 OpCode::PushOne,
                 OpCode::Assign,
             ],
+            ..Default::default()
         }];
         let constants = vec![Constant::String("key".to_string())];
         assert_eq!(
@@ -1212,6 +2597,7 @@ mod tests {
                 OpCode::Add,
                 OpCode::Return,
             ],
+            ..Default::default()
         }];
         let constants = vec![Constant::Number(1.0)];
         assert_eq!(
@@ -1220,6 +2606,116 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_call_function_with_scalar_variable_argument() {
+        let main = vec![
+            OpCode::VarRef(FIRST_GLOBAL_VAR),
+            OpCode::PushConstant(0),
+            OpCode::Assign,
+            OpCode::Pop,
+            OpCode::VarRef(FIRST_GLOBAL_VAR),
+            OpCode::Call { id: 0, argc: 1 },
+        ];
+        let functions = vec![Function {
+            parameters_count: 1,
+            instructions: vec![
+                OpCode::LocalVarRef(0),
+                OpCode::PushOne,
+                OpCode::Assign,
+                OpCode::Return,
+            ],
+            ..Default::default()
+        }];
+        let constants = vec![Constant::Number(0.0)];
+        let mut interpreter = Interpreter::new(HashMap::new(), constants, 1);
+        interpreter
+            .run(&main, &functions)
+            .expect("error running test");
+        assert_eq!(interpreter.pop_scalar(), Ok(ScalarValue::Number(1.0)));
+        assert_eq!(
+            interpreter.globals[FIRST_GLOBAL_VAR as usize],
+            GlobalValue::Scalar(ScalarValue::Number(0.0))
+        );
+    }
+
+    #[test]
+    fn test_call_function_with_uninitialized_variable_used_as_array() {
+        let main = vec![
+            OpCode::VarRef(FIRST_GLOBAL_VAR),
+            OpCode::Call { id: 0, argc: 1 },
+        ];
+        let functions = vec![Function {
+            parameters_count: 1,
+            instructions: vec![
+                OpCode::PushConstant(0),
+                OpCode::LocalArrayRef(0),
+                OpCode::PushOne,
+                OpCode::Assign,
+                OpCode::Return,
+            ],
+            ..Default::default()
+        }];
+        let constants = vec![Constant::String("key".to_string())];
+        let mut interpreter = Interpreter::new(HashMap::new(), constants, 1);
+        interpreter
+            .run(&main, &functions)
+            .expect("error running test");
+        assert_eq!(
+            interpreter.globals[FIRST_GLOBAL_VAR as usize],
+            GlobalValue::Array(HashMap::from([(
+                "key".to_string(),
+                ScalarValue::Number(1.0)
+            )]))
+        );
+    }
+
+    #[test]
+    fn test_call_function_with_extra_arguments() {
+        let main = vec![
+            OpCode::PushConstant(0),
+            OpCode::PushConstant(1),
+            OpCode::Call { id: 0, argc: 2 },
+        ];
+        let functions = vec![Function {
+            parameters_count: 1,
+            instructions: vec![OpCode::LocalVarRef(0), OpCode::Return],
+            ..Default::default()
+        }];
+        let constants = vec![Constant::Number(1.0), Constant::Number(2.0)];
+        assert_eq!(
+            interpret_with_functions(main, constants, 0, functions),
+            ScalarValue::Number(1.0)
+        );
+    }
+
+    #[test]
+    fn test_return_restores_caller_locals() {
+        let main = vec![OpCode::PushConstant(0), OpCode::Call { id: 0, argc: 1 }];
+        let functions = vec![
+            Function {
+                parameters_count: 1,
+                instructions: vec![
+                    OpCode::PushConstant(1),
+                    OpCode::Call { id: 1, argc: 1 },
+                    OpCode::Pop,
+                    OpCode::LocalVarRef(0),
+                    OpCode::Return,
+                ],
+                ..Default::default()
+            },
+            Function {
+                parameters_count: 1,
+                instructions: vec![OpCode::LocalVarRef(0), OpCode::Return],
+                ..Default::default()
+            },
+        ];
+        let constants = vec![Constant::Number(1.0), Constant::Number(2.0)];
+        assert_eq!(
+            interpret_with_functions(main, constants, 0, functions),
+            ScalarValue::Number(1.0)
+        );
+    }
+
     #[test]
     fn test_access_whole_record_field() {
         let instructions = vec![OpCode::PushConstant(0), OpCode::FieldRef];
@@ -1245,18 +2741,136 @@ mod tests {
         ];
         let constants = vec![Constant::Number(9.0)];
 
-        let mut interpreter = Interpreter::new(HashMap::new(), HashMap::new(), constants, 0);
-        interpreter
-            .run(
-                &instructions,
-                &[],
-                &["test".to_string(), "test".to_string()],
-            )
-            .unwrap();
+        let mut interpreter = Interpreter::new(HashMap::new(), constants, 0);
+        interpreter.fields = vec![ScalarValue::String("test".to_string()); 2];
+        interpreter.run(&instructions, &[]).unwrap();
         assert_eq!(interpreter.fields.len(), 10);
         assert_eq!(
             interpreter.globals[SpecialVar::Nf as usize],
-            ScalarValue::Number(10.0).into()
+            ScalarValue::Number(9.0).into()
+        );
+    }
+
+    #[test]
+    fn test_assign_to_field_rebuilds_record() {
+        let instructions = vec![
+            OpCode::PushConstant(0),
+            OpCode::FieldRef,
+            OpCode::PushConstant(1),
+            OpCode::Assign,
+            OpCode::Pop,
+            OpCode::PushConstant(2),
+            OpCode::FieldRef,
+        ];
+        let constants = vec![
+            Constant::Number(4.0),
+            Constant::String("d".to_string()),
+            Constant::Number(0.0),
+        ];
+        let mut interpreter = Interpreter::new(HashMap::new(), constants, 0);
+        interpreter.set_record("a b".to_string()).unwrap();
+        interpreter.run(&instructions, &[]).unwrap();
+        assert_eq!(
+            interpreter.pop_scalar().unwrap(),
+            ScalarValue::StrNum("a b  d".to_string())
+        );
+    }
+
+    #[test]
+    fn test_assign_to_nf_rebuilds_record() {
+        let instructions = vec![
+            OpCode::VarRef(SpecialVar::Nf as u32),
+            OpCode::PushConstant(0),
+            OpCode::Assign,
+            OpCode::Pop,
+            OpCode::PushConstant(1),
+            OpCode::FieldRef,
+        ];
+        let constants = vec![Constant::Number(2.0), Constant::Number(0.0)];
+        let mut interpreter = Interpreter::new(HashMap::new(), constants, 0);
+        interpreter.set_record("a b c".to_string()).unwrap();
+        interpreter.run(&instructions, &[]).unwrap();
+        assert_eq!(
+            interpreter.pop_scalar().unwrap(),
+            ScalarValue::StrNum("a b".to_string())
+        );
+        assert_eq!(interpreter.fields.len(), 3);
+    }
+
+    #[test]
+    fn test_string_to_number() {
+        assert_eq!(string_to_number("  12abc"), 12.0);
+        assert_eq!(string_to_number("-1.5e2x"), -150.0);
+        assert_eq!(string_to_number(".5"), 0.5);
+        assert_eq!(string_to_number("3."), 3.0);
+        assert_eq!(string_to_number("2e"), 2.0);
+        assert_eq!(string_to_number("+.e1"), 0.0);
+        assert_eq!(string_to_number("0x1A"), 0.0);
+        assert_eq!(string_to_number("inf"), 0.0);
+        assert_eq!(string_to_number("nan"), 0.0);
+    }
+
+    #[test]
+    fn test_sprintf() {
+        let number = ScalarValue::Number;
+        let string = |s: &str| ScalarValue::String(s.to_string());
+        assert_eq!(
+            sprintf(
+                "%d %i %5.2f %e",
+                &[number(3.9), number(-2.0), number(1.0), number(10.0)]
+            ),
+            Ok("3 -2  1.00 1.000000e+01".to_string())
+        );
+        assert_eq!(
+            sprintf(
+                "%x %o %c %c %%",
+                &[number(255.0), number(8.0), number(65.0), string("bc")]
+            ),
+            Ok("ff 10 A b %".to_string())
+        );
+        assert_eq!(
+            sprintf(
+                "[%*d] [%-*s] [%.*s]",
+                &[
+                    number(4.0),
+                    number(1.0),
+                    number(-3.0),
+                    string("a"),
+                    number(2.0),
+                    string("abc")
+                ]
+            ),
+            Ok("[   1] [a  ] [ab]".to_string())
+        );
+        assert_eq!(
+            sprintf("%s|%d", &[string("a")]),
+            Err("not enough arguments for format '%s|%d'".to_string())
+        );
+        assert_eq!(sprintf("%%", &[]), Ok("%".to_string()));
+        assert!(sprintf("%y", &[number(1.0)]).is_err());
+        assert!(sprintf("%-5", &[number(1.0)]).is_err());
+    }
+
+    #[test]
+    fn test_substitute() {
+        let regex = Regex::new("b*").unwrap();
+        assert_eq!(
+            substitute(&regex, "-", "abc", true),
+            ("-a-c-".to_string(), 3)
         );
+        assert_eq!(
+            substitute(&regex, "-", "abc", false),
+            ("-abc".to_string(), 1)
+        );
+
+        let regex = Regex::new("^a|c$").unwrap();
+        assert_eq!(substitute(&regex, "X", "abc", true), ("XbX".to_string(), 2));
+
+        let regex = Regex::new("x").unwrap();
+        assert_eq!(substitute(&regex, "[&]", "axa", true).0, "a[x]a");
+        assert_eq!(substitute(&regex, "\\&", "x", true).0, "&");
+        assert_eq!(substitute(&regex, "\\\\&", "x", true).0, "\\x");
+        assert_eq!(substitute(&regex, "a\\b", "x", true).0, "a\\b");
+        assert_eq!(substitute(&regex, "y", "abc", true), ("abc".to_string(), 0));
     }
 }
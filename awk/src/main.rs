@@ -7,20 +7,98 @@
 // SPDX-License-Identifier: MIT
 //
 
-use compiler::compile_program;
+use clap::Parser;
+use compiler::{compile_program, compile_program_files, SourceFile};
+use gettextrs::{bind_textdomain_codeset, textdomain};
+use interpreter::interpret;
+use plib::PROJECT_NAME;
+use program::source_location;
 
 mod compiler;
 mod interpreter;
 mod program;
+mod regex;
+mod streams;
 
-fn main() {
-    let text = r#"
-    BEGIN {
-        a[1]
+/// awk - pattern scanning and processing language
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about)]
+struct Args {
+    /// Define the input field separator.
+    #[arg(short = 'F')]
+    field_separator: Option<String>,
+
+    /// Assign a value to a variable before the program runs, as var=value.
+    #[arg(short = 'v')]
+    assignments: Vec<String>,
+
+    /// Read the program from this file instead of the first operand. If
+    /// given more than once, the program is made of all the files in turn.
+    #[arg(short = 'f')]
+    progfiles: Vec<String>,
+
+    /// Print the compiled program instead of running it.
+    #[arg(long)]
+    dump: bool,
+
+    /// Warn about constructs that are not portable or likely mistakes, like
+    /// the use of uninitialized variables.
+    #[arg(long)]
+    lint: bool,
+
+    /// The program text, unless -f is given, followed by the input files.
+    #[arg(trailing_var_arg = true)]
+    arguments: Vec<String>,
+}
+
+fn run(args: Args) -> Result<i32, Box<dyn std::error::Error>> {
+    let mut arguments = args.arguments.into_iter();
+    let program = if args.progfiles.is_empty() {
+        compile_program(&arguments.next().ok_or("no program given")?)?
+    } else {
+        let files = args
+            .progfiles
+            .into_iter()
+            .map(|filename| {
+                let contents = std::fs::read_to_string(&filename)
+                    .map_err(|e| format!("can't read program file {}: {}", filename, e))?;
+                Ok(SourceFile { filename, contents })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        compile_program_files(&files)?
+    };
+    if args.lint {
+        for (line, message) in &program.warnings {
+            let location = source_location(&program.source_files, *line);
+            eprintln!("awk: warning: {}: {}", location, message);
+        }
     }
-    "#;
-    let program = compile_program(text)
-        .inspect_err(|e| println!("{}", e))
-        .unwrap();
-    println!("{:?}", program);
+    if args.dump {
+        print!("{}", program);
+        return Ok(0);
+    }
+    Ok(interpret(
+        program,
+        args.assignments,
+        arguments.collect(),
+        args.field_separator,
+        args.lint,
+    )?)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    textdomain(PROJECT_NAME)?;
+    bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
+
+    let exit_code = match run(args) {
+        Ok(status) => status,
+        Err(e) => {
+            eprintln!("awk: {}", e);
+            2
+        }
+    };
+
+    std::process::exit(exit_code)
 }
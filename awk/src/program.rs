@@ -7,10 +7,61 @@
 // SPDX-License-Identifier: MIT
 //
 
+use crate::regex::Regex;
 use core::fmt;
+use std::collections::HashMap;
+use std::rc::Rc;
 
 pub type VarId = u32;
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BuiltinFunction {
+    Atan2,
+    Cos,
+    Sin,
+    Exp,
+    Log,
+    Sqrt,
+    Int,
+    Rand,
+    Srand,
+    Gsub,
+    Index,
+    Length,
+    Match,
+    Split,
+    Sprintf,
+    Sub,
+    Substr,
+    ToLower,
+    ToUpper,
+    Close,
+    System,
+}
+
+/// Where print writes its output.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Output {
+    Stdout,
+    /// A file, truncated when it is first opened (`>`).
+    Truncate,
+    /// A file, appended to (`>>`).
+    Append,
+    /// The standard input of a command (`|`).
+    Pipe,
+}
+
+/// Where getline reads a record from.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GetlineSource {
+    /// The current input file, the same as the main loop.
+    Main,
+    /// A file (`getline < file`).
+    File,
+    /// The standard output of a command (`cmd | getline`).
+    Command,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum OpCode {
     // binary operations
@@ -43,7 +94,8 @@ pub enum OpCode {
     PreDec,
 
     AsNumber,
-    // push the value on top of the stack
+    // push a copy of the value on top of the stack. A reference to an
+    // array element is copied along with the index preceding it
     Dup,
     // pop the value from the stack
     Pop,
@@ -63,9 +115,20 @@ pub enum OpCode {
     LocalVarRef(u32),
     LocalArrayRef(u32),
 
-    // delete an element from the array with the given id.
-    // The index of the element is on top of the stack.
-    Delete(u32),
+    // delete an element from the array with reference on top of the stack.
+    // The index of the element precedes it
+    Delete,
+    // delete all the elements of the array with reference on top of the stack
+    ClearArray,
+
+    // replace the array reference on top of the stack with an iterator over
+    // the keys the array has
+    BeginForIn,
+    // assign the next key of the iterator to the variable with reference on
+    // top of the stack, which the iterator precedes. Keys deleted from the
+    // array in the meantime are skipped. Jumps by the given offset if there
+    // are no keys left
+    ForInNext(i32),
 
     // jump forwards or backwards by the given offset.
     // Offset 0 is the jump instruction
@@ -73,7 +136,19 @@ pub enum OpCode {
     JumpIfTrue(i32),
     Jump(i32),
 
-    Call { id: u32, argc: u16 },
+    // call the function with the `argc` arguments on top of the stack,
+    // which become its first locals. `argc` is at least the number of
+    // parameters of the function, and the arguments after them are dropped
+    Call {
+        id: u32,
+        argc: u16,
+    },
+    // call a builtin function with the arguments on top of the stack.
+    // Pushes the result on the stack
+    CallBuiltin {
+        function: BuiltinFunction,
+        argc: u16,
+    },
 
     // Push the constant value on top of the stack
     PushConstant(u32),
@@ -84,7 +159,27 @@ pub enum OpCode {
     // Push the uninitialized scalar value on top of the stack
     PushUninitializedScalar,
 
-    Print,
+    // print the `argc` values on top of the stack. The file or command to
+    // write to, if any, is pushed after them
+    Print {
+        argc: u16,
+        output: Output,
+    },
+
+    // print the `argc` values on top of the stack formatted by the first
+    // of them. The file or command to write to, if any, is pushed after them
+    Printf {
+        argc: u16,
+        output: Output,
+    },
+    // read a record, and push 1 on success, 0 at the end of the input and
+    // -1 on error. The name of the file or command, if any, is on top of
+    // the stack, preceded by a reference to the variable to assign if
+    // `assign` is true. Otherwise the record is assigned to $0
+    Getline {
+        source: GetlineSource,
+        assign: bool,
+    },
 
     Next,
     Exit,
@@ -98,7 +193,7 @@ pub enum OpCode {
 pub enum Constant {
     Number(f64),
     String(String),
-    Regex(String),
+    Regex(Rc<Regex>),
 }
 
 #[derive(Debug, PartialEq)]
@@ -111,10 +206,37 @@ pub enum Pattern {
     All,
 }
 
+/// The source lines a sequence of instructions was compiled from. Each entry
+/// holds the index of the first instruction of a line and the line number,
+/// so an instruction belongs to the line of the last entry starting at or
+/// before it.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct LineTable(pub Vec<(usize, u32)>);
+
+impl LineTable {
+    /// Record that the instructions from `start` on come from `line`.
+    pub fn add(&mut self, start: usize, line: u32) {
+        match self.0.last_mut() {
+            Some((_, last_line)) if *last_line == line => {}
+            Some((last_start, last_line)) if *last_start == start => *last_line = line,
+            _ => self.0.push((start, line)),
+        }
+    }
+
+    /// The line the instruction at `index` comes from.
+    pub fn line(&self, index: usize) -> Option<u32> {
+        let entry = self.0.partition_point(|&(start, _)| start <= index);
+        entry.checked_sub(1).map(|entry| self.0[entry].1)
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct AwkRule {
     pub pattern: Pattern,
     pub instructions: Vec<OpCode>,
+    /// The line the rule starts at, where its pattern is.
+    pub line: u32,
+    pub lines: LineTable,
 }
 
 #[derive(Clone, Copy)]
@@ -124,10 +246,12 @@ pub enum NameType {
     Function,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Default)]
 pub struct Function {
+    pub name: String,
     pub parameters_count: usize,
     pub instructions: Vec<OpCode>,
+    pub lines: LineTable,
 }
 
 pub struct Program {
@@ -135,9 +259,19 @@ pub struct Program {
     pub globals_count: usize,
 
     pub begin_instructions: Vec<OpCode>,
+    pub begin_lines: LineTable,
     pub rules: Vec<AwkRule>,
     pub end_instructions: Vec<OpCode>,
+    pub end_lines: LineTable,
     pub functions: Vec<Function>,
+    /// The ids of the global variables, by name, for the assignments
+    /// given on the command line.
+    pub global_names: HashMap<String, u32>,
+    /// The files the program was read from, with the line of the program
+    /// each of them starts at. Empty if the program was given as an operand.
+    pub source_files: Vec<(String, u32)>,
+    /// The portability problems found while compiling, with their line.
+    pub warnings: Vec<(u32, String)>,
 }
 
 impl fmt::Debug for Program {
@@ -152,6 +286,130 @@ impl fmt::Debug for Program {
     }
 }
 
+/// The location of `line` of the program, in the file it comes from if the
+/// program was read from `source_files`.
+pub fn source_location(source_files: &[(String, u32)], line: u32) -> String {
+    match source_files.iter().rev().find(|(_, start)| *start <= line) {
+        Some((filename, start)) => format!("{}:{}", filename, line - start + 1),
+        None => format!("line {}", line),
+    }
+}
+
+impl Program {
+    // the listing of `instructions`, preceded by the line each group of them
+    // comes from, if known
+    fn write_instructions(
+        &self,
+        f: &mut fmt::Formatter,
+        instructions: &[OpCode],
+        lines: Option<&LineTable>,
+        global_names: &[&str],
+    ) -> fmt::Result {
+        for (ip, instruction) in instructions.iter().enumerate() {
+            let line = lines.and_then(|lines| lines.0.iter().find(|(start, _)| *start == ip));
+            if let Some((_, line)) = line {
+                writeln!(f, "  line {}:", line)?;
+            }
+            let comment = match *instruction {
+                OpCode::VarRef(id) | OpCode::ArrayRef(id) => global_names[id as usize].to_string(),
+                OpCode::PushConstant(index) => match &self.constants[index as usize] {
+                    Constant::Number(n) => n.to_string(),
+                    Constant::String(s) => format!("{:?}", s),
+                    Constant::Regex(regex) => format!("{:?}", regex),
+                },
+                OpCode::Call { id, .. } => self.functions[id as usize].name.clone(),
+                OpCode::JumpIfFalse(offset)
+                | OpCode::JumpIfTrue(offset)
+                | OpCode::Jump(offset)
+                | OpCode::ForInNext(offset) => format!("-> {}", ip as i64 + offset as i64),
+                _ => String::new(),
+            };
+            let listing = format!(
+                "{:>4}: {:<32} {}",
+                ip,
+                format!("{:?}", instruction),
+                comment
+            );
+            writeln!(f, "    {}", listing.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// The listing of the compiled program, for the --dump option.
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut global_names = vec![""; self.globals_count];
+        for (name, id) in &self.global_names {
+            global_names[*id as usize] = name;
+        }
+
+        writeln!(f, "constants:")?;
+        for (index, constant) in self.constants.iter().enumerate() {
+            writeln!(f, "    {:>4}: {:?}", index, constant)?;
+        }
+        writeln!(f, "globals:")?;
+        for (id, name) in global_names
+            .iter()
+            .enumerate()
+            .skip(SpecialVar::Count as usize)
+        {
+            writeln!(f, "    {:>4}: {}", id, name)?;
+        }
+        if !self.begin_instructions.is_empty() {
+            writeln!(f, "BEGIN:")?;
+            self.write_instructions(
+                f,
+                &self.begin_instructions,
+                Some(&self.begin_lines),
+                &global_names,
+            )?;
+        }
+        for (index, rule) in self.rules.iter().enumerate() {
+            writeln!(f, "rule {} (line {}):", index + 1, rule.line)?;
+            match &rule.pattern {
+                Pattern::Expr(pattern) => {
+                    writeln!(f, "  pattern:")?;
+                    self.write_instructions(f, pattern, None, &global_names)?;
+                }
+                Pattern::Range { start, end } => {
+                    writeln!(f, "  range start:")?;
+                    self.write_instructions(f, start, None, &global_names)?;
+                    writeln!(f, "  range end:")?;
+                    self.write_instructions(f, end, None, &global_names)?;
+                }
+                Pattern::All => {}
+            }
+            writeln!(f, "  action:")?;
+            self.write_instructions(f, &rule.instructions, Some(&rule.lines), &global_names)?;
+        }
+        if !self.end_instructions.is_empty() {
+            writeln!(f, "END:")?;
+            self.write_instructions(
+                f,
+                &self.end_instructions,
+                Some(&self.end_lines),
+                &global_names,
+            )?;
+        }
+        for function in &self.functions {
+            writeln!(
+                f,
+                "function {} (parameters: {}):",
+                function.name, function.parameters_count
+            )?;
+            self.write_instructions(
+                f,
+                &function.instructions,
+                Some(&function.lines),
+                &global_names,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
 #[repr(u32)]
 pub enum SpecialVar {
     Argc,
@@ -0,0 +1,141 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use core::fmt;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// The number of regexes a `RegexCache` keeps before it is emptied.
+const MAX_CACHED_REGEXES: usize = 64;
+
+/// An extended regular expression, along with the text it was compiled
+/// from.
+pub struct Regex {
+    pattern: String,
+    regex: plib::regex::Regex,
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, String> {
+        let regex = plib::regex::Regex::extended(pattern.as_bytes())
+            .map_err(|e| format!("invalid regular expression '{}': {}", pattern, e))?;
+        Ok(Regex {
+            pattern: pattern.to_string(),
+            regex,
+        })
+    }
+
+    /// The byte offsets of the leftmost match in `s`.
+    pub fn find(&self, s: &str) -> Option<(usize, usize)> {
+        self.regex.exec(s.as_bytes(), false)?[0]
+    }
+
+    /// The byte offsets of the leftmost match in `s` that starts at or after
+    /// `start`, where `^` only matches at the start of `s`.
+    pub fn find_at(&self, s: &str, start: usize) -> Option<(usize, usize)> {
+        let (match_start, match_end) = self.regex.exec(&s.as_bytes()[start..], start > 0)?[0]?;
+        Some((start + match_start, start + match_end))
+    }
+
+    pub fn matches(&self, s: &str) -> bool {
+        self.find(s).is_some()
+    }
+
+    /// The byte offsets of the leftmost non-empty match in `s` that starts
+    /// at or after `start`.
+    pub fn find_non_empty_at(&self, s: &str, mut start: usize) -> Option<(usize, usize)> {
+        loop {
+            let (match_start, match_end) = self.find_at(s, start)?;
+            if match_start < match_end {
+                return Some((match_start, match_end));
+            }
+            // skip the character after an empty match
+            start = match_start + s[match_start..].chars().next()?.len_utf8();
+        }
+    }
+
+    /// The parts of `s` between the non-empty matches of the regex.
+    pub fn split<'s>(&self, s: &'s str) -> Vec<&'s str> {
+        let mut parts = Vec::new();
+        let mut part_start = 0;
+        while let Some((start, end)) = self.find_non_empty_at(s, part_start) {
+            parts.push(&s[part_start..start]);
+            part_start = end;
+        }
+        parts.push(&s[part_start..]);
+        parts
+    }
+}
+
+/// The regexes compiled from strings at runtime, by pattern, so that a
+/// dynamic regex is not compiled again for each record.
+#[derive(Default)]
+pub struct RegexCache {
+    regexes: HashMap<String, Rc<Regex>>,
+}
+
+impl RegexCache {
+    pub fn get(&mut self, pattern: &str) -> Result<Rc<Regex>, String> {
+        if let Some(regex) = self.regexes.get(pattern) {
+            return Ok(regex.clone());
+        }
+        if self.regexes.len() >= MAX_CACHED_REGEXES {
+            self.regexes.clear();
+        }
+        let regex = Rc::new(Regex::new(pattern)?);
+        self.regexes.insert(pattern.to_string(), regex.clone());
+        Ok(regex)
+    }
+}
+
+impl fmt::Debug for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "/{}/", self.pattern)
+    }
+}
+
+impl PartialEq for Regex {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern == other.pattern
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find() {
+        let regex = Regex::new("b+|x").unwrap();
+        assert_eq!(regex.find("abbc"), Some((1, 3)));
+        assert!(regex.matches("x"));
+        assert!(!regex.matches("ac"));
+        assert!(Regex::new("(a").is_err());
+    }
+
+    #[test]
+    fn test_regex_cache() {
+        let mut cache = RegexCache::default();
+        let regex = cache.get("a+").unwrap();
+        assert!(Rc::ptr_eq(&regex, &cache.get("a+").unwrap()));
+        assert!(cache.get("(").is_err());
+        for i in 0..MAX_CACHED_REGEXES {
+            cache.get(&i.to_string()).unwrap();
+        }
+        assert!(cache.regexes.len() <= MAX_CACHED_REGEXES);
+    }
+
+    #[test]
+    fn test_split() {
+        let regex = Regex::new("[,;]+").unwrap();
+        assert_eq!(regex.split("a,b;;c"), vec!["a", "b", "c"]);
+        assert_eq!(regex.split(",a,"), vec!["", "a", ""]);
+        assert_eq!(Regex::new("^a").unwrap().split("aab"), vec!["", "ab"]);
+    }
+}
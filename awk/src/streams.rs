@@ -0,0 +1,365 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+//! The files and commands a program reads with getline and writes with
+//! print, kept open by name until they are closed.
+
+use crate::program::{GetlineSource, Output};
+use crate::regex::Regex;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::rc::Rc;
+
+/// What ends a record, as given by RS.
+#[derive(Debug, Clone)]
+pub enum RecordSeparator {
+    Char(u8),
+    /// One or more blank lines, when RS is empty.
+    Paragraph,
+    /// The matches of a regex, when RS is longer than one character.
+    Regex(Rc<Regex>),
+}
+
+/// Reads the records of a file or of the output of a command.
+pub struct RecordReader {
+    reader: Box<dyn BufRead>,
+    // what was read past the end of the last record
+    pending: Vec<u8>,
+}
+
+impl RecordReader {
+    pub fn new(reader: impl BufRead + 'static) -> Self {
+        RecordReader {
+            reader: Box::new(reader),
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn stdin() -> Self {
+        RecordReader::new(io::stdin().lock())
+    }
+
+    pub fn open(path: &str) -> io::Result<Self> {
+        Ok(RecordReader::new(BufReader::new(File::open(path)?)))
+    }
+
+    // the input up to and including the next `delimiter`, or up to the end
+    // of the input. Returns `None` at the end of the input
+    fn read_until(&mut self, delimiter: u8) -> io::Result<Option<Vec<u8>>> {
+        if let Some(position) = self.pending.iter().position(|&b| b == delimiter) {
+            let rest = self.pending.split_off(position + 1);
+            return Ok(Some(std::mem::replace(&mut self.pending, rest)));
+        }
+        let mut data = std::mem::take(&mut self.pending);
+        self.reader.read_until(delimiter, &mut data)?;
+        Ok(if data.is_empty() { None } else { Some(data) })
+    }
+
+    fn read_paragraph(&mut self) -> io::Result<Option<String>> {
+        let mut lines: Vec<String> = Vec::new();
+        while let Some(mut line) = self.read_until(b'\n')? {
+            if line.last() == Some(&b'\n') {
+                line.pop();
+            }
+            if line.is_empty() {
+                if lines.is_empty() {
+                    // blank lines before the record
+                    continue;
+                }
+                break;
+            }
+            lines.push(String::from_utf8_lossy(&line).into_owned());
+        }
+        Ok(if lines.is_empty() {
+            None
+        } else {
+            Some(lines.join("\n"))
+        })
+    }
+
+    fn read_regex_record(&mut self, regex: &Regex) -> io::Result<Option<String>> {
+        let mut end_of_input = false;
+        loop {
+            let text = String::from_utf8_lossy(&self.pending).into_owned();
+            // a match at the end of what was read may go on in what follows
+            match regex.find_non_empty_at(&text, 0) {
+                Some((start, end)) if end < text.len() || end_of_input => {
+                    self.pending = text.as_bytes()[end..].to_vec();
+                    return Ok(Some(text[..start].to_string()));
+                }
+                _ if end_of_input => {
+                    self.pending.clear();
+                    return Ok(if text.is_empty() { None } else { Some(text) });
+                }
+                _ => {}
+            }
+            end_of_input = self.reader.read_until(b'\n', &mut self.pending)? == 0;
+        }
+    }
+
+    /// Read the next record, which ends with `separator` or at the end of
+    /// the input. Returns `None` at the end of the input.
+    pub fn read_record(&mut self, separator: &RecordSeparator) -> io::Result<Option<String>> {
+        match separator {
+            RecordSeparator::Char(separator) => {
+                let Some(mut record) = self.read_until(*separator)? else {
+                    return Ok(None);
+                };
+                if record.last() == Some(separator) {
+                    record.pop();
+                }
+                Ok(Some(String::from_utf8_lossy(&record).into_owned()))
+            }
+            RecordSeparator::Paragraph => self.read_paragraph(),
+            RecordSeparator::Regex(regex) => self.read_regex_record(regex),
+        }
+    }
+}
+
+enum OutputStream {
+    File(BufWriter<File>),
+    Pipe {
+        child: Child,
+        stdin: BufWriter<ChildStdin>,
+    },
+    Stderr,
+}
+
+enum InputStream {
+    File(RecordReader),
+    Command { child: Child, reader: RecordReader },
+}
+
+// the exit status of a command, as close() and system() return it
+fn status_value(status: ExitStatus) -> i32 {
+    match (status.code(), status.signal()) {
+        (Some(code), _) => code,
+        (None, Some(signal)) => 256 + signal,
+        _ => -1,
+    }
+}
+
+fn spawn_shell(command: &str) -> Command {
+    let mut process = Command::new("sh");
+    process.arg("-c").arg(command);
+    process
+}
+
+pub struct Streams {
+    stdout: BufWriter<io::Stdout>,
+    stdin: Option<RecordReader>,
+    outputs: HashMap<String, OutputStream>,
+    inputs: HashMap<String, InputStream>,
+}
+
+impl Default for Streams {
+    fn default() -> Self {
+        Streams {
+            stdout: BufWriter::new(io::stdout()),
+            stdin: None,
+            outputs: HashMap::new(),
+            inputs: HashMap::new(),
+        }
+    }
+}
+
+impl Streams {
+    /// The standard input, shared by the main input and getline.
+    pub fn stdin(&mut self) -> &mut RecordReader {
+        self.stdin.get_or_insert_with(RecordReader::stdin)
+    }
+
+    fn open_output(&mut self, output: Output, name: &str) -> io::Result<OutputStream> {
+        match output {
+            Output::Truncate | Output::Append if name == "/dev/stderr" => Ok(OutputStream::Stderr),
+            Output::Truncate => Ok(OutputStream::File(BufWriter::new(File::create(name)?))),
+            Output::Append => {
+                let file = File::options().create(true).append(true).open(name)?;
+                Ok(OutputStream::File(BufWriter::new(file)))
+            }
+            Output::Pipe => {
+                // what was written before comes before the output of the command
+                self.stdout.flush()?;
+                let mut child = spawn_shell(name).stdin(Stdio::piped()).spawn()?;
+                let stdin = BufWriter::new(child.stdin.take().expect("stdin is piped"));
+                Ok(OutputStream::Pipe { child, stdin })
+            }
+            Output::Stdout => unreachable!("stdout is not opened"),
+        }
+    }
+
+    /// Write `data` to the standard output, or to the file or command
+    /// `name`, which is opened the first time it is written to.
+    pub fn write(&mut self, output: Output, name: &str, data: &str) -> Result<(), String> {
+        let result = if output == Output::Stdout || name == "/dev/stdout" || name == "-" {
+            self.stdout.write_all(data.as_bytes())
+        } else {
+            if !self.outputs.contains_key(name) {
+                let stream = self
+                    .open_output(output, name)
+                    .map_err(|e| format!("can't redirect to '{}': {}", name, e))?;
+                self.outputs.insert(name.to_string(), stream);
+            }
+            match self.outputs.get_mut(name).unwrap() {
+                OutputStream::File(file) => file.write_all(data.as_bytes()),
+                OutputStream::Pipe { stdin, .. } => stdin.write_all(data.as_bytes()),
+                OutputStream::Stderr => io::stderr().write_all(data.as_bytes()),
+            }
+        };
+        result.map_err(|e| format!("error writing to '{}': {}", name, e))
+    }
+
+    fn open_input(&mut self, source: GetlineSource, name: &str) -> io::Result<InputStream> {
+        match source {
+            GetlineSource::File => Ok(InputStream::File(RecordReader::open(name)?)),
+            GetlineSource::Command => {
+                self.flush();
+                let mut child = spawn_shell(name).stdout(Stdio::piped()).spawn()?;
+                let stdout = child.stdout.take().expect("stdout is piped");
+                let reader = RecordReader::new(BufReader::new(stdout));
+                Ok(InputStream::Command { child, reader })
+            }
+            GetlineSource::Main => unreachable!("the main input is not opened by name"),
+        }
+    }
+
+    /// Read the next record of the file or command `name`, which is
+    /// opened the first time it is read from.
+    pub fn read_record(
+        &mut self,
+        source: GetlineSource,
+        name: &str,
+        separator: &RecordSeparator,
+    ) -> io::Result<Option<String>> {
+        if source == GetlineSource::File && (name == "-" || name == "/dev/stdin") {
+            return self.stdin().read_record(separator);
+        }
+        if !self.inputs.contains_key(name) {
+            let stream = self.open_input(source, name)?;
+            self.inputs.insert(name.to_string(), stream);
+        }
+        match self.inputs.get_mut(name).unwrap() {
+            InputStream::File(reader) => reader.read_record(separator),
+            InputStream::Command { reader, .. } => reader.read_record(separator),
+        }
+    }
+
+    /// Flush the standard output and the files and commands written to.
+    pub fn flush(&mut self) {
+        self.stdout.flush().ok();
+        for output in self.outputs.values_mut() {
+            match output {
+                OutputStream::File(file) => file.flush().ok(),
+                OutputStream::Pipe { stdin, .. } => stdin.flush().ok(),
+                OutputStream::Stderr => None,
+            };
+        }
+    }
+
+    /// Close the file or command `name`, so that using it again opens it
+    /// anew. Returns the exit status of a command, 0 for a file, and -1 if
+    /// nothing of that name is open.
+    pub fn close(&mut self, name: &str) -> i32 {
+        let mut result = -1;
+        if let Some(output) = self.outputs.remove(name) {
+            result = match output {
+                OutputStream::File(mut file) => file.flush().map_or(-1, |_| 0),
+                OutputStream::Pipe { mut child, stdin } => {
+                    // the command sees the end of its input once stdin is dropped
+                    self.stdout.flush().ok();
+                    let flushed = stdin.into_inner().is_ok();
+                    match child.wait() {
+                        Ok(status) if flushed => status_value(status),
+                        _ => -1,
+                    }
+                }
+                OutputStream::Stderr => 0,
+            };
+        }
+        if let Some(input) = self.inputs.remove(name) {
+            result = match input {
+                InputStream::File(_) => 0,
+                InputStream::Command { mut child, reader } => {
+                    drop(reader);
+                    child.wait().map_or(-1, status_value)
+                }
+            };
+        }
+        result
+    }
+
+    /// Run `command` with the shell, and return its exit status.
+    pub fn system(&mut self, command: &str) -> i32 {
+        self.flush();
+        spawn_shell(command).status().map_or(-1, status_value)
+    }
+
+    /// Close everything, waiting for the commands to finish.
+    pub fn close_all(&mut self) {
+        self.flush();
+        let names: Vec<String> = self
+            .outputs
+            .keys()
+            .chain(self.inputs.keys())
+            .cloned()
+            .collect();
+        for name in names {
+            self.close(&name);
+        }
+        self.stdout.flush().ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_all(input: &'static str, separator: RecordSeparator) -> Vec<String> {
+        let mut reader = RecordReader::new(input.as_bytes());
+        let mut records = Vec::new();
+        while let Some(record) = reader.read_record(&separator).unwrap() {
+            records.push(record);
+        }
+        records
+    }
+
+    #[test]
+    fn test_read_records() {
+        assert_eq!(
+            read_all("a\nb\n\nc", RecordSeparator::Char(b'\n')),
+            vec!["a", "b", "", "c"]
+        );
+        assert_eq!(
+            read_all("a;b;", RecordSeparator::Char(b';')),
+            vec!["a", "b"]
+        );
+    }
+
+    #[test]
+    fn test_read_paragraphs() {
+        assert_eq!(
+            read_all("\n\na\nb\n\n\n\nc d\n\n", RecordSeparator::Paragraph),
+            vec!["a\nb", "c d"]
+        );
+    }
+
+    #[test]
+    fn test_read_regex_records() {
+        let separator = RecordSeparator::Regex(Rc::new(Regex::new("[0-9]+").unwrap()));
+        assert_eq!(
+            read_all("a12b\n3\n4c", separator),
+            vec!["a", "b\n", "\n", "c"]
+        );
+        let separator = RecordSeparator::Regex(Rc::new(Regex::new("\n\n+").unwrap()));
+        assert_eq!(read_all("a\n\n\nb\n", separator), vec!["a", "b\n"]);
+    }
+}
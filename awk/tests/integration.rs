@@ -0,0 +1,716 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+// Run awk in `dir`, feeding `stdin` to it
+fn awk(dir: &str, args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_awk"))
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn check(output: &Output, expected_out: &str, expected_err: &str, code: i32) {
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected_out);
+    assert_eq!(String::from_utf8_lossy(&output.stderr), expected_err);
+    assert_eq!(output.status.code(), Some(code));
+}
+
+#[test]
+fn test_awk_rules() {
+    let dir = &format!("{}/test_awk_rules", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    fs::write(format!("{dir}/input"), "a b\nb c d\nc\n").unwrap();
+    let program = r#"
+        BEGIN { print "start" }
+        /b/ { print NR, NF, $2 }
+        $1 == "c" { print FILENAME ":" FNR; next }
+        { print "last rule", $0 }
+        END { print "end", NR }
+    "#;
+    let output = awk(dir, &[program, "input"], "");
+    check(
+        &output,
+        "start\n1 2 b\nlast rule a b\n2 3 c\nlast rule b c d\ninput:3\nend 3\n",
+        "",
+        0,
+    );
+
+    let output = awk(dir, &["-F:", "{ print $2 }"], "a:b\nc:d\n");
+    check(&output, "b\nd\n", "", 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_awk_print_redirection() {
+    let dir = &format!("{}/test_awk_print_redirection", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    let program = r#"BEGIN {
+        print "a" > "out"
+        print "b" > "out"
+        print "c" >> "log"
+        print "z\ny" | "sort"
+    }"#;
+    fs::write(format!("{dir}/log"), "0\n").unwrap();
+    let output = awk(dir, &[program], "");
+    check(&output, "y\nz\n", "", 0);
+    assert_eq!(fs::read_to_string(format!("{dir}/out")).unwrap(), "a\nb\n");
+    assert_eq!(fs::read_to_string(format!("{dir}/log")).unwrap(), "0\nc\n");
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_awk_getline() {
+    let dir = &format!("{}/test_awk_getline", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    fs::write(format!("{dir}/file"), "1\n2\n").unwrap();
+    let program = r#"{
+        getline line < "file"
+        "echo cmd" | getline cmd
+        getline
+        print $0, line, cmd, NR
+    }
+    END { print (getline line < "missing") }"#;
+    let output = awk(dir, &[program], "a\nb\nc\n");
+    check(&output, "b 1 cmd 3\nc 2 cmd 4\n-1\n", "", 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_awk_close() {
+    let dir = &format!("{}/test_awk_close", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    let program = r#"BEGIN {
+        print "a" > "file"
+        print close("file")
+        print "b" > "file"
+        close("file")
+        while ((getline line < "file") > 0)
+            print "read", line
+        print close("file")
+
+        "echo x; exit 3" | getline x
+        print x, close("echo x; exit 3")
+        "echo x; exit 3" | getline y
+        print y
+
+        print "to pipe" | "cat; exit 5"
+        print close("cat; exit 5")
+        print close("not open")
+    }"#;
+    let output = awk(dir, &[program], "");
+    check(&output, "0\nread b\n0\nx 3\nx\nto pipe\n5\n-1\n", "", 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_awk_printf() {
+    let dir = &format!("{}/test_awk_printf", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    let program = r#"{
+        printf "%-3s|%5.1f|%c%c\n", $1, $2, $3, $2 + 60
+        printf("%s\n", sprintf("%03d", NR)) > "out"
+    }"#;
+    let output = awk(
+        dir,
+        &[program],
+        "a 5 x
+bcde 10.25 yz
+",
+    );
+    check(
+        &output,
+        "a  |  5.0|xA
+bcde| 10.2|yF
+",
+        "",
+        0,
+    );
+    assert_eq!(
+        fs::read_to_string(format!("{dir}/out")).unwrap(),
+        "001\n002\n"
+    );
+
+    let output = awk(dir, &[r#"BEGIN { printf "%z" }"#], "");
+    check(
+        &output,
+        "",
+        "awk: line 1: invalid format specification '%z'\n",
+        2,
+    );
+
+    let output = awk(dir, &[r#"BEGIN { printf "%d %d", 1 }"#], "");
+    check(
+        &output,
+        "",
+        "awk: line 1: not enough arguments for format '%d %d'\n",
+        2,
+    );
+
+    // strings are converted to numbers as decimal numbers only
+    let output = awk(
+        dir,
+        &[r#"BEGIN { printf "%d %d %g\n", "0x1A", " 12ab", "1e2" }"#],
+        "",
+    );
+    check(&output, "0 12 100\n", "", 0);
+
+    // comparisons other than > can be printed without parentheses
+    let program = r#"BEGIN { print 1==1.0, 2<1, 3>=3, 1!=1 > "cmp"; close("cmp") }"#;
+    let output = awk(dir, &[program], "");
+    check(&output, "", "", 0);
+    assert_eq!(
+        fs::read_to_string(format!("{dir}/cmp")).unwrap(),
+        "1 0 1 0\n"
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_awk_record_separators() {
+    let dir = &format!("{}/test_awk_record_separators", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    let program = r#"BEGIN { RS = ""; FS = ":" } { print NR, NF, $1 "|" $2 }"#;
+    let output = awk(dir, &[program], "\n\na b:c\nd\n\n\ne\n");
+    check(&output, "1 3 a b|c\n2 1 e|\n", "", 0);
+
+    let program = r#"BEGIN { RS = ";+|--" } { print NR ": " $0 }"#;
+    let output = awk(dir, &[program], "a;;b--c;");
+    check(&output, "1: a\n2: b\n3: c\n", "", 0);
+
+    let program = r#"BEGIN { RS = ";" } { print NR ": " $0 }"#;
+    let output = awk(dir, &[program], "a;b\nc;");
+    check(&output, "1: a\n2: b\nc\n", "", 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_awk_split() {
+    let dir = &format!("{}/test_awk_split", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    let program = r#"{
+        n = split($0, words)
+        m = split($0, parts, /[0-9]+/)
+        k = split($0, chars, "-")
+        print n, words[1], m, parts[2], k, chars[2], (3 in chars)
+    }"#;
+    let output = awk(dir, &[program], "a-1b 22-c\n");
+    check(&output, "2 a-1b 3 b  3 1b 22 1\n", "", 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_awk_sub_gsub() {
+    let dir = &format!("{}/test_awk_sub_gsub", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    let program = r#"{
+        n = gsub(/o/, "<&>")
+        print n, $0, NF, $2
+        sub("l+", "\\&", $1)
+        x = "aaa"
+        print $1, gsub(/a/, "b", x), x, sub(/z/, "", x)
+    }"#;
+    let output = awk(dir, &[program], "hello world\n");
+    check(
+        &output,
+        "2 hell<o> w<o>rld 2 w<o>rld\nhe&<o> 3 bbb 0\n",
+        "",
+        0,
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_awk_dynamic_regex() {
+    let dir = &format!("{}/test_awk_dynamic_regex", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    let program = r##"BEGIN { digits = "[0-9]+" }
+    $0 ~ digits { print "number", match($0, digits), RLENGTH }
+    $1 !~ "^" $2 { n = split($0, parts, "[" $2 "]"); print n, parts[1] }
+    { gsub(digits "$", "#"); print }"##;
+    let output = awk(dir, &[program], "ab12 b\nx a\nc 3\n");
+    check(
+        &output,
+        "number 3 2\n3 a\nab12 b\n2 x \nx a\nnumber 3 1\n2 c \nc #\n",
+        "",
+        0,
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_awk_arrays_and_loops() {
+    let dir = &format!("{}/test_awk_arrays_and_loops", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    let program = r#"
+    { count[$1]++ }
+    END {
+        for (word in count)
+            print word, count[word] | "sort"
+        close("sort")
+        delete count["b"]
+        for (word in count) {
+            if (word == "c")
+                continue
+            n++
+            delete count
+        }
+        print n, ("a" in count)
+        while (1) {
+            if (++i == 3)
+                break
+        }
+        print i
+    }"#;
+    let output = awk(dir, &[program], "b\na\nb\nc\n");
+    check(&output, "a 1\nb 2\nc 1\n1 0\n3\n", "", 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_awk_multidimensional_arrays() {
+    let dir = &format!(
+        "{}/test_awk_multidimensional_arrays",
+        env!("CARGO_TARGET_TMPDIR")
+    );
+    fs::create_dir(dir).unwrap();
+    let program = r#"
+    { for (i = 1; i <= NF; i++) grid[NR, i] = $i }
+    END {
+        print grid[2, 1], ((1, 3) in grid), ((3, 1) in grid)
+        delete grid[1, 1]
+        for (key in grid) {
+            split(key, index_, SUBSEP)
+            if (index_[1] == 1)
+                print index_[2], grid[key] | "sort"
+        }
+        close("sort")
+        SUBSEP = ":"
+        grid["a", "b"]
+        print (("a:b") in grid)
+    }"#;
+    let output = awk(dir, &[program], "a b c\nd e\n");
+    check(&output, "d 1 0\n2 b\n3 c\n1\n", "", 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_awk_argv() {
+    let dir = &format!("{}/test_awk_argv", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    fs::write(format!("{dir}/a"), "a1\na2\n").unwrap();
+    fs::write(format!("{dir}/b"), "b1\n").unwrap();
+    let program = r#"
+    BEGIN { print "[" FILENAME "]", ARGC, ARGV[0] }
+    { print FILENAME, FNR, NR, x, $0 }
+    END { print x }"#;
+    let output = awk(
+        dir,
+        &[program, "x=1", "a", "x=2", "-", "", "b", "x=3"],
+        "in\n",
+    );
+    check(
+        &output,
+        "[] 8 awk\na 1 1 1 a1\na 2 2 1 a2\n- 1 3 2 in\nb 1 4 2 b1\n3\n",
+        "",
+        0,
+    );
+
+    let program = r#"
+    BEGIN { ARGV[1] = "b"; delete ARGV[2]; ARGV[ARGC++] = "a" }
+    { print FILENAME, $0 }"#;
+    let output = awk(dir, &[program, "missing", "missing"], "");
+    check(&output, "b b1\na a1\na a2\n", "", 0);
+
+    let output = awk(dir, &["{ print x, $0 }", "x=1"], "in\n");
+    check(&output, "1 in\n", "", 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_awk_assignments() {
+    let dir = &format!("{}/test_awk_assignments", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    let program = r#"
+    BEGIN { printf "%s|%s|", x, y }
+    { print x, y }"#;
+    let output = awk(
+        dir,
+        &["-v", r"x=a\tb", "-v", "y=1", program, r"x=\101\\"],
+        "in\n",
+    );
+    check(&output, "a\tb|1|A\\ 1\n", "", 0);
+
+    let output = awk(dir, &["-v", "1x=2", "BEGIN {}"], "");
+    check(&output, "", "awk: invalid variable assignment '1x=2'\n", 2);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_awk_program_files() {
+    let dir = &format!("{}/test_awk_program_files", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    fs::write(
+        format!("{dir}/lib.awk"),
+        "function double(x) {\n  return 2 * x\n}",
+    )
+    .unwrap();
+    fs::write(format!("{dir}/main.awk"), "{ print double($1) }\n").unwrap();
+    fs::write(format!("{dir}/bad.awk"), "BEGIN {\n  x = = 1\n}\n").unwrap();
+    let output = awk(dir, &["-f", "lib.awk", "-f", "main.awk"], "1\n21\n");
+    check(&output, "2\n42\n", "", 0);
+
+    let output = awk(dir, &["-f", "lib.awk", "-f", "bad.awk"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("bad.awk:2:7"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_awk_rand() {
+    let dir = &format!("{}/test_awk_rand", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    let program = r#"BEGIN {
+        print srand(5), srand(), (srand(7) > 1000)
+        x = rand()
+        srand(7)
+        print (x == rand())
+        for (i = 0; i < 1000; i++) {
+            r = rand()
+            if (r < 0)
+                out_of_range++
+            if (r >= 1)
+                out_of_range++
+        }
+        print out_of_range + 0
+    }"#;
+    let output = awk(dir, &[program], "");
+    check(&output, "0 5 1\n1\n0\n", "", 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_awk_comparisons() {
+    let dir = &format!("{}/test_awk_comparisons", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    let program = r#"{
+        print ($1 == $2), ($1 == "10"), ($1 < $3), ($4 ? "true" : "false")
+        print (x == 0), (x == ""), ("10" < "9"), (10 < 9)
+        print (0 || 0), (0 || "a"), (2 && 1), (1 && 0)
+    }"#;
+    let output = awk(dir, &[program], "10 10.0 abc 0.0\n");
+    check(&output, "1 1 1 false\n1 1 1 0\n0 1 1 0\n", "", 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_awk_field_assignment() {
+    let dir = &format!("{}/test_awk_field_assignment", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    let program = r#"
+    BEGIN { OFS = "-" }
+    {
+        $5 = "e"
+        print $0, NF
+        NF = 2
+        print $0, $3 "|"
+        $1 = $1; $2++
+        print
+        $0 = "x  y"
+        print $2, NF
+    }"#;
+    let output = awk(dir, &[program], "a 1 c\n");
+    check(&output, "a-1-c--e-5\na-1-|\na-2\ny-2\n", "", 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_awk_exit() {
+    let dir = &format!("{}/test_awk_exit", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    let program = r#"
+    { print "first"; exit 4; print "skipped" }
+    { print "second" }
+    END { print "end", NR }"#;
+    let output = awk(dir, &[program], "a\nb\n");
+    check(&output, "first\nend 1\n", "", 4);
+
+    let program = r#"
+    BEGIN { exit 3 }
+    { print "skipped" }
+    END { print "end"; exit; print "skipped" }"#;
+    let output = awk(dir, &[program], "a\n");
+    check(&output, "end\n", "", 3);
+
+    let program = "function f() { exit 5 } { f() } END { exit 6 }";
+    let output = awk(dir, &[program], "a\n");
+    check(&output, "", "", 6);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_awk_functions() {
+    let dir = &format!("{}/test_awk_functions", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    let program = r#"
+    function fill(a, n,   i) { for (i = 1; i <= n; i++) a[i] = i * i }
+    function sum(a, n,   i, s) { for (i = 1; i <= n; i++) s = s + a[i]; return s }
+    function inc(x) { x++; return x }
+    function fact(n) { if (n <= 1) return 1; return n * fact(n - 1) }
+    function outer(   local) { inner(local); return local[1] }
+    function inner(array) { array[1] = "set" }
+    function defaults(a, b) { return a == "" && b == 0 }
+    function change(f) { f = "changed"; return f }
+    BEGIN {
+        fill(squares, 4); print sum(squares, 4)
+        y = 3; print inc(y), y
+        print fact(10), later()
+        print outer(), (defaults())
+    }
+    { print change($1), $1 }
+    function later() { return "defined later" }"#;
+    let output = awk(dir, &[program], "one two\n");
+    check(
+        &output,
+        "30\n4 3\n3628800 defined later\nset 1\nchanged one\n",
+        "",
+        0,
+    );
+
+    let program = "function f(a) { a[1] = 1 } BEGIN { s = 1; f(s) }";
+    let output = awk(dir, &[program], "");
+    check(
+        &output,
+        "",
+        "awk: line 1: in function f: scalar used in array context\n",
+        2,
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_awk_dump() {
+    let dir = &format!("{}/test_awk_dump", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    let program = r#"function twice(x) { return 2 * x }
+BEGIN { while (i < 2) print twice(i++) }
+$1 ~ /a/"#;
+    let output = awk(dir, &["--dump", program], "");
+    let expected = concat!(
+        "constants:\n",
+        "       0: Number(2.0)\n",
+        "       1: Number(2.0)\n",
+        "       2: Number(1.0)\n",
+        "       3: Regex(/a/)\n",
+        "       4: Number(0.0)\n",
+        "globals:\n",
+        "      16: i\n",
+        "BEGIN:\n",
+        "  line 2:\n",
+        "       0: VarRef(16)                       i\n",
+        "       1: PushConstant(1)                  2\n",
+        "       2: Lt\n",
+        "       3: JumpIfFalse(6)                   -> 9\n",
+        "       4: VarRef(16)                       i\n",
+        "       5: PostInc\n",
+        "       6: Call { id: 0, argc: 1 }          twice\n",
+        "       7: Print { argc: 1, output: Stdout }\n",
+        "       8: Jump(-8)                         -> 0\n",
+        "rule 1 (line 3):\n",
+        "  pattern:\n",
+        "       0: PushConstant(2)                  1\n",
+        "       1: FieldRef\n",
+        "       2: PushConstant(3)                  /a/\n",
+        "       3: Match\n",
+        "  action:\n",
+        "  line 3:\n",
+        "       0: PushConstant(4)                  0\n",
+        "       1: FieldRef\n",
+        "       2: Print { argc: 1, output: Stdout }\n",
+        "function twice (parameters: 1):\n",
+        "  line 1:\n",
+        "       0: PushConstant(0)                  2\n",
+        "       1: LocalVarRef(0)\n",
+        "       2: Mul\n",
+        "       3: Return\n",
+    );
+    check(&output, expected, "", 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_awk_lint() {
+    let dir = &format!("{}/test_awk_lint", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    let program = r#"function f(a) { return a }
+BEGIN { FS = ":"; ENVIRON["HOME"] = "/" }
+{ print f($1, $2), unset }"#;
+    let output = awk(dir, &["--lint", program], "a:b\n");
+    check(
+        &output,
+        "a \n",
+        concat!(
+            "awk: warning: line 2: assigning to ENVIRON does not change the environment of commands\n",
+            "awk: warning: line 3: function 'f' called with more arguments than parameters\n",
+            "awk: warning: variable 'unset' used before it is set\n",
+        ),
+        0,
+    );
+
+    let output = awk(dir, &[program], "a:b\n");
+    check(&output, "a \n", "", 0);
+
+    // unknown escape sequences stand for the character after the backslash
+    let program = r#"END { NR = 1; print "\q" }"#;
+    let output = awk(dir, &["--lint", program], "");
+    check(
+        &output,
+        "q\n",
+        concat!(
+            "awk: warning: line 1: assigning NR in END does not change the number of records read\n",
+            "awk: warning: line 1: non-portable escape sequence '\\q' in string\n",
+        ),
+        0,
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_awk_number_formats() {
+    let dir = &format!("{}/test_awk_number_formats", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    let output = awk(
+        dir,
+        &[r#"BEGIN { OFMT = "%.2f"; x = 3.14159; print x }"#],
+        "",
+    );
+    check(&output, "3.14\n", "", 0);
+
+    let program =
+        r#"BEGIN { CONVFMT = "%d"; x = 3.14159; print (x ""); a[x] = 1; for (k in a) print k }"#;
+    let output = awk(dir, &[program], "");
+    check(&output, "3\n3\n", "", 0);
+
+    // integral values are not affected by either format
+    let program = r#"BEGIN { OFMT = CONVFMT = "%.2f"; x = 42; print x, (x "") }"#;
+    let output = awk(dir, &[program], "");
+    check(&output, "42 42\n", "", 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_awk_compound_assignment() {
+    let dir = &format!(
+        "{}/test_awk_compound_assignment",
+        env!("CARGO_TARGET_TMPDIR")
+    );
+    fs::create_dir(dir).unwrap();
+    let program = r#"
+        BEGIN { x += 1; x *= 5; a["k"] += 3; a["k"] ^= 2; n = 7; n %= 4; n /= 2; print x, a["k"], n }
+        { $2 += 1; print; print NF }
+        function f(arr, k) { arr[k] -= 10; return arr[k] }
+        END { print f(a, "k"), a["k"]; i = 2; a[i++] += 1; print i, a[2] }
+    "#;
+    let output = awk(dir, &[program], "1 2 3\n");
+    check(&output, "5 9 1.5\n1 3 3\n3\n-1 -1\n3 1\n", "", 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_awk_unary_plus() {
+    let dir = &format!("{}/test_awk_unary_plus", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    let program = r#"BEGIN { x = "3"; y = +x; print +x, y + 1, +"2.50abc", -+"4", +z }"#;
+    let output = awk(dir, &[program], "");
+    check(&output, "3 4 2.5 -4 0\n", "", 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_awk_errors() {
+    let dir = &format!("{}/test_awk_errors", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    let output = awk(dir, &["BEGIN { x = 0; print 1 / x }"], "");
+    check(&output, "", "awk: line 1: division by zero\n", 2);
+
+    let program = r#"
+    function ratio(a, b) {
+        return a / b
+    }
+    { print ratio($1, $2) }
+    $1 % $2 { print }"#;
+    let output = awk(dir, &[program], "4 2\n1 0\n");
+    check(
+        &output,
+        "2\n",
+        "awk: line 3: in function ratio: division by zero\n",
+        2,
+    );
+
+    let output = awk(dir, &["NR == 2, $1 % $2 { print }"], "4 2\n1 0\n");
+    check(&output, "", "awk: line 1: division by zero\n", 2);
+
+    fs::write(format!("{dir}/first.awk"), "BEGIN {\n  x = 1\n}\n").unwrap();
+    fs::write(
+        format!("{dir}/second.awk"),
+        "END {\n  a[1] = 1\n  a = 2\n}\n",
+    )
+    .unwrap();
+    let output = awk(dir, &["-f", "first.awk", "-f", "second.awk"], "");
+    check(
+        &output,
+        "",
+        "awk: second.awk:3: array used in scalar context\n",
+        2,
+    );
+
+    let output = awk(dir, &["{ print }", "missing"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("awk: can't open file missing"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -8,6 +8,7 @@
 //
 
 use std::ffi::OsString;
+use std::io::{self, BufRead, IsTerminal};
 
 use bc_util::{
     interpreter::{ExecutionResult, Interpreter},
@@ -38,42 +39,51 @@ fn print_output_or_error(result: ExecutionResult<String>) {
         }
         Err(e) => {
             print!("{}", e.partial_output());
-            println!("{}", e);
+            eprintln!("{}", e);
         }
     }
 }
 
-fn main() -> Result<()> {
-    textdomain(PROJECT_NAME)?;
-    bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
-
-    let args = Args::parse();
-    let mut interpreter = Interpreter::default();
+/// Appends `line` to the statements read so far and executes them once
+/// they form a complete program.
+fn exec_line(interpreter: &mut Interpreter, line_buffer: &mut String, line: &str) {
+    line_buffer.push_str(line);
+    line_buffer.push('\n');
+    match parse_program(line_buffer, None) {
+        Ok(program) => {
+            print_output_or_error(interpreter.exec(program));
+            line_buffer.clear();
+        }
+        Err(e) if !e.is_incomplete => {
+            eprintln!("{}", e);
+            line_buffer.clear();
+        }
+        _ => {}
+    }
+}
 
-    if args.define_math_functions {
-        let lib = parse_program(include_str!("bc_util/math_functions.bc"), None)
-            .expect("error parsing standard math functions");
-        interpreter
-            .exec(lib)
-            .expect("error loading standard math functions");
+/// Reports a program left incomplete at the end of the input.
+fn finish_input(line_buffer: &str) {
+    if let Err(e) = parse_program(line_buffer, None) {
+        eprintln!("{}", e);
     }
+}
 
-    for file in args.files {
-        match std::fs::read_to_string(&file) {
-            Ok(s) => match parse_program(&s, file.to_str()) {
-                Ok(program) => print_output_or_error(interpreter.exec(program)),
-                Err(e) => println!("{}", e),
-            },
-            Err(_) => {
-                eprintln!("Could not read file: {}", file.to_string_lossy());
-                return Ok(());
-            }
-        };
+fn exec_stdin(interpreter: &mut Interpreter) -> io::Result<()> {
+    let mut line_buffer = String::new();
+    for line in io::stdin().lock().lines() {
+        exec_line(interpreter, &mut line_buffer, &line?);
         if interpreter.has_quit() {
             return Ok(());
         }
     }
+    if !line_buffer.is_empty() {
+        finish_input(&line_buffer);
+    }
+    Ok(())
+}
 
+fn exec_interactive(interpreter: &mut Interpreter) -> Result<()> {
     let mut repl = DefaultEditor::new()?;
     let mut line_buffer = String::new();
     while !interpreter.has_quit() {
@@ -84,34 +94,61 @@ fn main() -> Result<()> {
         };
         match line {
             Ok(line) => {
-                line_buffer.push_str(&line);
-                line_buffer.push('\n');
-                match parse_program(&line_buffer, None) {
-                    Ok(program) => {
-                        print_output_or_error(interpreter.exec(program));
-                        line_buffer.clear();
-                    }
-                    Err(e) if !e.is_incomplete => {
-                        println!("{}", e);
-                        line_buffer.clear();
-                    }
-                    _ => {}
-                }
+                exec_line(interpreter, &mut line_buffer, &line);
                 repl.add_history_entry(line)?;
             }
             Err(ReadlineError::Eof) => {
-                println!("CTRL-D");
+                if !line_buffer.is_empty() {
+                    finish_input(&line_buffer);
+                }
                 break;
             }
             Err(ReadlineError::Interrupted) => {
-                println!("CTRL-C");
                 break;
             }
             Err(e) => {
-                eprintln!("Error: {:?}", e);
+                eprintln!("bc: {}", e);
                 break;
             }
         }
     }
     Ok(())
 }
+
+fn main() -> Result<()> {
+    textdomain(PROJECT_NAME)?;
+    bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
+
+    let args = Args::parse();
+    let mut interpreter = Interpreter::default();
+
+    if args.define_math_functions {
+        let lib = parse_program(include_str!("bc_util/math_functions.bc"), None)
+            .expect("error parsing standard math functions");
+        interpreter
+            .exec(lib)
+            .expect("error loading standard math functions");
+    }
+
+    for file in args.files {
+        match std::fs::read_to_string(&file) {
+            Ok(s) => match parse_program(&s, file.to_str()) {
+                Ok(program) => print_output_or_error(interpreter.exec(program)),
+                Err(e) => eprintln!("{}", e),
+            },
+            Err(e) => {
+                eprintln!("bc: {}: {}", file.to_string_lossy(), e);
+                std::process::exit(1);
+            }
+        };
+        if interpreter.has_quit() {
+            return Ok(());
+        }
+    }
+
+    if io::stdin().is_terminal() {
+        exec_interactive(&mut interpreter)
+    } else {
+        exec_stdin(&mut interpreter).map_err(ReadlineError::Io)
+    }
+}
@@ -7,7 +7,7 @@
 // SPDX-License-Identifier: MIT
 //
 
-use std::{fmt::Write, rc::Rc};
+use std::rc::Rc;
 
 use crate::bc_util::instructions::Variable;
 
@@ -104,6 +104,10 @@ pub type ExecutionResult<T> = Result<T, ExecutionError>;
 
 type NameMap<T> = [T; 26];
 
+/// Maximum number of characters on an output line, including the
+/// backslash that ends a continued line.
+const LINE_LENGTH: usize = 70;
+
 fn name_index(name: char) -> usize {
     (name as u8 - b'a') as usize
 }
@@ -113,6 +117,7 @@ fn contains_quit(stmt: &StmtInstruction) -> bool {
         StmtInstruction::Quit => true,
         StmtInstruction::If { body, .. } => body.iter().any(contains_quit),
         StmtInstruction::While { body, .. } => body.iter().any(contains_quit),
+        StmtInstruction::For { body, .. } => body.iter().any(contains_quit),
         _ => false,
     }
 }
@@ -126,6 +131,20 @@ fn should_print(expr: &ExprInstruction) -> bool {
     )
 }
 
+/// Writes `number` followed by a newline, splitting it across lines
+/// ending in a backslash so that no line exceeds `LINE_LENGTH` characters.
+fn write_number(output: &mut String, number: &str) {
+    let mut rest = number;
+    while rest.len() >= LINE_LENGTH {
+        let (line, remainder) = rest.split_at(LINE_LENGTH - 1);
+        output.push_str(line);
+        output.push_str("\\\n");
+        rest = remainder;
+    }
+    output.push_str(rest);
+    output.push('\n');
+}
+
 fn get_or_extend(array: &mut Vec<Number>, index: usize) -> &mut Number {
     if index >= array.len() {
         array.resize_with(index + 1, Number::zero);
@@ -506,9 +525,7 @@ impl Interpreter {
             StmtInstruction::Expr(expr) => {
                 let value = self.eval_expr(expr)?;
                 if should_print(expr) {
-                    // this should never fail
-                    writeln!(&mut self.output, "{}", value.to_string(self.obase))
-                        .expect("error appending to string");
+                    write_number(&mut self.output, &value.to_string(self.obase));
                 }
             }
             StmtInstruction::DefineFunction { .. } => {
@@ -9,377 +9,303 @@
 
 extern crate plib;
 
-use gettextrs::{bind_textdomain_codeset, textdomain};
+use gettextrs::{bind_textdomain_codeset, gettext, textdomain};
+use plib::regex::Regex;
 use plib::PROJECT_NAME;
-use regex::Regex;
-
-#[derive(Clone, Debug, PartialEq)]
-enum Token {
-    LParen,
-    RParen,
-    OpMul,
-    OpDiv,
-    OpRem,
-    OpAdd,
-    OpSub,
-    OpEq,
-    OpGT,
-    OpLT,
-    OpGE,
-    OpLE,
-    OpNE,
-    OpAnd,
-    OpOr,
-    OpMatch,
-    Integer(i64),
-    Str(String),
-}
-
-// comparison operators
-#[derive(Clone, Debug)]
-enum CmpOp {
-    EQ,
-    NE,
-    GT,
-    LT,
-    GE,
-    LE,
-}
-
-// integer operations
-#[derive(Clone, Debug)]
-enum IntOp {
+use std::cmp::Ordering;
+use std::fmt;
+
+// binary operators
+#[derive(Clone, Copy, Debug)]
+enum Op {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
     Add,
     Sub,
     Mul,
     Div,
     Rem,
+    Match,
 }
 
-// convert an lval to a string
-fn token_display(t: &Token) -> String {
-    match t {
-        Token::Integer(val) => val.to_string(),
-        Token::Str(val) => String::from(val),
-        _ => {
-            panic!("BUG: not an lval");
-        }
+impl Op {
+    fn from_arg(s: &str) -> Option<Op> {
+        let op = match s {
+            "|" => Op::Or,
+            "&" => Op::And,
+            "=" => Op::Eq,
+            "!=" => Op::Ne,
+            ">" => Op::Gt,
+            ">=" => Op::Ge,
+            "<" => Op::Lt,
+            "<=" => Op::Le,
+            "+" => Op::Add,
+            "-" => Op::Sub,
+            "*" => Op::Mul,
+            "/" => Op::Div,
+            "%" => Op::Rem,
+            ":" => Op::Match,
+            _ => return None,
+        };
+        Some(op)
     }
-}
 
-// is token an lval?
-fn token_is_lval(t: &Token) -> bool {
-    match t {
-        Token::Integer(_) => true,
-        Token::Str(_) => true,
-        _ => false,
+    // operators of higher precedence bind more tightly;
+    // all operators are left-associative
+    fn precedence(self) -> u8 {
+        match self {
+            Op::Or => 0,
+            Op::And => 1,
+            Op::Eq | Op::Ne | Op::Gt | Op::Ge | Op::Lt | Op::Le => 2,
+            Op::Add | Op::Sub => 3,
+            Op::Mul | Op::Div | Op::Rem => 4,
+            Op::Match => 5,
+        }
     }
 }
 
-// is token zero?
-fn token_is_zero(t: &Token) -> bool {
-    match t {
-        Token::Integer(val) => *val == 0,
-        Token::Str(s) => s.is_empty(),
-        _ => false,
-    }
+#[derive(Debug)]
+enum Expr {
+    Operand(String),
+    Binary(Box<Expr>, Op, Box<Expr>),
 }
 
-// convert token to string
-fn token_to_string(t: &Token) -> Result<String, &'static str> {
-    match t {
-        Token::Integer(val) => Ok(val.to_string()),
-        Token::Str(val) => Ok(String::from(val)),
-        _ => Err("syntax error: not a string"),
-    }
-}
+#[derive(Debug)]
+enum ExprError {
+    /// The expression is invalid (exit status 2).
+    Invalid(String),
 
-// convert token to integer
-fn token_to_int(t: &Token) -> Option<i64> {
-    match t {
-        Token::Integer(val) => Some(*val),
-        _ => None,
-    }
+    /// The expression could not be evaluated (exit status 3).
+    Failed(String),
 }
 
-// convert token to integer, returning an error if not an integer
-fn token_to_int_req(t: &Token) -> Result<i64, &'static str> {
-    match token_to_int(t) {
-        Some(val) => Ok(val),
-        None => Err("syntax error: not an integer"),
+impl ExprError {
+    fn exit_code(&self) -> i32 {
+        match self {
+            ExprError::Invalid(_) => 2,
+            ExprError::Failed(_) => 3,
+        }
     }
 }
 
-// parse a single token
-fn parse_token(s: &str) -> Token {
-    match s {
-        "(" => Token::LParen,
-        ")" => Token::RParen,
-        "*" => Token::OpMul,
-        "/" => Token::OpDiv,
-        "%" => Token::OpRem,
-        "+" => Token::OpAdd,
-        "-" => Token::OpSub,
-        "=" => Token::OpEq,
-        ">" => Token::OpGT,
-        "<" => Token::OpLT,
-        ">=" => Token::OpGE,
-        "<=" => Token::OpLE,
-        "!=" => Token::OpNE,
-        "&" => Token::OpAnd,
-        "|" => Token::OpOr,
-        ":" => Token::OpMatch,
-        _ => match s.parse::<i64>() {
-            Ok(n) => Token::Integer(n),
-            Err(_) => Token::Str(String::from(s)),
-        },
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExprError::Invalid(msg) | ExprError::Failed(msg) => write!(f, "{}", msg),
+        }
     }
 }
 
-// tokenize the command line arguments, all in a single pass
-fn tokenize() -> Vec<Token> {
-    // collect program's command line args
-    let mut args: Vec<String> = std::env::args().collect();
-    args.remove(0); // remove 1st value, the unnecessary program name
-
-    // parse each arg into a Token
-    let mut tokens = Vec::new();
-    for arg in &args {
-        tokens.push(parse_token(arg));
+// the value of an integer argument: an optional minus sign followed
+// by decimal digits; other strings are not integers
+fn integer(s: &str) -> Option<i64> {
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
     }
-
-    tokens
+    s.parse().ok()
 }
 
-// compare two integers
-fn cmpint(lhs: i64, rhs: i64, op: CmpOp) -> Token {
-    let result: bool = match op {
-        CmpOp::EQ => lhs == rhs,
-        CmpOp::NE => lhs != rhs,
-        CmpOp::GT => lhs > rhs,
-        CmpOp::LT => lhs < rhs,
-        CmpOp::GE => lhs >= rhs,
-        CmpOp::LE => lhs <= rhs,
-    };
-
-    Token::Integer(result as i64)
+fn is_null_or_zero(s: &str) -> bool {
+    s.is_empty() || integer(s) == Some(0)
 }
 
-// compare two strings
-fn cmpstr(lhs: &Token, rhs: &Token, op: CmpOp) -> Result<Token, &'static str> {
-    let lhs = token_to_string(lhs)?;
-    let rhs = token_to_string(rhs)?;
-
-    let result: bool = match op {
-        CmpOp::EQ => lhs == rhs,
-        CmpOp::NE => lhs != rhs,
-        CmpOp::GT => lhs > rhs,
-        CmpOp::LT => lhs < rhs,
-        CmpOp::GE => lhs >= rhs,
-        CmpOp::LE => lhs <= rhs,
-    };
+fn bool_value(b: bool) -> String {
+    String::from(if b { "1" } else { "0" })
+}
 
-    Ok(Token::Integer(result as i64))
+struct Parser<'a> {
+    args: &'a [String],
+    pos: usize,
 }
 
-// perform a comparison operation
-fn cmpop(lhs: &Token, rhs: &Token, op: CmpOp) -> Result<Token, &'static str> {
-    let lhs_int = token_to_int(lhs);
-    let rhs_int = token_to_int(rhs);
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Option<&'a str> {
+        let arg = self.args.get(self.pos)?;
+        self.pos += 1;
+        Some(arg)
+    }
 
-    // if both are integers, perform int comparison
-    if lhs_int.is_some() && rhs_int.is_some() {
-        Ok(cmpint(lhs_int.unwrap(), rhs_int.unwrap(), op))
+    // an operand, or a parenthesized expression
+    fn parse_primary(&mut self) -> Result<Expr, ExprError> {
+        match self.next() {
+            None if self.pos == 0 => Err(ExprError::Invalid(gettext("missing operand"))),
+            None => Err(ExprError::Invalid(gettext!(
+                "syntax error: missing argument after '{}'",
+                self.args[self.pos - 1]
+            ))),
+            Some("(") => {
+                let expr = self.parse_expr(0)?;
+                match self.next() {
+                    Some(")") => Ok(expr),
+                    _ => Err(ExprError::Invalid(gettext(
+                        "syntax error: expecting ')' after '('",
+                    ))),
+                }
+            }
+            Some(s) => Ok(Expr::Operand(String::from(s))),
+        }
+    }
 
-    // otherwise, convert int to string, and perform string compare
-    } else if let Some(val) = lhs_int {
-        let tmp = Token::Str(val.to_string());
-        cmpstr(&tmp, rhs, op)
-    } else if let Some(val) = rhs_int {
-        let tmp = Token::Str(val.to_string());
-        cmpstr(lhs, &tmp, op)
-    } else {
-        cmpstr(lhs, rhs, op)
+    // an expression whose operators all have at least `min_precedence`
+    fn parse_expr(&mut self, min_precedence: u8) -> Result<Expr, ExprError> {
+        let mut lhs = self.parse_primary()?;
+        while let Some(op) = self.args.get(self.pos).and_then(|s| Op::from_arg(s)) {
+            if op.precedence() < min_precedence {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.parse_expr(op.precedence() + 1)?;
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(rhs));
+        }
+        Ok(lhs)
     }
 }
 
-// perform an integer math operation
-fn intop(lhs: &Token, rhs: &Token, op: IntOp) -> Result<Token, &'static str> {
-    let i1 = token_to_int_req(lhs)?;
-    let i2 = token_to_int_req(rhs)?;
-
-    match op {
-        IntOp::Add => Ok(Token::Integer(i1 + i2)),
-        IntOp::Sub => Ok(Token::Integer(i1 - i2)),
-        IntOp::Mul => Ok(Token::Integer(i1 * i2)),
-        IntOp::Div => Ok(Token::Integer(i1 / i2)),
-        IntOp::Rem => Ok(Token::Integer(i1 % i2)),
+fn parse(args: &[String]) -> Result<Expr, ExprError> {
+    let mut parser = Parser { args, pos: 0 };
+    let expr = parser.parse_expr(0)?;
+    match args.get(parser.pos) {
+        None => Ok(expr),
+        Some(arg) => Err(ExprError::Invalid(gettext!(
+            "syntax error: unexpected argument '{}'",
+            arg
+        ))),
     }
 }
 
-// logical and/or operation
-fn logop(lhs: &Token, rhs: &Token, is_and: bool) -> Token {
-    let lhs_zero = token_is_zero(lhs);
-    let rhs_zero = token_is_zero(rhs);
-
-    if is_and {
-        if !lhs_zero && !rhs_zero {
-            lhs.clone()
-        } else {
-            Token::Integer(0)
-        }
-    } else {
-        if !lhs_zero {
-            lhs.clone()
-        } else if !rhs_zero {
-            rhs.clone()
-        } else {
-            Token::Integer(0)
-        }
+// compare as integers if both are integers, as strings otherwise
+fn compare(lhs: &str, rhs: &str) -> Ordering {
+    match (integer(lhs), integer(rhs)) {
+        (Some(l), Some(r)) => l.cmp(&r),
+        _ => lhs.cmp(rhs),
     }
 }
 
-// regex match operation
-fn matchop(lhs: &Token, rhs: &Token) -> Result<Token, &'static str> {
-    let lhs = token_to_string(lhs)?;
-    let rhs = token_to_string(rhs)?;
-
-    let re = match Regex::new(&rhs) {
-        Ok(re_res) => re_res,
-        Err(_) => {
-            return Err("invalid regex");
-        }
+fn arithmetic(lhs: &str, op: Op, rhs: &str) -> Result<String, ExprError> {
+    let (Some(l), Some(r)) = (integer(lhs), integer(rhs)) else {
+        return Err(ExprError::Invalid(gettext("non-integer argument")));
     };
+    if r == 0 && matches!(op, Op::Div | Op::Rem) {
+        return Err(ExprError::Failed(gettext("division by zero")));
+    }
 
-    match re.captures(&lhs) {
-        // no regex match: zero
-        None => Ok(Token::Integer(0)),
-
-        // regex matched
-        Some(caps) => {
-            let cap1 = caps.get(1);
-
-            // if regex subexpression #1 matched, return as string
-            if let Some(mtch) = cap1 {
-                Ok(Token::Str(String::from(mtch.as_str())))
-
-            // otherwise, return length of overall match as int
-            } else {
-                Ok(Token::Integer(caps.get(0).unwrap().len() as i64))
-            }
-        }
+    let result = match op {
+        Op::Add => l.checked_add(r),
+        Op::Sub => l.checked_sub(r),
+        Op::Mul => l.checked_mul(r),
+        Op::Div => l.checked_div(r),
+        Op::Rem => l.checked_rem(r),
+        _ => unreachable!(),
+    };
+    match result {
+        Some(n) => Ok(n.to_string()),
+        None => Err(ExprError::Failed(gettext("integer overflow"))),
     }
 }
 
-// find closing right paren
-fn find_matching_paren(tokens: &[Token]) -> Option<usize> {
-    let mut depth = 0;
-    for (i, token) in tokens.iter().enumerate() {
-        match token {
-            Token::LParen => depth += 1,
-            Token::RParen => {
-                if depth == 0 {
-                    return None;
-                }
-                depth -= 1;
-                if depth == 0 {
-                    return Some(i);
-                }
-            }
-            _ => {}
-        }
+// match `s` against the basic regular expression `pattern`, anchored at
+// the start of `s`: the string matched by the first subexpression if the
+// pattern has one, otherwise the number of characters matched
+fn match_regex(s: &str, pattern: &str) -> Result<String, ExprError> {
+    let regex = Regex::new(pattern.as_bytes())
+        .map_err(|e| ExprError::Invalid(format!("{}: {}", pattern, e)))?;
+
+    // the leftmost match is at the start of `s` whenever an anchored one is
+    let groups = regex
+        .exec(s.as_bytes(), false)
+        .filter(|groups| matches!(groups[0], Some((0, _))));
+
+    let bytes = s.as_bytes();
+    if regex.groups() > 0 {
+        let group = groups.and_then(|groups| groups[1]);
+        Ok(group.map_or_else(String::new, |(start, end)| {
+            String::from_utf8_lossy(&bytes[start..end]).into_owned()
+        }))
+    } else {
+        let len = groups.and_then(|groups| groups[0]).map_or(0, |(_, end)| {
+            String::from_utf8_lossy(&bytes[..end]).chars().count()
+        });
+        Ok(len.to_string())
     }
-
-    None
 }
 
-// evaluate an expression
-fn eval_expression(tokens: &[Token]) -> Result<Token, &'static str> {
-    let mut tokens = tokens.to_vec();
+fn eval(expr: &Expr) -> Result<String, ExprError> {
+    let (lhs, op, rhs) = match expr {
+        Expr::Operand(s) => return Ok(s.clone()),
+        Expr::Binary(lhs, op, rhs) => (lhs, *op, rhs),
+    };
 
-    // continually consume tokens until only one remains
-    while tokens.len() >= 3 {
-        // handle nested expressions: left hand side
-        if tokens[0] == Token::LParen {
-            if let Some(i) = find_matching_paren(&tokens) {
-                let subexpr = &tokens[1..i];
-                let result = eval_expression(subexpr)?;
-                tokens.splice(0..=i, vec![result]);
-                continue;
-            } else {
-                return Err("syntax error EP0: unmatched left paren");
+    // the right-hand side of | and & is only evaluated when it
+    // determines the result
+    match op {
+        Op::Or => {
+            let lhs = eval(lhs)?;
+            if !is_null_or_zero(&lhs) {
+                return Ok(lhs);
             }
-        }
-        // handle nested expressions: right hand side
-        if tokens[2] == Token::LParen {
-            if let Some(i) = find_matching_paren(&tokens[2..]) {
-                let subexpr = &tokens[3..i + 2];
-                let result = eval_expression(subexpr)?;
-                tokens.splice(2..=i + 2, vec![result]);
-                continue;
-            } else {
-                return Err("syntax error EP1: unmatched left paren");
+            let rhs = eval(rhs)?;
+            if !rhs.is_empty() {
+                return Ok(rhs);
             }
+            return Ok(String::from("0"));
         }
-
-        // extract our left hand side, operator, and right hand side
-        let lhs = &tokens[0];
-        let operator = &tokens[1];
-        let rhs = &tokens[2];
-
-        // dispatch to the appropriate operation
-        let result = match operator {
-            Token::OpAdd => intop(lhs, rhs, IntOp::Add)?,
-            Token::OpSub => intop(lhs, rhs, IntOp::Sub)?,
-            Token::OpMul => intop(lhs, rhs, IntOp::Mul)?,
-            Token::OpDiv => intop(lhs, rhs, IntOp::Div)?,
-            Token::OpRem => intop(lhs, rhs, IntOp::Rem)?,
-
-            Token::OpEq => cmpop(lhs, rhs, CmpOp::EQ)?,
-            Token::OpNE => cmpop(lhs, rhs, CmpOp::NE)?,
-            Token::OpGT => cmpop(lhs, rhs, CmpOp::GT)?,
-            Token::OpLT => cmpop(lhs, rhs, CmpOp::LT)?,
-            Token::OpGE => cmpop(lhs, rhs, CmpOp::GE)?,
-            Token::OpLE => cmpop(lhs, rhs, CmpOp::LE)?,
-
-            Token::OpAnd => logop(lhs, rhs, true),
-            Token::OpOr => logop(lhs, rhs, false),
-
-            Token::OpMatch => matchop(lhs, rhs)?,
-
-            Token::LParen | Token::RParen | Token::Integer(_) | Token::Str(_) => {
-                return Err("syntax error: wanted operator");
+        Op::And => {
+            let lhs = eval(lhs)?;
+            if is_null_or_zero(&lhs) || is_null_or_zero(&eval(rhs)?) {
+                return Ok(String::from("0"));
             }
-        };
-
-        // replace the lhs, operator, and rhs with the result
-        tokens.splice(0..=2, vec![result]);
+            return Ok(lhs);
+        }
+        _ => {}
     }
 
-    // final result should be a single token
-    if tokens.len() == 1 {
-        let lhs = &tokens[0];
-        if token_is_lval(lhs) {
-            Ok(lhs.clone())
-        } else {
-            Err("syntax error: E1")
-        }
-    } else {
-        Err("syntax error: E2")
+    let lhs = eval(lhs)?;
+    let rhs = eval(rhs)?;
+    match op {
+        Op::Eq => Ok(bool_value(compare(&lhs, &rhs) == Ordering::Equal)),
+        Op::Ne => Ok(bool_value(compare(&lhs, &rhs) != Ordering::Equal)),
+        Op::Gt => Ok(bool_value(compare(&lhs, &rhs) == Ordering::Greater)),
+        Op::Ge => Ok(bool_value(compare(&lhs, &rhs) != Ordering::Less)),
+        Op::Lt => Ok(bool_value(compare(&lhs, &rhs) == Ordering::Less)),
+        Op::Le => Ok(bool_value(compare(&lhs, &rhs) != Ordering::Greater)),
+        Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Rem => arithmetic(&lhs, op, &rhs),
+        Op::Match => match_regex(&lhs, &rhs),
+        Op::Or | Op::And => unreachable!(),
     }
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // the regular expressions of : use the character classes of the locale
+    unsafe {
+        libc::setlocale(libc::LC_ALL, c"".as_ptr());
+    }
+
     // initialize translations
     textdomain(PROJECT_NAME)?;
     bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
 
-    // tokenize and evaluate the expression
-    let arg_tokens = tokenize();
-    let final_val = eval_expression(&arg_tokens)?;
-
-    // display the result
-    println!("{}", token_display(&final_val));
+    // the expression is the whole command line, except for a
+    // leading "--" that ends options
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() > 1 && args[0] == "--" {
+        args.remove(0);
+    }
 
-    Ok(())
+    match parse(&args).and_then(|expr| eval(&expr)) {
+        Ok(value) => {
+            println!("{}", value);
+            std::process::exit(if is_null_or_zero(&value) { 1 } else { 0 })
+        }
+        Err(e) => {
+            eprintln!("expr: {}", e);
+            std::process::exit(e.exit_code())
+        }
+    }
 }
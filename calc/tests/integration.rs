@@ -9,7 +9,7 @@
 
 use plib::{run_test, TestPlan};
 
-fn expr_test(args: &[&str], expected_output: &str) {
+fn expr_test(args: &[&str], expected_output: &str, expected_exit_code: i32) {
     let str_args: Vec<String> = args.iter().map(|s| String::from(*s)).collect();
 
     run_test(TestPlan {
@@ -18,7 +18,20 @@ fn expr_test(args: &[&str], expected_output: &str) {
         stdin_data: String::new(),
         expected_out: String::from(expected_output),
         expected_err: String::from(""),
-        expected_exit_code: 0,
+        expected_exit_code,
+    });
+}
+
+fn expr_error_test(args: &[&str], expected_error: &str, expected_exit_code: i32) {
+    let str_args: Vec<String> = args.iter().map(|s| String::from(*s)).collect();
+
+    run_test(TestPlan {
+        cmd: String::from("expr"),
+        args: str_args,
+        stdin_data: String::new(),
+        expected_out: String::new(),
+        expected_err: String::from(expected_error),
+        expected_exit_code,
     });
 }
 
@@ -64,40 +77,102 @@ macro_rules! test_bc_l {
 
 #[test]
 fn test_expr_logops() {
-    expr_test(&["4", "|", "5", "+", "1"], "5\n");
-    expr_test(&["0", "|", "5", "+", "1"], "6\n");
-    expr_test(&["4", "&", "5", "+", "1"], "5\n");
-    expr_test(&["4", "&", "0", "+", "1"], "1\n");
-    expr_test(&["0", "%", "5", "+", "1"], "1\n");
+    expr_test(&["4", "|", "5", "+", "1"], "4\n", 0);
+    expr_test(&["0", "|", "5", "+", "1"], "6\n", 0);
+    expr_test(&["", "|", "0"], "0\n", 1);
+    expr_test(&["4", "&", "5", "+", "1"], "4\n", 0);
+    expr_test(&["4", "&", "0", "+", "1"], "4\n", 0);
+    expr_test(&["4", "&", "0"], "0\n", 1);
+    expr_test(&["", "&", "1"], "0\n", 1);
+    expr_test(&["0", "%", "5", "+", "1"], "1\n", 0);
+
+    // the unneeded operand is not evaluated
+    expr_test(&["1", "|", "1", "/", "0"], "1\n", 0);
+    expr_test(&["0", "&", "1", "/", "0"], "0\n", 1);
 }
 
 #[test]
 fn test_expr_intops() {
-    expr_test(&["4", "+", "4", "+", "1"], "9\n");
-    expr_test(&["4", "-", "4", "+", "1"], "1\n");
-    expr_test(&["4", "*", "4", "+", "1"], "17\n");
-    expr_test(&["4", "/", "4", "+", "1"], "2\n");
-    expr_test(&["4", "%", "4", "+", "1"], "1\n");
+    expr_test(&["4", "+", "4", "+", "1"], "9\n", 0);
+    expr_test(&["4", "-", "4", "+", "1"], "1\n", 0);
+    expr_test(&["4", "*", "4", "+", "1"], "17\n", 0);
+    expr_test(&["4", "/", "4", "+", "1"], "2\n", 0);
+    expr_test(&["4", "%", "4", "+", "1"], "1\n", 0);
+    expr_test(&["1", "+", "4", "*", "4"], "17\n", 0);
+    expr_test(&["-7", "/", "2"], "-3\n", 0);
+    expr_test(&["-7", "%", "2"], "-1\n", 0);
+    expr_test(&["(", "1", "+", "4", ")", "*", "4"], "20\n", 0);
+    expr_test(&["2", "-", "2"], "0\n", 1);
 }
 
 #[test]
 fn test_expr_cmpint() {
-    expr_test(&["4", "<", "5", "+", "1"], "2\n");
-    expr_test(&["4", ">", "5", "+", "1"], "1\n");
-    expr_test(&["4", "<=", "5", "+", "1"], "2\n");
-    expr_test(&["4", ">=", "5", "+", "1"], "1\n");
-    expr_test(&["4", "=", "5", "+", "1"], "1\n");
-    expr_test(&["4", "!=", "5", "+", "1"], "2\n");
+    expr_test(&["4", "<", "5", "+", "1"], "1\n", 0);
+    expr_test(&["4", ">", "5", "+", "1"], "0\n", 1);
+    expr_test(&["4", "<=", "5", "+", "1"], "1\n", 0);
+    expr_test(&["4", ">=", "5", "+", "1"], "0\n", 1);
+    expr_test(&["4", "=", "5", "+", "1"], "0\n", 1);
+    expr_test(&["4", "!=", "5", "+", "1"], "1\n", 0);
+    expr_test(&["10", ">", "9"], "1\n", 0);
+    expr_test(&["010", "=", "10"], "1\n", 0);
 }
 
 #[test]
 fn test_expr_cmpstr() {
-    expr_test(&["aaa", "<", "bbb", "+", "1"], "2\n");
-    expr_test(&["aaa", ">", "bbb", "+", "1"], "1\n");
-    expr_test(&["aaa", "<=", "bbb", "+", "1"], "2\n");
-    expr_test(&["aaa", ">=", "bbb", "+", "1"], "1\n");
-    expr_test(&["aaa", "=", "bbb", "+", "1"], "1\n");
-    expr_test(&["aaa", "!=", "bbb", "+", "1"], "2\n");
+    expr_test(&["aaa", "<", "bbb"], "1\n", 0);
+    expr_test(&["aaa", ">", "bbb"], "0\n", 1);
+    expr_test(&["aaa", "<=", "bbb"], "1\n", 0);
+    expr_test(&["aaa", ">=", "bbb"], "0\n", 1);
+    expr_test(&["aaa", "=", "bbb"], "0\n", 1);
+    expr_test(&["aaa", "!=", "bbb"], "1\n", 0);
+    expr_test(&["10", ">", "9a"], "0\n", 1);
+}
+
+#[test]
+fn test_expr_match() {
+    expr_test(&["abcdef", ":", "abc"], "3\n", 0);
+    expr_test(&["abcdef", ":", "a.*e"], "5\n", 0);
+    expr_test(&["abcdef", ":", "bcd"], "0\n", 1);
+    expr_test(&["abcdef", ":", "a\\(.*\\)e"], "bcd\n", 0);
+    expr_test(&["abcdef", ":", "x\\(.*\\)"], "\n", 1);
+    expr_test(&["a+b", ":", "a+"], "2\n", 0);
+    expr_test(&["abc", ":", "a", "+", "1"], "2\n", 0);
+}
+
+#[test]
+fn test_expr_strings() {
+    expr_test(&["hello"], "hello\n", 0);
+    expr_test(&["0"], "0\n", 1);
+    expr_test(&[""], "\n", 1);
+    expr_test(&["--", "-5", "+", "2"], "-3\n", 0);
+    expr_test(&["=", "=", "="], "1\n", 0);
+}
+
+#[test]
+fn test_expr_errors() {
+    expr_error_test(&[], "expr: missing operand\n", 2);
+    expr_error_test(
+        &["1", "+"],
+        "expr: syntax error: missing argument after '+'\n",
+        2,
+    );
+    expr_error_test(
+        &["(", "1"],
+        "expr: syntax error: expecting ')' after '('\n",
+        2,
+    );
+    expr_error_test(
+        &["1", "2"],
+        "expr: syntax error: unexpected argument '2'\n",
+        2,
+    );
+    expr_error_test(&["a", "+", "1"], "expr: non-integer argument\n", 2);
+    expr_error_test(&["1", "/", "0"], "expr: division by zero\n", 3);
+    expr_error_test(
+        &["9223372036854775807", "+", "1"],
+        "expr: integer overflow\n",
+        3,
+    );
 }
 
 #[test]
@@ -160,6 +235,11 @@ fn test_bc_define_function_with_parameters() {
     test_bc!(define_function_with_parameters)
 }
 
+#[test]
+fn test_bc_define_function_on_one_line() {
+    test_bc!(define_function_on_one_line)
+}
+
 #[test]
 fn test_bc_div() {
     test_bc!(div)
@@ -195,6 +275,11 @@ fn test_bc_length() {
     test_bc!(length)
 }
 
+#[test]
+fn test_bc_long_numbers_are_split_across_lines() {
+    test_bc!(long_numbers_are_split_across_lines)
+}
+
 #[test]
 fn test_bc_mod() {
     test_bc!(mod)
@@ -344,3 +429,20 @@ fn test_bc_sin_to_scale_18() {
 fn test_bc_cos_to_scale_18() {
     test_bc_l!(cos_to_scale_18)
 }
+
+#[test]
+fn test_bc_math_library_results_are_exact() {
+    test_bc_l!(math_library_results_are_exact)
+}
+
+#[test]
+fn test_bc_errors_are_written_to_stderr() {
+    run_test(TestPlan {
+        cmd: String::from("bc"),
+        args: vec![],
+        stdin_data: String::from("1\n1 / 0\n2\n"),
+        expected_out: String::from("1\n2\n"),
+        expected_err: String::from("runtime error (line 1): division by zero\n"),
+        expected_exit_code: 0,
+    });
+}
@@ -6,21 +6,23 @@
 // file in the root directory of this project.
 // SPDX-License-Identifier: MIT
 //
-// TODO:
-// - add tests (how?)
-// - double-check that Rust stftime() is POSIX compliant
-//
 
 extern crate clap;
+extern crate libc;
 extern crate plib;
 
-use chrono::{DateTime, Datelike, Local, LocalResult, TimeZone, Utc};
 use clap::Parser;
-use gettextrs::{bind_textdomain_codeset, textdomain};
+use gettextrs::{bind_textdomain_codeset, gettext, textdomain};
 use plib::PROJECT_NAME;
+use std::ffi::CString;
+use std::io;
 
 const DEF_TIMESTR: &str = "%a %b %e %H:%M:%S %Z %Y";
 
+extern "C" {
+    fn tzset();
+}
+
 /// date - write the date and time
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about)]
@@ -29,113 +31,338 @@ struct Args {
     #[arg(short, long)]
     utc: bool,
 
+    /// Display the time described by STRING rather than the current time:
+    /// an ISO-8601 date and time, @seconds since the Epoch, or relative
+    /// items such as "yesterday" or "+3 days".
+    #[arg(
+        short,
+        long = "date",
+        value_name = "STRING",
+        allow_hyphen_values = true
+    )]
+    date: Option<String>,
+
     /// If prefixed with '+', Display the current time in the given FORMAT,
     /// as in strftime(3).  Otherwise, set the current time to the given
-    /// string.
+    /// string, in the form mmddhhmm[[cc]yy].
     timestr: Option<String>,
 }
 
-fn show_time_local(formatstr: &str) -> String {
-    let now = chrono::Local::now();
-    now.format(formatstr).to_string()
+// the broken-down local time of `t`
+fn local_time(t: libc::time_t) -> io::Result<libc::tm> {
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&t, &mut tm) }.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(tm)
 }
 
-fn show_time_utc(formatstr: &str) -> String {
-    let now = chrono::Utc::now();
-    now.format(formatstr).to_string()
-}
+/// Format `tm` with the conversion specifications of strftime(3), in the
+/// conventions of the current locale.
+fn strftime(format: &str, tm: &libc::tm) -> String {
+    // a trailing byte tells an empty result from one too long for the
+    // buffer, as strftime() returns 0 for both
+    let format = match CString::new(format!("{}_", format)) {
+        Ok(format) => format,
+        Err(_) => return String::new(),
+    };
 
-fn show_time(utc: bool, formatstr: &str) {
-    let timestr = {
-        if utc {
-            show_time_utc(formatstr)
-        } else {
-            show_time_local(formatstr)
+    let mut size = 256;
+    loop {
+        let mut buf: Vec<u8> = vec![0; size];
+        let len = unsafe {
+            libc::strftime(
+                buf.as_mut_ptr() as *mut libc::c_char,
+                size,
+                format.as_ptr(),
+                tm,
+            )
+        };
+        if len > 0 {
+            buf.truncate(len - 1);
+            return String::from_utf8_lossy(&buf).into_owned();
         }
+        size *= 2;
+    }
+}
+
+fn show_time(t: libc::time_t, formatstr: &str) -> io::Result<()> {
+    let tm = local_time(t)?;
+    println!("{}", strftime(formatstr, &tm));
+    Ok(())
+}
+
+// the value of a string of 1 or more ASCII digits
+fn number(s: &str) -> Option<libc::c_int> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+/// Parse a UTC offset: Z, +hh, +hhmm or +hh:mm.  Returns seconds east of
+/// UTC.
+fn parse_utc_offset(s: &str) -> Option<libc::time_t> {
+    if s == "Z" {
+        return Some(0);
+    }
+    let (sign, rest) = match s.as_bytes().first()? {
+        b'+' => (1, &s[1..]),
+        b'-' => (-1, &s[1..]),
+        _ => return None,
     };
+    let rest = rest.replace(':', "");
+    let (hours, minutes) = match rest.len() {
+        2 => (number(&rest)?, 0),
+        4 => (number(&rest[..2])?, number(&rest[2..])?),
+        _ => return None,
+    };
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    Some(sign * (hours as libc::time_t * 3600 + minutes as libc::time_t * 60))
+}
 
-    println!("{}", timestr);
+/// Parse the time of day hh:mm[:ss[.fraction]] into `tm`, returning the
+/// rest of `s`, which may be a UTC offset.
+fn parse_clock<'a>(s: &'a str, tm: &mut libc::tm) -> Option<&'a str> {
+    let end = s.find(['Z', '+', '-']).unwrap_or(s.len());
+    let (clock, zone) = s.split_at(end);
+    let mut fields = clock.split(':');
+    tm.tm_hour = number(fields.next()?)?;
+    tm.tm_min = number(fields.next()?)?;
+    tm.tm_sec = match fields.next() {
+        // fractions of a second are ignored
+        Some(sec) => number(sec.split(['.', ',']).next()?)?,
+        None => 0,
+    };
+    if fields.next().is_some() || tm.tm_hour > 23 || tm.tm_min > 59 || tm.tm_sec > 60 {
+        return None;
+    }
+    Some(zone)
 }
 
-fn set_time(utc: bool, timestr: &str) -> Result<(), &'static str> {
-    for ch in timestr.chars() {
-        if !ch.is_ascii_digit() {
-            return Err("date: invalid date");
-        }
+/// Parse an absolute time: @seconds, or an ISO-8601 date yyyy-mm-dd and
+/// optionally a time of day, joined by a T or given as the next word, and
+/// a UTC offset, which may also be the word after the time of day.  A
+/// time of day alone is today's.  Returns the time and the number of words
+/// used.
+fn parse_absolute(words: &[&str], now: libc::time_t) -> Option<(libc::time_t, usize)> {
+    let first = *words.first()?;
+    if let Some(secs) = first.strip_prefix('@') {
+        let secs = secs.split(['.', ',']).next()?;
+        return secs.parse().ok().map(|t| (t, 1));
     }
 
-    let cur_year = {
-        if utc {
-            let now = chrono::Utc::now();
-            now.year()
-        } else {
-            let now = chrono::Local::now();
-            now.year()
-        }
+    let mut tm = local_time(now).ok()?;
+    let mut used = 1;
+    let (date, clock) = match first.split_once(['T', 't']) {
+        Some((date, clock)) => (Some(date), Some(clock)),
+        None if first.contains(':') => (None, Some(first)),
+        None if first.contains('-') => match words.get(1) {
+            Some(next) if next.contains(':') => {
+                used = 2;
+                (Some(first), Some(*next))
+            }
+            _ => (Some(first), None),
+        },
+        None => return None,
     };
 
-    let (year, month, day, hour, minute) = match timestr.len() {
-        8 => {
-            let month = timestr[0..2].parse::<u32>().unwrap();
-            let day = timestr[2..4].parse::<u32>().unwrap();
-            let hour = timestr[4..6].parse::<u32>().unwrap();
-            let minute = timestr[6..8].parse::<u32>().unwrap();
-            (cur_year, month, day, hour, minute)
+    if let Some(date) = date {
+        let mut fields = date.splitn(3, '-');
+        let year = fields.next()?;
+        if year.len() < 4 {
+            return None;
         }
-        10 => {
-            let month = timestr[0..2].parse::<u32>().unwrap();
-            let day = timestr[2..4].parse::<u32>().unwrap();
-            let hour = timestr[4..6].parse::<u32>().unwrap();
-            let minute = timestr[6..8].parse::<u32>().unwrap();
-            let year = timestr[8..10].parse::<i32>().unwrap();
-            if year < 70 {
-                (year + 2000, month, day, hour, minute)
-            } else {
-                (year + 1900, month, day, hour, minute)
-            }
+        tm.tm_year = number(year)? - 1900;
+        tm.tm_mon = number(fields.next()?)? - 1;
+        tm.tm_mday = number(fields.next()?)?;
+        if !(0..12).contains(&tm.tm_mon) || !(1..=31).contains(&tm.tm_mday) {
+            return None;
         }
-        12 => {
-            let month = timestr[0..2].parse::<u32>().unwrap();
-            let day = timestr[2..4].parse::<u32>().unwrap();
-            let hour = timestr[4..6].parse::<u32>().unwrap();
-            let minute = timestr[6..8].parse::<u32>().unwrap();
-            let year = timestr[8..12].parse::<i32>().unwrap();
-            (year, month, day, hour, minute)
+        tm.tm_hour = 0;
+        tm.tm_min = 0;
+        tm.tm_sec = 0;
+    }
+
+    let mut offset = None;
+    if let Some(clock) = clock {
+        let zone = parse_clock(clock, &mut tm)?;
+        if !zone.is_empty() {
+            offset = Some(parse_utc_offset(zone)?);
+        } else if let Some(zone) = words.get(used).and_then(|w| parse_utc_offset(w)) {
+            // a count, as in "10:00 +02 hours", is not a UTC offset
+            if words.get(used + 1).is_none_or(|w| parse_unit(w).is_none()) {
+                offset = Some(zone);
+                used += 1;
+            }
         }
-        _ => {
-            return Err("date: invalid date");
+    }
+
+    let (mon, mday) = (tm.tm_mon, tm.tm_mday);
+    let t = match offset {
+        Some(offset) => (unsafe { libc::timegm(&mut tm) }) - offset,
+        None => {
+            tm.tm_isdst = -1;
+            unsafe { libc::mktime(&mut tm) }
         }
     };
+    if tm.tm_mon != mon || tm.tm_mday != mday {
+        return None;
+    }
+    Some((t, used))
+}
+
+// the relative units, with their length in seconds, days or months
+enum Unit {
+    Seconds(libc::time_t),
+    Days(libc::c_int),
+    Months(libc::c_int),
+}
+
+fn parse_unit(word: &str) -> Option<Unit> {
+    let word = word.to_ascii_lowercase();
+    let word = word.strip_suffix('s').unwrap_or(&word);
+    let unit = match word {
+        "sec" | "second" => Unit::Seconds(1),
+        "min" | "minute" => Unit::Seconds(60),
+        "hour" => Unit::Seconds(3600),
+        "day" => Unit::Days(1),
+        "week" => Unit::Days(7),
+        "fortnight" => Unit::Days(14),
+        "month" => Unit::Months(1),
+        "year" => Unit::Months(12),
+        _ => return None,
+    };
+    Some(unit)
+}
+
+/// Parse the -d string: an optional absolute time followed by relative
+/// items such as "yesterday", "+3 days", "2 hours ago" or "next week".
+/// Returns None, as for an invalid string, if the time is out of range.
+fn parse_date_string(s: &str, now: libc::time_t) -> Option<libc::time_t> {
+    let words: Vec<&str> = s.split_whitespace().collect();
+    let (base, used) = parse_absolute(&words, now).unwrap_or((now, 0));
 
-    // calculate system time
-    let new_time = {
-        if utc {
-            match chrono::Utc.with_ymd_and_hms(year, month, day, hour, minute, 0) {
-                LocalResult::<DateTime<Utc>>::Single(t) => t.timestamp(),
-                _ => {
-                    return Err("date: invalid date");
-                }
+    let mut seconds: libc::time_t = 0;
+    let mut days: libc::c_int = 0;
+    let mut months: libc::c_int = 0;
+    let mut i = used;
+    while i < words.len() {
+        let word = words[i].to_ascii_lowercase();
+        i += 1;
+        let count = match word.as_str() {
+            "now" | "today" => continue,
+            "yesterday" => {
+                days = days.checked_sub(1)?;
+                continue;
             }
-        } else {
-            match chrono::Local.with_ymd_and_hms(year, month, day, hour, minute, 0) {
-                LocalResult::<DateTime<Local>>::Single(t) => t.timestamp(),
-                _ => {
-                    return Err("date: invalid date");
-                }
+            "tomorrow" => {
+                days = days.checked_add(1)?;
+                continue;
             }
+            "next" => Some(1),
+            "last" => Some(-1),
+            "this" => Some(0),
+            word => word.parse::<libc::c_int>().ok(),
+        };
+
+        // a unit on its own counts one
+        let unit = match count {
+            Some(_) => {
+                i += 1;
+                parse_unit(words.get(i - 1)?)?
+            }
+            None => parse_unit(&word)?,
+        };
+        let mut count = count.unwrap_or(1);
+        if words.get(i).is_some_and(|w| w.eq_ignore_ascii_case("ago")) {
+            count = count.checked_neg()?;
+            i += 1;
         }
-    };
+
+        match unit {
+            Unit::Seconds(secs) => {
+                seconds = seconds.checked_add(secs.checked_mul(count.into())?)?
+            }
+            Unit::Days(n) => days = days.checked_add(n.checked_mul(count)?)?,
+            Unit::Months(n) => months = months.checked_add(n.checked_mul(count)?)?,
+        }
+    }
+
+    // days and months move the calendar date, keeping the time of day
+    // across daylight saving changes
+    let mut tm = local_time(base).ok()?;
+    tm.tm_mday = tm.tm_mday.checked_add(days)?;
+    tm.tm_mon = tm.tm_mon.checked_add(months)?;
+    tm.tm_isdst = -1;
+    // mktime sets tm_wday on success, which tells an error apart from
+    // the time one second before the Epoch
+    tm.tm_wday = -1;
+    let t = unsafe { libc::mktime(&mut tm) };
+    if t == -1 && tm.tm_wday == -1 {
+        return None;
+    }
+    t.checked_add(seconds)
+}
+
+// the digits of `timestr` at `range`, which are known to be ASCII digits
+fn digits(timestr: &str, range: std::ops::Range<usize>) -> libc::c_int {
+    timestr[range].parse().unwrap()
+}
+
+/// Parse the mmddhhmm[[cc]yy] operand, in the current year when no year
+/// is given.
+fn parse_set_time(timestr: &str) -> Option<libc::time_t> {
+    if ![8, 10, 12].contains(&timestr.len()) || !timestr.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let now = unsafe { libc::time(std::ptr::null_mut()) };
+    let mut tm = local_time(now).ok()?;
+    tm.tm_mon = digits(timestr, 0..2) - 1;
+    tm.tm_mday = digits(timestr, 2..4);
+    tm.tm_hour = digits(timestr, 4..6);
+    tm.tm_min = digits(timestr, 6..8);
+    tm.tm_sec = 0;
+    match timestr.len() {
+        8 => {}
+        10 => {
+            // two-digit years 69-99 are in the 20th century
+            let year = digits(timestr, 8..10);
+            tm.tm_year = if year < 69 { year + 100 } else { year };
+        }
+        _ => tm.tm_year = digits(timestr, 8..12) - 1900,
+    }
+    if !(0..12).contains(&tm.tm_mon)
+        || !(1..=31).contains(&tm.tm_mday)
+        || !(0..24).contains(&tm.tm_hour)
+        || !(0..60).contains(&tm.tm_min)
+    {
+        return None;
+    }
+
+    // mktime() normalizes days past the end of the month
+    let (mon, mday) = (tm.tm_mon, tm.tm_mday);
+    tm.tm_isdst = -1;
+    let t = unsafe { libc::mktime(&mut tm) };
+    if t == -1 || tm.tm_mon != mon || tm.tm_mday != mday {
+        return None;
+    }
+    Some(t)
+}
+
+fn set_time(timestr: &str) -> Result<(), String> {
+    let t = parse_set_time(timestr).ok_or_else(|| gettext!("invalid date '{}'", timestr))?;
 
     let new_time = libc::timespec {
-        tv_sec: new_time,
+        tv_sec: t,
         tv_nsec: 0,
     };
-
-    // set system time
-    unsafe {
-        if libc::clock_settime(libc::CLOCK_REALTIME, &new_time) != 0 {
-            return Err("date: failed to set time");
-        }
+    if unsafe { libc::clock_settime(libc::CLOCK_REALTIME, &new_time) } != 0 {
+        let err = io::Error::last_os_error();
+        return Err(format!("{}: {}", gettext("cannot set date"), err));
     }
 
     Ok(())
@@ -148,15 +375,40 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     textdomain(PROJECT_NAME)?;
     bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
 
-    match &args.timestr {
-        None => show_time(args.utc, DEF_TIMESTR),
-        Some(timestr) => {
-            if timestr.starts_with("+") {
-                show_time(args.utc, &timestr[1..]);
-            } else {
-                set_time(args.utc, &timestr)?;
+    // the names of %a, %b, %c and the like follow the locale
+    unsafe {
+        libc::setlocale(libc::LC_ALL, c"".as_ptr());
+    }
+
+    if args.utc {
+        std::env::set_var("TZ", "UTC0");
+    }
+    unsafe { tzset() };
+
+    let now = unsafe { libc::time(std::ptr::null_mut()) };
+    let t = match &args.date {
+        None => now,
+        Some(date) => match parse_date_string(date, now) {
+            Some(t) => t,
+            None => {
+                eprintln!("date: {}", gettext!("invalid date '{}'", date));
+                std::process::exit(1);
             }
-        }
+        },
+    };
+
+    let result = match &args.timestr {
+        None => show_time(t, DEF_TIMESTR).map_err(|e| e.to_string()),
+        Some(timestr) => match timestr.strip_prefix('+') {
+            Some(formatstr) => show_time(t, formatstr).map_err(|e| e.to_string()),
+            None if args.date.is_some() => Err(gettext("cannot set the date together with -d")),
+            None => set_time(timestr),
+        },
+    };
+
+    if let Err(e) = result {
+        eprintln!("date: {}", e);
+        std::process::exit(1);
     }
 
     Ok(())
@@ -0,0 +1,112 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+extern crate clap;
+extern crate libc;
+extern crate plib;
+
+use clap::Parser;
+use gettextrs::{bind_textdomain_codeset, textdomain};
+use plib::PROJECT_NAME;
+use std::ffi::OsString;
+use std::io;
+use std::process::Command;
+use std::time::Instant;
+
+/// time - time a simple command
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about)]
+struct Args {
+    /// Write the timing output to standard error in the POSIX format.
+    #[arg(short = 'p')]
+    posix: bool,
+
+    /// The utility to invoke, and its arguments.
+    #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+    utility: Vec<OsString>,
+}
+
+fn timeval_secs(tv: &libc::timeval) -> f64 {
+    tv.tv_sec as f64 + tv.tv_usec as f64 / 1_000_000.0
+}
+
+/// Wait for the child `pid`, returning its wait status and resource
+/// usage.
+fn wait_child(pid: libc::pid_t) -> io::Result<(libc::c_int, libc::rusage)> {
+    let mut status = 0;
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        let res = unsafe { libc::wait4(pid, &mut status, 0, &mut usage) };
+        if res == pid {
+            return Ok((status, usage));
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // parse command line arguments
+    let args = Args::parse();
+
+    textdomain(PROJECT_NAME)?;
+    bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
+
+    let start = Instant::now();
+    let child = Command::new(&args.utility[0])
+        .args(&args.utility[1..])
+        .spawn();
+    let child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("time: {}: {}", args.utility[0].to_string_lossy(), e);
+            let exit_code = if e.kind() == io::ErrorKind::NotFound {
+                127
+            } else {
+                126
+            };
+            std::process::exit(exit_code);
+        }
+    };
+
+    // like a shell, leave interrupts from the terminal to the utility
+    unsafe {
+        libc::signal(libc::SIGINT, libc::SIG_IGN);
+        libc::signal(libc::SIGQUIT, libc::SIG_IGN);
+    }
+
+    let (status, usage) = match wait_child(child.id() as libc::pid_t) {
+        Ok(waited) => waited,
+        Err(e) => {
+            eprintln!("time: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let real = start.elapsed().as_secs_f64();
+    let user = timeval_secs(&usage.ru_utime);
+    let sys = timeval_secs(&usage.ru_stime);
+
+    if args.posix {
+        eprintln!("real {:.2}\nuser {:.2}\nsys {:.2}", real, user, sys);
+    } else {
+        eprintln!("{:>13.2} real {:>13.2} user {:>13.2} sys", real, user, sys);
+    }
+
+    // the exit status of the utility is passed through
+    let exit_code = if libc::WIFEXITED(status) {
+        libc::WEXITSTATUS(status)
+    } else if libc::WIFSIGNALED(status) {
+        128 + libc::WTERMSIG(status)
+    } else {
+        1
+    };
+    std::process::exit(exit_code)
+}
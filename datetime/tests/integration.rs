@@ -0,0 +1,280 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use plib::{run_test, run_test_with_checker, TestPlan};
+use std::os::unix::fs::PermissionsExt;
+
+fn date_test(args: &[&str], expected_out: &str, expected_err: &str, expected_exit_code: i32) {
+    run_test(TestPlan {
+        cmd: String::from("date"),
+        args: args.iter().map(|s| s.to_string()).collect(),
+        stdin_data: String::new(),
+        expected_out: String::from(expected_out),
+        expected_err: String::from(expected_err),
+        expected_exit_code,
+    });
+}
+
+#[test]
+fn test_date_utc_zone() {
+    date_test(&["-u", "+%Z %z"], "UTC +0000\n", "", 0);
+}
+
+#[test]
+fn test_date_literal_format() {
+    date_test(&["+%%|%n|%t|"], "%|\n|\t|\n", "", 0);
+    date_test(&["+"], "\n", "", 0);
+}
+
+#[test]
+fn test_date_invalid_operand() {
+    date_test(&["1301"], "", "date: invalid date '1301'\n", 1);
+    date_test(&["0231120024"], "", "date: invalid date '0231120024'\n", 1);
+    date_test(&["12a11200"], "", "date: invalid date '12a11200'\n", 1);
+}
+
+#[test]
+fn test_date_epoch() {
+    date_test(
+        &["-u", "-d", "@0", "+%F %T"],
+        "1970-01-01 00:00:00\n",
+        "",
+        0,
+    );
+    date_test(
+        &["-u", "-d", "@1700000000", "+%a %b %e %j %U %W"],
+        "Tue Nov 14 318 46 46\n",
+        "",
+        0,
+    );
+}
+
+#[test]
+fn test_date_iso8601() {
+    date_test(
+        &["-u", "-d", "2024-02-29", "+%F %T"],
+        "2024-02-29 00:00:00\n",
+        "",
+        0,
+    );
+    date_test(
+        &["-u", "-d", "2024-02-29T12:30:45+05:30", "+%F %T"],
+        "2024-02-29 07:00:45\n",
+        "",
+        0,
+    );
+    date_test(
+        &["-u", "--date", "2024-02-29 12:30:45Z", "+%F %T"],
+        "2024-02-29 12:30:45\n",
+        "",
+        0,
+    );
+
+    // the UTC offset as a word of its own
+    date_test(
+        &["-u", "-d", "2020-01-01 10:00:00 +0200", "+%F %T"],
+        "2020-01-01 08:00:00\n",
+        "",
+        0,
+    );
+    date_test(
+        &["-u", "-d", "2020-01-01 10:00 -03:30 +1 day", "+%F %T"],
+        "2020-01-02 13:30:00\n",
+        "",
+        0,
+    );
+    // but not a count of units
+    date_test(
+        &["-u", "-d", "2020-01-01 10:00:00 +02 hours", "+%F %T"],
+        "2020-01-01 12:00:00\n",
+        "",
+        0,
+    );
+}
+
+#[test]
+fn test_date_relative() {
+    date_test(
+        &["-u", "-d", "2024-02-28 +1 day", "+%F"],
+        "2024-02-29\n",
+        "",
+        0,
+    );
+    date_test(&["-u", "-d", "@0 yesterday", "+%F"], "1969-12-31\n", "", 0);
+    date_test(
+        &["-u", "-d", "@0 2 hours ago", "+%F %T"],
+        "1969-12-31 22:00:00\n",
+        "",
+        0,
+    );
+    date_test(&["-u", "-d", "@0 next week", "+%F"], "1970-01-08\n", "", 0);
+}
+
+#[test]
+fn test_date_leading_hyphen() {
+    // a relative date starting with a hyphen is the value of -d, not an
+    // option
+    let plan = TestPlan {
+        cmd: String::from("date"),
+        args: vec![
+            String::from("-u"),
+            String::from("-d"),
+            String::from("-1 week"),
+            String::from("+%s"),
+        ],
+        stdin_data: String::new(),
+        expected_out: String::new(),
+        expected_err: String::new(),
+        expected_exit_code: 0,
+    };
+    run_test_with_checker(plan, |_, output| {
+        assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+        assert_eq!(output.status.code(), Some(0));
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let t: i64 = String::from_utf8_lossy(&output.stdout)
+            .trim_end()
+            .parse()
+            .unwrap();
+        assert!((now - 7 * 86400 - t).abs() < 60);
+    });
+}
+
+#[test]
+fn test_date_invalid_string() {
+    date_test(
+        &["-d", "2024-02-30"],
+        "",
+        "date: invalid date '2024-02-30'\n",
+        1,
+    );
+    date_test(
+        &["-d", "@0 +3 dayz"],
+        "",
+        "date: invalid date '@0 +3 dayz'\n",
+        1,
+    );
+}
+
+#[test]
+fn test_date_relative_overflow() {
+    for date in [
+        "2000000000 weeks",
+        "@0 2000000000 years ago",
+        "@0 9223372036854775807 seconds",
+        "2147483647 days tomorrow",
+    ] {
+        date_test(
+            &["-u", "-d", date, "+%F"],
+            "",
+            &format!("date: invalid date '{}'\n", date),
+            1,
+        );
+    }
+    date_test(
+        &["-u", "-d", "@-1", "+%F %T"],
+        "1969-12-31 23:59:59\n",
+        "",
+        0,
+    );
+}
+
+fn time_test(args: &[&str], expected_out: &str, expected_err: &str, expected_exit_code: i32) {
+    run_test(TestPlan {
+        cmd: String::from("time"),
+        args: args.iter().map(|s| s.to_string()).collect(),
+        stdin_data: String::new(),
+        expected_out: String::from(expected_out),
+        expected_err: String::from(expected_err),
+        expected_exit_code,
+    });
+}
+
+// The seconds of a "name seconds" line of the -p output
+fn posix_time(line: Option<&str>, name: &str) -> f64 {
+    let line = line.unwrap_or_else(|| panic!("missing {} line", name));
+    let (prefix, secs) = line.split_once(' ').unwrap();
+    assert_eq!(prefix, name);
+    secs.parse().unwrap()
+}
+
+#[test]
+fn test_time_posix_format() {
+    let plan = TestPlan {
+        cmd: String::from("time"),
+        args: vec![
+            String::from("-p"),
+            String::from("sh"),
+            String::from("-c"),
+            String::from("sleep 0.2; echo done"),
+        ],
+        stdin_data: String::new(),
+        expected_out: String::new(),
+        expected_err: String::new(),
+        expected_exit_code: 0,
+    };
+    run_test_with_checker(plan, |_, output| {
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "done\n");
+        assert_eq!(output.status.code(), Some(0));
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.ends_with('\n'));
+        let mut lines = stderr.lines();
+        let real = posix_time(lines.next(), "real");
+        let user = posix_time(lines.next(), "user");
+        let sys = posix_time(lines.next(), "sys");
+        assert_eq!(lines.next(), None);
+        assert!((0.2..10.0).contains(&real));
+        assert!(user >= 0.0 && sys >= 0.0);
+    });
+}
+
+#[test]
+fn test_time_exit_status() {
+    // the utility's exit status is passed through, after the timing
+    for (script, exit_code) in [("exit 0", 0), ("exit 5", 5), ("kill -TERM $$", 143)] {
+        let plan = TestPlan {
+            cmd: String::from("time"),
+            args: vec![
+                String::from("-p"),
+                String::from("sh"),
+                String::from("-c"),
+                String::from(script),
+            ],
+            stdin_data: String::new(),
+            expected_out: String::new(),
+            expected_err: String::new(),
+            expected_exit_code: exit_code,
+        };
+        run_test_with_checker(plan, |plan, output| {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            assert_eq!(stderr.lines().count(), 3);
+            assert_eq!(output.status.code(), Some(plan.expected_exit_code));
+        });
+    }
+
+    time_test(
+        &["-p", "time_no_such_utility"],
+        "",
+        "time: time_no_such_utility: No such file or directory (os error 2)\n",
+        127,
+    );
+
+    let path = format!("{}/test_time_not_executable", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::write(&path, "#!/bin/sh\n").unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+    time_test(
+        &[&path],
+        "",
+        &format!("time: {path}: Permission denied (os error 13)\n"),
+        126,
+    );
+}
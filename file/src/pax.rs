@@ -0,0 +1,309 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+extern crate clap;
+extern crate libc;
+extern crate plib;
+
+mod pax_util;
+
+use clap::Parser;
+use gettextrs::{bind_textdomain_codeset, textdomain};
+use pax_util::archive::{open_reader, open_writer, ArchiveReader, ArchiveWriter, Format, Member};
+use pax_util::copy::Copier;
+use pax_util::create::{Creator, Dereference, FileWalker};
+use pax_util::extract::{Extractor, Preserve};
+use pax_util::list::list_member;
+use pax_util::select::Selector;
+use pax_util::subst::Renamer;
+use plib::PROJECT_NAME;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+// size of the reads from the archive file
+const READ_SIZE: usize = 10240;
+
+/// pax - portable archive interchange
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about)]
+struct Args {
+    /// Read an archive file from standard input, or the archive named by -f.
+    #[arg(short)]
+    read: bool,
+
+    /// Write files to standard output, or the archive named by -f, in the
+    /// specified archive format.
+    #[arg(short)]
+    write: bool,
+
+    /// Block the output at a positive decimal integer number of bytes per
+    /// write to the archive file, optionally followed by b (512-byte
+    /// units) or k (1024-byte units).
+    #[arg(short, value_parser = parse_block_size)]
+    blocksize: Option<usize>,
+
+    /// Cause directories being copied or archived, or archive entries
+    /// being extracted, to match only the directory file itself and not
+    /// its contents.
+    #[arg(short = 'd')]
+    no_descend: bool,
+
+    /// Specify the pathname of the input or output archive, overriding the
+    /// default standard input (-r or list mode) or standard output (-w).
+    #[arg(short = 'f')]
+    archive: Option<String>,
+
+    /// Follow symbolic links named on the command line.
+    #[arg(short = 'H', overrides_with = "dereference")]
+    dereference_args: bool,
+
+    /// Follow all symbolic links.
+    #[arg(short = 'L', overrides_with = "dereference_args")]
+    dereference: bool,
+
+    /// Prevent the overwriting of existing files.
+    #[arg(short = 'k')]
+    keep: bool,
+
+    /// Select only the first archive member that matches each pattern
+    /// operand.
+    #[arg(short = 'n')]
+    first_match: bool,
+
+    /// Specify one or more file characteristic options (privileges):
+    /// a, e, m, o, p.
+    #[arg(short = 'p')]
+    privileges: Vec<String>,
+
+    /// Modify file or archive member names according to the substitution
+    /// expression /old/new/[gp], where old is a basic regular expression.
+    #[arg(short = 's')]
+    substitutions: Vec<String>,
+
+    /// In list mode, produce a verbose table of contents; otherwise, write
+    /// archive member pathnames to standard error.
+    #[arg(short)]
+    verbose: bool,
+
+    /// When traversing the file hierarchy, do not descend into directories
+    /// that have a different device ID.
+    #[arg(short = 'X')]
+    xdev: bool,
+
+    /// Specify the output archive format: cpio, pax or ustar.  When
+    /// reading, the format is detected.
+    #[arg(short = 'x', value_parser = Format::parse, default_value = "ustar")]
+    format: Format,
+
+    /// Files to archive (-w) or copy (-rw, followed by the destination
+    /// directory), or patterns selecting members (-r and list mode).
+    operands: Vec<String>,
+}
+
+fn parse_block_size(arg: &str) -> Result<usize, String> {
+    let (digits, unit) = match arg.as_bytes().last() {
+        Some(b'b') => (&arg[..arg.len() - 1], 512),
+        Some(b'k') => (&arg[..arg.len() - 1], 1024),
+        _ => (arg, 1),
+    };
+    let size = digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit))
+        .filter(|&n| n > 0 && n % 512 == 0)
+        .ok_or_else(|| format!("invalid block size: {}", arg))?;
+    Ok(size)
+}
+
+fn open_input(args: &Args) -> io::Result<Box<dyn Read>> {
+    match &args.archive {
+        Some(path) if path != "-" => Ok(Box::new(fs::File::open(path)?)),
+        _ => Ok(Box::new(io::stdin().lock())),
+    }
+}
+
+fn open_output(args: &Args) -> io::Result<Box<dyn Write>> {
+    match &args.archive {
+        Some(path) if path != "-" => Ok(Box::new(fs::File::create(path)?)),
+        _ => Ok(Box::new(io::stdout().lock())),
+    }
+}
+
+fn archive_name(args: &Args) -> &str {
+    match &args.archive {
+        Some(path) => path,
+        None => "stdin",
+    }
+}
+
+fn invalid_input(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+/// Call `process` for each member selected by the pattern operands, after
+/// renaming it.  Returns false if a pattern did not match any member.
+fn for_each_member<F>(
+    args: &Args,
+    reader: &mut dyn ArchiveReader,
+    mut process: F,
+) -> io::Result<bool>
+where
+    F: FnMut(&Member, &mut dyn ArchiveReader) -> io::Result<()>,
+{
+    let renamer = Renamer::new(&args.substitutions).map_err(invalid_input)?;
+    let mut selector = Selector::new(&args.operands, args.first_match, !args.no_descend);
+
+    while let Some(mut member) = reader.next_member()? {
+        if !selector.select(&member.path) || !renamer.rename_member(&mut member) {
+            continue;
+        }
+        process(&member, reader)?;
+    }
+
+    let mut success = true;
+    for pattern in selector.unmatched() {
+        eprintln!("pax: {}: pattern not matched", pattern);
+        success = false;
+    }
+    Ok(success)
+}
+
+/// List mode (neither -r nor -w): write the table of contents.
+fn list_archive(args: &Args, reader: &mut dyn ArchiveReader) -> io::Result<bool> {
+    for_each_member(args, reader, |member, _| list_member(member, args.verbose))
+}
+
+fn extractor(args: &Args) -> io::Result<Extractor> {
+    let preserve = Preserve::parse(&args.privileges).map_err(invalid_input)?;
+    Ok(Extractor::new(preserve, args.keep))
+}
+
+/// -r: extract the members of the archive.
+fn read_archive(args: &Args, reader: &mut dyn ArchiveReader) -> io::Result<bool> {
+    let mut extractor = extractor(args)?;
+    let mut success = true;
+
+    success &= for_each_member(args, reader, |member, reader| {
+        if args.verbose {
+            eprintln!("{}", member.path.display());
+        }
+        if let Err(e) = extractor.extract(member, &mut |file| reader.read_data(file)) {
+            eprintln!("{}: {}", member.path.display(), e);
+            success = false;
+        }
+        Ok(())
+    })?;
+
+    success &= extractor.finish();
+    Ok(success)
+}
+
+/// -w and -rw: write the files named by `files`, or by the lines of
+/// standard input, to `writer`.
+fn write_files(args: &Args, files: &[String], writer: &mut dyn ArchiveWriter) -> io::Result<bool> {
+    let walker = FileWalker {
+        deref: if args.dereference {
+            Dereference::Always
+        } else if args.dereference_args {
+            Dereference::CommandLine
+        } else {
+            Dereference::Never
+        },
+        descend: !args.no_descend,
+        xdev: args.xdev,
+    };
+    let renamer = Renamer::new(&args.substitutions).map_err(invalid_input)?;
+    let mut creator = Creator::new(writer, &renamer, args.verbose);
+    let mut success = true;
+
+    let mut add = |path: &str| {
+        success &= walker.walk(Path::new(path), &mut |path, md| creator.add(path, md));
+    };
+
+    if files.is_empty() {
+        for line in io::stdin().lock().lines() {
+            let line = line?;
+            if !line.is_empty() {
+                add(&line);
+            }
+        }
+    } else {
+        for path in files {
+            add(path);
+        }
+    }
+
+    writer.finish()?;
+    Ok(success)
+}
+
+/// -rw: copy the files to the directory named by the last operand.
+fn copy_files(args: &Args) -> io::Result<bool> {
+    let Some((dest, files)) = args.operands.split_last() else {
+        return Err(invalid_input(String::from(
+            "copy mode requires a destination directory",
+        )));
+    };
+    if !Path::new(dest).is_dir() {
+        return Err(invalid_input(format!("{}: not a directory", dest)));
+    }
+
+    let mut copier = Copier::new(Path::new(dest), extractor(args)?);
+    let success = write_files(args, files, &mut copier)?;
+    Ok(success && !copier.failed)
+}
+
+fn pax(args: &Args) -> io::Result<bool> {
+    match (args.read, args.write) {
+        (false, true) => {
+            let block_size = args
+                .blocksize
+                .unwrap_or_else(|| args.format.default_block_size());
+            let mut writer = open_writer(args.format, open_output(args)?, block_size);
+            write_files(args, &args.operands, writer.as_mut())
+        }
+        (true, true) => copy_files(args),
+        (read, false) => {
+            let input = BufReader::with_capacity(READ_SIZE, open_input(args)?);
+            let result = open_reader(Box::new(input)).and_then(|mut reader| {
+                if read {
+                    read_archive(args, reader.as_mut())
+                } else {
+                    list_archive(args, reader.as_mut())
+                }
+            });
+            result.map_err(|e| match e.kind() {
+                io::ErrorKind::InvalidData => {
+                    io::Error::new(e.kind(), format!("{}: {}", archive_name(args), e))
+                }
+                _ => e,
+            })
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // parse command line arguments
+    let args = Args::parse();
+
+    textdomain(PROJECT_NAME)?;
+    bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
+
+    let exit_code = match pax(&args) {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            eprintln!("pax: {}", e);
+            1
+        }
+    };
+
+    std::process::exit(exit_code)
+}
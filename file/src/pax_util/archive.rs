@@ -0,0 +1,288 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use super::cpio::{self, CpioReader, CpioWriter};
+use super::ustar::{self, UstarReader, UstarWriter};
+use std::ffi::CStr;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::{Path, PathBuf};
+
+/// Size of the records that ustar archives are made of.
+pub const RECORD_SIZE: usize = 512;
+
+/// Archive formats supported by -x.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Cpio,
+    Pax,
+    Ustar,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Result<Format, String> {
+        match name {
+            "cpio" => Ok(Format::Cpio),
+            "pax" => Ok(Format::Pax),
+            "ustar" => Ok(Format::Ustar),
+            _ => Err(format!("unknown archive format: {}", name)),
+        }
+    }
+
+    /// Block size used for writing when -b is not given.
+    pub fn default_block_size(&self) -> usize {
+        match self {
+            Format::Cpio => 5120,
+            Format::Pax | Format::Ustar => 10240,
+        }
+    }
+}
+
+/// Type of an archive member, with the data that depends on the type.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MemberKind {
+    Regular,
+    Directory,
+    Symlink(PathBuf),
+
+    /// A hard link to a member stored earlier in the archive.
+    HardLink(PathBuf),
+    CharDevice(u32, u32),
+    BlockDevice(u32, u32),
+    Fifo,
+}
+
+/// The header of a file stored in an archive.
+#[derive(Clone, Debug)]
+pub struct Member {
+    pub path: PathBuf,
+    pub kind: MemberKind,
+
+    /// File mode bits, without the file type.
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub uname: String,
+    pub gname: String,
+
+    /// Size of the data following the header; zero except for regular
+    /// files.
+    pub size: u64,
+    pub mtime: i64,
+    pub mtime_nsec: u32,
+
+    /// Access time, for formats that store it.
+    pub atime: Option<(i64, u32)>,
+
+    /// File serial number and link count, used by cpio to describe hard
+    /// links.
+    pub dev: u64,
+    pub ino: u64,
+    pub nlink: u64,
+}
+
+fn user_name(uid: u32) -> String {
+    let pwd = unsafe { libc::getpwuid(uid) };
+    if pwd.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr((*pwd).pw_name) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn group_name(gid: u32) -> String {
+    let grp = unsafe { libc::getgrgid(gid) };
+    if grp.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr((*grp).gr_name) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+impl Member {
+    /// Describe the file at `path` with metadata `md`.
+    pub fn from_metadata(path: &Path, md: &fs::Metadata) -> io::Result<Member> {
+        let ft = md.file_type();
+        let rdev = md.rdev();
+        let (major, minor) = (libc::major(rdev), libc::minor(rdev));
+
+        let kind = if ft.is_dir() {
+            MemberKind::Directory
+        } else if ft.is_symlink() {
+            MemberKind::Symlink(fs::read_link(path)?)
+        } else if ft.is_char_device() {
+            MemberKind::CharDevice(major, minor)
+        } else if ft.is_block_device() {
+            MemberKind::BlockDevice(major, minor)
+        } else if ft.is_fifo() {
+            MemberKind::Fifo
+        } else if ft.is_file() {
+            MemberKind::Regular
+        } else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "file type cannot be archived",
+            ));
+        };
+
+        let size = if kind == MemberKind::Regular {
+            md.size()
+        } else {
+            0
+        };
+
+        Ok(Member {
+            path: path.to_path_buf(),
+            kind,
+            mode: md.mode() & 0o7777,
+            uid: md.uid(),
+            gid: md.gid(),
+            uname: user_name(md.uid()),
+            gname: group_name(md.gid()),
+            size,
+            mtime: md.mtime(),
+            mtime_nsec: md.mtime_nsec() as u32,
+            atime: None,
+            dev: md.dev(),
+            ino: md.ino(),
+            nlink: md.nlink(),
+        })
+    }
+}
+
+/// Sequential access to the members of an archive.
+pub trait ArchiveReader {
+    /// Read the header of the next member, or return `None` at the end
+    /// of the archive.  Data of the previous member that was not read is
+    /// skipped.
+    fn next_member(&mut self) -> io::Result<Option<Member>>;
+
+    /// Copy the data of the current member to `out`.
+    fn read_data(&mut self, out: &mut dyn Write) -> io::Result<()>;
+}
+
+/// Sequential creation of an archive.
+pub trait ArchiveWriter {
+    /// Append `member`, followed by `member.size` bytes read from `data`.
+    ///
+    /// Nothing is written if the member cannot be represented in the
+    /// archive format.
+    fn write_member(&mut self, member: &Member, data: &mut dyn Read) -> io::Result<()>;
+
+    /// Write the end of archive marker, and flush the output.
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+/// Output that is written in blocks of a fixed size.
+///
+/// Flushing writes the pending partial block padded with zeros, so it must
+/// only be done at the end of the archive.
+pub struct BlockWriter<W: Write> {
+    inner: W,
+    buf: Vec<u8>,
+    block_size: usize,
+}
+
+impl<W: Write> BlockWriter<W> {
+    pub fn new(inner: W, block_size: usize) -> BlockWriter<W> {
+        BlockWriter {
+            inner,
+            buf: Vec::with_capacity(block_size),
+            block_size,
+        }
+    }
+}
+
+impl<W: Write> Write for BlockWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let n = data.len().min(self.block_size - self.buf.len());
+        self.buf.extend_from_slice(&data[..n]);
+        if self.buf.len() == self.block_size {
+            self.inner.write_all(&self.buf)?;
+            self.buf.clear();
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            self.buf.resize(self.block_size, 0);
+            self.inner.write_all(&self.buf)?;
+            self.buf.clear();
+        }
+        self.inner.flush()
+    }
+}
+
+/// Write `size` bytes from `data` to `out`.  If `data` ends early, the
+/// rest is filled with zeros, so the archive stays consistent with the
+/// header that was already written.
+pub fn copy_exact(data: &mut dyn Read, out: &mut dyn Write, size: u64) -> io::Result<()> {
+    let copied = io::copy(&mut data.take(size), out)?;
+    if copied < size {
+        io::copy(&mut io::repeat(0).take(size - copied), out)?;
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "file shrank while being archived",
+        ));
+    }
+    Ok(())
+}
+
+/// Open the archive read from `input`, detecting its format from the
+/// first record.
+pub fn open_reader(mut input: Box<dyn Read>) -> io::Result<Box<dyn ArchiveReader>> {
+    let mut record = vec![0u8; RECORD_SIZE];
+    let mut filled = 0;
+    while filled < RECORD_SIZE {
+        match input.read(&mut record[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    record.truncate(filled);
+
+    // an empty input, or one of zero records, is an empty archive
+    let format = if record.starts_with(cpio::MAGIC) {
+        Format::Cpio
+    } else if record.iter().all(|&b| b == 0) || ustar::is_header(&record) {
+        Format::Ustar
+    } else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "unknown archive format",
+        ));
+    };
+
+    // the record is read again by the format reader
+    let input = io::Cursor::new(record).chain(input);
+    Ok(match format {
+        Format::Cpio => Box::new(CpioReader::new(input)),
+        Format::Pax | Format::Ustar => Box::new(UstarReader::new(input)),
+    })
+}
+
+/// Create an archive in `format` written to `output`.
+pub fn open_writer(
+    format: Format,
+    output: Box<dyn Write>,
+    block_size: usize,
+) -> Box<dyn ArchiveWriter> {
+    let output = BlockWriter::new(output, block_size);
+    match format {
+        Format::Cpio => Box::new(CpioWriter::new(output)),
+        Format::Pax => Box::new(UstarWriter::new(output, true)),
+        Format::Ustar => Box::new(UstarWriter::new(output, false)),
+    }
+}
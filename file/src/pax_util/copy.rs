@@ -0,0 +1,67 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use super::archive::{ArchiveWriter, Member, MemberKind};
+use super::extract::Extractor;
+use std::fs;
+use std::io::{self, Read};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// Copy mode (-rw): the members written are extracted below a
+/// destination directory instead of being archived.
+pub struct Copier {
+    dest: PathBuf,
+    extractor: Extractor,
+
+    /// Whether setting the attributes of a directory failed.
+    pub failed: bool,
+}
+
+impl Copier {
+    pub fn new(dest: &Path, extractor: Extractor) -> Copier {
+        Copier {
+            dest: dest.to_path_buf(),
+            extractor,
+            failed: false,
+        }
+    }
+
+    fn target(&self, path: &Path) -> PathBuf {
+        self.dest.join(path.strip_prefix("/").unwrap_or(path))
+    }
+}
+
+impl ArchiveWriter for Copier {
+    fn write_member(&mut self, member: &Member, data: &mut dyn Read) -> io::Result<()> {
+        let mut member = member.clone();
+        member.path = self.target(&member.path);
+        if let MemberKind::HardLink(target) = &mut member.kind {
+            *target = self.target(target);
+        }
+
+        // the destination would be removed before it is copied
+        if let Ok(md) = fs::symlink_metadata(&member.path) {
+            if (md.dev(), md.ino()) == (member.dev, member.ino) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "source and destination are the same file",
+                ));
+            }
+        }
+
+        self.extractor
+            .extract(&member, &mut |file| io::copy(data, file).map(|_| ()))
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.failed = !self.extractor.finish();
+        Ok(())
+    }
+}
@@ -0,0 +1,424 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+//! The cpio interchange format, XCU pax "cpio Interchange Format": the
+//! portable ASCII ("odc") variant, with octal header fields.
+
+use super::archive::{copy_exact, ArchiveReader, ArchiveWriter, Member, MemberKind};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::io::{self, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+
+pub const MAGIC: &[u8] = b"070707";
+const TRAILER: &[u8] = b"TRAILER!!!";
+const HEADER_SIZE: usize = 76;
+
+// offset and length of each header field
+const DEV: (usize, usize) = (6, 6);
+const INO: (usize, usize) = (12, 6);
+const MODE: (usize, usize) = (18, 6);
+const UID: (usize, usize) = (24, 6);
+const GID: (usize, usize) = (30, 6);
+const NLINK: (usize, usize) = (36, 6);
+const RDEV: (usize, usize) = (42, 6);
+const MTIME: (usize, usize) = (48, 11);
+const NAMESIZE: (usize, usize) = (59, 6);
+const FILESIZE: (usize, usize) = (65, 11);
+
+// file types of the mode field, from <cpio.h>
+const C_ISDIR: u32 = 0o040000;
+const C_ISFIFO: u32 = 0o010000;
+const C_ISREG: u32 = 0o100000;
+const C_ISBLK: u32 = 0o060000;
+const C_ISCHR: u32 = 0o020000;
+const C_ISLNK: u32 = 0o120000;
+const C_ISMASK: u32 = 0o170000;
+
+// symbolic link targets larger than this are not believed
+const MAX_LINK_SIZE: u64 = 1 << 16;
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn parse_num(header: &[u8], (offset, len): (usize, usize)) -> io::Result<u64> {
+    std::str::from_utf8(&header[offset..offset + len])
+        .ok()
+        .and_then(|s| u64::from_str_radix(s, 8).ok())
+        .ok_or_else(|| invalid_data("invalid cpio header"))
+}
+
+fn read_full(inner: &mut dyn Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match inner.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Reader of a cpio archive.
+pub struct CpioReader<R: Read> {
+    inner: R,
+
+    // data bytes of the current member not read yet, and bytes to skip
+    // that are not part of its data
+    remaining: u64,
+    skip: u64,
+    done: bool,
+
+    // first pathname seen for each (dev, ino) with several links
+    links: HashMap<(u64, u64), PathBuf>,
+}
+
+impl<R: Read> CpioReader<R> {
+    pub fn new(inner: R) -> CpioReader<R> {
+        CpioReader {
+            inner,
+            remaining: 0,
+            skip: 0,
+            done: false,
+            links: HashMap::new(),
+        }
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        if read_full(&mut self.inner, buf)? < buf.len() {
+            return Err(invalid_data("unexpected end of archive"));
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> ArchiveReader for CpioReader<R> {
+    fn next_member(&mut self) -> io::Result<Option<Member>> {
+        let n = self.remaining + self.skip;
+        if io::copy(&mut (&mut self.inner).take(n), &mut io::sink())? < n {
+            return Err(invalid_data("unexpected end of archive"));
+        }
+        self.remaining = 0;
+        self.skip = 0;
+        if self.done {
+            return Ok(None);
+        }
+
+        // the archive ends with a trailer member
+        let mut header = [0u8; HEADER_SIZE];
+        self.read_exact(&mut header)?;
+        if !header.starts_with(MAGIC) {
+            return Err(invalid_data("invalid cpio header"));
+        }
+
+        let namesize = parse_num(&header, NAMESIZE)? as usize;
+        if namesize == 0 {
+            return Err(invalid_data("invalid cpio header"));
+        }
+        let mut name = vec![0u8; namesize];
+        self.read_exact(&mut name)?;
+        if let Some(end) = name.iter().position(|&b| b == 0) {
+            name.truncate(end);
+        }
+        if name == TRAILER {
+            self.done = true;
+            return Ok(None);
+        }
+        while name.len() > 1 && name.ends_with(b"/") {
+            name.pop();
+        }
+        let path = PathBuf::from(OsStr::from_bytes(&name));
+
+        let mode = parse_num(&header, MODE)? as u32;
+        let rdev = parse_num(&header, RDEV)?;
+        let (major, minor) = (libc::major(rdev), libc::minor(rdev));
+        let dev = parse_num(&header, DEV)?;
+        let ino = parse_num(&header, INO)?;
+        let nlink = parse_num(&header, NLINK)?;
+        let mut size = parse_num(&header, FILESIZE)?;
+
+        // unknown types are extracted as regular files
+        let mut kind = match mode & C_ISMASK {
+            C_ISDIR => MemberKind::Directory,
+            C_ISFIFO => MemberKind::Fifo,
+            C_ISCHR => MemberKind::CharDevice(major, minor),
+            C_ISBLK => MemberKind::BlockDevice(major, minor),
+            C_ISLNK => {
+                if size > MAX_LINK_SIZE {
+                    return Err(invalid_data("invalid cpio header"));
+                }
+                let mut target = vec![0u8; size as usize];
+                self.read_exact(&mut target)?;
+                size = 0;
+                MemberKind::Symlink(PathBuf::from(OsStr::from_bytes(&target)))
+            }
+            _ => MemberKind::Regular,
+        };
+
+        // later links to a file are stored without data
+        if nlink > 1 && kind != MemberKind::Directory {
+            match self.links.get(&(dev, ino)) {
+                Some(first) if size == 0 => kind = MemberKind::HardLink(first.clone()),
+                Some(_) => {}
+                None => {
+                    self.links.insert((dev, ino), path.clone());
+                }
+            }
+        }
+
+        if kind == MemberKind::Regular {
+            self.remaining = size;
+        } else {
+            self.skip = size;
+            size = 0;
+        }
+
+        Ok(Some(Member {
+            path,
+            kind,
+            mode: mode & 0o7777,
+            uid: parse_num(&header, UID)? as u32,
+            gid: parse_num(&header, GID)? as u32,
+            uname: String::new(),
+            gname: String::new(),
+            size,
+            mtime: parse_num(&header, MTIME)? as i64,
+            mtime_nsec: 0,
+            atime: None,
+            dev,
+            ino,
+            nlink,
+        }))
+    }
+
+    fn read_data(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        let n = io::copy(&mut (&mut self.inner).take(self.remaining), out)?;
+        if n < self.remaining {
+            return Err(invalid_data("unexpected end of archive"));
+        }
+        self.remaining = 0;
+        Ok(())
+    }
+}
+
+/// Writer of a cpio archive.
+pub struct CpioWriter<W: Write> {
+    inner: W,
+
+    // file serial numbers are renumbered, so they fit in the header; files
+    // with several links keep a common number
+    inos: HashMap<(u64, u64), u64>,
+    next_ino: u64,
+}
+
+impl<W: Write> CpioWriter<W> {
+    pub fn new(inner: W) -> CpioWriter<W> {
+        CpioWriter {
+            inner,
+            inos: HashMap::new(),
+            next_ino: 1,
+        }
+    }
+
+    fn new_ino(&mut self) -> u64 {
+        let ino = self.next_ino;
+        self.next_ino = self.next_ino % 0o777777 + 1;
+        ino
+    }
+}
+
+fn put_num(
+    header: &mut String,
+    (_, len): (usize, usize),
+    value: u64,
+    what: &str,
+) -> io::Result<()> {
+    let s = format!("{:0width$o}", value, width = len);
+    if s.len() > len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} too large for cpio format", what),
+        ));
+    }
+    header.push_str(&s);
+    Ok(())
+}
+
+/// Encode a cpio header; `fields` holds the values of the fields after
+/// the magic number, in order.
+fn build_header(fields: &[u64; 10]) -> io::Result<String> {
+    const FIELDS: [((usize, usize), &str); 10] = [
+        (DEV, "device number"),
+        (INO, "file serial number"),
+        (MODE, "mode"),
+        (UID, "user ID"),
+        (GID, "group ID"),
+        (NLINK, "link count"),
+        (RDEV, "device number"),
+        (MTIME, "modification time"),
+        (NAMESIZE, "pathname"),
+        (FILESIZE, "file size"),
+    ];
+
+    let mut header = String::from_utf8_lossy(MAGIC).into_owned();
+    for ((f, what), &value) in FIELDS.iter().zip(fields) {
+        put_num(&mut header, *f, value, what)?;
+    }
+    Ok(header)
+}
+
+impl<W: Write> ArchiveWriter for CpioWriter<W> {
+    fn write_member(&mut self, member: &Member, data: &mut dyn Read) -> io::Result<()> {
+        let (ftype, rdev) = match &member.kind {
+            MemberKind::Regular | MemberKind::HardLink(_) => (C_ISREG, 0),
+            MemberKind::Directory => (C_ISDIR, 0),
+            MemberKind::Symlink(_) => (C_ISLNK, 0),
+            MemberKind::CharDevice(major, minor) => (C_ISCHR, libc::makedev(*major, *minor)),
+            MemberKind::BlockDevice(major, minor) => (C_ISBLK, libc::makedev(*major, *minor)),
+            MemberKind::Fifo => (C_ISFIFO, 0),
+        };
+
+        // the target of a symbolic link is stored as its data
+        let target;
+        let (data, size): (&mut dyn Read, u64) = match &member.kind {
+            MemberKind::Symlink(t) => {
+                target = t.as_os_str().as_bytes();
+                (&mut &target[..], target.len() as u64)
+            }
+            MemberKind::HardLink(_) => (data, 0),
+            _ => (data, member.size),
+        };
+
+        let linked = member.kind != MemberKind::Directory && member.nlink > 1;
+        let ino = match self.inos.get(&(member.dev, member.ino)) {
+            Some(&ino) if linked => ino,
+            _ => {
+                let ino = self.new_ino();
+                if linked {
+                    self.inos.insert((member.dev, member.ino), ino);
+                }
+                ino
+            }
+        };
+
+        let name = member.path.as_os_str().as_bytes();
+        let header = build_header(&[
+            0,
+            ino,
+            (ftype | member.mode) as u64,
+            member.uid as u64,
+            member.gid as u64,
+            member.nlink,
+            rdev,
+            member.mtime.max(0) as u64,
+            name.len() as u64 + 1,
+            size,
+        ])?;
+
+        self.inner.write_all(header.as_bytes())?;
+        self.inner.write_all(name)?;
+        self.inner.write_all(b"\0")?;
+        copy_exact(data, &mut self.inner, size)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let header = build_header(&[0, 0, 0, 0, 0, 1, 0, 0, TRAILER.len() as u64 + 1, 0])?;
+        self.inner.write_all(header.as_bytes())?;
+        self.inner.write_all(TRAILER)?;
+        self.inner.write_all(b"\0")?;
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(path: &str, kind: MemberKind, ino: u64, nlink: u64) -> Member {
+        Member {
+            path: PathBuf::from(path),
+            kind,
+            mode: 0o644,
+            uid: 1000,
+            gid: 100,
+            uname: String::new(),
+            gname: String::new(),
+            size: 0,
+            mtime: 1_700_000_000,
+            mtime_nsec: 0,
+            atime: None,
+            dev: 1,
+            ino,
+            nlink,
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let mut file = member("a", MemberKind::Regular, 100_000_000, 2);
+        file.size = 3;
+        let link = member(
+            "b",
+            MemberKind::HardLink(PathBuf::from("a")),
+            100_000_000,
+            2,
+        );
+        let sym = member("c", MemberKind::Symlink(PathBuf::from("a")), 7, 1);
+        let dir = member("d", MemberKind::Directory, 8, 2);
+
+        let mut archive = Vec::new();
+        let mut writer = CpioWriter::new(&mut archive);
+        for m in [&file, &link, &sym, &dir] {
+            writer.write_member(m, &mut &b"abc"[..]).unwrap();
+        }
+        writer.finish().unwrap();
+        assert!(archive.starts_with(MAGIC));
+        assert_eq!(archive.len(), 5 * HEADER_SIZE + 2 + 2 + 2 + 2 + 11 + 3 + 1);
+
+        let mut reader = CpioReader::new(&archive[..]);
+        let a = reader.next_member().unwrap().unwrap();
+        assert_eq!(
+            (a.kind, a.size, a.mode, a.uid),
+            (MemberKind::Regular, 3, 0o644, 1000)
+        );
+        let mut data = Vec::new();
+        reader.read_data(&mut data).unwrap();
+        assert_eq!(data, b"abc");
+
+        let b = reader.next_member().unwrap().unwrap();
+        assert_eq!(b.kind, MemberKind::HardLink(PathBuf::from("a")));
+        let c = reader.next_member().unwrap().unwrap();
+        assert_eq!(
+            (c.kind, c.size),
+            (MemberKind::Symlink(PathBuf::from("a")), 0)
+        );
+        let d = reader.next_member().unwrap().unwrap();
+        assert_eq!(d.kind, MemberKind::Directory);
+        assert!(reader.next_member().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut reader = CpioReader::new(&b"070707000000"[..]);
+        assert!(reader.next_member().is_err());
+
+        let mut reader = CpioReader::new(&[b'x'; HEADER_SIZE][..]);
+        assert!(reader.next_member().is_err());
+
+        let mut m = member("a", MemberKind::Regular, 1, 1);
+        m.uid = 0o1000000;
+        let mut writer = CpioWriter::new(Vec::new());
+        let err = writer.write_member(&m, &mut io::empty()).unwrap_err();
+        assert_eq!(err.to_string(), "user ID too large for cpio format");
+    }
+}
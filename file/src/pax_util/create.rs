@@ -0,0 +1,195 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use super::archive::{ArchiveWriter, Member, MemberKind};
+use super::subst::Renamer;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// Which symbolic links are followed when walking file operands.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dereference {
+    Never,
+
+    /// Only those named on the command line (-H).
+    CommandLine,
+
+    /// All of them (-L).
+    Always,
+}
+
+/// Traversal of the file operands of -w and copy mode.
+pub struct FileWalker {
+    pub deref: Dereference,
+
+    /// Write directories without their entries (-d).
+    pub descend: bool,
+
+    /// Stay on the device of each operand (-X).
+    pub xdev: bool,
+}
+
+impl FileWalker {
+    /// Visit `root` and, for directories, the files below it, in
+    /// pre-order.  Errors are reported on stderr; returns false if there
+    /// were any.
+    pub fn walk<F>(&self, root: &Path, visit: &mut F) -> bool
+    where
+        F: FnMut(&Path, &fs::Metadata) -> bool,
+    {
+        let mut ancestors = Vec::new();
+        self.walk_path(root, true, None, &mut ancestors, visit)
+    }
+
+    fn walk_path<F>(
+        &self,
+        path: &Path,
+        top: bool,
+        root_dev: Option<u64>,
+        ancestors: &mut Vec<(u64, u64)>,
+        visit: &mut F,
+    ) -> bool
+    where
+        F: FnMut(&Path, &fs::Metadata) -> bool,
+    {
+        let follow = match self.deref {
+            Dereference::Always => true,
+            Dereference::CommandLine => top,
+            Dereference::Never => false,
+        };
+        let md = if follow {
+            fs::metadata(path).or_else(|_| fs::symlink_metadata(path))
+        } else {
+            fs::symlink_metadata(path)
+        };
+        let md = match md {
+            Ok(md) => md,
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                return false;
+            }
+        };
+
+        let mut success = visit(path, &md);
+
+        let root_dev = root_dev.unwrap_or(md.dev());
+        if !md.is_dir() || !self.descend || (self.xdev && md.dev() != root_dev) {
+            return success;
+        }
+
+        let id = (md.dev(), md.ino());
+        if ancestors.contains(&id) {
+            eprintln!("{}: file system loop detected", path.display());
+            return false;
+        }
+
+        // entries are sorted, so archives do not depend on directory order
+        let mut names = Vec::new();
+        match fs::read_dir(path) {
+            Ok(entries) => {
+                for entry in entries {
+                    match entry {
+                        Ok(entry) => names.push(entry.file_name()),
+                        Err(e) => {
+                            eprintln!("{}: {}", path.display(), e);
+                            success = false;
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                return false;
+            }
+        }
+        names.sort();
+
+        ancestors.push(id);
+        for name in names {
+            let child = path.join(name);
+            success &= self.walk_path(&child, false, Some(root_dev), ancestors, visit);
+        }
+        ancestors.pop();
+
+        success
+    }
+}
+
+/// Writes files to an archive, storing files with several links once.
+pub struct Creator<'a> {
+    writer: &'a mut dyn ArchiveWriter,
+    renamer: &'a Renamer,
+    verbose: bool,
+
+    // first pathname archived for each (dev, ino) with several links
+    links: HashMap<(u64, u64), PathBuf>,
+}
+
+impl<'a> Creator<'a> {
+    pub fn new(
+        writer: &'a mut dyn ArchiveWriter,
+        renamer: &'a Renamer,
+        verbose: bool,
+    ) -> Creator<'a> {
+        Creator {
+            writer,
+            renamer,
+            verbose,
+            links: HashMap::new(),
+        }
+    }
+
+    fn add_member(&mut self, path: &Path, md: &fs::Metadata) -> io::Result<()> {
+        let mut member = Member::from_metadata(path, md)?;
+        if !self.renamer.rename_member(&mut member) {
+            return Ok(());
+        }
+
+        let id = (md.dev(), md.ino());
+        let linked = !md.is_dir() && md.nlink() > 1;
+        if linked {
+            if let Some(first) = self.links.get(&id) {
+                member.kind = MemberKind::HardLink(first.clone());
+                member.size = 0;
+            }
+        }
+
+        if member.kind == MemberKind::Regular {
+            let mut file = fs::File::open(path)?;
+            self.writer.write_member(&member, &mut file)?;
+        } else {
+            self.writer.write_member(&member, &mut io::empty())?;
+        }
+
+        // later links refer to the first pathname actually archived
+        if linked && !self.links.contains_key(&id) {
+            self.links.insert(id, member.path);
+        }
+
+        if self.verbose {
+            eprintln!("{}", path.display());
+        }
+        Ok(())
+    }
+
+    /// Archive the file at `path`.  Errors are reported on stderr;
+    /// returns false if the file could not be archived.
+    pub fn add(&mut self, path: &Path, md: &fs::Metadata) -> bool {
+        match self.add_member(path, md) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,200 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+//! Records of the extended headers of the pax format, XCU pax "pax
+//! Extended Header".  Each record has the form `"%d %s=%s\n"`: its length
+//! in bytes, including the length itself, a keyword and a value.
+
+use super::archive::{Member, MemberKind};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+
+/// Keyword-value pairs of an extended header.
+pub type Records = HashMap<String, Vec<u8>>;
+
+fn invalid_record() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid extended header record")
+}
+
+/// Parse the data of an extended header into (keyword, value) pairs, in
+/// the order they appear.
+pub fn parse_records(mut data: &[u8]) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let mut records = Vec::new();
+
+    // the data may be padded with null bytes
+    while !data.is_empty() && data[0] != 0 {
+        let space = data
+            .iter()
+            .position(|&b| b == b' ')
+            .ok_or_else(invalid_record)?;
+        let len: usize = std::str::from_utf8(&data[..space])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(invalid_record)?;
+        if len <= space + 1 || len > data.len() || data[len - 1] != b'\n' {
+            return Err(invalid_record());
+        }
+
+        let record = &data[space + 1..len - 1];
+        let eq = record
+            .iter()
+            .position(|&b| b == b'=')
+            .ok_or_else(invalid_record)?;
+        let keyword = String::from_utf8_lossy(&record[..eq]).into_owned();
+        records.push((keyword, record[eq + 1..].to_vec()));
+
+        data = &data[len..];
+    }
+
+    Ok(records)
+}
+
+/// Format one record, computing its self-inclusive length.
+pub fn format_record(keyword: &str, value: &[u8]) -> Vec<u8> {
+    // keyword, value, space, '=' and newline
+    let base = keyword.len() + value.len() + 3;
+    let mut len = base + 1;
+    while base + len.to_string().len() != len {
+        len = base + len.to_string().len();
+    }
+
+    let mut record = format!("{} {}=", len, keyword).into_bytes();
+    record.extend_from_slice(value);
+    record.push(b'\n');
+    record
+}
+
+/// Parse a time value: seconds since the epoch, with an optional
+/// fraction.
+pub fn parse_time(value: &[u8]) -> Option<(i64, u32)> {
+    let s = std::str::from_utf8(value).ok()?;
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    let (int, frac) = s.split_once('.').unwrap_or((s, ""));
+    if int.is_empty() || !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let secs: i64 = int.parse().ok()?;
+    let mut nsec: u32 = 0;
+    for (i, b) in frac.bytes().take(9).enumerate() {
+        nsec += (b - b'0') as u32 * 10u32.pow(8 - i as u32);
+    }
+
+    if !negative {
+        Some((secs, nsec))
+    } else if nsec == 0 {
+        Some((-secs, 0))
+    } else {
+        Some((-secs - 1, 1_000_000_000 - nsec))
+    }
+}
+
+/// Format a time value, with as many fraction digits as needed.
+pub fn format_time(sec: i64, nsec: u32) -> String {
+    if nsec == 0 {
+        return sec.to_string();
+    }
+
+    // negative times are written as a negative decimal number
+    let (sign, sec, nsec) = if sec < 0 {
+        ("-", -(sec + 1), 1_000_000_000 - nsec)
+    } else {
+        ("", sec, nsec)
+    };
+    let frac = format!("{:09}", nsec);
+    format!("{}{}.{}", sign, sec, frac.trim_end_matches('0'))
+}
+
+fn parse_number(value: &[u8]) -> io::Result<u64> {
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(invalid_record)
+}
+
+/// Override the fields of `member` with the values of `records`.  Records
+/// with an empty value are ignored, so the header field is used.
+pub fn apply(member: &mut Member, records: &Records) -> io::Result<()> {
+    for (keyword, value) in records {
+        if value.is_empty() {
+            continue;
+        }
+
+        match keyword.as_str() {
+            "path" => member.path = PathBuf::from(OsStr::from_bytes(value)),
+            "linkpath" => {
+                let target = PathBuf::from(OsStr::from_bytes(value));
+                match &mut member.kind {
+                    MemberKind::Symlink(t) | MemberKind::HardLink(t) => *t = target,
+                    _ => {}
+                }
+            }
+            "size" => member.size = parse_number(value)?,
+            "uid" => member.uid = parse_number(value)? as u32,
+            "gid" => member.gid = parse_number(value)? as u32,
+            "uname" => member.uname = String::from_utf8_lossy(value).into_owned(),
+            "gname" => member.gname = String::from_utf8_lossy(value).into_owned(),
+            "mtime" => {
+                (member.mtime, member.mtime_nsec) = parse_time(value).ok_or_else(invalid_record)?;
+            }
+            "atime" => member.atime = Some(parse_time(value).ok_or_else(invalid_record)?),
+
+            // other keywords, such as charset or comment, do not affect
+            // extraction
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records() {
+        assert_eq!(format_record("path", b"abc"), b"12 path=abc\n");
+
+        // the length grows by a digit
+        let value = vec![b'x'; 91];
+        let record = format_record("path", &value);
+        assert_eq!(record.len(), 101);
+        assert!(record.starts_with(b"101 path="));
+
+        let mut data = format_record("path", b"a/b");
+        data.extend(format_record("mtime", b"1.5"));
+        data.extend([0u8; 10]);
+        let records = parse_records(&data).unwrap();
+        assert_eq!(records[0], (String::from("path"), b"a/b".to_vec()));
+        assert_eq!(records[1], (String::from("mtime"), b"1.5".to_vec()));
+
+        assert!(parse_records(b"99 path=a\n").is_err());
+        assert!(parse_records(b"10 pathab\n").is_err());
+    }
+
+    #[test]
+    fn test_times() {
+        assert_eq!(parse_time(b"1700000000"), Some((1_700_000_000, 0)));
+        assert_eq!(parse_time(b"1.25"), Some((1, 250_000_000)));
+        assert_eq!(parse_time(b"-1.25"), Some((-2, 750_000_000)));
+        assert_eq!(parse_time(b"1.1234567891"), Some((1, 123_456_789)));
+        assert_eq!(parse_time(b"x"), None);
+
+        assert_eq!(format_time(5, 0), "5");
+        assert_eq!(format_time(1, 250_000_000), "1.25");
+        assert_eq!(format_time(-2, 750_000_000), "-1.25");
+    }
+}
@@ -0,0 +1,275 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use super::archive::{Member, MemberKind};
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::Path;
+
+/// File characteristics restored by -r, selected with -p.
+#[derive(Clone, Copy, Debug)]
+pub struct Preserve {
+    pub atime: bool,
+    pub mtime: bool,
+    pub owner: bool,
+    pub mode: bool,
+}
+
+impl Preserve {
+    /// Parse the option-arguments of -p.  Later characters override
+    /// earlier ones.
+    pub fn parse(specs: &[String]) -> Result<Preserve, String> {
+        // by default, only the times are preserved
+        let mut preserve = Preserve {
+            atime: true,
+            mtime: true,
+            owner: false,
+            mode: false,
+        };
+
+        for c in specs.iter().flat_map(|s| s.chars()) {
+            match c {
+                'a' => preserve.atime = false,
+                'm' => preserve.mtime = false,
+                'o' => preserve.owner = true,
+                'p' => preserve.mode = true,
+                'e' => {
+                    preserve = Preserve {
+                        atime: true,
+                        mtime: true,
+                        owner: true,
+                        mode: true,
+                    }
+                }
+                _ => return Err(format!("-p: invalid option character: {}", c)),
+            }
+        }
+
+        Ok(preserve)
+    }
+}
+
+fn cstring(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))
+}
+
+fn check(res: libc::c_int) -> io::Result<()> {
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// the user ID to restore: the user name takes precedence over the ID
+fn lookup_uid(member: &Member) -> u32 {
+    if let Ok(name) = CString::new(member.uname.as_str()) {
+        let pwd = unsafe { libc::getpwnam(name.as_ptr()) };
+        if !member.uname.is_empty() && !pwd.is_null() {
+            return unsafe { (*pwd).pw_uid };
+        }
+    }
+    member.uid
+}
+
+fn lookup_gid(member: &Member) -> u32 {
+    if let Ok(name) = CString::new(member.gname.as_str()) {
+        let grp = unsafe { libc::getgrnam(name.as_ptr()) };
+        if !member.gname.is_empty() && !grp.is_null() {
+            return unsafe { (*grp).gr_gid };
+        }
+    }
+    member.gid
+}
+
+fn current_umask() -> u32 {
+    let mask = unsafe { libc::umask(0) };
+    unsafe { libc::umask(mask) };
+    mask as u32
+}
+
+// remove an existing file that is in the way, but never a directory
+fn remove_existing(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(md) if md.is_dir() => Err(io::Error::from_raw_os_error(libc::EEXIST)),
+        Ok(_) => fs::remove_file(path),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Creates files from the members of an archive.
+pub struct Extractor {
+    preserve: Preserve,
+
+    // do not overwrite existing files (-k)
+    keep: bool,
+    umask: u32,
+
+    // directories whose attributes are set once their entries are
+    // extracted, and whether they were created by us
+    dirs: Vec<(Member, bool)>,
+}
+
+impl Extractor {
+    pub fn new(preserve: Preserve, keep: bool) -> Extractor {
+        Extractor {
+            preserve,
+            keep,
+            umask: current_umask(),
+            dirs: Vec::new(),
+        }
+    }
+
+    // set ownership, mode and times of an extracted file
+    fn set_attributes(&self, member: &Member, created: bool) -> io::Result<()> {
+        let path = cstring(&member.path)?;
+        let is_symlink = matches!(member.kind, MemberKind::Symlink(_));
+
+        if self.preserve.owner {
+            let (uid, gid) = (lookup_uid(member), lookup_gid(member));
+            check(unsafe { libc::lchown(path.as_ptr(), uid, gid) })?;
+        }
+
+        // changing the owner clears the set-ID bits, so the mode follows
+        if !is_symlink {
+            if self.preserve.mode {
+                check(unsafe { libc::chmod(path.as_ptr(), member.mode as libc::mode_t) })?;
+            } else if created && member.kind == MemberKind::Directory {
+                let mode = member.mode & 0o777 & !self.umask;
+                check(unsafe { libc::chmod(path.as_ptr(), mode as libc::mode_t) })?;
+            }
+        }
+
+        // the access time is only stored by some formats
+        let timespec = |time: Option<(i64, u32)>| match time {
+            Some((sec, nsec)) => libc::timespec {
+                tv_sec: sec as libc::time_t,
+                tv_nsec: nsec as libc::c_long,
+            },
+            None => libc::timespec {
+                tv_sec: 0,
+                tv_nsec: libc::UTIME_OMIT,
+            },
+        };
+        let atime = member.atime.filter(|_| self.preserve.atime);
+        let mtime = Some((member.mtime, member.mtime_nsec)).filter(|_| self.preserve.mtime);
+        if atime.is_some() || mtime.is_some() {
+            let times = [timespec(atime), timespec(mtime)];
+            check(unsafe {
+                libc::utimensat(
+                    libc::AT_FDCWD,
+                    path.as_ptr(),
+                    times.as_ptr(),
+                    libc::AT_SYMLINK_NOFOLLOW,
+                )
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Create the file described by `member`.  The data of regular files
+    /// is written by `data`.
+    pub fn extract(
+        &mut self,
+        member: &Member,
+        data: &mut dyn FnMut(&mut fs::File) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let path = &member.path;
+        if path.as_os_str().is_empty() {
+            return Ok(());
+        }
+
+        // intermediate directories are created as needed
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        let exists = fs::symlink_metadata(path).ok();
+
+        if member.kind == MemberKind::Directory {
+            let created = match &exists {
+                Some(md) if md.is_dir() => false,
+                Some(_) if self.keep => return Ok(()),
+                _ => {
+                    remove_existing(path)?;
+                    fs::DirBuilder::new().mode(0o700).create(path)?;
+                    true
+                }
+            };
+            self.dirs.push((member.clone(), created));
+            return Ok(());
+        }
+
+        if exists.is_some() {
+            if self.keep {
+                return Ok(());
+            }
+            remove_existing(path)?;
+        }
+
+        let cpath = cstring(path)?;
+        let perm = (member.mode & 0o777) as libc::mode_t;
+        match &member.kind {
+            MemberKind::Regular => {
+                let mut file = fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .mode(perm as u32)
+                    .open(path)?;
+                data(&mut file)?;
+            }
+            MemberKind::HardLink(target) => {
+                fs::hard_link(target, path)?;
+
+                // the attributes are those of the file linked to
+                return Ok(());
+            }
+            MemberKind::Symlink(target) => std::os::unix::fs::symlink(target, path)?,
+            MemberKind::Fifo => check(unsafe { libc::mkfifo(cpath.as_ptr(), perm) })?,
+            MemberKind::CharDevice(major, minor) => check(unsafe {
+                libc::mknod(
+                    cpath.as_ptr(),
+                    libc::S_IFCHR | perm,
+                    libc::makedev(*major, *minor),
+                )
+            })?,
+            MemberKind::BlockDevice(major, minor) => check(unsafe {
+                libc::mknod(
+                    cpath.as_ptr(),
+                    libc::S_IFBLK | perm,
+                    libc::makedev(*major, *minor),
+                )
+            })?,
+            MemberKind::Directory => unreachable!(),
+        }
+
+        self.set_attributes(member, true)
+    }
+
+    /// Set the attributes of the extracted directories.  Errors are
+    /// reported on stderr; returns false if there were any.
+    pub fn finish(&mut self) -> bool {
+        let mut success = true;
+        let dirs = std::mem::take(&mut self.dirs);
+        for (member, created) in dirs.iter().rev() {
+            if let Err(e) = self.set_attributes(member, *created) {
+                eprintln!("{}: {}", member.path.display(), e);
+                success = false;
+            }
+        }
+        success
+    }
+}
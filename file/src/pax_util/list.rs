@@ -0,0 +1,122 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use super::archive::{Member, MemberKind};
+use chrono::{DateTime, Local, TimeZone};
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+
+const DATE_TIME_FORMAT_RECENT: &str = "%b %e %H:%M";
+const DATE_TIME_FORMAT_OLD_OR_FUTURE: &str = "%b %e  %Y";
+
+// six months, in seconds, as used by ls -l
+const SIX_MONTHS: i64 = 6 * 30 * 24 * 60 * 60;
+
+/// The file mode of `member` in the format of ls -l, e.g. `drwxr-xr-x`.
+pub fn mode_string(member: &Member) -> String {
+    let type_char = match member.kind {
+        MemberKind::Regular | MemberKind::HardLink(_) => '-',
+        MemberKind::Directory => 'd',
+        MemberKind::Symlink(_) => 'l',
+        MemberKind::CharDevice(..) => 'c',
+        MemberKind::BlockDevice(..) => 'b',
+        MemberKind::Fifo => 'p',
+    };
+
+    let mode = member.mode;
+    let bit = |mask: u32, c: char| if mode & mask != 0 { c } else { '-' };
+
+    // execute bit combined with a set-ID or sticky bit
+    let exec = |x: u32, special: u32, set: char| match (mode & x != 0, mode & special != 0) {
+        (true, true) => set,
+        (false, true) => set.to_ascii_uppercase(),
+        (true, false) => 'x',
+        (false, false) => '-',
+    };
+
+    let mut s = String::with_capacity(10);
+    s.push(type_char);
+    s.push(bit(0o400, 'r'));
+    s.push(bit(0o200, 'w'));
+    s.push(exec(0o100, 0o4000, 's'));
+    s.push(bit(0o040, 'r'));
+    s.push(bit(0o020, 'w'));
+    s.push(exec(0o010, 0o2000, 's'));
+    s.push(bit(0o004, 'r'));
+    s.push(bit(0o002, 'w'));
+    s.push(exec(0o001, 0o1000, 't'));
+    s
+}
+
+fn format_time(mtime: i64) -> String {
+    let now = Local::now().timestamp();
+    let format = if mtime <= now && now - mtime < SIX_MONTHS {
+        DATE_TIME_FORMAT_RECENT
+    } else {
+        DATE_TIME_FORMAT_OLD_OR_FUTURE
+    };
+    match Local.timestamp_opt(mtime, 0).single() {
+        Some(dt) => {
+            let dt: DateTime<Local> = dt;
+            dt.format(format).to_string()
+        }
+        None => mtime.to_string(),
+    }
+}
+
+fn owner(name: &str, id: u32) -> String {
+    if name.is_empty() {
+        id.to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Write the table of contents entry of `member` to stdout: only the
+/// pathname, or with `verbose`, a line in the format of ls -l.
+pub fn list_member(member: &Member, verbose: bool) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+
+    if verbose {
+        let size = match member.kind {
+            MemberKind::CharDevice(major, minor) | MemberKind::BlockDevice(major, minor) => {
+                format!("{}, {}", major, minor)
+            }
+            _ => member.size.to_string(),
+        };
+        write!(
+            out,
+            "{} {:>2} {:<8} {:<8} {:>8} {} ",
+            mode_string(member),
+            1,
+            owner(&member.uname, member.uid),
+            owner(&member.gname, member.gid),
+            size,
+            format_time(member.mtime)
+        )?;
+    }
+
+    out.write_all(member.path.as_os_str().as_bytes())?;
+
+    if verbose {
+        match &member.kind {
+            MemberKind::HardLink(target) => {
+                out.write_all(b" == ")?;
+                out.write_all(target.as_os_str().as_bytes())?;
+            }
+            MemberKind::Symlink(target) => {
+                out.write_all(b" -> ")?;
+                out.write_all(target.as_os_str().as_bytes())?;
+            }
+            _ => {}
+        }
+    }
+
+    out.write_all(b"\n")
+}
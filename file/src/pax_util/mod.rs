@@ -0,0 +1,19 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+pub mod archive;
+pub mod copy;
+pub mod cpio;
+pub mod create;
+pub mod exthdr;
+pub mod extract;
+pub mod list;
+pub mod select;
+pub mod subst;
+pub mod ustar;
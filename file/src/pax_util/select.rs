@@ -0,0 +1,133 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use plib::fnmatch::{MatchOptions, Pattern};
+use std::path::{Path, PathBuf};
+
+struct Operand {
+    text: String,
+    pattern: Pattern,
+
+    // the first member pathname selected by the pattern
+    matched: Option<PathBuf>,
+}
+
+/// Selection of archive members by the pattern operands of -r and list
+/// mode.
+pub struct Selector {
+    operands: Vec<Operand>,
+
+    /// Each pattern selects only the first member it matches (-n).
+    first_only: bool,
+
+    /// A pattern matching a directory selects the files below it, unless
+    /// -d is given.
+    descend: bool,
+}
+
+impl Selector {
+    pub fn new(patterns: &[String], first_only: bool, descend: bool) -> Selector {
+        let operands = patterns
+            .iter()
+            .map(|text| Operand {
+                text: text.clone(),
+                pattern: Pattern::new(text, MatchOptions::default()),
+                matched: None,
+            })
+            .collect();
+        Selector {
+            operands,
+            first_only,
+            descend,
+        }
+    }
+
+    /// Whether the member named `path` is selected.  Without patterns,
+    /// all members are.
+    pub fn select(&mut self, path: &Path) -> bool {
+        if self.operands.is_empty() {
+            return true;
+        }
+
+        for op in &mut self.operands {
+            let name = path.to_string_lossy();
+            if op.pattern.matches(&name) && !(self.first_only && op.matched.is_some()) {
+                op.matched.get_or_insert_with(|| path.to_path_buf());
+                return true;
+            }
+
+            if !self.descend {
+                continue;
+            }
+
+            // with -n, only below the directory that was matched first
+            for dir in path.ancestors().skip(1) {
+                if dir.as_os_str().is_empty() {
+                    break;
+                }
+                let selected = match &op.matched {
+                    Some(first) if self.first_only => dir == first,
+                    _ => op.pattern.matches(&dir.to_string_lossy()),
+                };
+                if selected {
+                    op.matched.get_or_insert_with(|| dir.to_path_buf());
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    /// The patterns that did not select any member.
+    pub fn unmatched(&self) -> impl Iterator<Item = &str> {
+        self.operands
+            .iter()
+            .filter(|op| op.matched.is_none())
+            .map(|op| op.text.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selected(selector: &mut Selector, paths: &[&str]) -> Vec<String> {
+        paths
+            .iter()
+            .filter(|p| selector.select(Path::new(p)))
+            .map(|p| p.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_select() {
+        let paths = ["a", "a/x.c", "a/y.h", "b", "b/z.c"];
+
+        let mut all = Selector::new(&[], false, true);
+        assert_eq!(selected(&mut all, &paths), paths);
+
+        // the slash is matched by wildcards
+        let mut sel = Selector::new(&[String::from("*.c")], false, true);
+        assert_eq!(selected(&mut sel, &paths), ["a/x.c", "b/z.c"]);
+
+        let mut sel = Selector::new(&[String::from("a"), String::from("q")], false, true);
+        assert_eq!(selected(&mut sel, &paths), ["a", "a/x.c", "a/y.h"]);
+        assert_eq!(sel.unmatched().collect::<Vec<_>>(), ["q"]);
+
+        let mut sel = Selector::new(&[String::from("a")], false, false);
+        assert_eq!(selected(&mut sel, &paths), ["a"]);
+
+        let mut sel = Selector::new(&[String::from("*.c")], true, true);
+        assert_eq!(selected(&mut sel, &paths), ["a/x.c"]);
+
+        let mut sel = Selector::new(&[String::from("?")], true, true);
+        assert_eq!(selected(&mut sel, &paths), ["a", "a/x.c", "a/y.h"]);
+    }
+}
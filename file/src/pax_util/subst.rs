@@ -0,0 +1,279 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+//! The pathname substitutions of -s, of the form `/old/new/[gp]`, where
+//! `old` is a basic regular expression.
+
+use super::archive::{Member, MemberKind};
+use plib::regex::Regex;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+enum Piece {
+    Literal(u8),
+
+    /// The match (`&`), or a subexpression (`\n`).
+    Group(usize),
+}
+
+/// One substitution expression of -s.
+pub struct Substitution {
+    regex: Regex,
+    replacement: Vec<Piece>,
+
+    /// Replace all matches, not only the first one (g flag).
+    global: bool,
+
+    /// Write each substitution made to stderr (p flag).
+    print: bool,
+}
+
+// split `s` at the first occurrence of `delim` not preceded by a
+// backslash, which is removed from escaped delimiters
+fn split_delimited(s: &[u8], delim: u8) -> Option<(Vec<u8>, &[u8])> {
+    let mut field = Vec::new();
+    let mut i = 0;
+    while i < s.len() {
+        match s[i] {
+            b'\\' if s.get(i + 1) == Some(&delim) => {
+                field.push(delim);
+                i += 2;
+            }
+            b'\\' if i + 1 < s.len() => {
+                field.extend_from_slice(&s[i..i + 2]);
+                i += 2;
+            }
+            b if b == delim => return Some((field, &s[i + 1..])),
+            b => {
+                field.push(b);
+                i += 1;
+            }
+        }
+    }
+    None
+}
+
+impl Substitution {
+    pub fn parse(spec: &str) -> Result<Substitution, String> {
+        let invalid = || format!("invalid substitution: {}", spec);
+
+        let bytes = spec.as_bytes();
+        let delim = match bytes.first() {
+            Some(&b) if b != b'\\' && b != b'\n' => b,
+            _ => return Err(invalid()),
+        };
+        let (old, rest) = split_delimited(&bytes[1..], delim).ok_or_else(invalid)?;
+        let (new, flags) = split_delimited(rest, delim).ok_or_else(invalid)?;
+
+        let regex = Regex::new(&old).map_err(|_| invalid())?;
+
+        let mut replacement = Vec::new();
+        let mut iter = new.iter();
+        while let Some(&b) = iter.next() {
+            match b {
+                b'&' => replacement.push(Piece::Group(0)),
+                b'\\' => match iter.next() {
+                    Some(&d) if d.is_ascii_digit() && d != b'0' => {
+                        let n = (d - b'0') as usize;
+                        if n > regex.groups() {
+                            return Err(invalid());
+                        }
+                        replacement.push(Piece::Group(n));
+                    }
+                    Some(&c) => replacement.push(Piece::Literal(c)),
+                    None => return Err(invalid()),
+                },
+                _ => replacement.push(Piece::Literal(b)),
+            }
+        }
+
+        let mut subst = Substitution {
+            regex,
+            replacement,
+            global: false,
+            print: false,
+        };
+        for &flag in flags {
+            match flag {
+                b'g' => subst.global = true,
+                b'p' => subst.print = true,
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(subst)
+    }
+
+    /// Apply the substitution to `name`, or return `None` if the regular
+    /// expression does not match.
+    pub fn apply(&self, name: &[u8]) -> Option<Vec<u8>> {
+        let mut result = Vec::new();
+        let mut pos = 0;
+        let mut matched = false;
+
+        while pos <= name.len() {
+            let Some(groups) = self.regex.exec(&name[pos..], pos > 0) else {
+                break;
+            };
+            let Some((start, end)) = groups[0] else {
+                break;
+            };
+            matched = true;
+
+            result.extend_from_slice(&name[pos..pos + start]);
+            for piece in &self.replacement {
+                match piece {
+                    Piece::Literal(b) => result.push(*b),
+                    Piece::Group(n) => {
+                        if let Some((s, e)) = groups[*n] {
+                            result.extend_from_slice(&name[pos + s..pos + e]);
+                        }
+                    }
+                }
+            }
+
+            // an empty match does not match again at the same position
+            if start == end {
+                if let Some(&b) = name.get(pos + end) {
+                    result.push(b);
+                }
+                pos += end + 1;
+            } else {
+                pos += end;
+            }
+
+            if !self.global {
+                break;
+            }
+        }
+
+        if !matched {
+            return None;
+        }
+        if pos < name.len() {
+            result.extend_from_slice(&name[pos..]);
+        }
+        Some(result)
+    }
+}
+
+/// The substitutions of -s, in the order they were given.
+#[derive(Default)]
+pub struct Renamer {
+    substs: Vec<Substitution>,
+}
+
+impl Renamer {
+    pub fn new(specs: &[String]) -> Result<Renamer, String> {
+        let substs = specs
+            .iter()
+            .map(|spec| Substitution::parse(spec))
+            .collect::<Result<_, _>>()?;
+        Ok(Renamer { substs })
+    }
+
+    // the first substitution that matches is applied; an empty result
+    // means the file is skipped
+    fn rename(&self, path: &Path, report: bool) -> Option<PathBuf> {
+        let old = path.as_os_str().as_bytes();
+        for subst in &self.substs {
+            if let Some(new) = subst.apply(old) {
+                let new = PathBuf::from(OsStr::from_bytes(&new));
+                if report && subst.print {
+                    eprintln!("{} >> {}", path.display(), new.display());
+                }
+                return (!new.as_os_str().is_empty()).then_some(new);
+            }
+        }
+        Some(path.to_path_buf())
+    }
+
+    /// Rename `member`, and the file it is a hard link to.  Returns false
+    /// if the member is to be skipped.
+    pub fn rename_member(&self, member: &mut Member) -> bool {
+        match self.rename(&member.path, true) {
+            Some(path) => member.path = path,
+            None => return false,
+        }
+        if let MemberKind::HardLink(target) = &mut member.kind {
+            if let Some(path) = self.rename(target, false) {
+                *target = path;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subst(spec: &str, name: &str) -> Option<String> {
+        let s = Substitution::parse(spec).unwrap();
+        s.apply(name.as_bytes())
+            .map(|v| String::from_utf8(v).unwrap())
+    }
+
+    #[test]
+    fn test_substitution() {
+        assert_eq!(subst("/a/b/", "banana").as_deref(), Some("bbnana"));
+        assert_eq!(subst("/a/b/g", "banana").as_deref(), Some("bbnbnb"));
+        assert_eq!(subst("/x/y/", "banana"), None);
+        assert_eq!(subst("/^src/dst/", "src/a").as_deref(), Some("dst/a"));
+        assert_eq!(subst("|/|_|g", "a/b/c").as_deref(), Some("a_b_c"));
+        assert_eq!(subst("/\\/b/X/", "a/b").as_deref(), Some("aX"));
+        assert_eq!(subst("/.*/[&]/", "ab").as_deref(), Some("[ab]"));
+        assert_eq!(
+            subst("/\\(a\\)\\(b\\)/\\2\\1\\&/", "xaby").as_deref(),
+            Some("xba&y")
+        );
+        assert_eq!(subst("/x*/-/g", "abc").as_deref(), Some("-a-b-c-"));
+        assert_eq!(subst("/^/p/g", "ab").as_deref(), Some("pab"));
+        assert_eq!(subst("/.*//", "abc").as_deref(), Some(""));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(Substitution::parse("").is_err());
+        assert!(Substitution::parse("/a/b").is_err());
+        assert!(Substitution::parse("/a/b/x").is_err());
+        assert!(Substitution::parse("/a/\\1/").is_err());
+        assert!(Substitution::parse("/\\(/b/").is_err());
+    }
+
+    #[test]
+    fn test_renamer() {
+        let specs = [String::from("/^a$//"), String::from("/a/b/")];
+        let renamer = Renamer::new(&specs).unwrap();
+
+        let mut member = Member {
+            path: PathBuf::from("a"),
+            kind: MemberKind::Regular,
+            mode: 0o644,
+            uid: 0,
+            gid: 0,
+            uname: String::new(),
+            gname: String::new(),
+            size: 0,
+            mtime: 0,
+            mtime_nsec: 0,
+            atime: None,
+            dev: 0,
+            ino: 0,
+            nlink: 1,
+        };
+        assert!(!renamer.rename_member(&mut member));
+
+        member.path = PathBuf::from("xa");
+        member.kind = MemberKind::HardLink(PathBuf::from("ya"));
+        assert!(renamer.rename_member(&mut member));
+        assert_eq!(member.path, PathBuf::from("xb"));
+        assert_eq!(member.kind, MemberKind::HardLink(PathBuf::from("yb")));
+    }
+}
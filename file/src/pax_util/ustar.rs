@@ -0,0 +1,734 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+//! The ustar interchange format, XCU pax "ustar Interchange Format", and
+//! the pax interchange format, which extends it with extended headers.
+
+use super::archive::{copy_exact, ArchiveReader, ArchiveWriter, Member, MemberKind, RECORD_SIZE};
+use super::exthdr::{self, Records};
+use std::ffi::OsStr;
+use std::io::{self, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+
+// offset and length of each header field
+const NAME: (usize, usize) = (0, 100);
+const MODE: (usize, usize) = (100, 8);
+const UID: (usize, usize) = (108, 8);
+const GID: (usize, usize) = (116, 8);
+const SIZE: (usize, usize) = (124, 12);
+const MTIME: (usize, usize) = (136, 12);
+const CHKSUM: (usize, usize) = (148, 8);
+const TYPEFLAG: usize = 156;
+const LINKNAME: (usize, usize) = (157, 100);
+const MAGIC: (usize, usize) = (257, 6);
+const VERSION: (usize, usize) = (263, 2);
+const UNAME: (usize, usize) = (265, 32);
+const GNAME: (usize, usize) = (297, 32);
+const DEVMAJOR: (usize, usize) = (329, 8);
+const DEVMINOR: (usize, usize) = (337, 8);
+const PREFIX: (usize, usize) = (345, 155);
+
+const TMAGIC: &[u8] = b"ustar\0";
+const TVERSION: &[u8] = b"00";
+
+// largest values of the numeric fields
+const MAX_ID: u64 = 0o7777777;
+const MAX_SIZE: u64 = 0o77777777777;
+
+// extended headers larger than this are not believed
+const MAX_EXTENDED_SIZE: u64 = 1 << 20;
+
+/// Number of padding bytes following `size` bytes of data.
+pub fn padding(size: u64) -> u64 {
+    (RECORD_SIZE as u64 - size % RECORD_SIZE as u64) % RECORD_SIZE as u64
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn field(header: &[u8], (offset, len): (usize, usize)) -> &[u8] {
+    &header[offset..offset + len]
+}
+
+// a string field, terminated by a null byte unless it fills the field
+fn parse_str(header: &[u8], f: (usize, usize)) -> &[u8] {
+    let bytes = field(header, f);
+    match bytes.iter().position(|&b| b == 0) {
+        Some(end) => &bytes[..end],
+        None => bytes,
+    }
+}
+
+// an octal number field, optionally surrounded by spaces and nulls.  The
+// base-256 encoding used by other implementations for large values is
+// also accepted.
+fn parse_num(header: &[u8], f: (usize, usize)) -> io::Result<u64> {
+    let bytes = field(header, f);
+    if bytes[0] & 0x80 != 0 {
+        let mut value: u64 = (bytes[0] & 0x3f) as u64;
+        for &b in &bytes[1..] {
+            value = value
+                .checked_mul(256)
+                .ok_or_else(|| invalid_data("numeric field overflow"))?
+                + b as u64;
+        }
+        return Ok(value);
+    }
+
+    let digits: Vec<u8> = bytes
+        .iter()
+        .copied()
+        .skip_while(|&b| b == b' ')
+        .take_while(|&b| b != b' ' && b != 0)
+        .collect();
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    let s = std::str::from_utf8(&digits).map_err(|_| invalid_data("invalid numeric field"))?;
+    u64::from_str_radix(s, 8).map_err(|_| invalid_data("invalid numeric field"))
+}
+
+fn checksum(header: &[u8]) -> u64 {
+    header
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| {
+            if (CHKSUM.0..CHKSUM.0 + CHKSUM.1).contains(&i) {
+                b' ' as u64
+            } else {
+                b as u64
+            }
+        })
+        .sum()
+}
+
+fn put_checksum(header: &mut [u8; RECORD_SIZE]) {
+    let sum = checksum(header);
+    let chksum = format!("{:06o}\0 ", sum);
+    header[CHKSUM.0..CHKSUM.0 + CHKSUM.1].copy_from_slice(chksum.as_bytes());
+}
+
+/// Whether `record` looks like a tar header: ustar, pax, or the older
+/// formats that only differ in the fields they use.
+pub fn is_header(record: &[u8]) -> bool {
+    if record.len() != RECORD_SIZE {
+        return false;
+    }
+    field(record, MAGIC).starts_with(b"ustar")
+        || matches!(parse_num(record, CHKSUM), Ok(sum) if sum == checksum(record))
+}
+
+/// Decode a ustar header record.
+pub fn parse_header(header: &[u8]) -> io::Result<Member> {
+    let sum = parse_num(header, CHKSUM)?;
+    if sum != checksum(header) {
+        return Err(invalid_data("header checksum error"));
+    }
+
+    let name = parse_str(header, NAME);
+    let prefix = parse_str(header, PREFIX);
+    let mut path = Vec::with_capacity(prefix.len() + 1 + name.len());
+    if !prefix.is_empty() {
+        path.extend_from_slice(prefix);
+        path.push(b'/');
+    }
+    path.extend_from_slice(name);
+
+    let linkname = PathBuf::from(OsStr::from_bytes(parse_str(header, LINKNAME)));
+    let devmajor = parse_num(header, DEVMAJOR)? as u32;
+    let devminor = parse_num(header, DEVMINOR)? as u32;
+
+    // unknown types are extracted as regular files
+    let kind = match header[TYPEFLAG] {
+        b'1' => MemberKind::HardLink(linkname),
+        b'2' => MemberKind::Symlink(linkname),
+        b'3' => MemberKind::CharDevice(devmajor, devminor),
+        b'4' => MemberKind::BlockDevice(devmajor, devminor),
+        b'5' => MemberKind::Directory,
+        b'6' => MemberKind::Fifo,
+        _ => MemberKind::Regular,
+    };
+
+    // a trailing slash only marks directories
+    while path.len() > 1 && path.ends_with(b"/") {
+        path.pop();
+    }
+
+    Ok(Member {
+        path: PathBuf::from(OsStr::from_bytes(&path)),
+        mode: parse_num(header, MODE)? as u32 & 0o7777,
+        uid: parse_num(header, UID)? as u32,
+        gid: parse_num(header, GID)? as u32,
+        uname: String::from_utf8_lossy(parse_str(header, UNAME)).into_owned(),
+        gname: String::from_utf8_lossy(parse_str(header, GNAME)).into_owned(),
+        size: parse_num(header, SIZE)?,
+        mtime: parse_num(header, MTIME)? as i64,
+        mtime_nsec: 0,
+        atime: None,
+        dev: 0,
+        ino: 0,
+        nlink: 1,
+        kind,
+    })
+}
+
+fn put_str(header: &mut [u8], (offset, len): (usize, usize), value: &[u8]) -> io::Result<()> {
+    if value.len() > len {
+        return Err(invalid_data("value too long for ustar header"));
+    }
+    header[offset..offset + value.len()].copy_from_slice(value);
+    Ok(())
+}
+
+// a zero-padded octal number, terminated by a null byte
+fn put_num(header: &mut [u8], f: (usize, usize), value: u64, what: &str) -> io::Result<()> {
+    let digits = f.1 - 1;
+    let s = format!("{:0width$o}", value, width = digits);
+    if s.len() > digits {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} too large for ustar format", what),
+        ));
+    }
+    put_str(header, f, s.as_bytes())
+}
+
+// split a pathname into the prefix and name fields
+fn split_path(path: &[u8]) -> Option<(&[u8], &[u8])> {
+    if path.len() <= NAME.1 {
+        return Some((b"", path));
+    }
+
+    // the shortest prefix that leaves a name which fits
+    let pos = path
+        .iter()
+        .enumerate()
+        .position(|(i, &b)| b == b'/' && path.len() - i - 1 <= NAME.1)?;
+    let (prefix, name) = (&path[..pos], &path[pos + 1..]);
+    if prefix.len() > PREFIX.1 || name.is_empty() {
+        return None;
+    }
+    Some((prefix, name))
+}
+
+/// Encode `member` as a ustar header record.
+pub fn build_header(member: &Member) -> io::Result<[u8; RECORD_SIZE]> {
+    let mut header = [0u8; RECORD_SIZE];
+
+    let mut path = member.path.as_os_str().as_bytes().to_vec();
+    if member.kind == MemberKind::Directory && !path.ends_with(b"/") {
+        path.push(b'/');
+    }
+    let (prefix, name) = split_path(&path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "pathname too long for ustar format",
+        )
+    })?;
+    put_str(&mut header, NAME, name)?;
+    put_str(&mut header, PREFIX, prefix)?;
+
+    let (typeflag, linkname, dev) = match &member.kind {
+        MemberKind::Regular => (b'0', None, None),
+        MemberKind::HardLink(target) => (b'1', Some(target), None),
+        MemberKind::Symlink(target) => (b'2', Some(target), None),
+        MemberKind::CharDevice(major, minor) => (b'3', None, Some((*major, *minor))),
+        MemberKind::BlockDevice(major, minor) => (b'4', None, Some((*major, *minor))),
+        MemberKind::Directory => (b'5', None, None),
+        MemberKind::Fifo => (b'6', None, None),
+    };
+    header[TYPEFLAG] = typeflag;
+    if let Some(target) = linkname {
+        put_str(&mut header, LINKNAME, target.as_os_str().as_bytes()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "link name too long for ustar format",
+            )
+        })?;
+    }
+    let (major, minor) = dev.unwrap_or((0, 0));
+
+    put_num(&mut header, MODE, member.mode as u64, "mode")?;
+    put_num(&mut header, UID, member.uid as u64, "user ID")?;
+    put_num(&mut header, GID, member.gid as u64, "group ID")?;
+    put_num(&mut header, SIZE, member.size, "file size")?;
+    put_num(
+        &mut header,
+        MTIME,
+        member.mtime.max(0) as u64,
+        "modification time",
+    )?;
+    put_num(&mut header, DEVMAJOR, major as u64, "device number")?;
+    put_num(&mut header, DEVMINOR, minor as u64, "device number")?;
+    put_str(&mut header, MAGIC, TMAGIC)?;
+    put_str(&mut header, VERSION, TVERSION)?;
+
+    // names that do not fit are left out; the IDs are used instead
+    let uname = member.uname.as_bytes();
+    if uname.len() < UNAME.1 {
+        put_str(&mut header, UNAME, uname)?;
+    }
+    let gname = member.gname.as_bytes();
+    if gname.len() < GNAME.1 {
+        put_str(&mut header, GNAME, gname)?;
+    }
+
+    put_checksum(&mut header);
+    Ok(header)
+}
+
+fn clip_name(name: &[u8], len: usize) -> Vec<u8> {
+    name[..name.len().min(len)].to_vec()
+}
+
+/// Split `member` into the extended header records needed to describe it
+/// in the pax format, and a member with the values that fit in a ustar
+/// header.
+pub fn extended_records(member: &Member) -> (Vec<u8>, Member) {
+    let mut records = Vec::new();
+    let mut clipped = member.clone();
+
+    let path = member.path.as_os_str().as_bytes();
+    let mut dir_path = path.to_vec();
+    if member.kind == MemberKind::Directory && !path.ends_with(b"/") {
+        dir_path.push(b'/');
+    }
+    if split_path(&dir_path).is_none() {
+        records.extend(exthdr::format_record("path", path));
+
+        // the last component, shortened to leave room for a slash
+        let base = member.path.file_name().map_or(path, |s| s.as_bytes());
+        clipped.path = PathBuf::from(OsStr::from_bytes(&clip_name(base, NAME.1 - 1)));
+    }
+
+    match &mut clipped.kind {
+        MemberKind::Symlink(target) | MemberKind::HardLink(target)
+            if target.as_os_str().len() > LINKNAME.1 =>
+        {
+            let bytes = target.as_os_str().as_bytes();
+            records.extend(exthdr::format_record("linkpath", bytes));
+            *target = PathBuf::from(OsStr::from_bytes(&clip_name(bytes, LINKNAME.1)));
+        }
+        _ => {}
+    }
+
+    if member.size > MAX_SIZE {
+        records.extend(exthdr::format_record(
+            "size",
+            member.size.to_string().as_bytes(),
+        ));
+        clipped.size = 0;
+    }
+    if member.uid as u64 > MAX_ID {
+        records.extend(exthdr::format_record(
+            "uid",
+            member.uid.to_string().as_bytes(),
+        ));
+        clipped.uid = 0;
+    }
+    if member.gid as u64 > MAX_ID {
+        records.extend(exthdr::format_record(
+            "gid",
+            member.gid.to_string().as_bytes(),
+        ));
+        clipped.gid = 0;
+    }
+    if member.uname.len() >= UNAME.1 {
+        records.extend(exthdr::format_record("uname", member.uname.as_bytes()));
+        clipped.uname.clear();
+    }
+    if member.gname.len() >= GNAME.1 {
+        records.extend(exthdr::format_record("gname", member.gname.as_bytes()));
+        clipped.gname.clear();
+    }
+
+    if member.mtime_nsec != 0 || member.mtime < 0 || member.mtime as u64 > MAX_SIZE {
+        let mtime = exthdr::format_time(member.mtime, member.mtime_nsec);
+        records.extend(exthdr::format_record("mtime", mtime.as_bytes()));
+        clipped.mtime = member.mtime.clamp(0, MAX_SIZE as i64);
+        clipped.mtime_nsec = 0;
+    }
+    if let Some((sec, nsec)) = member.atime {
+        let atime = exthdr::format_time(sec, nsec);
+        records.extend(exthdr::format_record("atime", atime.as_bytes()));
+    }
+
+    (records, clipped)
+}
+
+/// Build the header of the extended header of `member`, which has `size`
+/// bytes of records.  It is named `dir/PaxHeaders.pid/name`, as suggested
+/// by XCU pax.
+fn build_extended_header(member: &Member, size: u64) -> io::Result<[u8; RECORD_SIZE]> {
+    let path = member.path.as_os_str().as_bytes();
+    let base = member.path.file_name().map_or(path, |s| s.as_bytes());
+    let mut name = format!("PaxHeaders.{}/", std::process::id()).into_bytes();
+    name.extend(clip_name(base, NAME.1 - name.len()));
+
+    let dir = member
+        .path
+        .parent()
+        .map_or(&b""[..], |p| p.as_os_str().as_bytes());
+    let mut full = dir.to_vec();
+    if !full.is_empty() {
+        full.push(b'/');
+    }
+    full.extend_from_slice(&name);
+    if split_path(&full).is_some() {
+        name = full;
+    }
+
+    let ext = Member {
+        path: PathBuf::from(OsStr::from_bytes(&name)),
+        kind: MemberKind::Regular,
+        mode: 0o644,
+        size,
+        atime: None,
+        ..member.clone()
+    };
+    let mut header = build_header(&ext)?;
+    header[TYPEFLAG] = b'x';
+    put_checksum(&mut header);
+    Ok(header)
+}
+
+/// Reader of a ustar or pax archive.  The long name entries of GNU tar
+/// are understood as well.
+pub struct UstarReader<R: Read> {
+    inner: R,
+
+    // data and padding bytes of the current member not read yet
+    remaining: u64,
+    padding: u64,
+
+    // records of the global extended headers read so far
+    globals: Records,
+}
+
+impl<R: Read> UstarReader<R> {
+    pub fn new(inner: R) -> UstarReader<R> {
+        UstarReader {
+            inner,
+            remaining: 0,
+            padding: 0,
+            globals: Records::new(),
+        }
+    }
+
+    // read the data of an extended header, or of a GNU long name
+    fn read_extended(&mut self, size: u64) -> io::Result<Vec<u8>> {
+        if size > MAX_EXTENDED_SIZE {
+            return Err(invalid_data("extended header too large"));
+        }
+        let mut data = Vec::with_capacity(size as usize);
+        (&mut self.inner).take(size).read_to_end(&mut data)?;
+        if (data.len() as u64) < size {
+            return Err(invalid_data("unexpected end of archive"));
+        }
+        self.skip(padding(size))?;
+        Ok(data)
+    }
+
+    fn skip(&mut self, n: u64) -> io::Result<()> {
+        let skipped = io::copy(&mut (&mut self.inner).take(n), &mut io::sink())?;
+        if skipped < n {
+            return Err(invalid_data("unexpected end of archive"));
+        }
+        Ok(())
+    }
+
+    // read a whole record, or return false at the end of input
+    fn read_record(&mut self, record: &mut [u8; RECORD_SIZE]) -> io::Result<bool> {
+        let mut filled = 0;
+        while filled < RECORD_SIZE {
+            match self.inner.read(&mut record[filled..]) {
+                Ok(0) if filled == 0 => return Ok(false),
+                Ok(0) => return Err(invalid_data("unexpected end of archive")),
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl<R: Read> ArchiveReader for UstarReader<R> {
+    fn next_member(&mut self) -> io::Result<Option<Member>> {
+        self.skip(self.remaining + self.padding)?;
+        self.remaining = 0;
+        self.padding = 0;
+
+        let mut locals = Records::new();
+        let mut long_name = None;
+        let mut long_link = None;
+
+        let mut member = loop {
+            let mut header = [0u8; RECORD_SIZE];
+            if !self.read_record(&mut header)? {
+                if !locals.is_empty() || long_name.is_some() || long_link.is_some() {
+                    return Err(invalid_data("unexpected end of archive"));
+                }
+                return Ok(None);
+            }
+
+            // the archive ends with two zero records; the second one, and
+            // any padding of the last block, is not read
+            if header.iter().all(|&b| b == 0) {
+                return Ok(None);
+            }
+
+            let member = parse_header(&header)?;
+            match header[TYPEFLAG] {
+                b'x' => {
+                    let data = self.read_extended(member.size)?;
+                    locals.extend(exthdr::parse_records(&data)?);
+                }
+                b'g' => {
+                    // an empty value removes a global record
+                    let data = self.read_extended(member.size)?;
+                    for (keyword, value) in exthdr::parse_records(&data)? {
+                        if value.is_empty() {
+                            self.globals.remove(&keyword);
+                        } else {
+                            self.globals.insert(keyword, value);
+                        }
+                    }
+                }
+                b'L' | b'K' => {
+                    let mut data = self.read_extended(member.size)?;
+                    if let Some(end) = data.iter().position(|&b| b == 0) {
+                        data.truncate(end);
+                    }
+                    if header[TYPEFLAG] == b'L' {
+                        long_name = Some(data);
+                    } else {
+                        long_link = Some(data);
+                    }
+                }
+                _ => break member,
+            }
+        };
+
+        if let Some(name) = long_name {
+            locals.entry(String::from("path")).or_insert(name);
+        }
+        if let Some(name) = long_link {
+            locals.entry(String::from("linkpath")).or_insert(name);
+        }
+        exthdr::apply(&mut member, &self.globals)?;
+        exthdr::apply(&mut member, &locals)?;
+
+        let mut path = member.path.as_os_str().as_bytes();
+        while path.len() > 1 && path.ends_with(b"/") {
+            path = &path[..path.len() - 1];
+        }
+        member.path = PathBuf::from(OsStr::from_bytes(path));
+
+        if member.kind == MemberKind::Regular {
+            self.remaining = member.size;
+            self.padding = padding(member.size);
+        } else {
+            // data is stored for other types too, if the size is set
+            self.padding = member.size + padding(member.size);
+        }
+        Ok(Some(member))
+    }
+
+    fn read_data(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        let n = io::copy(&mut (&mut self.inner).take(self.remaining), out)?;
+        if n < self.remaining {
+            return Err(invalid_data("unexpected end of archive"));
+        }
+        self.remaining = 0;
+        Ok(())
+    }
+}
+
+/// Writer of a ustar archive, or of a pax archive when extended headers
+/// are enabled.
+pub struct UstarWriter<W: Write> {
+    inner: W,
+    extended: bool,
+}
+
+impl<W: Write> UstarWriter<W> {
+    pub fn new(inner: W, extended: bool) -> UstarWriter<W> {
+        UstarWriter { inner, extended }
+    }
+}
+
+impl<W: Write> ArchiveWriter for UstarWriter<W> {
+    fn write_member(&mut self, member: &Member, data: &mut dyn Read) -> io::Result<()> {
+        let (records, header) = if self.extended {
+            let (records, clipped) = extended_records(member);
+            (records, build_header(&clipped)?)
+        } else {
+            (Vec::new(), build_header(member)?)
+        };
+
+        if !records.is_empty() {
+            let size = records.len() as u64;
+            self.inner
+                .write_all(&build_extended_header(member, size)?)?;
+            self.inner.write_all(&records)?;
+            self.inner
+                .write_all(&[0u8; RECORD_SIZE][..padding(size) as usize])?;
+        }
+        self.inner.write_all(&header)?;
+
+        let result = copy_exact(data, &mut self.inner, member.size);
+        self.inner
+            .write_all(&[0u8; RECORD_SIZE][..padding(member.size) as usize])?;
+        result
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.inner.write_all(&[0u8; 2 * RECORD_SIZE])?;
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(path: &str, kind: MemberKind) -> Member {
+        Member {
+            path: PathBuf::from(path),
+            kind,
+            mode: 0o644,
+            uid: 1000,
+            gid: 100,
+            uname: String::from("user"),
+            gname: String::from("users"),
+            size: 0,
+            mtime: 1_700_000_000,
+            mtime_nsec: 0,
+            atime: None,
+            dev: 0,
+            ino: 0,
+            nlink: 1,
+        }
+    }
+
+    #[test]
+    fn test_header_roundtrip() {
+        let mut m = member("dir/file.txt", MemberKind::Regular);
+        m.size = 1234;
+        let header = build_header(&m).unwrap();
+        assert_eq!(field(&header, MAGIC), TMAGIC);
+
+        let parsed = parse_header(&header).unwrap();
+        assert_eq!(parsed.path, m.path);
+        assert_eq!(parsed.kind, m.kind);
+        assert_eq!(parsed.mode, 0o644);
+        assert_eq!(parsed.uid, 1000);
+        assert_eq!(parsed.gname, "users");
+        assert_eq!(parsed.size, 1234);
+        assert_eq!(parsed.mtime, 1_700_000_000);
+
+        let link = member("a", MemberKind::Symlink(PathBuf::from("target")));
+        let parsed = parse_header(&build_header(&link).unwrap()).unwrap();
+        assert_eq!(parsed.kind, link.kind);
+
+        let dir = member("a/b", MemberKind::Directory);
+        let header = build_header(&dir).unwrap();
+        assert_eq!(parse_str(&header, NAME), b"a/b/");
+        assert_eq!(parse_header(&header).unwrap().path, PathBuf::from("a/b"));
+    }
+
+    #[test]
+    fn test_long_names() {
+        let long = format!("{}/{}", "p".repeat(150), "n".repeat(100));
+        let header = build_header(&member(&long, MemberKind::Regular)).unwrap();
+        assert_eq!(parse_str(&header, PREFIX).len(), 150);
+        assert_eq!(parse_header(&header).unwrap().path, PathBuf::from(&long));
+
+        // a component that does not fit in the name field
+        assert!(build_header(&member(&"n".repeat(101), MemberKind::Regular)).is_err());
+
+        // a prefix that does not fit
+        let long = format!("{}/{}", "p".repeat(156), "n");
+        assert!(build_header(&member(&long, MemberKind::Regular)).is_err());
+
+        let target = PathBuf::from("t".repeat(101));
+        assert!(build_header(&member("a", MemberKind::Symlink(target))).is_err());
+    }
+
+    #[test]
+    fn test_checksum_error() {
+        let mut header = build_header(&member("a", MemberKind::Regular)).unwrap();
+        assert!(is_header(&header));
+        header[0] = b'b';
+        assert!(parse_header(&header).is_err());
+
+        // the magic is enough to recognize the format
+        assert!(is_header(&header));
+        header[MAGIC.0] = b'x';
+        assert!(!is_header(&header));
+    }
+
+    fn roundtrip(members: &[Member]) -> Vec<Member> {
+        let mut archive = Vec::new();
+        let mut writer = UstarWriter::new(&mut archive, true);
+        for m in members {
+            writer.write_member(m, &mut io::repeat(b'x')).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut reader = UstarReader::new(&archive[..]);
+        let mut result = Vec::new();
+        while let Some(m) = reader.next_member().unwrap() {
+            result.push(m);
+        }
+        result
+    }
+
+    #[test]
+    fn test_extended_headers() {
+        let long = format!("{}/{}", "d".repeat(200), "f".repeat(150));
+        let mut file = member(&long, MemberKind::Regular);
+        file.size = 10;
+        file.mtime_nsec = 500_000_000;
+        file.uname = "u".repeat(40);
+        let target = PathBuf::from("t".repeat(300));
+        let link = member("link", MemberKind::Symlink(target.clone()));
+        let plain = member("plain", MemberKind::Regular);
+
+        let result = roundtrip(&[file, link, plain]);
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].path, PathBuf::from(&long));
+        assert_eq!(result[0].size, 10);
+        assert_eq!(
+            (result[0].mtime, result[0].mtime_nsec),
+            (1_700_000_000, 500_000_000)
+        );
+        assert_eq!(result[0].uname, "u".repeat(40));
+        assert_eq!(result[1].kind, MemberKind::Symlink(target));
+
+        // extended header records apply to one member only
+        assert_eq!(result[2].path, PathBuf::from("plain"));
+        assert_eq!(result[2].mtime_nsec, 0);
+    }
+
+    #[test]
+    fn test_no_extended_header() {
+        // members that fit in a ustar header are written without one
+        let mut archive = Vec::new();
+        let mut writer = UstarWriter::new(&mut archive, true);
+        writer
+            .write_member(&member("a", MemberKind::Regular), &mut io::empty())
+            .unwrap();
+        assert_eq!(archive.len(), RECORD_SIZE);
+        assert_eq!(archive[TYPEFLAG], b'0');
+    }
+}
@@ -7,6 +7,8 @@
 // SPDX-License-Identifier: MIT
 //
 
+mod pax;
+
 use std::{env, path::PathBuf};
 
 use plib::{run_test, TestPlan};
@@ -0,0 +1,447 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+// Run pax in `dir`, feeding `stdin` to it
+fn pax(dir: &str, args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pax"))
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout_lines(output: &Output) -> Vec<String> {
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(String::from)
+        .collect()
+}
+
+// Create this hierarchy under dir/src:
+//   a.txt      "hello\n", mode 0640
+//   hard       hard link to a.txt
+//   sym        symbolic link to a.txt
+//   sub/       directory, mode 0750
+//   sub/b.txt  empty
+fn create_tree(dir: &str) {
+    let src = format!("{dir}/src");
+    fs::create_dir_all(format!("{src}/sub")).unwrap();
+    fs::write(format!("{src}/a.txt"), b"hello\n").unwrap();
+    fs::set_permissions(format!("{src}/a.txt"), fs::Permissions::from_mode(0o640)).unwrap();
+    fs::hard_link(format!("{src}/a.txt"), format!("{src}/hard")).unwrap();
+    std::os::unix::fs::symlink("a.txt", format!("{src}/sym")).unwrap();
+    fs::write(format!("{src}/sub/b.txt"), b"").unwrap();
+    fs::set_permissions(format!("{src}/sub"), fs::Permissions::from_mode(0o750)).unwrap();
+}
+
+#[test]
+fn test_pax_write_list() {
+    let dir = &format!("{}/test_pax_write_list", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    create_tree(dir);
+
+    let output = pax(dir, &["-w", "-f", "archive.tar", "src"], b"");
+    assert_eq!(output.status.code(), Some(0));
+
+    // the archive is a whole number of 10240-byte blocks
+    let size = fs::metadata(format!("{dir}/archive.tar")).unwrap().len();
+    assert_eq!(size % 10240, 0);
+
+    let output = pax(dir, &["-f", "archive.tar"], b"");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout_lines(&output),
+        [
+            "src",
+            "src/a.txt",
+            "src/hard",
+            "src/sub",
+            "src/sub/b.txt",
+            "src/sym"
+        ]
+    );
+
+    let output = pax(dir, &["-v", "-f", "archive.tar"], b"");
+    let lines = stdout_lines(&output);
+    assert!(lines[0].starts_with("drwxr-xr-x"));
+    assert!(lines[1].starts_with("-rw-r-----"));
+    assert!(lines[1].contains(" 6 "));
+    assert!(lines[2].ends_with("src/hard == src/a.txt"));
+    assert!(lines[3].starts_with("drwxr-x---"));
+    assert!(lines[5].ends_with("src/sym -> a.txt"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_pax_write_stdin_list() {
+    let dir = &format!("{}/test_pax_write_stdin_list", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    create_tree(dir);
+
+    // without operands, pathnames are read from standard input
+    let output = pax(dir, &["-w", "-b", "1k"], b"src/a.txt\nsrc/sym\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(output.stdout.len() % 1024, 0);
+    fs::write(format!("{dir}/archive.tar"), &output.stdout).unwrap();
+
+    let output = pax(dir, &["-f", "archive.tar"], b"");
+    assert_eq!(stdout_lines(&output), ["src/a.txt", "src/sym"]);
+
+    // -d archives directories without their contents
+    let output = pax(dir, &["-w", "-d", "src"], b"");
+    let output = pax(dir, &[], &output.stdout);
+    assert_eq!(stdout_lines(&output), ["src"]);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_pax_read() {
+    let dir = &format!("{}/test_pax_read", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    create_tree(dir);
+
+    let src_mtime = fs::metadata(format!("{dir}/src/a.txt")).unwrap().mtime();
+    let archive = pax(dir, &["-w", "src"], b"").stdout;
+
+    let out = &format!("{dir}/out");
+    fs::create_dir(out).unwrap();
+    let output = pax(out, &["-r", "-v"], &archive);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "src\nsrc/a.txt\nsrc/hard\nsrc/sub\nsrc/sub/b.txt\nsrc/sym\n"
+    );
+
+    assert_eq!(fs::read(format!("{out}/src/a.txt")).unwrap(), b"hello\n");
+    let a = fs::metadata(format!("{out}/src/a.txt")).unwrap();
+    let hard = fs::metadata(format!("{out}/src/hard")).unwrap();
+    assert_eq!(a.ino(), hard.ino());
+    assert_eq!(a.mtime(), src_mtime);
+    assert_eq!(
+        fs::read_link(format!("{out}/src/sym")).unwrap(),
+        Path::new("a.txt")
+    );
+    assert!(Path::new(&format!("{out}/src/sub/b.txt")).exists());
+
+    // -p p restores the mode bits exactly
+    let out = &format!("{dir}/out_p");
+    fs::create_dir(out).unwrap();
+    let output = pax(out, &["-r", "-p", "p"], &archive);
+    assert_eq!(output.status.code(), Some(0));
+    let sub = fs::metadata(format!("{out}/src/sub")).unwrap();
+    assert_eq!(sub.mode() & 0o7777, 0o750);
+
+    // -k does not overwrite existing files
+    fs::write(format!("{out}/src/a.txt"), b"changed\n").unwrap();
+    let output = pax(out, &["-r", "-k"], &archive);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(fs::read(format!("{out}/src/a.txt")).unwrap(), b"changed\n");
+
+    let output = pax(out, &["-r"], &archive);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(fs::read(format!("{out}/src/a.txt")).unwrap(), b"hello\n");
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_pax_long_names() {
+    let dir = &format!("{}/test_pax_long_names", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+
+    // a long pathname is split between the prefix and name fields
+    let deep = format!("{}/{}", "d".repeat(120), "f".repeat(90));
+    fs::create_dir_all(format!("{dir}/{}", "d".repeat(120))).unwrap();
+    fs::write(format!("{dir}/{deep}"), b"").unwrap();
+
+    // a component that does not fit in the name field
+    let long = "n".repeat(101);
+    fs::write(format!("{dir}/{long}"), b"").unwrap();
+
+    let output = pax(dir, &["-w", &deep, &long], b"");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!("{long}: pathname too long for ustar format\n")
+    );
+
+    let output = pax(dir, &[], &output.stdout);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout_lines(&output), [deep]);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_pax_invalid_archive() {
+    let dir = &format!("{}/test_pax_invalid_archive", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    create_tree(dir);
+
+    let archive = pax(dir, &["-w", "src/a.txt"], b"").stdout;
+
+    // truncated in the middle of the data
+    let output = pax(dir, &["-r"], &archive[..600]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "pax: stdin: unexpected end of archive\n"
+    );
+
+    let mut corrupt = archive.clone();
+    corrupt[0] = b'x';
+    let output = pax(dir, &[], &corrupt);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "pax: stdin: header checksum error\n"
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_pax_cpio() {
+    let dir = &format!("{}/test_pax_cpio", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    create_tree(dir);
+
+    let output = pax(dir, &["-w", "-x", "cpio", "src"], b"");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.starts_with(b"070707"));
+    assert_eq!(output.stdout.len() % 5120, 0);
+    let archive = output.stdout;
+
+    // the format is detected when reading
+    let output = pax(dir, &["-v"], &archive);
+    assert_eq!(output.status.code(), Some(0));
+    let lines = stdout_lines(&output);
+    assert_eq!(lines.len(), 6);
+    assert!(lines[1].starts_with("-rw-r-----"));
+    assert!(lines[2].ends_with("src/hard == src/a.txt"));
+    assert!(lines[5].ends_with("src/sym -> a.txt"));
+
+    let out = &format!("{dir}/out");
+    fs::create_dir(out).unwrap();
+    let output = pax(out, &["-r"], &archive);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(fs::read(format!("{out}/src/a.txt")).unwrap(), b"hello\n");
+    let a = fs::metadata(format!("{out}/src/a.txt")).unwrap();
+    let hard = fs::metadata(format!("{out}/src/hard")).unwrap();
+    assert_eq!(a.ino(), hard.ino());
+    assert_eq!(
+        fs::read_link(format!("{out}/src/sym")).unwrap(),
+        Path::new("a.txt")
+    );
+    assert!(Path::new(&format!("{out}/src/sub/b.txt")).exists());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_pax_extended_headers() {
+    let dir = &format!("{}/test_pax_extended_headers", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+
+    // too long for the ustar format
+    let parent = (0..3)
+        .map(|_| "d".repeat(100))
+        .collect::<Vec<_>>()
+        .join("/");
+    let long = format!("{parent}/{}", "f".repeat(120));
+    fs::create_dir_all(format!("{dir}/{parent}")).unwrap();
+    fs::write(format!("{dir}/{long}"), b"data\n").unwrap();
+
+    // a modification time with a fraction of a second
+    let file = fs::File::options()
+        .write(true)
+        .open(format!("{dir}/{long}"))
+        .unwrap();
+    let mtime = std::time::UNIX_EPOCH + std::time::Duration::new(1_700_000_000, 123_456_789);
+    file.set_modified(mtime).unwrap();
+
+    let output = pax(dir, &["-w", "-x", "pax", &long], b"");
+    assert_eq!(output.status.code(), Some(0));
+    let archive = output.stdout;
+
+    let output = pax(dir, &[], &archive);
+    assert_eq!(stdout_lines(&output), [long.as_str()]);
+
+    let out = &format!("{dir}/out");
+    fs::create_dir(out).unwrap();
+    let output = pax(out, &["-r"], &archive);
+    assert_eq!(output.status.code(), Some(0));
+    let md = fs::metadata(format!("{out}/{long}")).unwrap();
+    assert_eq!(fs::read(format!("{out}/{long}")).unwrap(), b"data\n");
+    assert_eq!((md.mtime(), md.mtime_nsec()), (1_700_000_000, 123_456_789));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_pax_format_errors() {
+    let dir = &format!("{}/test_pax_format_errors", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+
+    let output = pax(dir, &["-w", "-x", "bogus"], b"");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+
+    let output = pax(dir, &[], &[b'x'; 1024]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "pax: stdin: unknown archive format\n"
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_pax_copy() {
+    let dir = &format!("{}/test_pax_copy", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    create_tree(dir);
+    fs::create_dir(format!("{dir}/dest")).unwrap();
+
+    let output = pax(dir, &["-rw", "src", "dest"], b"");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        fs::read(format!("{dir}/dest/src/a.txt")).unwrap(),
+        b"hello\n"
+    );
+    let a = fs::metadata(format!("{dir}/dest/src/a.txt")).unwrap();
+    let hard = fs::metadata(format!("{dir}/dest/src/hard")).unwrap();
+    assert_eq!(a.ino(), hard.ino());
+    assert_eq!(
+        fs::read_link(format!("{dir}/dest/src/sym")).unwrap(),
+        Path::new("a.txt")
+    );
+    assert!(Path::new(&format!("{dir}/dest/src/sub/b.txt")).exists());
+
+    // pathnames are read from standard input without file operands
+    let output = pax(
+        dir,
+        &["-r", "-w", "-s", ",^src,copy,", "dest"],
+        b"src/a.txt\n",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        fs::read(format!("{dir}/dest/copy/a.txt")).unwrap(),
+        b"hello\n"
+    );
+
+    // a file is not copied onto itself
+    let output = pax(dir, &["-rw", "src/a.txt", "."], b"");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "src/a.txt: source and destination are the same file\n"
+    );
+    assert_eq!(fs::read(format!("{dir}/src/a.txt")).unwrap(), b"hello\n");
+
+    let output = pax(dir, &["-rw", "src", "missing"], b"");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "pax: missing: not a directory\n"
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_pax_substitutions() {
+    let dir = &format!("{}/test_pax_substitutions", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    create_tree(dir);
+
+    // hard links follow the substitution of the file they link to; an
+    // empty result skips the file
+    let archive = pax(
+        dir,
+        &["-w", "-s", "/.*sub.*//", "-s", "/^src/dst/p", "src"],
+        b"",
+    );
+    assert_eq!(archive.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&archive.stderr).contains("src/a.txt >> dst/a.txt\n"));
+
+    let output = pax(dir, &["-v"], &archive.stdout);
+    let lines = stdout_lines(&output);
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].ends_with(" dst"));
+    assert!(lines[2].ends_with("dst/hard == dst/a.txt"));
+
+    // substitutions apply when reading, too
+    let output = pax(dir, &["-s", "/a/A/g"], &archive.stdout);
+    assert_eq!(
+        stdout_lines(&output),
+        ["dst", "dst/A.txt", "dst/hArd", "dst/sym"]
+    );
+
+    let output = pax(dir, &["-w", "-s", "/a/b", "src"], b"");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "pax: invalid substitution: /a/b\n"
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_pax_patterns() {
+    let dir = &format!("{}/test_pax_patterns", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    create_tree(dir);
+    let archive = pax(dir, &["-w", "src"], b"").stdout;
+
+    let output = pax(dir, &[], &archive);
+    assert_eq!(output.status.code(), Some(0));
+
+    // wildcards match slashes
+    let output = pax(dir, &["*.txt"], &archive);
+    assert_eq!(stdout_lines(&output), ["src/a.txt", "src/sub/b.txt"]);
+
+    // directories select their contents, unless -d is given
+    let output = pax(dir, &["src/sub"], &archive);
+    assert_eq!(stdout_lines(&output), ["src/sub", "src/sub/b.txt"]);
+    let output = pax(dir, &["-d", "src/sub"], &archive);
+    assert_eq!(stdout_lines(&output), ["src/sub"]);
+
+    let output = pax(dir, &["-n", "*.txt"], &archive);
+    assert_eq!(stdout_lines(&output), ["src/a.txt"]);
+
+    let out = &format!("{dir}/out");
+    fs::create_dir(out).unwrap();
+    let output = pax(out, &["-r", "src/s*", "nothing"], &archive);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "pax: nothing: pattern not matched\n"
+    );
+    assert!(Path::new(&format!("{out}/src/sub/b.txt")).exists());
+    assert!(fs::symlink_metadata(format!("{out}/src/sym")).is_ok());
+    assert!(!Path::new(&format!("{out}/src/a.txt")).exists());
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -32,7 +32,8 @@ struct Args {
     #[arg(short = 'P', long)]
     portable: bool,
 
-    /// Include total allocated-space figures in the output.
+    /// Include total allocated-space figures in the output, on a final line
+    /// adding up the file systems shown (not with -P).
     #[arg(short, long)]
     total: bool,
 
@@ -65,56 +66,63 @@ fn stat(filename_str: &str) -> io::Result<libc::stat> {
 struct Mount {
     devname: String,
     dir: String,
-    dev: i64,
-    masked: bool,
-    cached_statfs: libc::statfs,
+    dev: libc::dev_t,
+    cached_statvfs: libc::statvfs,
 }
 
 struct MountList {
     mounts: Vec<Mount>,
-    has_masks: bool,
 }
 
 impl MountList {
     fn new() -> MountList {
-        MountList {
-            mounts: Vec::new(),
-            has_masks: false,
-        }
-    }
-
-    fn mask_all(&mut self) {
-        for mount in &mut self.mounts {
-            mount.masked = true;
-        }
+        MountList { mounts: Vec::new() }
     }
 
-    fn ensure_masked(&mut self) {
-        if !self.has_masks {
-            self.mask_all();
-            self.has_masks = true;
-        }
-    }
+    fn push(&mut self, devname: &CStr, dirname: &CStr) {
+        let dirname = dirname.to_string_lossy().into_owned();
 
-    fn push(&mut self, fsstat: &libc::statfs, devname: &CStr, dirname: &CStr) {
-        let dev = {
-            if let Ok(st) = stat(devname.to_str().unwrap()) {
-                st.st_rdev as i64
-            } else if let Ok(st) = stat(dirname.to_str().unwrap()) {
-                st.st_dev as i64
-            } else {
-                -1
-            }
+        // skip file systems that cannot be queried, e.g. due to permissions
+        let Ok(st) = stat(&dirname) else {
+            return;
+        };
+        let Ok(fsstat) = statvfs(&dirname) else {
+            return;
         };
 
         self.mounts.push(Mount {
-            devname: String::from(devname.to_str().unwrap()),
-            dir: String::from(dirname.to_str().unwrap()),
-            dev,
-            masked: false,
-            cached_statfs: *fsstat,
+            devname: devname.to_string_lossy().into_owned(),
+            dir: dirname,
+            dev: st.st_dev,
+            cached_statvfs: fsstat,
         });
     }
+
+    // find the file system containing filename.  When several mounts share
+    // a device, the one mounted last is the visible one.
+    fn find_by_file(&self, filename: &str) -> io::Result<&Mount> {
+        let st = stat(filename)?;
+
+        self.mounts
+            .iter()
+            .rev()
+            .find(|mount| mount.dev == st.st_dev)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "file system not found"))
+    }
+}
+
+fn statvfs(filename_str: &str) -> io::Result<libc::statvfs> {
+    let filename = CString::new(filename_str)?;
+
+    unsafe {
+        let mut st: libc::statvfs = std::mem::zeroed();
+        let rc = libc::statvfs(filename.as_ptr(), &mut st);
+        if rc == 0 {
+            Ok(st)
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
 }
 
 #[cfg(target_os = "macos")]
@@ -132,7 +140,7 @@ fn read_mount_info() -> io::Result<MountList> {
         for mount in mounts {
             let devname = to_cstr(&mount.f_mntfromname);
             let dirname = to_cstr(&mount.f_mntonname);
-            info.push(&mount, devname, dirname);
+            info.push(devname, dirname);
         }
     }
 
@@ -157,18 +165,9 @@ fn read_mount_info() -> io::Result<MountList> {
                 break;
             }
 
-            let me_devname = (*me).mnt_fsname;
-            let me_dirname = (*me).mnt_dir;
-            let devname = CStr::from_ptr(me_devname);
-            let dirname = CStr::from_ptr(me_dirname);
-
-            let mut mount: libc::statfs = std::mem::zeroed();
-            let rc = libc::statfs(dirname.as_ptr(), &mut mount);
-            if rc < 0 {
-                return Err(io::Error::last_os_error());
-            }
-
-            info.push(&mount, devname, dirname);
+            let devname = CStr::from_ptr((*me).mnt_fsname);
+            let dirname = CStr::from_ptr((*me).mnt_dir);
+            info.push(devname, dirname);
         }
 
         libc::endmntent(f);
@@ -177,76 +176,95 @@ fn read_mount_info() -> io::Result<MountList> {
     Ok(info)
 }
 
-fn mask_fs_by_file(info: &mut MountList, filename: &str) -> io::Result<()> {
-    let stat_res = stat(filename);
-    if let Err(e) = stat_res {
-        eprintln!("{}: {}", filename, e);
-        return Err(e);
-    }
-    let stat = stat_res.unwrap();
+// space figures for one file system, in units of block_size
+struct Usage {
+    total: u64,
+    used: u64,
+    avail: u64,
+    capacity: u64,
+}
 
-    for mount in &mut info.mounts {
-        if stat.st_dev as i64 == mount.dev {
-            info.has_masks = true;
-            mount.masked = true;
-        }
-    }
+impl Usage {
+    // the statvfs fields are narrower than u64 on some systems
+    #[allow(clippy::useless_conversion)]
+    fn new(sf: &libc::statvfs, block_size: u64) -> Usage {
+        // fragment size is the unit for the block counts
+        let frsize = if sf.f_frsize != 0 {
+            u64::from(sf.f_frsize)
+        } else {
+            u64::from(sf.f_bsize)
+        };
 
-    Ok(())
-}
+        let blocks = u64::from(sf.f_blocks);
+        let bfree = u64::from(sf.f_bfree);
+        let bavail = u64::from(sf.f_bavail);
 
-fn show_mount(args: &Args, block_size: u64, mount: &Mount) {
-    let sf = &mount.cached_statfs;
+        let total = (blocks * frsize).div_ceil(block_size);
+        let used = ((blocks - bfree) * frsize).div_ceil(block_size);
+        let avail = (bavail * frsize).div_ceil(block_size);
 
-    let blksz = sf.f_bsize as u64;
+        Usage::from_figures(total, used, avail)
+    }
 
-    let total = (sf.f_blocks * blksz) / block_size;
-    let avail = (sf.f_bavail * blksz) / block_size;
-    let free = (sf.f_bfree * blksz) / block_size;
-    let used = total - free;
+    fn from_figures(total: u64, used: u64, avail: u64) -> Usage {
+        // percentage of the space available to unprivileged users that is
+        // in use, rounded up to the next integer
+        let capacity = if used + avail == 0 {
+            0
+        } else {
+            (used * 100).div_ceil(used + avail)
+        };
 
-    if total == 0 {
-        return;
+        Usage {
+            total,
+            used,
+            avail,
+            capacity,
+        }
     }
 
-    let pct = ((total - avail) * 100) / total;
+    // the figures of the file systems in `usages` taken together
+    fn sum(usages: &[Usage]) -> Usage {
+        Usage::from_figures(
+            usages.iter().map(|u| u.total).sum(),
+            usages.iter().map(|u| u.used).sum(),
+            usages.iter().map(|u| u.avail).sum(),
+        )
+    }
+}
 
+fn show_header(args: &Args, block_size: u64) {
     if args.portable {
         println!(
-            "{:>20} {:>9} {:>9} {:>9} {:>7} {}",
-            mount.devname, total, used, avail, pct, mount.dir
+            "Filesystem {}-blocks Used Available Capacity Mounted on",
+            block_size
         );
     } else {
         println!(
-            "{:>20} {:>9} {:>9} {:>9} {:>3} {}",
-            mount.devname, total, used, avail, pct, mount.dir
+            "Filesystem         {:>4}-blocks      Used Available Use % Mounted on",
+            block_size
         );
     }
 }
 
-fn show_info(args: &Args, info: &MountList) {
-    let block_size: u64 = match args.kilo {
-        true => 1024,
-        false => 512,
-    };
-
+fn show_usage(args: &Args, name: &str, usage: &Usage, dir: &str) {
     if args.portable {
         println!(
-            "Filesystem         {:>4}-blocks      Used Available Capacity Mounted on",
-            block_size
+            "{} {} {} {} {}% {}",
+            name, usage.total, usage.used, usage.avail, usage.capacity, dir
         );
     } else {
         println!(
-            "Filesystem         {:>4}-blocks      Used Available Use % Mounted on",
-            block_size
+            "{:>20} {:>9} {:>9} {:>9} {:>4}% {}",
+            name, usage.total, usage.used, usage.avail, usage.capacity, dir
         );
     }
+}
 
-    for mount in &info.mounts {
-        if mount.masked {
-            show_mount(args, block_size, &mount);
-        }
-    }
+fn show_mount(args: &Args, block_size: u64, mount: &Mount) -> Usage {
+    let usage = Usage::new(&mount.cached_statvfs, block_size);
+    show_usage(args, &mount.devname, &usage, &mount.dir);
+    usage
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -256,18 +274,43 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     textdomain(PROJECT_NAME)?;
     bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
 
-    let mut info = read_mount_info()?;
+    let info = read_mount_info()?;
 
+    let block_size: u64 = match args.kilo {
+        true => 1024,
+        false => 512,
+    };
+
+    let mut exit_code = 0;
+
+    show_header(&args, block_size);
+
+    let mut usages = Vec::new();
     if args.files.is_empty() {
-        info.mask_all();
+        // file systems without any blocks (proc, sysfs, ...) are only
+        // shown when explicitly requested
+        for mount in &info.mounts {
+            if mount.cached_statvfs.f_blocks != 0 {
+                usages.push(show_mount(&args, block_size, mount));
+            }
+        }
     } else {
         for file in &args.files {
-            mask_fs_by_file(&mut info, file)?;
+            match info.find_by_file(file) {
+                Ok(mount) => usages.push(show_mount(&args, block_size, mount)),
+                Err(e) => {
+                    exit_code = 1;
+                    eprintln!("{}: {}", file, e);
+                }
+            }
         }
     }
 
-    info.ensure_masked();
-    show_info(&args, &info);
+    // a final line with the space of all the file systems shown.  The -P
+    // format has exactly one line per file system
+    if args.total && !args.portable {
+        show_usage(&args, "total", &Usage::sum(&usages), "-");
+    }
 
-    Ok(())
+    std::process::exit(exit_code)
 }
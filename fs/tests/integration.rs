@@ -0,0 +1,155 @@
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use plib::{run_test, run_test_with_checker, TestPlan};
+use std::os::unix::fs::MetadataExt;
+use std::process::Output;
+
+fn df_plan(args: &[&str]) -> TestPlan {
+    TestPlan {
+        cmd: String::from("df"),
+        args: args.iter().map(|s| String::from(*s)).collect(),
+        stdin_data: String::new(),
+        expected_out: String::new(),
+        expected_err: String::new(),
+        expected_exit_code: 0,
+    }
+}
+
+// The lines of the -P output, after checking the header: each is the
+// file system name, the total, used and available space, the capacity
+// and the mount point
+fn portable_lines(output: &Output, block_size: u64) -> Vec<(String, u64, u64, u64, u64, String)> {
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+    assert_eq!(output.status.code(), Some(0));
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut lines = stdout.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        format!(
+            "Filesystem {}-blocks Used Available Capacity Mounted on",
+            block_size
+        )
+    );
+    lines
+        .map(|line| {
+            let fields: Vec<&str> = line.splitn(6, ' ').collect();
+            assert_eq!(fields.len(), 6, "line '{}'", line);
+            let capacity = fields[4].strip_suffix('%').unwrap();
+            (
+                fields[0].to_string(),
+                fields[1].parse().unwrap(),
+                fields[2].parse().unwrap(),
+                fields[3].parse().unwrap(),
+                capacity.parse().unwrap(),
+                fields[5].to_string(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_df_portable() {
+    run_test_with_checker(df_plan(&["-P"]), |_, output| {
+        let lines = portable_lines(output, 512);
+        assert!(!lines.is_empty());
+        for (_, total, used, avail, capacity, _) in lines {
+            assert!(used <= total);
+            assert!(capacity <= 100);
+            if used + avail > 0 {
+                // capacity is rounded up
+                assert_eq!(capacity, (used * 100).div_ceil(used + avail));
+            }
+        }
+    });
+}
+
+#[test]
+fn test_df_kilo() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    let mut total_512 = 0;
+    run_test_with_checker(df_plan(&["-P", dir]), |_, output| {
+        total_512 = portable_lines(output, 512)[0].1;
+    });
+    run_test_with_checker(df_plan(&["-P", "-k", dir]), |_, output| {
+        // the totals are rounded up to whole blocks
+        let total_1024 = portable_lines(output, 1024)[0].1;
+        assert!(total_1024 * 2 == total_512 || total_1024 * 2 == total_512 + 1);
+    });
+}
+
+#[test]
+fn test_df_file_operand() {
+    let file = format!("{}/test_df_file_operand", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::write(&file, "").unwrap();
+    let dev = std::fs::metadata(&file).unwrap().dev();
+
+    run_test_with_checker(df_plan(&["-P", &file]), |_, output| {
+        let lines = portable_lines(output, 512);
+        assert_eq!(lines.len(), 1);
+        let mount_point = &lines[0].5;
+        assert_eq!(std::fs::metadata(mount_point).unwrap().dev(), dev);
+    });
+
+    run_test(TestPlan {
+        expected_out: String::from("Filesystem 512-blocks Used Available Capacity Mounted on\n"),
+        expected_err: String::from("/nonexistent/df: No such file or directory (os error 2)\n"),
+        expected_exit_code: 1,
+        ..df_plan(&["-P", "/nonexistent/df"])
+    });
+}
+
+#[test]
+fn test_df_total() {
+    let dir = env!("CARGO_TARGET_TMPDIR");
+    run_test_with_checker(df_plan(&["-t", dir, dir]), |_, output| {
+        assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+        assert_eq!(output.status.code(), Some(0));
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let lines: Vec<Vec<&str>> = stdout
+            .lines()
+            .skip(1)
+            .map(|line| line.split_whitespace().collect())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        let total = &lines[2];
+        assert_eq!((total[0], total[5]), ("total", "-"));
+        for column in 1..4 {
+            let figure: u64 = lines[0][column].parse().unwrap();
+            assert_eq!(total[column].parse::<u64>().unwrap(), figure * 2);
+        }
+        assert_eq!(total[4], lines[0][4]);
+    });
+
+    // the -P format has one line per file system, and no total
+    run_test_with_checker(df_plan(&["-P", "-t", dir]), |_, output| {
+        let lines = portable_lines(output, 512);
+        assert_eq!(lines.len(), 1);
+        assert_ne!(lines[0].0, "total");
+    });
+}
+
+#[test]
+fn test_df_default_format() {
+    run_test_with_checker(df_plan(&[env!("CARGO_TARGET_TMPDIR")]), |_, output| {
+        assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+        assert_eq!(output.status.code(), Some(0));
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let lines: Vec<&str> = stdout.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("Filesystem"));
+        assert!(lines[0].contains(" Use % "));
+        // the capacity is written with a percent sign, as with -P
+        let fields: Vec<&str> = lines[1].split_whitespace().collect();
+        assert!(fields[4].ends_with('%'), "line '{}'", lines[1]);
+    });
+}
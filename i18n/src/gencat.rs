@@ -0,0 +1,468 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+extern crate clap;
+extern crate plib;
+
+use clap::Parser;
+use gettextrs::{bind_textdomain_codeset, gettext, textdomain};
+use plib::PROJECT_NAME;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+// the set of messages which precede any $set directive
+const NL_SETD: u32 = 1;
+
+// the magic numbers of the glibc and the BSD (and musl) catalog formats
+const GLIBC_MAGIC: u32 = 0x960408de;
+const BSD_MAGIC: u32 = 0xff88ff89;
+
+/// gencat - generate a formatted message catalog
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about)]
+struct Args {
+    /// The message catalog to create, or to merge the messages into if it
+    /// exists; "-" for standard output.
+    catfile: PathBuf,
+
+    /// The message source files; "-" for standard input.
+    #[arg(required = true)]
+    msgfile: Vec<PathBuf>,
+}
+
+/// A message catalog: the messages of each set, by message number.
+type Catalog = BTreeMap<u32, BTreeMap<u32, Vec<u8>>>;
+
+fn invalid_catalog() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        gettext("not a valid message catalog"),
+    )
+}
+
+// a 32-bit word of a catalog file
+fn word(data: &[u8], offset: usize, big_endian: bool) -> io::Result<u32> {
+    let bytes: [u8; 4] = data
+        .get(offset..offset + 4)
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(invalid_catalog)?;
+    Ok(if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    })
+}
+
+// a null-terminated catalog string
+fn string(data: &[u8], offset: usize) -> io::Result<Vec<u8>> {
+    let rest = data.get(offset..).ok_or_else(invalid_catalog)?;
+    let len = rest
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or_else(invalid_catalog)?;
+    Ok(rest[..len].to_vec())
+}
+
+/// Read a glibc catalog: a header of magic number, table size and table
+/// depth, a hash table of (set + 1, message, string offset) entries in
+/// both byte orders, and the strings.
+fn read_glibc(data: &[u8]) -> io::Result<Catalog> {
+    let big_endian = word(data, 0, false)? != GLIBC_MAGIC;
+    let size = word(data, 4, big_endian)? as usize;
+    let depth = word(data, 8, big_endian)? as usize;
+    let entries = size.checked_mul(depth).ok_or_else(invalid_catalog)?;
+    let strings = 12 + entries * 3 * 4 * 2;
+
+    let mut catalog = Catalog::new();
+    for entry in 0..entries {
+        let offset = 12 + entry * 3 * 4;
+        let set = word(data, offset, big_endian)?;
+        if set == 0 {
+            continue;
+        }
+        let msg = word(data, offset + 4, big_endian)?;
+        let text = string(data, strings + word(data, offset + 8, big_endian)? as usize)?;
+        catalog.entry(set - 1).or_default().insert(msg, text);
+    }
+    Ok(catalog)
+}
+
+/// Write a glibc catalog.  catgets() finds a message at index
+/// ((set + 1) * message) % size of one of the depth planes of the table,
+/// so the size is chosen to keep the table small.
+fn write_glibc(catalog: &Catalog) -> Vec<u8> {
+    // glibc orders the sets last to first
+    let messages: Vec<(u32, u32, &[u8])> = catalog
+        .iter()
+        .rev()
+        .flat_map(|(&set, msgs)| {
+            msgs.iter()
+                .map(move |(&msg, text)| (set + 1, msg, text.as_slice()))
+        })
+        .collect();
+    let hash = |set: u32, msg: u32, size: usize| set.wrapping_mul(msg) as usize % size;
+
+    let mut best = (1, 1);
+    let mut best_total = usize::MAX;
+    let mut size = 1 + messages.len() / 5;
+    while size <= best_total {
+        let mut count = vec![0; size];
+        let mut depth = 1;
+        for &(set, msg, _) in &messages {
+            let idx = hash(set, msg, size);
+            count[idx] += 1;
+            depth = depth.max(count[idx]);
+        }
+        if size * depth < best_total {
+            best_total = size * depth;
+            best = (size, depth);
+        }
+        size += 1;
+    }
+    let (size, depth) = best;
+
+    let mut table = vec![0u32; size * depth * 3];
+    let mut count = vec![0; size];
+    let mut strings = Vec::new();
+    for &(set, msg, text) in &messages {
+        let idx = hash(set, msg, size);
+        let entry = (count[idx] * size + idx) * 3;
+        count[idx] += 1;
+        table[entry] = set;
+        table[entry + 1] = msg;
+        table[entry + 2] = strings.len() as u32;
+        strings.extend_from_slice(text);
+        strings.push(0);
+    }
+
+    let mut data = Vec::new();
+    for n in [GLIBC_MAGIC, size as u32, depth as u32] {
+        data.extend_from_slice(&n.to_ne_bytes());
+    }
+    for n in &table {
+        data.extend_from_slice(&n.to_ne_bytes());
+    }
+    for n in &table {
+        data.extend_from_slice(&n.swap_bytes().to_ne_bytes());
+    }
+    data.extend_from_slice(&strings);
+    data
+}
+
+/// Read a BSD catalog: a header of magic number, set count, data size and
+/// the offsets of the message headers and texts, followed by the set
+/// headers (set, message count, first message header), the message headers
+/// (message, length, text offset) and the texts, all big-endian.
+fn read_bsd(data: &[u8]) -> io::Result<Catalog> {
+    const HEADER: usize = 20;
+    let nsets = word(data, 4, true)? as usize;
+    let msg_headers = HEADER + word(data, 12, true)? as usize;
+    let texts = HEADER + word(data, 16, true)? as usize;
+
+    let mut catalog = Catalog::new();
+    for i in 0..nsets {
+        let offset = HEADER + i * 12;
+        let set = word(data, offset, true)?;
+        let nmsgs = word(data, offset + 4, true)? as usize;
+        let first = word(data, offset + 8, true)? as usize;
+        let msgs = catalog.entry(set).or_default();
+        for j in first..first + nmsgs {
+            let offset = msg_headers + j * 12;
+            let msg = word(data, offset, true)?;
+            let text = string(data, texts + word(data, offset + 8, true)? as usize)?;
+            msgs.insert(msg, text);
+        }
+    }
+    Ok(catalog)
+}
+
+/// Write a BSD catalog, with the sets and messages in ascending order.
+fn write_bsd(catalog: &Catalog) -> Vec<u8> {
+    let nsets = catalog.len();
+    let nmsgs: usize = catalog.values().map(|msgs| msgs.len()).sum();
+
+    let mut set_headers = Vec::new();
+    let mut msg_headers = Vec::new();
+    let mut texts = Vec::new();
+    let mut index = 0;
+    for (&set, msgs) in catalog {
+        for n in [set, msgs.len() as u32, index as u32] {
+            set_headers.extend_from_slice(&n.to_be_bytes());
+        }
+        index += msgs.len();
+        for (&msg, text) in msgs {
+            for n in [msg, text.len() as u32 + 1, texts.len() as u32] {
+                msg_headers.extend_from_slice(&n.to_be_bytes());
+            }
+            texts.extend_from_slice(text);
+            texts.push(0);
+        }
+    }
+
+    let mut data = Vec::new();
+    let size = set_headers.len() + msg_headers.len() + texts.len();
+    for n in [
+        BSD_MAGIC,
+        nsets as u32,
+        size as u32,
+        (nsets * 12) as u32,
+        (nsets * 12 + nmsgs * 12) as u32,
+    ] {
+        data.extend_from_slice(&n.to_be_bytes());
+    }
+    data.extend_from_slice(&set_headers);
+    data.extend_from_slice(&msg_headers);
+    data.extend_from_slice(&texts);
+    data
+}
+
+/// Read an existing catalog, in either format.
+fn read_catalog(data: &[u8]) -> io::Result<Catalog> {
+    let magic = word(data, 0, false)?;
+    if magic == GLIBC_MAGIC || magic.swap_bytes() == GLIBC_MAGIC {
+        read_glibc(data)
+    } else if magic.swap_bytes() == BSD_MAGIC {
+        read_bsd(data)
+    } else {
+        Err(invalid_catalog())
+    }
+}
+
+/// Write a catalog in the format of the system's catgets().
+fn write_catalog(catalog: &Catalog) -> Vec<u8> {
+    if cfg!(all(target_os = "linux", target_env = "gnu")) {
+        write_glibc(catalog)
+    } else {
+        write_bsd(catalog)
+    }
+}
+
+fn is_blank(b: u8) -> bool {
+    b == b' ' || b == b'\t'
+}
+
+// a set or message number: a positive decimal number
+fn parse_number(s: &[u8]) -> Option<u32> {
+    let n: u32 = std::str::from_utf8(s).ok()?.parse().ok()?;
+    if n == 0 || n > i32::MAX as u32 {
+        return None;
+    }
+    Some(n)
+}
+
+/// Decode message text: the escape sequences \n, \t, \v, \b, \r, \f, \\
+/// and \ddd (octal), and, when a quote character is in effect, text
+/// enclosed in quotes, within which \ escapes the quote character.
+fn message_text(text: &[u8], quote: Option<u8>) -> Vec<u8> {
+    let (quoted, text) = match (quote, text.first()) {
+        (Some(q), Some(&first)) if first == q => (quote, &text[1..]),
+        _ => (None, text),
+    };
+
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < text.len() {
+        let b = text[i];
+        i += 1;
+        if Some(b) == quoted {
+            break;
+        }
+        if b != b'\\' {
+            result.push(b);
+            continue;
+        }
+        let Some(&c) = text.get(i) else {
+            break;
+        };
+        i += 1;
+        match c {
+            b'n' => result.push(b'\n'),
+            b't' => result.push(b'\t'),
+            b'v' => result.push(0x0b),
+            b'b' => result.push(0x08),
+            b'r' => result.push(b'\r'),
+            b'f' => result.push(0x0c),
+            b'0'..=b'7' => {
+                let mut n = u32::from(c - b'0');
+                for _ in 0..2 {
+                    match text.get(i) {
+                        Some(&d @ b'0'..=b'7') => {
+                            n = n * 8 + u32::from(d - b'0');
+                            i += 1;
+                        }
+                        _ => break,
+                    }
+                }
+                result.push(n as u8);
+            }
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// The state of a message source file being read.
+struct Source<'a> {
+    name: &'a str,
+    set: u32,
+    quote: Option<u8>,
+    errors: usize,
+}
+
+impl Source<'_> {
+    fn error(&mut self, line: usize, msg: &str) {
+        eprintln!("gencat: {}:{}: {}", self.name, line, msg);
+        self.errors += 1;
+    }
+
+    fn directive(&mut self, catalog: &mut Catalog, lineno: usize, line: &[u8]) {
+        let end = line.iter().position(|&b| is_blank(b)).unwrap_or(line.len());
+        let (name, rest) = line.split_at(end);
+        let rest = &rest[rest.iter().take_while(|&&b| is_blank(b)).count()..];
+        let arg = &rest[..rest.iter().position(|&b| is_blank(b)).unwrap_or(rest.len())];
+
+        match name {
+            // "$" followed by a blank, or alone, is a comment
+            b"" => {}
+            b"set" | b"delset" => {
+                let Some(set) = parse_number(arg) else {
+                    self.error(lineno, &gettext("invalid set number"));
+                    return;
+                };
+                if name == b"set" {
+                    self.set = set;
+                } else {
+                    catalog.remove(&set);
+                }
+            }
+            b"quote" => self.quote = rest.first().copied(),
+            _ => eprintln!(
+                "gencat: {}:{}: {}",
+                self.name,
+                lineno,
+                gettext!(
+                    "unknown directive '{}': line ignored",
+                    String::from_utf8_lossy(name)
+                )
+            ),
+        }
+    }
+
+    fn message(&mut self, catalog: &mut Catalog, lineno: usize, line: &[u8]) {
+        let digits = line.iter().take_while(|b| b.is_ascii_digit()).count();
+        let Some(msg) = parse_number(&line[..digits]) else {
+            self.error(lineno, &gettext("invalid message number"));
+            return;
+        };
+
+        match line.get(digits) {
+            // a number alone deletes the message
+            None => {
+                if let Some(msgs) = catalog.get_mut(&self.set) {
+                    msgs.remove(&msg);
+                }
+            }
+            Some(&b) if is_blank(b) => {
+                let text = message_text(&line[digits + 1..], self.quote);
+                catalog.entry(self.set).or_default().insert(msg, text);
+            }
+            Some(_) => self.error(lineno, &gettext("malformed line")),
+        }
+    }
+}
+
+/// Add the messages of a source file to the catalog, returning the number
+/// of errors found.
+fn read_source(catalog: &mut Catalog, name: &str, data: &[u8]) -> usize {
+    let mut source = Source {
+        name,
+        set: NL_SETD,
+        quote: None,
+        errors: 0,
+    };
+
+    let mut lines = data.split(|&b| b == b'\n').enumerate().peekable();
+    while let Some((i, line)) = lines.next() {
+        // a line ending in an unescaped backslash continues on the next
+        let mut line = line.to_vec();
+        while line.iter().rev().take_while(|&&b| b == b'\\').count() % 2 == 1 {
+            line.pop();
+            match lines.next() {
+                Some((_, next)) => line.extend_from_slice(next),
+                None => break,
+            }
+        }
+
+        if line.iter().all(|&b| is_blank(b)) {
+            continue;
+        }
+        match line.strip_prefix(b"$") {
+            Some(directive) => source.directive(catalog, i + 1, directive),
+            None => source.message(catalog, i + 1, &line),
+        }
+    }
+    source.errors
+}
+
+fn read_input(path: &Path) -> io::Result<Vec<u8>> {
+    if path.as_os_str() == "-" {
+        let mut data = Vec::new();
+        io::stdin().read_to_end(&mut data)?;
+        Ok(data)
+    } else {
+        fs::read(path)
+    }
+}
+
+fn gencat(args: &Args) -> Result<(), String> {
+    let to_stdout = args.catfile.as_os_str() == "-";
+
+    // the messages of an existing catalog are merged with the new ones
+    let mut catalog = match fs::read(&args.catfile) {
+        Ok(data) if !to_stdout && !data.is_empty() => {
+            read_catalog(&data).map_err(|e| format!("{}: {}", args.catfile.display(), e))?
+        }
+        Ok(_) => Catalog::new(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Catalog::new(),
+        Err(e) => return Err(format!("{}: {}", args.catfile.display(), e)),
+    };
+
+    let mut errors = 0;
+    for path in &args.msgfile {
+        let data = read_input(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        errors += read_source(&mut catalog, &path.display().to_string(), &data);
+    }
+    if errors > 0 {
+        return Err(gettext("errors in message source, catalog not written"));
+    }
+
+    let data = write_catalog(&catalog);
+    if to_stdout {
+        io::stdout().write_all(&data).map_err(|e| e.to_string())
+    } else {
+        fs::write(&args.catfile, data).map_err(|e| format!("{}: {}", args.catfile.display(), e))
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // parse command line arguments
+    let args = Args::parse();
+
+    textdomain(PROJECT_NAME)?;
+    bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
+
+    if let Err(e) = gencat(&args) {
+        eprintln!("gencat: {}", e);
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,184 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+extern "C" {
+    fn catopen(name: *const libc::c_char, oflag: libc::c_int) -> *mut libc::c_void;
+    fn catgets(
+        catd: *mut libc::c_void,
+        set_id: libc::c_int,
+        msg_id: libc::c_int,
+        s: *const libc::c_char,
+    ) -> *mut libc::c_char;
+    fn catclose(catd: *mut libc::c_void) -> libc::c_int;
+}
+
+fn gencat(args: &[&Path], stdin_data: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_gencat"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin_data.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+// the messages of a catalog, as catgets() finds them; None when absent
+fn catalog_messages(catfile: &Path, ids: &[(i32, i32)]) -> Vec<Option<String>> {
+    let name = CString::new(catfile.to_str().unwrap()).unwrap();
+    let absent = c"\x01absent";
+    let catd = unsafe { catopen(name.as_ptr(), 0) };
+    assert_ne!(catd as isize, -1, "catopen failed");
+
+    let messages = ids
+        .iter()
+        .map(|&(set, msg)| {
+            let s = unsafe { catgets(catd, set, msg, absent.as_ptr()) };
+            let s = unsafe { CStr::from_ptr(s) };
+            (s != absent).then(|| s.to_string_lossy().into_owned())
+        })
+        .collect();
+    unsafe { catclose(catd) };
+    messages
+}
+
+#[test]
+fn test_gencat_messages() {
+    let dir = &Path::new(env!("CARGO_TARGET_TMPDIR")).join("test_gencat_messages");
+    fs::create_dir(dir).unwrap();
+    let msgfile = dir.join("test.msg");
+    let catfile = dir.join("test.cat");
+    fs::write(
+        &msgfile,
+        "$ a comment\n\
+         1 default set\n\
+         $set 2 second set\n\
+         1 tab\\tnewline\\n\n\
+         2 octal \\101\\102 backslash \\\\\n\
+         3 continued \\\n\
+         line\n\
+         4  leading space\n\
+         5 \n\
+         $quote \"\n\
+         6 \"quoted  \" ignored\n\
+         7 \"escaped \\\" quote\"\n\
+         $set 10\n\
+         1 set ten\n",
+    )
+    .unwrap();
+
+    let output = gencat(&[&catfile, &msgfile], "");
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+
+    let messages = catalog_messages(
+        &catfile,
+        &[
+            (1, 1),
+            (2, 1),
+            (2, 2),
+            (2, 3),
+            (2, 4),
+            (2, 5),
+            (2, 6),
+            (2, 7),
+            (10, 1),
+            (3, 1),
+        ],
+    );
+    let expected = [
+        Some("default set"),
+        Some("tab\tnewline\n"),
+        Some("octal AB backslash \\"),
+        Some("continued line"),
+        Some(" leading space"),
+        Some(""),
+        Some("quoted  "),
+        Some("escaped \" quote"),
+        Some("set ten"),
+        None,
+    ];
+    assert_eq!(messages, expected.map(|s| s.map(String::from)));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_gencat_merge() {
+    let dir = &Path::new(env!("CARGO_TARGET_TMPDIR")).join("test_gencat_merge");
+    fs::create_dir(dir).unwrap();
+    let catfile = dir.join("merge.cat");
+    let first = dir.join("first.msg");
+    let second = dir.join("second.msg");
+    fs::write(&first, "1 one\n2 two\n3 three\n$set 2\n1 other set\n").unwrap();
+    fs::write(&second, "2 replaced\n3\n4 four\n$delset 2\n").unwrap();
+
+    assert!(gencat(&[&catfile, &first], "").status.success());
+    assert!(gencat(&[&catfile, &second], "").status.success());
+
+    let messages = catalog_messages(&catfile, &[(1, 1), (1, 2), (1, 3), (1, 4), (2, 1)]);
+    let expected = [Some("one"), Some("replaced"), None, Some("four"), None];
+    assert_eq!(messages, expected.map(|s| s.map(String::from)));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_gencat_stdio() {
+    let dir = &Path::new(env!("CARGO_TARGET_TMPDIR")).join("test_gencat_stdio");
+    fs::create_dir(dir).unwrap();
+    let catfile = dir.join("stdio.cat");
+    let source = "$set 3\n7 from standard input\n";
+
+    assert!(gencat(&[&catfile, Path::new("-")], source).status.success());
+    let output = gencat(&[Path::new("-"), Path::new("-")], source);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, fs::read(&catfile).unwrap());
+
+    let messages = catalog_messages(&catfile, &[(3, 7)]);
+    assert_eq!(messages, [Some(String::from("from standard input"))]);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_gencat_errors() {
+    let dir = &Path::new(env!("CARGO_TARGET_TMPDIR")).join("test_gencat_errors");
+    fs::create_dir(dir).unwrap();
+    let catfile = dir.join("errors.cat");
+    let msgfile = dir.join("errors.msg");
+    fs::write(&msgfile, "1 good\nbad line\n$set 0\n2x malformed\n").unwrap();
+
+    let output = gencat(&[&catfile, &msgfile], "");
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("errors.msg:2:"));
+    assert!(stderr.contains("errors.msg:3:"));
+    assert!(stderr.contains("errors.msg:4:"));
+    assert!(!catfile.exists());
+
+    // an existing file must be a catalog to merge into
+    fs::write(&catfile, "not a catalog").unwrap();
+    fs::write(&msgfile, "1 good\n").unwrap();
+    assert_eq!(gencat(&[&catfile, &msgfile], "").status.code(), Some(1));
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,102 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+extern crate clap;
+extern crate libc;
+extern crate plib;
+
+mod m4_util;
+
+use clap::{ArgAction, Parser};
+use gettextrs::{bind_textdomain_codeset, textdomain};
+use m4_util::processor::Processor;
+use plib::PROJECT_NAME;
+use std::io::{self, BufWriter, Read};
+use std::path::{Path, PathBuf};
+
+/// m4 - macro processor
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about)]
+struct Args {
+    /// Define name to value, or to the null string if =value is omitted.
+    #[arg(short = 'D', long, action = ArgAction::Append, value_name = "NAME[=VALUE]")]
+    define: Vec<String>,
+
+    /// Undefine name.
+    #[arg(short = 'U', long, action = ArgAction::Append, value_name = "NAME")]
+    undefine: Vec<String>,
+
+    /// The files to process, standard input if none or "-".
+    files: Vec<PathBuf>,
+}
+
+fn read_input(path: &Path) -> io::Result<Vec<u8>> {
+    if path.as_os_str() == "-" {
+        let mut text = Vec::new();
+        io::stdin().lock().read_to_end(&mut text)?;
+        Ok(text)
+    } else {
+        std::fs::read(path)
+    }
+}
+
+fn m4(args: Args) -> io::Result<i32> {
+    let mut processor = Processor::new(BufWriter::new(io::stdout().lock()));
+    for define in &args.define {
+        let (name, value) = define.split_once('=').unwrap_or((define, ""));
+        processor.define(name.as_bytes(), value.as_bytes());
+    }
+    for name in &args.undefine {
+        processor.undefine(name.as_bytes());
+    }
+
+    let mut files = args.files;
+    if files.is_empty() {
+        files.push(PathBuf::from("-"));
+    }
+
+    let mut read_failed = false;
+    for path in &files {
+        match read_input(path) {
+            Ok(text) => processor.process(&text)?,
+            Err(e) => {
+                eprintln!("m4: {}: {}", path.display(), e);
+                read_failed = true;
+            }
+        }
+        if processor.exiting() {
+            break;
+        }
+    }
+
+    let exit_code = processor.finish()?;
+    Ok(if read_failed && exit_code == 0 {
+        1
+    } else {
+        exit_code
+    })
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // parse command line arguments
+    let args = Args::parse();
+
+    textdomain(PROJECT_NAME)?;
+    bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
+
+    let exit_code = match m4(args) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("m4: {}", e);
+            1
+        }
+    };
+
+    std::process::exit(exit_code)
+}
@@ -0,0 +1,317 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+//! The expressions of the eval macro: integer arithmetic with the
+//! operators of C, plus `**` for exponentiation.
+
+use gettextrs::gettext;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum EvalError {
+    Syntax,
+    DivideByZero,
+    NegativeExponent,
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            EvalError::Syntax => gettext("invalid expression"),
+            EvalError::DivideByZero => gettext("divide by zero"),
+            EvalError::NegativeExponent => gettext("negative exponent"),
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Token {
+    Number(i64),
+    Op(&'static str),
+}
+
+// longest operators first, so that "<<" is not read as two "<"
+const OPERATORS: [&str; 25] = [
+    "**", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "<", ">", "&",
+    "^", "|", "!", "~", "(", ")", "?", ":",
+];
+
+fn tokenize(s: &str) -> Result<Vec<Token>, EvalError> {
+    let mut tokens = Vec::new();
+    let mut rest = s.trim_start();
+    while !rest.is_empty() {
+        if rest.starts_with(|c: char| c.is_ascii_digit()) {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len());
+            tokens.push(Token::Number(parse_number(&rest[..end])?));
+            rest = &rest[end..];
+        } else {
+            let op = OPERATORS
+                .iter()
+                .find(|op| rest.starts_with(*op))
+                .ok_or(EvalError::Syntax)?;
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+// a decimal, octal (leading 0) or hexadecimal (leading 0x) integer
+fn parse_number(s: &str) -> Result<i64, EvalError> {
+    let (digits, radix) = if let Some(hex) = s.strip_prefix("0x").or(s.strip_prefix("0X")) {
+        (hex, 16)
+    } else if s.len() > 1 && s.starts_with('0') {
+        (&s[1..], 8)
+    } else {
+        (s, 10)
+    };
+    u64::from_str_radix(digits, radix)
+        .map(|n| n as i64)
+        .map_err(|_| EvalError::Syntax)
+}
+
+enum Node {
+    Number(i64),
+    Unary(&'static str, Box<Node>),
+    Binary(&'static str, Box<Node>, Box<Node>),
+    Conditional(Box<Node>, Box<Node>, Box<Node>),
+}
+
+// binary operators, from the lowest precedence to the highest
+const PRECEDENCE: [&[&str]; 11] = [
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", "<=", ">", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+    &["**"],
+];
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), EvalError> {
+        if self.peek_op() != Some(op) {
+            return Err(EvalError::Syntax);
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    // cond ? expr : expr, which groups from the right
+    fn parse_conditional(&mut self) -> Result<Node, EvalError> {
+        let cond = self.parse_binary(0)?;
+        if self.peek_op() != Some("?") {
+            return Ok(cond);
+        }
+        self.pos += 1;
+        let then = self.parse_conditional()?;
+        self.expect(":")?;
+        let otherwise = self.parse_conditional()?;
+        Ok(Node::Conditional(
+            Box::new(cond),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    fn parse_binary(&mut self, level: usize) -> Result<Node, EvalError> {
+        if level == PRECEDENCE.len() {
+            return self.parse_unary();
+        }
+        let mut lhs = self.parse_binary(level + 1)?;
+        while let Some(op) = self.peek_op().filter(|op| PRECEDENCE[level].contains(op)) {
+            self.pos += 1;
+            // ** is the only right-associative operator
+            let rhs = if op == "**" {
+                self.parse_binary(level)?
+            } else {
+                self.parse_binary(level + 1)?
+            };
+            lhs = Node::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Node, EvalError> {
+        match self.tokens.get(self.pos).copied() {
+            Some(Token::Number(n)) => {
+                self.pos += 1;
+                Ok(Node::Number(n))
+            }
+            Some(Token::Op("(")) => {
+                self.pos += 1;
+                let node = self.parse_conditional()?;
+                self.expect(")")?;
+                Ok(node)
+            }
+            Some(Token::Op(op @ ("+" | "-" | "~" | "!"))) => {
+                self.pos += 1;
+                Ok(Node::Unary(op, Box::new(self.parse_unary()?)))
+            }
+            _ => Err(EvalError::Syntax),
+        }
+    }
+}
+
+fn power(base: i64, exp: i64) -> Result<i64, EvalError> {
+    if exp < 0 {
+        return Err(EvalError::NegativeExponent);
+    }
+    let mut result: i64 = 1;
+    for _ in 0..exp {
+        result = result.wrapping_mul(base);
+        // further multiplications do not change these
+        if result == 0 || result == 1 && base == 1 {
+            break;
+        }
+    }
+    Ok(result)
+}
+
+fn evaluate(node: &Node) -> Result<i64, EvalError> {
+    let value = match node {
+        Node::Number(n) => *n,
+        Node::Unary(op, operand) => {
+            let n = evaluate(operand)?;
+            match *op {
+                "+" => n,
+                "-" => n.wrapping_neg(),
+                "~" => !n,
+                "!" => (n == 0) as i64,
+                _ => unreachable!(),
+            }
+        }
+        // the operand that does not determine the result is not evaluated
+        Node::Binary("&&", lhs, rhs) => (evaluate(lhs)? != 0 && evaluate(rhs)? != 0) as i64,
+        Node::Binary("||", lhs, rhs) => (evaluate(lhs)? != 0 || evaluate(rhs)? != 0) as i64,
+        Node::Conditional(cond, then, otherwise) => {
+            if evaluate(cond)? != 0 {
+                evaluate(then)?
+            } else {
+                evaluate(otherwise)?
+            }
+        }
+        Node::Binary(op, lhs, rhs) => {
+            let l = evaluate(lhs)?;
+            let r = evaluate(rhs)?;
+            match *op {
+                "|" => l | r,
+                "^" => l ^ r,
+                "&" => l & r,
+                "==" => (l == r) as i64,
+                "!=" => (l != r) as i64,
+                "<" => (l < r) as i64,
+                "<=" => (l <= r) as i64,
+                ">" => (l > r) as i64,
+                ">=" => (l >= r) as i64,
+                "<<" => l.wrapping_shl(r as u32),
+                ">>" => l.wrapping_shr(r as u32),
+                "+" => l.wrapping_add(r),
+                "-" => l.wrapping_sub(r),
+                "*" => l.wrapping_mul(r),
+                "/" | "%" if r == 0 => return Err(EvalError::DivideByZero),
+                "/" => l.wrapping_div(r),
+                "%" => l.wrapping_rem(r),
+                "**" => power(l, r)?,
+                _ => unreachable!(),
+            }
+        }
+    };
+    Ok(value)
+}
+
+/// Evaluate the arithmetic expression `expr`.
+pub fn eval(expr: &str) -> Result<i64, EvalError> {
+    let mut parser = Parser {
+        tokens: tokenize(expr)?,
+        pos: 0,
+    };
+    let node = parser.parse_conditional()?;
+    if parser.pos != parser.tokens.len() {
+        return Err(EvalError::Syntax);
+    }
+    evaluate(&node)
+}
+
+/// Format `n` in `radix`, with at least `width` digits.
+pub fn format_radix(n: i64, radix: u32, width: usize) -> String {
+    let mut digits = Vec::new();
+    let mut m = n.unsigned_abs();
+    loop {
+        digits.push(std::char::from_digit((m % radix as u64) as u32, radix).unwrap());
+        m /= radix as u64;
+        if m == 0 {
+            break;
+        }
+    }
+    while digits.len() < width {
+        digits.push('0');
+    }
+    if n < 0 {
+        digits.push('-');
+    }
+    digits.iter().rev().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval() {
+        assert_eq!(eval("1 + 2 * 3"), Ok(7));
+        assert_eq!(eval("(1 + 2) * 3"), Ok(9));
+        assert_eq!(eval("2 ** 3 ** 2"), Ok(512));
+        assert_eq!(eval("-2 ** 2"), Ok(4));
+        assert_eq!(eval("7 / 2 - 7 % 2"), Ok(2));
+        assert_eq!(eval("1 << 4 | 1"), Ok(17));
+        assert_eq!(eval("0x10 + 010"), Ok(24));
+        assert_eq!(eval("3 > 2 && 2 > 1"), Ok(1));
+        assert_eq!(eval("!0 + ~0"), Ok(0));
+        assert_eq!(eval("1 ? 2 : 3"), Ok(2));
+        assert_eq!(eval("0 ? 2 : 0 ? 3 : 4"), Ok(4));
+        assert_eq!(eval("1 == 1 != 0"), Ok(1));
+        assert_eq!(eval("0 && 1 / 0"), Ok(0));
+        assert_eq!(eval("1 / 0"), Err(EvalError::DivideByZero));
+        assert_eq!(eval("2 ** -1"), Err(EvalError::NegativeExponent));
+        assert_eq!(eval("1 +"), Err(EvalError::Syntax));
+        assert_eq!(eval("(1"), Err(EvalError::Syntax));
+        assert_eq!(eval("1 2"), Err(EvalError::Syntax));
+        assert_eq!(eval("08"), Err(EvalError::Syntax));
+        assert_eq!(eval(""), Err(EvalError::Syntax));
+    }
+
+    #[test]
+    fn test_format_radix() {
+        assert_eq!(format_radix(255, 16, 0), "ff");
+        assert_eq!(format_radix(5, 2, 8), "00000101");
+        assert_eq!(format_radix(-5, 10, 3), "-005");
+        assert_eq!(format_radix(0, 10, 0), "0");
+    }
+}
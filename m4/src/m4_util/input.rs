@@ -0,0 +1,69 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+//! The input of the macro processor: the text of the input files, to
+//! which the expansion of each macro is pushed back to be rescanned.
+
+/// A stack of unread input, the next byte being the last one.
+#[derive(Default)]
+pub struct Input {
+    pushback: Vec<u8>,
+}
+
+impl Input {
+    /// Push `text` back, to be read before the rest of the input.
+    pub fn push(&mut self, text: &[u8]) {
+        self.pushback.extend(text.iter().rev());
+    }
+
+    pub fn next(&mut self) -> Option<u8> {
+        self.pushback.pop()
+    }
+
+    pub fn peek(&self) -> Option<u8> {
+        self.pushback.last().copied()
+    }
+
+    /// Whether the input starts with `s`; an empty `s` never matches.
+    pub fn looking_at(&self, s: &[u8]) -> bool {
+        !s.is_empty()
+            && s.len() <= self.pushback.len()
+            && self.pushback.iter().rev().zip(s).all(|(a, b)| a == b)
+    }
+
+    /// Consume `s` if the input starts with it.
+    pub fn skip(&mut self, s: &[u8]) -> bool {
+        if !self.looking_at(s) {
+            return false;
+        }
+        self.pushback.truncate(self.pushback.len() - s.len());
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pushback() {
+        let mut input = Input::default();
+        input.push(b"cd");
+        input.push(b"ab");
+        assert!(input.looking_at(b"abc"));
+        assert!(!input.looking_at(b"abd"));
+        assert!(!input.looking_at(b""));
+        assert!(input.skip(b"ab"));
+        assert_eq!(input.peek(), Some(b'c'));
+        assert_eq!(input.next(), Some(b'c'));
+        assert_eq!(input.next(), Some(b'd'));
+        assert_eq!(input.peek(), None);
+        assert!(!input.looking_at(b"d"));
+    }
+}
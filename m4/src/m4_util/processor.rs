@@ -0,0 +1,781 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+//! The macro processor: scanning of the input into names, quoted strings
+//! and comments, expansion of macro calls and the builtin macros.
+
+use super::eval::{eval, format_radix};
+use super::input::Input;
+use gettextrs::gettext;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::CString;
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::ExitStatusExt;
+use std::process::Command;
+
+const DEFAULT_LQUOTE: &[u8] = b"`";
+const DEFAULT_RQUOTE: &[u8] = b"'";
+const DEFAULT_BCOMMENT: &[u8] = b"#";
+const DEFAULT_ECOMMENT: &[u8] = b"\n";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Builtin {
+    Changecom,
+    Changequote,
+    Decr,
+    Define,
+    Defn,
+    Divert,
+    Divnum,
+    Dnl,
+    Dumpdef,
+    Errprint,
+    Eval,
+    Ifdef,
+    Ifelse,
+    Include,
+    Incr,
+    Index,
+    Len,
+    M4exit,
+    M4wrap,
+    Maketemp,
+    Mkstemp,
+    Popdef,
+    Pushdef,
+    Shift,
+    Sinclude,
+    Substr,
+    Syscmd,
+    Sysval,
+    Traceoff,
+    Traceon,
+    Translit,
+    Undefine,
+    Undivert,
+}
+
+const BUILTINS: [(&str, Builtin); 33] = [
+    ("changecom", Builtin::Changecom),
+    ("changequote", Builtin::Changequote),
+    ("decr", Builtin::Decr),
+    ("define", Builtin::Define),
+    ("defn", Builtin::Defn),
+    ("divert", Builtin::Divert),
+    ("divnum", Builtin::Divnum),
+    ("dnl", Builtin::Dnl),
+    ("dumpdef", Builtin::Dumpdef),
+    ("errprint", Builtin::Errprint),
+    ("eval", Builtin::Eval),
+    ("ifdef", Builtin::Ifdef),
+    ("ifelse", Builtin::Ifelse),
+    ("include", Builtin::Include),
+    ("incr", Builtin::Incr),
+    ("index", Builtin::Index),
+    ("len", Builtin::Len),
+    ("m4exit", Builtin::M4exit),
+    ("m4wrap", Builtin::M4wrap),
+    ("maketemp", Builtin::Maketemp),
+    ("mkstemp", Builtin::Mkstemp),
+    ("popdef", Builtin::Popdef),
+    ("pushdef", Builtin::Pushdef),
+    ("shift", Builtin::Shift),
+    ("sinclude", Builtin::Sinclude),
+    ("substr", Builtin::Substr),
+    ("syscmd", Builtin::Syscmd),
+    ("sysval", Builtin::Sysval),
+    ("traceoff", Builtin::Traceoff),
+    ("traceon", Builtin::Traceon),
+    ("translit", Builtin::Translit),
+    ("undefine", Builtin::Undefine),
+    ("undivert", Builtin::Undivert),
+];
+
+impl Builtin {
+    fn name(self) -> &'static str {
+        BUILTINS.iter().find(|(_, b)| *b == self).unwrap().0
+    }
+
+    // builtins that are only recognized when followed by arguments,
+    // so that words such as "define" and "index" can appear in text
+    fn needs_args(self) -> bool {
+        !matches!(
+            self,
+            Builtin::Changecom
+                | Builtin::Changequote
+                | Builtin::Divert
+                | Builtin::Divnum
+                | Builtin::Dnl
+                | Builtin::Dumpdef
+                | Builtin::M4exit
+                | Builtin::Sysval
+                | Builtin::Traceoff
+                | Builtin::Traceon
+                | Builtin::Undivert
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Definition {
+    Builtin(Builtin),
+    Text(Vec<u8>),
+}
+
+enum Token {
+    /// A name, which may be a macro to call.
+    Name(Vec<u8>),
+
+    /// Text that is copied as is: a quoted string without its outer
+    /// quotes, or a comment with its delimiters.
+    Text(Vec<u8>),
+
+    Char(u8),
+}
+
+fn is_name_start(b: u8) -> bool {
+    b.is_ascii_alphabetic() || b == b'_'
+}
+
+fn is_name_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+fn parse_int(arg: &[u8]) -> Option<i64> {
+    std::str::from_utf8(arg).ok()?.trim().parse().ok()
+}
+
+// the characters of the from and to arguments of translit,
+// with ranges such as a-z expanded
+fn expand_ranges(s: &[u8]) -> Vec<u8> {
+    let mut chars = Vec::new();
+    let mut i = 0;
+    while i < s.len() {
+        if s.get(i + 1) == Some(&b'-') && i + 2 < s.len() {
+            let (first, last) = (s[i], s[i + 2]);
+            if first <= last {
+                chars.extend(first..=last);
+            } else {
+                chars.extend((last..=first).rev());
+            }
+            i += 3;
+        } else {
+            chars.push(s[i]);
+            i += 1;
+        }
+    }
+    chars
+}
+
+fn translit(s: &[u8], from: &[u8], to: &[u8]) -> Vec<u8> {
+    let from = expand_ranges(from);
+    let to = expand_ranges(to);
+    s.iter()
+        .filter_map(|b| match from.iter().position(|f| f == b) {
+            Some(i) => to.get(i).copied(),
+            None => Some(*b),
+        })
+        .collect()
+}
+
+pub struct Processor<W: Write> {
+    input: Input,
+    macros: HashMap<Vec<u8>, Vec<Definition>>,
+
+    lquote: Vec<u8>,
+    rquote: Vec<u8>,
+    bcomment: Vec<u8>,
+    ecomment: Vec<u8>,
+
+    output: W,
+
+    /// The current diversion; 0 is the output, negative ones are
+    /// discarded.
+    diversion: i64,
+    diversions: BTreeMap<i64, Vec<u8>>,
+
+    /// Text saved by m4wrap, to be read at the end of the input.
+    wrapped: Vec<Vec<u8>>,
+
+    /// The exit status of the last syscmd.
+    sysval: i32,
+
+    trace_all: bool,
+    traced: HashSet<Vec<u8>>,
+
+    /// The nesting of macro calls whose arguments are being read.
+    depth: usize,
+
+    /// The exit status requested by m4exit.
+    exit: Option<i32>,
+
+    /// Whether an error has been reported.
+    failed: bool,
+}
+
+impl<W: Write> Processor<W> {
+    pub fn new(output: W) -> Processor<W> {
+        let macros = BUILTINS
+            .iter()
+            .map(|(name, b)| (name.as_bytes().to_vec(), vec![Definition::Builtin(*b)]))
+            .collect();
+        Processor {
+            input: Input::default(),
+            macros,
+            lquote: DEFAULT_LQUOTE.to_vec(),
+            rquote: DEFAULT_RQUOTE.to_vec(),
+            bcomment: DEFAULT_BCOMMENT.to_vec(),
+            ecomment: DEFAULT_ECOMMENT.to_vec(),
+            output,
+            diversion: 0,
+            diversions: BTreeMap::new(),
+            wrapped: Vec::new(),
+            sysval: 0,
+            trace_all: false,
+            traced: HashSet::new(),
+            depth: 0,
+            exit: None,
+            failed: false,
+        }
+    }
+
+    fn error(&mut self, msg: &str) {
+        eprintln!("m4: {}", msg);
+        self.failed = true;
+    }
+
+    /// Define `name` as `value`, as the define macro does.
+    pub fn define(&mut self, name: &[u8], value: &[u8]) {
+        let defs = self.macros.entry(name.to_vec()).or_default();
+        defs.pop();
+        defs.push(Definition::Text(value.to_vec()));
+    }
+
+    /// Remove all definitions of `name`, as the undefine macro does.
+    pub fn undefine(&mut self, name: &[u8]) {
+        self.macros.remove(name);
+    }
+
+    fn lookup(&self, name: &[u8]) -> Option<&Definition> {
+        self.macros.get(name).and_then(|defs| defs.last())
+    }
+
+    fn quote(&self, s: &[u8]) -> Vec<u8> {
+        [&self.lquote[..], s, &self.rquote[..]].concat()
+    }
+
+    fn emit(&mut self, text: &[u8]) -> io::Result<()> {
+        match self.diversion {
+            0 => self.output.write_all(text),
+            n if n > 0 => {
+                self.diversions.entry(n).or_default().extend(text);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn next_token(&mut self) -> io::Result<Option<Token>> {
+        if self.input.skip(&self.bcomment.clone()) {
+            let mut text = self.bcomment.clone();
+            while !self.input.skip(&self.ecomment.clone()) {
+                match self.input.next() {
+                    Some(b) => text.push(b),
+                    // a comment ends at the end of the input
+                    None => return Ok(Some(Token::Text(text))),
+                }
+            }
+            text.extend(&self.ecomment);
+            return Ok(Some(Token::Text(text)));
+        }
+
+        if self.input.peek().is_some_and(is_name_start) {
+            let mut name = Vec::new();
+            while let Some(b) = self.input.peek().filter(|b| is_name_char(*b)) {
+                name.push(b);
+                self.input.next();
+            }
+            return Ok(Some(Token::Name(name)));
+        }
+
+        if self.input.skip(&self.lquote.clone()) {
+            let mut text = Vec::new();
+            let mut depth = 1;
+            loop {
+                // the end quote is checked first, so that quotes
+                // which are the same string do not nest
+                if self.input.skip(&self.rquote.clone()) {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(Some(Token::Text(text)));
+                    }
+                    text.extend(&self.rquote);
+                } else if self.input.skip(&self.lquote.clone()) {
+                    depth += 1;
+                    text.extend(&self.lquote);
+                } else {
+                    match self.input.next() {
+                        Some(b) => text.push(b),
+                        None => {
+                            return Err(io::Error::other(gettext("end of input in quoted string")))
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(self.input.next().map(Token::Char))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .input
+            .peek()
+            .is_some_and(|b| matches!(b, b' ' | b'\t' | b'\n'))
+        {
+            self.input.next();
+        }
+    }
+
+    // read the arguments of a macro call, after its opening parenthesis
+    fn collect_args(&mut self, args: &mut Vec<Vec<u8>>) -> io::Result<()> {
+        let mut arg = Vec::new();
+        let mut parens = 0;
+        self.skip_whitespace();
+        loop {
+            let token = self.next_token()?.ok_or_else(|| {
+                io::Error::other(gettext!(
+                    "end of input in argument list of '{}'",
+                    String::from_utf8_lossy(&args[0])
+                ))
+            })?;
+            match token {
+                Token::Name(name) => {
+                    if let Some(text) = self.expand_name(name)? {
+                        arg.extend(text);
+                    }
+                }
+                Token::Text(text) => arg.extend(text),
+                Token::Char(b')') if parens == 0 => {
+                    args.push(arg);
+                    return Ok(());
+                }
+                Token::Char(b',') if parens == 0 => {
+                    args.push(std::mem::take(&mut arg));
+                    self.skip_whitespace();
+                }
+                Token::Char(b) => {
+                    match b {
+                        b'(' => parens += 1,
+                        b')' => parens -= 1,
+                        _ => {}
+                    }
+                    arg.push(b);
+                }
+            }
+        }
+    }
+
+    /// Call the macro `name`, pushing its expansion back to the input;
+    /// returns the text to copy instead when `name` is not a macro call.
+    fn expand_name(&mut self, name: Vec<u8>) -> io::Result<Option<Vec<u8>>> {
+        let Some(definition) = self.lookup(&name).cloned() else {
+            return Ok(Some(name));
+        };
+        let has_args = self.input.peek() == Some(b'(');
+        if let Definition::Builtin(b) = definition {
+            if b.needs_args() && !has_args {
+                return Ok(Some(name));
+            }
+        }
+
+        let mut args = vec![name];
+        if has_args {
+            self.input.next();
+            self.depth += 1;
+            let result = self.collect_args(&mut args);
+            self.depth -= 1;
+            result?;
+        }
+        if self.trace_all || self.traced.contains(&args[0]) {
+            self.trace(&args);
+        }
+
+        let expansion = match definition {
+            Definition::Text(text) => self.substitute(&text, &args),
+            Definition::Builtin(b) => self.call_builtin(b, &args)?,
+        };
+        self.input.push(&expansion);
+        Ok(None)
+    }
+
+    fn trace(&self, args: &[Vec<u8>]) {
+        let mut line = format!(
+            "m4trace: -{}- {}",
+            self.depth + 1,
+            String::from_utf8_lossy(&args[0])
+        );
+        if args.len() > 1 {
+            let args: Vec<_> = args[1..]
+                .iter()
+                .map(|arg| String::from_utf8_lossy(arg))
+                .collect();
+            line.push_str(&format!("({})", args.join(", ")));
+        }
+        eprintln!("{}", line);
+    }
+
+    // replace $0 to $9, $#, $* and $@ in the definition of a macro
+    fn substitute(&self, text: &[u8], args: &[Vec<u8>]) -> Vec<u8> {
+        let mut result = Vec::new();
+        let mut i = 0;
+        while i < text.len() {
+            if text[i] != b'$' || i + 1 == text.len() {
+                result.push(text[i]);
+                i += 1;
+                continue;
+            }
+            match text[i + 1] {
+                d @ b'0'..=b'9' => {
+                    if let Some(arg) = args.get((d - b'0') as usize) {
+                        result.extend(arg);
+                    }
+                }
+                b'#' => result.extend((args.len() - 1).to_string().bytes()),
+                b'*' => result.extend(args[1..].join(&b","[..])),
+                b'@' => {
+                    let quoted: Vec<_> = args[1..].iter().map(|arg| self.quote(arg)).collect();
+                    result.extend(quoted.join(&b","[..]));
+                }
+                _ => {
+                    result.push(b'$');
+                    i += 1;
+                    continue;
+                }
+            }
+            i += 2;
+        }
+        result
+    }
+
+    fn call_builtin(&mut self, builtin: Builtin, args: &[Vec<u8>]) -> io::Result<Vec<u8>> {
+        let arg = |i: usize| args.get(i).map_or(&[][..], |a| &a[..]);
+        // arguments of the call, not counting an empty argument list "()"
+        let nargs = if args.len() == 2 && args[1].is_empty() {
+            0
+        } else {
+            args.len() - 1
+        };
+
+        let mut result = Vec::new();
+        match builtin {
+            Builtin::Define | Builtin::Pushdef => {
+                let definition = Definition::Text(arg(2).to_vec());
+                let defs = self.macros.entry(arg(1).to_vec()).or_default();
+                if builtin == Builtin::Define {
+                    defs.pop();
+                }
+                defs.push(definition);
+            }
+            Builtin::Undefine => {
+                for name in &args[1..] {
+                    self.macros.remove(name);
+                }
+            }
+            Builtin::Popdef => {
+                for name in &args[1..] {
+                    if let Some(defs) = self.macros.get_mut(name) {
+                        defs.pop();
+                        if defs.is_empty() {
+                            self.macros.remove(name);
+                        }
+                    }
+                }
+            }
+            Builtin::Defn => {
+                for name in &args[1..] {
+                    // the definition of a builtin cannot be written as text
+                    if let Some(Definition::Text(text)) = self.lookup(name) {
+                        result.extend(self.quote(text));
+                    }
+                }
+            }
+            Builtin::Ifdef => {
+                let i = if self.lookup(arg(1)).is_some() { 2 } else { 3 };
+                result.extend(arg(i));
+            }
+            Builtin::Ifelse => {
+                // ifelse(a, b, equal, [c, d, equal, ...] not-equal)
+                let mut rest = &args[1..];
+                while rest.len() >= 3 {
+                    if rest[0] == rest[1] {
+                        result.extend(&rest[2]);
+                        return Ok(result);
+                    }
+                    rest = &rest[3..];
+                }
+                if rest.len() == 1 {
+                    result.extend(&rest[0]);
+                }
+            }
+            Builtin::Shift => {
+                if args.len() > 2 {
+                    let quoted: Vec<_> = args[2..].iter().map(|a| self.quote(a)).collect();
+                    result.extend(quoted.join(&b","[..]));
+                }
+            }
+            Builtin::Changequote => {
+                if nargs == 0 {
+                    self.lquote = DEFAULT_LQUOTE.to_vec();
+                    self.rquote = DEFAULT_RQUOTE.to_vec();
+                } else {
+                    self.lquote = arg(1).to_vec();
+                    self.rquote = if args.len() > 2 {
+                        arg(2)
+                    } else {
+                        DEFAULT_RQUOTE
+                    }
+                    .to_vec();
+                    if self.lquote.is_empty() {
+                        self.rquote.clear();
+                    }
+                }
+            }
+            Builtin::Changecom => {
+                if nargs == 0 {
+                    self.bcomment.clear();
+                    self.ecomment.clear();
+                } else {
+                    self.bcomment = arg(1).to_vec();
+                    self.ecomment = if args.len() > 2 {
+                        arg(2)
+                    } else {
+                        DEFAULT_ECOMMENT
+                    }
+                    .to_vec();
+                }
+            }
+            Builtin::Divert => {
+                let n = if nargs == 0 {
+                    Some(0)
+                } else {
+                    parse_int(arg(1))
+                };
+                match n {
+                    Some(n) => self.diversion = n,
+                    None => self.error(&gettext!(
+                        "non-numeric argument to divert: '{}'",
+                        String::from_utf8_lossy(arg(1))
+                    )),
+                }
+            }
+            Builtin::Divnum => result.extend(self.diversion.to_string().bytes()),
+            Builtin::Undivert => {
+                let numbers: Vec<i64> = if nargs == 0 {
+                    self.diversions.keys().copied().collect()
+                } else {
+                    args[1..].iter().filter_map(|a| parse_int(a)).collect()
+                };
+                for n in numbers {
+                    if n != self.diversion {
+                        if let Some(text) = self.diversions.remove(&n) {
+                            self.emit(&text)?;
+                        }
+                    }
+                }
+            }
+            Builtin::Dnl => while self.input.next().is_some_and(|b| b != b'\n') {},
+            Builtin::Dumpdef => {
+                let mut names: Vec<Vec<u8>> = if nargs == 0 {
+                    self.macros.keys().cloned().collect()
+                } else {
+                    args[1..].to_vec()
+                };
+                names.sort();
+                for name in names {
+                    let Some(definition) = self.lookup(&name) else {
+                        continue;
+                    };
+                    let text = match definition {
+                        Definition::Builtin(b) => format!("<{}>", b.name()),
+                        Definition::Text(text) => String::from_utf8_lossy(text).into_owned(),
+                    };
+                    eprintln!("{}:\t{}", String::from_utf8_lossy(&name), text);
+                }
+            }
+            Builtin::Errprint => {
+                let mut stderr = io::stderr();
+                stderr.write_all(&args[1..].join(&b' '))?;
+                stderr.flush()?;
+            }
+            Builtin::Eval => result.extend(self.eval(arg(1), arg(2), arg(3)).bytes()),
+            Builtin::Incr | Builtin::Decr => match parse_int(arg(1)) {
+                Some(n) => {
+                    let n = if builtin == Builtin::Incr {
+                        n.wrapping_add(1)
+                    } else {
+                        n.wrapping_sub(1)
+                    };
+                    result.extend(n.to_string().bytes());
+                }
+                None => self.error(&gettext!(
+                    "non-numeric argument to {}: '{}'",
+                    builtin.name(),
+                    String::from_utf8_lossy(arg(1))
+                )),
+            },
+            Builtin::Index => {
+                let (s, sub) = (arg(1), arg(2));
+                let index = (0..=s.len().saturating_sub(sub.len()))
+                    .find(|&i| s[i..].starts_with(sub))
+                    .map_or(-1, |i| i as i64);
+                result.extend(index.to_string().bytes());
+            }
+            Builtin::Len => result.extend(arg(1).len().to_string().bytes()),
+            Builtin::Substr => {
+                let s = arg(1);
+                let start = parse_int(arg(2)).unwrap_or(0).max(0) as usize;
+                let len = if args.len() > 3 {
+                    parse_int(arg(3)).unwrap_or(0).max(0) as usize
+                } else {
+                    s.len()
+                };
+                if start < s.len() {
+                    result.extend(&s[start..s.len().min(start.saturating_add(len))]);
+                }
+            }
+            Builtin::Translit => result.extend(translit(arg(1), arg(2), arg(3))),
+            Builtin::Include | Builtin::Sinclude => {
+                match std::fs::read(std::ffi::OsStr::from_bytes(arg(1))) {
+                    Ok(text) => self.input.push(&text),
+                    Err(e) if builtin == Builtin::Include => {
+                        self.error(&format!("{}: {}", String::from_utf8_lossy(arg(1)), e))
+                    }
+                    Err(_) => {}
+                }
+            }
+            Builtin::M4exit => {
+                self.exit = Some(parse_int(arg(1)).unwrap_or(0) as i32);
+            }
+            Builtin::M4wrap => self.wrapped.push(arg(1).to_vec()),
+            Builtin::Maketemp | Builtin::Mkstemp => match self.make_temp(arg(1)) {
+                Ok(name) => result.extend(name),
+                Err(e) => self.error(&format!("{}: {}", String::from_utf8_lossy(arg(1)), e)),
+            },
+            Builtin::Syscmd => {
+                // the output of the command follows what was written before it
+                self.output.flush()?;
+                let status = Command::new("sh")
+                    .arg("-c")
+                    .arg(std::ffi::OsStr::from_bytes(arg(1)))
+                    .status()?;
+                self.sysval = status
+                    .code()
+                    .or_else(|| status.signal().map(|sig| 128 + sig))
+                    .unwrap_or(0);
+            }
+            Builtin::Sysval => result.extend(self.sysval.to_string().bytes()),
+            Builtin::Traceon | Builtin::Traceoff => {
+                let on = builtin == Builtin::Traceon;
+                if nargs == 0 {
+                    self.trace_all = on;
+                    self.traced.clear();
+                } else {
+                    for name in &args[1..] {
+                        if on {
+                            self.traced.insert(name.clone());
+                        } else {
+                            self.traced.remove(name);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    fn eval(&mut self, expr: &[u8], radix: &[u8], width: &[u8]) -> String {
+        let expr = String::from_utf8_lossy(expr);
+        let radix = if radix.is_empty() {
+            Some(10)
+        } else {
+            parse_int(radix).filter(|r| (2..=36).contains(r))
+        };
+        let Some(radix) = radix else {
+            self.error(&gettext("invalid radix in eval"));
+            return String::new();
+        };
+        let width = parse_int(width).unwrap_or(0).max(0) as usize;
+        match eval(&expr) {
+            Ok(n) => format_radix(n, radix as u32, width),
+            Err(e) => {
+                self.error(&format!("eval: {}: {}", expr, e));
+                String::new()
+            }
+        }
+    }
+
+    // create a file from a template ending in XXXXXX, returning its name
+    fn make_temp(&self, template: &[u8]) -> io::Result<Vec<u8>> {
+        let template = CString::new(template)?;
+        let mut name = template.into_bytes_with_nul();
+        let fd = unsafe { libc::mkstemp(name.as_mut_ptr() as *mut libc::c_char) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        unsafe { libc::close(fd) };
+        name.pop();
+        Ok(name)
+    }
+
+    /// Whether m4exit has been called.
+    pub fn exiting(&self) -> bool {
+        self.exit.is_some()
+    }
+
+    /// Process `text`, an input file, up to its end or to a call to m4exit.
+    pub fn process(&mut self, text: &[u8]) -> io::Result<()> {
+        self.input.push(text);
+        while !self.exiting() {
+            let Some(token) = self.next_token()? else {
+                break;
+            };
+            let text = match token {
+                Token::Name(name) => self.expand_name(name)?,
+                Token::Text(text) => Some(text),
+                Token::Char(b) => Some(vec![b]),
+            };
+            if let Some(text) = text {
+                self.emit(&text)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the text saved by m4wrap, write the diversions to the output
+    /// and return the exit status.
+    pub fn finish(mut self) -> io::Result<i32> {
+        while !self.exiting() && !self.wrapped.is_empty() {
+            let wrapped = std::mem::take(&mut self.wrapped).concat();
+            self.process(&wrapped)?;
+        }
+
+        let diversions = std::mem::take(&mut self.diversions);
+        for text in diversions.values() {
+            self.output.write_all(text)?;
+        }
+        self.output.flush()?;
+
+        Ok(match self.exit {
+            Some(code) => code,
+            None => self.failed as i32,
+        })
+    }
+}
@@ -0,0 +1,133 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use plib::{run_test, TestPlan};
+
+fn m4_test(args: &[&str], stdin_data: &str, expected_out: &str, expected_err: &str, code: i32) {
+    run_test(TestPlan {
+        cmd: String::from("m4"),
+        args: args.iter().map(|s| String::from(*s)).collect(),
+        stdin_data: String::from(stdin_data),
+        expected_out: String::from(expected_out),
+        expected_err: String::from(expected_err),
+        expected_exit_code: code,
+    });
+}
+
+macro_rules! test_m4 {
+    ($test_name:ident) => {
+        m4_test(
+            &[concat!("tests/m4/", stringify!($test_name), ".m4")],
+            "",
+            include_str!(concat!("m4/", stringify!($test_name), ".out")),
+            "",
+            0,
+        )
+    };
+}
+
+#[test]
+fn test_m4_define_and_arguments() {
+    test_m4!(define_and_arguments)
+}
+
+#[test]
+fn test_m4_quotes_and_comments() {
+    test_m4!(quotes_and_comments)
+}
+
+#[test]
+fn test_m4_conditionals() {
+    test_m4!(conditionals)
+}
+
+#[test]
+fn test_m4_diversions() {
+    test_m4!(diversions)
+}
+
+#[test]
+fn test_m4_eval() {
+    test_m4!(eval)
+}
+
+#[test]
+fn test_m4_strings() {
+    test_m4!(strings)
+}
+
+#[test]
+fn test_m4_m4wrap() {
+    test_m4!(m4wrap)
+}
+
+#[test]
+fn test_m4_include() {
+    test_m4!(include)
+}
+
+#[test]
+fn test_m4_stdin_and_defines() {
+    m4_test(
+        &["-D", "NAME=value", "-DEMPTY", "-U", "len", "-"],
+        "NAME [EMPTY] len(x)\n",
+        "value [] len(x)\n",
+        "",
+        0,
+    );
+}
+
+#[test]
+fn test_m4_syscmd() {
+    m4_test(
+        &[],
+        "before\nsyscmd(`echo command; exit 3')sysval\n",
+        "before\ncommand\n3\n",
+        "",
+        0,
+    );
+}
+
+#[test]
+fn test_m4_m4exit() {
+    m4_test(
+        &[],
+        "divert(1)diverted\ndivert(0)m4exit(4)not read\n",
+        "diverted\n",
+        "",
+        4,
+    );
+}
+
+#[test]
+fn test_m4_errors() {
+    m4_test(
+        &[],
+        "eval(1 / 0)|incr(x)|include(`tests/m4/missing.txt')|end\n",
+        "|||end\n",
+        "m4: eval: 1 / 0: divide by zero\n\
+         m4: non-numeric argument to incr: 'x'\n\
+         m4: tests/m4/missing.txt: No such file or directory (os error 2)\n",
+        1,
+    );
+    m4_test(
+        &[],
+        "define(`x', `unterminated)\n",
+        "",
+        "m4: end of input in quoted string\n",
+        1,
+    );
+    m4_test(
+        &[],
+        "define(`x',\n",
+        "",
+        "m4: end of input in argument list of 'define'\n",
+        1,
+    );
+}
@@ -0,0 +1,4 @@
+define(`defined')dnl
+ifdef(`defined', yes, no) ifdef(`undefined', yes, no) ifdef(`undefined', yes)
+ifelse(a, b, eq, ne) ifelse(a, a, eq, ne) ifelse(a, b, eq)
+ifelse(a, b, 1, c, c, 2, 3) ifelse(a, b, 1, c, d, 2, 3)
@@ -0,0 +1,11 @@
+define(`greet', `Hello, $1!')dnl
+greet(`world')
+define(`cat', `$1$2$3')cat(a, b, c)
+define(`count', `$#')count count() count(a, b)
+define(`all', `[$*] [$@]')all(a, `b,c')
+define(`args', ``$0' $1')args(x)
+define(`f', `[$1]')f(a(b,c), `d,e')
+define(`rev', `ifelse($#, 0, , $#, 1, `$1', `rev(shift($@)),$1')')rev(1, 2, 3)
+pushdef(`x', 1)pushdef(`x', 2)x popdef(`x')x undefine(`x')x
+define(`a', `A')defn(`a') `defn' define
+define without arguments is text
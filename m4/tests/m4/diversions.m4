@@ -0,0 +1,7 @@
+divert(-1)discarded
+divert(2)two
+divert(1)one
+divert(0)divnum
+divert(3)three
+divert`'undivert(2)undiverted
+divnum
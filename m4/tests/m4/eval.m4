@@ -0,0 +1,4 @@
+eval(1 + 2 * 3) eval((1 + 2) * 3) eval(2 ** 10) eval(-7 / 2) eval(-7 % 2)
+eval(1 << 4 | 1) eval(0x1f & 7) eval(010) eval(3 > 2 && 2 > 1) eval(!0)
+eval(1 ? 2 : 3) eval(255, 16) eval(5, 2, 8) eval(-5, 10, 3)
+incr(41) decr(0)
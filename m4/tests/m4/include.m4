@@ -0,0 +1,3 @@
+include(`tests/m4/included.txt')dnl
+sinclude(`tests/m4/missing.txt')dnl
+define(`text', `TEXT')include(`tests/m4/included.txt')dnl
@@ -0,0 +1,5 @@
+m4wrap(`wrapped text
+')dnl
+divert(1)diverted
+divert(0)dnl
+main text
@@ -0,0 +1,11 @@
+define(`a', `A')dnl
+`quoted `nested' a' a
+# a comment is copied: a `b'
+changequote([, ])[quoted a] a `a'
+changequote`'`a' a
+changecom(`//')// a comment
+# not a comment: a
+changecom`'# a
+dnl this line is discarded
+text dnl the rest of this line too
+end
@@ -0,0 +1,5 @@
+len(`hello') len(`')
+index(`hello world', `wor') index(`hello', `z') index(`hello', `')
+substr(`hello world', 6) substr(`hello', 1, 3) substr(`hello', 9)
+translit(`hello', `a-z', `A-Z') translit(`hello', `l') translit(`abc', `abc', `cab')
+shift(a, b, c)
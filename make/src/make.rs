@@ -0,0 +1,249 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+extern crate clap;
+extern crate libc;
+extern crate plib;
+
+mod make_util;
+
+use clap::{ArgAction, Parser};
+use gettextrs::{bind_textdomain_codeset, textdomain};
+use make_util::graph;
+use make_util::jobs::{self, Options, Outcome};
+use make_util::macros::Origin;
+use make_util::makefile::Makefile;
+use plib::PROJECT_NAME;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// make - maintain, update, and regenerate groups of programs
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about, args_override_self = true)]
+struct Args {
+    /// Environment variables override macro definitions in makefiles.
+    #[arg(short = 'e', long)]
+    environment_overrides: bool,
+
+    /// Read the makefile from this file, standard input if "-".
+    #[arg(short = 'f', long = "file", action = ArgAction::Append, value_name = "MAKEFILE")]
+    makefiles: Vec<String>,
+
+    /// Ignore the exit status of commands.
+    #[arg(short = 'i', long)]
+    ignore_errors: bool,
+
+    /// Run up to this many jobs at the same time.
+    #[arg(short = 'j', long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    jobs: u32,
+
+    /// Continue with the targets that do not depend on a failed one.
+    #[arg(short = 'k', long, overrides_with = "stop")]
+    keep_going: bool,
+
+    /// Write the commands to run without running them.
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+
+    /// Write the macro definitions and rules.
+    #[arg(short = 'p', long)]
+    print_database: bool,
+
+    /// Exit with status 1 if the targets are not up to date, without
+    /// running any command.
+    #[arg(short = 'q', long)]
+    question: bool,
+
+    /// Do not use the default rules.
+    #[arg(short = 'r', long)]
+    no_builtin_rules: bool,
+
+    /// Stop at the first failed target, cancelling -k.
+    #[arg(short = 'S', long, overrides_with = "keep_going")]
+    stop: bool,
+
+    /// Do not write the commands before running them.
+    #[arg(short = 's', long)]
+    silent: bool,
+
+    /// Touch the targets instead of running their commands.
+    #[arg(short = 't', long)]
+    touch: bool,
+
+    /// The targets to make, the first target of the makefile if none,
+    /// and macro=value definitions, which override those of the makefiles.
+    targets: Vec<String>,
+}
+
+// the words of MAKEFLAGS, where a backslash escapes the next character
+fn split_makeflags(flags: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut chars = flags.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => word.extend(chars.next()),
+            c if c.is_whitespace() => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    // the options can be given as letters alone, as POSIX allows
+    if let Some(first) = words.first_mut() {
+        if !first.starts_with('-') && !first.contains('=') {
+            first.insert(0, '-');
+        }
+    }
+    words
+}
+
+// MAKEFLAGS for recursive makes: the options that are passed on, and the
+// macro operands
+fn makeflags(args: &Args, macros: &[(&str, &str)]) -> String {
+    let options = [
+        (args.environment_overrides, 'e'),
+        (args.ignore_errors, 'i'),
+        (args.keep_going, 'k'),
+        (args.dry_run, 'n'),
+        (args.question, 'q'),
+        (args.no_builtin_rules, 'r'),
+        (args.stop, 'S'),
+        (args.silent, 's'),
+        (args.touch, 't'),
+    ];
+    let letters: String = options
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, letter)| letter)
+        .collect();
+    let mut words = Vec::new();
+    if !letters.is_empty() {
+        words.push(format!("-{}", letters));
+    }
+    for (name, value) in macros {
+        let value = value.replace('\\', "\\\\").replace(' ', "\\ ");
+        words.push(format!("{}={}", name, value));
+    }
+    words.join(" ")
+}
+
+fn read_makefile(makefile: &mut Makefile, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let text = if path == "-" {
+        let mut text = String::new();
+        io::stdin().lock().read_to_string(&mut text)?;
+        text
+    } else {
+        std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?
+    };
+    makefile.parse(&text, path, Origin::Makefile)?;
+    Ok(())
+}
+
+fn make(args: Args) -> Result<Outcome, Box<dyn std::error::Error>> {
+    let (macros, targets): (Vec<&String>, Vec<&String>) =
+        args.targets.iter().partition(|arg| arg.contains('='));
+    let macros: Vec<(&str, &str)> = macros
+        .into_iter()
+        .filter_map(|arg| arg.split_once('='))
+        .collect();
+
+    // the commands see the options and macro operands, and so does any
+    // make they run
+    let flags = makeflags(&args, &macros);
+    std::env::set_var("MAKEFLAGS", &flags);
+    for (name, value) in &macros {
+        std::env::set_var(name, value);
+    }
+
+    let mut makefile = Makefile::new(!args.no_builtin_rules, args.environment_overrides);
+    let program = std::env::current_exe()
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| String::from("make"));
+    makefile.macros.define("MAKE", program, Origin::Builtin);
+    makefile.macros.define("MAKEFLAGS", flags, Origin::Builtin);
+    for (name, value) in &macros {
+        makefile
+            .macros
+            .define(name, value.to_string(), Origin::CommandLine);
+    }
+
+    let mut paths = args.makefiles.clone();
+    if paths.is_empty() {
+        if let Some(path) = ["makefile", "Makefile"]
+            .into_iter()
+            .find(|path| Path::new(path).exists())
+        {
+            paths.push(path.to_string());
+        }
+    }
+    for path in &paths {
+        read_makefile(&mut makefile, path)?;
+    }
+
+    if args.print_database {
+        makefile.print();
+    }
+
+    let goals = if targets.is_empty() {
+        match &makefile.default_goal {
+            Some(goal) => vec![goal.clone()],
+            None if args.print_database => return Ok(Outcome::UpToDate),
+            None if paths.is_empty() => {
+                return Err("no targets specified and no makefile found".into())
+            }
+            None => return Err("no targets".into()),
+        }
+    } else {
+        targets.into_iter().cloned().collect()
+    };
+
+    let options = Options {
+        jobs: args.jobs as usize,
+        ignore_errors: args.ignore_errors,
+        keep_going: args.keep_going,
+        dry_run: args.dry_run,
+        question: args.question,
+        silent: args.silent,
+        touch: args.touch,
+    };
+    let graph = graph::resolve(&makefile, &goals);
+    Ok(jobs::make(&makefile, &graph, &options))
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // parse command line arguments, after the options passed on by a
+    // parent make
+    let mut argv: Vec<String> = std::env::args().collect();
+    if let Ok(flags) = std::env::var("MAKEFLAGS") {
+        argv.splice(1..1, split_makeflags(&flags));
+    }
+    let args = Args::parse_from(argv);
+
+    textdomain(PROJECT_NAME)?;
+    bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
+
+    let exit_code = match make(args) {
+        Ok(Outcome::UpToDate) => 0,
+        Ok(Outcome::OutOfDate) => 1,
+        Ok(Outcome::Failed) => 2,
+        Err(e) => {
+            eprintln!("make: {}", e);
+            2
+        }
+    };
+
+    std::process::exit(exit_code)
+}
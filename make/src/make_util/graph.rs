@@ -0,0 +1,207 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+//! The dependency graph of the targets to make, with the rule found for
+//! each of them.
+
+use super::makefile::Makefile;
+use std::collections::HashMap;
+use std::path::Path;
+
+pub struct Node {
+    pub name: String,
+    pub prerequisites: Vec<usize>,
+    pub commands: Vec<String>,
+
+    /// Whether there is a rule, explicit or inferred, for the target.
+    pub has_rule: bool,
+
+    /// The prerequisite found by an inference rule, for $<.
+    pub source: Option<String>,
+
+    /// The target name without its suffix, for $*.
+    pub stem: String,
+
+    /// The target is not a file, and is always out of date (.PHONY).
+    pub phony: bool,
+}
+
+pub struct Graph {
+    pub nodes: Vec<Node>,
+
+    /// The nodes in the order a sequential make builds them: each one
+    /// after its prerequisites.
+    pub order: Vec<usize>,
+
+    pub goals: Vec<usize>,
+}
+
+struct Resolver<'a> {
+    makefile: &'a Makefile,
+    nodes: Vec<Node>,
+    index: HashMap<String, usize>,
+    visiting: Vec<bool>,
+    order: Vec<usize>,
+}
+
+impl Resolver<'_> {
+    // whether `name` exists or has an explicit rule to make it
+    fn can_make(&self, name: &str) -> bool {
+        self.makefile.targets.contains_key(name) || Path::new(name).exists()
+    }
+
+    // the inference rule for `name`: its source, stem and commands
+    fn infer(&self, name: &str) -> Option<(String, String, Vec<String>)> {
+        let makefile = self.makefile;
+        for to in &makefile.suffixes {
+            let Some(stem) = name.strip_suffix(to.as_str()).filter(|s| !s.is_empty()) else {
+                continue;
+            };
+            for from in &makefile.suffixes {
+                if let Some(commands) = makefile.inference_rules.get(&format!("{}{}", from, to)) {
+                    let source = format!("{}{}", stem, from);
+                    if self.can_make(&source) {
+                        return Some((source, stem.to_string(), commands.clone()));
+                    }
+                }
+            }
+        }
+        for from in &makefile.suffixes {
+            if let Some(commands) = makefile.inference_rules.get(from) {
+                let source = format!("{}{}", name, from);
+                if self.can_make(&source) {
+                    return Some((source, name.to_string(), commands.clone()));
+                }
+            }
+        }
+        None
+    }
+
+    fn stem(&self, name: &str) -> String {
+        self.makefile
+            .suffixes
+            .iter()
+            .find_map(|s| name.strip_suffix(s.as_str()))
+            .unwrap_or(name)
+            .to_string()
+    }
+
+    fn visit(&mut self, name: &str) -> usize {
+        if let Some(&i) = self.index.get(name) {
+            return i;
+        }
+        let i = self.nodes.len();
+        self.index.insert(name.to_string(), i);
+        self.visiting.push(true);
+
+        let target = self.makefile.targets.get(name);
+        let mut prerequisites: Vec<String> =
+            target.map(|t| t.prerequisites.clone()).unwrap_or_default();
+        let mut commands: Vec<String> = target.map(|t| t.commands.clone()).unwrap_or_default();
+        let mut source = None;
+        let mut stem = self.stem(name);
+        let phony = self.makefile.phony.contains(name);
+        if commands.is_empty() && !phony {
+            if let Some((inferred, inferred_stem, inferred_commands)) = self.infer(name) {
+                prerequisites.insert(0, inferred.clone());
+                source = Some(inferred);
+                stem = inferred_stem;
+                commands = inferred_commands;
+            }
+        }
+        self.nodes.push(Node {
+            name: name.to_string(),
+            prerequisites: Vec::new(),
+            has_rule: target.is_some() || source.is_some() || phony,
+            commands,
+            source,
+            stem,
+            phony,
+        });
+
+        let mut edges = Vec::new();
+        for prerequisite in &prerequisites {
+            let j = self.visit(prerequisite);
+            if self.visiting[j] {
+                eprintln!(
+                    "make: circular dependency {} <- {} dropped",
+                    name, prerequisite
+                );
+            } else if !edges.contains(&j) {
+                edges.push(j);
+            }
+        }
+        self.nodes[i].prerequisites = edges;
+        self.visiting[i] = false;
+        self.order.push(i);
+        i
+    }
+}
+
+/// The graph of `goals` and of everything they depend on.
+pub fn resolve(makefile: &Makefile, goals: &[String]) -> Graph {
+    let mut resolver = Resolver {
+        makefile,
+        nodes: Vec::new(),
+        index: HashMap::new(),
+        visiting: Vec::new(),
+        order: Vec::new(),
+    };
+    let goals = goals.iter().map(|goal| resolver.visit(goal)).collect();
+    Graph {
+        nodes: resolver.nodes,
+        order: resolver.order,
+        goals,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::make_util::macros::Origin;
+
+    #[test]
+    fn test_resolve() {
+        let mut makefile = Makefile::default();
+        makefile.suffixes = vec![String::from(".o"), String::from(".x")];
+        makefile
+            .parse(
+                "all: prog extra\n\
+                 prog: a.o b.o\n\
+                 \tlink\n\
+                 a.x b.x:\n\
+                 extra: prog\n\
+                 .x.o:\n\
+                 \tcompile $<\n\
+                 loop: loop2\n\
+                 loop2: loop\n",
+                "makefile",
+                Origin::Makefile,
+            )
+            .unwrap();
+
+        let graph = resolve(&makefile, &[String::from("all")]);
+        let names: Vec<_> = graph
+            .order
+            .iter()
+            .map(|&i| graph.nodes[i].name.as_str())
+            .collect();
+        assert_eq!(names, ["a.x", "a.o", "b.x", "b.o", "prog", "extra", "all"]);
+
+        let a = &graph.nodes[graph.order[1]];
+        assert_eq!(a.source.as_deref(), Some("a.x"));
+        assert_eq!(a.stem, "a");
+        assert_eq!(a.commands, ["compile $<"]);
+        assert!(graph.nodes[graph.goals[0]].has_rule);
+
+        let graph = resolve(&makefile, &[String::from("loop")]);
+        assert_eq!(graph.nodes[0].prerequisites, [1]);
+        assert!(graph.nodes[1].prerequisites.is_empty());
+    }
+}
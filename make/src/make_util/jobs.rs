@@ -0,0 +1,391 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+//! The execution of the commands that bring the targets of a graph up
+//! to date, running up to a given number of jobs at a time.
+//!
+//! A target is started once all of its prerequisites are done, in the
+//! order a sequential make would build it, so that one job gives the
+//! same results as a sequential make. With more jobs, the output of each
+//! target is collected and written when it is done, to keep the output
+//! of the targets apart.
+
+use super::graph::{Graph, Node};
+use super::makefile::Makefile;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Command, ExitStatus};
+use std::sync::mpsc;
+use std::thread;
+use std::time::SystemTime;
+
+#[derive(Default)]
+pub struct Options {
+    /// The number of jobs to run at the same time.
+    pub jobs: usize,
+    pub ignore_errors: bool,
+    pub keep_going: bool,
+    pub dry_run: bool,
+    pub question: bool,
+    pub silent: bool,
+    pub touch: bool,
+}
+
+/// How the goals were made.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    UpToDate,
+    /// Some target is out of date (-q).
+    OutOfDate,
+    Failed,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Pending,
+    Running,
+    Done {
+        /// Whether the target was remade, or is considered to be.
+        remade: bool,
+    },
+    Failed,
+}
+
+// a command line, with its prefixes removed
+struct CommandLine {
+    text: String,
+    silent: bool,
+    ignore_errors: bool,
+    always: bool,
+}
+
+impl CommandLine {
+    fn new(line: &str) -> CommandLine {
+        let mut command = CommandLine {
+            text: String::new(),
+            silent: false,
+            ignore_errors: false,
+            always: false,
+        };
+        let mut text = line.trim_start();
+        loop {
+            match text.chars().next() {
+                Some('@') => command.silent = true,
+                Some('-') => command.ignore_errors = true,
+                Some('+') => command.always = true,
+                _ => break,
+            }
+            text = text[1..].trim_start();
+        }
+        command.text = text.to_string();
+        command
+    }
+}
+
+struct Job {
+    node: usize,
+    name: String,
+    commands: Vec<CommandLine>,
+}
+
+struct JobResult {
+    node: usize,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    success: bool,
+}
+
+fn mtime(name: &str) -> Option<SystemTime> {
+    fs::metadata(name).and_then(|m| m.modified()).ok()
+}
+
+fn touch(name: &str) -> io::Result<()> {
+    let file = File::options().create(true).append(true).open(name)?;
+    file.set_modified(SystemTime::now())
+}
+
+fn status_message(status: ExitStatus) -> String {
+    match (status.code(), status.signal()) {
+        (Some(code), _) => format!("Error {}", code),
+        (None, Some(signal)) => format!("Signal {}", signal),
+        _ => String::from("Error"),
+    }
+}
+
+struct Executor<'a> {
+    makefile: &'a Makefile,
+    graph: &'a Graph,
+    options: &'a Options,
+    states: Vec<State>,
+    shell: String,
+    out_of_date: bool,
+    failed: bool,
+}
+
+impl Executor<'_> {
+    // the next target whose prerequisites are all done; the dependents of
+    // failed targets fail in turn on the way
+    fn next_ready(&mut self) -> Option<usize> {
+        let graph = self.graph;
+        for &i in &graph.order {
+            if self.states[i] != State::Pending {
+                continue;
+            }
+            let node = &graph.nodes[i];
+            let prerequisites = node.prerequisites.iter().map(|&p| self.states[p]);
+            if prerequisites.clone().any(|s| s == State::Failed) {
+                self.states[i] = State::Failed;
+                if self.options.keep_going && graph.goals.contains(&i) {
+                    eprintln!("make: target '{}' not remade because of errors", node.name);
+                }
+                continue;
+            }
+            if prerequisites
+                .clone()
+                .all(|s| matches!(s, State::Done { .. }))
+            {
+                return Some(i);
+            }
+        }
+        None
+    }
+
+    // bring the target up to date if it needs no job, otherwise return the
+    // job that does
+    fn start(&mut self, i: usize) -> Option<Job> {
+        let graph = self.graph;
+        let node = &graph.nodes[i];
+        let time = if node.phony { None } else { mtime(&node.name) };
+        if !node.has_rule && node.commands.is_empty() {
+            self.states[i] = if time.is_some() {
+                State::Done { remade: false }
+            } else {
+                eprintln!("make: don't know how to make '{}'", node.name);
+                State::Failed
+            };
+            return None;
+        }
+
+        // the prerequisites newer than the target, all of them if the
+        // target does not exist
+        let newer: Vec<&str> = node
+            .prerequisites
+            .iter()
+            .filter(|&&p| match time {
+                None => true,
+                Some(_) if self.states[p] == (State::Done { remade: true }) => true,
+                Some(t) => mtime(&graph.nodes[p].name).is_none_or(|pt| pt > t),
+            })
+            .map(|&p| graph.nodes[p].name.as_str())
+            .collect();
+        if time.is_some() && newer.is_empty() {
+            self.states[i] = State::Done { remade: false };
+            return None;
+        }
+        if node.commands.is_empty() {
+            self.states[i] = State::Done { remade: true };
+            return None;
+        }
+        if self.options.question {
+            self.out_of_date = true;
+            self.states[i] = State::Done { remade: true };
+            return None;
+        }
+        if self.options.touch && node.phony {
+            // there is no file to touch
+            self.states[i] = State::Done { remade: true };
+            return None;
+        }
+        if self.options.touch {
+            if !self.options.silent {
+                println!("touch {}", node.name);
+            }
+            self.states[i] = match touch(&node.name) {
+                Ok(()) => State::Done { remade: true },
+                Err(e) => {
+                    eprintln!("make: {}: {}", node.name, e);
+                    State::Failed
+                }
+            };
+            return None;
+        }
+
+        let commands = self.expand_commands(node, &newer);
+        self.states[i] = State::Running;
+        Some(Job {
+            node: i,
+            name: node.name.clone(),
+            commands,
+        })
+    }
+
+    fn expand_commands(&self, node: &Node, newer: &[&str]) -> Vec<CommandLine> {
+        let first = node
+            .prerequisites
+            .first()
+            .map(|&p| &self.graph.nodes[p].name);
+        let internal = |c: char| match c {
+            '@' => Some(node.name.clone()),
+            '<' => node
+                .source
+                .as_ref()
+                .or(first)
+                .cloned()
+                .or(Some(String::new())),
+            '?' => Some(newer.join(" ")),
+            '*' => Some(node.stem.clone()),
+            '%' => Some(String::new()),
+            _ => None,
+        };
+        let makefile = self.makefile;
+        node.commands
+            .iter()
+            .map(|command| {
+                let mut line = CommandLine::new(&makefile.macros.expand_with(command, &internal));
+                line.silent |= makefile.silent.applies(&node.name);
+                line.ignore_errors |= makefile.ignore.applies(&node.name);
+                // a recursive make is run even with -n, to pass it on
+                line.always |= command.contains("$(MAKE)") || command.contains("${MAKE}");
+                line
+            })
+            .collect()
+    }
+
+    fn finish(&mut self, result: JobResult) {
+        io::stdout().write_all(&result.stdout).ok();
+        io::stdout().flush().ok();
+        io::stderr().write_all(&result.stderr).ok();
+        self.states[result.node] = if result.success {
+            State::Done { remade: true }
+        } else {
+            self.failed = true;
+            State::Failed
+        };
+    }
+
+    fn run(&mut self) -> Outcome {
+        // the output of jobs is only collected when they run in parallel
+        let collect = self.options.jobs > 1;
+        let (sender, receiver) = mpsc::channel();
+        thread::scope(|scope| {
+            let mut running = 0;
+            loop {
+                while running < self.options.jobs && (self.options.keep_going || !self.failed) {
+                    let Some(i) = self.next_ready() else {
+                        break;
+                    };
+                    if let Some(job) = self.start(i) {
+                        let sender = sender.clone();
+                        let shell = self.shell.clone();
+                        let options = self.options;
+                        running += 1;
+                        scope.spawn(move || {
+                            sender.send(run_job(job, &shell, options, collect)).ok();
+                        });
+                    } else if self.states[i] == State::Failed {
+                        self.failed = true;
+                    }
+                }
+                if running == 0 {
+                    break;
+                }
+                let result = receiver.recv().expect("a job is running");
+                running -= 1;
+                self.finish(result);
+            }
+        });
+
+        if self.failed {
+            return Outcome::Failed;
+        }
+        if self.out_of_date {
+            return Outcome::OutOfDate;
+        }
+        if !self.options.question {
+            for &goal in &self.graph.goals {
+                if self.states[goal] == (State::Done { remade: false }) {
+                    println!("make: '{}' is up to date.", self.graph.nodes[goal].name);
+                }
+            }
+        }
+        Outcome::UpToDate
+    }
+}
+
+// run the commands of a job in turn, until one of them fails
+fn run_job(job: Job, shell: &str, options: &Options, collect: bool) -> JobResult {
+    let mut result = JobResult {
+        node: job.node,
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+        success: true,
+    };
+    for command in &job.commands {
+        if options.dry_run || !(command.silent || options.silent) {
+            if collect {
+                writeln!(result.stdout, "{}", command.text).ok();
+            } else {
+                println!("{}", command.text);
+            }
+        }
+        if options.dry_run && !command.always {
+            continue;
+        }
+
+        let mut process = Command::new(shell);
+        process.arg("-c").arg(&command.text);
+        let status = if collect {
+            process.output().map(|output| {
+                result.stdout.extend(output.stdout);
+                result.stderr.extend(output.stderr);
+                output.status
+            })
+        } else {
+            io::stdout().flush().ok();
+            process.status()
+        };
+
+        let message = match status {
+            Ok(status) if status.success() => continue,
+            Ok(status) => status_message(status),
+            Err(e) => format!("{}: {}", shell, e),
+        };
+        let ignored = command.ignore_errors || options.ignore_errors;
+        let line = if ignored {
+            format!("make: [{}] {} (ignored)\n", job.name, message)
+        } else {
+            format!("make: *** [{}] {}\n", job.name, message)
+        };
+        if collect {
+            result.stderr.extend(line.as_bytes());
+        } else {
+            eprint!("{}", line);
+        }
+        if !ignored {
+            result.success = false;
+            break;
+        }
+    }
+    result
+}
+
+/// Make the goals of `graph`.
+pub fn make(makefile: &Makefile, graph: &Graph, options: &Options) -> Outcome {
+    let mut executor = Executor {
+        makefile,
+        graph,
+        options,
+        states: vec![State::Pending; graph.nodes.len()],
+        shell: makefile.macros.expand("$(SHELL)"),
+        out_of_date: false,
+        failed: false,
+    };
+    executor.run()
+}
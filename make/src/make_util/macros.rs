@@ -0,0 +1,281 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+//! Macro definitions, which take precedence according to where they come
+//! from, and the expansion of macro references in strings.
+
+use std::collections::HashMap;
+
+// a macro that refers to itself would otherwise be expanded forever
+const MAX_DEPTH: usize = 64;
+
+/// Where a macro is defined.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Origin {
+    /// The default macros of make itself.
+    Builtin,
+    Environment,
+    Makefile,
+    /// A macro=value operand, or one passed on in MAKEFLAGS.
+    CommandLine,
+}
+
+struct Macro {
+    value: String,
+    origin: Origin,
+}
+
+/// The value of the internal macros ($@, $<, ...) of the target whose
+/// commands are expanded.
+pub type Internal<'a> = &'a dyn Fn(char) -> Option<String>;
+
+#[derive(Default)]
+pub struct Macros {
+    table: HashMap<String, Macro>,
+
+    /// Environment variables take precedence over makefile macros (-e).
+    pub env_overrides: bool,
+}
+
+// the directory part of a pathname, "." if it has none
+fn dir_part(word: &str) -> &str {
+    match word.rfind('/') {
+        Some(0) => "/",
+        Some(i) => &word[..i],
+        None => ".",
+    }
+}
+
+fn file_part(word: &str) -> &str {
+    word.rsplit('/').next().unwrap_or(word)
+}
+
+// the substitution of $(name:from=to) in one word: a pattern with % if
+// `from` has one, otherwise a suffix
+fn substitute_word(word: &str, from: &str, to: &str) -> String {
+    if let Some((prefix, suffix)) = from.split_once('%') {
+        if word.len() >= prefix.len() + suffix.len()
+            && word.starts_with(prefix)
+            && word.ends_with(suffix)
+        {
+            let stem = &word[prefix.len()..word.len() - suffix.len()];
+            return to.replacen('%', stem, 1);
+        }
+        return word.to_string();
+    }
+    match word.strip_suffix(from) {
+        Some(base) if !from.is_empty() => format!("{}{}", base, to),
+        _ => word.to_string(),
+    }
+}
+
+// the index of the parenthesis or brace closing the one opened before
+// `s`, counting nested ones
+fn find_close(s: &str, open: char, close: char) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        if c == open {
+            depth += 1;
+        } else if c == close {
+            if depth == 0 {
+                return Some(i);
+            }
+            depth -= 1;
+        }
+    }
+    None
+}
+
+impl Macros {
+    // definitions of a higher rank replace those of a lower one
+    fn rank(&self, origin: Origin) -> u8 {
+        match origin {
+            Origin::Builtin => 0,
+            Origin::Environment if self.env_overrides => 3,
+            Origin::Environment => 1,
+            Origin::Makefile => 2,
+            Origin::CommandLine => 4,
+        }
+    }
+
+    /// Define `name`, unless it has a definition that takes precedence.
+    pub fn define(&mut self, name: &str, value: String, origin: Origin) {
+        if let Some(old) = self.table.get(name) {
+            if self.rank(old.origin) > self.rank(origin) {
+                return;
+            }
+        }
+        self.table.insert(name.to_string(), Macro { value, origin });
+    }
+
+    /// Append `value` to the definition of `name`, separated by a space.
+    pub fn append(&mut self, name: &str, value: &str, origin: Origin) {
+        let value = match self.table.get(name) {
+            Some(old) if !old.value.is_empty() => format!("{} {}", old.value, value),
+            _ => value.to_string(),
+        };
+        self.define(name, value, origin);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.table.get(name).map(|m| m.value.as_str())
+    }
+
+    /// The names and unexpanded values of all macros, sorted by name.
+    pub fn definitions(&self) -> Vec<(&str, &str)> {
+        let mut defs: Vec<_> = self
+            .table
+            .iter()
+            .map(|(name, m)| (name.as_str(), m.value.as_str()))
+            .collect();
+        defs.sort();
+        defs
+    }
+
+    /// Expand the macro references of `s`.
+    pub fn expand(&self, s: &str) -> String {
+        self.expand_with(s, &|_| None)
+    }
+
+    /// Expand the macro references of `s`, with the internal macros of
+    /// a target.
+    pub fn expand_with(&self, s: &str, internal: Internal) -> String {
+        self.expand_depth(s, internal, 0)
+    }
+
+    fn lookup(&self, name: &str, internal: Internal, depth: usize) -> String {
+        let mut chars = name.chars();
+        if let (Some(c), modifier) = (chars.next(), chars.as_str()) {
+            if let Some(value) = internal(c).filter(|_| matches!(modifier, "" | "D" | "F")) {
+                return match modifier {
+                    "D" => value
+                        .split_whitespace()
+                        .map(dir_part)
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    "F" => value
+                        .split_whitespace()
+                        .map(file_part)
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    _ => value,
+                };
+            }
+        }
+        match self.get(name) {
+            Some(value) if depth < MAX_DEPTH => self.expand_depth(value, internal, depth + 1),
+            _ => String::new(),
+        }
+    }
+
+    fn expand_depth(&self, s: &str, internal: Internal, depth: usize) -> String {
+        let mut result = String::new();
+        let mut rest = s;
+        while let Some(i) = rest.find('$') {
+            result.push_str(&rest[..i]);
+            rest = &rest[i + 1..];
+            let Some(c) = rest.chars().next() else {
+                result.push('$');
+                break;
+            };
+            let after = &rest[c.len_utf8()..];
+            let close = match c {
+                '(' => find_close(after, '(', ')'),
+                '{' => find_close(after, '{', '}'),
+                '$' => {
+                    result.push('$');
+                    rest = after;
+                    continue;
+                }
+                _ => {
+                    result.push_str(&self.lookup(&rest[..c.len_utf8()], internal, depth));
+                    rest = after;
+                    continue;
+                }
+            };
+            let Some(close) = close else {
+                // an unterminated reference is kept as is
+                result.push('$');
+                continue;
+            };
+
+            // the name itself can contain macro references
+            let reference = self.expand_depth(&after[..close], internal, depth);
+            rest = &after[close + 1..];
+            let substitution = reference
+                .split_once(':')
+                .and_then(|(name, subst)| Some((name, subst.split_once('=')?)));
+            match substitution {
+                Some((name, (from, to))) => {
+                    let value = self.lookup(name, internal, depth);
+                    let words: Vec<_> = value
+                        .split_whitespace()
+                        .map(|word| substitute_word(word, from, to))
+                        .collect();
+                    result.push_str(&words.join(" "));
+                }
+                None => result.push_str(&self.lookup(&reference, internal, depth)),
+            }
+        }
+        result.push_str(rest);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let mut macros = Macros::default();
+        macros.define("A", String::from("a"), Origin::Makefile);
+        macros.define("AB", String::from("$(A)b"), Origin::Makefile);
+        macros.define("N", String::from("B"), Origin::Makefile);
+        macros.define("SRCS", String::from("x.c dir/y.c z.h"), Origin::Makefile);
+
+        assert_eq!(macros.expand("$(A) ${AB} $A $$A $(UNDEF)."), "a ab a $A .");
+        assert_eq!(macros.expand("$(A$(N))"), "ab");
+        assert_eq!(macros.expand("$(SRCS:.c=.o)"), "x.o dir/y.o z.h");
+        assert_eq!(
+            macros.expand("$(SRCS:%.c=obj/%.o)"),
+            "obj/x.o obj/dir/y.o z.h"
+        );
+        assert_eq!(macros.expand("unterminated $(A"), "unterminated $(A");
+
+        let internal = |c: char| (c == '@').then(|| String::from("dir/target.o"));
+        assert_eq!(
+            macros.expand_with("$@ $(@D) $(@F) $(A)", &internal),
+            "dir/target.o dir target.o a"
+        );
+    }
+
+    #[test]
+    fn test_precedence() {
+        let mut macros = Macros::default();
+        macros.define("X", String::from("env"), Origin::Environment);
+        macros.define("X", String::from("makefile"), Origin::Makefile);
+        assert_eq!(macros.get("X"), Some("makefile"));
+        macros.define("X", String::from("command line"), Origin::CommandLine);
+        macros.define("X", String::from("makefile"), Origin::Makefile);
+        macros.append("X", "appended", Origin::Makefile);
+        assert_eq!(macros.get("X"), Some("command line"));
+
+        let mut macros = Macros {
+            env_overrides: true,
+            ..Default::default()
+        };
+        macros.define("X", String::from("env"), Origin::Environment);
+        macros.define("X", String::from("makefile"), Origin::Makefile);
+        assert_eq!(macros.get("X"), Some("env"));
+
+        macros.define("R", String::from("$(R)"), Origin::Makefile);
+        assert_eq!(macros.expand("[$(R)]"), "[]");
+    }
+}
@@ -0,0 +1,551 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+//! The makefile: its macros, target rules and inference rules, and the
+//! parsing of makefile text.
+
+use super::macros::{Macros, Origin};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::process::{Command, Stdio};
+
+/// The default rules, which are read before any makefile unless -r is
+/// given.
+const BUILTIN_RULES: &str = "\
+MAKE=make
+AR=ar
+ARFLAGS=-rv
+YACC=yacc
+YFLAGS=
+LEX=lex
+LFLAGS=
+LDFLAGS=
+CC=c99
+CFLAGS=-O 1
+FC=fort77
+FFLAGS=-O 1
+.c:
+\t$(CC) $(CFLAGS) $(LDFLAGS) -o $@ $<
+.f:
+\t$(FC) $(FFLAGS) $(LDFLAGS) -o $@ $<
+.sh:
+\tcp $< $@
+\tchmod a+x $@
+.c.o:
+\t$(CC) $(CFLAGS) -c $<
+.f.o:
+\t$(FC) $(FFLAGS) -c $<
+.y.o:
+\t$(YACC) $(YFLAGS) $<
+\t$(CC) $(CFLAGS) -c y.tab.c
+\trm -f y.tab.c
+\tmv y.tab.o $@
+.l.o:
+\t$(LEX) $(LFLAGS) $<
+\t$(CC) $(CFLAGS) -c lex.yy.c
+\trm -f lex.yy.c
+\tmv lex.yy.o $@
+.y.c:
+\t$(YACC) $(YFLAGS) $<
+\tmv y.tab.c $@
+.l.c:
+\t$(LEX) $(LFLAGS) $<
+\tmv lex.yy.c $@
+.c.a:
+\t$(CC) -c $(CFLAGS) $<
+\t$(AR) $(ARFLAGS) $@ $*.o
+\trm -f $*.o
+.f.a:
+\t$(FC) -c $(FFLAGS) $<
+\t$(AR) $(ARFLAGS) $@ $*.o
+\trm -f $*.o
+";
+
+// a makefile that includes itself would otherwise be read forever
+const MAX_INCLUDES: usize = 16;
+
+const BUILTIN_SUFFIXES: [&str; 7] = [".o", ".c", ".y", ".l", ".a", ".sh", ".f"];
+
+#[derive(Default)]
+pub struct Target {
+    pub prerequisites: Vec<String>,
+    pub commands: Vec<String>,
+
+    // the rule the commands come from, so that the commands of a later
+    // rule replace them
+    commands_rule: usize,
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    file: String,
+    line: usize,
+    msg: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.msg)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// what the command lines being read belong to
+enum Context {
+    None,
+    Targets(Vec<String>),
+    Inference(String),
+    /// A special target, whose commands are ignored.
+    Special,
+}
+
+/// The targets a special target such as .SILENT applies to: those given
+/// as its prerequisites, or all of them if it has none.
+#[derive(Default)]
+pub struct SpecialTarget {
+    all: bool,
+    targets: HashSet<String>,
+}
+
+impl SpecialTarget {
+    fn add(&mut self, prerequisites: Vec<String>) {
+        if prerequisites.is_empty() {
+            self.all = true;
+        }
+        self.targets.extend(prerequisites);
+    }
+
+    pub fn applies(&self, name: &str) -> bool {
+        self.all || self.targets.contains(name)
+    }
+}
+
+#[derive(Default)]
+pub struct Makefile {
+    pub macros: Macros,
+    pub targets: HashMap<String, Target>,
+
+    /// The targets, in the order they first appear in a rule.
+    pub target_order: Vec<String>,
+
+    /// The target made when none is given on the command line.
+    pub default_goal: Option<String>,
+
+    /// The commands of the inference rules, by target (".c.o", ".sh").
+    pub inference_rules: HashMap<String, Vec<String>>,
+    pub suffixes: Vec<String>,
+
+    /// The targets that are not files (.PHONY).
+    pub phony: HashSet<String>,
+
+    /// The targets whose commands are not written (.SILENT).
+    pub silent: SpecialTarget,
+
+    /// The targets whose commands failing is not an error (.IGNORE).
+    pub ignore: SpecialTarget,
+
+    rules: usize,
+    includes: usize,
+}
+
+// the position of the first ':' or '=' outside macro references
+fn find_separator(line: &str) -> Option<(usize, char)> {
+    let mut depth = 0;
+    let mut prev = '\0';
+    for (i, c) in line.char_indices() {
+        match c {
+            '(' | '{' if prev == '$' || depth > 0 => depth += 1,
+            ')' | '}' if depth > 0 => depth -= 1,
+            ':' | '=' if depth == 0 => return Some((i, c)),
+            _ => {}
+        }
+        // "$$" is a literal dollar sign, not the start of a reference
+        prev = if prev == '$' && c == '$' { '\0' } else { c };
+    }
+    None
+}
+
+fn ends_with_backslash(line: &str) -> bool {
+    line.chars().rev().take_while(|&c| c == '\\').count() % 2 == 1
+}
+
+// the output of a shell command, for the != assignment
+fn shell_output(shell: &str, command: &str) -> String {
+    let output = Command::new(shell)
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output();
+    match output {
+        Ok(output) => {
+            let text = String::from_utf8_lossy(&output.stdout);
+            text.strip_suffix('\n').unwrap_or(&text).replace('\n', " ")
+        }
+        Err(e) => {
+            eprintln!("make: {}: {}", shell, e);
+            String::new()
+        }
+    }
+}
+
+impl Makefile {
+    /// A makefile with the macros of the environment and, unless
+    /// `builtin_rules` is false, the default rules.
+    pub fn new(builtin_rules: bool, env_overrides: bool) -> Makefile {
+        let mut makefile = Makefile::default();
+        makefile.macros.env_overrides = env_overrides;
+        makefile
+            .macros
+            .define("SHELL", String::from("/bin/sh"), Origin::Builtin);
+        for (name, value) in std::env::vars() {
+            if name != "SHELL" && name != "MAKEFLAGS" {
+                makefile.macros.define(&name, value, Origin::Environment);
+            }
+        }
+        if builtin_rules {
+            makefile.suffixes = BUILTIN_SUFFIXES.iter().map(|s| s.to_string()).collect();
+            makefile
+                .parse(BUILTIN_RULES, "builtin rules", Origin::Builtin)
+                .expect("builtin rules are valid");
+        }
+        makefile
+    }
+
+    /// Whether `name` is the target of an inference rule: one or two of
+    /// the known suffixes.
+    fn is_inference_target(&self, name: &str) -> bool {
+        self.suffixes.iter().any(|s1| {
+            name == s1
+                || name
+                    .strip_prefix(s1.as_str())
+                    .is_some_and(|s2| self.suffixes.iter().any(|s| s == s2))
+        })
+    }
+
+    fn add_command(&mut self, context: &Context, command: String) {
+        match context {
+            Context::None | Context::Special => {}
+            Context::Targets(names) => {
+                for name in names {
+                    let target = self.targets.get_mut(name).expect("target of the rule");
+                    if target.commands_rule != self.rules {
+                        if !target.commands.is_empty() {
+                            eprintln!("make: warning: overriding commands for target '{}'", name);
+                        }
+                        target.commands.clear();
+                        target.commands_rule = self.rules;
+                    }
+                    target.commands.push(command.clone());
+                }
+            }
+            Context::Inference(name) => {
+                if let Some(commands) = self.inference_rules.get_mut(name) {
+                    commands.push(command);
+                }
+            }
+        }
+    }
+
+    fn define_macro(&mut self, name: &str, op: &str, value: &str, origin: Origin) -> bool {
+        let name = self.macros.expand(name);
+        let name = name.trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return false;
+        }
+        let value = value.trim();
+        match op {
+            "+=" => self.macros.append(name, value, origin),
+            "?=" => {
+                if self.macros.get(name).is_none() {
+                    self.macros.define(name, value.to_string(), origin);
+                }
+            }
+            ":=" | "::=" => {
+                let value = self.macros.expand(value);
+                self.macros.define(name, value, origin);
+            }
+            "!=" => {
+                let shell = self.macros.expand("$(SHELL)");
+                let value = shell_output(&shell, &self.macros.expand(value));
+                self.macros.define(name, value, origin);
+            }
+            _ => self.macros.define(name, value.to_string(), origin),
+        }
+        true
+    }
+
+    fn add_rule(&mut self, targets: &str, rest: &str) -> Context {
+        self.rules += 1;
+        let (prerequisites, command) = match rest.split_once(';') {
+            Some((prerequisites, command)) => (prerequisites, Some(command.trim_start())),
+            None => (rest, None),
+        };
+        let targets: Vec<String> = self
+            .macros
+            .expand(targets)
+            .split_whitespace()
+            .map(String::from)
+            .collect();
+        let prerequisites: Vec<String> = self
+            .macros
+            .expand(prerequisites)
+            .split_whitespace()
+            .map(String::from)
+            .collect();
+
+        if let [name] = targets.as_slice() {
+            match name.as_str() {
+                ".PHONY" => self.phony.extend(prerequisites),
+                ".SILENT" => self.silent.add(prerequisites),
+                ".IGNORE" => self.ignore.add(prerequisites),
+                ".SUFFIXES" if prerequisites.is_empty() => self.suffixes.clear(),
+                ".SUFFIXES" => self.suffixes.extend(prerequisites),
+                _ => return self.add_target_rule(targets, prerequisites, command),
+            }
+            return Context::Special;
+        }
+        self.add_target_rule(targets, prerequisites, command)
+    }
+
+    fn add_target_rule(
+        &mut self,
+        targets: Vec<String>,
+        prerequisites: Vec<String>,
+        command: Option<&str>,
+    ) -> Context {
+        let context = if targets.len() == 1
+            && prerequisites.is_empty()
+            && self.is_inference_target(&targets[0])
+        {
+            // a new definition of an inference rule replaces the old one
+            self.inference_rules.insert(targets[0].clone(), Vec::new());
+            Context::Inference(targets[0].clone())
+        } else {
+            for name in &targets {
+                if !self.targets.contains_key(name) {
+                    self.target_order.push(name.clone());
+                }
+                let target = self.targets.entry(name.clone()).or_default();
+                target.prerequisites.extend(prerequisites.iter().cloned());
+                if self.default_goal.is_none() && (!name.starts_with('.') || name.contains('/')) {
+                    self.default_goal = Some(name.clone());
+                }
+            }
+            Context::Targets(targets)
+        };
+        if let Some(command) = command {
+            self.add_command(&context, command.to_string());
+        }
+        context
+    }
+
+    /// Read the macro definitions and rules of makefile `text`, read from
+    /// `file`.
+    pub fn parse(&mut self, text: &str, file: &str, origin: Origin) -> Result<(), ParseError> {
+        let lines: Vec<&str> = text.lines().collect();
+        let mut context = Context::None;
+        let mut i = 0;
+        while i < lines.len() {
+            let lineno = i + 1;
+            let error = |msg: String| ParseError {
+                file: file.to_string(),
+                line: lineno,
+                msg,
+            };
+            let mut line = lines[i].to_string();
+            i += 1;
+
+            if let Some(command) = line.strip_prefix('\t') {
+                // a command line keeps its escaped newlines, for the shell
+                let mut command = command.to_string();
+                while ends_with_backslash(&command) && i < lines.len() {
+                    command.push('\n');
+                    command.push_str(lines[i].strip_prefix('\t').unwrap_or(lines[i]));
+                    i += 1;
+                }
+                if let Context::None = context {
+                    if command.trim().is_empty() || command.trim_start().starts_with('#') {
+                        continue;
+                    }
+                    return Err(error(String::from("command line without a target")));
+                }
+                if !command.trim().is_empty() {
+                    self.add_command(&context, command);
+                }
+                continue;
+            }
+
+            while ends_with_backslash(&line) && i < lines.len() {
+                line.pop();
+                line.truncate(line.trim_end().len());
+                line.push(' ');
+                line.push_str(lines[i].trim_start());
+                i += 1;
+            }
+            if let Some(comment) = line.find('#') {
+                line.truncate(comment);
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            if let Some((directive, paths)) = line.split_once(char::is_whitespace) {
+                if matches!(directive, "include" | "-include") && find_separator(&line).is_none() {
+                    if self.includes == MAX_INCLUDES {
+                        return Err(error(String::from("includes nested too deeply")));
+                    }
+                    for path in self.macros.expand(paths).split_whitespace() {
+                        let text = match std::fs::read_to_string(path) {
+                            Ok(text) => text,
+                            Err(_) if directive == "-include" => continue,
+                            Err(e) => return Err(error(format!("{}: {}", path, e))),
+                        };
+                        self.includes += 1;
+                        let result = self.parse(&text, path, origin);
+                        self.includes -= 1;
+                        result?;
+                    }
+                    context = Context::None;
+                    continue;
+                }
+            }
+
+            match find_separator(&line) {
+                Some((pos, '=')) => {
+                    let (name, op) = match line[..pos].chars().last() {
+                        Some(c @ ('+' | '?' | '!')) => (&line[..pos - 1], format!("{}=", c)),
+                        _ => (&line[..pos], String::from("=")),
+                    };
+                    if !self.define_macro(name, &op, &line[pos + 1..], origin) {
+                        return Err(error(format!("invalid macro name '{}'", name.trim())));
+                    }
+                    context = Context::None;
+                }
+                Some((pos, _)) => {
+                    let rest = &line[pos + 1..];
+                    let assignment = if let Some(value) = rest.strip_prefix('=') {
+                        Some((":=", value))
+                    } else {
+                        rest.strip_prefix(":=").map(|value| ("::=", value))
+                    };
+                    context = match assignment {
+                        Some((op, value)) => {
+                            if !self.define_macro(&line[..pos], op, value, origin) {
+                                return Err(error(format!(
+                                    "invalid macro name '{}'",
+                                    line[..pos].trim()
+                                )));
+                            }
+                            Context::None
+                        }
+                        None => {
+                            let rest = rest.strip_prefix(':').unwrap_or(rest);
+                            self.add_rule(&line[..pos], rest)
+                        }
+                    };
+                }
+                None => return Err(error(String::from("missing separator"))),
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the macros and rules, as for -p.
+    pub fn print(&self) {
+        for (name, value) in self.macros.definitions() {
+            println!("{} = {}", name, value);
+        }
+        let mut inference: Vec<_> = self.inference_rules.iter().collect();
+        inference.sort();
+        for (name, commands) in inference {
+            println!("\n{}:", name);
+            for command in commands {
+                println!("\t{}", command);
+            }
+        }
+        for name in &self.target_order {
+            let target = &self.targets[name];
+            println!("\n{}: {}", name, target.prerequisites.join(" "));
+            for command in &target.commands {
+                println!("\t{}", command);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Makefile {
+        let mut makefile = Makefile::new(false, false);
+        makefile.suffixes = vec![String::from(".c"), String::from(".o")];
+        makefile.parse(text, "makefile", Origin::Makefile).unwrap();
+        makefile
+    }
+
+    #[test]
+    fn test_parse() {
+        let makefile = parse(
+            "# comment\n\
+             OBJS = a.o \\\n\tb.o\n\
+             CC := cc\n\
+             all: prog # comment\n\
+             \n\
+             prog: $(OBJS) ; $(CC) -o $@ $(OBJS)\n\
+             $(OBJS:.o=.c): common.h\n\
+             .c.o:\n\
+             \t$(CC) -c $< \\\n\t  -o $@\n\
+             \n\
+             \techo still .c.o\n",
+        );
+        assert_eq!(makefile.macros.get("OBJS"), Some("a.o b.o"));
+        assert_eq!(makefile.default_goal.as_deref(), Some("all"));
+        assert_eq!(makefile.target_order, ["all", "prog", "a.c", "b.c"]);
+        assert_eq!(makefile.targets["prog"].prerequisites, ["a.o", "b.o"]);
+        assert_eq!(makefile.targets["prog"].commands, ["$(CC) -o $@ $(OBJS)"]);
+        assert_eq!(makefile.targets["b.c"].prerequisites, ["common.h"]);
+        assert_eq!(
+            makefile.inference_rules[".c.o"],
+            ["$(CC) -c $< \\\n  -o $@", "echo still .c.o"]
+        );
+    }
+
+    #[test]
+    fn test_assignments() {
+        let makefile = parse(
+            "A = 1\n\
+             B = $(A)\n\
+             C := $(A)\n\
+             A = 2\n\
+             A += 3\n\
+             A ?= 4\n\
+             D ?= 5\n\
+             E != echo one; echo two\n",
+        );
+        assert_eq!(makefile.macros.expand("$(B) $(C) $(D)"), "2 3 1 5");
+        assert_eq!(makefile.macros.get("E"), Some("one two"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut makefile = Makefile::new(false, false);
+        let err = makefile
+            .parse("all:\n\ttrue\n\njunk\n", "makefile", Origin::Makefile)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "makefile:4: missing separator");
+
+        let err = makefile
+            .parse("\tcommand\n", "other", Origin::Makefile)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "other:1: command line without a target");
+    }
+}
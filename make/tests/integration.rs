@@ -0,0 +1,443 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, Instant, SystemTime};
+
+// Run make in `dir`, feeding `stdin` to it
+fn make(dir: &str, args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_make"))
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn check(output: &Output, expected_out: &str, expected_err: &str, code: i32) {
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected_out);
+    assert_eq!(String::from_utf8_lossy(&output.stderr), expected_err);
+    assert_eq!(output.status.code(), Some(code));
+}
+
+// Set the modification time of `path`, `age` seconds ago
+fn set_age(path: &str, age: u64) {
+    File::options()
+        .append(true)
+        .open(path)
+        .unwrap()
+        .set_modified(SystemTime::now() - Duration::from_secs(age))
+        .unwrap();
+}
+
+#[test]
+fn test_make_rules_and_macros() {
+    let dir = &format!("{}/test_make_rules_and_macros", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    fs::write(
+        format!("{dir}/makefile"),
+        "SRCS = a.c sub/b.c\n\
+         OBJS = $(SRCS:.c=.o)\n\
+         \n\
+         all: first second\n\
+         \t@echo all: $(OBJS)\n\
+         \n\
+         first:\n\
+         \techo making $@\n\
+         second: first\n\
+         \t@echo $@ after $<, $(@D) $(@F) '$$HOME'\n\
+         \t-@exit 4\n\
+         \t@echo $(OBJS:%.o=obj/%.o)\n",
+    )
+    .unwrap();
+    check(
+        &make(dir, &[], ""),
+        "echo making first\n\
+         making first\n\
+         second after first, . second $HOME\n\
+         obj/a.o obj/sub/b.o\n\
+         all: a.o sub/b.o\n",
+        "make: [second] Error 4 (ignored)\n",
+        0,
+    );
+    check(&make(dir, &["-s", "first"], ""), "making first\n", "", 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_make_up_to_date() {
+    let dir = &format!("{}/test_make_up_to_date", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    fs::write(
+        format!("{dir}/makefile"),
+        "prog: main.o util.o\n\
+         \t@echo link $?; touch $@\n\
+         main.o util.o: defs.h\n\
+         \t@echo compile $@; touch $@\n",
+    )
+    .unwrap();
+    for file in ["defs.h", "main.o", "util.o", "prog"] {
+        fs::write(format!("{dir}/{file}"), "").unwrap();
+    }
+    set_age(&format!("{dir}/defs.h"), 30);
+    set_age(&format!("{dir}/main.o"), 20);
+    set_age(&format!("{dir}/util.o"), 20);
+    set_age(&format!("{dir}/prog"), 10);
+
+    check(&make(dir, &[], ""), "make: 'prog' is up to date.\n", "", 0);
+    check(&make(dir, &["-q"], ""), "", "", 0);
+
+    // util.o is now newer than prog, and main.o stays older
+    set_age(&format!("{dir}/util.o"), 5);
+    check(&make(dir, &["-q"], ""), "", "", 1);
+    check(&make(dir, &[], ""), "link util.o\n", "", 0);
+    check(&make(dir, &[], ""), "make: 'prog' is up to date.\n", "", 0);
+
+    // a missing target is made along with everything depending on it
+    fs::remove_file(format!("{dir}/main.o")).unwrap();
+    check(
+        &make(dir, &["-n"], ""),
+        "echo compile main.o; touch main.o\n\
+         echo link main.o; touch prog\n",
+        "",
+        0,
+    );
+    check(&make(dir, &["-t"], ""), "touch main.o\ntouch prog\n", "", 0);
+    check(&make(dir, &[], ""), "make: 'prog' is up to date.\n", "", 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_make_touch_phony() {
+    let dir = &format!("{}/test_make_touch_phony", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    fs::write(
+        format!("{dir}/makefile"),
+        ".PHONY: all\n\
+         all: prog\n\
+         \t@echo done\n\
+         prog:\n\
+         \t@echo making $@\n",
+    )
+    .unwrap();
+
+    // the phony target is not a file, so it is not created
+    check(&make(dir, &["-t"], ""), "touch prog\n", "", 0);
+    assert!(fs::metadata(format!("{dir}/prog")).is_ok());
+    assert!(fs::metadata(format!("{dir}/all")).is_err());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_make_inference_rules() {
+    let dir = &format!("{}/test_make_inference_rules", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    fs::write(
+        format!("{dir}/makefile"),
+        "all: tool data.out\n\
+         .SUFFIXES: .in .out\n\
+         .in.out:\n\
+         \tcat $< $< > $@\n",
+    )
+    .unwrap();
+    fs::write(format!("{dir}/tool.sh"), "#!/bin/sh\necho tool ran\n").unwrap();
+    fs::write(format!("{dir}/data.in"), "data\n").unwrap();
+
+    // the default rule for .sh makes tool, and the suffixes added to
+    // .SUFFIXES make .in.out an inference rule
+    check(
+        &make(dir, &[], ""),
+        "cp tool.sh tool\nchmod a+x tool\ncat data.in data.in > data.out\n",
+        "",
+        0,
+    );
+    assert_eq!(
+        fs::read_to_string(format!("{dir}/data.out")).unwrap(),
+        "data\ndata\n"
+    );
+    let output = Command::new(format!("{dir}/tool")).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "tool ran\n");
+
+    fs::remove_file(format!("{dir}/tool")).unwrap();
+    check(
+        &make(dir, &["-r", "tool"], ""),
+        "",
+        "make: don't know how to make 'tool'\n",
+        2,
+    );
+
+    // clearing .SUFFIXES removes the default rules
+    fs::remove_file(format!("{dir}/data.out")).unwrap();
+    check(
+        &make(dir, &["-f", "-", "tool"], ".SUFFIXES:\n"),
+        "",
+        "make: don't know how to make 'tool'\n",
+        2,
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_make_errors() {
+    let dir = &format!("{}/test_make_errors", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    fs::write(
+        format!("{dir}/makefile"),
+        "all: fails other\n\
+         fails:\n\
+         \t@echo before\n\
+         \t@exit 3\n\
+         \t@echo after\n\
+         other:\n\
+         \t@echo other\n",
+    )
+    .unwrap();
+    check(
+        &make(dir, &[], ""),
+        "before\n",
+        "make: *** [fails] Error 3\n",
+        2,
+    );
+    check(
+        &make(dir, &["-k"], ""),
+        "before\nother\n",
+        "make: *** [fails] Error 3\n\
+         make: target 'all' not remade because of errors\n",
+        2,
+    );
+    check(
+        &make(dir, &["-k", "-S"], ""),
+        "before\n",
+        "make: *** [fails] Error 3\n",
+        2,
+    );
+    check(
+        &make(dir, &["-i"], ""),
+        "before\nafter\nother\n",
+        "make: [fails] Error 3 (ignored)\n",
+        0,
+    );
+    check(
+        &make(dir, &["missing"], ""),
+        "",
+        "make: don't know how to make 'missing'\n",
+        2,
+    );
+    check(
+        &make(dir, &["-f", "-"], "all:\n\ttrue\nnot a rule\n"),
+        "",
+        "make: -:3: missing separator\n",
+        2,
+    );
+    check(
+        &make(dir, &["-f", "nonexistent"], ""),
+        "",
+        "make: nonexistent: No such file or directory (os error 2)\n",
+        2,
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_make_parallel() {
+    let dir = &format!("{}/test_make_parallel", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    fs::write(
+        format!("{dir}/makefile"),
+        "all: a b c\n\
+         \t@echo done\n\
+         a b c:\n\
+         \t@echo $@ start\n\
+         \t@sleep 1\n\
+         \t@echo $@ end\n",
+    )
+    .unwrap();
+
+    // the three targets run at the same time, and the output of each
+    // of them is kept together
+    let start = Instant::now();
+    let output = make(dir, &["-j", "3"], "");
+    assert!(start.elapsed() < Duration::from_millis(2500));
+    let lines: Vec<_> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(String::from)
+        .collect();
+    assert_eq!(lines.len(), 7);
+    for target in lines[..6].chunks(2) {
+        let name = &target[0][..1];
+        assert_eq!(target, [format!("{name} start"), format!("{name} end")]);
+    }
+    assert_eq!(lines[6], "done");
+    assert_eq!(output.status.code(), Some(0));
+
+    // one job builds in the same order as a sequential make
+    check(
+        &make(dir, &["-j", "1", "-f", "-"], "all: a b\na b:\n\t@echo $@\n"),
+        "a\nb\n",
+        "",
+        0,
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_make_parallel_failure() {
+    let dir = &format!("{}/test_make_parallel_failure", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    fs::write(
+        format!("{dir}/makefile"),
+        "all: slow fails next\n\
+         slow:\n\
+         \t@sleep 1\n\
+         \t@echo slow done\n\
+         fails:\n\
+         \t@echo failing\n\
+         \t@exit 1\n\
+         next: fails\n\
+         \t@echo next\n",
+    )
+    .unwrap();
+
+    // the job already running finishes, and no other one is started
+    check(
+        &make(dir, &["-j", "2"], ""),
+        "failing\nslow done\n",
+        "make: *** [fails] Error 1\n",
+        2,
+    );
+    check(
+        &make(dir, &["-j", "2", "-k"], ""),
+        "failing\nslow done\n",
+        "make: *** [fails] Error 1\n\
+         make: target 'all' not remade because of errors\n",
+        2,
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_make_include() {
+    let dir = &format!("{}/test_make_include", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    fs::write(
+        format!("{dir}/makefile"),
+        "NAME = rules\n\
+         include $(NAME).mk other.mk\n\
+         -include missing.mk\n\
+         all: included\n\
+         \t@echo all $(FROM)\n",
+    )
+    .unwrap();
+    fs::write(format!("{dir}/rules.mk"), "FROM = rules.mk\n").unwrap();
+    fs::write(format!("{dir}/other.mk"), "included:\n\t@echo included\n").unwrap();
+    check(&make(dir, &["all"], ""), "included\nall rules.mk\n", "", 0);
+
+    check(
+        &make(dir, &["-f", "-"], "include missing.mk\n"),
+        "",
+        "make: -:1: missing.mk: No such file or directory (os error 2)\n",
+        2,
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_make_special_targets() {
+    let dir = &format!("{}/test_make_special_targets", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    fs::write(
+        format!("{dir}/makefile"),
+        ".PHONY: clean\n\
+         .SILENT: quiet\n\
+         .IGNORE: clean\n\
+         clean:\n\
+         \techo cleaning\n\
+         \texit 1\n\
+         quiet:\n\
+         \techo quiet\n",
+    )
+    .unwrap();
+
+    // clean is made even though a file of that name exists
+    fs::write(format!("{dir}/clean"), "").unwrap();
+    check(
+        &make(dir, &["clean", "quiet"], ""),
+        "echo cleaning\ncleaning\nexit 1\nquiet\n",
+        "make: [clean] Error 1 (ignored)\n",
+        0,
+    );
+
+    check(
+        &make(dir, &["-f", "-", "all"], ".SILENT:\nall:\n\techo silent\n"),
+        "silent\n",
+        "",
+        0,
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_make_macro_operands() {
+    let dir = &format!("{}/test_make_macro_operands", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    fs::write(
+        format!("{dir}/makefile"),
+        "MODE = makefile\n\
+         MODE += appended\n\
+         all:\n\
+         \t@echo $(MODE) $(OTHER)\n\
+         \t@$(MAKE) -f sub.mk\n",
+    )
+    .unwrap();
+    fs::write(
+        format!("{dir}/sub.mk"),
+        "MODE = sub\nsub:\n\techo sub: $(MODE)\n",
+    )
+    .unwrap();
+
+    // the operands override the makefiles, of this make and of the one it
+    // runs, along with the options
+    check(
+        &make(dir, &["MODE=command line", "OTHER=x", "-s"], ""),
+        "command line x\nsub: command line\n",
+        "",
+        0,
+    );
+    check(
+        &make(dir, &["-n"], ""),
+        &format!(
+            "echo makefile appended \n{} -f sub.mk\necho sub: sub\n",
+            env!("CARGO_BIN_EXE_make")
+        ),
+        "",
+        0,
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -12,12 +12,17 @@ extern crate libc;
 extern crate plib;
 
 use clap::Parser;
+use errno::{errno, set_errno, Errno};
 use gettextrs::{bind_textdomain_codeset, textdomain};
 use plib::PROJECT_NAME;
 use std::ffi::CString;
-use std::path::{Component, Path};
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
 
-const _POSIX_PATH_MAX: usize = 255;
+// POSIX minimum limits; _POSIX_PATH_MAX includes the terminating null
+const _POSIX_PATH_MAX: usize = 256;
 const _POSIX_NAME_MAX: usize = 14;
 
 /// pathchk - check pathnames
@@ -25,114 +30,168 @@ const _POSIX_NAME_MAX: usize = 14;
 #[command(author, version, about, long_about)]
 struct Args {
     /// Instead of performing checks based on the underlying file system,
-    /// perform portable, POSIX-compliant checks.
-    #[arg(short, long, group = "mode")]
+    /// check against the POSIX minimum limits and the portable filename
+    /// character set.
+    #[arg(short)]
     portable: bool,
 
     /// Instead of performing checks based on the underlying file system,
-    /// Check each component in pathname for basic validity
-    #[arg(short = 'P', group = "mode")]
+    /// check for empty pathnames and components beginning with '-'.
+    #[arg(short = 'P')]
     basic: bool,
 
     /// The pathnames to be checked
     pathnames: Vec<String>,
 }
 
-fn check_path_basic(pathname: &str) -> Result<(), &'static str> {
+// the components of pathname, ignoring redundant slashes
+fn components(pathname: &str) -> impl Iterator<Item = &str> {
+    pathname.split('/').filter(|c| !c.is_empty())
+}
+
+fn is_portable_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-'
+}
+
+fn check_path_basic(pathname: &str) -> Result<(), String> {
     if pathname.is_empty() {
-        return Err("empty pathname");
+        return Err(String::from("empty pathname"));
     }
 
-    for component in Path::new(pathname).components() {
-        match component {
-            Component::Normal(filename) => {
-                if filename.to_string_lossy().starts_with("-") {
-                    return Err("filename begins with -");
-                }
-            }
-            _ => {}
+    for component in components(pathname) {
+        if component.starts_with('-') {
+            return Err(format!("leading '-' in component: {}", component));
         }
     }
 
     Ok(())
 }
 
-fn check_path_limits(
-    pathname: &str,
-    max_path: usize,
-    max_name: usize,
-    check_ascii: bool,
-) -> Result<(), &'static str> {
-    if pathname.len() > max_path {
-        return Err("pathname too long");
+fn check_path_posix(pathname: &str) -> Result<(), String> {
+    if pathname.len() >= _POSIX_PATH_MAX {
+        return Err(format!(
+            "pathname length {} exceeds limit {}",
+            pathname.len(),
+            _POSIX_PATH_MAX - 1
+        ));
     }
 
-    for component in Path::new(pathname).components() {
-        match component {
-            Component::Normal(filename) => {
-                if filename.len() > max_name {
-                    return Err("filename too long");
-                }
-                if check_ascii && !filename.is_ascii() {
-                    return Err("filename contains non-portable characters");
-                }
-            }
-            _ => {}
+    for component in components(pathname) {
+        if component.len() > _POSIX_NAME_MAX {
+            return Err(format!(
+                "component length {} exceeds limit {}: {}",
+                component.len(),
+                _POSIX_NAME_MAX,
+                component
+            ));
+        }
+        if let Some(c) = component.chars().find(|c| !is_portable_char(*c)) {
+            return Err(format!(
+                "non-portable character '{}' in component: {}",
+                c, component
+            ));
         }
     }
 
     Ok(())
 }
 
-// find the first existing directory in the path
-fn find_fshandle(pathname: &str) -> Result<String, &'static str> {
-    let mut path = Path::new(pathname);
-    let mut fsh = String::new();
-
-    while !path.exists() {
-        match path.parent() {
-            Some(parent) => {
-                fsh = parent.to_string_lossy().to_string();
-                path = parent;
-            }
-            None => {
-                fsh = path.to_string_lossy().to_string();
-                break;
-            }
-        }
+// a limit of the file system containing dir, or None if it is unlimited
+fn pathconf(dir: &Path, name: libc::c_int) -> Result<Option<usize>, String> {
+    let dir_cstr = CString::new(dir.as_os_str().as_bytes()).unwrap();
+
+    // pathconf returns -1 without changing errno for unlimited values
+    set_errno(Errno(0));
+    let value = unsafe { libc::pathconf(dir_cstr.as_ptr(), name) };
+    if value < 0 {
+        return match errno().0 {
+            0 => Ok(None),
+            e => Err(format!(
+                "{}: {}",
+                dir.display(),
+                io::Error::from_raw_os_error(e)
+            )),
+        };
     }
-
-    Ok(fsh)
+    Ok(Some(value as usize))
 }
 
-fn check_path_posix(pathname: &str) -> Result<(), &'static str> {
-    check_path_limits(pathname, _POSIX_PATH_MAX, _POSIX_NAME_MAX, true)
+fn is_searchable(dir: &Path) -> bool {
+    let dir_cstr = CString::new(dir.as_os_str().as_bytes()).unwrap();
+    unsafe { libc::access(dir_cstr.as_ptr(), libc::X_OK) == 0 }
 }
 
-fn check_path_fs(pathname: &str) -> Result<(), &'static str> {
-    let fsh = find_fshandle(pathname)?;
-    let fsh = CString::new(fsh).unwrap();
+fn check_path_fs(pathname: &str) -> Result<(), String> {
+    if pathname.is_empty() {
+        return Err(String::from("empty pathname"));
+    }
 
-    let path_max = unsafe { libc::pathconf(fsh.as_ptr(), libc::_PC_PATH_MAX) };
-    if path_max < 0 {
-        return Err("pathconf error(path length)");
+    let mut dir = if pathname.starts_with('/') {
+        PathBuf::from("/")
+    } else {
+        PathBuf::from(".")
+    };
+
+    if let Some(path_max) = pathconf(&dir, libc::_PC_PATH_MAX)? {
+        if pathname.len() >= path_max {
+            return Err(format!(
+                "pathname length {} exceeds limit {}",
+                pathname.len(),
+                path_max - 1
+            ));
+        }
     }
-    let name_max = unsafe { libc::pathconf(fsh.as_ptr(), libc::_PC_NAME_MAX) };
-    if name_max < 0 {
-        return Err("pathconf error(name length)");
+
+    // Components that do not exist yet are checked against the limits of
+    // the last existing directory, in which they would be created.
+    let mut name_max = pathconf(&dir, libc::_PC_NAME_MAX)?;
+    let mut exists = true;
+
+    for component in components(pathname) {
+        if exists {
+            match fs::metadata(&dir) {
+                Ok(md) if !md.is_dir() => {
+                    return Err(format!("{}: not a directory", dir.display()));
+                }
+                Ok(_) => {
+                    if !is_searchable(&dir) {
+                        return Err(format!("{}: directory not searchable", dir.display()));
+                    }
+                    name_max = pathconf(&dir, libc::_PC_NAME_MAX)?;
+                }
+                Err(_) => exists = false,
+            }
+        }
+
+        if let Some(name_max) = name_max {
+            if component.len() > name_max {
+                return Err(format!(
+                    "component length {} exceeds limit {}: {}",
+                    component.len(),
+                    name_max,
+                    component
+                ));
+            }
+        }
+
+        dir.push(component);
     }
 
-    check_path_limits(pathname, path_max as usize, name_max as usize, false)
+    Ok(())
 }
 
-fn check_path(args: &Args, pathname: &str) -> Result<(), &'static str> {
+fn check_path(args: &Args, pathname: &str) -> Result<(), String> {
+    if !args.portable && !args.basic {
+        return check_path_fs(pathname);
+    }
+
+    if args.basic {
+        check_path_basic(pathname)?;
+    }
     if args.portable {
-        check_path_posix(pathname)
-    } else if args.basic {
-        check_path_basic(pathname)
-    } else {
-        check_path_fs(pathname)
+        check_path_posix(pathname)?;
     }
+    Ok(())
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -0,0 +1,88 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use plib::{run_test, TestPlan};
+
+fn pathchk_test(args: &[&str], expected_err: &str, expected_exit_code: i32) {
+    let str_args: Vec<String> = args.iter().map(|s| String::from(*s)).collect();
+
+    run_test(TestPlan {
+        cmd: String::from("pathchk"),
+        args: str_args,
+        stdin_data: String::new(),
+        expected_out: String::new(),
+        expected_err: String::from(expected_err),
+        expected_exit_code,
+    });
+}
+
+#[test]
+fn test_pathchk_fs() {
+    pathchk_test(&["a/b/c", "/nonexistent/dir"], "", 0);
+    pathchk_test(&[""], ": empty pathname\n", 1);
+
+    let long = "x".repeat(300);
+    pathchk_test(
+        &[&long],
+        &format!("{long}: component length 300 exceeds limit 255: {long}\n"),
+        1,
+    );
+}
+
+#[test]
+fn test_pathchk_not_directory() {
+    let file = &format!("{}/test_pathchk_file", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::write(file, b"").unwrap();
+
+    pathchk_test(
+        &[&format!("{file}/x")],
+        &format!("{file}/x: {file}: not a directory\n"),
+        1,
+    );
+}
+
+#[test]
+fn test_pathchk_portable() {
+    pathchk_test(&["-p", "abc/d.e_f-g"], "", 0);
+    pathchk_test(
+        &["-p", "abc$"],
+        "abc$: non-portable character '$' in component: abc$\n",
+        1,
+    );
+    pathchk_test(
+        &["-p", "a/fifteen_chars_x"],
+        "a/fifteen_chars_x: component length 15 exceeds limit 14: fifteen_chars_x\n",
+        1,
+    );
+
+    let long = "a/".repeat(128);
+    pathchk_test(
+        &["-p", &long],
+        &format!("{long}: pathname length 256 exceeds limit 255\n"),
+        1,
+    );
+}
+
+#[test]
+fn test_pathchk_basic() {
+    pathchk_test(&["-P", "a/b"], "", 0);
+    pathchk_test(&["-P", ""], ": empty pathname\n", 1);
+    pathchk_test(
+        &["-P", "--", "a/-b"],
+        "a/-b: leading '-' in component: -b\n",
+        1,
+    );
+
+    // -p and -P may be combined
+    pathchk_test(
+        &["-p", "-P", "--", "-b"],
+        "-b: leading '-' in component: -b\n",
+        1,
+    );
+}
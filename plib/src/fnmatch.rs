@@ -0,0 +1,342 @@
+//
+// Copyright (c) 2024 Jeff Garzik
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+//! Shell-style pattern matching, as described in XCU 2.13 "Pattern
+//! Matching Notation".
+
+/// Options controlling [`fnmatch`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MatchOptions {
+    /// A slash in the string is only matched by a slash in the pattern,
+    /// never by `*`, `?` or a bracket expression (`FNM_PATHNAME`).
+    pub pathname: bool,
+
+    /// A leading period in the string, or one following a slash when
+    /// `pathname` is set, is only matched by a period in the pattern
+    /// (`FNM_PERIOD`).
+    pub period: bool,
+
+    /// Backslash is an ordinary character (`FNM_NOESCAPE`).
+    pub noescape: bool,
+}
+
+#[derive(Debug)]
+enum BracketItem {
+    Char(char),
+    Range(char, char),
+    Class(String),
+}
+
+#[derive(Debug)]
+struct Bracket {
+    negated: bool,
+    items: Vec<BracketItem>,
+}
+
+impl Bracket {
+    fn matches(&self, c: char) -> bool {
+        let found = self.items.iter().any(|item| match item {
+            BracketItem::Char(ch) => *ch == c,
+            BracketItem::Range(lo, hi) => *lo <= c && c <= *hi,
+            BracketItem::Class(name) => class_matches(name, c),
+        });
+        found != self.negated
+    }
+}
+
+fn class_matches(name: &str, c: char) -> bool {
+    match name {
+        "alnum" => c.is_alphanumeric(),
+        "alpha" => c.is_alphabetic(),
+        "blank" => c == ' ' || c == '\t',
+        "cntrl" => c.is_control(),
+        "digit" => c.is_ascii_digit(),
+        "graph" => !c.is_control() && !c.is_whitespace(),
+        "lower" => c.is_lowercase(),
+        "print" => !c.is_control(),
+        "punct" => c.is_ascii_punctuation(),
+        "space" => c.is_whitespace(),
+        "upper" => c.is_uppercase(),
+        "xdigit" => c.is_ascii_hexdigit(),
+        _ => false,
+    }
+}
+
+#[derive(Debug)]
+enum Token {
+    Literal(char),
+    Any,
+    Star,
+    Bracket(Bracket),
+}
+
+// Parse the bracket expression starting after the '[' at p[start].
+// Returns the expression and the index following the closing ']', or None
+// if the bracket is not terminated, in which case '[' is an ordinary
+// character.
+fn parse_bracket(p: &[char], start: usize, opts: MatchOptions) -> Option<(Bracket, usize)> {
+    let mut i = start;
+    let mut negated = false;
+    if i < p.len() && (p[i] == '!' || p[i] == '^') {
+        negated = true;
+        i += 1;
+    }
+
+    let mut items = Vec::new();
+    let mut first = true;
+    loop {
+        if i >= p.len() {
+            return None;
+        }
+        let c = p[i];
+        if c == ']' && !first {
+            return Some((Bracket { negated, items }, i + 1));
+        }
+        first = false;
+
+        // character class, equivalence class or collating symbol
+        if c == '[' && i + 1 < p.len() && matches!(p[i + 1], ':' | '=' | '.') {
+            let delim = p[i + 1];
+            let body_start = i + 2;
+            let mut j = body_start;
+            while j + 1 < p.len() && !(p[j] == delim && p[j + 1] == ']') {
+                j += 1;
+            }
+            if j + 1 >= p.len() {
+                return None;
+            }
+            let body: String = p[body_start..j].iter().collect();
+            i = j + 2;
+            match delim {
+                ':' => items.push(BracketItem::Class(body)),
+                _ => {
+                    // only single-character equivalence classes and
+                    // collating symbols are supported
+                    let mut chars = body.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(ch), None) => items.push(BracketItem::Char(ch)),
+                        _ => return None,
+                    }
+                }
+            }
+            continue;
+        }
+
+        let lo = if c == '\\' && !opts.noescape && i + 1 < p.len() {
+            i += 1;
+            p[i]
+        } else {
+            c
+        };
+        i += 1;
+
+        // range expression
+        if i + 1 < p.len() && p[i] == '-' && p[i + 1] != ']' {
+            let mut hi = p[i + 1];
+            i += 2;
+            if hi == '\\' && !opts.noescape && i < p.len() {
+                hi = p[i];
+                i += 1;
+            }
+            items.push(BracketItem::Range(lo, hi));
+        } else {
+            items.push(BracketItem::Char(lo));
+        }
+    }
+}
+
+fn tokenize(pattern: &str, opts: MatchOptions) -> Vec<Token> {
+    let p: Vec<char> = pattern.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < p.len() {
+        match p[i] {
+            '*' => {
+                // consecutive stars are equivalent to a single one
+                if !matches!(tokens.last(), Some(Token::Star)) {
+                    tokens.push(Token::Star);
+                }
+                i += 1;
+            }
+            '?' => {
+                tokens.push(Token::Any);
+                i += 1;
+            }
+            '[' => match parse_bracket(&p, i + 1, opts) {
+                Some((bracket, next)) => {
+                    tokens.push(Token::Bracket(bracket));
+                    i = next;
+                }
+                None => {
+                    tokens.push(Token::Literal('['));
+                    i += 1;
+                }
+            },
+            '\\' if !opts.noescape && i + 1 < p.len() => {
+                tokens.push(Token::Literal(p[i + 1]));
+                i += 2;
+            }
+            c => {
+                tokens.push(Token::Literal(c));
+                i += 1;
+            }
+        }
+    }
+
+    tokens
+}
+
+/// A compiled pattern, for matching many strings against the same pattern.
+#[derive(Debug)]
+pub struct Pattern {
+    tokens: Vec<Token>,
+    opts: MatchOptions,
+}
+
+impl Pattern {
+    pub fn new(pattern: &str, opts: MatchOptions) -> Pattern {
+        Pattern {
+            tokens: tokenize(pattern, opts),
+            opts,
+        }
+    }
+
+    /// Return true if the whole of `string` is matched by the pattern.
+    pub fn matches(&self, string: &str) -> bool {
+        let s: Vec<char> = string.chars().collect();
+        self.matches_chars(&s)
+    }
+
+    fn is_leading_period(&self, s: &[char], si: usize) -> bool {
+        self.opts.period && s[si] == '.' && (si == 0 || (self.opts.pathname && s[si - 1] == '/'))
+    }
+
+    // can a wildcard ('?', '*' or a bracket expression) match s[si]?
+    fn wildcard_ok(&self, s: &[char], si: usize) -> bool {
+        let slash = self.opts.pathname && s[si] == '/';
+        !slash && !self.is_leading_period(s, si)
+    }
+
+    fn matches_chars(&self, s: &[char]) -> bool {
+        let tokens = &self.tokens;
+        let mut pi = 0;
+        let mut si = 0;
+
+        // position after the most recent star, and the string position it
+        // was tried at, for backtracking
+        let mut star: Option<(usize, usize)> = None;
+
+        while si < s.len() {
+            if pi < tokens.len() {
+                let advance = match &tokens[pi] {
+                    Token::Star => {
+                        star = Some((pi + 1, si));
+                        pi += 1;
+                        continue;
+                    }
+                    Token::Any => self.wildcard_ok(s, si),
+                    Token::Literal(c) => *c == s[si],
+                    Token::Bracket(b) => self.wildcard_ok(s, si) && b.matches(s[si]),
+                };
+                if advance {
+                    pi += 1;
+                    si += 1;
+                    continue;
+                }
+            }
+
+            // let the last star absorb one more character.  Earlier stars
+            // cannot do better, as they are subject to the same limits.
+            match star {
+                Some((star_pi, star_si)) if self.wildcard_ok(s, star_si) => {
+                    star = Some((star_pi, star_si + 1));
+                    pi = star_pi;
+                    si = star_si + 1;
+                }
+                _ => return false,
+            }
+        }
+
+        tokens[pi..].iter().all(|t| matches!(t, Token::Star))
+    }
+}
+
+/// Return true if `string` matches the shell pattern `pattern`.
+pub fn fnmatch(pattern: &str, string: &str, opts: MatchOptions) -> bool {
+    Pattern::new(pattern, opts).matches(string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn m(pattern: &str, string: &str) -> bool {
+        fnmatch(pattern, string, MatchOptions::default())
+    }
+
+    #[test]
+    fn test_wildcards() {
+        assert!(m("*", ""));
+        assert!(m("*.rs", "main.rs"));
+        assert!(!m("*.rs", "main.rc"));
+        assert!(m("a?c", "abc"));
+        assert!(!m("a?c", "ac"));
+        assert!(m("a*b*c", "aXXbYYbZc"));
+        assert!(!m("a*b*c", "aXXbYYbZ"));
+        assert!(m("*a*a*a*", "aaa"));
+    }
+
+    #[test]
+    fn test_brackets() {
+        assert!(m("[abc]", "b"));
+        assert!(!m("[abc]", "d"));
+        assert!(m("[!abc]", "d"));
+        assert!(m("[a-c]x", "bx"));
+        assert!(m("[]]", "]"));
+        assert!(m("[!]]", "a"));
+        assert!(m("[a-]", "-"));
+        assert!(m("[[:digit:]][[:upper:]]", "7Q"));
+        assert!(!m("[[:digit:]]", "x"));
+        assert!(m("[[=a=]]", "a"));
+        // an unterminated bracket is an ordinary character
+        assert!(m("[ab", "[ab"));
+    }
+
+    #[test]
+    fn test_escapes() {
+        assert!(m("\\*", "*"));
+        assert!(!m("\\*", "a"));
+        assert!(m("a\\", "a\\"));
+
+        let opts = MatchOptions {
+            noescape: true,
+            ..Default::default()
+        };
+        assert!(fnmatch("\\*", "\\abc", opts));
+    }
+
+    #[test]
+    fn test_pathname_and_period() {
+        let opts = MatchOptions {
+            pathname: true,
+            period: true,
+            ..Default::default()
+        };
+        assert!(fnmatch("*/*.c", "src/main.c", opts));
+        assert!(!fnmatch("*.c", "src/main.c", opts));
+        assert!(!fnmatch("src?main.c", "src/main.c", opts));
+        assert!(!fnmatch("*", ".hidden", opts));
+        assert!(!fnmatch("src/*", "src/.hidden", opts));
+        assert!(fnmatch("src/.*", "src/.hidden", opts));
+
+        assert!(m("*", ".hidden"));
+        assert!(m("*.c", "src/main.c"));
+    }
+}
@@ -40,3 +40,15 @@ pub fn input_reader(
     let file = input_stream(pathname, dashed_stdin)?;
     Ok(io::BufReader::new(file))
 }
+
+/// Write `prompt` to standard error, prefixed with the utility `name`, and
+/// read the answer from standard input.  Only an answer starting with 'y'
+/// is a yes; end of file or an error reading the answer counts as no.
+pub fn prompt_user(name: &str, prompt: &str) -> bool {
+    eprint!("{}: {} ", name, prompt);
+    let mut response = String::new();
+    match io::stdin().read_line(&mut response) {
+        Ok(0) | Err(_) => false,
+        Ok(_) => response.to_lowercase().starts_with('y'),
+    }
+}
@@ -7,10 +7,15 @@
 // SPDX-License-Identifier: MIT
 //
 
+pub mod fnmatch;
 pub mod group;
 pub mod io;
 pub mod lzw;
 pub mod modestr;
+pub mod regex;
+pub mod signal;
+pub mod terminfo;
+pub mod termios;
 pub mod testing;
 pub mod utmpx;
 
@@ -10,651 +10,538 @@
 // adapted from FreeBSD's zopen.c.
 //
 
-use std::io::{self, Error, ErrorKind, Read};
+//! The adaptive Lempel-Ziv-Welch coding of compress(1), used by .Z files.
+//!
+//! Codes start at 9 bits and grow up to the maximum given in the header.
+//! They are packed least significant bit first, in groups of eight codes;
+//! when the code size changes, or the table is cleared, the rest of the
+//! current group is padded, which decoders must mirror.
 
-const INIT_BITS: u32 = 9;
-const HSIZE: usize = 69_001;
-const CHECK_GAP: usize = 10_000;
-const BITS: u32 = 16;
-const MAGIC_HEADER: [u8; 2] = [0x1F, 0x9D];
-const HDR_BIT_MASK: u8 = 0x1f;
-const HDR_BLOCK_MASK: u8 = 0x80;
-const FIRST: i32 = 257;
-const CLEAR: i32 = 256;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
 
-const LMASK: [i32; 9] = [0xff, 0xfe, 0xfc, 0xf8, 0xf0, 0xe0, 0xc0, 0x80, 0x00];
-const RMASK: [i32; 9] = [0x00, 0x01, 0x03, 0x07, 0x0f, 0x1f, 0x3f, 0x7f, 0xff];
+const INIT_BITS: u32 = 9;
 
-/// A wrapper around the Read trait object used
-/// for reading the compressed file or
-/// file to be compressed
-struct CompReader {
-    inner_rdr: Box<dyn Read>,
-}
+/// Largest number of bits per code supported.
+pub const MAX_BITS: u32 = 16;
 
-impl CompReader {
-    fn new(rdr: Box<dyn Read>) -> Self {
-        Self { inner_rdr: rdr }
-    }
+/// Default number of bits per code.
+pub const DEFAULT_BITS: u32 = 16;
 
-    fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let mut total_read = 0;
+/// Number of input bytes between checks of the compression ratio, once
+/// the table is full.
+const CHECK_GAP: u64 = 10_000;
 
-        while total_read < buf.len() {
-            match self.inner_rdr.read(&mut buf[total_read..]) {
-                Ok(n) => {
-                    if n == 0 {
-                        break;
-                    }
-                    total_read += n;
-                }
-                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
-                Err(e) => {
-                    return Err(e);
-                }
-            }
-        }
+pub const MAGIC_HEADER: [u8; 2] = [0x1f, 0x9d];
+const HDR_BIT_MASK: u8 = 0x1f;
+const HDR_BLOCK_MASK: u8 = 0x80;
 
-        if total_read == 0 {
-            return Err(io::Error::new(ErrorKind::UnexpectedEof, "Unexpected EOF"));
-        }
+/// Code that resets the table, in block mode.
+const CLEAR: u32 = 256;
 
-        Ok(total_read)
-    }
-}
+/// First free code, in block mode.
+const FIRST: u32 = 257;
 
 fn max_code(n_bits: u32) -> u32 {
-    (1 << (n_bits)) - 1
+    (1 << n_bits) - 1
 }
 
-pub struct UnixLZWReader {
-    /// the reader of the compressed file or the file to be compressed
-    rdr: CompReader,
-
-    /// if the compressed file has header or not
-    have_hdr: bool,
+fn corrupt_input() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "corrupt input")
+}
 
-    /// if the eof has been reached or not
-    eof: bool,
+/// Compresses data written to it, writing the compressed stream to the
+/// inner writer.  [`UnixLZWWriter::finish`] must be called at the end.
+pub struct UnixLZWWriter<W: Write> {
+    inner: W,
 
-    /// the max no of bits for the code(maxmaxcode is derived from this)
+    /// Maximum number of bits per code, and the code it allows
     maxbits: u32,
+    maxmaxcode: u32,
 
-    /// the current max no of bits for the code
+    /// Current number of bits per code, and the largest code it allows
     n_bits: u32,
+    maxcode: u32,
 
-    /// if BLOCK_MASK is enabled in the compressed file or not
-    block_compress: bool,
+    /// Codes of the strings in the table, keyed by the code of the prefix
+    /// and the last byte
+    table: HashMap<(u32, u8), u32>,
+    free_ent: u32,
 
-    /// It indicates if the buffer has to be cleared or not
-    clear: bool,
+    /// Code of the string matched so far, if any input was read
+    ent: Option<u32>,
 
-    code: i32,
+    /// Group of codes being packed, and the bit offset in it
+    buf: [u8; MAX_BITS as usize],
+    offset: u32,
 
-    /// the previously recognized code
-    oldcode: i32,
+    /// Compression ratio at the last check, and when to check next
+    ratio: u64,
+    checkpoint: u64,
 
-    incode: i32,
+    bytes_in: u64,
+    bytes_out: u64,
+}
 
-    /// the max no of codes that can be created of n_bits
-    maxcode: i32,
+impl<W: Write> UnixLZWWriter<W> {
+    /// Start a compressed stream with codes of at most `bits` bits, which
+    /// must be between 9 and [`MAX_BITS`].
+    pub fn new(mut inner: W, bits: u32) -> io::Result<UnixLZWWriter<W>> {
+        if !(INIT_BITS..=MAX_BITS).contains(&bits) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid number of bits",
+            ));
+        }
 
-    /// the max value of maxcode
-    maxmaxcode: i32,
+        // block mode is always used
+        let header = [
+            MAGIC_HEADER[0],
+            MAGIC_HEADER[1],
+            bits as u8 | HDR_BLOCK_MASK,
+        ];
+        inner.write_all(&header)?;
+
+        Ok(UnixLZWWriter {
+            inner,
+            maxbits: bits,
+            maxmaxcode: 1 << bits,
+            n_bits: INIT_BITS,
+            maxcode: max_code(INIT_BITS),
+            table: HashMap::new(),
+            free_ent: FIRST,
+            ent: None,
+            buf: [0; MAX_BITS as usize],
+            offset: 0,
+            ratio: 0,
+            checkpoint: CHECK_GAP,
+            bytes_in: 0,
+            bytes_out: header.len() as u64,
+        })
+    }
 
-    /// the next free entry on the table
-    free_ent: i32,
+    /// Number of bytes compressed so far.
+    pub fn bytes_in(&self) -> u64 {
+        self.bytes_in
+    }
 
-    finchar: i32,
+    /// Number of bytes of compressed output so far, including the header.
+    pub fn bytes_out(&self) -> u64 {
+        self.bytes_out
+    }
 
-    /// It's the current read offset
-    roffset: i32,
+    fn write_group(&mut self, len: usize) -> io::Result<()> {
+        self.inner.write_all(&self.buf[..len])?;
+        self.bytes_out += len as u64;
+        self.buf = [0; MAX_BITS as usize];
+        self.offset = 0;
+        Ok(())
+    }
 
-    size: i32,
+    // append a code; `clear` is set for the CLEAR code
+    fn output(&mut self, code: u32, clear: bool) -> io::Result<()> {
+        let mut value = code as u64;
+        let mut offset = self.offset;
+        let mut bits = self.n_bits;
+        while bits > 0 {
+            let byte = (offset / 8) as usize;
+            let shift = offset % 8;
+            let n = bits.min(8 - shift);
+            self.buf[byte] |= ((value & ((1 << n) - 1)) << shift) as u8;
+            value >>= n;
+            offset += n;
+            bits -= n;
+        }
+        self.offset = offset;
 
-    /// Buffer to fill as we go on read  from the read stream
-    gbuf: [u8; BITS as usize],
+        if self.offset == self.n_bits * 8 {
+            self.write_group(self.n_bits as usize)?;
+        }
 
-    tab_suffix: [i32; HSIZE],
+        // a new code size starts a new group
+        if self.free_ent > self.maxcode || clear {
+            if self.offset > 0 {
+                self.write_group(self.n_bits as usize)?;
+            }
+            if clear {
+                self.n_bits = INIT_BITS;
+                self.maxcode = max_code(INIT_BITS);
+            } else {
+                self.n_bits += 1;
+                self.maxcode = if self.n_bits == self.maxbits {
+                    self.maxmaxcode
+                } else {
+                    max_code(self.n_bits)
+                };
+            }
+        }
+        Ok(())
+    }
 
-    tab_prefix: [u16; HSIZE],
-}
+    // once the table is full, it is cleared when the compression ratio
+    // starts to fall
+    fn check_ratio(&mut self) -> io::Result<()> {
+        self.checkpoint = self.bytes_in + CHECK_GAP;
 
-impl UnixLZWReader {
-    pub fn new(rdr: Box<dyn Read>) -> UnixLZWReader {
-        UnixLZWReader {
-            rdr: CompReader::new(rdr),
+        let ratio = (self.bytes_in << 8) / self.bytes_out.max(1);
+        if ratio > self.ratio {
+            self.ratio = ratio;
+        } else {
+            self.ratio = 0;
+            self.table.clear();
+            self.free_ent = FIRST;
+            self.output(CLEAR, true)?;
+        }
+        Ok(())
+    }
 
-            have_hdr: false,
+    /// Write the last code, and flush the inner writer.  Nothing may be
+    /// written afterwards.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if let Some(ent) = self.ent.take() {
+            self.output(ent, false)?;
+        }
+        if self.offset > 0 {
+            self.write_group(self.offset.div_ceil(8) as usize)?;
+        }
+        self.inner.flush()
+    }
 
-            eof: false,
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
 
-            maxbits: 0,
+impl<W: Write> Write for UnixLZWWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        for &c in data {
+            self.bytes_in += 1;
 
-            n_bits: 0,
+            let Some(ent) = self.ent else {
+                self.ent = Some(c as u32);
+                continue;
+            };
+            if let Some(&code) = self.table.get(&(ent, c)) {
+                self.ent = Some(code);
+                continue;
+            }
 
-            block_compress: false,
+            self.output(ent, false)?;
+            self.ent = Some(c as u32);
 
-            clear: false,
+            if self.free_ent < self.maxmaxcode {
+                self.table.insert((ent, c), self.free_ent);
+                self.free_ent += 1;
+            } else if self.bytes_in >= self.checkpoint {
+                self.check_ratio()?;
+            }
+        }
+        Ok(data.len())
+    }
 
-            code: 0,
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
 
-            oldcode: 0,
+/// Decompresses the compressed stream read from the inner reader.
+pub struct UnixLZWReader<R: Read> {
+    inner: R,
+    have_hdr: bool,
+    eof: bool,
 
-            incode: 0,
+    maxbits: u32,
+    maxmaxcode: u32,
+    block_compress: bool,
 
-            maxcode: 0,
+    n_bits: u32,
+    maxcode: u32,
 
-            maxmaxcode: 0,
+    /// Group of codes being unpacked, and the bit offset and number of
+    /// usable bits in it
+    buf: [u8; MAX_BITS as usize],
+    offset: u32,
+    size: u32,
 
-            free_ent: 0,
+    /// Whether the next code starts a new group with 9-bit codes
+    clear: bool,
 
-            finchar: 0,
+    /// Each code of the table is a prefix code followed by a byte
+    prefix: Vec<u16>,
+    suffix: Vec<u8>,
+    free_ent: u32,
 
-            roffset: 0,
+    oldcode: Option<u32>,
+    finchar: u8,
 
+    /// Decoded bytes not returned yet
+    pending: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read> UnixLZWReader<R> {
+    pub fn new(inner: R) -> UnixLZWReader<R> {
+        UnixLZWReader {
+            inner,
+            have_hdr: false,
+            eof: false,
+            maxbits: 0,
+            maxmaxcode: 0,
+            block_compress: false,
+            n_bits: INIT_BITS,
+            maxcode: max_code(INIT_BITS),
+            buf: [0; MAX_BITS as usize],
+            offset: 0,
             size: 0,
+            clear: false,
+            prefix: vec![0; 1 << MAX_BITS],
+            suffix: (0..1u32 << MAX_BITS).map(|c| c as u8).collect(),
+            free_ent: 0,
+            oldcode: None,
+            finchar: 0,
+            pending: Vec::new(),
+            pos: 0,
+        }
+    }
 
-            gbuf: [0; BITS as usize],
+    fn read_full(&mut self, buf_len: usize) -> io::Result<usize> {
+        let mut filled = 0;
+        while filled < buf_len {
+            match self.inner.read(&mut self.buf[filled..buf_len]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(filled)
+    }
 
-            tab_suffix: [0; HSIZE],
+    fn read_header(&mut self) -> io::Result<()> {
+        let mut header = [0u8; 3];
+        let mut filled = 0;
+        while filled < header.len() {
+            match self.inner.read(&mut header[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if filled < header.len() || header[..2] != MAGIC_HEADER {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not in compressed format",
+            ));
+        }
 
-            tab_prefix: [0; HSIZE],
+        self.maxbits = (header[2] & HDR_BIT_MASK) as u32;
+        self.block_compress = header[2] & HDR_BLOCK_MASK != 0;
+        if !(INIT_BITS..=MAX_BITS).contains(&self.maxbits) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "compressed with {} bits, can only handle {} bits",
+                    self.maxbits, MAX_BITS
+                ),
+            ));
         }
+        self.maxmaxcode = 1 << self.maxbits;
+        self.free_ent = if self.block_compress { FIRST } else { 256 };
+        self.have_hdr = true;
+        Ok(())
     }
 
-    fn getcode(&mut self) -> i32 {
-        if self.clear || self.roffset >= self.size || self.free_ent > self.maxcode {
-            // as free_ent represents the index of the next available entry that can be made
-            // on the table, so if its more than the self.maxcode (i.e max allowed no of codes),
-            // which is derived from the current no of bits of code, then we need to expand
-            // our entry by increasing the n_bits by 1 and then updating the max_code
-            // from that
+    // the next code, or None at the end of input
+    fn getcode(&mut self) -> io::Result<Option<u32>> {
+        if self.clear || self.offset >= self.size || self.free_ent > self.maxcode {
             if self.free_ent > self.maxcode {
                 self.n_bits += 1;
                 self.maxcode = if self.n_bits == self.maxbits {
                     self.maxmaxcode
                 } else {
-                    max_code(self.n_bits) as i32
+                    max_code(self.n_bits)
                 };
             }
-
-            // reset the table entry back to smallest one
             if self.clear {
                 self.n_bits = INIT_BITS;
-                self.maxcode = max_code(self.n_bits) as i32;
+                self.maxcode = max_code(INIT_BITS);
                 self.clear = false;
             }
 
-            // the buffer for current max n of bits
-            let gbuf = &mut self.gbuf[0..self.n_bits as usize];
-
-            match self.rdr.read_exact(gbuf) {
-                Ok(n) => {
-                    self.size = n as i32;
-                }
-                Err(_) => return -1,
+            let n = self.read_full(self.n_bits as usize)?;
+            if n == 0 {
+                return Ok(None);
             }
+            self.offset = 0;
 
-            self.roffset = 0;
-            self.size = (self.size << 3) - (self.n_bits - 1) as i32;
+            // a partial code at the end is padding
+            self.size = (n as u32 * 8).saturating_sub(self.n_bits - 1);
+            if self.size == 0 {
+                return Ok(None);
+            }
         }
 
-        let mut r_off = self.roffset;
-        let mut bits = self.n_bits;
-        let mut bp: usize = 0;
-
-        bp = bp + (r_off >> 3) as usize;
-        r_off = r_off & 7;
-
-        let mut gcode: i32 = (self.gbuf[bp] as i32) >> r_off;
-        bp = bp + 1;
-        bits = bits - (8 - r_off) as u32;
-        r_off = 8 - r_off;
-
-        if bits >= 8 {
-            gcode = gcode | ((self.gbuf[bp] as i32) << r_off);
-            bp = bp + 1;
-            r_off = r_off + 8;
-            bits = bits - 8;
+        let mut code: u32 = 0;
+        let mut got = 0;
+        while got < self.n_bits {
+            let byte = self.buf[(self.offset / 8) as usize] as u32;
+            let shift = self.offset % 8;
+            let n = (self.n_bits - got).min(8 - shift);
+            code |= ((byte >> shift) & ((1 << n) - 1)) << got;
+            got += n;
+            self.offset += n;
         }
-
-        gcode = gcode | (((self.gbuf[bp] as i32) & RMASK[bits as usize]) << r_off);
-        self.roffset = self.roffset + self.n_bits as i32;
-
-        gcode
+        Ok(Some(code))
     }
 
-    /// Read from the compressed stream of file
-    pub fn read(&mut self) -> io::Result<Vec<u8>> {
-        let mut outbytes: Vec<u8> = Vec::new();
-
+    // decode codes until some output is available, or the input ends
+    fn decode(&mut self) -> io::Result<()> {
         if !self.have_hdr {
-            // 3-byte header: 2 byte magic, 1 byte a bitmask of options.
-            let mut header = [0; 3];
-            self.rdr.read_exact(&mut header)?;
-
-            let header_magic = &header[0..2];
-
-            if &MAGIC_HEADER[..] != header_magic {
-                return Err(Error::new(
-                    ErrorKind::Other,
-                    "invalid file header: magic number",
-                ));
-            }
-
-            // the third byte has bitmask of options
-            // (Eg) if it has 10011111
-            // that means the first bit represents the BLOCK_MASK i.e block_compress
-            // has to be enabled or not
-            //
-            // the bit that we get onwards represent the bit position of the value we want as
-            // max no bits
-            // if it's at 5th position then it means 2^4 = 16
-            let options = header[2];
-
-            self.maxbits = (options & HDR_BIT_MASK) as u32;
-            self.block_compress = (options & HDR_BLOCK_MASK) != 0;
-
-            if self.maxbits > BITS {
-                return Err(Error::new(ErrorKind::Other, "invalid file header: bits"));
-            }
-
-            // the max value that self.maxcode can have, which is derived
-            // from the maxbits that codes can have
-            // hence, 2^(self.maxbits)
-            self.maxmaxcode = 1 << self.maxbits;
-
-            // the no of bits of code that we start with
-            // btw, this no of bits also represent the fact that there can be
-            // 2 ^ (self.n_bits) entries in the table initially
-            self.n_bits = INIT_BITS; // 9
-            self.maxcode = max_code(self.n_bits) as i32; // 511
-
-            for code in (0..=255).rev() {
-                let idx: usize = code as usize;
-                self.code = code;
-                self.tab_prefix[idx] = 0;
-                self.tab_suffix[idx] = code;
-            }
-
-            if self.block_compress {
-                // TODO: understand why we need to skip one index (i.e 256) (initial guess is that
-                // we need that index reserverd for CLEAR)
-                self.free_ent = FIRST;
-            } else {
-                self.free_ent = 256;
-            }
-
-            self.have_hdr = true;
-
-            self.oldcode = self.getcode();
-            self.finchar = self.oldcode;
-            if self.oldcode < 0 {
-                self.eof = true;
-                return Ok(Vec::new());
-            }
-
-            outbytes.push(self.finchar as u8);
+            self.read_header()?;
         }
 
-        loop {
-            let mut stack: Vec<u8> = Vec::new();
-
-            self.code = self.getcode();
-            if self.code < 0 {
+        self.pending.clear();
+        self.pos = 0;
+        while self.pending.is_empty() {
+            let Some(mut code) = self.getcode()? else {
                 self.eof = true;
-                break;
-            }
+                return Ok(());
+            };
 
-            if (self.code == CLEAR) && self.block_compress {
-                // clear the table and fill it again with value
-                for code in (0..=255).rev() {
-                    let idx: usize = code as usize;
-                    self.code = code;
-                    self.tab_prefix[idx] = 0;
+            let Some(oldcode) = self.oldcode else {
+                if code > 255 {
+                    return Err(corrupt_input());
                 }
+                self.finchar = code as u8;
+                self.oldcode = Some(code);
+                self.pending.push(self.finchar);
+                return Ok(());
+            };
+
+            if code == CLEAR && self.block_compress {
                 self.clear = true;
                 self.free_ent = FIRST - 1;
-
-                self.code = self.getcode();
-                if self.code < 0 {
-                    self.eof = true;
-                    break;
+                match self.getcode()? {
+                    Some(c) => code = c,
+                    None => {
+                        self.eof = true;
+                        return Ok(());
+                    }
                 }
             }
-
-            self.incode = self.code;
-
-            if self.code >= self.free_ent {
-                stack.push(self.finchar as u8);
-                self.code = self.oldcode;
+            let incode = code;
+
+            // a code not in the table yet is the previous string followed
+            // by its own first byte
+            let start = self.pending.len();
+            if code >= self.free_ent {
+                if code > self.free_ent {
+                    return Err(corrupt_input());
+                }
+                self.pending.push(self.finchar);
+                code = oldcode;
             }
-
-            while self.code >= 256 {
-                let code = self.code as usize;
-                stack.push(self.tab_suffix[code] as u8);
-                self.code = self.tab_prefix[code] as i32;
+            while code >= 256 {
+                self.pending.push(self.suffix[code as usize]);
+                code = self.prefix[code as usize] as u32;
             }
-            self.finchar = self.tab_suffix[self.code as usize];
-            stack.push(self.finchar as u8);
+            self.finchar = self.suffix[code as usize];
+            self.pending.push(self.finchar);
+            self.pending[start..].reverse();
 
-            for stackchar in stack.iter().rev() {
-                outbytes.push(*stackchar);
-            }
-
-            self.code = self.free_ent;
-            if self.code < self.maxmaxcode {
-                let code = self.code as usize;
-                self.tab_prefix[code] = self.oldcode as u16;
-                self.tab_suffix[code] = self.finchar;
-                self.free_ent = self.code + 1;
+            if self.free_ent < self.maxmaxcode {
+                self.prefix[self.free_ent as usize] = oldcode as u16;
+                self.suffix[self.free_ent as usize] = self.finchar;
+                self.free_ent += 1;
             }
-
-            self.oldcode = self.incode;
+            self.oldcode = Some(incode);
         }
-
-        Ok(outbytes)
+        Ok(())
     }
 }
 
-/// Enum to store the current state of the writer
-#[derive(PartialEq)]
-enum WriterState {
-    Start,
-    Middle,
-}
-
-pub struct UnixLZWWriter {
-    /// The current state of the writer
-    state: WriterState,
-
-    /// Current number of bits per code
-    n_bits: u32,
-
-    /// Maximum bits allowed for codes
-    maxbits: u32,
-
-    /// Maximum code for current number of bits
-    maxcode: i32,
-
-    // Maximum code for maximum bits
-    maxmaxcode: i32,
-
-    /// Hash table for codes
-    htab: Vec<i32>,
-
-    /// Size of the hash table
-    hsize: i32,
-    /// Code table
-    codetab: Vec<u16>,
-
-    /// Next free entry in the table
-    free_ent: i32,
-
-    /// Flag for block compression
-    block_compress: bool,
-
-    /// Flag to clear the dictionary
-    clear_flg: bool,
-
-    ratio: i32,
-
-    /// Next checkpoint for compression ratio
-    checkpoint: i32,
-
-    /// Offset for current byte
-    offset: u32,
-
-    in_count: i32,
-
-    bytes_out: i32,
-
-    out_count: i32,
-
-    /// Buffer for output bytes
-    buf: [u8; BITS as usize],
-
-    write_params: WriteParams,
-}
-
-struct WriteParams {
-    fcode: i32,
-
-    ent: i32,
-
-    hsize_reg: i32,
+impl<R: Read> Read for UnixLZWReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.pending.len() {
+            if self.eof {
+                return Ok(0);
+            }
+            self.decode()?;
+        }
 
-    hshift: i32,
+        let n = out.len().min(self.pending.len() - self.pos);
+        out[..n].copy_from_slice(&self.pending[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
 }
 
-impl UnixLZWWriter {
-    pub fn new(mbits: Option<u32>) -> Self {
-        let maxbits = match mbits {
-            Some(m) => m.clamp(9, 16),
-            None => BITS,
-        };
-
-        let maxmaxcode = (1 << maxbits) as i32;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        Self {
-            state: WriterState::Start,
-            n_bits: INIT_BITS,
-            maxbits,
-            maxcode: max_code(INIT_BITS) as i32,
-            maxmaxcode,
-            htab: vec![-1; HSIZE],
-            codetab: vec![0; HSIZE],
-            hsize: HSIZE as i32,
-            free_ent: 0,
-            block_compress: true,
-            clear_flg: false,
-            ratio: 0,
-            checkpoint: CHECK_GAP as i32,
-            offset: 0,
-            in_count: 0,
-            bytes_out: 0,
-            out_count: 0,
-            buf: [0; BITS as usize],
-            write_params: WriteParams {
-                fcode: 0,
-                ent: 0,
-                hsize_reg: 0,
-                hshift: 0,
-            },
-        }
+    fn compress(data: &[u8], bits: u32) -> Vec<u8> {
+        let mut writer = UnixLZWWriter::new(Vec::new(), bits).unwrap();
+        writer.write_all(data).unwrap();
+        writer.finish().unwrap();
+        writer.into_inner()
     }
 
-    fn clear_block(&mut self) -> io::Result<()> {
-        self.checkpoint = self.in_count + CHECK_GAP as i32;
-
-        let rat = if self.in_count > 0x007fffff {
-            match self.bytes_out >> 8 {
-                0 => 0x7fffffff,
-                _ => self.in_count / (self.bytes_out >> 8),
-            }
-        } else {
-            (self.in_count << 8) / self.bytes_out
-        };
-
-        if rat > self.ratio {
-            self.ratio = rat;
-        } else {
-            self.ratio = 0;
-            self.clear_hash();
-            self.free_ent = FIRST;
-            self.clear_flg = true;
-            self.output(CLEAR)?;
-        }
-        Ok(())
+    fn uncompress(data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        UnixLZWReader::new(data).read_to_end(&mut out)?;
+        Ok(out)
     }
 
-    fn clear_hash(&mut self) {
-        self.htab.fill(-1)
+    // deterministic data that compresses somewhat, but not too well
+    fn sample(len: usize) -> Vec<u8> {
+        let mut state: u32 = 12345;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                b"abcdefgh \n"[(state >> 16) as usize % 10]
+            })
+            .collect()
     }
 
-    fn output(&mut self, mut ocode: i32) -> io::Result<Vec<u8>> {
-        let mut r_off = self.offset;
-        let mut bits = self.n_bits;
-        let mut bp: usize = 0;
-        let mut outbytes: Vec<u8> = Vec::new();
-
-        if ocode >= 0 {
-            bp += (r_off >> 3) as usize;
-            r_off &= 7;
-
-            self.buf[bp] = ((self.buf[bp] as i32 & RMASK[r_off as usize] as i32) as u8)
-                | ((ocode << r_off) & LMASK[r_off as usize] as i32) as u8;
-            bp += 1;
-            bits -= 8 - r_off;
-            ocode >>= 8 - r_off;
-
-            if bits >= 8 {
-                self.buf[bp] = ocode as u8;
-                bp += 1;
-                ocode >>= 8;
-                bits -= 8;
-            }
-
-            if bits > 0 {
-                self.buf[bp] = ocode as u8;
-            }
-
-            self.offset += self.n_bits;
-
-            if self.offset == (self.n_bits << 3) {
-                bits = self.n_bits;
-                self.bytes_out += bits as i32;
-                let temp_buf = &self.buf[..bits as usize];
-                outbytes.extend(temp_buf);
-                self.offset = 0;
-            }
-
-            if self.free_ent > self.maxcode || self.clear_flg {
-                if self.offset > 0 {
-                    let temp_buf = &self.buf[..self.n_bits as usize];
-                    outbytes.extend(temp_buf);
-                    self.bytes_out += self.n_bits as i32;
-                }
-                self.offset = 0;
-
-                if self.clear_flg {
-                    self.maxcode = max_code(INIT_BITS) as i32;
-                    self.clear_flg = false;
-                } else {
-                    self.n_bits += 1;
-                    self.maxcode = if self.n_bits == self.maxbits {
-                        self.maxmaxcode
-                    } else {
-                        max_code(self.n_bits) as i32
-                    };
-                }
-            }
-        } else {
-            if self.offset > 0 {
-                self.offset = (self.offset + 7) / 8;
-                let temp_buf = &self.buf[..self.offset as usize];
-                outbytes.extend(temp_buf);
-                self.bytes_out += self.offset as i32;
-            }
-            self.offset = 0;
-        }
-
-        Ok(outbytes)
+    #[test]
+    fn test_known_output() {
+        // as produced by compress(1)
+        assert_eq!(compress(b"", 16), [0x1f, 0x9d, 0x90]);
+        assert_eq!(
+            compress(b"aaaa\n", 16),
+            [0x1f, 0x9d, 0x90, 0x61, 0x02, 0x86, 0x51, 0x00]
+        );
     }
 
-    pub fn write(&mut self, buffer: &[u8]) -> io::Result<Vec<u8>> {
-        let mut outbytes: Vec<u8> = Vec::new();
-
-        if self.state == WriterState::Start {
-            self.state = WriterState::Middle;
-            self.maxmaxcode = 1 << self.maxbits;
-            outbytes.extend(&[0x1F, 0x9D]);
-            let tmp = (self.maxbits as u8)
-                | if self.block_compress {
-                    HDR_BLOCK_MASK
-                } else {
-                    0
-                };
-            outbytes.push(tmp);
-
-            self.bytes_out = 3;
-            self.clear_flg = false;
-            self.in_count = 1;
-            self.free_ent = if self.block_compress { FIRST } else { 256 };
-
-            let mut fcode = self.hsize;
-            let mut hshift = 0;
-            while fcode < 65536 {
-                hshift += 1;
-                fcode *= 2;
+    #[test]
+    fn test_roundtrip() {
+        for bits in [9, 12, 16] {
+            for data in [
+                Vec::new(),
+                b"a".to_vec(),
+                b"abababababababababab".to_vec(),
+                vec![0u8; 100_000],
+                sample(300_000),
+            ] {
+                let compressed = compress(&data, bits);
+                assert_eq!(compressed[2], 0x80 | bits as u8);
+                assert_eq!(uncompress(&compressed).unwrap(), data);
             }
-            self.write_params = WriteParams {
-                fcode,
-                ent: buffer[0] as i32,
-                hsize_reg: self.hsize,
-                hshift: 8 - hshift,
-            };
-
-            self.clear_hash();
         }
+    }
 
-        for &item in &buffer[1..] {
-            let c = item as i32;
-            self.in_count += 1;
-
-            self.write_params.fcode = ((c) << self.maxbits) + (self.write_params.ent);
-            let mut i = (c << self.write_params.hshift) ^ self.write_params.ent;
-
-            let mut skip_flag = false;
-            if self.htab[i as usize] as i32 == self.write_params.fcode {
-                self.write_params.ent = self.codetab[i as usize] as i32;
-                continue;
-            } else if self.htab[i as usize] as i32 >= 0 {
-                let mut disp = self.write_params.hsize_reg - i;
-                if i == 0 {
-                    disp = 1;
-                }
-                loop {
-                    i -= disp;
-                    if i < 0 {
-                        i += self.write_params.hsize_reg;
-                    }
-                    if self.htab[i as usize] as i32 == self.write_params.fcode {
-                        self.write_params.ent = self.codetab[i as usize] as i32;
-                        skip_flag = true;
-                        break;
-                    }
-                    if self.htab[i as usize] < 0 {
-                        break;
-                    }
-                }
-            }
-            if skip_flag {
-                continue;
-            }
-
-            outbytes.extend(self.output(self.write_params.ent)?);
-            self.out_count += 1;
-            self.write_params.ent = c;
+    #[test]
+    fn test_invalid() {
+        assert!(UnixLZWWriter::new(Vec::new(), 8).is_err());
+        assert!(UnixLZWWriter::new(Vec::new(), 17).is_err());
 
-            if self.free_ent < self.maxmaxcode {
-                self.codetab[i as usize] = self.free_ent as u16;
-                self.free_ent += 1;
-                self.htab[i as usize] = self.write_params.fcode as i32;
-            } else if self.in_count >= self.checkpoint && self.block_compress {
-                self.clear_block()?;
-            }
-        }
-        Ok(outbytes)
-    }
+        assert!(uncompress(b"").is_err());
+        assert!(uncompress(b"\x1f\x8b\x08").is_err());
+        assert!(uncompress(b"\x1f\x9d\x91").is_err());
 
-    /// Return the remaining bytes which could not be written from close()
-    pub fn close(&mut self) -> io::Result<Vec<u8>> {
-        let mut outbytes: Vec<u8> = Vec::new();
-        outbytes.extend(self.output(self.write_params.ent)?);
-        self.out_count += 1;
-        outbytes.extend(self.output(-1)?);
-        Ok(outbytes)
+        // the first code is not a literal
+        assert!(uncompress(&[0x1f, 0x9d, 0x90, 0xff, 0xff]).is_err());
     }
 }
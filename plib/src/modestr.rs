@@ -8,8 +8,8 @@
 //
 
 use libc::{
-    S_IRGRP, S_IROTH, S_IRUSR, S_IRWXG, S_IRWXO, S_IRWXU, S_ISUID, S_ISVTX, S_IWGRP, S_IWOTH,
-    S_IWUSR, S_IXGRP, S_IXOTH, S_IXUSR,
+    S_IRGRP, S_IROTH, S_IRUSR, S_IRWXG, S_IRWXO, S_IRWXU, S_ISGID, S_ISUID, S_ISVTX, S_IWGRP,
+    S_IWOTH, S_IWUSR, S_IXGRP, S_IXOTH, S_IXUSR,
 };
 
 #[derive(PartialEq, Debug)]
@@ -110,11 +110,11 @@ enum ParseState {
 }
 
 pub fn parse(mode: &str) -> Result<ChmodMode, String> {
-    match u32::from_str_radix(mode, 8) {
-        Ok(m) => {
-            return Ok(ChmodMode::Absolute(m));
-        }
-        Err(_) => {}
+    if !mode.is_empty() && mode.chars().all(|c| c.is_digit(8)) {
+        return match u32::from_str_radix(mode, 8) {
+            Ok(m) if m <= 0o7777 => Ok(ChmodMode::Absolute(m)),
+            _ => Err(format!("invalid mode: {}", mode)),
+        };
     }
 
     let mut state = ParseState::Wholist;
@@ -224,159 +224,126 @@ pub fn parse(mode: &str) -> Result<ChmodMode, String> {
         symbolic.clauses.push(clause);
     }
 
+    // every clause requires at least one action
+    if symbolic.clauses.is_empty() || symbolic.clauses.iter().any(|c| c.actions.is_empty()) {
+        return Err(format!("invalid mode: {}", mode));
+    }
+
     Ok(ChmodMode::Symbolic(symbolic))
 }
 
-// apply symbolic mutations to the given file at path
-pub fn mutate(cur_mode: u32, symbolic: &ChmodSymbolic) -> u32 {
-    let mut new_mode = cur_mode;
-    let mut user = cur_mode & S_IRWXU as u32;
-    let mut group = cur_mode & S_IRWXG as u32;
-    let mut others = cur_mode & S_IRWXO as u32;
+// query the process file mode creation mask
+fn current_umask() -> u32 {
+    unsafe {
+        let mask = libc::umask(0);
+        libc::umask(mask);
+        mask as u32
+    }
+}
 
-    // apply each clause
-    for clause in &symbolic.clauses {
-        // apply each action
-        for action in &clause.actions {
-            match action.op {
-                // add bits to the mode
-                ChmodActionOp::Add => {
-                    if action.copy_user {
-                        user |= cur_mode & S_IRWXU as u32;
-                    }
-                    if action.copy_group {
-                        group |= cur_mode & S_IRWXG as u32;
-                    }
-                    if action.copy_others {
-                        others |= cur_mode & S_IRWXO as u32;
-                    }
-                    if action.read {
-                        user |= S_IRUSR as u32;
-                        group |= S_IRGRP as u32;
-                        others |= S_IROTH as u32;
-                    }
-                    if action.write {
-                        user |= S_IWUSR as u32;
-                        group |= S_IWGRP as u32;
-                        others |= S_IWOTH as u32;
-                    }
-                    if action.execute {
-                        user |= S_IXUSR as u32;
-                        group |= S_IXGRP as u32;
-                        others |= S_IXOTH as u32;
-                    }
-                    if action.execute_dir {
-                        user |= S_IXUSR as u32;
-                        group |= S_IXGRP as u32;
-                        others |= S_IXOTH as u32;
-                    }
-                    if action.setuid {
-                        user |= S_ISUID as u32;
-                    }
-                    if action.sticky {
-                        others |= S_ISVTX as u32;
-                    }
-                }
+// mode bits affected by the wholist of a clause
+fn clause_affected(clause: &ChmodClause) -> u32 {
+    let mut affected = 0;
+    if clause.user {
+        affected |= S_ISUID | S_IRWXU;
+    }
+    if clause.group {
+        affected |= S_ISGID | S_IRWXG;
+    }
+    if clause.others {
+        affected |= S_ISVTX | S_IRWXO;
+    }
+    affected
+}
 
-                // remove bits from the mode
-                ChmodActionOp::Remove => {
-                    if action.copy_user {
-                        user &= !(cur_mode & S_IRWXU as u32);
-                    }
-                    if action.copy_group {
-                        group &= !(cur_mode & S_IRWXG as u32);
-                    }
-                    if action.copy_others {
-                        others &= !(cur_mode & S_IRWXO as u32);
-                    }
-                    if action.read {
-                        user &= !S_IRUSR as u32;
-                        group &= !S_IRGRP as u32;
-                        others &= !S_IROTH as u32;
-                    }
-                    if action.write {
-                        user &= !S_IWUSR as u32;
-                        group &= !S_IWGRP as u32;
-                        others &= !S_IWOTH as u32;
-                    }
-                    if action.execute {
-                        user &= !S_IXUSR as u32;
-                        group &= !S_IXGRP as u32;
-                        others &= !S_IXOTH as u32;
-                    }
-                    if action.execute_dir {
-                        user &= !S_IXUSR as u32;
-                        group &= !S_IXGRP as u32;
-                        others &= !S_IXOTH as u32;
-                    }
-                    if action.setuid {
-                        user &= !S_ISUID as u32;
-                    }
-                    if action.sticky {
-                        others &= !S_ISVTX as u32;
-                    }
-                }
+// mode bits named by the permlist or permcopy of an action
+fn action_value(action: &ChmodAction, cur_mode: u32, is_dir: bool) -> u32 {
+    let mut value = 0;
 
-                // set the mode bits
-                ChmodActionOp::Set => {
-                    if action.copy_user {
-                        user = cur_mode & S_IRWXU as u32;
-                    } else {
-                        user = 0;
-                    }
-                    if action.copy_group {
-                        group = cur_mode & S_IRWXG as u32;
-                    } else {
-                        group = 0;
-                    }
-                    if action.copy_others {
-                        others = cur_mode & S_IRWXO as u32;
-                    } else {
-                        others = 0;
-                    }
-                    if action.read {
-                        user |= S_IRUSR as u32;
-                        group |= S_IRGRP as u32;
-                        others |= S_IROTH as u32;
-                    }
-                    if action.write {
-                        user |= S_IWUSR as u32;
-                        group |= S_IWGRP as u32;
-                        others |= S_IWOTH as u32;
-                    }
-                    if action.execute {
-                        user |= S_IXUSR as u32;
-                        group |= S_IXGRP as u32;
-                        others |= S_IXOTH as u32;
-                    }
-                    if action.execute_dir {
-                        user |= S_IXUSR as u32;
-                        group |= S_IXGRP as u32;
-                        others |= S_IXOTH as u32;
-                    }
-                    if action.setuid {
-                        user |= S_ISUID as u32;
-                    }
-                    if action.sticky {
-                        others |= S_ISVTX as u32;
-                    }
-                }
-            }
-        }
+    // copy the permissions of one class into all three classes
+    if action.copy_user {
+        value |= ((cur_mode & S_IRWXU) >> 6) * 0o111;
+    }
+    if action.copy_group {
+        value |= ((cur_mode & S_IRWXG) >> 3) * 0o111;
+    }
+    if action.copy_others {
+        value |= (cur_mode & S_IRWXO) * 0o111;
+    }
 
-        // apply the clause
-        if clause.user {
-            new_mode = (new_mode & !S_IRWXU as u32) | user;
-        }
-        if clause.group {
-            new_mode = (new_mode & !S_IRWXG as u32) | group;
+    if action.read {
+        value |= S_IRUSR | S_IRGRP | S_IROTH;
+    }
+    if action.write {
+        value |= S_IWUSR | S_IWGRP | S_IWOTH;
+    }
+    if action.execute {
+        value |= S_IXUSR | S_IXGRP | S_IXOTH;
+    }
+    // X only applies to directories, or to files that already have
+    // at least one execute bit set
+    if action.execute_dir && (is_dir || (cur_mode & (S_IXUSR | S_IXGRP | S_IXOTH)) != 0) {
+        value |= S_IXUSR | S_IXGRP | S_IXOTH;
+    }
+    if action.setuid {
+        value |= S_ISUID | S_ISGID;
+    }
+    if action.sticky {
+        value |= S_ISVTX;
+    }
+
+    value
+}
+
+/// Apply symbolic mutations to the mode bits `init_mode` of a file.
+///
+/// `is_dir` controls the behavior of the `X` permission.  Clauses
+/// without a wholist act as if `a` was given, except that bits set in
+/// the process umask are left untouched.  File type bits in
+/// `init_mode` are preserved.
+pub fn mutate(init_mode: u32, is_dir: bool, symbolic: &ChmodSymbolic) -> u32 {
+    mutate_masked(init_mode, is_dir, symbolic, current_umask)
+}
+
+/// Apply symbolic mutations like [`mutate`], but as if the process umask
+/// were zero, so that clauses without a wholist act exactly as if `a`
+/// was given.
+pub fn mutate_unmasked(init_mode: u32, is_dir: bool, symbolic: &ChmodSymbolic) -> u32 {
+    mutate_masked(init_mode, is_dir, symbolic, || 0)
+}
+
+// the umask is only queried when a clause without a wholist needs it
+fn mutate_masked(
+    init_mode: u32,
+    is_dir: bool,
+    symbolic: &ChmodSymbolic,
+    get_umask: fn() -> u32,
+) -> u32 {
+    const MODE_BITS: u32 = 0o7777;
+
+    let mut mode = init_mode & MODE_BITS;
+    let mut umask = None;
+
+    for clause in &symbolic.clauses {
+        let mut affected = clause_affected(clause);
+        let mut mask = affected;
+        if affected == 0 {
+            affected = MODE_BITS;
+            mask = MODE_BITS & !*umask.get_or_insert_with(get_umask);
         }
-        if clause.others {
-            new_mode = (new_mode & !S_IRWXO as u32) | others;
+
+        for action in &clause.actions {
+            let value = action_value(action, mode, is_dir) & mask;
+
+            match action.op {
+                ChmodActionOp::Add => mode |= value,
+                ChmodActionOp::Remove => mode &= !value,
+                ChmodActionOp::Set => mode = (mode & !affected) | value,
+            }
         }
     }
 
-    new_mode
+    (init_mode & !MODE_BITS) | mode
 }
 
 #[cfg(test)]
@@ -425,4 +392,80 @@ mod tests {
             _ => panic!("unexpected mode"),
         }
     }
+
+    fn sym(mode: &str) -> ChmodSymbolic {
+        match parse(mode).unwrap() {
+            ChmodMode::Symbolic(s) => s,
+            _ => panic!("unexpected mode"),
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(parse("").is_err());
+        assert!(parse("u").is_err());
+        assert!(parse("u+z").is_err());
+        assert!(parse("u+gx").is_err());
+        assert!(parse("u+x,g").is_err());
+        assert!(parse("17777").is_err());
+        assert!(matches!(parse("0755"), Ok(ChmodMode::Absolute(0o755))));
+    }
+
+    #[test]
+    fn test_mutate_basic() {
+        assert_eq!(mutate(0o644, false, &sym("u+x")), 0o744);
+        assert_eq!(mutate(0o777, false, &sym("go-w")), 0o755);
+        assert_eq!(mutate(0o777, false, &sym("o=")), 0o770);
+        assert_eq!(mutate(0o600, false, &sym("a+r,u-w")), 0o444);
+        assert_eq!(mutate(0o600, false, &sym("u=r+x")), 0o500);
+    }
+
+    #[test]
+    fn test_mutate_copy() {
+        assert_eq!(mutate(0o750, false, &sym("o=g")), 0o755);
+        assert_eq!(mutate(0o640, false, &sym("g+u")), 0o660);
+        assert_eq!(mutate(0o764, false, &sym("u-o")), 0o364);
+        // copies see the result of earlier clauses
+        assert_eq!(mutate(0o700, false, &sym("g=u,o=g")), 0o777);
+    }
+
+    #[test]
+    fn test_mutate_conditional_execute() {
+        assert_eq!(mutate(0o644, false, &sym("a+X")), 0o644);
+        assert_eq!(mutate(0o744, false, &sym("a+X")), 0o755);
+        assert_eq!(mutate(0o644, true, &sym("a+X")), 0o755);
+    }
+
+    #[test]
+    fn test_mutate_special_bits() {
+        assert_eq!(mutate(0o755, false, &sym("u+s")), 0o4755);
+        assert_eq!(mutate(0o755, false, &sym("g+s")), 0o2755);
+        assert_eq!(mutate(0o755, false, &sym("ug+s")), 0o6755);
+        assert_eq!(mutate(0o755, false, &sym("o+s")), 0o755);
+        assert_eq!(mutate(0o755, true, &sym("o+t")), 0o1755);
+        assert_eq!(mutate(0o755, true, &sym("u+t")), 0o755);
+        assert_eq!(mutate(0o6755, false, &sym("u=rwx")), 0o2755);
+    }
+
+    #[test]
+    fn test_mutate_preserves_file_type() {
+        let regular = libc::S_IFREG;
+        assert_eq!(mutate(regular | 0o600, false, &sym("a+r")), regular | 0o644);
+    }
+
+    #[test]
+    fn test_mutate_umask_relative() {
+        let umask = current_umask();
+        assert_eq!(mutate(0, false, &sym("+rw")), 0o666 & !umask);
+        assert_eq!(mutate(0o777, false, &sym("-w")), 0o777 & !(0o222 & !umask));
+        assert_eq!(mutate(0, false, &sym("a+rw")), 0o666);
+    }
+
+    #[test]
+    fn test_mutate_unmasked() {
+        assert_eq!(mutate_unmasked(0, false, &sym("+rw")), 0o666);
+        assert_eq!(mutate_unmasked(0, false, &sym("u=rw,+x")), 0o711);
+        assert_eq!(mutate_unmasked(0o777, false, &sym("-w")), 0o555);
+        assert_eq!(mutate_unmasked(0o644, false, &sym("go-r")), 0o600);
+    }
 }
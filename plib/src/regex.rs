@@ -0,0 +1,201 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+//! Basic and extended regular expressions, as described in XBD 9.3 "Basic
+//! Regular Expressions" and XBD 9.4 "Extended Regular Expressions",
+//! compiled and matched by the C library.
+
+use std::ffi::CString;
+use std::mem::MaybeUninit;
+
+// subexpressions that can be referred to, \1 to \9
+const MAX_GROUPS: usize = 10;
+
+// The index of the `]` that ends the bracket expression starting at
+// `start`, or the end of the pattern if it is not terminated. A `]` right
+// after `[` or `[^` is a literal, and so is one in a class name like
+// `[:alpha:]`, a collating symbol or an equivalence class.
+fn bracket_end(pattern: &[u8], start: usize) -> usize {
+    let mut i = start + 1;
+    if pattern.get(i) == Some(&b'^') {
+        i += 1;
+    }
+    if pattern.get(i) == Some(&b']') {
+        i += 1;
+    }
+    while i < pattern.len() {
+        match pattern[i] {
+            b']' => return i,
+            b'[' if matches!(pattern.get(i + 1), Some(b':' | b'.' | b'=')) => {
+                let delimiter = pattern[i + 1];
+                let name_len = pattern[i + 2..]
+                    .windows(2)
+                    .position(|w| w == [delimiter, b']']);
+                // continue after the ] that ends the name
+                i = name_len.map_or(pattern.len(), |len| i + 2 + len + 1);
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    pattern.len()
+}
+
+/// A regular expression, compiled by regcomp().
+pub struct Regex {
+    re: Box<libc::regex_t>,
+
+    // number of subexpressions
+    groups: usize,
+}
+
+impl Regex {
+    /// Compile `pattern`, returning the message of regerror() when it is
+    /// not a valid basic regular expression.
+    pub fn new(pattern: &[u8]) -> Result<Regex, String> {
+        let mut re = Regex::compile(pattern, 0)?;
+
+        // the field of regex_t that has the number is not accessible
+        let mut iter = pattern.iter();
+        while let Some(&b) = iter.next() {
+            if b == b'\\' && iter.next() == Some(&b'(') {
+                re.groups += 1;
+            }
+        }
+        Ok(re)
+    }
+
+    /// Compile `pattern` as an extended regular expression.
+    pub fn extended(pattern: &[u8]) -> Result<Regex, String> {
+        let mut re = Regex::compile(pattern, libc::REG_EXTENDED)?;
+        let mut i = 0;
+        while i < pattern.len() {
+            match pattern[i] {
+                b'\\' => i += 1,
+                b'[' => i = bracket_end(pattern, i),
+                b'(' => re.groups += 1,
+                _ => {}
+            }
+            i += 1;
+        }
+        Ok(re)
+    }
+
+    fn compile(pattern: &[u8], cflags: libc::c_int) -> Result<Regex, String> {
+        let pattern = CString::new(pattern).map_err(|e| e.to_string())?;
+        let mut re = Box::new(MaybeUninit::<libc::regex_t>::uninit());
+        let res = unsafe { libc::regcomp(re.as_mut_ptr(), pattern.as_ptr(), cflags) };
+        if res != 0 {
+            let mut buf = [0u8; 256];
+            let len =
+                unsafe { libc::regerror(res, re.as_ptr(), buf.as_mut_ptr().cast(), buf.len()) };
+            let len = len.clamp(1, buf.len()) - 1;
+            return Err(String::from_utf8_lossy(&buf[..len]).into_owned());
+        }
+        let re = unsafe { Box::from_raw(Box::into_raw(re) as *mut libc::regex_t) };
+        Ok(Regex { re, groups: 0 })
+    }
+
+    /// The number of subexpressions of the pattern.
+    pub fn groups(&self) -> usize {
+        self.groups
+    }
+
+    /// The offsets of the leftmost match in `s`, followed by those of
+    /// each subexpression; `None` for subexpressions that did not
+    /// participate in the match. When `notbol` is set, the start of `s`
+    /// is not the beginning of a line (`REG_NOTBOL`).
+    pub fn exec(&self, s: &[u8], notbol: bool) -> Option<Vec<Option<(usize, usize)>>> {
+        let s = CString::new(s).ok()?;
+        let mut matches = [libc::regmatch_t {
+            rm_so: -1,
+            rm_eo: -1,
+        }; MAX_GROUPS];
+        let flags = if notbol { libc::REG_NOTBOL } else { 0 };
+        let res = unsafe {
+            libc::regexec(
+                &*self.re,
+                s.as_ptr(),
+                MAX_GROUPS,
+                matches.as_mut_ptr(),
+                flags,
+            )
+        };
+        if res != 0 {
+            return None;
+        }
+
+        let groups = matches
+            .iter()
+            .take(self.groups + 1)
+            .map(|m| (m.rm_so >= 0).then_some((m.rm_so as usize, m.rm_eo as usize)))
+            .collect();
+        Some(groups)
+    }
+}
+
+impl Drop for Regex {
+    fn drop(&mut self) {
+        unsafe { libc::regfree(&mut *self.re) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match() {
+        let re = Regex::new(b"a\\(b*\\)c").unwrap();
+        assert_eq!(re.groups(), 1);
+        assert_eq!(
+            re.exec(b"xabbc", false),
+            Some(vec![Some((1, 5)), Some((2, 4))])
+        );
+        assert_eq!(
+            re.exec(b"xac", false),
+            Some(vec![Some((1, 3)), Some((2, 2))])
+        );
+        assert_eq!(re.exec(b"xyz", false), None);
+
+        // + and ? are ordinary characters in a basic regular expression
+        let re = Regex::new(b"a+").unwrap();
+        assert_eq!(re.exec(b"aa", false), None);
+        assert_eq!(re.exec(b"a+", false), Some(vec![Some((0, 2))]));
+
+        let re = Regex::new(b"^a").unwrap();
+        assert_eq!(re.exec(b"ab", true), None);
+    }
+
+    #[test]
+    fn test_extended() {
+        let re = Regex::extended(b"a(b+|c)[(]").unwrap();
+        assert_eq!(re.groups(), 1);
+        assert_eq!(
+            re.exec(b"xabb(", false),
+            Some(vec![Some((1, 5)), Some((2, 4))])
+        );
+        assert_eq!(re.exec(b"xa(", false), None);
+        assert!(Regex::extended(b"a(b").is_err());
+
+        // a leading ] is part of the bracket expression, as is the ] of a
+        // class name
+        let re = Regex::extended(b"[]()]x").unwrap();
+        assert_eq!(re.groups(), 0);
+        assert_eq!(re.exec(b"(x", false), Some(vec![Some((0, 2))]));
+        assert_eq!(Regex::extended(b"[^]()](y)").unwrap().groups(), 1);
+        assert_eq!(Regex::extended(b"[[:alpha:](]").unwrap().groups(), 0);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(Regex::new(b"a\\(b").is_err());
+        assert!(Regex::new(b"[a").is_err());
+    }
+}
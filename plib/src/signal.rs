@@ -0,0 +1,130 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+//! Signal names, as used by kill and by the shell's trap and kill
+//! builtins.  Names are written without the SIG prefix.
+
+/// The signals of the running platform, in numeric order.
+pub const SIGNALS: &[(&str, i32)] = &[
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("ILL", libc::SIGILL),
+    ("TRAP", libc::SIGTRAP),
+    ("ABRT", libc::SIGABRT),
+    #[cfg(target_os = "macos")]
+    ("EMT", libc::SIGEMT),
+    #[cfg(target_os = "linux")]
+    ("BUS", libc::SIGBUS),
+    ("FPE", libc::SIGFPE),
+    ("KILL", libc::SIGKILL),
+    #[cfg(target_os = "linux")]
+    ("USR1", libc::SIGUSR1),
+    #[cfg(target_os = "macos")]
+    ("BUS", libc::SIGBUS),
+    ("SEGV", libc::SIGSEGV),
+    #[cfg(target_os = "linux")]
+    ("USR2", libc::SIGUSR2),
+    #[cfg(target_os = "macos")]
+    ("SYS", libc::SIGSYS),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    #[cfg(target_os = "linux")]
+    ("STKFLT", libc::SIGSTKFLT),
+    #[cfg(target_os = "linux")]
+    ("CHLD", libc::SIGCHLD),
+    #[cfg(target_os = "linux")]
+    ("CONT", libc::SIGCONT),
+    #[cfg(target_os = "macos")]
+    ("URG", libc::SIGURG),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    #[cfg(target_os = "macos")]
+    ("CONT", libc::SIGCONT),
+    #[cfg(target_os = "macos")]
+    ("CHLD", libc::SIGCHLD),
+    ("TTIN", libc::SIGTTIN),
+    ("TTOU", libc::SIGTTOU),
+    #[cfg(target_os = "linux")]
+    ("URG", libc::SIGURG),
+    #[cfg(target_os = "macos")]
+    ("IO", libc::SIGIO),
+    ("XCPU", libc::SIGXCPU),
+    ("XFSZ", libc::SIGXFSZ),
+    ("VTALRM", libc::SIGVTALRM),
+    ("PROF", libc::SIGPROF),
+    ("WINCH", libc::SIGWINCH),
+    #[cfg(target_os = "linux")]
+    ("IO", libc::SIGIO),
+    #[cfg(target_os = "linux")]
+    ("PWR", libc::SIGPWR),
+    #[cfg(target_os = "linux")]
+    ("SYS", libc::SIGSYS),
+    #[cfg(target_os = "macos")]
+    ("INFO", libc::SIGINFO),
+    #[cfg(target_os = "macos")]
+    ("USR1", libc::SIGUSR1),
+    #[cfg(target_os = "macos")]
+    ("USR2", libc::SIGUSR2),
+];
+
+// other names accepted for some signals
+const ALIASES: &[(&str, i32)] = &[
+    ("IOT", libc::SIGABRT),
+    ("CLD", libc::SIGCHLD),
+    ("POLL", libc::SIGIO),
+];
+
+/// The number of the signal `name`, which is matched without regard to
+/// case and may carry the SIG prefix.  "0" names the null signal.
+pub fn signal_number(name: &str) -> Option<i32> {
+    if name == "0" {
+        return Some(0);
+    }
+
+    let name = name.to_ascii_uppercase();
+    let name = name.strip_prefix("SIG").unwrap_or(&name);
+    SIGNALS
+        .iter()
+        .chain(ALIASES)
+        .find(|(signame, _)| *signame == name)
+        .map(|(_, signo)| *signo)
+}
+
+/// The name of signal number `signo`.
+pub fn signal_name(signo: i32) -> Option<&'static str> {
+    SIGNALS
+        .iter()
+        .find(|(_, n)| *n == signo)
+        .map(|(name, _)| *name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signal_names() {
+        assert_eq!(signal_number("TERM"), Some(libc::SIGTERM));
+        assert_eq!(signal_number("sigkill"), Some(libc::SIGKILL));
+        assert_eq!(signal_number("Hup"), Some(libc::SIGHUP));
+        assert_eq!(signal_number("IOT"), Some(libc::SIGABRT));
+        assert_eq!(signal_number("0"), Some(0));
+        assert_eq!(signal_number("BOGUS"), None);
+        assert_eq!(signal_number("SIG"), None);
+
+        assert_eq!(signal_name(libc::SIGINT), Some("INT"));
+        assert_eq!(signal_name(libc::SIGABRT), Some("ABRT"));
+        assert_eq!(signal_name(0), None);
+
+        // the table is in numeric order, without duplicates
+        assert!(SIGNALS.windows(2).all(|w| w[0].1 < w[1].1));
+    }
+}
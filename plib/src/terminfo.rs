@@ -0,0 +1,813 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+//! Terminal capabilities: a reader for compiled terminfo entries, and the
+//! expansion of parameterized capability strings.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, Error, ErrorKind, Write};
+use std::path::PathBuf;
+
+// the magic numbers of the legacy format, and of the format with 32-bit
+// numbers
+const MAGIC_LEGACY: u16 = 0o432;
+const MAGIC_32BIT: u16 = 0o1036;
+
+/// The standard boolean capabilities, in the order of compiled entries.
+pub const BOOLEAN_NAMES: &[&str] = &[
+    "bw", "am", "xsb", "xhp", "xenl", "eo", "gn", "hc", "km", "hs", "in", "da", "db", "mir",
+    "msgr", "os", "eslok", "xt", "hz", "ul", "xon", "nxon", "mc5i", "chts", "nrrmc", "npc",
+    "ndscr", "ccc", "bce", "hls", "xhpa", "crxm", "daisy", "xvpa", "sam", "cpix", "lpix", "OTbs",
+    "OTns", "OTnc", "OTMT", "OTNL", "OTpt", "OTxr",
+];
+
+/// The standard numeric capabilities, in the order of compiled entries.
+pub const NUMBER_NAMES: &[&str] = &[
+    "cols", "it", "lines", "lm", "xmc", "pb", "vt", "wsl", "nlab", "lh", "lw", "ma", "wnum",
+    "colors", "pairs", "ncv", "bufsz", "spinv", "spinh", "maddr", "mjump", "mcs", "mls", "npins",
+    "orc", "orl", "orhi", "orvi", "cps", "widcs", "btns", "bitwin", "bitype", "OTug", "OTdC",
+    "OTdN", "OTdB", "OTdT", "OTkn",
+];
+
+/// The standard string capabilities, in the order of compiled entries.
+pub const STRING_NAMES: &[&str] = &[
+    "cbt", "bel", "cr", "csr", "tbc", "clear", "el", "ed", "hpa", "cmdch", "cup", "cud1", "home",
+    "civis", "cub1", "mrcup", "cnorm", "cuf1", "ll", "cuu1", "cvvis", "dch1", "dl1", "dsl", "hd",
+    "smacs", "blink", "bold", "smcup", "smdc", "dim", "smir", "invis", "prot", "rev", "smso",
+    "smul", "ech", "rmacs", "sgr0", "rmcup", "rmdc", "rmir", "rmso", "rmul", "flash", "ff", "fsl",
+    "is1", "is2", "is3", "if", "ich1", "il1", "ip", "kbs", "ktbc", "kclr", "kctab", "kdch1",
+    "kdl1", "kcud1", "krmir", "kel", "ked", "kf0", "kf1", "kf10", "kf2", "kf3", "kf4", "kf5",
+    "kf6", "kf7", "kf8", "kf9", "khome", "kich1", "kil1", "kcub1", "kll", "knp", "kpp", "kcuf1",
+    "kind", "kri", "khts", "kcuu1", "rmkx", "smkx", "lf0", "lf1", "lf10", "lf2", "lf3", "lf4",
+    "lf5", "lf6", "lf7", "lf8", "lf9", "rmm", "smm", "nel", "pad", "dch", "dl", "cud", "ich",
+    "indn", "il", "cub", "cuf", "rin", "cuu", "pfkey", "pfloc", "pfx", "mc0", "mc4", "mc5", "rep",
+    "rs1", "rs2", "rs3", "rf", "rc", "vpa", "sc", "ind", "ri", "sgr", "hts", "wind", "ht", "tsl",
+    "uc", "hu", "iprog", "ka1", "ka3", "kb2", "kc1", "kc3", "mc5p", "rmp", "acsc", "pln", "kcbt",
+    "smxon", "rmxon", "smam", "rmam", "xonc", "xoffc", "enacs", "smln", "rmln", "kbeg", "kcan",
+    "kclo", "kcmd", "kcpy", "kcrt", "kend", "kent", "kext", "kfnd", "khlp", "kmrk", "kmsg", "kmov",
+    "knxt", "kopn", "kopt", "kprv", "kprt", "krdo", "kref", "krfr", "krpl", "krst", "kres", "ksav",
+    "kspd", "kund", "kBEG", "kCAN", "kCMD", "kCPY", "kCRT", "kDC", "kDL", "kslt", "kEND", "kEOL",
+    "kEXT", "kFND", "kHLP", "kHOM", "kIC", "kLFT", "kMSG", "kMOV", "kNXT", "kOPT", "kPRV", "kPRT",
+    "kRDO", "kRPL", "kRIT", "kRES", "kSAV", "kSPD", "kUND", "rfi", "kf11", "kf12", "kf13", "kf14",
+    "kf15", "kf16", "kf17", "kf18", "kf19", "kf20", "kf21", "kf22", "kf23", "kf24", "kf25", "kf26",
+    "kf27", "kf28", "kf29", "kf30", "kf31", "kf32", "kf33", "kf34", "kf35", "kf36", "kf37", "kf38",
+    "kf39", "kf40", "kf41", "kf42", "kf43", "kf44", "kf45", "kf46", "kf47", "kf48", "kf49", "kf50",
+    "kf51", "kf52", "kf53", "kf54", "kf55", "kf56", "kf57", "kf58", "kf59", "kf60", "kf61", "kf62",
+    "kf63", "el1", "mgc", "smgl", "smgr", "fln", "sclk", "dclk", "rmclk", "cwin", "wingo", "hup",
+    "dial", "qdial", "tone", "pulse", "hook", "pause", "wait", "u0", "u1", "u2", "u3", "u4", "u5",
+    "u6", "u7", "u8", "u9", "op", "oc", "initc", "initp", "scp", "setf", "setb", "cpi", "lpi",
+    "chr", "cvr", "defc", "swidm", "sdrfq", "sitm", "slm", "smicm", "snlq", "snrmq", "sshm",
+    "ssubm", "ssupm", "sum", "rwidm", "ritm", "rlm", "rmicm", "rshm", "rsubm", "rsupm", "rum",
+    "mhpa", "mcud1", "mcub1", "mcuf1", "mvpa", "mcuu1", "porder", "mcud", "mcub", "mcuf", "mcuu",
+    "scs", "smgb", "smgbp", "smglp", "smgrp", "smgt", "smgtp", "sbim", "scsd", "rbim", "rcsd",
+    "subcs", "supcs", "docr", "zerom", "csnm", "kmous", "minfo", "reqmp", "getm", "setaf", "setab",
+    "pfxl", "devt", "csin", "s0ds", "s1ds", "s2ds", "s3ds", "smglr", "smgtb", "birep", "binel",
+    "bicr", "colornm", "defbi", "endbi", "setcolor", "slines", "dispc", "smpch", "rmpch", "smsc",
+    "rmsc", "pctrm", "scesc", "scesa", "ehhlm", "elhlm", "elohlm", "erhlm", "ethlm", "evhlm",
+    "sgr1", "slength", "OTi2", "OTrs", "OTnl", "OTbc", "OTko", "OTma", "OTG2", "OTG3", "OTG1",
+    "OTG4", "OTGR", "OTGL", "OTGU", "OTGD", "OTGH", "OTGV", "OTGC", "meml", "memu", "box1",
+];
+
+/// The type of a capability.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CapKind {
+    Boolean,
+    Number,
+    String,
+}
+
+/// The capabilities of one terminal type, keyed by capname.
+#[derive(Debug, Default)]
+pub struct Terminfo {
+    /// The names of the terminal type, the last being its description.
+    pub names: Vec<String>,
+    booleans: HashMap<String, bool>,
+    numbers: HashMap<String, i32>,
+    strings: HashMap<String, Vec<u8>>,
+}
+
+// the directories of the terminfo database, in search order
+fn search_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(dir) = env::var_os("TERMINFO") {
+        dirs.push(PathBuf::from(dir));
+    }
+    if let Some(home) = env::var_os("HOME") {
+        dirs.push(PathBuf::from(home).join(".terminfo"));
+    }
+    let defaults = [
+        "/etc/terminfo",
+        "/lib/terminfo",
+        "/usr/share/terminfo",
+        "/usr/lib/terminfo",
+    ];
+    match env::var("TERMINFO_DIRS") {
+        Ok(list) => {
+            // an empty entry stands for the default locations
+            for dir in list.split(':') {
+                if dir.is_empty() {
+                    dirs.extend(defaults.iter().map(PathBuf::from));
+                } else {
+                    dirs.push(PathBuf::from(dir));
+                }
+            }
+        }
+        Err(_) => dirs.extend(defaults.iter().map(PathBuf::from)),
+    }
+    dirs
+}
+
+// a little-endian 16-bit value, as read from compiled entries
+fn read_i16(data: &[u8], pos: usize) -> io::Result<i16> {
+    data.get(pos..pos + 2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(truncated)
+}
+
+fn read_i32(data: &[u8], pos: usize) -> io::Result<i32> {
+    data.get(pos..pos + 4)
+        .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(truncated)
+}
+
+fn read_count(data: &[u8], pos: usize) -> io::Result<usize> {
+    let value = read_i16(data, pos)?;
+    usize::try_from(value).map_err(|_| invalid())
+}
+
+fn truncated() -> Error {
+    Error::new(ErrorKind::InvalidData, "truncated terminfo entry")
+}
+
+fn invalid() -> Error {
+    Error::new(ErrorKind::InvalidData, "invalid terminfo entry")
+}
+
+// the NUL-terminated string at `offset` of a string table
+fn table_string(table: &[u8], offset: usize) -> io::Result<&[u8]> {
+    let rest = table.get(offset..).ok_or_else(invalid)?;
+    let len = rest.iter().position(|b| *b == 0).ok_or_else(invalid)?;
+    Ok(&rest[..len])
+}
+
+// Reads the counted sections of a compiled entry, keeping track of the
+// position and of the alignment of each section to an even offset.
+struct Sections<'a> {
+    data: &'a [u8],
+    pos: usize,
+    number_size: usize,
+}
+
+impl<'a> Sections<'a> {
+    fn bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(truncated)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn align(&mut self) {
+        self.pos += self.pos % 2;
+    }
+
+    fn booleans(&mut self, count: usize) -> io::Result<Vec<bool>> {
+        let values = self.bytes(count)?.iter().map(|b| *b == 1).collect();
+        self.align();
+        Ok(values)
+    }
+
+    fn numbers(&mut self, count: usize) -> io::Result<Vec<i32>> {
+        let mut values = Vec::with_capacity(count);
+        for _ in 0..count {
+            let value = if self.number_size == 4 {
+                read_i32(self.data, self.pos)?
+            } else {
+                read_i16(self.data, self.pos)? as i32
+            };
+            self.pos += self.number_size;
+            values.push(value);
+        }
+        Ok(values)
+    }
+
+    // string table offsets; negative offsets are absent or cancelled
+    // capabilities
+    fn offsets(&mut self, count: usize) -> io::Result<Vec<Option<usize>>> {
+        let mut values = Vec::with_capacity(count);
+        for _ in 0..count {
+            let offset = read_i16(self.data, self.pos)?;
+            self.pos += 2;
+            values.push(usize::try_from(offset).ok());
+        }
+        Ok(values)
+    }
+}
+
+impl Terminfo {
+    /// Load the entry for the terminal type named by the TERM environment
+    /// variable.
+    pub fn from_env() -> io::Result<Terminfo> {
+        match env::var("TERM") {
+            Ok(name) if !name.is_empty() => Terminfo::from_name(&name),
+            _ => Err(Error::new(
+                ErrorKind::NotFound,
+                "TERM environment variable not set",
+            )),
+        }
+    }
+
+    /// Load the entry for the terminal type `name` from the terminfo
+    /// database.
+    pub fn from_name(name: &str) -> io::Result<Terminfo> {
+        let first = match name.chars().next() {
+            Some(c) if !name.contains('/') => c,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("invalid terminal type \"{}\"", name),
+                ))
+            }
+        };
+
+        // entries are filed under their first letter, or its hexadecimal
+        // code on case-insensitive filesystems
+        for dir in search_dirs() {
+            for subdir in [first.to_string(), format!("{:02x}", first as u32)] {
+                let path = dir.join(subdir).join(name);
+                if let Ok(data) = fs::read(&path) {
+                    return Terminfo::parse(&data);
+                }
+            }
+        }
+
+        Err(Error::new(
+            ErrorKind::NotFound,
+            format!("unknown terminal type \"{}\"", name),
+        ))
+    }
+
+    /// Parse a compiled terminfo entry.
+    pub fn parse(data: &[u8]) -> io::Result<Terminfo> {
+        let number_size = match read_i16(data, 0)? as u16 {
+            MAGIC_LEGACY => 2,
+            MAGIC_32BIT => 4,
+            _ => return Err(Error::new(ErrorKind::InvalidData, "not a terminfo entry")),
+        };
+        let names_size = read_count(data, 2)?;
+        let n_booleans = read_count(data, 4)?;
+        let n_numbers = read_count(data, 6)?;
+        let n_strings = read_count(data, 8)?;
+        let table_size = read_count(data, 10)?;
+
+        let mut sections = Sections {
+            data,
+            pos: 12,
+            number_size,
+        };
+        let mut info = Terminfo::default();
+
+        let names = sections.bytes(names_size)?;
+        let names = names.split(|b| *b == 0).next().unwrap_or_default();
+        info.names = String::from_utf8_lossy(names)
+            .split('|')
+            .map(String::from)
+            .collect();
+
+        let booleans = sections.booleans(n_booleans)?;
+        let numbers = sections.numbers(n_numbers)?;
+        let offsets = sections.offsets(n_strings)?;
+        let table = sections.bytes(table_size)?;
+        sections.align();
+
+        for (name, value) in BOOLEAN_NAMES.iter().zip(booleans) {
+            if value {
+                info.booleans.insert(name.to_string(), true);
+            }
+        }
+        for (name, value) in NUMBER_NAMES.iter().zip(numbers) {
+            if value >= 0 {
+                info.numbers.insert(name.to_string(), value);
+            }
+        }
+        for (name, offset) in STRING_NAMES.iter().zip(offsets) {
+            if let Some(offset) = offset {
+                let value = table_string(table, offset)?;
+                info.strings.insert(name.to_string(), value.to_vec());
+            }
+        }
+
+        // user-defined capabilities follow in an extended section
+        if sections.pos < data.len() {
+            info.parse_extended(&mut sections)?;
+        }
+
+        Ok(info)
+    }
+
+    fn parse_extended(&mut self, sections: &mut Sections) -> io::Result<()> {
+        let header = sections.bytes(10)?;
+        let n_booleans = read_count(header, 0)?;
+        let n_numbers = read_count(header, 2)?;
+        let n_strings = read_count(header, 4)?;
+        let table_size = read_count(header, 8)?;
+
+        let booleans = sections.booleans(n_booleans)?;
+        let numbers = sections.numbers(n_numbers)?;
+        let offsets = sections.offsets(n_strings)?;
+        let name_offsets = sections.offsets(n_booleans + n_numbers + n_strings)?;
+        let table = sections.bytes(table_size)?;
+
+        // the names follow the string values in the table
+        let mut names_start = 0;
+        let mut values = Vec::with_capacity(n_strings);
+        for offset in offsets {
+            let value = match offset {
+                Some(offset) => {
+                    let value = table_string(table, offset)?;
+                    names_start = names_start.max(offset + value.len() + 1);
+                    Some(value)
+                }
+                None => None,
+            };
+            values.push(value);
+        }
+        let names_table = table.get(names_start..).ok_or_else(invalid)?;
+        let mut names = Vec::with_capacity(name_offsets.len());
+        for offset in name_offsets {
+            let offset = offset.ok_or_else(invalid)?;
+            let name = table_string(names_table, offset)?;
+            names.push(String::from_utf8_lossy(name).into_owned());
+        }
+        let mut names = names.into_iter();
+
+        for (name, value) in names.by_ref().zip(booleans) {
+            if value {
+                self.booleans.insert(name, true);
+            }
+        }
+        for (name, value) in names.by_ref().zip(numbers) {
+            if value >= 0 {
+                self.numbers.insert(name, value);
+            }
+        }
+        for (name, value) in names.zip(values) {
+            if let Some(value) = value {
+                self.strings.insert(name, value.to_vec());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The type of the capability `capname`, if it is a standard
+    /// capability or one defined by this entry.
+    pub fn kind(&self, capname: &str) -> Option<CapKind> {
+        if BOOLEAN_NAMES.contains(&capname) || self.booleans.contains_key(capname) {
+            Some(CapKind::Boolean)
+        } else if NUMBER_NAMES.contains(&capname) || self.numbers.contains_key(capname) {
+            Some(CapKind::Number)
+        } else if STRING_NAMES.contains(&capname) || self.strings.contains_key(capname) {
+            Some(CapKind::String)
+        } else {
+            None
+        }
+    }
+
+    pub fn flag(&self, capname: &str) -> bool {
+        self.booleans.contains_key(capname)
+    }
+
+    pub fn number(&self, capname: &str) -> Option<i32> {
+        self.numbers.get(capname).copied()
+    }
+
+    pub fn string(&self, capname: &str) -> Option<&[u8]> {
+        self.strings.get(capname).map(|s| s.as_slice())
+    }
+}
+
+/// A parameter of a capability string.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Param {
+    Number(i32),
+    String(Vec<u8>),
+}
+
+impl Param {
+    fn number(&self) -> i32 {
+        match self {
+            Param::Number(n) => *n,
+            Param::String(_) => 0,
+        }
+    }
+}
+
+// the printf-style conversion of a %d, %o, %x, %X, %c or %s
+#[derive(Default)]
+struct Conversion {
+    left: bool,
+    sign: bool,
+    space: bool,
+    alternate: bool,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl Conversion {
+    fn format(&self, conv: u8, param: &Param) -> Vec<u8> {
+        let (mut prefix, mut body) = match (conv, param) {
+            (b's', Param::String(s)) => {
+                let len = self.precision.map_or(s.len(), |p| p.min(s.len()));
+                (Vec::new(), s[..len].to_vec())
+            }
+            (b's', Param::Number(n)) => (Vec::new(), n.to_string().into_bytes()),
+            (b'c', param) => (Vec::new(), vec![param.number() as u8]),
+            (_, param) => self.format_number(conv, param.number()),
+        };
+
+        let len = prefix.len() + body.len();
+        if len < self.width {
+            let pad = self.width - len;
+            if self.left {
+                body.extend(std::iter::repeat_n(b' ', pad));
+            } else if self.zero && !b"cs".contains(&conv) {
+                // zeros go between the sign or prefix and the digits
+                body.splice(0..0, std::iter::repeat_n(b'0', pad));
+            } else {
+                prefix.splice(0..0, std::iter::repeat_n(b' ', pad));
+            }
+        }
+        prefix.extend(body);
+        prefix
+    }
+
+    // the sign or radix prefix, and the digits, of a number
+    fn format_number(&self, conv: u8, n: i32) -> (Vec<u8>, Vec<u8>) {
+        let magnitude = n.unsigned_abs();
+        let mut digits = match conv {
+            b'o' => format!("{:o}", magnitude),
+            b'x' => format!("{:x}", magnitude),
+            b'X' => format!("{:X}", magnitude),
+            _ => magnitude.to_string(),
+        };
+        if let Some(precision) = self.precision {
+            if digits.len() < precision {
+                digits.insert_str(0, &"0".repeat(precision - digits.len()));
+            }
+        }
+
+        let mut prefix = String::new();
+        if n < 0 {
+            prefix.push('-');
+        } else if self.sign && conv == b'd' {
+            prefix.push('+');
+        } else if self.space && conv == b'd' {
+            prefix.push(' ');
+        }
+        if self.alternate && magnitude != 0 {
+            match conv {
+                b'o' if !digits.starts_with('0') => prefix.push('0'),
+                b'x' => prefix.push_str("0x"),
+                b'X' => prefix.push_str("0X"),
+                _ => {}
+            }
+        }
+        (prefix.into_bytes(), digits.into_bytes())
+    }
+}
+
+// Skip the rest of a then-part starting at `pos`, past the %e or %; that
+// ends it (`at_else`), or the rest of an else-part, past its %;.
+fn skip_conditional(cap: &[u8], mut pos: usize, at_else: bool) -> usize {
+    let mut depth = 0;
+    while pos < cap.len() {
+        if cap[pos] != b'%' || pos + 1 >= cap.len() {
+            pos += 1;
+            continue;
+        }
+        let op = cap[pos + 1];
+        pos += 2;
+        match op {
+            b'?' => depth += 1,
+            b';' if depth == 0 => return pos,
+            b';' => depth -= 1,
+            b'e' if depth == 0 && at_else => return pos,
+            _ => {}
+        }
+    }
+    pos
+}
+
+fn expand_error(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidInput, msg.to_string())
+}
+
+/// Expand the parameterized capability string `cap` with `params`.
+pub fn tparm(cap: &[u8], params: &[Param]) -> io::Result<Vec<u8>> {
+    let mut params: Vec<Param> = params.to_vec();
+    params.resize(9, Param::Number(0));
+    let mut stack: Vec<Param> = Vec::new();
+    let mut dynamic_vars = vec![Param::Number(0); 26];
+    let mut static_vars = vec![Param::Number(0); 26];
+    let mut out = Vec::new();
+
+    let pop = |stack: &mut Vec<Param>| stack.pop().unwrap_or(Param::Number(0));
+    let pop_number = |stack: &mut Vec<Param>| stack.pop().map_or(0, |p| p.number());
+
+    let mut pos = 0;
+    while pos < cap.len() {
+        let c = cap[pos];
+        pos += 1;
+        if c != b'%' {
+            out.push(c);
+            continue;
+        }
+        let op = *cap
+            .get(pos)
+            .ok_or_else(|| expand_error("incomplete % sequence"))?;
+        pos += 1;
+
+        match op {
+            b'%' => out.push(b'%'),
+            b'c' | b's' | b'd' | b'o' | b'x' | b'X' => {
+                let param = pop(&mut stack);
+                out.extend(Conversion::default().format(op, &param));
+            }
+            b':' | b'#' | b' ' | b'.' | b'0'..=b'9' => {
+                // a printf-style conversion with flags, width and precision
+                let mut conv = Conversion::default();
+                pos -= 1;
+                if cap[pos] == b':' {
+                    pos += 1;
+                }
+                while let Some(flag) = cap.get(pos) {
+                    match flag {
+                        b'-' => conv.left = true,
+                        b'+' => conv.sign = true,
+                        b' ' => conv.space = true,
+                        b'#' => conv.alternate = true,
+                        b'0' => conv.zero = true,
+                        _ => break,
+                    }
+                    pos += 1;
+                }
+                while let Some(digit @ b'0'..=b'9') = cap.get(pos) {
+                    conv.width = conv.width * 10 + (digit - b'0') as usize;
+                    pos += 1;
+                }
+                if cap.get(pos) == Some(&b'.') {
+                    pos += 1;
+                    let mut precision = 0;
+                    while let Some(digit @ b'0'..=b'9') = cap.get(pos) {
+                        precision = precision * 10 + (digit - b'0') as usize;
+                        pos += 1;
+                    }
+                    conv.precision = Some(precision);
+                }
+                match cap.get(pos) {
+                    Some(op @ (b'c' | b's' | b'd' | b'o' | b'x' | b'X')) => {
+                        let param = pop(&mut stack);
+                        out.extend(conv.format(*op, &param));
+                        pos += 1;
+                    }
+                    _ => return Err(expand_error("invalid % conversion")),
+                }
+            }
+            b'p' => {
+                let n = match cap.get(pos) {
+                    Some(digit @ b'1'..=b'9') => (digit - b'1') as usize,
+                    _ => return Err(expand_error("invalid parameter number")),
+                };
+                stack.push(params[n].clone());
+                pos += 1;
+            }
+            b'P' | b'g' => {
+                let (vars, index) = match cap.get(pos) {
+                    Some(name @ b'a'..=b'z') => (&mut dynamic_vars, (name - b'a') as usize),
+                    Some(name @ b'A'..=b'Z') => (&mut static_vars, (name - b'A') as usize),
+                    _ => return Err(expand_error("invalid variable name")),
+                };
+                if op == b'P' {
+                    vars[index] = pop(&mut stack);
+                } else {
+                    stack.push(vars[index].clone());
+                }
+                pos += 1;
+            }
+            b'\'' => {
+                let c = *cap
+                    .get(pos)
+                    .ok_or_else(|| expand_error("invalid character constant"))?;
+                if cap.get(pos + 1) != Some(&b'\'') {
+                    return Err(expand_error("invalid character constant"));
+                }
+                stack.push(Param::Number(c as i32));
+                pos += 2;
+            }
+            b'{' => {
+                let end = cap[pos..]
+                    .iter()
+                    .position(|b| *b == b'}')
+                    .ok_or_else(|| expand_error("invalid integer constant"))?;
+                let n = std::str::from_utf8(&cap[pos..pos + end])
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .ok_or_else(|| expand_error("invalid integer constant"))?;
+                stack.push(Param::Number(n));
+                pos += end + 1;
+            }
+            b'l' => {
+                let len = match pop(&mut stack) {
+                    Param::String(s) => s.len() as i32,
+                    Param::Number(_) => 0,
+                };
+                stack.push(Param::Number(len));
+            }
+            b'+' | b'-' | b'*' | b'/' | b'm' | b'&' | b'|' | b'^' | b'=' | b'>' | b'<' | b'A'
+            | b'O' => {
+                let b = pop_number(&mut stack);
+                let a = pop_number(&mut stack);
+                let value = match op {
+                    b'+' => a.wrapping_add(b),
+                    b'-' => a.wrapping_sub(b),
+                    b'*' => a.wrapping_mul(b),
+                    b'/' => a.checked_div(b).unwrap_or(0),
+                    b'm' => a.checked_rem(b).unwrap_or(0),
+                    b'&' => a & b,
+                    b'|' => a | b,
+                    b'^' => a ^ b,
+                    b'=' => (a == b) as i32,
+                    b'>' => (a > b) as i32,
+                    b'<' => (a < b) as i32,
+                    b'A' => (a != 0 && b != 0) as i32,
+                    _ => (a != 0 || b != 0) as i32,
+                };
+                stack.push(Param::Number(value));
+            }
+            b'!' => {
+                let a = pop_number(&mut stack);
+                stack.push(Param::Number((a == 0) as i32));
+            }
+            b'~' => {
+                let a = pop_number(&mut stack);
+                stack.push(Param::Number(!a));
+            }
+            b'i' => {
+                for param in params.iter_mut().take(2) {
+                    if let Param::Number(n) = param {
+                        *n += 1;
+                    }
+                }
+            }
+            b'?' | b';' => {}
+            b't' => {
+                if pop_number(&mut stack) == 0 {
+                    pos = skip_conditional(cap, pos, true);
+                }
+            }
+            b'e' => pos = skip_conditional(cap, pos, false),
+            _ => return Err(expand_error("invalid % sequence")),
+        }
+    }
+
+    Ok(out)
+}
+
+/// Write a capability string, leaving out its $<..> padding specifications.
+pub fn write_cap<W: Write>(w: &mut W, cap: &[u8]) -> io::Result<()> {
+    let mut rest = cap;
+    while let Some(start) = rest.windows(2).position(|w| w == b"$<") {
+        let end = rest[start..].iter().position(|b| *b == b'>');
+        match end {
+            Some(end)
+                if rest[start + 2..start + end]
+                    .iter()
+                    .all(|b| b.is_ascii_digit() || b".*/".contains(b)) =>
+            {
+                w.write_all(&rest[..start])?;
+                rest = &rest[start + end + 1..];
+            }
+            _ => {
+                w.write_all(&rest[..start + 2])?;
+                rest = &rest[start + 2..];
+            }
+        }
+    }
+    w.write_all(rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // compile a legacy-format entry with the given standard capabilities
+    fn compile(names: &str, booleans: &[u8], numbers: &[i16], strings: &[Option<&str>]) -> Vec<u8> {
+        let mut table = Vec::new();
+        let mut offsets = Vec::new();
+        for s in strings {
+            match s {
+                Some(s) => {
+                    offsets.push(table.len() as i16);
+                    table.extend(s.as_bytes());
+                    table.push(0);
+                }
+                None => offsets.push(-1),
+            }
+        }
+
+        let mut data = Vec::new();
+        for value in [
+            MAGIC_LEGACY as i16,
+            names.len() as i16 + 1,
+            booleans.len() as i16,
+            numbers.len() as i16,
+            offsets.len() as i16,
+            table.len() as i16,
+        ] {
+            data.extend(value.to_le_bytes());
+        }
+        data.extend(names.as_bytes());
+        data.push(0);
+        data.extend(booleans);
+        if data.len() % 2 != 0 {
+            data.push(0);
+        }
+        for value in numbers.iter().chain(offsets.iter()) {
+            data.extend(value.to_le_bytes());
+        }
+        data.extend(table);
+        data
+    }
+
+    #[test]
+    fn test_parse() {
+        let data = compile(
+            "dumb|80-column dumb tty",
+            &[0, 1],
+            &[80, -1, 24],
+            &[None, Some("\x07"), Some("\r")],
+        );
+        let info = Terminfo::parse(&data).unwrap();
+        assert_eq!(info.names, vec!["dumb", "80-column dumb tty"]);
+        assert!(!info.flag("bw"));
+        assert!(info.flag("am"));
+        assert_eq!(info.number("cols"), Some(80));
+        assert_eq!(info.number("it"), None);
+        assert_eq!(info.number("lines"), Some(24));
+        assert_eq!(info.string("cbt"), None);
+        assert_eq!(info.string("bel"), Some(&b"\x07"[..]));
+        assert_eq!(info.string("cr"), Some(&b"\r"[..]));
+        assert_eq!(info.kind("clear"), Some(CapKind::String));
+        assert_eq!(info.kind("nosuchcap"), None);
+
+        assert!(Terminfo::parse(b"not terminfo").is_err());
+        assert!(Terminfo::parse(&data[..20]).is_err());
+    }
+
+    #[test]
+    fn test_tparm() {
+        let n =
+            |values: &[i32]| -> Vec<Param> { values.iter().map(|v| Param::Number(*v)).collect() };
+
+        let cup = b"\x1b[%i%p1%d;%p2%dH";
+        assert_eq!(tparm(cup, &n(&[4, 9])).unwrap(), b"\x1b[5;10H");
+
+        let setaf = b"\x1b[%?%p1%{8}%<%t3%p1%d%e%p1%{16}%<%t9%p1%{8}%-%d%e38;5;%p1%d%;m";
+        assert_eq!(tparm(setaf, &n(&[1])).unwrap(), b"\x1b[31m");
+        assert_eq!(tparm(setaf, &n(&[9])).unwrap(), b"\x1b[91m");
+        assert_eq!(tparm(setaf, &n(&[200])).unwrap(), b"\x1b[38;5;200m");
+
+        assert_eq!(
+            tparm(b"%p1%03d|%p1%:-4x|%p1%#o", &n(&[9])).unwrap(),
+            b"009|9   |011"
+        );
+        assert_eq!(
+            tparm(b"%p1%c%'A'%c%{66}%c%%", &n(&[b'x' as i32])).unwrap(),
+            b"xAB%"
+        );
+        assert_eq!(tparm(b"%p1%Pa%ga%ga%*%d", &n(&[7])).unwrap(), b"49");
+        assert_eq!(
+            tparm(b"%p1%s:%p1%l%d", &[Param::String(b"abc".to_vec())]).unwrap(),
+            b"abc:3"
+        );
+        assert!(tparm(b"%p0", &[]).is_err());
+    }
+
+    #[test]
+    fn test_write_cap() {
+        let mut out = Vec::new();
+        write_cap(&mut out, b"\x1b[?5h$<100/>\x1b[?5l$x$<").unwrap();
+        assert_eq!(out, b"\x1b[?5h\x1b[?5l$x$<");
+    }
+}
@@ -0,0 +1,412 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+//! Terminal attributes: the mode flags, control characters and baud rates
+//! of the general terminal interface, named as stty names them.
+
+use libc::{cc_t, speed_t, tcflag_t};
+use std::io;
+use std::os::unix::io::RawFd;
+
+pub use libc::termios as Termios;
+
+/// The value of a control character that is disabled.
+#[cfg(target_os = "linux")]
+pub const VDISABLE: cc_t = 0;
+#[cfg(not(target_os = "linux"))]
+pub const VDISABLE: cc_t = 0xff;
+
+/// Read the attributes of the terminal open on `fd`.
+pub fn get_attr(fd: RawFd) -> io::Result<Termios> {
+    let mut ti: Termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut ti) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(ti)
+}
+
+/// Set the attributes of the terminal open on `fd`, once pending output
+/// has been written.
+pub fn set_attr(fd: RawFd, ti: &Termios) -> io::Result<()> {
+    if unsafe { libc::tcsetattr(fd, libc::TCSADRAIN, ti) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// The four sets of mode flags.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FlagSet {
+    Control,
+    Input,
+    Output,
+    Local,
+}
+
+impl FlagSet {
+    pub fn flags(self, ti: &Termios) -> tcflag_t {
+        match self {
+            FlagSet::Control => ti.c_cflag,
+            FlagSet::Input => ti.c_iflag,
+            FlagSet::Output => ti.c_oflag,
+            FlagSet::Local => ti.c_lflag,
+        }
+    }
+
+    pub fn flags_mut(self, ti: &mut Termios) -> &mut tcflag_t {
+        match self {
+            FlagSet::Control => &mut ti.c_cflag,
+            FlagSet::Input => &mut ti.c_iflag,
+            FlagSet::Output => &mut ti.c_oflag,
+            FlagSet::Local => &mut ti.c_lflag,
+        }
+    }
+}
+
+/// A mode: the value `bits` of the field `mask` in one of the flag sets.
+pub struct Mode {
+    pub name: &'static str,
+    pub set: FlagSet,
+    pub bits: tcflag_t,
+    pub mask: tcflag_t,
+
+    /// Whether -name clears the mode.  Modes that select one value of a
+    /// multi-bit field, such as cs8, cannot be negated.
+    pub negatable: bool,
+}
+
+impl Mode {
+    pub fn is_set(&self, ti: &Termios) -> bool {
+        self.set.flags(ti) & self.mask == self.bits
+    }
+
+    /// Set the mode, or clear it if `on` is false.
+    pub fn apply(&self, ti: &mut Termios, on: bool) {
+        let flags = self.set.flags_mut(ti);
+        *flags &= !self.mask;
+        if on {
+            *flags |= self.bits;
+        }
+    }
+}
+
+const fn flag(name: &'static str, set: FlagSet, bits: tcflag_t) -> Mode {
+    Mode {
+        name,
+        set,
+        bits,
+        mask: bits,
+        negatable: true,
+    }
+}
+
+const fn field(name: &'static str, set: FlagSet, bits: tcflag_t, mask: tcflag_t) -> Mode {
+    Mode {
+        name,
+        set,
+        bits,
+        mask,
+        negatable: false,
+    }
+}
+
+use FlagSet::{Control, Input, Local, Output};
+
+/// The modes, in the order stty lists them.
+pub const MODES: &[Mode] = &[
+    // control modes
+    flag("parenb", Control, libc::PARENB),
+    flag("parodd", Control, libc::PARODD),
+    field("cs5", Control, libc::CS5, libc::CSIZE),
+    field("cs6", Control, libc::CS6, libc::CSIZE),
+    field("cs7", Control, libc::CS7, libc::CSIZE),
+    field("cs8", Control, libc::CS8, libc::CSIZE),
+    flag("hupcl", Control, libc::HUPCL),
+    flag("cstopb", Control, libc::CSTOPB),
+    flag("cread", Control, libc::CREAD),
+    flag("clocal", Control, libc::CLOCAL),
+    flag("crtscts", Control, libc::CRTSCTS),
+    // input modes
+    flag("ignbrk", Input, libc::IGNBRK),
+    flag("brkint", Input, libc::BRKINT),
+    flag("ignpar", Input, libc::IGNPAR),
+    flag("parmrk", Input, libc::PARMRK),
+    flag("inpck", Input, libc::INPCK),
+    flag("istrip", Input, libc::ISTRIP),
+    flag("inlcr", Input, libc::INLCR),
+    flag("igncr", Input, libc::IGNCR),
+    flag("icrnl", Input, libc::ICRNL),
+    flag("ixon", Input, libc::IXON),
+    flag("ixoff", Input, libc::IXOFF),
+    flag("ixany", Input, libc::IXANY),
+    flag("imaxbel", Input, libc::IMAXBEL),
+    flag("iutf8", Input, libc::IUTF8),
+    // output modes
+    flag("opost", Output, libc::OPOST),
+    flag("onlcr", Output, libc::ONLCR),
+    flag("ocrnl", Output, libc::OCRNL),
+    flag("onocr", Output, libc::ONOCR),
+    flag("onlret", Output, libc::ONLRET),
+    flag("ofill", Output, libc::OFILL),
+    flag("ofdel", Output, libc::OFDEL),
+    field("nl0", Output, libc::NL0, libc::NLDLY),
+    field("nl1", Output, libc::NL1, libc::NLDLY),
+    field("cr0", Output, libc::CR0, libc::CRDLY),
+    field("cr1", Output, libc::CR1, libc::CRDLY),
+    field("cr2", Output, libc::CR2, libc::CRDLY),
+    field("cr3", Output, libc::CR3, libc::CRDLY),
+    field("tab0", Output, libc::TAB0, libc::TABDLY),
+    field("tab1", Output, libc::TAB1, libc::TABDLY),
+    field("tab2", Output, libc::TAB2, libc::TABDLY),
+    field("tab3", Output, libc::TAB3, libc::TABDLY),
+    field("bs0", Output, libc::BS0, libc::BSDLY),
+    field("bs1", Output, libc::BS1, libc::BSDLY),
+    field("vt0", Output, libc::VT0, libc::VTDLY),
+    field("vt1", Output, libc::VT1, libc::VTDLY),
+    field("ff0", Output, libc::FF0, libc::FFDLY),
+    field("ff1", Output, libc::FF1, libc::FFDLY),
+    // local modes
+    flag("isig", Local, libc::ISIG),
+    flag("icanon", Local, libc::ICANON),
+    flag("iexten", Local, libc::IEXTEN),
+    flag("echo", Local, libc::ECHO),
+    flag("echoe", Local, libc::ECHOE),
+    flag("echok", Local, libc::ECHOK),
+    flag("echonl", Local, libc::ECHONL),
+    flag("noflsh", Local, libc::NOFLSH),
+    flag("tostop", Local, libc::TOSTOP),
+    flag("echoprt", Local, libc::ECHOPRT),
+    flag("echoctl", Local, libc::ECHOCTL),
+    flag("echoke", Local, libc::ECHOKE),
+    flag("flusho", Local, libc::FLUSHO),
+    flag("pendin", Local, libc::PENDIN),
+];
+
+/// The mode named `name`.
+pub fn find_mode(name: &str) -> Option<&'static Mode> {
+    MODES.iter().find(|mode| mode.name == name)
+}
+
+/// A control character: its name, its index in c_cc and its usual value.
+pub struct ControlChar {
+    pub name: &'static str,
+    pub index: usize,
+    pub default: cc_t,
+}
+
+const fn ctrl(c: u8) -> cc_t {
+    c & 0x1f
+}
+
+/// The control characters, in the order stty lists them.  The MIN and
+/// TIME values of non-canonical input are numbers rather than characters,
+/// and are not among them.
+pub const CONTROL_CHARS: &[ControlChar] = &[
+    ControlChar {
+        name: "intr",
+        index: libc::VINTR,
+        default: ctrl(b'c'),
+    },
+    ControlChar {
+        name: "quit",
+        index: libc::VQUIT,
+        default: ctrl(b'\\'),
+    },
+    ControlChar {
+        name: "erase",
+        index: libc::VERASE,
+        default: 0x7f,
+    },
+    ControlChar {
+        name: "kill",
+        index: libc::VKILL,
+        default: ctrl(b'u'),
+    },
+    ControlChar {
+        name: "eof",
+        index: libc::VEOF,
+        default: ctrl(b'd'),
+    },
+    ControlChar {
+        name: "eol",
+        index: libc::VEOL,
+        default: VDISABLE,
+    },
+    ControlChar {
+        name: "eol2",
+        index: libc::VEOL2,
+        default: VDISABLE,
+    },
+    ControlChar {
+        name: "start",
+        index: libc::VSTART,
+        default: ctrl(b'q'),
+    },
+    ControlChar {
+        name: "stop",
+        index: libc::VSTOP,
+        default: ctrl(b's'),
+    },
+    ControlChar {
+        name: "susp",
+        index: libc::VSUSP,
+        default: ctrl(b'z'),
+    },
+    ControlChar {
+        name: "rprnt",
+        index: libc::VREPRINT,
+        default: ctrl(b'r'),
+    },
+    ControlChar {
+        name: "werase",
+        index: libc::VWERASE,
+        default: ctrl(b'w'),
+    },
+    ControlChar {
+        name: "lnext",
+        index: libc::VLNEXT,
+        default: ctrl(b'v'),
+    },
+    ControlChar {
+        name: "discard",
+        index: libc::VDISCARD,
+        default: ctrl(b'o'),
+    },
+];
+
+/// The control character named `name`.
+pub fn find_control_char(name: &str) -> Option<&'static ControlChar> {
+    CONTROL_CHARS.iter().find(|cc| cc.name == name)
+}
+
+/// Write a control character value the way stty shows it: ^X for
+/// control characters, ^? for DEL and <undef> when disabled.
+pub fn format_cc(value: cc_t) -> String {
+    match value {
+        VDISABLE => String::from("<undef>"),
+        0x7f => String::from("^?"),
+        c if c < 0x20 => format!("^{}", (c + 0x40) as char),
+        c => (c as char).to_string(),
+    }
+}
+
+/// Parse a control character value: ^X, ^? and a single character, with
+/// ^- and undef disabling the character.
+pub fn parse_cc(s: &str) -> Option<cc_t> {
+    if s == "^-" || s == "undef" {
+        return Some(VDISABLE);
+    }
+    match s.as_bytes() {
+        [b'^', b'?'] => Some(0x7f),
+        [b'^', c] if (b'@'..=b'_').contains(&c.to_ascii_uppercase()) => {
+            Some(c.to_ascii_uppercase() & 0x1f)
+        }
+        [c] => Some(*c),
+        _ => None,
+    }
+}
+
+/// Baud rates and their speed_t values.
+pub const SPEEDS: &[(u32, speed_t)] = &[
+    (0, libc::B0),
+    (50, libc::B50),
+    (75, libc::B75),
+    (110, libc::B110),
+    (134, libc::B134),
+    (150, libc::B150),
+    (200, libc::B200),
+    (300, libc::B300),
+    (600, libc::B600),
+    (1200, libc::B1200),
+    (1800, libc::B1800),
+    (2400, libc::B2400),
+    (4800, libc::B4800),
+    (9600, libc::B9600),
+    (19200, libc::B19200),
+    (38400, libc::B38400),
+    (57600, libc::B57600),
+    (115200, libc::B115200),
+    (230400, libc::B230400),
+];
+
+/// The speed_t value of a baud rate.
+pub fn speed_from_baud(baud: u32) -> Option<speed_t> {
+    SPEEDS.iter().find(|(b, _)| *b == baud).map(|(_, s)| *s)
+}
+
+/// The baud rate of a speed_t value.
+pub fn baud_from_speed(speed: speed_t) -> Option<u32> {
+    SPEEDS.iter().find(|(_, s)| *s == speed).map(|(b, _)| *b)
+}
+
+pub fn ispeed(ti: &Termios) -> speed_t {
+    unsafe { libc::cfgetispeed(ti) }
+}
+
+pub fn ospeed(ti: &Termios) -> speed_t {
+    unsafe { libc::cfgetospeed(ti) }
+}
+
+pub fn set_ispeed(ti: &mut Termios, speed: speed_t) -> io::Result<()> {
+    if unsafe { libc::cfsetispeed(ti, speed) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub fn set_ospeed(ti: &mut Termios, speed: speed_t) -> io::Result<()> {
+    if unsafe { libc::cfsetospeed(ti, speed) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_control_chars() {
+        assert_eq!(parse_cc("^C"), Some(3));
+        assert_eq!(parse_cc("^c"), Some(3));
+        assert_eq!(parse_cc("^?"), Some(0x7f));
+        assert_eq!(parse_cc("^\\"), Some(0x1c));
+        assert_eq!(parse_cc("x"), Some(b'x'));
+        assert_eq!(parse_cc("^-"), Some(VDISABLE));
+        assert_eq!(parse_cc("undef"), Some(VDISABLE));
+        assert_eq!(parse_cc("^1"), None);
+        assert_eq!(parse_cc("ab"), None);
+
+        assert_eq!(format_cc(3), "^C");
+        assert_eq!(format_cc(0x7f), "^?");
+        assert_eq!(format_cc(0x1c), "^\\");
+        assert_eq!(format_cc(b'x'), "x");
+        assert_eq!(format_cc(VDISABLE), "<undef>");
+    }
+
+    #[test]
+    fn test_modes() {
+        let mut ti: Termios = unsafe { std::mem::zeroed() };
+        let cs7 = find_mode("cs7").unwrap();
+        let cs8 = find_mode("cs8").unwrap();
+        let parenb = find_mode("parenb").unwrap();
+
+        cs7.apply(&mut ti, true);
+        parenb.apply(&mut ti, true);
+        assert!(cs7.is_set(&ti) && !cs8.is_set(&ti) && parenb.is_set(&ti));
+
+        cs8.apply(&mut ti, true);
+        parenb.apply(&mut ti, false);
+        assert!(!cs7.is_set(&ti) && cs8.is_set(&ti) && !parenb.is_set(&ti));
+
+        assert_eq!(speed_from_baud(9600), Some(libc::B9600));
+        assert_eq!(baud_from_speed(libc::B38400), Some(38400));
+        assert_eq!(speed_from_baud(9601), None);
+    }
+}
@@ -10,6 +10,8 @@
 extern crate libc;
 use libc::{endutxent, getutxent, setutxent};
 use std::ffi::CStr;
+use std::io;
+use std::path::Path;
 
 #[derive(Debug)]
 pub struct Utmpx {
@@ -84,3 +86,38 @@ pub fn load() -> Vec<Utmpx> {
 
     entries
 }
+
+/// Load the entries of the utmpx-format file `path`, rather than of the
+/// system's user accounting database.
+#[cfg(any(all(target_os = "linux", target_env = "gnu"), target_os = "macos"))]
+pub fn load_file(path: &Path) -> io::Result<Vec<Utmpx>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    // utmpxname() does not check the file, which is then silently empty
+    std::fs::metadata(path)?;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let res = unsafe { libc::utmpxname(c_path.as_ptr()) };
+
+    // glibc returns 0 on success, macOS non-zero
+    let failed = if cfg!(target_os = "macos") {
+        res == 0
+    } else {
+        res != 0
+    };
+    if failed {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(load())
+}
+
+#[cfg(not(any(all(target_os = "linux", target_env = "gnu"), target_os = "macos")))]
+pub fn load_file(_path: &Path) -> io::Result<Vec<Utmpx>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "reading other utmpx files is not supported",
+    ))
+}
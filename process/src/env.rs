@@ -13,11 +13,12 @@ extern crate plib;
 use clap::Parser;
 use gettextrs::{bind_textdomain_codeset, textdomain};
 use plib::PROJECT_NAME;
-use std::collections::HashMap;
 use std::env;
-use std::io;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
 use std::os::unix::process::CommandExt;
-use std::process::{Command, Stdio};
+use std::process::Command;
 
 /// env - set the environment for command invocation
 #[derive(Parser, Debug)]
@@ -28,83 +29,92 @@ struct Args {
     ignore_env: bool,
 
     /// NAME=VALUE pairs, the utility to invoke, and its arguments.
-    operands: Vec<String>,
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    operands: Vec<OsString>,
 }
 
-fn separate_ops(sv: &Vec<String>) -> (Vec<String>, Vec<String>) {
-    let mut envs = Vec::new();
-    let mut util_args = Vec::new();
-    let mut in_envs = true;
-
-    for s in sv {
-        if in_envs {
-            if s.contains('=') {
-                envs.push(String::from(s));
-                continue;
-            }
-
-            in_envs = false;
-
-            // fall through
-        }
-
-        util_args.push(String::from(s));
-    }
-
-    (envs, util_args)
+/// Split the operands into the leading NAME=VALUE settings and the
+/// utility with its arguments.
+fn separate_ops(sv: &[OsString]) -> (&[OsString], &[OsString]) {
+    let n_envs = sv
+        .iter()
+        .take_while(|s| s.as_bytes().contains(&b'='))
+        .count();
+    sv.split_at(n_envs)
 }
 
-fn merge_env(new_env: &Vec<String>, clear: bool) -> HashMap<String, String> {
-    let mut map = HashMap::new();
-
-    if !clear {
-        for (key, value) in env::vars() {
-            map.insert(String::from(key), String::from(value));
-        }
-    }
+// the environment, in its original order with settings replacing the
+// inherited values
+fn merge_env(new_env: &[OsString], clear: bool) -> Vec<(OsString, OsString)> {
+    let mut vars: Vec<(OsString, OsString)> = if clear {
+        Vec::new()
+    } else {
+        env::vars_os().collect()
+    };
 
     for env_op in new_env {
-        let (key, value) = env_op.split_once('=').unwrap();
-        map.insert(String::from(key), String::from(value));
+        let bytes = env_op.as_bytes();
+        let eq = bytes.iter().position(|b| *b == b'=').unwrap();
+        let key = OsStr::from_bytes(&bytes[..eq]).to_os_string();
+        let value = OsStr::from_bytes(&bytes[eq + 1..]).to_os_string();
+        match vars.iter_mut().find(|(k, _)| *k == key) {
+            Some(var) => var.1 = value,
+            None => vars.push((key, value)),
+        }
     }
 
-    map
+    vars
 }
 
-fn print_env(envs: HashMap<String, String>) -> Result<(), Box<dyn std::error::Error>> {
+fn print_env(envs: &[(OsString, OsString)]) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
     for (key, value) in envs {
-        println!("{}={}", key, value);
+        stdout.write_all(key.as_bytes())?;
+        stdout.write_all(b"=")?;
+        stdout.write_all(value.as_bytes())?;
+        stdout.write_all(b"\n")?;
     }
 
-    Ok(())
+    stdout.flush()
 }
 
-fn exec_util(envs: HashMap<String, String>, util_args: Vec<String>) -> io::Result<()> {
-    Err(Command::new(&util_args[0])
+fn exec_util(envs: Vec<(OsString, OsString)>, util_args: &[OsString]) -> io::Error {
+    Command::new(&util_args[0])
         .args(&util_args[1..])
-        .stdin(Stdio::inherit())
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
         .env_clear()
-        .envs(&envs)
-        .exec())
+        .envs(envs)
+        .exec()
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // parse command line arguments
-    let args = Args::parse();
+    let mut args = Args::parse();
 
     textdomain(PROJECT_NAME)?;
     bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
 
+    // a leading "-" is the obsolescent form of -i
+    if args.operands.first().is_some_and(|op| op == "-") {
+        args.operands.remove(0);
+        args.ignore_env = true;
+    }
+
     let (envs, util_args) = separate_ops(&args.operands);
-    let new_env = merge_env(&envs, args.ignore_env);
+    let new_env = merge_env(envs, args.ignore_env);
 
     if util_args.is_empty() {
-        return print_env(new_env);
+        print_env(&new_env)?;
+        return Ok(());
     }
 
-    exec_util(new_env, util_args)?;
-
-    Ok(())
+    // exec only returns on failure: the utility was not found (127) or
+    // could not be invoked (126)
+    let err = exec_util(new_env, util_args);
+    eprintln!("env: {}: {}", util_args[0].to_string_lossy(), err);
+    let exit_code = if err.kind() == io::ErrorKind::NotFound {
+        127
+    } else {
+        126
+    };
+    std::process::exit(exit_code)
 }
@@ -7,108 +7,123 @@
 // SPDX-License-Identifier: MIT
 //
 
-extern crate clap;
 extern crate libc;
 extern crate plib;
 
-mod osdata;
-
-use gettextrs::{bind_textdomain_codeset, textdomain};
+use gettextrs::{bind_textdomain_codeset, gettext, textdomain};
+use plib::signal::{signal_name, signal_number, SIGNALS};
 use plib::PROJECT_NAME;
-use regex::Regex;
-use std::collections::HashMap;
-
-fn lookup_signum(sigmap: &HashMap<&str, u32>, signame: &str) -> Result<u32, &'static str> {
-    if signame == "0" {
-        Ok(0)
-    } else {
-        match sigmap.get(signame) {
-            Some(&sig_no) => Ok(sig_no),
-            None => Err("Unknown signal name"),
-        }
+
+fn lookup_signum(signame: &str) -> Result<i32, String> {
+    match signal_number(signame) {
+        Some(sig_no) => Ok(sig_no),
+        None => Err(gettext!("{}: invalid signal specification", signame)),
+    }
+}
+
+// the obsolescent -signal_number form takes any known signal number
+fn check_signum(numstr: &str) -> Result<i32, String> {
+    match numstr.parse::<i32>() {
+        Ok(sig_no) if sig_no == 0 || signal_name(sig_no).is_some() => Ok(sig_no),
+        _ => Err(gettext!("{}: invalid signal specification", numstr)),
     }
 }
 
 enum ConfigMode {
-    Signal(u32),
+    Signal(i32),
     List,
 }
 
 struct Config {
     mode: ConfigMode,
-    pids: Vec<u32>,
+    operands: Vec<String>,
 }
 
-fn parse_cmdline(sigmap: &HashMap<&str, u32>) -> Result<Config, &'static str> {
-    let signame_re = Regex::new(r"^-(\w+)$").unwrap();
-    let signum_re = Regex::new(r"^-(\d+)$").unwrap();
-
-    let mut pids = Vec::new();
-    let mut mode = ConfigMode::Signal(libc::SIGTERM as u32);
-    let mut in_args = true;
-    let mut in_s_arg = false;
-    for arg in std::env::args().skip(1) {
-        if in_args {
-            if in_s_arg {
-                let sig_no = lookup_signum(sigmap, &arg)?;
-                mode = ConfigMode::Signal(sig_no);
-                in_s_arg = false;
-            } else if arg == "-s" || arg == "--signal" {
-                in_s_arg = true;
-            } else if arg == "-l" || arg == "--list" {
-                mode = ConfigMode::List;
-            } else if arg == "--" {
-                in_args = false;
-            } else if let Some(caps) = signame_re.captures(&arg) {
-                let namestr = caps.get(1).unwrap().as_str();
-                let sig_no = lookup_signum(sigmap, namestr)?;
-                mode = ConfigMode::Signal(sig_no);
-            } else if let Some(caps) = signum_re.captures(&arg) {
-                let numstr = caps.get(1).unwrap().as_str();
-                let sig_no = numstr.parse::<u32>().unwrap();
-                mode = ConfigMode::Signal(sig_no);
+fn parse_cmdline() -> Result<Config, String> {
+    let mut args = std::env::args().skip(1).peekable();
+    let mut mode = ConfigMode::Signal(libc::SIGTERM);
+
+    // at most one option, as operands may be negative process group IDs
+    if let Some(arg) = args.peek().cloned() {
+        if arg == "-l" || arg == "--list" {
+            mode = ConfigMode::List;
+            args.next();
+        } else if arg == "-s" || arg == "--signal" {
+            args.next();
+            let signame = args
+                .next()
+                .ok_or_else(|| gettext("option requires an argument -- 's'"))?;
+            mode = ConfigMode::Signal(lookup_signum(&signame)?);
+        } else if arg != "--" && arg.len() > 1 && arg.starts_with('-') {
+            let spec = &arg[1..];
+            let sig_no = if spec.bytes().all(|b| b.is_ascii_digit()) {
+                check_signum(spec)?
             } else {
-                in_args = false;
-            }
-
-            if in_args || arg == "--" {
-                continue;
-            }
-
-            // fall through to process non-option arguments
+                lookup_signum(spec)?
+            };
+            mode = ConfigMode::Signal(sig_no);
+            args.next();
         }
+    }
+    if args.peek().is_some_and(|arg| arg == "--") {
+        args.next();
+    }
 
-        match arg.parse::<u32>() {
-            Ok(pid) => pids.push(pid),
-            Err(_) => {
-                return Err("Invalid PID");
-            }
-        }
+    let operands: Vec<String> = args.collect();
+    if matches!(mode, ConfigMode::Signal(_)) && operands.is_empty() {
+        return Err(gettext("no process ID specified"));
     }
 
-    Ok(Config { mode, pids })
+    Ok(Config { mode, operands })
 }
 
-fn list_signals(sigmap: &HashMap<&str, u32>) -> u32 {
-    let mut output = String::new();
-    for name in sigmap.keys() {
-        output.push_str(name);
-        output.push(' ');
+/// Write the signal names, or those of the signals that terminated
+/// processes with the given exit statuses.
+fn list_signals(operands: &[String]) -> i32 {
+    if operands.is_empty() {
+        let names: Vec<&str> = SIGNALS.iter().map(|(name, _)| *name).collect();
+        println!("{}", names.join(" "));
+        return 0;
     }
 
-    println!("{}", output);
-
-    0
+    let mut exit_code = 0;
+    for operand in operands {
+        // the exit status of a process killed by a signal is 128 + signo
+        let name = operand
+            .parse::<i32>()
+            .ok()
+            .map(|n| if n > 128 { n - 128 } else { n })
+            .and_then(signal_name);
+        match name {
+            Some(name) => println!("{}", name),
+            None => {
+                eprintln!("kill: {}", gettext!("{}: invalid exit status", operand));
+                exit_code = 1;
+            }
+        }
+    }
+    exit_code
 }
 
-fn send_signal(prog_cfg: &Config, sig_no: u32) -> u32 {
+/// Send `sig_no` to each process operand.  Negative process IDs name
+/// process groups.
+fn send_signal(operands: &[String], sig_no: i32) -> i32 {
     let mut exit_code = 0;
 
-    for pid in &prog_cfg.pids {
-        let res = unsafe { libc::kill(*pid as libc::pid_t, sig_no as i32) };
+    for operand in operands {
+        let pid = match operand.parse::<libc::pid_t>() {
+            Ok(pid) => pid,
+            Err(_) => {
+                eprintln!("kill: {}", gettext!("{}: invalid process ID", operand));
+                exit_code = 1;
+                continue;
+            }
+        };
+
+        let res = unsafe { libc::kill(pid, sig_no) };
         if res != 0 {
             let err = std::io::Error::last_os_error();
-            eprintln!("kill pid {}: {}", pid, err);
+            eprintln!("kill: {}: {}", pid, err);
             exit_code = 1;
         }
     }
@@ -120,13 +135,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     textdomain(PROJECT_NAME)?;
     bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
 
-    let sigmap = osdata::get_sigmap();
-    let prog_cfg = parse_cmdline(&sigmap)?;
+    let prog_cfg = match parse_cmdline() {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            eprintln!("kill: {}", e);
+            std::process::exit(2);
+        }
+    };
 
     let exit_code = match prog_cfg.mode {
-        ConfigMode::List => list_signals(&sigmap),
-        ConfigMode::Signal(sig_no) => send_signal(&prog_cfg, sig_no),
+        ConfigMode::List => list_signals(&prog_cfg.operands),
+        ConfigMode::Signal(sig_no) => send_signal(&prog_cfg.operands, sig_no),
     };
 
-    std::process::exit(exit_code as i32)
+    std::process::exit(exit_code)
 }
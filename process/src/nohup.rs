@@ -7,14 +7,17 @@
 // SPDX-License-Identifier: MIT
 //
 
-use gettextrs::{bind_textdomain_codeset, textdomain};
+use gettextrs::{bind_textdomain_codeset, gettext, textdomain};
 use libc::signal;
-use libc::{dup, dup2, SIGHUP, SIG_IGN};
+use libc::{dup2, fcntl, F_DUPFD_CLOEXEC, SIGHUP, SIG_IGN};
 use plib::PROJECT_NAME;
 use std::env;
 use std::fs::{File, OpenOptions};
 use std::io;
+use std::os::unix::fs::OpenOptionsExt;
 use std::os::unix::io::AsRawFd;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
 use std::process::{self, Command};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -26,120 +29,93 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         signal(SIGHUP, SIG_IGN);
     }
 
-    // Save the original stderr
-    let original_stderr = unsafe { dup(libc::STDERR_FILENO) };
+    // Save the original stderr, closed on exec so the utility does not
+    // inherit it
+    let original_stderr = unsafe { fcntl(libc::STDERR_FILENO, F_DUPFD_CLOEXEC, 0) };
     if original_stderr == -1 {
-        eprintln!("Failed to duplicate stderr");
+        eprintln!("nohup: {}", io::Error::last_os_error());
         process::exit(127);
     }
 
     // Getting the command and arguments
-    let mut args = env::args().skip(1);
+    let mut args = env::args().skip(1).peekable();
+    if args.peek().is_some_and(|arg| arg == "--") {
+        args.next();
+    }
     let command = match args.next() {
         Some(cmd) => cmd,
         None => {
-            eprintln!("Usage: nohup <command> [args...]");
+            eprintln!("{}", gettext("usage: nohup utility [argument...]"));
             process::exit(127);
         }
     };
 
-    // Redirecting stdout and stderr to the nohup.out file if they are connected to a terminal
-    if atty::is(atty::Stream::Stdout) || atty::is(atty::Stream::Stderr) {
-        let nohup_out_file =
-            get_nohup_out_file().expect("Failed to open nohup.out in current or home directory");
-
-        if atty::is(atty::Stream::Stdout) {
-            let fd = nohup_out_file.0.as_raw_fd();
-
-            if unsafe { dup2(fd, libc::STDOUT_FILENO) } == -1 {
-                eprintln!("Failed to redirect stdout");
+    // Output to a terminal is appended to nohup.out instead
+    if atty::is(atty::Stream::Stdout) {
+        let (file, path) = match get_nohup_out_file() {
+            Ok(nohup_out) => nohup_out,
+            Err(e) => {
+                eprintln!("nohup: {}: {}", gettext("cannot open nohup.out"), e);
                 process::exit(127);
             }
+        };
 
-            match nohup_out_file.1 {
-                NohupDir::Current => {
-                    eprintln!(
-                        "Name of the file to which the output is being appended: `nohup.out`"
-                    );
-                }
-                NohupDir::Home => {
-                    eprintln!(
-                        "Name of the file to which the output is being appended: `$HOME/nohup.out`"
-                    );
-                }
-            }
-        }
-
-        if atty::is(atty::Stream::Stderr) {
-            let fd = nohup_out_file.0.as_raw_fd();
+        eprintln!(
+            "nohup: {}",
+            gettext!("appending output to {}", path.display())
+        );
 
-            if unsafe { dup2(fd, libc::STDERR_FILENO) } == -1 {
-                eprintln!("Failed to redirect stderr");
-                process::exit(127);
-            }
+        if unsafe { dup2(file.as_raw_fd(), libc::STDOUT_FILENO) } == -1 {
+            eprintln!("nohup: {}", io::Error::last_os_error());
+            process::exit(127);
         }
     }
 
-    match Command::new(command).args(args).spawn() {
-        Ok(mut process) => {
-            process::exit(process.wait()?.code().unwrap_or(127));
-        }
-        Err(error) => {
-            use std::io::ErrorKind;
-
-            // Restore the original stderr
-
-            if unsafe { dup2(original_stderr, libc::STDERR_FILENO) } == -1 {
-                eprintln!("Failed to restore stderr");
-                process::exit(127);
-            }
+    // Errors written to a terminal go wherever the output goes
+    if atty::is(atty::Stream::Stderr)
+        && unsafe { dup2(libc::STDOUT_FILENO, libc::STDERR_FILENO) } == -1
+    {
+        eprintln!("nohup: {}", io::Error::last_os_error());
+        process::exit(127);
+    }
 
-            // Close the duplicated descriptor as it's no longer needed
-            unsafe { libc::close(original_stderr) };
+    // exec only returns on failure
+    let error = Command::new(&command).args(args).exec();
 
-            match error.kind() {
-                ErrorKind::NotFound => {
-                    eprintln!("Error: command not found");
-                    process::exit(127);
-                }
-                _ => {
-                    eprintln!("Error: command found but could not be invoked");
-                    process::exit(126);
-                }
-            }
-        }
+    // Restore the original stderr to report the error
+    unsafe {
+        dup2(original_stderr, libc::STDERR_FILENO);
+        libc::close(original_stderr);
     }
-}
 
-enum NohupDir {
-    Current,
-    Home,
+    eprintln!("nohup: {}: {}", command, error);
+    if error.kind() == io::ErrorKind::NotFound {
+        process::exit(127);
+    } else {
+        process::exit(126);
+    }
 }
 
-fn get_nohup_out_file() -> Result<(File, NohupDir), io::Error> {
-    // Attempting to open or create a nohup.out file in the current directory
-    match OpenOptions::new()
+fn open_nohup_out(path: &PathBuf) -> io::Result<File> {
+    OpenOptions::new()
         .create(true)
         .append(true)
-        .open("nohup.out")
-    {
-        Ok(file) => Ok((file, NohupDir::Current)),
-        Err(_) => {
-            // If unsuccessful, attempt to create a nohup.out file in the home directory
-            if let Some(home_dir) = dirs::home_dir() {
-                let mut home_nohup_path = home_dir;
-                home_nohup_path.push("nohup.out");
-                let file = OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open(home_nohup_path)?;
-                Ok((file, NohupDir::Home))
-            } else {
-                Err(io::Error::new(
-                    io::ErrorKind::NotFound,
-                    "Home directory not found",
-                ))
-            }
+        .mode(0o600)
+        .open(path)
+}
+
+/// Open nohup.out in the current directory, or in $HOME if that fails.
+fn get_nohup_out_file() -> io::Result<(File, PathBuf)> {
+    let path = PathBuf::from("nohup.out");
+    match open_nohup_out(&path) {
+        Ok(file) => Ok((file, path)),
+        Err(e) => {
+            let Some(home) = env::var_os("HOME") else {
+                return Err(e);
+            };
+            let path = PathBuf::from(home).join("nohup.out");
+            let file = open_nohup_out(&path)?;
+            Ok((file, path))
         }
     }
 }
@@ -0,0 +1,259 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+extern crate clap;
+extern crate libc;
+extern crate plib;
+
+mod ps_util;
+
+use clap::Parser;
+use gettextrs::{bind_textdomain_codeset, gettext, textdomain};
+use plib::PROJECT_NAME;
+use ps_util::format::{self, Column, Field, Formatter};
+use ps_util::Process;
+use std::ffi::CString;
+use std::os::unix::fs::MetadataExt;
+
+/// ps - report process status
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about)]
+struct Args {
+    /// Write information for all processes.
+    #[arg(short = 'A')]
+    all: bool,
+
+    /// Write information for all processes; equivalent to -A.
+    #[arg(short = 'e')]
+    every: bool,
+
+    /// Write information for all processes associated with terminals, except session leaders.
+    #[arg(short = 'a')]
+    terminals: bool,
+
+    /// Write information for all processes, except session leaders.
+    #[arg(short = 'd')]
+    no_leaders: bool,
+
+    /// Generate a full listing.
+    #[arg(short = 'f')]
+    full: bool,
+
+    /// Write information according to the format specification: field names separated by commas or blanks, each optionally followed by =header.
+    #[arg(short = 'o')]
+    format: Vec<String>,
+
+    /// Write information for the processes whose process ID numbers are given in the list.
+    #[arg(short = 'p')]
+    pids: Vec<String>,
+
+    /// Write information for the processes associated with the terminals given in the list.
+    #[arg(short = 't')]
+    ttys: Vec<String>,
+
+    /// Write information for the processes whose effective user ID numbers or login names are given in the list.
+    #[arg(short = 'u')]
+    users: Vec<String>,
+
+    /// Write information for the processes whose real user ID numbers or login names are given in the list.
+    #[arg(short = 'U')]
+    real_users: Vec<String>,
+
+    /// Write information for the processes whose real group ID numbers or group names are given in the list.
+    #[arg(short = 'G')]
+    real_groups: Vec<String>,
+}
+
+// the items of lists separated by commas or blanks
+fn list_items(lists: &[String]) -> impl Iterator<Item = &str> {
+    lists
+        .iter()
+        .flat_map(|list| list.split(|c: char| c == ',' || c.is_ascii_whitespace()))
+        .filter(|item| !item.is_empty())
+}
+
+fn lookup_uid(name: &str) -> Result<u32, String> {
+    if let Ok(uid) = name.parse() {
+        return Ok(uid);
+    }
+    let c_name = CString::new(name).map_err(|e| e.to_string())?;
+    let passwd = unsafe { libc::getpwnam(c_name.as_ptr()) };
+    if passwd.is_null() {
+        return Err(gettext!("{}: unknown user", name));
+    }
+    Ok(unsafe { (*passwd).pw_uid })
+}
+
+fn lookup_gid(name: &str) -> Result<u32, String> {
+    if let Ok(gid) = name.parse() {
+        return Ok(gid);
+    }
+    let c_name = CString::new(name).map_err(|e| e.to_string())?;
+    let group = unsafe { libc::getgrnam(c_name.as_ptr()) };
+    if group.is_null() {
+        return Err(gettext!("{}: unknown group", name));
+    }
+    Ok(unsafe { (*group).gr_gid })
+}
+
+// terminals are named as by tty(1), with or without the /dev/ prefix
+fn lookup_tty(name: &str) -> Result<u64, String> {
+    let path = if name.starts_with('/') {
+        name.to_string()
+    } else {
+        format!("/dev/{}", name)
+    };
+    match std::fs::metadata(path) {
+        Ok(md) => Ok(md.rdev()),
+        Err(_) => Err(gettext!("{}: unknown terminal", name)),
+    }
+}
+
+/// The processes selected by the options.
+struct Selection {
+    all: bool,
+    terminals: bool,
+    no_leaders: bool,
+    pids: Vec<i32>,
+    ttys: Vec<u64>,
+    users: Vec<u32>,
+    real_users: Vec<u32>,
+    real_groups: Vec<u32>,
+}
+
+impl Selection {
+    fn new(args: &Args) -> Result<Selection, String> {
+        let pids = list_items(&args.pids)
+            .map(|s| s.parse().map_err(|_| gettext!("{}: invalid process ID", s)))
+            .collect::<Result<_, _>>()?;
+        Ok(Selection {
+            all: args.all || args.every,
+            terminals: args.terminals,
+            no_leaders: args.no_leaders,
+            pids,
+            ttys: list_items(&args.ttys)
+                .map(lookup_tty)
+                .collect::<Result<_, _>>()?,
+            users: list_items(&args.users)
+                .map(lookup_uid)
+                .collect::<Result<_, _>>()?,
+            real_users: list_items(&args.real_users)
+                .map(lookup_uid)
+                .collect::<Result<_, _>>()?,
+            real_groups: list_items(&args.real_groups)
+                .map(lookup_gid)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn is_default(&self) -> bool {
+        !self.all
+            && !self.terminals
+            && !self.no_leaders
+            && self.pids.is_empty()
+            && self.ttys.is_empty()
+            && self.users.is_empty()
+            && self.real_users.is_empty()
+            && self.real_groups.is_empty()
+    }
+
+    /// Whether `p` is selected by any of the options.  Without options,
+    /// the processes with the effective user ID and controlling terminal
+    /// of the invoker are selected.
+    fn selects(&self, p: &Process, invoker: Option<&Process>) -> bool {
+        if self.is_default() {
+            let euid = unsafe { libc::geteuid() };
+            let tty = invoker.and_then(|me| me.tty);
+            return p.euid == euid && p.tty == tty;
+        }
+
+        let leader = p.pid == p.sid;
+        self.all
+            || (self.terminals && p.tty.is_some() && !leader)
+            || (self.no_leaders && !leader)
+            || self.pids.contains(&p.pid)
+            || p.tty.is_some_and(|tty| self.ttys.contains(&tty))
+            || self.users.contains(&p.euid)
+            || self.real_users.contains(&p.ruid)
+            || self.real_groups.contains(&p.rgid)
+    }
+}
+
+fn output_columns(args: &Args) -> Result<Vec<Column>, String> {
+    if !args.format.is_empty() {
+        let mut columns = Vec::new();
+        for spec in &args.format {
+            columns.extend(format::parse_format(spec)?);
+        }
+        return Ok(columns);
+    }
+
+    let columns = if args.full {
+        vec![
+            Column::new(Field::User, "UID"),
+            Column::new(Field::Pid, "PID"),
+            Column::new(Field::Ppid, "PPID"),
+            Column::new(Field::C, "C"),
+            Column::new(Field::Stime, "STIME"),
+            Column::new(Field::Tty, "TTY"),
+            Column::new(Field::Time, "TIME"),
+            Column::new(Field::Args, "CMD"),
+        ]
+    } else {
+        vec![
+            Column::new(Field::Pid, "PID"),
+            Column::new(Field::Tty, "TTY"),
+            Column::new(Field::Time, "TIME"),
+            Column::new(Field::Comm, "CMD"),
+        ]
+    };
+    Ok(columns)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    textdomain(PROJECT_NAME)?;
+    bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
+
+    let (columns, selection) = match output_columns(&args).and_then(|columns| {
+        let selection = Selection::new(&args)?;
+        Ok((columns, selection))
+    }) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("ps: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let source = ps_util::open_source()?;
+    let processes = source.processes()?;
+    let invoker = processes
+        .iter()
+        .find(|p| p.pid == std::process::id() as i32);
+
+    let formatter = Formatter::new(source.as_ref());
+    let rows: Vec<Vec<String>> = processes
+        .iter()
+        .filter(|p| selection.selects(p, invoker))
+        .map(|p| {
+            columns
+                .iter()
+                .map(|col| formatter.value(col.field, p))
+                .collect()
+        })
+        .collect();
+
+    for line in format::layout(&columns, &rows) {
+        println!("{}", line);
+    }
+
+    Ok(())
+}
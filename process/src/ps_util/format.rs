@@ -0,0 +1,386 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use super::{Process, ProcessSource};
+use chrono::{DateTime, Datelike, Local};
+use std::ffi::CStr;
+use std::time::{Duration, SystemTime};
+
+/// A field of the output, as named in -o format specifications.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Field {
+    Ruser,
+    User,
+    Rgroup,
+    Group,
+    Pid,
+    Ppid,
+    Pgid,
+    Pcpu,
+    Vsz,
+    Nice,
+    Etime,
+    Time,
+    Tty,
+    Comm,
+    Args,
+
+    // fields of the -f listing, beyond those of POSIX
+    C,
+    Stime,
+}
+
+impl Field {
+    pub fn parse(name: &str) -> Option<Field> {
+        let field = match name {
+            "ruser" => Field::Ruser,
+            "user" => Field::User,
+            "rgroup" => Field::Rgroup,
+            "group" => Field::Group,
+            "pid" => Field::Pid,
+            "ppid" => Field::Ppid,
+            "pgid" => Field::Pgid,
+            "pcpu" => Field::Pcpu,
+            "vsz" => Field::Vsz,
+            "nice" => Field::Nice,
+            "etime" => Field::Etime,
+            "time" => Field::Time,
+            "tty" => Field::Tty,
+            "comm" => Field::Comm,
+            "args" => Field::Args,
+            "c" => Field::C,
+            "stime" => Field::Stime,
+            _ => return None,
+        };
+        Some(field)
+    }
+
+    pub fn default_header(self) -> &'static str {
+        match self {
+            Field::Ruser => "RUSER",
+            Field::User => "USER",
+            Field::Rgroup => "RGROUP",
+            Field::Group => "GROUP",
+            Field::Pid => "PID",
+            Field::Ppid => "PPID",
+            Field::Pgid => "PGID",
+            Field::Pcpu => "%CPU",
+            Field::Vsz => "VSZ",
+            Field::Nice => "NI",
+            Field::Etime => "ELAPSED",
+            Field::Time => "TIME",
+            Field::Tty => "TT",
+            Field::Comm => "COMMAND",
+            Field::Args => "COMMAND",
+            Field::C => "C",
+            Field::Stime => "STIME",
+        }
+    }
+
+    // numeric fields are aligned to the right
+    fn right_aligned(self) -> bool {
+        matches!(
+            self,
+            Field::Pid
+                | Field::Ppid
+                | Field::Pgid
+                | Field::Pcpu
+                | Field::Vsz
+                | Field::Nice
+                | Field::Etime
+                | Field::Time
+                | Field::C
+        )
+    }
+}
+
+/// An output column: a field and its header text.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Column {
+    pub field: Field,
+    pub header: String,
+}
+
+impl Column {
+    pub fn new(field: Field, header: &str) -> Column {
+        Column {
+            field,
+            header: header.to_string(),
+        }
+    }
+}
+
+/// Parse the option-argument of -o: field names separated by commas or
+/// blanks.  A name may be followed by `=header`, in which case the header
+/// text is the rest of the option-argument.
+pub fn parse_format(spec: &str) -> Result<Vec<Column>, String> {
+    let is_sep = |c: char| c == ',' || c.is_ascii_whitespace();
+    let mut columns = Vec::new();
+    let mut rest = spec;
+
+    loop {
+        rest = rest.trim_start_matches(is_sep);
+        if rest.is_empty() {
+            break;
+        }
+
+        let end = rest.find(|c| is_sep(c) || c == '=').unwrap_or(rest.len());
+        let name = &rest[..end];
+        let field =
+            Field::parse(name).ok_or_else(|| format!("unknown format specifier: {}", name))?;
+
+        if let Some(header) = rest[end..].strip_prefix('=') {
+            columns.push(Column::new(field, header));
+            break;
+        }
+        columns.push(Column::new(field, field.default_header()));
+        rest = &rest[end..];
+    }
+
+    if columns.is_empty() {
+        return Err(format!("empty format specification: '{}'", spec));
+    }
+    Ok(columns)
+}
+
+/// Format `secs` as [dd-]hh:mm:ss, the format of the time field.
+pub fn format_time(secs: u64) -> String {
+    let (days, hours, mins, secs) = split_secs(secs);
+    if days > 0 {
+        format!("{}-{:02}:{:02}:{:02}", days, hours, mins, secs)
+    } else {
+        format!("{:02}:{:02}:{:02}", hours, mins, secs)
+    }
+}
+
+/// Format `secs` as [[dd-]hh:]mm:ss, the format of the etime field.
+pub fn format_etime(secs: u64) -> String {
+    let (days, hours, mins, secs) = split_secs(secs);
+    if days > 0 {
+        format!("{}-{:02}:{:02}:{:02}", days, hours, mins, secs)
+    } else if hours > 0 {
+        format!("{:02}:{:02}:{:02}", hours, mins, secs)
+    } else {
+        format!("{:02}:{:02}", mins, secs)
+    }
+}
+
+fn split_secs(secs: u64) -> (u64, u64, u64, u64) {
+    (secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60)
+}
+
+fn user_name(uid: u32) -> String {
+    unsafe {
+        let passwd = libc::getpwuid(uid);
+        if passwd.is_null() {
+            return uid.to_string();
+        }
+        CStr::from_ptr((*passwd).pw_name)
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+fn group_name(gid: u32) -> String {
+    unsafe {
+        let group = libc::getgrgid(gid);
+        if group.is_null() {
+            return gid.to_string();
+        }
+        CStr::from_ptr((*group).gr_name)
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+/// Renders the fields of processes as text.
+pub struct Formatter<'a> {
+    source: &'a dyn ProcessSource,
+    now: SystemTime,
+}
+
+impl<'a> Formatter<'a> {
+    pub fn new(source: &'a dyn ProcessSource) -> Formatter<'a> {
+        Formatter {
+            source,
+            now: SystemTime::now(),
+        }
+    }
+
+    fn elapsed(&self, process: &Process) -> Duration {
+        self.now
+            .duration_since(process.start_time)
+            .unwrap_or_default()
+    }
+
+    // percentage of the elapsed time spent running
+    fn cpu_percent(&self, process: &Process) -> f64 {
+        let elapsed = self.elapsed(process).as_secs_f64();
+        if elapsed <= 0.0 {
+            return 0.0;
+        }
+        100.0 * process.cpu_time.as_secs_f64() / elapsed
+    }
+
+    // the start time, as the time of day for processes started today
+    fn start_time(&self, process: &Process) -> String {
+        let start: DateTime<Local> = process.start_time.into();
+        let now: DateTime<Local> = self.now.into();
+        if start.date_naive() == now.date_naive() {
+            start.format("%H:%M").to_string()
+        } else if start.year() == now.year() {
+            start.format("%b%d").to_string()
+        } else {
+            start.format("%Y").to_string()
+        }
+    }
+
+    pub fn value(&self, field: Field, process: &Process) -> String {
+        match field {
+            Field::Ruser => user_name(process.ruid),
+            Field::User => user_name(process.euid),
+            Field::Rgroup => group_name(process.rgid),
+            Field::Group => group_name(process.egid),
+            Field::Pid => process.pid.to_string(),
+            Field::Ppid => process.ppid.to_string(),
+            Field::Pgid => process.pgid.to_string(),
+            Field::Pcpu => format!("{:.1}", self.cpu_percent(process)),
+            Field::Vsz => process.vsz.to_string(),
+            Field::Nice => process.nice.to_string(),
+            Field::Etime => format_etime(self.elapsed(process).as_secs()),
+            Field::Time => format_time(process.cpu_time.as_secs()),
+            Field::Tty => match process.tty {
+                Some(dev) => self
+                    .source
+                    .terminal_name(dev)
+                    .unwrap_or_else(|| String::from("?")),
+                None => String::from("?"),
+            },
+            Field::Comm => process.comm.clone(),
+            Field::Args => {
+                if process.args.is_empty() {
+                    format!("[{}]", process.comm)
+                } else {
+                    process.args.join(" ")
+                }
+            }
+            Field::C => (self.cpu_percent(process) as u64).to_string(),
+            Field::Stime => self.start_time(process),
+        }
+    }
+}
+
+/// Lay out `rows` in columns.  Each column is as wide as its widest value
+/// and at least as wide as its header; an empty header still reserves the
+/// width of the default one.  The header line is left out when all
+/// headers are empty.
+pub fn layout(columns: &[Column], rows: &[Vec<String>]) -> Vec<String> {
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, col)| {
+            let header_width = if col.header.is_empty() {
+                col.field.default_header().chars().count()
+            } else {
+                col.header.chars().count()
+            };
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .fold(header_width, usize::max)
+        })
+        .collect();
+
+    let line = |cells: &[String]| {
+        let mut line = String::new();
+        for (i, (cell, col)) in cells.iter().zip(columns).enumerate() {
+            if i > 0 {
+                line.push(' ');
+            }
+            if col.field.right_aligned() {
+                line.push_str(&format!("{:>1$}", cell, widths[i]));
+            } else {
+                line.push_str(&format!("{:<1$}", cell, widths[i]));
+            }
+        }
+        line.trim_end().to_string()
+    };
+
+    let mut lines = Vec::new();
+    if columns.iter().any(|col| !col.header.is_empty()) {
+        let headers: Vec<String> = columns.iter().map(|col| col.header.clone()).collect();
+        lines.push(line(&headers));
+    }
+    lines.extend(rows.iter().map(|row| line(row)));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(
+            parse_format("pid,ppid comm").unwrap(),
+            [
+                Column::new(Field::Pid, "PID"),
+                Column::new(Field::Ppid, "PPID"),
+                Column::new(Field::Comm, "COMMAND"),
+            ]
+        );
+
+        // the header extends to the end of the option-argument
+        assert_eq!(
+            parse_format("user,pid=Process, ID").unwrap(),
+            [
+                Column::new(Field::User, "USER"),
+                Column::new(Field::Pid, "Process, ID")
+            ]
+        );
+        assert_eq!(
+            parse_format("user=,pid").unwrap(),
+            [Column::new(Field::User, ",pid")]
+        );
+        assert_eq!(parse_format("pid=").unwrap(), [Column::new(Field::Pid, "")]);
+
+        assert!(parse_format("pid,bogus").is_err());
+        assert!(parse_format(" , ").is_err());
+    }
+
+    #[test]
+    fn test_time_formats() {
+        assert_eq!(format_time(0), "00:00:00");
+        assert_eq!(format_time(3725), "01:02:05");
+        assert_eq!(format_time(2 * 86400 + 61), "2-00:01:01");
+
+        assert_eq!(format_etime(65), "01:05");
+        assert_eq!(format_etime(3725), "01:02:05");
+        assert_eq!(format_etime(86400), "1-00:00:00");
+    }
+
+    #[test]
+    fn test_layout() {
+        let columns = [
+            Column::new(Field::Pid, "PID"),
+            Column::new(Field::Comm, "CMD"),
+        ];
+        let rows = vec![
+            vec![String::from("1"), String::from("init")],
+            vec![String::from("12345"), String::from("sh")],
+        ];
+        assert_eq!(
+            layout(&columns, &rows),
+            ["  PID CMD", "    1 init", "12345 sh"]
+        );
+
+        // empty headers keep the default header width
+        let columns = [Column::new(Field::Pid, ""), Column::new(Field::Comm, "")];
+        assert_eq!(layout(&columns, &rows[..1]), ["  1 init"]);
+    }
+}
@@ -0,0 +1,75 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+pub mod format;
+#[cfg(target_os = "linux")]
+mod procfs;
+
+use std::io;
+use std::time::{Duration, SystemTime};
+
+/// The information ps reports about one process.
+#[derive(Clone, Debug)]
+pub struct Process {
+    pub pid: i32,
+    pub ppid: i32,
+    pub pgid: i32,
+    pub sid: i32,
+    pub ruid: u32,
+    pub euid: u32,
+    pub rgid: u32,
+    pub egid: u32,
+
+    /// Device number of the controlling terminal, if any.
+    pub tty: Option<u64>,
+
+    /// Name of the command, as the system records it.
+    pub comm: String,
+
+    /// Command line arguments; empty for processes such as kernel
+    /// threads, which have none.
+    pub args: Vec<String>,
+
+    pub nice: i32,
+
+    /// Size of the virtual address space, in kilobytes.
+    pub vsz: u64,
+
+    pub start_time: SystemTime,
+
+    /// User and system CPU time consumed.
+    pub cpu_time: Duration,
+}
+
+/// A source of process information.  Each platform supplies its own.
+pub trait ProcessSource {
+    /// All processes on the system.  Processes that exit while they are
+    /// being read are left out.
+    fn processes(&self) -> io::Result<Vec<Process>>;
+
+    /// The name of the terminal with device number `dev`, relative to
+    /// /dev.
+    fn terminal_name(&self, dev: u64) -> Option<String>;
+}
+
+/// The process information source of the running platform.
+pub fn open_source() -> io::Result<Box<dyn ProcessSource>> {
+    #[cfg(target_os = "linux")]
+    {
+        Ok(Box::new(procfs::ProcFs::new()?))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "process information is not available on this platform",
+        ))
+    }
+}
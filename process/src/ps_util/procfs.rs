@@ -0,0 +1,170 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use super::{Process, ProcessSource};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// Process information read from the Linux /proc filesystem.
+pub struct ProcFs {
+    boot_time: SystemTime,
+    clock_ticks: u64,
+    terminals: HashMap<u64, String>,
+}
+
+impl ProcFs {
+    pub fn new() -> io::Result<ProcFs> {
+        let stat = fs::read_to_string("/proc/stat")?;
+        let btime = stat
+            .lines()
+            .find_map(|line| line.strip_prefix("btime "))
+            .and_then(|s| s.trim().parse::<u64>().ok())
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "/proc/stat: no boot time")
+            })?;
+
+        let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+
+        let mut terminals = HashMap::new();
+        add_terminals(&mut terminals, Path::new("/dev"), "", |name| {
+            name.starts_with("tty") || name == "console"
+        });
+        add_terminals(&mut terminals, Path::new("/dev/pts"), "pts/", |_| true);
+
+        Ok(ProcFs {
+            boot_time: SystemTime::UNIX_EPOCH + Duration::from_secs(btime),
+            clock_ticks: if ticks > 0 { ticks as u64 } else { 100 },
+            terminals,
+        })
+    }
+
+    fn ticks(&self, n: u64) -> Duration {
+        Duration::from_secs(n / self.clock_ticks)
+            + Duration::from_nanos(n % self.clock_ticks * 1_000_000_000 / self.clock_ticks)
+    }
+
+    fn read_process(&self, dir: &Path) -> io::Result<Process> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed stat file");
+
+        // the command name is in parentheses and may itself contain them
+        let stat = fs::read_to_string(dir.join("stat"))?;
+        let open = stat.find('(').ok_or_else(invalid)?;
+        let close = stat.rfind(')').ok_or_else(invalid)?;
+        let pid = stat[..open].trim().parse().map_err(|_| invalid())?;
+        let comm = stat[open + 1..close].to_string();
+        let fields: Vec<&str> = stat[close + 1..].split_whitespace().collect();
+        if fields.len() < 21 {
+            return Err(invalid());
+        }
+        let num = |i: usize| fields[i].parse::<i64>().map_err(|_| invalid());
+
+        let tty_nr = num(4)? as u64;
+        let tty = if tty_nr == 0 {
+            None
+        } else {
+            let major = (tty_nr >> 8) & 0xfff;
+            let minor = (tty_nr & 0xff) | ((tty_nr >> 12) & 0xfff00);
+            Some(libc::makedev(major as u32, minor as u32))
+        };
+
+        let mut ids = ([0u32; 2], [0u32; 2]);
+        let status = fs::read_to_string(dir.join("status"))?;
+        for line in status.lines() {
+            let target = if let Some(rest) = line.strip_prefix("Uid:") {
+                (&mut ids.0, rest)
+            } else if let Some(rest) = line.strip_prefix("Gid:") {
+                (&mut ids.1, rest)
+            } else {
+                continue;
+            };
+            for (id, s) in target.0.iter_mut().zip(target.1.split_whitespace()) {
+                *id = s.parse().map_err(|_| invalid())?;
+            }
+        }
+
+        let cmdline = fs::read(dir.join("cmdline")).unwrap_or_default();
+        let args = cmdline
+            .split(|b| *b == 0)
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| String::from_utf8_lossy(arg).into_owned())
+            .collect();
+
+        Ok(Process {
+            pid,
+            ppid: num(1)? as i32,
+            pgid: num(2)? as i32,
+            sid: num(3)? as i32,
+            ruid: ids.0[0],
+            euid: ids.0[1],
+            rgid: ids.1[0],
+            egid: ids.1[1],
+            tty,
+            comm,
+            args,
+            nice: num(16)? as i32,
+            vsz: num(20)? as u64 / 1024,
+            start_time: self.boot_time + self.ticks(num(19)? as u64),
+            cpu_time: self.ticks((num(11)? + num(12)?) as u64),
+        })
+    }
+}
+
+// record the character devices in `dir` whose names satisfy `wanted`
+fn add_terminals(
+    terminals: &mut HashMap<u64, String>,
+    dir: &Path,
+    prefix: &str,
+    wanted: impl Fn(&str) -> bool,
+) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !wanted(&name) {
+            continue;
+        }
+        if let Ok(md) = entry.metadata() {
+            if md.file_type().is_char_device() {
+                terminals
+                    .entry(md.rdev())
+                    .or_insert_with(|| format!("{}{}", prefix, name));
+            }
+        }
+    }
+}
+
+impl ProcessSource for ProcFs {
+    fn processes(&self) -> io::Result<Vec<Process>> {
+        let mut processes = Vec::new();
+        for entry in fs::read_dir("/proc")? {
+            let entry = entry?;
+            let is_pid = entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.bytes().all(|b| b.is_ascii_digit()));
+            if !is_pid {
+                continue;
+            }
+            if let Ok(process) = self.read_process(&entry.path()) {
+                processes.push(process);
+            }
+        }
+        processes.sort_by_key(|p| p.pid);
+        Ok(processes)
+    }
+
+    fn terminal_name(&self, dev: u64) -> Option<String> {
+        self.terminals.get(&dev).cloned()
+    }
+}
@@ -6,57 +6,62 @@
 // file in the root directory of this project.
 // SPDX-License-Identifier: MIT
 //
-// TODO:
-// - the -0 argument used in conjunction with find -print0
-// - trace mode (-t)
-// - prompt mode (-p)
-// - insert mode (-I)
-// - split by lines (-L)
-// - exit feature (-x)
-// - write tests
-//
 
 extern crate clap;
+extern crate libc;
 extern crate plib;
 
 use clap::Parser;
-use gettextrs::{bind_textdomain_codeset, textdomain};
+use gettextrs::{bind_textdomain_codeset, gettext, textdomain};
 use plib::PROJECT_NAME;
-use std::io::{self, Read};
-use std::process::{Command, Stdio};
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Bytes, Write};
+use std::iter::Peekable;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Child, Command, ExitStatus, Stdio};
+
+// headroom left below ARG_MAX, as POSIX requires
+const ARG_MAX_HEADROOM: usize = 2048;
+
+// the least value of ARG_MAX that POSIX permits
+const POSIX_ARG_MAX: usize = 4096;
 
-const ARG_MAX: i32 = 131072; // arbitrary.  todo: discover actual value
-const MAX_ARGS_BYTES: usize = ARG_MAX as usize - 2048;
+// at most this many initial arguments are subject to replacement (-I)
+const MAX_REPLACE_ARGS: usize = 5;
 
 /// xargs - construct argument lists and invoke utility
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about)]
 struct Args {
     /// The utility shall be executed for each non-empty number lines of arguments from standard input.
-    #[arg(short = 'L', long)]
-    lines: Option<usize>,
+    #[arg(short = 'L', long, value_parser = clap::value_parser!(u64).range(1..),
+          overrides_with_all = ["maxnum", "replstr"])]
+    lines: Option<u64>,
 
     /// Invoke utility using as many standard input arguments as possible, up to number
-    #[arg(short = 'n', long)]
-    maxnum: Option<usize>,
+    #[arg(short = 'n', long, value_parser = clap::value_parser!(u64).range(1..),
+          overrides_with_all = ["lines", "replstr"])]
+    maxnum: Option<u64>,
 
     /// Invoke utility using as many standard input arguments as possible yielding a command line length less than size
-    #[arg(short = 's', long)]
-    maxsize: Option<usize>,
+    #[arg(short = 's', long, value_parser = clap::value_parser!(u64).range(1..))]
+    maxsize: Option<u64>,
 
     /// Use eofstr as the logical end-of-file string.
     #[arg(short = 'E', long, default_value = "")]
     eofstr: String,
 
-    /// Insert mode.
-    #[arg(short = 'I', long)]
+    /// Insert mode: utility is executed for each line from standard input, with each occurrence of replstr in the arguments replaced by the line.
+    #[arg(short = 'I', long, overrides_with_all = ["lines", "maxnum"])]
     replstr: Option<String>,
 
-    /// prompt mode
+    /// Prompt mode: ask on standard error whether to execute each command line, reading the answer from /dev/tty.
     #[arg(short, long)]
     prompt: bool,
 
-    /// trace mode
+    /// Trace mode: write each command line to standard error before executing it.
     #[arg(short, long)]
     trace: bool,
 
@@ -64,209 +69,466 @@ struct Args {
     #[arg(short = 'x', long)]
     exit: bool,
 
-    /// utility to invoke
-    util: String,
+    /// Input arguments are terminated by null bytes, and quotes and backslashes are not special.
+    #[arg(short = '0', long)]
+    null: bool,
 
-    /// utility arguments
-    util_args: Vec<String>,
+    /// Run up to maxprocs invocations of the utility at once; 0 runs as many as possible.
+    #[arg(short = 'P', long, default_value_t = 1)]
+    maxprocs: u64,
+
+    /// The utility to invoke (echo by default) and its initial arguments
+    #[arg(trailing_var_arg = true)]
+    utility: Vec<String>,
 }
 
-// find a string in a vector of strings
-fn find_str(needle: &str, haystack: &[String]) -> Option<usize> {
-    haystack.iter().position(|s| s == needle)
+/// An argument read from standard input.
+struct Token {
+    arg: Vec<u8>,
+
+    /// Whether quotes were used in the argument.
+    quoted: bool,
+
+    /// Whether the argument ends a line of input, which it does unless
+    /// the line ends with a blank.
+    ends_line: bool,
 }
 
-// execute the utility
-fn exec_util(util: &str, util_args: Vec<String>) -> io::Result<()> {
-    let _output = Command::new(util)
-        .args(util_args)
-        .stdin(Stdio::null())
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .output()?;
+/// Splits standard input into arguments, following the quoting rules of
+/// xargs: single and double quotes enclose strings that may not span
+/// lines, and a backslash escapes the next character.
+struct Tokenizer<R: BufRead> {
+    input: Peekable<Bytes<R>>,
 
-    Ok(())
+    /// In insert mode, each line is a single argument.
+    whole_lines: bool,
+
+    /// Arguments are terminated by null bytes instead (-0).
+    null_delimited: bool,
 }
 
-struct ParseState {
-    // cmdline-related state
-    util_size: usize,
-    util_n_args: usize,
-
-    // input state
-    tmp_arg: String,
-    in_arg: bool,
-    in_quote: bool,
-    in_escape: bool,
-    quote_char: char,
-    skip_remainder: bool,
-
-    // output state
-    max_bytes: usize,
-    max_args: Option<usize>,
-
-    // parsed args, ready for exec
-    args: Vec<String>,
+fn is_blank(b: u8) -> bool {
+    b == b' ' || b == b'\t'
 }
 
-impl ParseState {
-    fn new(args: &Args) -> ParseState {
-        let mut total = args.util.len();
-        for arg in &args.util_args {
-            total += arg.len();
-        }
+impl Token {
+    // an unquoted argument equal to eofstr ends the input
+    fn is_eof(&self, eofstr: &str) -> bool {
+        !eofstr.is_empty() && !self.quoted && self.arg == eofstr.as_bytes()
+    }
+}
 
-        ParseState {
-            util_size: total,
-            util_n_args: args.util_args.len(),
-            tmp_arg: String::new(),
-            in_arg: false,
-            in_quote: false,
-            in_escape: false,
-            quote_char: '"',
-            skip_remainder: false,
-            max_bytes: args.maxsize.unwrap_or(MAX_ARGS_BYTES),
-            max_args: args.maxnum,
-            args: Vec::new(),
+impl<R: BufRead> Tokenizer<R> {
+    fn new(input: R, whole_lines: bool, null_delimited: bool) -> Tokenizer<R> {
+        Tokenizer {
+            input: input.bytes().peekable(),
+            whole_lines,
+            null_delimited,
         }
     }
 
-    fn full(&self) -> bool {
-        let mut total = 0;
-        for arg in &self.args {
-            total += arg.len();
-        }
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        self.input.next().transpose()
+    }
 
-        if (self.util_size + total) >= self.max_bytes {
-            true
-        } else if let Some(max_args) = self.max_args {
-            (self.util_n_args + self.args.len()) >= max_args
-        } else {
-            false
+    // an argument terminated by a null byte, taken literally
+    fn next_null_delimited(&mut self) -> io::Result<Option<Token>> {
+        let mut arg = Vec::new();
+        loop {
+            match self.next_byte()? {
+                Some(0) => break,
+                Some(b) => arg.push(b),
+                None if arg.is_empty() => return Ok(None),
+                None => break,
+            }
         }
+        Ok(Some(Token {
+            arg,
+            quoted: false,
+            ends_line: true,
+        }))
     }
 
-    fn remove_args(&mut self) -> Vec<String> {
-        let mut total = self.util_size;
-        let mut ret = Vec::new();
-        while !self.args.is_empty() {
-            // stop if adding the next arg would exceed the max size
-            if (total + self.args[0].len()) > self.max_bytes {
+    fn next_arg(&mut self) -> io::Result<Option<Token>> {
+        if self.null_delimited {
+            return self.next_null_delimited();
+        }
+
+        // leading blanks and empty lines are ignored
+        while let Some(Ok(b)) = self.input.peek() {
+            if !is_blank(*b) && *b != b'\n' {
                 break;
             }
+            self.input.next();
+        }
 
-            // add the next arg
-            let arg = self.args.remove(0);
-            total += arg.len();
-            ret.push(arg);
-
-            // stop if we have reached the max number of args
-            if let Some(max_args) = self.max_args {
-                if (ret.len() + self.util_n_args) == max_args {
+        let mut token = Token {
+            arg: Vec::new(),
+            quoted: false,
+            ends_line: true,
+        };
+        let Some(mut b) = self.next_byte()? else {
+            return Ok(None);
+        };
+
+        loop {
+            match b {
+                b'\n' => break,
+                b if is_blank(b) && !self.whole_lines => {
+                    token.ends_line = false;
                     break;
                 }
+                b'\\' => match self.next_byte()? {
+                    Some(escaped) => token.arg.push(escaped),
+                    None => break,
+                },
+                b'\'' | b'"' => {
+                    token.quoted = true;
+                    loop {
+                        match self.next_byte()? {
+                            Some(c) if c == b => break,
+                            Some(b'\n') | None => {
+                                let msg = if b == b'"' {
+                                    gettext("unmatched double quote")
+                                } else {
+                                    gettext("unmatched single quote")
+                                };
+                                return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+                            }
+                            Some(c) => token.arg.push(c),
+                        }
+                    }
+                }
+                b => token.arg.push(b),
+            }
+
+            match self.next_byte()? {
+                Some(next) => b = next,
+                None => break,
             }
         }
 
-        ret
+        Ok(Some(token))
     }
+}
 
-    fn parse_buf(&mut self, buf: &[u8]) -> io::Result<()> {
-        if self.skip_remainder {
-            return Ok(());
-        }
+/// The largest command line that may be constructed: {ARG_MAX} less the
+/// headroom and the size of the environment.
+fn max_command_size() -> usize {
+    let arg_max = unsafe { libc::sysconf(libc::_SC_ARG_MAX) };
+    let arg_max = if arg_max > 0 {
+        arg_max as usize
+    } else {
+        POSIX_ARG_MAX
+    };
+    let env_size: usize = std::env::vars_os()
+        .map(|(key, value)| key.len() + value.len() + 2 + std::mem::size_of::<usize>())
+        .sum();
+    arg_max
+        .saturating_sub(ARG_MAX_HEADROOM)
+        .saturating_sub(env_size)
+}
 
-        for c8 in buf {
-            let ch = *c8 as char;
+// the size an argument takes in a command line
+fn arg_size(arg: &[u8]) -> usize {
+    arg.len() + 1 + std::mem::size_of::<usize>()
+}
 
-            if self.in_quote {
-                if ch == self.quote_char {
-                    self.in_quote = false;
-                    self.in_arg = false;
-                    self.args.push(self.tmp_arg.clone());
-                    self.tmp_arg = String::new();
-                } else {
-                    self.tmp_arg.push(ch);
-                }
-            } else if self.in_escape {
-                self.in_escape = false;
-                self.tmp_arg.push(ch);
-            } else if self.in_arg && ch.is_whitespace() {
-                self.in_arg = false;
-                self.args.push(self.tmp_arg.clone());
-                self.tmp_arg = String::new();
-            } else if ch == '\'' || ch == '"' {
-                self.in_arg = true;
-                self.in_quote = true;
-                self.quote_char = ch;
-            } else if ch == '\\' {
-                self.in_escape = true;
-            } else if ch.is_whitespace() {
-                // ignore whitespace
+/// The outcome of starting the utility.
+#[derive(PartialEq)]
+enum Status {
+    Continue,
+
+    /// xargs must stop invoking the utility.
+    Stop,
+}
+
+/// Runs the utility on the constructed command lines, up to -P of them
+/// at once, and records the exit status of xargs.
+struct Executor {
+    trace: bool,
+    prompt: Option<io::BufReader<File>>,
+    max_procs: usize,
+
+    /// The running children and the utility names they run.
+    running: Vec<(Child, String)>,
+
+    /// Some invocation exited with a non-zero status.
+    failed: bool,
+
+    /// The exit status xargs stopped with; the first reason to stop wins.
+    stop: Option<i32>,
+}
+
+impl Executor {
+    fn new(args: &Args) -> io::Result<Executor> {
+        let prompt = if args.prompt {
+            let tty = File::open("/dev/tty")?;
+            Some(BufReader::new(tty))
+        } else {
+            None
+        };
+        Ok(Executor {
+            trace: args.trace,
+            prompt,
+            max_procs: if args.maxprocs == 0 {
+                usize::MAX
             } else {
-                self.in_arg = true;
-                self.tmp_arg.push(ch);
+                args.maxprocs as usize
+            },
+            running: Vec::new(),
+            failed: false,
+            stop: None,
+        })
+    }
+
+    // ask whether to run `line`: the prompt goes to standard error, and
+    // the answer is read from the terminal
+    fn confirm(&mut self, line: &str) -> io::Result<bool> {
+        let Some(tty) = &mut self.prompt else {
+            return Ok(true);
+        };
+        eprint!("{} ?...", line);
+        io::stderr().flush()?;
+        let mut response = String::new();
+        tty.read_line(&mut response)?;
+        Ok(response.trim_start().to_lowercase().starts_with('y'))
+    }
+
+    fn stop_with(&mut self, exit_code: i32) -> Status {
+        self.stop.get_or_insert(exit_code);
+        Status::Stop
+    }
+
+    /// Start the utility with `argv`, once a child slot is free.
+    fn run(&mut self, argv: &[Vec<u8>]) -> io::Result<Status> {
+        let line = argv
+            .iter()
+            .map(|arg| String::from_utf8_lossy(arg))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if self.prompt.is_some() {
+            if !self.confirm(&line)? {
+                return Ok(Status::Continue);
+            }
+        } else if self.trace {
+            eprintln!("{}", line);
+        }
+
+        let util = OsStr::from_bytes(&argv[0]);
+        let child = Command::new(util)
+            .args(argv[1..].iter().map(|arg| OsStr::from_bytes(arg)))
+            .stdin(Stdio::null())
+            .spawn();
+        match child {
+            Ok(child) => self
+                .running
+                .push((child, util.to_string_lossy().into_owned())),
+            Err(e) => {
+                eprintln!("xargs: {}: {}", util.to_string_lossy(), e);
+                let exit_code = if e.kind() == io::ErrorKind::NotFound {
+                    127
+                } else {
+                    126
+                };
+                return Ok(self.stop_with(exit_code));
             }
         }
 
-        Ok(())
+        // all slots are in use: wait for one to become free
+        while self.running.len() >= self.max_procs {
+            if self.wait_one()? == Status::Stop {
+                return Ok(Status::Stop);
+            }
+        }
+        Ok(Status::Continue)
+    }
+
+    /// Wait for any of the running children to exit.
+    fn wait_one(&mut self) -> io::Result<Status> {
+        let mut status = 0;
+        let pid = loop {
+            let pid = unsafe { libc::waitpid(-1, &mut status, 0) };
+            if pid > 0 {
+                break pid as u32;
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        };
+        let Some(pos) = self.running.iter().position(|(child, _)| child.id() == pid) else {
+            return Ok(Status::Continue);
+        };
+        let (_, util) = self.running.swap_remove(pos);
+
+        let status = ExitStatus::from_raw(status);
+        match status.code() {
+            Some(0) => Ok(Status::Continue),
+            Some(255) => {
+                eprintln!(
+                    "xargs: {}",
+                    gettext!("{}: exited with status 255; aborting", util)
+                );
+                Ok(self.stop_with(124))
+            }
+            Some(_) => {
+                self.failed = true;
+                Ok(Status::Continue)
+            }
+            None => {
+                eprintln!(
+                    "xargs: {}",
+                    gettext!(
+                        "{}: terminated by signal {}",
+                        util,
+                        status.signal().unwrap_or(0)
+                    )
+                );
+                Ok(self.stop_with(125))
+            }
+        }
     }
 
-    fn parse_finalize(&mut self) {
-        if self.in_arg {
-            self.in_arg = false;
-            self.args.push(self.tmp_arg.clone());
-            self.tmp_arg = String::new();
+    /// Wait for the children still running and return the exit status
+    /// of xargs.
+    fn finish(&mut self) -> io::Result<i32> {
+        while !self.running.is_empty() {
+            self.wait_one()?;
         }
+        Ok(match self.stop {
+            Some(exit_code) => exit_code,
+            None if self.failed => 123,
+            None => 0,
+        })
     }
+}
 
-    fn postprocess(&mut self, args: &Args) -> io::Result<()> {
-        if !args.eofstr.is_empty() {
-            if let Some(pos) = find_str(&args.eofstr, &self.args) {
-                self.args.truncate(pos);
-                self.skip_remainder = true;
+fn too_long() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        gettext("argument list too long"),
+    )
+}
+
+/// Insert mode: run the utility once per input line, with the line
+/// replacing `replstr` in the initial arguments.
+fn run_insert_mode(
+    args: &Args,
+    replstr: &str,
+    command: &[Vec<u8>],
+    max_size: usize,
+    executor: &mut Executor,
+) -> io::Result<()> {
+    let mut tokens = Tokenizer::new(BufReader::new(io::stdin().lock()), true, args.null);
+    let replstr = replstr.as_bytes();
+
+    while let Some(token) = tokens.next_arg()? {
+        if token.is_eof(&args.eofstr) {
+            break;
+        }
+
+        let mut argv = vec![command[0].clone()];
+        for (i, arg) in command[1..].iter().enumerate() {
+            if i < MAX_REPLACE_ARGS && !replstr.is_empty() {
+                argv.push(replace(arg, replstr, &token.arg));
+            } else {
+                argv.push(arg.clone());
             }
         }
 
-        Ok(())
+        // -x is implied
+        if argv.iter().map(|arg| arg_size(arg)).sum::<usize>() > max_size {
+            return Err(too_long());
+        }
+        if executor.run(&argv)? == Status::Stop {
+            return Ok(());
+        }
     }
+
+    Ok(())
 }
 
-fn read_and_spawn(args: &Args) -> io::Result<()> {
-    let mut state = ParseState::new(args);
+fn replace(arg: &[u8], from: &[u8], to: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < arg.len() {
+        if arg[i..].starts_with(from) {
+            out.extend_from_slice(to);
+            i += from.len();
+        } else {
+            out.push(arg[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
+/// Run the utility with as many arguments from standard input as the
+/// limits of -n, -L and -s allow.
+fn run_append_mode(
+    args: &Args,
+    command: &[Vec<u8>],
+    max_size: usize,
+    executor: &mut Executor,
+) -> io::Result<()> {
+    let mut tokens = Tokenizer::new(BufReader::new(io::stdin().lock()), false, args.null);
+    let base_size: usize = command.iter().map(|arg| arg_size(arg)).sum();
+    if base_size > max_size {
+        return Err(too_long());
+    }
 
-    let mut buffer = [0; plib::BUFSZ];
+    let mut argv = command.to_vec();
+    let mut size = base_size;
+    let mut lines = 0;
+    let mut ran = false;
 
-    // read stdin until EOF
-    loop {
-        // read a chunk of input
-        let n_read = io::stdin().read(&mut buffer)?;
-        if n_read == 0 {
+    while let Some(token) = tokens.next_arg()? {
+        if token.is_eof(&args.eofstr) {
             break;
         }
 
-        // parse the line
-        state.parse_buf(&buffer[..n_read])?;
+        // the argument does not fit: run what there is first
+        if size + arg_size(&token.arg) > max_size {
+            let n_args = argv.len() - command.len();
+            if n_args == 0 || (args.exit && args.maxnum.is_some_and(|n| (n_args as u64) < n)) {
+                return Err(too_long());
+            }
+            if executor.run(&argv)? == Status::Stop {
+                return Ok(());
+            }
+            ran = true;
+            argv.truncate(command.len());
+            size = base_size;
+            lines = 0;
+            if size + arg_size(&token.arg) > max_size {
+                return Err(too_long());
+            }
+        }
 
-        // handle eofstr and other details
-        state.postprocess(args)?;
+        size += arg_size(&token.arg);
+        argv.push(token.arg);
+        if token.ends_line {
+            lines += 1;
+        }
 
-        // if enough args, spawn the utility
-        if state.full() {
-            let mut util_args = args.util_args.clone();
-            util_args.append(&mut state.remove_args());
-            exec_util(&args.util, util_args)?;
+        let n_args = (argv.len() - command.len()) as u64;
+        let full =
+            args.maxnum.is_some_and(|n| n_args >= n) || args.lines.is_some_and(|n| lines >= n);
+        if full {
+            if executor.run(&argv)? == Status::Stop {
+                return Ok(());
+            }
+            ran = true;
+            argv.truncate(command.len());
+            size = base_size;
+            lines = 0;
         }
     }
 
-    // finalize parsing
-    state.parse_finalize();
-
-    // if there are any remaining args, spawn the utility
-    if !state.args.is_empty() {
-        let mut util_args = args.util_args.clone();
-        util_args.append(&mut state.remove_args());
-        exec_util(&args.util, util_args)?;
+    // the utility is run at least once
+    if argv.len() > command.len() || !ran {
+        executor.run(&argv)?;
     }
 
     Ok(())
@@ -279,7 +541,42 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     textdomain(PROJECT_NAME)?;
     bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
 
-    read_and_spawn(&args)?;
+    let mut command: Vec<Vec<u8>> = args
+        .utility
+        .iter()
+        .map(|arg| arg.as_bytes().to_vec())
+        .collect();
+    if command.is_empty() {
+        command.push(b"echo".to_vec());
+    }
 
-    Ok(())
+    // larger sizes than the system supports are reduced to its limit
+    let max_size = match args.maxsize {
+        Some(size) => (size as usize).min(max_command_size()),
+        None => max_command_size(),
+    };
+
+    let mut executor = match Executor::new(&args) {
+        Ok(executor) => executor,
+        Err(e) => {
+            eprintln!("xargs: /dev/tty: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let result = match &args.replstr {
+        Some(replstr) => run_insert_mode(&args, replstr, &command, max_size, &mut executor),
+        None => run_append_mode(&args, &command, max_size, &mut executor),
+    };
+
+    // children still running are waited for, even after an error
+    let finished = executor.finish();
+    let exit_code = match result.and(finished) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("xargs: {}", e);
+            executor.stop.unwrap_or(1)
+        }
+    };
+    std::process::exit(exit_code)
 }
@@ -0,0 +1,80 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use plib::{run_test, TestPlan};
+use std::os::unix::fs::PermissionsExt;
+
+fn env_test(args: &[&str], expected_out: &str, expected_err: &str, expected_exit_code: i32) {
+    run_test(TestPlan {
+        cmd: String::from("env"),
+        args: args.iter().map(|s| String::from(*s)).collect(),
+        stdin_data: String::new(),
+        expected_out: String::from(expected_out),
+        expected_err: String::from(expected_err),
+        expected_exit_code,
+    });
+}
+
+#[test]
+fn test_env_ignore_environment() {
+    env_test(&["-i"], "", "", 0);
+    env_test(&["-i", "A=1", "B=2"], "A=1\nB=2\n", "", 0);
+    env_test(
+        &["-i", "A=1", "/bin/sh", "-c", "echo $A $HOME"],
+        "1\n",
+        "",
+        0,
+    );
+}
+
+#[test]
+fn test_env_obsolescent_hyphen() {
+    env_test(&["-"], "", "", 0);
+    env_test(&["-", "A=1", "B=x=y"], "A=1\nB=x=y\n", "", 0);
+}
+
+#[test]
+fn test_env_settings_order() {
+    // a later setting of a name replaces an earlier one, in its place
+    env_test(&["-i", "A=1", "B=2", "A=3"], "A=3\nB=2\n", "", 0);
+    env_test(&["-i", "A=", "B=2"], "A=\nB=2\n", "", 0);
+
+    // the settings override the inherited environment
+    env_test(
+        &["HOME=/env_test", "sh", "-c", "echo $HOME"],
+        "/env_test\n",
+        "",
+        0,
+    );
+
+    // operands after the utility are its arguments, not settings
+    env_test(&["-i", "/bin/echo", "A=1"], "A=1\n", "", 0);
+}
+
+#[test]
+fn test_env_exit_status() {
+    env_test(&["sh", "-c", "exit 3"], "", "", 3);
+
+    env_test(
+        &["env_no_such_utility"],
+        "",
+        "env: env_no_such_utility: No such file or directory (os error 2)\n",
+        127,
+    );
+
+    let path = format!("{}/test_env_not_executable", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::write(&path, "#!/bin/sh\n").unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+    env_test(
+        &[&path],
+        "",
+        &format!("env: {path}: Permission denied (os error 13)\n"),
+        126,
+    );
+}
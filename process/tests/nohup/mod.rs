@@ -0,0 +1,153 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use plib::{run_test, TestPlan};
+use std::fs::{self, File};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::FromRawFd;
+use std::process::{Command, Stdio};
+
+fn nohup_test(args: &[&str], expected_out: &str, expected_err: &str, expected_exit_code: i32) {
+    run_test(TestPlan {
+        cmd: String::from("nohup"),
+        args: args.iter().map(|s| String::from(*s)).collect(),
+        stdin_data: String::new(),
+        expected_out: String::from(expected_out),
+        expected_err: String::from(expected_err),
+        expected_exit_code,
+    });
+}
+
+// Run nohup in `dir` with its output to a terminal, returning what it
+// writes to standard error and its exit status
+fn nohup_on_terminal(dir: &str, home: &str, args: &[&str]) -> (String, Option<i32>) {
+    let (mut master, mut slave) = (0, 0);
+    let res = unsafe {
+        libc::openpty(
+            &mut master,
+            &mut slave,
+            std::ptr::null_mut(),
+            std::ptr::null(),
+            std::ptr::null(),
+        )
+    };
+    assert_eq!(res, 0, "openpty failed");
+    let master = unsafe { File::from_raw_fd(master) };
+    let slave = unsafe { File::from_raw_fd(slave) };
+
+    let output = Command::new(env!("CARGO_BIN_EXE_nohup"))
+        .args(args)
+        .current_dir(dir)
+        .env("HOME", home)
+        .stdin(Stdio::null())
+        .stdout(slave)
+        .stderr(Stdio::piped())
+        .output()
+        .unwrap();
+    drop(master);
+    (
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+        output.status.code(),
+    )
+}
+
+#[test]
+fn test_nohup_runs_utility() {
+    nohup_test(&["echo", "a", "b"], "a b\n", "", 0);
+    nohup_test(&["--", "sh", "-c", "exit 3"], "", "", 3);
+
+    // the descriptor nohup keeps to report errors is not inherited
+    nohup_test(
+        &["sh", "-c", "test -e /dev/fd/3 && echo inherited"],
+        "",
+        "",
+        1,
+    );
+}
+
+#[test]
+fn test_nohup_exit_status() {
+    nohup_test(
+        &["nohup_no_such_utility"],
+        "",
+        "nohup: nohup_no_such_utility: No such file or directory (os error 2)\n",
+        127,
+    );
+
+    let dir = &format!("{}/test_nohup_exit_status", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    let path = format!("{dir}/not_executable");
+    fs::write(&path, "#!/bin/sh\n").unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+    nohup_test(
+        &[&path],
+        "",
+        &format!("nohup: {path}: Permission denied (os error 13)\n"),
+        126,
+    );
+
+    nohup_test(&[], "", "usage: nohup utility [argument...]\n", 127);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_nohup_creates_and_appends_nohup_out() {
+    let dir = &format!("{}/test_nohup_out", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    let path = format!("{dir}/nohup.out");
+
+    let (err, code) = nohup_on_terminal(dir, dir, &["echo", "a"]);
+    assert_eq!(err, "nohup: appending output to nohup.out\n");
+    assert_eq!(code, Some(0));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "a\n");
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    let (err, code) = nohup_on_terminal(dir, dir, &["echo", "b"]);
+    assert_eq!(err, "nohup: appending output to nohup.out\n");
+    assert_eq!(code, Some(0));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "a\nb\n");
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_nohup_out_in_home() {
+    // a directory named nohup.out cannot be opened for writing, even by
+    // a privileged user
+    let dir = &format!("{}/test_nohup_out_in_home", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(dir).unwrap();
+    fs::create_dir(format!("{dir}/nohup.out")).unwrap();
+    let home = format!("{dir}/home");
+    fs::create_dir(&home).unwrap();
+
+    let (err, code) = nohup_on_terminal(dir, &home, &["echo", "a"]);
+    assert_eq!(
+        err,
+        format!("nohup: appending output to {home}/nohup.out\n")
+    );
+    assert_eq!(code, Some(0));
+    assert_eq!(
+        fs::read_to_string(format!("{home}/nohup.out")).unwrap(),
+        "a\n"
+    );
+
+    // without a writable nohup.out the utility is not run
+    fs::remove_file(format!("{home}/nohup.out")).unwrap();
+    fs::create_dir(format!("{home}/nohup.out")).unwrap();
+    let (err, code) = nohup_on_terminal(dir, &home, &["echo", "a"]);
+    assert_eq!(
+        err,
+        "nohup: cannot open nohup.out: Is a directory (os error 21)\n"
+    );
+    assert_eq!(code, Some(127));
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,90 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use plib::{run_test, TestPlan};
+
+fn ps_test(args: &[&str], expected_out: &str, expected_err: &str, expected_exit_code: i32) {
+    run_test(TestPlan {
+        cmd: String::from("ps"),
+        args: args.iter().map(|s| String::from(*s)).collect(),
+        stdin_data: String::new(),
+        expected_out: String::from(expected_out),
+        expected_err: String::from(expected_err),
+        expected_exit_code,
+    });
+}
+
+// the process running the tests, which is the parent of ps
+fn test_pid() -> String {
+    std::process::id().to_string()
+}
+
+#[test]
+fn test_ps_select_pid() {
+    let pid = test_pid();
+    let ppid = unsafe { libc::getppid() }.to_string();
+    let pid_width = pid.len().max(3);
+    let ppid_width = ppid.len().max(4);
+    ps_test(
+        &["-p", &pid, "-o", "pid,ppid"],
+        &format!(
+            "{:>pid_width$} {:>ppid_width$}\n{:>pid_width$} {:>ppid_width$}\n",
+            "PID", "PPID", pid, ppid
+        ),
+        "",
+        0,
+    );
+
+    ps_test(&["-p", "x"], "", "ps: x: invalid process ID\n", 1);
+}
+
+#[test]
+fn test_ps_headers() {
+    let pid = test_pid();
+
+    // the header text is renamed
+    let width = pid.len().max(2);
+    ps_test(
+        &["-p", &pid, "-o", "pid=ID"],
+        &format!("{:>width$}\n{:>width$}\n", "ID", pid),
+        "",
+        0,
+    );
+
+    // with all headers empty, there is no header line
+    let width = pid.len().max(3);
+    ps_test(
+        &["-p", &pid, "-o", "pid="],
+        &format!("{:>width$}\n", pid),
+        "",
+        0,
+    );
+}
+
+#[test]
+fn test_ps_column_widths() {
+    let pid = test_pid();
+    let comm = std::fs::read_to_string("/proc/self/comm").unwrap();
+    let comm = comm.trim_end();
+
+    // a column is as wide as its header or its widest value; text is
+    // aligned to the left and numbers to the right
+    let header = "PROCESS-IDENTIFIER";
+    let width = header.len();
+    ps_test(
+        &["-p", &pid, "-o", "comm=C", "-o", &format!("pid={header}")],
+        &format!(
+            "{:<comm_width$} {header}\n{comm} {pid:>width$}\n",
+            "C",
+            comm_width = comm.len()
+        ),
+        "",
+        0,
+    );
+}
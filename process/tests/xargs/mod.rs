@@ -0,0 +1,191 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use plib::{run_test, TestPlan};
+
+fn xargs_test(args: &[&str], stdin_data: &str, expected_out: &str, expected_exit_code: i32) {
+    run_test(TestPlan {
+        cmd: String::from("xargs"),
+        args: args.iter().map(|s| String::from(*s)).collect(),
+        stdin_data: String::from(stdin_data),
+        expected_out: String::from(expected_out),
+        expected_err: String::new(),
+        expected_exit_code,
+    });
+}
+
+fn xargs_error_test(args: &[&str], stdin_data: &str, expected_err: &str, expected_exit_code: i32) {
+    run_test(TestPlan {
+        cmd: String::from("xargs"),
+        args: args.iter().map(|s| String::from(*s)).collect(),
+        stdin_data: String::from(stdin_data),
+        expected_out: String::new(),
+        expected_err: String::from(expected_err),
+        expected_exit_code,
+    });
+}
+
+#[test]
+fn test_xargs_default_echo() {
+    xargs_test(&[], "a b\nc\n", "a b c\n", 0);
+    xargs_test(&["echo", "-"], "", "-\n", 0);
+}
+
+#[test]
+fn test_xargs_quoting() {
+    xargs_test(
+        &["-n", "1"],
+        "\"a b\" 'c d' e\\ f g\\\nh \"\"\n",
+        "a b\nc d\ne f\ng\nh\n\n",
+        0,
+    );
+    xargs_error_test(&[], "a \"b\nc\"\n", "xargs: unmatched double quote\n", 1);
+    xargs_error_test(&[], "'a", "xargs: unmatched single quote\n", 1);
+}
+
+#[test]
+fn test_xargs_max_args() {
+    xargs_test(
+        &["-n", "2", "echo", "-"],
+        "1 2 3 4 5\n",
+        "- 1 2\n- 3 4\n- 5\n",
+        0,
+    );
+}
+
+#[test]
+fn test_xargs_lines() {
+    // a trailing blank continues a line
+    xargs_test(&["-L", "1"], "a b\nc \nd\n\ne\n", "a b\nc d\ne\n", 0);
+    xargs_test(&["-L", "2"], "a\nb\nc\n", "a b\nc\n", 0);
+}
+
+#[test]
+fn test_xargs_size() {
+    // each argument takes its length, a terminator and a pointer
+    let ptr = std::mem::size_of::<usize>();
+    let size = (5 + ptr) + 3 * (2 + ptr);
+    xargs_test(
+        &["-s", &size.to_string(), "echo"],
+        "a b c d e\n",
+        "a b c\nd e\n",
+        0,
+    );
+    xargs_error_test(
+        &["-s", &size.to_string(), "echo"],
+        "abcdefghijklmnopqrstuvwxyz0123456789\n",
+        "xargs: argument list too long\n",
+        1,
+    );
+    xargs_error_test(
+        &["-x", "-n", "4", "-s", &size.to_string(), "echo"],
+        "a b c d e\n",
+        "xargs: argument list too long\n",
+        1,
+    );
+}
+
+#[test]
+fn test_xargs_insert() {
+    xargs_test(
+        &["-I", "{}", "echo", "[{}]", "x{}y{}"],
+        "one\n  two  three \n\nfour\n",
+        "[one] xoneyone\n[two  three ] xtwo  three ytwo  three \n[four] xfouryfour\n",
+        0,
+    );
+}
+
+#[test]
+fn test_xargs_eofstr() {
+    xargs_test(&["-E", "STOP"], "a b\nSTOP c\n", "a b\n", 0);
+    xargs_test(&["-E", "STOP"], "a \"STOP\" c\n", "a STOP c\n", 0);
+}
+
+#[test]
+fn test_xargs_trace() {
+    run_test(TestPlan {
+        cmd: String::from("xargs"),
+        args: vec![
+            String::from("-t"),
+            String::from("-n1"),
+            String::from("echo"),
+        ],
+        stdin_data: String::from("a b\n"),
+        expected_out: String::from("a\nb\n"),
+        expected_err: String::from("echo a\necho b\n"),
+        expected_exit_code: 0,
+    });
+}
+
+#[test]
+fn test_xargs_exit_status() {
+    xargs_test(&["sh", "-c", "exit 3"], "a\n", "", 123);
+    xargs_error_test(
+        &["-n", "1", "sh", "-c", "echo $0 >&2; exit 255"],
+        "a\nb\n",
+        "a\nxargs: sh: exited with status 255; aborting\n",
+        124,
+    );
+    xargs_error_test(
+        &["/nonexistent/utility"],
+        "a\n",
+        "xargs: /nonexistent/utility: No such file or directory (os error 2)\n",
+        127,
+    );
+}
+
+#[test]
+fn test_xargs_null_delimited() {
+    xargs_test(
+        &["-0", "-n", "1", "echo"],
+        "a b\0c\"d\0\0e\\f\n",
+        "a b\nc\"d\n\ne\\f\n\n",
+        0,
+    );
+    xargs_test(
+        &["-0", "-I", "{}", "echo", "<{}>"],
+        "x y\0z",
+        "<x y>\n<z>\n",
+        0,
+    );
+}
+
+#[test]
+fn test_xargs_parallel() {
+    // the first invocation sees the file of the second only if both run
+    // at once
+    let script = "touch $1/$0; if [ $0 = a ]; then sleep 0.5; [ -e $1/b ] && echo both; fi";
+    let dir = &format!("{}/test_xargs_parallel", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::create_dir(dir).unwrap();
+    let input = format!("a {dir}\nb {dir}\n");
+    xargs_test(
+        &["-P", "2", "-L", "1", "sh", "-c", script],
+        &input,
+        "both\n",
+        0,
+    );
+
+    // a failure does not stop the other invocations
+    xargs_test(
+        &["-P", "3", "-n", "1", "sh", "-c", "exit $(($0 % 2))"],
+        "1 2 3 4 5 6\n",
+        "",
+        123,
+    );
+
+    // the first reason to stop decides the exit status
+    xargs_error_test(
+        &["-P", "0", "-n", "1", "sh", "-c", "sleep $0; exit 255"],
+        "0.2 0.6\n",
+        "xargs: sh: exited with status 255; aborting\nxargs: sh: exited with status 255; aborting\n",
+        124,
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -6,28 +6,18 @@
 // file in the root directory of this project.
 // SPDX-License-Identifier: MIT
 //
-// TODO:
-// - stty get-short display
-//
 
 extern crate clap;
 extern crate libc;
 extern crate plib;
 
-mod osdata;
-
 use clap::Parser;
-use gettextrs::{bind_textdomain_codeset, textdomain};
-use osdata::{ParamType, PARG, PNEG};
+use gettextrs::{bind_textdomain_codeset, gettext, textdomain};
+use plib::termios::{self, FlagSet, Termios, CONTROL_CHARS, MODES};
 use plib::PROJECT_NAME;
 use std::collections::HashMap;
-use std::io::{self, Error, ErrorKind};
-use termios::{
-    cc_t, cfgetispeed, cfgetospeed, cfsetispeed, cfsetospeed, speed_t, tcflag_t, tcsetattr,
-    Termios, TCSANOW,
-};
 
-const HDR_SAVE: &'static str = "pfmt1";
+const HDR_SAVE: &str = "pfmt1";
 
 /// stty - set the options for a terminal
 #[derive(Parser, Debug)]
@@ -42,506 +32,352 @@ struct Args {
     save: bool,
 
     /// List of terminal configuration commands
+    #[arg(allow_hyphen_values = true)]
     operands: Vec<String>,
 }
 
-fn speed_to_str(revspeed: &HashMap<speed_t, &'static str>, speed: speed_t) -> String {
-    match revspeed.get(&speed) {
-        None => format!("B{}?", speed),
-        Some(s) => String::from(*s),
+// the modes set by "sane", besides the default control characters
+const SANE_MODES: &[(&str, bool)] = &[
+    ("cread", true),
+    ("ignbrk", false),
+    ("brkint", true),
+    ("inlcr", false),
+    ("igncr", false),
+    ("icrnl", true),
+    ("ixoff", false),
+    ("ixany", false),
+    ("imaxbel", true),
+    ("iutf8", false),
+    ("opost", true),
+    ("onlcr", true),
+    ("ocrnl", false),
+    ("onocr", false),
+    ("onlret", false),
+    ("ofill", false),
+    ("ofdel", false),
+    ("nl0", true),
+    ("cr0", true),
+    ("tab0", true),
+    ("bs0", true),
+    ("vt0", true),
+    ("ff0", true),
+    ("isig", true),
+    ("icanon", true),
+    ("iexten", true),
+    ("echo", true),
+    ("echoe", true),
+    ("echok", true),
+    ("echonl", false),
+    ("noflsh", false),
+    ("tostop", false),
+    ("echoprt", false),
+    ("echoctl", true),
+    ("echoke", true),
+    ("flusho", false),
+];
+
+// the modes cleared by "raw" and set by "cooked" (or "-raw")
+const RAW_MODES: &[&str] = &[
+    "brkint", "ignpar", "istrip", "icrnl", "ixon", "opost", "isig", "icanon",
+];
+
+// the modes "raw" also clears
+const RAW_ONLY_MODES: &[&str] = &[
+    "ignbrk", "parmrk", "inpck", "inlcr", "igncr", "ixoff", "ixany", "imaxbel",
+];
+
+fn set_modes(ti: &mut Termios, modes: &[(&str, bool)]) {
+    for (name, on) in modes {
+        if let Some(mode) = termios::find_mode(name) {
+            mode.apply(ti, *on);
+        }
     }
 }
 
-fn ti_baud_str(revspeed: &HashMap<speed_t, &'static str>, ti: &Termios) -> String {
-    let ispeed = cfgetispeed(&ti);
-    let ispeed_str = speed_to_str(revspeed, ispeed);
-    let ospeed = cfgetospeed(&ti);
-    let ospeed_str = speed_to_str(revspeed, ospeed);
-
-    if ispeed == ospeed {
-        format!("speed {} baud;", ispeed_str)
-    } else {
-        format!("ispeed {} baud; ospeed {} baud;", ispeed_str, ospeed_str)
+fn set_sane(ti: &mut Termios) {
+    set_modes(ti, SANE_MODES);
+    for cc in CONTROL_CHARS {
+        ti.c_cc[cc.index] = cc.default;
     }
+    ti.c_cc[libc::VMIN] = 1;
+    ti.c_cc[libc::VTIME] = 0;
 }
 
-// display short-form stty values
-fn stty_show_short(ti: Termios) -> io::Result<()> {
-    stty_show_long(ti)
-}
-
-fn build_flagstr(name: &str, flag: tcflag_t, pflg: u32, vset: tcflag_t, mask: tcflag_t) -> String {
-    if (flag & mask) == vset {
-        format!("{}", name)
-    } else if (pflg & PNEG) != 0 {
-        format!("-{}", name)
-    } else {
-        String::new()
+fn set_raw(ti: &mut Termios, raw: bool) {
+    for name in RAW_MODES {
+        termios::find_mode(name).unwrap().apply(ti, !raw);
+    }
+    if raw {
+        for name in RAW_ONLY_MODES {
+            termios::find_mode(name).unwrap().apply(ti, false);
+        }
+        ti.c_cc[libc::VMIN] = 1;
+        ti.c_cc[libc::VTIME] = 0;
     }
 }
 
-fn flagmap_push(
-    ti: &Termios,
-    flagmap: &mut HashMap<&'static str, Vec<String>>,
-    name: &str,
-    param: &ParamType,
-) {
-    match param {
-        ParamType::Ifl(pflg, vset, vclear) => {
-            let flag = ti.c_iflag;
-            let flagstr = build_flagstr(name, flag, *pflg, *vset, *vclear);
-            if !flagstr.is_empty() {
-                let v = flagmap.get_mut("iflags").unwrap();
-                v.push(flagstr);
-            }
+/// Apply a composite mode such as raw or evenp.  Returns false if `name`
+/// is not one.
+fn set_composite(ti: &mut Termios, name: &str, negate: bool) -> Result<bool, String> {
+    match (name, negate) {
+        ("evenp" | "parity", false) => {
+            set_modes(ti, &[("parenb", true), ("parodd", false), ("cs7", true)])
         }
-        ParamType::Ofl(pflg, vset, vclear) => {
-            let flag = ti.c_oflag;
-            let flagstr = build_flagstr(name, flag, *pflg, *vset, *vclear);
-            if !flagstr.is_empty() {
-                let v = flagmap.get_mut("oflags").unwrap();
-                v.push(flagstr);
+        ("oddp", false) => set_modes(ti, &[("parenb", true), ("parodd", true), ("cs7", true)]),
+        ("evenp" | "parity" | "oddp", true) => set_modes(ti, &[("parenb", false), ("cs8", true)]),
+        ("nl", false) => set_modes(ti, &[("icrnl", false)]),
+        ("nl", true) => set_modes(ti, &[("icrnl", true), ("inlcr", false), ("igncr", false)]),
+        ("ek", false) => {
+            for name in ["erase", "kill"] {
+                let cc = termios::find_control_char(name).unwrap();
+                ti.c_cc[cc.index] = cc.default;
             }
         }
-        ParamType::Cfl(pflg, vset, vclear) => {
-            let flag = ti.c_cflag;
-            let flagstr = build_flagstr(name, flag, *pflg, *vset, *vclear);
-            if !flagstr.is_empty() {
-                let v = flagmap.get_mut("cflags").unwrap();
-                v.push(flagstr);
-            }
-        }
-        ParamType::Lfl(pflg, vset, vclear) => {
-            let flag = ti.c_lflag;
-            let flagstr = build_flagstr(name, flag, *pflg, *vset, *vclear);
-            if !flagstr.is_empty() {
-                let v = flagmap.get_mut("lflags").unwrap();
-                v.push(flagstr);
-            }
-        }
-        _ => {}
-    };
-}
-
-fn show_flags(name: &str, flags: &Vec<String>) {
-    println!("{}: {}", name, flags.join(" "));
-}
-
-fn show_cchars(tty_params: &HashMap<&'static str, ParamType>, ti: &Termios) {
-    let mut v = Vec::new();
-    let cchar_xlat = osdata::load_cchar_xlat();
-    let cchar_rev = osdata::reverse_charmap(&cchar_xlat);
-
-    // minor inefficiency: 2nd iteration through param list
-
-    for (name, param) in tty_params {
-        match param {
-            ParamType::Cchar(_pflg, chidx) => {
-                let ch = ti.c_cc[*chidx] as char;
-                let ch_rev = cchar_rev.get(&ch);
-                let ch_str = {
-                    if ch == '\0' {
-                        String::from("<undef>")
-                    } else if let Some(ch_xlat) = ch_rev {
-                        format!("^{}", ch_xlat)
-                    } else {
-                        format!("{}", ti.c_cc[*chidx] as u8)
-                    }
-                };
-
-                v.push(format!("{} = {}", name, ch_str));
-            }
-            _ => {}
+        ("sane", false) => set_sane(ti),
+        ("raw", _) => set_raw(ti, !negate),
+        ("cooked", _) => set_raw(ti, negate),
+        ("tabs", _) => set_modes(ti, &[(if negate { "tab3" } else { "tab0" }, true)]),
+        ("hup", _) => set_modes(ti, &[("hupcl", !negate)]),
+        ("ek" | "sane", true) => {
+            return Err(gettext!("invalid argument '-{}'", name));
         }
+        _ => return Ok(false),
     }
-
-    println!("cchars: {}", v.join("; "));
+    Ok(true)
 }
 
-// display long-form stty values
-fn stty_show_long(ti: Termios) -> io::Result<()> {
-    let speedmap = osdata::load_speeds();
-    let revspeed = osdata::load_speeds_rev(&speedmap);
-    println!("{}", ti_baud_str(&revspeed, &ti));
-
-    let tty_params = osdata::load_params();
-    let flagnames = vec!["lflags", "iflags", "oflags", "cflags"];
-
-    let mut flagmap: HashMap<&'static str, Vec<String>> = HashMap::new();
-    for name in &flagnames {
-        flagmap.insert(name, Vec::new());
+fn set_speed(ti: &mut Termios, arg: &str, input: bool, output: bool) -> Result<(), String> {
+    let speed = arg
+        .parse::<u32>()
+        .ok()
+        .and_then(termios::speed_from_baud)
+        .ok_or_else(|| gettext!("invalid speed '{}'", arg))?;
+    if input {
+        termios::set_ispeed(ti, speed).map_err(|e| e.to_string())?;
     }
-
-    for (name, param) in &tty_params {
-        flagmap_push(&ti, &mut flagmap, name, &param);
+    if output {
+        termios::set_ospeed(ti, speed).map_err(|e| e.to_string())?;
     }
-
-    for flagname in &flagnames {
-        show_flags(flagname, flagmap.get(flagname).unwrap());
-    }
-
-    show_cchars(&tty_params, &ti);
-
     Ok(())
 }
 
-// display compact, parse-able form stty values
-fn stty_show_compact(ti: Termios) -> io::Result<()> {
-    let mut tiv = Vec::new();
-
-    // encode settings as pairs of (String,u64)
-    tiv.push((String::from("ifl"), ti.c_iflag as u64));
-    tiv.push((String::from("ofl"), ti.c_oflag as u64));
-    tiv.push((String::from("cfl"), ti.c_cflag as u64));
-    tiv.push((String::from("lfl"), ti.c_lflag as u64));
-    tiv.push((String::from("isp"), cfgetispeed(&ti) as u64));
-    tiv.push((String::from("osp"), cfgetospeed(&ti) as u64));
-
-    // encode control chars as pairs of (String,u64)
-    for (i, cc) in ti.c_cc.iter().enumerate() {
-        tiv.push((format!("ch{}", i), *cc as u64));
-    }
+/// Apply the operands to `ti`.
+fn apply_operands(ti: &mut Termios, operands: &[String]) -> Result<(), String> {
+    let mut iter = operands.iter();
+    while let Some(operand) = iter.next() {
+        let (negate, name) = match operand.strip_prefix('-') {
+            Some(name) => (true, name),
+            None => (false, operand.as_str()),
+        };
+        let invalid = || gettext!("invalid argument '{}'", operand);
+        let mut option_arg = || {
+            iter.next()
+                .ok_or_else(|| gettext!("missing argument to '{}'", operand))
+        };
 
-    // convert pairs to list of strings
-    let mut sv = Vec::new();
-    sv.push(String::from(HDR_SAVE));
-    for te in tiv {
-        sv.push(format!("{}={}", te.0, te.1));
+        if let Some(mode) = termios::find_mode(name) {
+            if negate && !mode.negatable {
+                return Err(invalid());
+            }
+            mode.apply(ti, !negate);
+        } else if set_composite(ti, name, negate)? {
+            // done
+        } else if negate {
+            return Err(invalid());
+        } else if let Some(cc) = termios::find_control_char(name) {
+            let arg = option_arg()?;
+            ti.c_cc[cc.index] =
+                termios::parse_cc(arg).ok_or_else(|| gettext!("invalid character '{}'", arg))?;
+        } else if name == "min" || name == "time" {
+            let arg = option_arg()?;
+            let value = arg
+                .parse::<u8>()
+                .map_err(|_| gettext!("invalid integer argument '{}'", arg))?;
+            let index = if name == "min" {
+                libc::VMIN
+            } else {
+                libc::VTIME
+            };
+            ti.c_cc[index] = value;
+        } else if name == "ispeed" || name == "ospeed" {
+            let arg = option_arg()?;
+            set_speed(ti, arg, name == "ispeed", name == "ospeed")?;
+        } else if name.bytes().all(|b| b.is_ascii_digit()) {
+            set_speed(ti, name, true, true)?;
+        } else {
+            return Err(invalid());
+        }
     }
 
-    // convert list to single compact string
-    println!("{}", sv.join(":"));
-
     Ok(())
 }
 
-// set termio control chars based on pairmap values
-fn set_ti_cchar(
-    ti: &mut Termios,
-    pairmap: &HashMap<String, u64>,
-    mut dirty: bool,
-    idx: usize,
-) -> Result<bool, &'static str> {
-    // lookup control char.  should only fail if data corruption.
-    let mapkey = format!("ch{}", idx);
-    let value_res = pairmap.get(&mapkey);
-    if value_res.is_none() {
-        return Err("Invalid ent: missing cchar");
+fn speed_str(speed: libc::speed_t) -> String {
+    match termios::baud_from_speed(speed) {
+        Some(baud) => baud.to_string(),
+        None => speed.to_string(),
     }
-    let value = *value_res.unwrap() as termios::cc_t;
+}
 
-    // conditionally set the control char.  if changed, mark dirty.
-    if ti.c_cc[idx] != value {
-        ti.c_cc[idx] = value;
-        dirty = true;
+fn baud_str(ti: &Termios) -> String {
+    let ispeed = termios::ispeed(ti);
+    let ospeed = termios::ospeed(ti);
+    if ispeed == ospeed {
+        format!("speed {} baud;", speed_str(ispeed))
+    } else {
+        format!(
+            "ispeed {} baud; ospeed {} baud;",
+            speed_str(ispeed),
+            speed_str(ospeed)
+        )
     }
+}
 
-    Ok(dirty)
+// the size of the terminal, if known
+fn window_size() -> Option<(u16, u16)> {
+    let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
+    let res = unsafe { libc::ioctl(libc::STDIN_FILENO, libc::TIOCGWINSZ, &mut ws) };
+    (res == 0).then_some((ws.ws_row, ws.ws_col))
 }
 
-// set termio flags based on pairmap values
-fn set_ti_flags(
-    ti: &mut Termios,
-    pairmap: &HashMap<String, u64>,
-    mut dirty: bool,
-    setname: &str,
-) -> Result<bool, &'static str> {
-    // lookup flag set name.  should only fail if data corruption.
-    let value_res = pairmap.get(setname);
-    if value_res.is_none() {
-        return Err("Invalid ent: missing flags");
-    }
-    let value = *value_res.unwrap() as termios::tcflag_t;
-
-    // conditionally set the specified flag.  if changed, mark dirty.
-    match setname {
-        "ifl" => {
-            if ti.c_iflag != value {
-                ti.c_iflag = value;
-                dirty = true;
-            }
-        }
-        "ofl" => {
-            if ti.c_oflag != value {
-                ti.c_oflag = value;
-                dirty = true;
-            }
-        }
-        "cfl" => {
-            if ti.c_cflag != value {
-                ti.c_cflag = value;
-                dirty = true;
-            }
-        }
-        "lfl" => {
-            if ti.c_lflag != value {
-                ti.c_lflag = value;
-                dirty = true;
-            }
-        }
-        _ => {
-            return Err("BUG - should never occur");
-        }
+// a mode as listed: its name, negated when clear.  Modes that cannot be
+// negated are only listed when set.
+fn mode_str(mode: &termios::Mode, ti: &Termios) -> Option<String> {
+    if mode.is_set(ti) {
+        Some(mode.name.to_string())
+    } else if mode.negatable {
+        Some(format!("-{}", mode.name))
+    } else {
+        None
     }
-
-    Ok(dirty)
 }
 
-// mutate a termio flag based on the provided spec
-fn set_ti_flag(
-    flags: &mut tcflag_t,
-    val_set: tcflag_t,
-    val_clear: tcflag_t,
-    negate: bool,
-    mut dirty: bool,
-) -> bool {
-    // clear flag bits
-    let mut newflags = *flags & !val_clear;
-
-    // set flag bits (unless negation)
-    if !negate {
-        newflags = newflags | val_set;
+// display all settings
+fn stty_show_long(ti: &Termios) {
+    let mut line = baud_str(ti);
+    if let Some((rows, cols)) = window_size() {
+        line.push_str(&format!(" rows {}; columns {};", rows, cols));
     }
-
-    // commit flag bits to termio struct, if changed
-    if newflags != *flags {
-        dirty = true;
-        *flags = newflags;
+    println!("{}", line);
+
+    let mut cchars: Vec<String> = CONTROL_CHARS
+        .iter()
+        .map(|cc| format!("{} = {};", cc.name, termios::format_cc(ti.c_cc[cc.index])))
+        .collect();
+    cchars.push(format!("min = {};", ti.c_cc[libc::VMIN]));
+    cchars.push(format!("time = {};", ti.c_cc[libc::VTIME]));
+    println!("{}", cchars.join(" "));
+
+    for set in [
+        FlagSet::Control,
+        FlagSet::Input,
+        FlagSet::Output,
+        FlagSet::Local,
+    ] {
+        let modes: Vec<String> = MODES
+            .iter()
+            .filter(|mode| mode.set == set)
+            .filter_map(|mode| mode_str(mode, ti))
+            .collect();
+        println!("{}", modes.join(" "));
     }
-
-    dirty
 }
 
-fn set_ti_cchar_oparg(
-    xlat: &HashMap<char, char>,
-    cc: &mut cc_t,
-    op_arg: &str,
-    _dirty: bool,
-) -> Result<bool, &'static str> {
-    if op_arg == "^-" || op_arg == "undef" {
-        *cc = 0;
-        return Ok(true);
+// display the speed and the settings that differ from "sane"
+fn stty_show_short(ti: &Termios) {
+    println!("{}", baud_str(ti));
+
+    // "sane" leaves the character size and parity alone, but those differing
+    // from the common setting are worth showing too
+    let mut sane = *ti;
+    set_sane(&mut sane);
+    set_modes(
+        &mut sane,
+        &[
+            ("parenb", false),
+            ("parodd", false),
+            ("cs8", true),
+            ("cstopb", false),
+        ],
+    );
+
+    let cchars: Vec<String> = CONTROL_CHARS
+        .iter()
+        .filter(|cc| ti.c_cc[cc.index] != sane.c_cc[cc.index])
+        .map(|cc| format!("{} = {};", cc.name, termios::format_cc(ti.c_cc[cc.index])))
+        .collect();
+    if !cchars.is_empty() {
+        println!("{}", cchars.join(" "));
     }
 
-    if !op_arg.starts_with("^") || op_arg.len() != 2 {
-        return Err("Invalid cchar specification");
-    }
-    let ch = op_arg.chars().nth(1).unwrap();
-    let value_res = xlat.get(&ch);
-    if value_res.is_none() {
-        return Err("Unknown cchar specification");
+    let modes: Vec<String> = MODES
+        .iter()
+        .filter(|mode| mode.is_set(ti) != mode.is_set(&sane))
+        .filter_map(|mode| mode_str(mode, ti))
+        .collect();
+    if !modes.is_empty() {
+        println!("{}", modes.join(" "));
     }
-
-    let value = value_res.unwrap();
-    *cc = *value as cc_t;
-    Ok(true)
 }
 
-fn set_ti_speed(
-    ti: &mut Termios,
-    speedmap: &HashMap<&str, speed_t>,
-    is_input: bool,
-    op_arg: &str,
-) -> io::Result<()> {
-    let speed_res = speedmap.get(op_arg);
-    if speed_res.is_none() {
-        return Err(Error::new(ErrorKind::Other, "Invalid speed"));
-    }
-    let speed = speed_res.unwrap();
-
-    if is_input {
-        let _ = cfsetispeed(ti, *speed)?;
-    } else {
-        let _ = cfsetospeed(ti, *speed)?;
+// compact, parse-able form stty values
+fn compact_str(ti: &Termios) -> String {
+    let mut sv = vec![
+        String::from(HDR_SAVE),
+        format!("ifl={}", ti.c_iflag),
+        format!("ofl={}", ti.c_oflag),
+        format!("cfl={}", ti.c_cflag),
+        format!("lfl={}", ti.c_lflag),
+        format!("isp={}", termios::ispeed(ti)),
+        format!("osp={}", termios::ospeed(ti)),
+    ];
+    for (i, cc) in ti.c_cc.iter().enumerate() {
+        sv.push(format!("ch{}={}", i, cc));
     }
 
-    Ok(())
+    sv.join(":")
 }
 
-// update termio settings based on pairmap values
-fn merge_map(ti: &mut Termios, pairmap: &HashMap<String, u64>) -> Result<bool, &'static str> {
-    // push flags into termio struct
-    let mut dirty = false;
-    dirty = set_ti_flags(ti, pairmap, dirty, "ifl")?;
-    dirty = set_ti_flags(ti, pairmap, dirty, "ofl")?;
-    dirty = set_ti_flags(ti, pairmap, dirty, "cfl")?;
-    dirty = set_ti_flags(ti, pairmap, dirty, "lfl")?;
-
-    // push control chars into termio struct
-    let cclen = ti.c_cc.len();
-    for idx in 0..cclen {
-        dirty = set_ti_cchar(ti, &pairmap, dirty, idx)?;
-    }
-
-    Ok(dirty)
+// display compact, parse-able form stty values
+fn stty_show_compact(ti: &Termios) {
+    println!("{}", compact_str(ti));
 }
 
 // update termio settings based on compact-form input line
-fn stty_set_compact(mut ti: Termios, compact: &str) -> io::Result<()> {
-    // split by ':'
-    let parts: Vec<&str> = compact.split(":").collect();
+fn stty_set_compact(ti: &mut Termios, compact: &str) -> Result<(), String> {
+    let invalid = || gettext!("invalid argument '{}'", compact);
 
-    // assert validates _prior_ user input check, guaranteeing this condition
-    assert_eq!(parts[0], HDR_SAVE);
-
-    // iterate through strings, assuming they are KEY=VALUE pair strings.
-    // skip first entry, our header marker.
     let mut pairmap = HashMap::new();
-    let rawpairs = &parts[1..];
-    for pairstr in rawpairs {
-        // split string into KEY=VALUE
-        match pairstr.split_once('=') {
-            // parse value into u64 integer
-            Some((key, value)) => match value.parse::<u64>() {
-                Ok(n) => {
-                    // it's a valid entry.  insert into settings pairmap.
-                    pairmap.insert(String::from(key), n);
-                }
-                Err(_e) => {
-                    return Err(Error::new(ErrorKind::Other, "Invalid ent"));
-                }
-            },
-            None => {
-                return Err(Error::new(ErrorKind::Other, "Invalid ent"));
-            }
-        }
+    for pairstr in compact.split(':').skip(1) {
+        let (key, value) = pairstr.split_once('=').ok_or_else(invalid)?;
+        let value = value.parse::<u64>().map_err(|_| invalid())?;
+        pairmap.insert(key, value);
     }
-
-    // merge parsed settings into termio struct
-    let dirty = match merge_map(&mut ti, &pairmap) {
-        Ok(d) => d,
-        Err(e) => {
-            return Err(Error::new(ErrorKind::Other, e));
-        }
-    };
-
-    // finally, commit new termio settings (if any)
-    if dirty {
-        tcsetattr(libc::STDIN_FILENO, TCSANOW, &ti)?;
-    }
-
-    Ok(())
-}
-
-// update termio settings based on setting-per-arg parsed values
-fn stty_set_long(mut ti: Termios, args: &Args) -> io::Result<()> {
-    assert!(args.operands.len() > 1);
-
-    // load static list of params
-    let tty_params = osdata::load_params();
-    let cchar_xlat = osdata::load_cchar_xlat();
-    let speedmap = osdata::load_speeds();
-
-    let mut dirty = false;
-
-    // parse each operand
-    let mut idx = 0;
-    while idx < args.operands.len() {
-        let operand_raw = &args.operands[idx];
-
-        // if operand begins with "-", it is a negation
-        let mut negate = false;
-        let operand = {
-            if operand_raw.starts_with("-") {
-                negate = true;
-                &operand_raw[1..]
-            } else {
-                &operand_raw[..]
-            }
-        };
-
-        // special case: set two speeds, if all-numeric operand
-        if operand.parse::<u64>().is_ok() {
-            set_ti_speed(&mut ti, &speedmap, true, operand)?;
-            set_ti_speed(&mut ti, &speedmap, false, operand)?;
-            idx = idx + 1;
-            continue;
-        }
-
-        // lookup operand in param map
-        let param_res = tty_params.get(operand);
-        if param_res.is_none() {
-            let errstr = format!("Unknown operand {}", operand);
-            return Err(Error::new(ErrorKind::Other, errstr));
-        }
-        let param = param_res.unwrap();
-
-        let flags = match param {
-            ParamType::Cfl(pflg, _, _) => pflg,
-            ParamType::Ifl(pflg, _, _) => pflg,
-            ParamType::Ofl(pflg, _, _) => pflg,
-            ParamType::Lfl(pflg, _, _) => pflg,
-            ParamType::Cchar(pflg, _) => pflg,
-            ParamType::Ispeed(pflg) => pflg,
-            ParamType::Ospeed(pflg) => pflg,
-        };
-        if negate && ((flags & PNEG) == 0) {
-            let errstr = format!("Operand {} cannot be negated", operand);
-            return Err(Error::new(ErrorKind::Other, errstr));
-        }
-
-        let mut op_arg = String::new();
-        if (flags & PARG) != 0 {
-            idx = idx + 1;
-
-            if idx == args.operands.len() {
-                let errstr = format!("Missing operand for {}", operand);
-                return Err(Error::new(ErrorKind::Other, errstr));
-            }
-
-            op_arg = String::from(&args.operands[idx]);
-        }
-
-        // handle operand
-        match param {
-            ParamType::Cfl(_pflg, bset, bclear) => {
-                dirty = set_ti_flag(&mut ti.c_cflag, *bset, *bclear, negate, dirty);
-            }
-            ParamType::Ifl(_pflg, bset, bclear) => {
-                dirty = set_ti_flag(&mut ti.c_iflag, *bset, *bclear, negate, dirty);
-            }
-            ParamType::Ofl(_pflg, bset, bclear) => {
-                dirty = set_ti_flag(&mut ti.c_oflag, *bset, *bclear, negate, dirty);
-            }
-            ParamType::Lfl(_pflg, bset, bclear) => {
-                dirty = set_ti_flag(&mut ti.c_lflag, *bset, *bclear, negate, dirty);
-            }
-            ParamType::Cchar(_pflg, chidx) => {
-                let dirty_res =
-                    set_ti_cchar_oparg(&cchar_xlat, &mut ti.c_cc[*chidx], &op_arg, dirty);
-                if let Err(e) = dirty_res {
-                    return Err(Error::new(ErrorKind::Other, e));
-                }
-
-                dirty = dirty_res.unwrap();
-            }
-            ParamType::Ispeed(_pflg) => {
-                set_ti_speed(&mut ti, &speedmap, true, &op_arg)?;
-            }
-            ParamType::Ospeed(_pflg) => {
-                set_ti_speed(&mut ti, &speedmap, false, &op_arg)?;
-            }
-        }
-
-        idx = idx + 1;
-    }
-
-    // finally, commit new termio settings (if any)
-    // note: speed is changed in set_ti_speed(), not here.
-    if dirty {
-        tcsetattr(libc::STDIN_FILENO, TCSANOW, &ti)?;
+    let get = |key: &str| pairmap.get(key).copied().ok_or_else(invalid);
+
+    ti.c_iflag = get("ifl")? as libc::tcflag_t;
+    ti.c_oflag = get("ofl")? as libc::tcflag_t;
+    ti.c_cflag = get("cfl")? as libc::tcflag_t;
+    ti.c_lflag = get("lfl")? as libc::tcflag_t;
+    for i in 0..ti.c_cc.len() {
+        ti.c_cc[i] = get(&format!("ch{}", i))? as libc::cc_t;
     }
+    termios::set_ispeed(ti, get("isp")? as libc::speed_t).map_err(|e| e.to_string())?;
+    termios::set_ospeed(ti, get("osp")? as libc::speed_t).map_err(|e| e.to_string())?;
 
     Ok(())
 }
 
 // set termio settings based on CLI operands supplied
-fn stty_set(ti: Termios, args: &Args) -> io::Result<()> {
+fn stty_set(mut ti: Termios, args: &Args) -> Result<(), String> {
     if args.operands.len() == 1 && args.operands[0].starts_with(HDR_SAVE) {
-        stty_set_compact(ti, &args.operands[0])
+        stty_set_compact(&mut ti, &args.operands[0])?;
     } else {
-        stty_set_long(ti, args)
+        apply_operands(&mut ti, &args.operands)?;
     }
+
+    termios::set_attr(libc::STDIN_FILENO, &ti)
+        .map_err(|e| format!("{}: {}", gettext("standard input"), e))
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -552,24 +388,183 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
 
     // load termio settings
-    let ti = Termios::from_fd(libc::STDIN_FILENO)?;
+    let ti = match termios::get_attr(libc::STDIN_FILENO) {
+        Ok(ti) => ti,
+        Err(e) => {
+            eprintln!("stty: {}: {}", gettext("standard input"), e);
+            std::process::exit(1);
+        }
+    };
+
+    if (args.all || args.save) && !args.operands.is_empty() {
+        eprintln!(
+            "stty: {}",
+            gettext("the options for displaying settings take no operands")
+        );
+        std::process::exit(1);
+    }
 
     // display long form readable, if -a
     if args.all {
-        stty_show_long(ti)?;
+        stty_show_long(&ti);
 
     // display computer-parseable, if -g
     } else if args.save {
-        stty_show_compact(ti)?;
+        stty_show_compact(&ti);
 
     // display short form readable, if no args
     } else if args.operands.is_empty() {
-        stty_show_short(ti)?;
+        stty_show_short(&ti);
 
     // otherwise, a list of operands instructing termio updates
-    } else {
-        stty_set(ti, &args)?;
+    } else if let Err(e) = stty_set(ti, &args) {
+        eprintln!("stty: {}", e);
+        std::process::exit(1);
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // sane settings at 9600 baud
+    fn sane_termios() -> Termios {
+        let mut ti: Termios = unsafe { std::mem::zeroed() };
+        set_sane(&mut ti);
+        set_modes(&mut ti, &[("cs8", true)]);
+        termios::set_ispeed(&mut ti, libc::B9600).unwrap();
+        termios::set_ospeed(&mut ti, libc::B9600).unwrap();
+        ti
+    }
+
+    fn apply(ti: &mut Termios, operands: &[&str]) -> Result<(), String> {
+        let operands: Vec<String> = operands.iter().map(|s| s.to_string()).collect();
+        apply_operands(ti, &operands)
+    }
+
+    fn is_set(ti: &Termios, name: &str) -> bool {
+        termios::find_mode(name).unwrap().is_set(ti)
+    }
+
+    #[test]
+    fn test_negated_modes() {
+        let mut ti = sane_termios();
+        apply(&mut ti, &["-echo", "parenb"]).unwrap();
+        assert!(!is_set(&ti, "echo") && is_set(&ti, "parenb"));
+        apply(&mut ti, &["echo", "-parenb"]).unwrap();
+        assert!(is_set(&ti, "echo") && !is_set(&ti, "parenb"));
+
+        // modes selecting one value of a field cannot be negated
+        assert_eq!(
+            apply(&mut ti, &["-cs8"]),
+            Err("invalid argument '-cs8'".to_string())
+        );
+        assert_eq!(
+            apply(&mut ti, &["-ispeed"]),
+            Err("invalid argument '-ispeed'".to_string())
+        );
+        assert_eq!(
+            apply(&mut ti, &["bogus"]),
+            Err("invalid argument 'bogus'".to_string())
+        );
+        assert!(is_set(&ti, "cs8"));
+    }
+
+    #[test]
+    fn test_composite_modes() {
+        let mut ti = sane_termios();
+        apply(&mut ti, &["-echo", "-icanon", "erase", "x", "-opost"]).unwrap();
+        apply(&mut ti, &["sane"]).unwrap();
+        assert!(is_set(&ti, "echo") && is_set(&ti, "icanon") && is_set(&ti, "opost"));
+        assert_eq!(ti.c_cc[libc::VERASE], 0x7f);
+        assert_eq!(
+            apply(&mut ti, &["-sane"]),
+            Err("invalid argument '-sane'".to_string())
+        );
+
+        apply(&mut ti, &["raw"]).unwrap();
+        for name in ["icanon", "isig", "opost", "icrnl", "ixon", "imaxbel"] {
+            assert!(!is_set(&ti, name), "{} is set", name);
+        }
+        assert_eq!((ti.c_cc[libc::VMIN], ti.c_cc[libc::VTIME]), (1, 0));
+        apply(&mut ti, &["-raw"]).unwrap();
+        for name in ["icanon", "isig", "opost", "icrnl", "ixon", "brkint"] {
+            assert!(is_set(&ti, name), "{} is not set", name);
+        }
+
+        apply(&mut ti, &["evenp"]).unwrap();
+        assert!(is_set(&ti, "parenb") && !is_set(&ti, "parodd") && is_set(&ti, "cs7"));
+        apply(&mut ti, &["-evenp"]).unwrap();
+        assert!(!is_set(&ti, "parenb") && is_set(&ti, "cs8"));
+
+        apply(&mut ti, &["nl"]).unwrap();
+        assert!(!is_set(&ti, "icrnl"));
+        apply(&mut ti, &["inlcr", "igncr", "-nl"]).unwrap();
+        assert!(is_set(&ti, "icrnl") && !is_set(&ti, "inlcr") && !is_set(&ti, "igncr"));
+    }
+
+    #[test]
+    fn test_control_chars_and_timing() {
+        let mut ti = sane_termios();
+        apply(
+            &mut ti,
+            &["intr", "^X", "eof", "undef", "min", "5", "time", "10"],
+        )
+        .unwrap();
+        assert_eq!(ti.c_cc[libc::VINTR], 0x18);
+        assert_eq!(ti.c_cc[libc::VEOF], termios::VDISABLE);
+        assert_eq!((ti.c_cc[libc::VMIN], ti.c_cc[libc::VTIME]), (5, 10));
+
+        assert_eq!(
+            apply(&mut ti, &["intr"]),
+            Err("missing argument to 'intr'".to_string())
+        );
+        assert_eq!(
+            apply(&mut ti, &["min"]),
+            Err("missing argument to 'min'".to_string())
+        );
+        assert_eq!(
+            apply(&mut ti, &["intr", "ab"]),
+            Err("invalid character 'ab'".to_string())
+        );
+        assert_eq!(
+            apply(&mut ti, &["time", "256"]),
+            Err("invalid integer argument '256'".to_string())
+        );
+        assert_eq!(
+            apply(&mut ti, &["-intr", "^C"]),
+            Err("invalid argument '-intr'".to_string())
+        );
+        assert_eq!(ti.c_cc[libc::VINTR], 0x18);
+    }
+
+    #[test]
+    fn test_compact_round_trip() {
+        let mut ti = sane_termios();
+        apply(
+            &mut ti,
+            &[
+                "-echo", "evenp", "intr", "^X", "min", "3", "ispeed", "38400",
+            ],
+        )
+        .unwrap();
+
+        let compact = compact_str(&ti);
+        assert!(compact.starts_with(HDR_SAVE));
+        let mut restored: Termios = unsafe { std::mem::zeroed() };
+        stty_set_compact(&mut restored, &compact).unwrap();
+
+        assert_eq!(restored.c_iflag, ti.c_iflag);
+        assert_eq!(restored.c_oflag, ti.c_oflag);
+        assert_eq!(restored.c_cflag, ti.c_cflag);
+        assert_eq!(restored.c_lflag, ti.c_lflag);
+        assert_eq!(restored.c_cc, ti.c_cc);
+        assert_eq!(termios::ispeed(&restored), termios::ispeed(&ti));
+        assert_eq!(termios::ospeed(&restored), termios::ospeed(&ti));
+
+        assert!(stty_set_compact(&mut restored, "pfmt1:ifl=x").is_err());
+        assert!(stty_set_compact(&mut restored, "pfmt1:ifl=1").is_err());
+    }
+}
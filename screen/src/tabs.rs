@@ -16,9 +16,9 @@ extern crate plib;
 
 use clap::Parser;
 use gettextrs::{bind_textdomain_codeset, gettext, textdomain};
+use plib::terminfo::{self, Terminfo};
 use plib::PROJECT_NAME;
-use std::io::{self, Error, ErrorKind, Write};
-use terminfo::{capability as cap, Database};
+use std::io::{self, Write};
 
 // arbitrarily chosen.  todo: search if POSIX-ly correct.
 const MAX_STOPS: usize = 100;
@@ -174,21 +174,21 @@ fn parse_cmd_line(args: &Args) -> Result<Vec<u16>, &'static str> {
 }
 
 // set hardware tabs.
-fn set_hw_tabs(info: &Database, tabstops: &Vec<u16>) -> io::Result<()> {
-    let clear_cap = info.get::<cap::ClearAllTabs>();
-    let set_cap = info.get::<cap::SetTab>();
+fn set_hw_tabs(info: &Terminfo, tabstops: &Vec<u16>) -> io::Result<()> {
+    let (clear_cap, set_cap) = match (info.string("tbc"), info.string("hts")) {
+        (Some(clear_cap), Some(set_cap)) => (clear_cap, set_cap),
+        _ => {
+            let msg = gettext("Terminal does not support hardware tabs.");
+            return Err(io::Error::other(msg));
+        }
+    };
 
-    if clear_cap.is_none() || set_cap.is_none() {
-        let msg = gettext("Terminal does not support hardware tabs.");
-        return Err(Error::new(ErrorKind::Other, msg));
-    }
-    let clear_cap = clear_cap.unwrap();
-    let set_cap = set_cap.unwrap();
+    let mut stdout = io::stdout().lock();
 
     // clear existing tabs
-    if let Err(e) = clear_cap.expand().to(io::stdout()) {
+    if let Err(e) = terminfo::write_cap(&mut stdout, clear_cap) {
         let msg = format!("{}: {}", gettext("Failed to clear tabs"), e);
-        return Err(Error::new(ErrorKind::Other, msg));
+        return Err(io::Error::other(msg));
     }
 
     // set new tabs
@@ -197,17 +197,17 @@ fn set_hw_tabs(info: &Database, tabstops: &Vec<u16>) -> io::Result<()> {
         let stop = *stop as usize;
 
         while col < stop {
-            io::stdout().write_all(b" ")?;
+            stdout.write_all(b" ")?;
             col += 1;
         }
 
-        if let Err(e) = set_cap.expand().to(io::stdout()) {
+        if let Err(e) = terminfo::write_cap(&mut stdout, set_cap) {
             let msg = format!("{}: {}", gettext("Failed to set tab"), e);
-            return Err(Error::new(ErrorKind::Other, msg));
+            return Err(io::Error::other(msg));
         }
     }
 
-    Ok(())
+    stdout.flush()
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -218,8 +218,15 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
 
     let info = match args.term {
-        None => Database::from_env().unwrap(),
-        Some(ref termtype) => Database::from_name(termtype).unwrap(),
+        None => Terminfo::from_env(),
+        Some(ref termtype) => Terminfo::from_name(termtype),
+    };
+    let info = match info {
+        Ok(info) => info,
+        Err(e) => {
+            eprintln!("tabs: {}", e);
+            std::process::exit(1);
+        }
     };
 
     let tabstops = parse_cmd_line(&args)?;
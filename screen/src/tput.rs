@@ -6,19 +6,26 @@
 // file in the root directory of this project.
 // SPDX-License-Identifier: MIT
 //
-// TODO:
-// - eliminate unwrap. more error checking.
-// - read init-file and reset-file data from filesystem
-//
 
 extern crate clap;
 extern crate plib;
 
 use clap::Parser;
 use gettextrs::{bind_textdomain_codeset, gettext, textdomain};
+use plib::terminfo::{self, CapKind, Param, Terminfo};
 use plib::PROJECT_NAME;
-use std::io;
-use terminfo::{capability as cap, Database};
+use std::fs;
+use std::io::{self, Write};
+use std::process::Command;
+
+// exit status when no information is available about the terminal type
+const EXIT_NO_TERMINAL: i32 = 3;
+
+// exit status when the operand is invalid
+const EXIT_BAD_OPERAND: i32 = 4;
+
+// exit status for other errors
+const EXIT_ERROR: i32 = 5;
 
 /// tput - change terminal characteristics
 #[derive(Parser, Debug)]
@@ -28,54 +35,110 @@ struct Args {
     #[arg(short = 'T', long)]
     term: Option<String>,
 
-    /// Terminal operand to execute
+    /// Terminal operand to execute: clear, init, reset, or a capname
     operand: String,
+
+    /// Parameters of the capability
+    #[arg(allow_hyphen_values = true)]
+    params: Vec<String>,
 }
 
-fn tput_init(info: Database) -> terminfo::Result<()> {
-    if let Some(cap) = info.get::<cap::Init1String>() {
-        cap.expand().to(io::stdout())?;
-    }
-    if let Some(cap) = info.get::<cap::Init2String>() {
-        cap.expand().to(io::stdout())?;
-    }
-    if let Some(cap) = info.get::<cap::InitFile>() {
-        // FIXME
-        cap.expand().to(io::stdout())?;
-    }
-    if let Some(cap) = info.get::<cap::Init3String>() {
-        cap.expand().to(io::stdout())?;
+// The init and reset sequences: a reset string, and the init string used
+// in its place when the terminal has no such reset string.  The rf and if
+// capabilities name a file whose contents are written.
+const SEQUENCES: &[(&str, &str)] = &[("rs1", "is1"), ("rs2", "is2"), ("rf", "if"), ("rs3", "is3")];
+
+fn write_sequence(info: &Terminfo, reset: bool) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+
+    for (reset_cap, init_cap) in SEQUENCES {
+        let value = match info.string(reset_cap) {
+            Some(value) if reset => Some(value),
+            _ => info.string(init_cap),
+        };
+        let Some(value) = value else {
+            continue;
+        };
+
+        if *init_cap == "if" {
+            let path = String::from_utf8_lossy(value).into_owned();
+            stdout.write_all(
+                &fs::read(&path)
+                    .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?,
+            )?;
+        } else {
+            terminfo::write_cap(&mut stdout, value)?;
+        }
     }
 
-    Ok(())
+    stdout.flush()
 }
 
-fn tput_reset(info: Database) -> terminfo::Result<()> {
-    if let Some(cap) = info.get::<cap::Reset1String>() {
-        cap.expand().to(io::stdout())?;
-    }
-    if let Some(cap) = info.get::<cap::Reset2String>() {
-        cap.expand().to(io::stdout())?;
-    }
-    if let Some(cap) = info.get::<cap::ResetFile>() {
-        // FIXME
-        cap.expand().to(io::stdout())?;
-    }
-    if let Some(cap) = info.get::<cap::Reset3String>() {
-        cap.expand().to(io::stdout())?;
+fn tput_init(info: &Terminfo) -> io::Result<()> {
+    // the initialization program runs first
+    if let Some(iprog) = info.string("iprog") {
+        let iprog = String::from_utf8_lossy(iprog).into_owned();
+        Command::new(&iprog)
+            .status()
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", iprog, e)))?;
     }
 
-    Ok(())
+    write_sequence(info, false)
 }
 
-fn tput_clear(info: Database) -> terminfo::Result<()> {
-    if let Some(clear) = info.get::<cap::ClearScreen>() {
-        clear.expand().to(io::stdout())?;
+fn tput_reset(info: &Terminfo) -> io::Result<()> {
+    write_sequence(info, true)
+}
+
+fn tput_clear(info: &Terminfo) -> io::Result<()> {
+    if let Some(clear) = info.string("clear") {
+        let mut stdout = io::stdout().lock();
+        terminfo::write_cap(&mut stdout, clear)?;
+        stdout.flush()?;
     }
 
     Ok(())
 }
 
+/// Output the capability `capname`, returning the exit status: booleans
+/// are reported by the exit status alone, and a missing string
+/// capability is an exit status of 1.
+fn tput_capability(info: &Terminfo, capname: &str, params: &[String]) -> io::Result<i32> {
+    match info.kind(capname) {
+        Some(CapKind::Boolean) => Ok(if info.flag(capname) { 0 } else { 1 }),
+        Some(CapKind::Number) => {
+            println!("{}", info.number(capname).unwrap_or(-1));
+            Ok(0)
+        }
+        Some(CapKind::String) => {
+            let Some(value) = info.string(capname) else {
+                return Ok(1);
+            };
+            let params: Vec<Param> = params
+                .iter()
+                .map(|param| match param.parse() {
+                    Ok(n) => Param::Number(n),
+                    Err(_) => Param::String(param.as_bytes().to_vec()),
+                })
+                .collect();
+            let value = terminfo::tparm(value, &params)?;
+
+            let mut stdout = io::stdout().lock();
+            terminfo::write_cap(&mut stdout, &value)?;
+            stdout.flush()?;
+            Ok(0)
+        }
+        None => {
+            eprintln!(
+                "tput: {} '{}'",
+                gettext("unknown terminal capability"),
+                capname
+            );
+            Ok(EXIT_BAD_OPERAND)
+        }
+    }
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // parse command line arguments
     let args = Args::parse();
@@ -84,19 +147,30 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
 
     let info = match args.term {
-        None => Database::from_env().unwrap(),
-        Some(termtype) => Database::from_name(termtype).unwrap(),
+        None => Terminfo::from_env(),
+        Some(ref termtype) => Terminfo::from_name(termtype),
     };
-
-    match args.operand.as_str() {
-        "clear" => tput_clear(info)?,
-        "init" => tput_init(info)?,
-        "reset" => tput_reset(info)?,
-        _ => {
-            eprintln!("{}", gettext("Unknown terminal command"));
-            std::process::exit(1);
+    let info = match info {
+        Ok(info) => info,
+        Err(e) => {
+            eprintln!("tput: {}", e);
+            std::process::exit(EXIT_NO_TERMINAL);
         }
-    }
+    };
 
-    Ok(())
+    let result = match args.operand.as_str() {
+        "clear" => tput_clear(&info).map(|_| 0),
+        "init" => tput_init(&info).map(|_| 0),
+        "reset" => tput_reset(&info).map(|_| 0),
+        capname => tput_capability(&info, capname, &args.params),
+    };
+
+    let exit_code = match result {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("tput: {}", e);
+            EXIT_ERROR
+        }
+    };
+    std::process::exit(exit_code)
 }
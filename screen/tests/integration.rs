@@ -0,0 +1,153 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use std::fs;
+use std::process::{Command, Output};
+
+// a legacy-format terminfo entry with the given standard capabilities
+fn compile(names: &str, booleans: &[u8], numbers: &[i16], strings: &[Option<&str>]) -> Vec<u8> {
+    let mut table = Vec::new();
+    let mut offsets = Vec::new();
+    for s in strings {
+        match s {
+            Some(s) => {
+                offsets.push(table.len() as i16);
+                table.extend(s.as_bytes());
+                table.push(0);
+            }
+            None => offsets.push(-1),
+        }
+    }
+
+    let mut data = Vec::new();
+    for value in [
+        0o432,
+        names.len() as i16 + 1,
+        booleans.len() as i16,
+        numbers.len() as i16,
+        offsets.len() as i16,
+        table.len() as i16,
+    ] {
+        data.extend(value.to_le_bytes());
+    }
+    data.extend(names.as_bytes());
+    data.push(0);
+    data.extend(booleans);
+    if data.len() % 2 != 0 {
+        data.push(0);
+    }
+    for value in numbers.iter().chain(offsets.iter()) {
+        data.extend(value.to_le_bytes());
+    }
+    data.extend(table);
+    data
+}
+
+// a terminfo database in `dir` with the single entry "test-tput"
+fn create_terminfo(dir: &str) {
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(format!("{dir}/t")).unwrap();
+
+    // am; cols, lines; bel, clear, cup
+    let mut strings = vec![None; 11];
+    strings[1] = Some("\x07");
+    strings[5] = Some("\x1b[H\x1b[J");
+    strings[10] = Some("\x1b[%i%p1%d;%p2%dH");
+    let entry = compile(
+        "test-tput|terminal for the tput tests",
+        &[0, 1],
+        &[72, -1, 30],
+        &strings,
+    );
+    fs::write(format!("{dir}/t/test-tput"), entry).unwrap();
+}
+
+fn tput(dir: &str, term: &str, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_tput"))
+        .args(args)
+        .env("TERM", term)
+        .env("TERMINFO", dir)
+        .env("TERMINFO_DIRS", dir)
+        .output()
+        .unwrap()
+}
+
+fn check(output: &Output, expected_out: &str, expected_err: &str, expected_code: i32) {
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected_out);
+    assert_eq!(String::from_utf8_lossy(&output.stderr), expected_err);
+    assert_eq!(output.status.code(), Some(expected_code));
+}
+
+#[test]
+fn test_tput_unknown_terminal() {
+    let dir = &format!("{}/test_tput_unknown_terminal", env!("CARGO_TARGET_TMPDIR"));
+    create_terminfo(dir);
+
+    let output = tput(dir, "no-such-terminal", &["cols"]);
+    check(
+        &output,
+        "",
+        "tput: unknown terminal type \"no-such-terminal\"\n",
+        3,
+    );
+
+    let output = tput(dir, "test-tput", &["-T", "no-such-terminal", "cols"]);
+    check(
+        &output,
+        "",
+        "tput: unknown terminal type \"no-such-terminal\"\n",
+        3,
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_tput_unknown_capname() {
+    let dir = &format!("{}/test_tput_unknown_capname", env!("CARGO_TARGET_TMPDIR"));
+    create_terminfo(dir);
+
+    let output = tput(dir, "test-tput", &["nosuchcap"]);
+    check(
+        &output,
+        "",
+        "tput: unknown terminal capability 'nosuchcap'\n",
+        4,
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_tput_capabilities() {
+    let dir = &format!("{}/test_tput_capabilities", env!("CARGO_TARGET_TMPDIR"));
+    create_terminfo(dir);
+
+    check(&tput(dir, "test-tput", &["cols"]), "72\n", "", 0);
+    check(
+        &tput(dir, "no-such-terminal", &["-T", "test-tput", "lines"]),
+        "30\n",
+        "",
+        0,
+    );
+    check(&tput(dir, "test-tput", &["clear"]), "\x1b[H\x1b[J", "", 0);
+    check(
+        &tput(dir, "test-tput", &["cup", "4", "9"]),
+        "\x1b[5;10H",
+        "",
+        0,
+    );
+
+    // booleans are reported by the exit status, as are missing strings
+    check(&tput(dir, "test-tput", &["am"]), "", "", 0);
+    check(&tput(dir, "test-tput", &["bw"]), "", "", 1);
+    check(&tput(dir, "test-tput", &["el"]), "", "", 1);
+
+    fs::remove_dir_all(dir).unwrap();
+}
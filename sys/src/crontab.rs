@@ -0,0 +1,448 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+extern crate clap;
+extern crate libc;
+extern crate plib;
+
+use clap::Parser;
+use gettextrs::{bind_textdomain_codeset, gettext, textdomain};
+use plib::PROJECT_NAME;
+use std::env;
+use std::ffi::{CStr, CString, OsString};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// the directory of the users' crontabs, which the CRONTAB_DIR
+// environment variable overrides when crontab is not running with
+// privileges
+const CRONTAB_DIR: &str = "/var/spool/cron/crontabs";
+
+// the users allowed, or denied, the use of crontab
+const CRON_ALLOW: &str = "/etc/cron.allow";
+const CRON_DENY: &str = "/etc/cron.deny";
+
+/// crontab - schedule periodic background work
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about)]
+struct Args {
+    /// Edit a copy of the invoking user's crontab entry, and install it when the editor exits.
+    #[arg(short, group = "action")]
+    edit: bool,
+
+    /// Write the invoking user's crontab entry to standard output.
+    #[arg(short, group = "action")]
+    list: bool,
+
+    /// Remove the invoking user's crontab entry.
+    #[arg(short, group = "action")]
+    remove: bool,
+
+    /// The file to install as the invoking user's crontab entry; standard input if omitted.
+    #[arg(group = "action")]
+    file: Option<PathBuf>,
+}
+
+/// The invoking user.
+struct User {
+    name: String,
+    uid: libc::uid_t,
+    gid: libc::gid_t,
+}
+
+fn current_user() -> io::Result<User> {
+    let uid = unsafe { libc::getuid() };
+    let passwd = unsafe { libc::getpwuid(uid) };
+    if passwd.is_null() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            gettext("cannot determine the invoking user"),
+        ));
+    }
+    let name = unsafe { CStr::from_ptr((*passwd).pw_name) };
+    Ok(User {
+        name: name.to_string_lossy().into_owned(),
+        uid,
+        gid: unsafe { (*passwd).pw_gid },
+    })
+}
+
+// whether the user is listed in a cron.allow or cron.deny file
+fn listed_in(path: &str, user: &str) -> Option<bool> {
+    let contents = fs::read_to_string(path).ok()?;
+    Some(contents.lines().any(|line| line.trim() == user))
+}
+
+/// Whether the user may use crontab: a user must be listed in cron.allow
+/// if it exists, and otherwise must not be listed in cron.deny.
+fn is_allowed(user: &User) -> bool {
+    if user.uid == 0 {
+        return true;
+    }
+    match listed_in(CRON_ALLOW, &user.name) {
+        Some(allowed) => allowed,
+        None => !listed_in(CRON_DENY, &user.name).unwrap_or(false),
+    }
+}
+
+// whether crontab runs with the privileges of another user or group,
+// as a set-user-ID or set-group-ID utility
+fn is_privileged() -> bool {
+    unsafe { libc::getuid() != libc::geteuid() || libc::getgid() != libc::getegid() }
+}
+
+fn spool_dir() -> PathBuf {
+    match env::var_os("CRONTAB_DIR") {
+        Some(dir) if !is_privileged() => PathBuf::from(dir),
+        _ => PathBuf::from(CRONTAB_DIR),
+    }
+}
+
+/// Read `path` with the permissions of the invoking user rather than the
+/// privileges crontab runs with, by switching the effective ids to the
+/// real ones while the file is opened.
+fn read_as_invoking_user(path: &Path) -> io::Result<String> {
+    if !is_privileged() {
+        return fs::read_to_string(path);
+    }
+
+    let (euid, egid) = unsafe { (libc::geteuid(), libc::getegid()) };
+    if unsafe { libc::setegid(libc::getgid()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if unsafe { libc::seteuid(libc::getuid()) } != 0 {
+        let e = io::Error::last_os_error();
+        unsafe { libc::setegid(egid) };
+        return Err(e);
+    }
+    let file = fs::File::open(path);
+    if unsafe { libc::seteuid(euid) } != 0 || unsafe { libc::setegid(egid) } != 0 {
+        // continuing with the ids of neither user is not safe
+        eprintln!("crontab: {}", io::Error::last_os_error());
+        std::process::exit(1);
+    }
+
+    let mut contents = String::new();
+    file?.read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+// the fields of a crontab entry: name, and range of values
+const FIELDS: &[(&str, u32, u32)] = &[
+    ("minute", 0, 59),
+    ("hour", 0, 23),
+    ("day of month", 1, 31),
+    ("month", 1, 12),
+    ("day of week", 0, 7),
+];
+
+// a number within a field's range
+fn parse_value(s: &str, min: u32, max: u32) -> Option<u32> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok().filter(|n| (min..=max).contains(n))
+}
+
+/// Check a time field: a comma-separated list of numbers and ranges
+/// (a-b), or an asterisk.  A "/step" may follow an asterisk or a range.
+fn valid_field(field: &str, min: u32, max: u32) -> bool {
+    field.split(',').all(|element| {
+        let (range, step) = match element.split_once('/') {
+            Some((range, step)) => (range, Some(step)),
+            None => (element, None),
+        };
+        let valid_range = match range.split_once('-') {
+            _ if range == "*" => true,
+            Some((first, last)) => {
+                match (parse_value(first, min, max), parse_value(last, min, max)) {
+                    (Some(first), Some(last)) => first <= last,
+                    _ => false,
+                }
+            }
+            None => step.is_none() && parse_value(range, min, max).is_some(),
+        };
+        let valid_step = step.is_none_or(|step| parse_value(step, 1, max).is_some());
+        valid_range && valid_step
+    })
+}
+
+/// Check a crontab entry line, returning a description of the first error.
+fn check_line(line: &str) -> Result<(), String> {
+    let line = line.trim_start();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(());
+    }
+
+    // environment settings, NAME=value, are accepted as most cron
+    // daemons understand them
+    if let Some((name, _)) = line.split_once('=') {
+        let name = name.trim_end();
+        if !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_') {
+            return Ok(());
+        }
+    }
+
+    let mut rest = line;
+    for (name, min, max) in FIELDS {
+        let end = rest.find([' ', '\t']).unwrap_or(rest.len());
+        let field = &rest[..end];
+        if field.is_empty() {
+            return Err(gettext!("missing {} field", name));
+        }
+        if !valid_field(field, *min, *max) {
+            return Err(gettext!("invalid {} field '{}'", name, field));
+        }
+        rest = rest[end..].trim_start_matches([' ', '\t']);
+    }
+    if rest.is_empty() {
+        return Err(gettext("missing command"));
+    }
+
+    Ok(())
+}
+
+/// Check all entries of a crontab, reporting each error with the name
+/// of the file and the line number.
+fn check_crontab(name: &str, contents: &str) -> bool {
+    let mut valid = true;
+    for (i, line) in contents.lines().enumerate() {
+        if let Err(e) = check_line(line) {
+            eprintln!("crontab: {}:{}: {}", name, i + 1, e);
+            valid = false;
+        }
+    }
+    valid
+}
+
+/// Install `contents` as the user's crontab: written to a temporary file
+/// in the spool directory, owned by the user and readable by no one else,
+/// and then renamed into place.
+fn install(user: &User, contents: &str) -> io::Result<()> {
+    let dir = spool_dir();
+    if !dir.exists() {
+        fs::create_dir_all(&dir)?;
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+    }
+
+    let path = dir.join(&user.name);
+    let tmp_path = dir.join(format!(".{}.{}", user.name, std::process::id()));
+    let result = (|| {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&tmp_path)?;
+        file.write_all(contents.as_bytes())?;
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            file.write_all(b"\n")?;
+        }
+        file.sync_all()?;
+        std::os::unix::fs::chown(&tmp_path, Some(user.uid), Some(user.gid))?;
+        fs::rename(&tmp_path, &path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+fn crontab_path(user: &User) -> PathBuf {
+    spool_dir().join(&user.name)
+}
+
+fn no_crontab(user: &User) -> String {
+    gettext!("no crontab for {}", user.name)
+}
+
+fn list(user: &User) -> Result<(), String> {
+    let contents = match fs::read(crontab_path(user)) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(no_crontab(user)),
+        Err(e) => return Err(e.to_string()),
+    };
+    io::stdout().write_all(&contents).map_err(|e| e.to_string())
+}
+
+fn remove(user: &User) -> Result<(), String> {
+    match fs::remove_file(crontab_path(user)) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(no_crontab(user)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn install_file(user: &User, file: &Option<PathBuf>) -> Result<(), String> {
+    let (name, contents) = match file {
+        Some(path) if path.as_os_str() != "-" => {
+            let contents =
+                read_as_invoking_user(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            (path.display().to_string(), contents)
+        }
+        _ => {
+            let mut contents = String::new();
+            io::stdin()
+                .read_to_string(&mut contents)
+                .map_err(|e| e.to_string())?;
+            (gettext("standard input"), contents)
+        }
+    };
+
+    if !check_crontab(&name, &contents) {
+        return Err(gettext("errors in crontab file, not installed"));
+    }
+    install(user, &contents).map_err(|e| e.to_string())
+}
+
+// run the editor on `path`: VISUAL or EDITOR, which may include arguments,
+// as the invoking user
+fn run_editor(user: &User, path: &Path) -> Result<(), String> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| String::from("vi"));
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(path)
+        .gid(unsafe { libc::getgid() })
+        .uid(user.uid)
+        .status()
+        .map_err(|e| format!("{}: {}", editor, e))?;
+    if !status.success() {
+        return Err(gettext!("{}: editor exited unsuccessfully", editor));
+    }
+    Ok(())
+}
+
+// create a new directory, readable by the user only, to edit a copy of
+// the crontab in. TMPDIR is not used when running with privileges, as the
+// invoking user could have it point anywhere
+fn make_private_dir(user: &User) -> io::Result<PathBuf> {
+    let tmp = if is_privileged() {
+        PathBuf::from("/tmp")
+    } else {
+        env::temp_dir()
+    };
+    let template = CString::new(tmp.join("crontab.XXXXXX").into_os_string().into_vec())?;
+    let mut name = template.into_bytes_with_nul();
+    if unsafe { libc::mkdtemp(name.as_mut_ptr() as *mut libc::c_char) }.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    name.pop();
+    let dir = PathBuf::from(OsString::from_vec(name));
+    if let Err(e) = std::os::unix::fs::chown(&dir, Some(user.uid), Some(user.gid)) {
+        let _ = fs::remove_dir(&dir);
+        return Err(e);
+    }
+    Ok(dir)
+}
+
+fn edit(user: &User) -> Result<(), String> {
+    let original = match fs::read_to_string(crontab_path(user)) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.to_string()),
+    };
+
+    // the copy to edit is private to the user
+    let tmp_dir = make_private_dir(user).map_err(|e| e.to_string())?;
+    let tmp_path = tmp_dir.join("crontab");
+    // the editor may leave other files, like backups, next to the copy
+    let remove_copy = || {
+        let _ = fs::remove_dir_all(&tmp_dir);
+    };
+    let result = (|| {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&tmp_path)?;
+        file.write_all(original.as_bytes())?;
+        std::os::unix::fs::chown(&tmp_path, Some(user.uid), Some(user.gid))
+    })();
+    if let Err(e) = result {
+        remove_copy();
+        return Err(format!("{}: {}", tmp_path.display(), e));
+    }
+
+    let result = run_editor(user, &tmp_path).and_then(|_| {
+        read_as_invoking_user(&tmp_path).map_err(|e| format!("{}: {}", tmp_path.display(), e))
+    });
+    let contents = match result {
+        Ok(contents) => contents,
+        Err(e) => {
+            remove_copy();
+            return Err(e);
+        }
+    };
+
+    if contents == original {
+        remove_copy();
+        eprintln!("crontab: {}", gettext("no changes made to crontab"));
+        return Ok(());
+    }
+
+    // on errors, the edits are kept for the user to correct
+    if !check_crontab(&tmp_path.display().to_string(), &contents) {
+        return Err(gettext!(
+            "errors in crontab file, not installed; edits left in {}",
+            tmp_path.display()
+        ));
+    }
+
+    let result = install(user, &contents).map_err(|e| e.to_string());
+    remove_copy();
+    result
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // parse command line arguments
+    let args = Args::parse();
+
+    textdomain(PROJECT_NAME)?;
+    bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
+
+    let user = match current_user() {
+        Ok(user) => user,
+        Err(e) => {
+            eprintln!("crontab: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if !is_allowed(&user) {
+        eprintln!(
+            "crontab: {}",
+            gettext!("{} is not allowed to use crontab", user.name)
+        );
+        std::process::exit(1);
+    }
+
+    let result = if args.list {
+        list(&user)
+    } else if args.remove {
+        remove(&user)
+    } else if args.edit {
+        edit(&user)
+    } else {
+        install_file(&user, &args.file)
+    };
+
+    if let Err(e) = result {
+        eprintln!("crontab: {}", e);
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,355 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+extern crate clap;
+extern crate libc;
+extern crate plib;
+
+use clap::Parser;
+use errno::{errno, set_errno, Errno};
+use gettextrs::{bind_textdomain_codeset, gettext, textdomain};
+use plib::PROJECT_NAME;
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+extern "C" {
+    fn confstr(name: libc::c_int, buf: *mut libc::c_char, len: libc::size_t) -> libc::size_t;
+}
+
+/// How the value of a configuration variable is found.
+#[derive(Clone, Copy)]
+enum Source {
+    Sysconf(libc::c_int),
+    Pathconf(libc::c_int),
+    Confstr(libc::c_int),
+    Constant(i128),
+}
+
+use Source::{Confstr, Constant, Pathconf, Sysconf};
+
+const SYSTEM_VARS: &[(&str, Source)] = &[
+    // sysconf() limits
+    ("ARG_MAX", Sysconf(libc::_SC_ARG_MAX)),
+    ("ATEXIT_MAX", Sysconf(libc::_SC_ATEXIT_MAX)),
+    ("BC_BASE_MAX", Sysconf(libc::_SC_BC_BASE_MAX)),
+    ("BC_DIM_MAX", Sysconf(libc::_SC_BC_DIM_MAX)),
+    ("BC_SCALE_MAX", Sysconf(libc::_SC_BC_SCALE_MAX)),
+    ("BC_STRING_MAX", Sysconf(libc::_SC_BC_STRING_MAX)),
+    ("CHILD_MAX", Sysconf(libc::_SC_CHILD_MAX)),
+    ("CLK_TCK", Sysconf(libc::_SC_CLK_TCK)),
+    ("COLL_WEIGHTS_MAX", Sysconf(libc::_SC_COLL_WEIGHTS_MAX)),
+    ("DELAYTIMER_MAX", Sysconf(libc::_SC_DELAYTIMER_MAX)),
+    ("EXPR_NEST_MAX", Sysconf(libc::_SC_EXPR_NEST_MAX)),
+    ("HOST_NAME_MAX", Sysconf(libc::_SC_HOST_NAME_MAX)),
+    ("IOV_MAX", Sysconf(libc::_SC_IOV_MAX)),
+    ("LINE_MAX", Sysconf(libc::_SC_LINE_MAX)),
+    ("LOGIN_NAME_MAX", Sysconf(libc::_SC_LOGIN_NAME_MAX)),
+    ("NGROUPS_MAX", Sysconf(libc::_SC_NGROUPS_MAX)),
+    ("NPROCESSORS_CONF", Sysconf(libc::_SC_NPROCESSORS_CONF)),
+    ("NPROCESSORS_ONLN", Sysconf(libc::_SC_NPROCESSORS_ONLN)),
+    ("OPEN_MAX", Sysconf(libc::_SC_OPEN_MAX)),
+    ("PAGESIZE", Sysconf(libc::_SC_PAGESIZE)),
+    ("PAGE_SIZE", Sysconf(libc::_SC_PAGESIZE)),
+    ("RE_DUP_MAX", Sysconf(libc::_SC_RE_DUP_MAX)),
+    ("RTSIG_MAX", Sysconf(libc::_SC_RTSIG_MAX)),
+    ("SEM_NSEMS_MAX", Sysconf(libc::_SC_SEM_NSEMS_MAX)),
+    ("SEM_VALUE_MAX", Sysconf(libc::_SC_SEM_VALUE_MAX)),
+    ("SIGQUEUE_MAX", Sysconf(libc::_SC_SIGQUEUE_MAX)),
+    ("STREAM_MAX", Sysconf(libc::_SC_STREAM_MAX)),
+    ("SYMLOOP_MAX", Sysconf(libc::_SC_SYMLOOP_MAX)),
+    ("TIMER_MAX", Sysconf(libc::_SC_TIMER_MAX)),
+    ("TTY_NAME_MAX", Sysconf(libc::_SC_TTY_NAME_MAX)),
+    ("TZNAME_MAX", Sysconf(libc::_SC_TZNAME_MAX)),
+    // sysconf() options and versions
+    ("_POSIX_VERSION", Sysconf(libc::_SC_VERSION)),
+    ("POSIX2_VERSION", Sysconf(libc::_SC_2_VERSION)),
+    ("POSIX2_C_BIND", Sysconf(libc::_SC_2_C_BIND)),
+    ("POSIX2_C_DEV", Sysconf(libc::_SC_2_C_DEV)),
+    ("POSIX2_CHAR_TERM", Sysconf(libc::_SC_2_CHAR_TERM)),
+    ("POSIX2_FORT_DEV", Sysconf(libc::_SC_2_FORT_DEV)),
+    ("POSIX2_FORT_RUN", Sysconf(libc::_SC_2_FORT_RUN)),
+    ("POSIX2_LOCALEDEF", Sysconf(libc::_SC_2_LOCALEDEF)),
+    ("POSIX2_SW_DEV", Sysconf(libc::_SC_2_SW_DEV)),
+    ("POSIX2_UPE", Sysconf(libc::_SC_2_UPE)),
+    ("_POSIX_ASYNCHRONOUS_IO", Sysconf(libc::_SC_ASYNCHRONOUS_IO)),
+    ("_POSIX_FSYNC", Sysconf(libc::_SC_FSYNC)),
+    ("_POSIX_JOB_CONTROL", Sysconf(libc::_SC_JOB_CONTROL)),
+    ("_POSIX_MAPPED_FILES", Sysconf(libc::_SC_MAPPED_FILES)),
+    ("_POSIX_MONOTONIC_CLOCK", Sysconf(libc::_SC_MONOTONIC_CLOCK)),
+    ("_POSIX_REGEXP", Sysconf(libc::_SC_REGEXP)),
+    ("_POSIX_SAVED_IDS", Sysconf(libc::_SC_SAVED_IDS)),
+    ("_POSIX_SEMAPHORES", Sysconf(libc::_SC_SEMAPHORES)),
+    ("_POSIX_SHELL", Sysconf(libc::_SC_SHELL)),
+    ("_POSIX_THREADS", Sysconf(libc::_SC_THREADS)),
+    ("_POSIX_TIMERS", Sysconf(libc::_SC_TIMERS)),
+    ("_POSIX_V7_ILP32_OFF32", Sysconf(libc::_SC_V7_ILP32_OFF32)),
+    ("_POSIX_V7_ILP32_OFFBIG", Sysconf(libc::_SC_V7_ILP32_OFFBIG)),
+    ("_POSIX_V7_LP64_OFF64", Sysconf(libc::_SC_V7_LP64_OFF64)),
+    ("_POSIX_V7_LPBIG_OFFBIG", Sysconf(libc::_SC_V7_LPBIG_OFFBIG)),
+    ("_XOPEN_CRYPT", Sysconf(libc::_SC_XOPEN_CRYPT)),
+    ("_XOPEN_ENH_I18N", Sysconf(libc::_SC_XOPEN_ENH_I18N)),
+    ("_XOPEN_REALTIME", Sysconf(libc::_SC_XOPEN_REALTIME)),
+    ("_XOPEN_SHM", Sysconf(libc::_SC_XOPEN_SHM)),
+    ("_XOPEN_UNIX", Sysconf(libc::_SC_XOPEN_UNIX)),
+    ("_XOPEN_VERSION", Sysconf(libc::_SC_XOPEN_VERSION)),
+    // <limits.h> minimum values
+    ("_POSIX_ARG_MAX", Constant(4096)),
+    ("_POSIX_CHILD_MAX", Constant(25)),
+    ("_POSIX_HOST_NAME_MAX", Constant(255)),
+    ("_POSIX_LINK_MAX", Constant(8)),
+    ("_POSIX_LOGIN_NAME_MAX", Constant(9)),
+    ("_POSIX_MAX_CANON", Constant(255)),
+    ("_POSIX_MAX_INPUT", Constant(255)),
+    ("_POSIX_NAME_MAX", Constant(14)),
+    ("_POSIX_NGROUPS_MAX", Constant(8)),
+    ("_POSIX_OPEN_MAX", Constant(20)),
+    ("_POSIX_PATH_MAX", Constant(256)),
+    ("_POSIX_PIPE_BUF", Constant(512)),
+    ("_POSIX_RE_DUP_MAX", Constant(255)),
+    ("_POSIX_SSIZE_MAX", Constant(32767)),
+    ("_POSIX_STREAM_MAX", Constant(8)),
+    ("_POSIX_SYMLINK_MAX", Constant(255)),
+    ("_POSIX_SYMLOOP_MAX", Constant(8)),
+    ("_POSIX_TTY_NAME_MAX", Constant(9)),
+    ("_POSIX_TZNAME_MAX", Constant(6)),
+    ("_POSIX2_BC_BASE_MAX", Constant(99)),
+    ("_POSIX2_BC_DIM_MAX", Constant(2048)),
+    ("_POSIX2_BC_SCALE_MAX", Constant(99)),
+    ("_POSIX2_BC_STRING_MAX", Constant(1000)),
+    ("_POSIX2_COLL_WEIGHTS_MAX", Constant(2)),
+    ("_POSIX2_EXPR_NEST_MAX", Constant(32)),
+    ("_POSIX2_LINE_MAX", Constant(2048)),
+    ("_POSIX2_RE_DUP_MAX", Constant(255)),
+    ("_XOPEN_IOV_MAX", Constant(16)),
+    ("_XOPEN_NAME_MAX", Constant(255)),
+    ("_XOPEN_PATH_MAX", Constant(1024)),
+    // <limits.h> numerical limits
+    ("CHAR_BIT", Constant(8)),
+    ("CHAR_MAX", Constant(libc::c_char::MAX as i128)),
+    ("CHAR_MIN", Constant(libc::c_char::MIN as i128)),
+    ("INT_MAX", Constant(libc::c_int::MAX as i128)),
+    ("INT_MIN", Constant(libc::c_int::MIN as i128)),
+    ("LONG_BIT", Constant(libc::c_long::BITS as i128)),
+    ("LONG_MAX", Constant(libc::c_long::MAX as i128)),
+    ("LONG_MIN", Constant(libc::c_long::MIN as i128)),
+    ("MB_LEN_MAX", Constant(16)),
+    ("SCHAR_MAX", Constant(libc::c_schar::MAX as i128)),
+    ("SCHAR_MIN", Constant(libc::c_schar::MIN as i128)),
+    ("SHRT_MAX", Constant(libc::c_short::MAX as i128)),
+    ("SHRT_MIN", Constant(libc::c_short::MIN as i128)),
+    ("SSIZE_MAX", Constant(libc::ssize_t::MAX as i128)),
+    ("UCHAR_MAX", Constant(libc::c_uchar::MAX as i128)),
+    ("UINT_MAX", Constant(libc::c_uint::MAX as i128)),
+    ("ULONG_MAX", Constant(libc::c_ulong::MAX as i128)),
+    ("USHRT_MAX", Constant(libc::c_ushort::MAX as i128)),
+    ("WORD_BIT", Constant(libc::c_int::BITS as i128)),
+    ("NL_ARGMAX", Constant(9)),
+    ("NL_LANGMAX", Constant(14)),
+    ("NL_MSGMAX", Constant(32767)),
+    ("NL_SETMAX", Constant(255)),
+    ("NL_TEXTMAX", Constant(2048)),
+    ("NZERO", Constant(20)),
+];
+
+// the confstr() strings, whose names the libc crate does not define
+#[cfg(target_os = "linux")]
+const CONFSTR_VARS: &[(&str, Source)] = &[
+    ("PATH", Confstr(0)),
+    ("POSIX_V7_WIDTH_RESTRICTED_ENVS", Confstr(5)),
+    ("POSIX_V7_ILP32_OFF32_CFLAGS", Confstr(1132)),
+    ("POSIX_V7_ILP32_OFF32_LDFLAGS", Confstr(1133)),
+    ("POSIX_V7_ILP32_OFF32_LIBS", Confstr(1134)),
+    ("POSIX_V7_ILP32_OFFBIG_CFLAGS", Confstr(1136)),
+    ("POSIX_V7_ILP32_OFFBIG_LDFLAGS", Confstr(1137)),
+    ("POSIX_V7_ILP32_OFFBIG_LIBS", Confstr(1138)),
+    ("POSIX_V7_LP64_OFF64_CFLAGS", Confstr(1140)),
+    ("POSIX_V7_LP64_OFF64_LDFLAGS", Confstr(1141)),
+    ("POSIX_V7_LP64_OFF64_LIBS", Confstr(1142)),
+    ("POSIX_V7_LPBIG_OFFBIG_CFLAGS", Confstr(1144)),
+    ("POSIX_V7_LPBIG_OFFBIG_LDFLAGS", Confstr(1145)),
+    ("POSIX_V7_LPBIG_OFFBIG_LIBS", Confstr(1146)),
+];
+
+#[cfg(not(target_os = "linux"))]
+const CONFSTR_VARS: &[(&str, Source)] = &[("PATH", Confstr(libc::_CS_PATH))];
+
+const PATH_VARS: &[(&str, Source)] = &[
+    ("FILESIZEBITS", Pathconf(libc::_PC_FILESIZEBITS)),
+    ("LINK_MAX", Pathconf(libc::_PC_LINK_MAX)),
+    ("MAX_CANON", Pathconf(libc::_PC_MAX_CANON)),
+    ("MAX_INPUT", Pathconf(libc::_PC_MAX_INPUT)),
+    ("NAME_MAX", Pathconf(libc::_PC_NAME_MAX)),
+    ("PATH_MAX", Pathconf(libc::_PC_PATH_MAX)),
+    ("PIPE_BUF", Pathconf(libc::_PC_PIPE_BUF)),
+    ("POSIX2_SYMLINKS", Pathconf(libc::_PC_2_SYMLINKS)),
+    ("POSIX_ALLOC_SIZE_MIN", Pathconf(libc::_PC_ALLOC_SIZE_MIN)),
+    (
+        "POSIX_REC_INCR_XFER_SIZE",
+        Pathconf(libc::_PC_REC_INCR_XFER_SIZE),
+    ),
+    (
+        "POSIX_REC_MAX_XFER_SIZE",
+        Pathconf(libc::_PC_REC_MAX_XFER_SIZE),
+    ),
+    (
+        "POSIX_REC_MIN_XFER_SIZE",
+        Pathconf(libc::_PC_REC_MIN_XFER_SIZE),
+    ),
+    ("POSIX_REC_XFER_ALIGN", Pathconf(libc::_PC_REC_XFER_ALIGN)),
+    ("SYMLINK_MAX", Pathconf(libc::_PC_SYMLINK_MAX)),
+    (
+        "_POSIX_CHOWN_RESTRICTED",
+        Pathconf(libc::_PC_CHOWN_RESTRICTED),
+    ),
+    ("_POSIX_NO_TRUNC", Pathconf(libc::_PC_NO_TRUNC)),
+    ("_POSIX_VDISABLE", Pathconf(libc::_PC_VDISABLE)),
+    ("_POSIX_ASYNC_IO", Pathconf(libc::_PC_ASYNC_IO)),
+    ("_POSIX_PRIO_IO", Pathconf(libc::_PC_PRIO_IO)),
+    ("_POSIX_SYNC_IO", Pathconf(libc::_PC_SYNC_IO)),
+];
+
+// the programming environments accepted by -v
+const SPECIFICATIONS: &[(&str, libc::c_int)] = &[
+    ("POSIX_V7_ILP32_OFF32", libc::_SC_V7_ILP32_OFF32),
+    ("POSIX_V7_ILP32_OFFBIG", libc::_SC_V7_ILP32_OFFBIG),
+    ("POSIX_V7_LP64_OFF64", libc::_SC_V7_LP64_OFF64),
+    ("POSIX_V7_LPBIG_OFFBIG", libc::_SC_V7_LPBIG_OFFBIG),
+    ("POSIX_V6_ILP32_OFF32", libc::_SC_V6_ILP32_OFF32),
+    ("POSIX_V6_ILP32_OFFBIG", libc::_SC_V6_ILP32_OFFBIG),
+    ("POSIX_V6_LP64_OFF64", libc::_SC_V6_LP64_OFF64),
+    ("POSIX_V6_LPBIG_OFFBIG", libc::_SC_V6_LPBIG_OFFBIG),
+];
+
+/// getconf - get configuration values
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about)]
+struct Args {
+    /// Report values for the given programming environment.
+    #[arg(short = 'v', value_name = "SPECIFICATION")]
+    specification: Option<String>,
+
+    /// The system configuration variable, or the file system configuration
+    /// variable when a pathname is given.
+    name: String,
+
+    /// The pathname for a file system configuration variable.
+    pathname: Option<PathBuf>,
+}
+
+/// Find a variable in a table.  Names are accepted with or without a
+/// leading underscore, so _POSIX2_VERSION is POSIX2_VERSION and
+/// POSIX_VERSION is _POSIX_VERSION.
+fn lookup(table: &[(&str, Source)], name: &str) -> Option<Source> {
+    let bare = name.strip_prefix('_').unwrap_or(name);
+    table
+        .iter()
+        .find(|(n, _)| *n == name)
+        .or_else(|| {
+            table
+                .iter()
+                .find(|(n, _)| n.strip_prefix('_').unwrap_or(n) == bare)
+        })
+        .map(|(_, source)| *source)
+}
+
+fn sysconf_value(name: libc::c_int) -> Option<i128> {
+    // sysconf returns -1 for both unsupported and indeterminate values
+    let value = unsafe { libc::sysconf(name) };
+    if value == -1 {
+        None
+    } else {
+        Some(value as i128)
+    }
+}
+
+fn pathconf_value(path: &Path, name: libc::c_int) -> io::Result<Option<i128>> {
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    // pathconf returns -1 without changing errno for values with no limit
+    set_errno(Errno(0));
+    let value = unsafe { libc::pathconf(path.as_ptr(), name) };
+    if value == -1 {
+        return match errno().0 {
+            0 | libc::EINVAL => Ok(None),
+            e => Err(io::Error::from_raw_os_error(e)),
+        };
+    }
+    Ok(Some(value as i128))
+}
+
+fn confstr_value(name: libc::c_int) -> Option<String> {
+    let len = unsafe { confstr(name, std::ptr::null_mut(), 0) };
+    if len == 0 {
+        return None;
+    }
+    let mut buf = vec![0u8; len];
+    unsafe { confstr(name, buf.as_mut_ptr() as *mut libc::c_char, len) };
+    buf.truncate(len - 1);
+    Some(String::from_utf8_lossy(&buf).into_owned())
+}
+
+/// The value of a variable, or None if it is undefined on this system.
+fn value(source: Source, path: Option<&Path>) -> io::Result<Option<String>> {
+    let value = match source {
+        Sysconf(name) => sysconf_value(name).map(|n| n.to_string()),
+        Pathconf(name) => pathconf_value(path.unwrap(), name)?.map(|n| n.to_string()),
+        Confstr(name) => confstr_value(name),
+        Constant(n) => Some(n.to_string()),
+    };
+    Ok(value)
+}
+
+fn getconf(args: &Args) -> Result<(), String> {
+    if let Some(spec) = &args.specification {
+        let supported = SPECIFICATIONS
+            .iter()
+            .find(|(name, _)| name == spec)
+            .is_some_and(|(_, sc)| sysconf_value(*sc).is_some_and(|n| n > 0));
+        if !supported {
+            return Err(gettext!("unsupported programming environment '{}'", spec));
+        }
+    }
+
+    let source = match &args.pathname {
+        None => lookup(SYSTEM_VARS, &args.name).or_else(|| lookup(CONFSTR_VARS, &args.name)),
+        Some(_) => lookup(PATH_VARS, &args.name),
+    };
+    let Some(source) = source else {
+        if args.pathname.is_none() && lookup(PATH_VARS, &args.name).is_some() {
+            return Err(gettext!("'{}' requires a pathname", args.name));
+        }
+        return Err(gettext!("unrecognized variable '{}'", args.name));
+    };
+
+    let path = args.pathname.as_deref();
+    match value(source, path) {
+        Ok(Some(value)) => println!("{}", value),
+        Ok(None) => println!("undefined"),
+        Err(e) => return Err(format!("{}: {}", path.unwrap().display(), e)),
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // parse command line arguments
+    let args = Args::parse();
+
+    textdomain(PROJECT_NAME)?;
+    bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
+
+    if let Err(e) = getconf(&args) {
+        eprintln!("getconf: {}", e);
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
@@ -11,52 +11,68 @@ extern crate clap;
 extern crate libc;
 extern crate plib;
 
-use clap::Parser;
-use gettextrs::{bind_textdomain_codeset, textdomain};
+use clap::{ArgAction, Parser};
+use gettextrs::{bind_textdomain_codeset, gettext, textdomain};
 #[cfg(not(target_os = "macos"))]
 use libc::{msgctl, msgget, msqid_ds};
 use libc::{semctl, semget, shmctl, shmget, shmid_ds};
 use plib::PROJECT_NAME;
 use std::ffi::{c_int, c_ushort};
-use std::io::{self, Error, ErrorKind};
+use std::io::{self, Error};
 use std::ptr;
 
 /// ipcrm - remove an XSI message queue, semaphore set, or shared memory segment identifier
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about)]
 struct Args {
-    /// Remove the shared memory identifier semid from the system.
-    #[arg(short = 's', long)]
-    semid: Option<i32>,
+    /// Remove the semaphore identifier semid from the system.
+    #[arg(short = 's', long, action = ArgAction::Append)]
+    semid: Vec<i32>,
 
-    /// Remove the shared memory identifier, created with key semkey, from the system.
-    #[arg(short = 'S', long)]
-    semkey: Option<i32>,
+    /// Remove the semaphore identifier, created with key semkey, from the system.
+    #[arg(short = 'S', long, action = ArgAction::Append, value_parser = parse_key)]
+    semkey: Vec<libc::key_t>,
 
     /// Remove the shared memory identifier shmid from the system.
-    #[arg(short = 'm', long)]
-    shmid: Option<i32>,
+    #[arg(short = 'm', long, action = ArgAction::Append)]
+    shmid: Vec<i32>,
 
     /// Remove the shared memory identifier, created with key shmkey, from the system.
-    #[arg(short = 'M', long)]
-    shmkey: Option<i32>,
+    #[arg(short = 'M', long, action = ArgAction::Append, value_parser = parse_key)]
+    shmkey: Vec<libc::key_t>,
 
     /// Remove the message queue identifier msgid from the system.
     #[cfg(not(target_os = "macos"))]
-    #[arg(short = 'q', long)]
-    msgid: Option<i32>,
+    #[arg(short = 'q', long, action = ArgAction::Append)]
+    msgid: Vec<i32>,
 
     /// Remove the message queue identifier, created with key msgkey, from the system.
     #[cfg(not(target_os = "macos"))]
-    #[arg(short = 'Q', long)]
-    msgkey: Option<i32>,
+    #[arg(short = 'Q', long, action = ArgAction::Append, value_parser = parse_key)]
+    msgkey: Vec<libc::key_t>,
 }
 
-#[cfg(not(target_os = "macos"))]
-fn msg_key_lookup(msgkey: i32) -> io::Result<i32> {
-    if msgkey == libc::IPC_PRIVATE {
-        return Err(Error::new(ErrorKind::Other, "Invalid key"));
+/// Parse a key: decimal, or hexadecimal with a 0x prefix as ipcs writes it.
+fn parse_key(s: &str) -> Result<libc::key_t, String> {
+    let key = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).map(|n| n as libc::key_t),
+        None => s
+            .parse::<libc::key_t>()
+            .or_else(|_| s.parse::<u32>().map(|n| n as libc::key_t)),
+    };
+    key.map_err(|_| gettext!("invalid key '{}'", s))
+}
+
+fn check_key(key: libc::key_t) -> io::Result<()> {
+    if key == libc::IPC_PRIVATE {
+        return Err(Error::other(gettext("Invalid key")));
     }
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn msg_key_lookup(msgkey: libc::key_t) -> io::Result<i32> {
+    check_key(msgkey)?;
     let res: i32 = unsafe { msgget(msgkey, 0) };
 
     if res < 0 {
@@ -83,10 +99,8 @@ fn msg_rm(msgid: i32) -> io::Result<i32> {
     }
 }
 
-fn shm_key_lookup(shmkey: i32) -> io::Result<i32> {
-    if shmkey == libc::IPC_PRIVATE {
-        return Err(Error::new(ErrorKind::Other, "Invalid key"));
-    }
+fn shm_key_lookup(shmkey: libc::key_t) -> io::Result<i32> {
+    check_key(shmkey)?;
     let res: i32 = unsafe { shmget(shmkey, 0, 0) };
 
     if res < 0 {
@@ -112,10 +126,8 @@ fn shm_rm(shmid: i32) -> io::Result<i32> {
     }
 }
 
-fn sem_key_lookup(semkey: i32) -> io::Result<i32> {
-    if semkey == libc::IPC_PRIVATE {
-        return Err(Error::new(ErrorKind::Other, "Invalid key"));
-    }
+fn sem_key_lookup(semkey: libc::key_t) -> io::Result<i32> {
+    check_key(semkey)?;
     let res: i32 = unsafe { semget(semkey, 0, 0) };
 
     if res < 0 {
@@ -125,13 +137,12 @@ fn sem_key_lookup(semkey: i32) -> io::Result<i32> {
     }
 }
 
-// Define the union semun as per your requirements
+// the semctl() argument, which callers must define
 #[repr(C)]
 union semun {
     val: c_int,               // for SETVAL
     buf: *mut libc::semid_ds, // for IPC_STAT and IPC_SET
     array: *mut c_ushort,     // for GETALL and SETALL
-                              // Depending on your platform, you might need to add other fields as well
 }
 
 fn sem_rm(semid: i32) -> io::Result<i32> {
@@ -146,38 +157,48 @@ fn sem_rm(semid: i32) -> io::Result<i32> {
     }
 }
 
-fn remove_ipcs(args: &Args) -> io::Result<()> {
-    // remove semaphores
-    if let Some(semkey) = args.semkey {
-        let semid = sem_key_lookup(semkey)?;
-        sem_rm(semid)?;
-    }
-    if let Some(semid) = args.semid {
-        sem_rm(semid)?;
-    }
-
-    // remove shared memory segments
-    if let Some(shmkey) = args.shmkey {
-        let shmid = shm_key_lookup(shmkey)?;
-        shm_rm(shmid)?;
-    }
-    if let Some(shmid) = args.shmid {
-        shm_rm(shmid)?;
-    }
+/// Remove each identifier, reporting those which cannot be removed;
+/// returns whether all were removed.
+fn remove_ipcs(args: &Args) -> bool {
+    let mut success = true;
+    let mut report = |what: String, result: io::Result<i32>| {
+        if let Err(e) = result {
+            eprintln!("ipcrm: {}: {}", what, e);
+            success = false;
+        }
+    };
 
     // remove message queues
     #[cfg(not(target_os = "macos"))]
     {
-        if let Some(msgkey) = args.msgkey {
-            let msgid = msg_key_lookup(msgkey)?;
-            msg_rm(msgid)?;
+        for msgid in &args.msgid {
+            report(gettext!("msqid {}", msgid), msg_rm(*msgid));
         }
-        if let Some(msgid) = args.msgid {
-            msg_rm(msgid)?;
+        for msgkey in &args.msgkey {
+            let result = msg_key_lookup(*msgkey).and_then(msg_rm);
+            report(gettext!("msgkey {}", format!("{:#x}", msgkey)), result);
         }
     }
 
-    Ok(())
+    // remove shared memory segments
+    for shmid in &args.shmid {
+        report(gettext!("shmid {}", shmid), shm_rm(*shmid));
+    }
+    for shmkey in &args.shmkey {
+        let result = shm_key_lookup(*shmkey).and_then(shm_rm);
+        report(gettext!("shmkey {}", format!("{:#x}", shmkey)), result);
+    }
+
+    // remove semaphores
+    for semid in &args.semid {
+        report(gettext!("semid {}", semid), sem_rm(*semid));
+    }
+    for semkey in &args.semkey {
+        let result = sem_key_lookup(*semkey).and_then(sem_rm);
+        report(gettext!("semkey {}", format!("{:#x}", semkey)), result);
+    }
+
+    success
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -187,12 +208,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     textdomain(PROJECT_NAME)?;
     bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
 
-    let mut exit_code = 0;
-
-    if let Err(e) = remove_ipcs(&args) {
-        exit_code = 1;
-        eprintln!("{}", e);
-    }
+    let exit_code = if remove_ipcs(&args) { 0 } else { 1 };
 
     std::process::exit(exit_code)
 }
@@ -0,0 +1,420 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+extern crate clap;
+extern crate libc;
+extern crate plib;
+
+use clap::Parser;
+use gettextrs::{bind_textdomain_codeset, gettext, textdomain};
+use plib::PROJECT_NAME;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::fs;
+
+// where the kernel reports the status of each facility
+const SOURCE: &str = "/proc/sysvipc";
+
+// the shared memory mode bit of a segment marked for removal
+const SHM_DEST: i64 = 0o1000;
+
+/// ipcs - report XSI interprocess communication facilities status
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about)]
+struct Args {
+    /// Write information about active message queues.
+    #[arg(short = 'q')]
+    queues: bool,
+
+    /// Write information about active shared memory segments.
+    #[arg(short = 'm')]
+    shared_memory: bool,
+
+    /// Write information about active semaphore sets.
+    #[arg(short = 's')]
+    semaphores: bool,
+
+    /// Use all print options (-b, -c, -o, -p and -t).
+    #[arg(short = 'a')]
+    all: bool,
+
+    /// Write the maximum number of bytes in message queues, the size of
+    /// segments, and the number of semaphores in each semaphore set.
+    #[arg(short = 'b')]
+    max_size: bool,
+
+    /// Write the creator's user name and group name.
+    #[arg(short = 'c')]
+    creator: bool,
+
+    /// Write the outstanding usage: the bytes and messages on message
+    /// queues, and the number of processes attached to segments.
+    #[arg(short = 'o')]
+    outstanding: bool,
+
+    /// Write the process IDs of the last send and receive on message
+    /// queues, and of the creator and last attach or detach of segments.
+    #[arg(short = 'p')]
+    pid: bool,
+
+    /// Write the times of the last control operation and of the last
+    /// operations which changed each facility.
+    #[arg(short = 't')]
+    time: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Facility {
+    Queues,
+    SharedMemory,
+    Semaphores,
+}
+
+impl Facility {
+    fn letter(self) -> &'static str {
+        match self {
+            Facility::Queues => "q",
+            Facility::SharedMemory => "m",
+            Facility::Semaphores => "s",
+        }
+    }
+
+    fn title(self) -> String {
+        match self {
+            Facility::Queues => gettext("Message Queues"),
+            Facility::SharedMemory => gettext("Shared Memory"),
+            Facility::Semaphores => gettext("Semaphores"),
+        }
+    }
+
+    // the status file, and the name of its identifier column
+    fn status(self) -> (&'static str, &'static str) {
+        match self {
+            Facility::Queues => ("msg", "msqid"),
+            Facility::SharedMemory => ("shm", "shmid"),
+            Facility::Semaphores => ("sem", "semid"),
+        }
+    }
+}
+
+/// An entry of a status file: its values by column name.
+type Entry = HashMap<String, i64>;
+
+/// Read the entries of a facility, or None if it is not in the system.
+fn read_entries(facility: Facility) -> Option<Vec<Entry>> {
+    let (file, id) = facility.status();
+    let contents = fs::read_to_string(format!("{}/{}", SOURCE, file)).ok()?;
+    let mut lines = contents.lines();
+    let names: Vec<&str> = lines.next()?.split_whitespace().collect();
+
+    let mut entries: Vec<Entry> = lines
+        .map(|line| {
+            let values = line.split_whitespace().map(|v| v.parse().unwrap_or(0));
+            names.iter().map(|n| n.to_string()).zip(values).collect()
+        })
+        .collect();
+    entries.sort_by_key(|entry| entry.get(id).copied());
+    Some(entries)
+}
+
+fn user_name(uid: i64) -> String {
+    let passwd = unsafe { libc::getpwuid(uid as libc::uid_t) };
+    if passwd.is_null() {
+        return uid.to_string();
+    }
+    unsafe { CStr::from_ptr((*passwd).pw_name) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn group_name(gid: i64) -> String {
+    let group = unsafe { libc::getgrgid(gid as libc::gid_t) };
+    if group.is_null() {
+        return gid.to_string();
+    }
+    unsafe { CStr::from_ptr((*group).gr_name) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// The access modes: two status characters, then read and write (or, for
+/// semaphores, alter) permission for the owner, group and others.  The
+/// status of a segment is D when it has been removed but is still attached.
+fn mode_string(facility: Facility, perms: i64) -> String {
+    // the kernel reports the mode in octal digits
+    let mode = i64::from_str_radix(&perms.to_string(), 8).unwrap_or(0);
+
+    let mut s = String::new();
+    s.push(
+        if facility == Facility::SharedMemory && mode & SHM_DEST != 0 {
+            'D'
+        } else {
+            '-'
+        },
+    );
+    s.push('-');
+    let write = if facility == Facility::Semaphores {
+        'a'
+    } else {
+        'w'
+    };
+    for shift in [6, 3, 0] {
+        s.push(if mode >> shift & 0o4 != 0 { 'r' } else { '-' });
+        s.push(if mode >> shift & 0o2 != 0 { write } else { '-' });
+        s.push('-');
+    }
+    s
+}
+
+/// A time as hours:minutes:seconds, or "no-entry" if never set.
+fn time_string(t: i64) -> String {
+    if t == 0 {
+        return gettext("no-entry");
+    }
+    let t = t as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe { libc::localtime_r(&t, &mut tm) };
+    format!("{}:{:02}:{:02}", tm.tm_hour, tm.tm_min, tm.tm_sec)
+}
+
+/// The current date, in the default format of date.
+fn date_string() -> String {
+    let now = unsafe { libc::time(std::ptr::null_mut()) };
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe { libc::localtime_r(&now, &mut tm) };
+
+    let format = CString::new("%a %b %e %H:%M:%S %Z %Y").unwrap();
+    let mut buf = [0u8; 128];
+    let len = unsafe {
+        libc::strftime(
+            buf.as_mut_ptr() as *mut libc::c_char,
+            buf.len(),
+            format.as_ptr(),
+            &tm,
+        )
+    };
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+// the maximum number of bytes on a message queue, which the status file
+// does not report
+#[cfg(target_os = "linux")]
+fn queue_max_bytes(msqid: i64) -> String {
+    let mut ds: libc::msqid_ds = unsafe { std::mem::zeroed() };
+    if unsafe { libc::msgctl(msqid as libc::c_int, libc::IPC_STAT, &mut ds) } < 0 {
+        return String::from("-");
+    }
+    ds.msg_qbytes.to_string()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn queue_max_bytes(_msqid: i64) -> String {
+    String::from("-")
+}
+
+fn field(entry: &Entry, name: &str) -> i64 {
+    entry.get(name).copied().unwrap_or(0)
+}
+
+fn number(entry: &Entry, name: &str) -> String {
+    field(entry, name).to_string()
+}
+
+/// The option which selects a column.
+#[derive(Clone, Copy, PartialEq)]
+enum Group {
+    Basic,
+    Creator,
+    Outstanding,
+    MaxSize,
+    Pid,
+    Time,
+}
+
+/// How a column's value is found in an entry.
+#[derive(Clone, Copy)]
+enum Value {
+    Letter,
+    Id,
+    Key,
+    Mode,
+    User(&'static str),
+    GroupName(&'static str),
+    Number(&'static str),
+    Time(&'static str),
+    QueueMaxBytes,
+}
+
+impl Value {
+    fn format(self, facility: Facility, entry: &Entry) -> String {
+        match self {
+            Value::Letter => facility.letter().to_string(),
+            Value::Id => number(entry, facility.status().1),
+            Value::Key => format!("0x{:08x}", field(entry, "key") as i32),
+            Value::Mode => mode_string(facility, field(entry, "perms")),
+            Value::User(name) => user_name(field(entry, name)),
+            Value::GroupName(name) => group_name(field(entry, name)),
+            Value::Number(name) => number(entry, name),
+            Value::Time(name) => time_string(field(entry, name)),
+            Value::QueueMaxBytes => queue_max_bytes(field(entry, "msqid")),
+        }
+    }
+
+    // names are aligned left, and numbers right
+    fn is_numeric(self) -> bool {
+        !matches!(
+            self,
+            Value::Letter | Value::Mode | Value::User(_) | Value::GroupName(_)
+        )
+    }
+}
+
+/// A column: its heading, the option selecting it, the facility it
+/// applies to (all when None), and its value.
+type Column = (&'static str, Group, Option<Facility>, Value);
+
+const QUEUES: Option<Facility> = Some(Facility::Queues);
+const SHARED_MEMORY: Option<Facility> = Some(Facility::SharedMemory);
+const SEMAPHORES: Option<Facility> = Some(Facility::Semaphores);
+
+// the columns, in the order they are written
+const COLUMNS: &[Column] = &[
+    ("T", Group::Basic, None, Value::Letter),
+    ("ID", Group::Basic, None, Value::Id),
+    ("KEY", Group::Basic, None, Value::Key),
+    ("MODE", Group::Basic, None, Value::Mode),
+    ("OWNER", Group::Basic, None, Value::User("uid")),
+    ("GROUP", Group::Basic, None, Value::GroupName("gid")),
+    ("CREATOR", Group::Creator, None, Value::User("cuid")),
+    ("CGROUP", Group::Creator, None, Value::GroupName("cgid")),
+    (
+        "CBYTES",
+        Group::Outstanding,
+        QUEUES,
+        Value::Number("cbytes"),
+    ),
+    ("QNUM", Group::Outstanding, QUEUES, Value::Number("qnum")),
+    (
+        "NATTCH",
+        Group::Outstanding,
+        SHARED_MEMORY,
+        Value::Number("nattch"),
+    ),
+    ("QBYTES", Group::MaxSize, QUEUES, Value::QueueMaxBytes),
+    (
+        "SEGSZ",
+        Group::MaxSize,
+        SHARED_MEMORY,
+        Value::Number("size"),
+    ),
+    ("NSEMS", Group::MaxSize, SEMAPHORES, Value::Number("nsems")),
+    ("LSPID", Group::Pid, QUEUES, Value::Number("lspid")),
+    ("LRPID", Group::Pid, QUEUES, Value::Number("lrpid")),
+    ("CPID", Group::Pid, SHARED_MEMORY, Value::Number("cpid")),
+    ("LPID", Group::Pid, SHARED_MEMORY, Value::Number("lpid")),
+    ("STIME", Group::Time, QUEUES, Value::Time("stime")),
+    ("RTIME", Group::Time, QUEUES, Value::Time("rtime")),
+    ("ATIME", Group::Time, SHARED_MEMORY, Value::Time("atime")),
+    ("DTIME", Group::Time, SHARED_MEMORY, Value::Time("dtime")),
+    ("OTIME", Group::Time, SEMAPHORES, Value::Time("otime")),
+    ("CTIME", Group::Time, None, Value::Time("ctime")),
+];
+
+/// The columns of a facility selected by the options.
+fn columns(args: &Args, facility: Facility) -> Vec<&'static Column> {
+    COLUMNS
+        .iter()
+        .filter(|(_, group, only, _)| {
+            let selected = match group {
+                Group::Basic => true,
+                Group::Creator => args.creator,
+                Group::Outstanding => args.outstanding,
+                Group::MaxSize => args.max_size,
+                Group::Pid => args.pid,
+                Group::Time => args.time,
+            };
+            selected && only.is_none_or(|f| f == facility)
+        })
+        .collect()
+}
+
+fn print_facility(args: &Args, facility: Facility) {
+    let Some(entries) = read_entries(facility) else {
+        println!(
+            "{}",
+            gettext!("{} facility not in system.", facility.title())
+        );
+        return;
+    };
+    println!("{}:", facility.title());
+
+    let columns = columns(args, facility);
+    let mut rows: Vec<Vec<String>> = vec![columns.iter().map(|c| c.0.to_string()).collect()];
+    for entry in &entries {
+        rows.push(
+            columns
+                .iter()
+                .map(|c| c.3.format(facility, entry))
+                .collect(),
+        );
+    }
+
+    let widths: Vec<usize> = (0..columns.len())
+        .map(|i| rows.iter().map(|row| row[i].len()).max().unwrap_or(0))
+        .collect();
+    for row in &rows {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&columns)
+            .zip(&widths)
+            .map(|((value, column), &width)| {
+                if column.3.is_numeric() {
+                    format!("{:>width$}", value)
+                } else {
+                    format!("{:<width$}", value)
+                }
+            })
+            .collect();
+        println!("{}", line.join(" ").trim_end());
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // parse command line arguments
+    let mut args = Args::parse();
+    if args.all {
+        args.max_size = true;
+        args.creator = true;
+        args.outstanding = true;
+        args.pid = true;
+        args.time = true;
+    }
+
+    textdomain(PROJECT_NAME)?;
+    bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
+
+    // without a facility option, all are reported
+    let all_facilities = !args.queues && !args.shared_memory && !args.semaphores;
+
+    println!(
+        "{}",
+        gettext!("IPC status from {} as of {}", SOURCE, date_string())
+    );
+    for (selected, facility) in [
+        (args.queues, Facility::Queues),
+        (args.shared_memory, Facility::SharedMemory),
+        (args.semaphores, Facility::Semaphores),
+    ] {
+        if selected || all_facilities {
+            print_facility(&args, facility);
+        }
+    }
+
+    Ok(())
+}
@@ -6,17 +6,16 @@
 // file in the root directory of this project.
 // SPDX-License-Identifier: MIT
 //
-// TODO:
-// - the "-v" option is specified by POSIX and cannot be clap default -v
-//
 
 extern crate clap;
+extern crate libc;
 extern crate plib;
-extern crate uname;
 
 use clap::Parser;
 use gettextrs::{bind_textdomain_codeset, textdomain};
 use plib::PROJECT_NAME;
+use std::ffi::CStr;
+use std::io;
 
 /// uname - return system name
 #[derive(Parser, Debug)]
@@ -43,11 +42,44 @@ struct Args {
     system: bool,
 
     /// Write the current version level of this release of the operating system implementation.
-    #[arg(short, long)]
+    #[arg(short = 'v', long)]
     osversion: bool,
+
+    /// Write the processor type; an extension, reported as the hardware type.
+    #[arg(short, long)]
+    processor: bool,
+}
+
+/// The fields of the uname(2) system call.
+struct Info {
+    sysname: String,
+    nodename: String,
+    release: String,
+    version: String,
+    machine: String,
 }
 
-fn print_info(args: &Args, info: uname::Info) {
+fn uname() -> io::Result<Info> {
+    let mut uts: libc::utsname = unsafe { std::mem::zeroed() };
+    if unsafe { libc::uname(&mut uts) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let field = |chars: &[libc::c_char]| {
+        unsafe { CStr::from_ptr(chars.as_ptr()) }
+            .to_string_lossy()
+            .into_owned()
+    };
+    Ok(Info {
+        sysname: field(&uts.sysname),
+        nodename: field(&uts.nodename),
+        release: field(&uts.release),
+        version: field(&uts.version),
+        machine: field(&uts.machine),
+    })
+}
+
+fn print_info(args: &Args, info: Info) {
     let mut outs = Vec::new();
 
     if args.system {
@@ -63,6 +95,9 @@ fn print_info(args: &Args, info: uname::Info) {
         outs.push(info.version);
     }
     if args.machine {
+        outs.push(info.machine.clone());
+    }
+    if args.processor {
         outs.push(info.machine);
     }
 
@@ -79,6 +114,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         args.release = true;
         args.system = true;
         args.osversion = true;
+    } else if !(args.machine || args.node || args.release || args.osversion || args.processor) {
+        // with no options, only the system name is written
+        args.system = true;
     }
 
     textdomain(PROJECT_NAME)?;
@@ -86,10 +124,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let mut exit_code = 0;
 
-    match uname::uname() {
+    match uname() {
         Ok(info) => print_info(&args, info),
         Err(e) => {
-            eprintln!("{}", e);
+            eprintln!("uname: {}", e);
             exit_code = 1;
         }
     }
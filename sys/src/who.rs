@@ -9,12 +9,22 @@
 
 extern crate chrono;
 extern crate clap;
+extern crate libc;
 extern crate plib;
 
+use chrono::{Local, TimeZone};
 use clap::Parser;
-use gettextrs::{bind_textdomain_codeset, textdomain};
+use gettextrs::{bind_textdomain_codeset, gettext, textdomain};
+use plib::utmpx::Utmpx;
 use plib::PROJECT_NAME;
+use std::ffi::CStr;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
 use std::path::PathBuf;
+use std::time::SystemTime;
+
+// the group write permission bit, set by mesg y
+const GROUP_WRITE: u32 = 0o020;
 
 /// who - display who is on the system
 #[derive(Parser, Debug)]
@@ -57,7 +67,7 @@ struct Args {
     runlevel: bool,
 
     /// List only the name, line, and time fields (default).
-    #[arg(short, long = "short", default_value_t = true, group = "output")]
+    #[arg(short, long = "short")]
     short_format: bool,
 
     /// Indicate the last change to the system clock.
@@ -65,7 +75,7 @@ struct Args {
     last_change: bool,
 
     /// Show the state of each terminal
-    #[arg(short = 'T', long, group = "output")]
+    #[arg(short = 'T', long)]
     terminals: bool,
 
     /// Normal selection of information
@@ -82,55 +92,110 @@ struct Args {
 
 // convert timestamp into POSIX-specified strftime format
 fn fmt_timestamp(ts: libc::time_t) -> String {
-    let dt = chrono::DateTime::from_timestamp(ts, 0).unwrap();
-    dt.format("%b %e %H:%M").to_string()
+    match Local.timestamp_opt(ts, 0).single() {
+        Some(dt) => dt.format("%b %e %H:%M").to_string(),
+        None => String::new(),
+    }
 }
 
-fn print_fmt_short(entry: &plib::utmpx::Utmpx, line: &str) {
-    println!(
-        "{:<16} {:<12} {}",
-        entry.user,
-        line,
-        fmt_timestamp(entry.timestamp)
-    );
+// the state of a terminal: whether others may write to it
+fn term_state(line: &str) -> char {
+    match fs::metadata(format!("/dev/{}", line)) {
+        Ok(md) if md.mode() & GROUP_WRITE != 0 => '+',
+        Ok(_) => '-',
+        Err(_) => '?',
+    }
+}
+
+// the time since the last input on a terminal: "." when within the last
+// minute, "old" when over a day ago
+fn idle_time(line: &str) -> String {
+    let Ok(md) = fs::metadata(format!("/dev/{}", line)) else {
+        return String::from("?");
+    };
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+    let idle = now - md.atime();
+    if idle < 60 {
+        String::from(".")
+    } else if idle >= 24 * 60 * 60 {
+        String::from("old")
+    } else {
+        format!("{:02}:{:02}", idle / 3600, idle % 3600 / 60)
+    }
+}
+
+// one output line: the name, terminal state, line and time, with the idle
+// time and process ID when requested
+fn print_row(args: &Args, name: &str, state: char, line: &str, time: &str, idle: &str, pid: &str) {
+    let mut out = format!("{:<8}", name);
+    if args.terminals {
+        out.push_str(&format!(" {}", state));
+    }
+    out.push_str(&format!(" {:<12} {:<12}", line, time));
+    if args.idle_time {
+        out.push_str(&format!(" {:>5} {:>10}", idle, pid));
+    }
+    println!("{}", out.trim_end());
 }
 
-fn print_fmt_term(entry: &plib::utmpx::Utmpx, line: &str) {
-    let term_state = '?';
-    println!(
-        "{:<16} {} {:<12} {}",
-        entry.user,
-        term_state,
-        line,
-        fmt_timestamp(entry.timestamp)
+fn print_heading(args: &Args) {
+    print_row(
+        args,
+        &gettext("NAME"),
+        ' ',
+        &gettext("LINE"),
+        &gettext("TIME"),
+        &gettext("IDLE"),
+        &gettext("PID"),
     );
 }
 
-fn print_entry(args: &Args, entry: &plib::utmpx::Utmpx) {
-    let mut selected = false;
-    if (args.boot && entry.typ == libc::BOOT_TIME)
+fn print_entry(args: &Args, entry: &Utmpx) {
+    let time = fmt_timestamp(entry.timestamp);
+    let pid = entry.pid.to_string();
+
+    match entry.typ {
+        libc::BOOT_TIME => print_row(args, "", ' ', &gettext("system boot"), &time, "", ""),
+        libc::NEW_TIME => print_row(args, "", ' ', &gettext("clock change"), &time, "", ""),
+        libc::RUN_LVL => {
+            // the run-level is the low byte of the process ID field
+            let level = (entry.pid % 256) as u8 as char;
+            let line = format!("{} {}", gettext("run-level"), level);
+            print_row(args, "", ' ', &line, &time, "", "");
+        }
+        libc::USER_PROCESS => {
+            let state = term_state(&entry.line);
+            let idle = idle_time(&entry.line);
+            print_row(args, &entry.user, state, &entry.line, &time, &idle, &pid);
+        }
+        libc::LOGIN_PROCESS => {
+            let idle = idle_time(&entry.line);
+            print_row(args, "LOGIN", ' ', &entry.line, &time, &idle, &pid);
+        }
+        _ => print_row(args, &entry.user, ' ', &entry.line, &time, "", &pid),
+    }
+}
+
+fn is_selected(args: &Args, entry: &Utmpx) -> bool {
+    (args.boot && entry.typ == libc::BOOT_TIME)
         || (args.userproc && entry.typ == libc::USER_PROCESS)
         || (args.dead && entry.typ == libc::DEAD_PROCESS)
         || (args.login && entry.typ == libc::LOGIN_PROCESS)
+        || (args.process && entry.typ == libc::INIT_PROCESS)
         || (args.runlevel && entry.typ == libc::RUN_LVL)
-    {
-        selected = true;
-    }
-
-    if !selected {
-        return;
-    }
-
-    let line = match entry.typ {
-        libc::BOOT_TIME => "system boot",
-        _ => entry.line.as_str(),
-    };
+        || (args.last_change && entry.typ == libc::NEW_TIME)
+}
 
-    if args.short_format {
-        print_fmt_short(entry, line);
-    } else {
-        print_fmt_term(entry, line);
+// the terminal of standard input, without its /dev/ prefix
+fn current_line() -> Option<String> {
+    let name = unsafe { libc::ttyname(libc::STDIN_FILENO) };
+    if name.is_null() {
+        return None;
     }
+    let name = unsafe { CStr::from_ptr(name) }.to_string_lossy();
+    Some(name.strip_prefix("/dev/").unwrap_or(&name).to_string())
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -141,7 +206,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // parse command line arguments; if "who am i", use special args
     let mut args = {
         if am_i {
-            Args::parse_from(&["-m"])
+            Args::parse_from(["who", "-m"])
         } else {
             Args::parse()
         }
@@ -156,24 +221,65 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         args.last_change = true;
         args.terminals = true;
         args.idle_time = true;
-    } else if !args.boot && !args.dead && !args.login && !args.process && !args.runlevel {
+    } else if !args.boot
+        && !args.dead
+        && !args.login
+        && !args.process
+        && !args.runlevel
+        && !args.last_change
+    {
         args.userproc = true;
     }
+    if args.short_format {
+        args.terminals = false;
+        args.idle_time = false;
+    }
 
     textdomain(PROJECT_NAME)?;
     bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
 
-    let mut exit_code = 0;
+    let entries = match args.file {
+        None => plib::utmpx::load(),
+        Some(ref path) => match plib::utmpx::load_file(path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("who: {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        },
+    };
 
-    if args.file.is_some() {
-        eprintln!("who: -f option not yet implemented");
-        exit_code = 1;
+    // "who am i" and -m: only the user on the current terminal
+    let line = if args.current_terminal {
+        current_line()
+    } else {
+        None
+    };
+    let selected = entries.iter().filter(|entry| {
+        if args.current_terminal {
+            entry.typ == libc::USER_PROCESS && Some(&entry.line) == line.as_ref()
+        } else {
+            is_selected(&args, entry)
+        }
+    });
+
+    if args.summary {
+        let users: Vec<&str> = entries
+            .iter()
+            .filter(|entry| entry.typ == libc::USER_PROCESS)
+            .map(|entry| entry.user.as_str())
+            .collect();
+        println!("{}", users.join(" "));
+        println!("# {}={}", gettext("users"), users.len());
+        return Ok(());
     }
 
-    let entries = plib::utmpx::load();
-    for entry in &entries {
+    if args.heading {
+        print_heading(&args);
+    }
+    for entry in selected {
         print_entry(&args, entry);
     }
 
-    std::process::exit(exit_code)
+    Ok(())
 }
@@ -0,0 +1,341 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use std::process::Command;
+
+fn uname(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_uname"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_uname_default_is_system() {
+    assert_eq!(uname(&[]), uname(&["-s"]));
+}
+
+#[test]
+fn test_uname_all_order() {
+    let all = uname(&["-a"]);
+    assert_eq!(all, uname(&["-snrvm"]));
+    assert_eq!(all, uname(&["-m", "-v", "-r", "-n", "-s"]));
+
+    let fields = [
+        uname(&["-s"]),
+        uname(&["-n"]),
+        uname(&["-r"]),
+        uname(&["-v"]),
+        uname(&["-m"]),
+    ];
+    let joined: Vec<&str> = fields.iter().map(|f| f.trim_end()).collect();
+    assert_eq!(all, format!("{}\n", joined.join(" ")));
+}
+
+// the glibc utmpx record: type, pid, line, id, user, host, exit status,
+// session, time, address and padding
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+fn utmpx_record(typ: i16, pid: i32, line: &str, user: &str, time: i32) -> Vec<u8> {
+    fn field(s: &str, len: usize) -> Vec<u8> {
+        let mut bytes = s.as_bytes().to_vec();
+        bytes.resize(len, 0);
+        bytes
+    }
+
+    let mut record = Vec::new();
+    record.extend(typ.to_le_bytes());
+    record.extend([0, 0]);
+    record.extend(pid.to_le_bytes());
+    record.extend(field(line, 32));
+    record.extend(field("", 4));
+    record.extend(field(user, 32));
+    record.extend(field("", 256));
+    record.extend([0; 8]);
+    record.extend(time.to_le_bytes());
+    record.extend([0; 4]);
+    record.extend([0; 36]);
+    assert_eq!(record.len(), 384);
+    record
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+fn who_test(args: &[&str], expected_out: &str) {
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("who_utmp");
+    let mut data = utmpx_record(2, 0, "~", "reboot", 1700000000);
+    data.extend(utmpx_record(1, '5' as i32, "~", "runlevel", 1700000060));
+    data.extend(utmpx_record(7, 456, "pts/0", "alice", 1700000120));
+    data.extend(utmpx_record(7, 457, "pts/1", "bob", 1700000180));
+    data.extend(utmpx_record(8, 789, "pts/2", "", 1700000240));
+    std::fs::write(&path, data).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_who"))
+        .args(args)
+        .arg(&path)
+        .env("TZ", "UTC")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected_out);
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+#[test]
+fn test_who_users() {
+    who_test(
+        &[],
+        "alice    pts/0        Nov 14 22:15\nbob      pts/1        Nov 14 22:16\n",
+    );
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+#[test]
+fn test_who_boot_runlevel() {
+    who_test(
+        &["-H", "-b", "-r"],
+        "NAME     LINE         TIME\n         system boot  Nov 14 22:13\n         run-level 5  Nov 14 22:14\n",
+    );
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+#[test]
+fn test_who_summary() {
+    who_test(&["-q"], "alice bob\n# users=2\n");
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+#[test]
+fn test_who_state_idle() {
+    // the terminals do not exist
+    who_test(
+        &["-T", "-u"],
+        "alice    ? pts/0        Nov 14 22:15     ?        456\nbob      ? pts/1        Nov 14 22:16     ?        457\n",
+    );
+}
+
+fn crontab(dir: &std::path::Path, args: &[&str], stdin_data: &str) -> std::process::Output {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_crontab"))
+        .args(args)
+        .env("CRONTAB_DIR", dir)
+        .env("EDITOR", "sed -i s/^0/30/")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin_data.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_crontab_install_list_remove() {
+    // crontab creates the directory
+    let dir = &std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("test_crontab_install");
+    let entries = "# daily\nMAILTO=root\n0 5 * * 1-5 echo hi\n*/15 0-23/2 1,15 * 7 cmd % x\n";
+
+    let output = crontab(dir, &[], entries);
+    assert!(output.status.success());
+
+    let output = crontab(dir, &["-l"], "");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), entries);
+
+    let output = crontab(dir, &["-e"], "");
+    assert!(output.status.success());
+    let output = crontab(dir, &["-l"], "");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        entries.replace("\n0 5", "\n30 5")
+    );
+
+    let output = crontab(dir, &["-r"], "");
+    assert!(output.status.success());
+    let output = crontab(dir, &["-l"], "");
+    assert_eq!(output.status.code(), Some(1));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_crontab_invalid() {
+    let dir = &std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("test_crontab_invalid");
+    let output = crontab(dir, &["-"], "60 * * * * x\n* * * *\n1-0 * * * * y\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "crontab: standard input:1: invalid minute field '60'\n\
+         crontab: standard input:2: missing day of week field\n\
+         crontab: standard input:3: invalid minute field '1-0'\n\
+         crontab: errors in crontab file, not installed\n"
+    );
+
+    // nothing was installed
+    let output = crontab(dir, &["-l"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!dir.exists());
+}
+
+fn getconf(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_getconf"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn getconf_out(args: &[&str]) -> String {
+    let output = getconf(args);
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_getconf_system_var() {
+    let arg_max = unsafe { libc::sysconf(libc::_SC_ARG_MAX) };
+    assert_eq!(getconf_out(&["ARG_MAX"]), format!("{}\n", arg_max));
+
+    // names are accepted with or without a leading underscore
+    let version = getconf_out(&["_POSIX_VERSION"]);
+    assert_eq!(getconf_out(&["POSIX_VERSION"]), version);
+    assert_eq!(
+        getconf_out(&["_POSIX2_VERSION"]),
+        getconf_out(&["POSIX2_VERSION"])
+    );
+
+    assert_eq!(getconf_out(&["_POSIX_NAME_MAX"]), "14\n");
+    assert_eq!(getconf_out(&["CHAR_BIT"]), "8\n");
+}
+
+#[test]
+fn test_getconf_path() {
+    let path = getconf_out(&["PATH"]);
+    assert!(path
+        .trim_end()
+        .split(':')
+        .any(|dir| dir == "/bin" || dir == "/usr/bin"));
+}
+
+#[test]
+fn test_getconf_path_var() {
+    let name_max = unsafe { libc::pathconf(c"/".as_ptr(), libc::_PC_NAME_MAX) };
+    assert_eq!(getconf_out(&["NAME_MAX", "/"]), format!("{}\n", name_max));
+
+    // a path variable needs a pathname, which must exist
+    assert!(!getconf(&["NAME_MAX"]).status.success());
+    assert!(!getconf(&["NAME_MAX", "/nonexistent/path"]).status.success());
+}
+
+#[test]
+fn test_getconf_invalid() {
+    let output = getconf(&["NO_SUCH_VARIABLE"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert!(!output.stderr.is_empty());
+
+    assert!(!getconf(&["-v", "NO_SUCH_ENV", "PATH"]).status.success());
+}
+
+fn ipcs(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_ipcs"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn ipcrm(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_ipcrm"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+// the row of ipcs output for an identifier
+fn ipcs_row(output: &str, letter: &str, id: i32) -> Option<Vec<String>> {
+    output
+        .lines()
+        .map(|line| {
+            line.split_whitespace()
+                .map(String::from)
+                .collect::<Vec<_>>()
+        })
+        .find(|fields| fields.len() > 1 && fields[0] == letter && fields[1] == id.to_string())
+}
+
+#[test]
+fn test_ipcs_ipcrm_shared_memory() {
+    let shmid = unsafe { libc::shmget(libc::IPC_PRIVATE, 4096, libc::IPC_CREAT | 0o640) };
+    assert!(shmid >= 0);
+
+    let output = ipcs(&["-m", "-b"]);
+    assert!(output.starts_with("IPC status from "));
+    assert!(output.contains("Shared Memory:\nT "));
+    assert!(!output.contains("Message Queues"));
+    let row = ipcs_row(&output, "m", shmid).unwrap();
+    assert_eq!(row[2], "0x00000000");
+    assert_eq!(row[3], "--rw-r-----");
+    assert_eq!(row[6], "4096");
+
+    assert!(ipcrm(&["-m", &shmid.to_string()]).status.success());
+    assert!(ipcs_row(&ipcs(&["-m"]), "m", shmid).is_none());
+
+    // the identifier no longer exists
+    let output = ipcrm(&["-m", &shmid.to_string()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!output.stderr.is_empty());
+}
+
+#[test]
+fn test_ipcs_all_with_print_options() {
+    // the column headings of the shared memory table
+    let headings = |args: &[&str]| {
+        let output = ipcs(args);
+        let line = output.lines().find(|line| line.starts_with("T "));
+        line.unwrap().to_string()
+    };
+
+    // -a is the same as -bcopt, so giving them again changes nothing
+    let all = headings(&["-m", "-a"]);
+    assert_eq!(headings(&["-m", "-a", "-b"]), all);
+    assert_eq!(headings(&["-m", "-at"]), all);
+    assert_eq!(headings(&["-m", "-bcopt"]), all);
+}
+
+#[test]
+fn test_ipcs_ipcrm_keys() {
+    let key = 0x5e1f_0000 | (std::process::id() as libc::key_t & 0xffff);
+    let msqid = unsafe { libc::msgget(key, libc::IPC_CREAT | 0o600) };
+    assert!(msqid >= 0);
+    let semid = unsafe { libc::semget(key, 3, libc::IPC_CREAT | 0o600) };
+    assert!(semid >= 0);
+
+    let output = ipcs(&["-q", "-s", "-a"]);
+    let row = ipcs_row(&output, "q", msqid).unwrap();
+    assert_eq!(row[2], format!("0x{:08x}", key));
+    assert_eq!(row[3], "--rw-------");
+    let row = ipcs_row(&output, "s", semid).unwrap();
+    assert_eq!(row[3], "--ra-------");
+    assert_eq!(row[8], "3");
+
+    // keys are given in the hexadecimal form ipcs writes
+    let key = format!("0x{:08x}", key);
+    assert!(ipcrm(&["-Q", &key, "-S", &key]).status.success());
+    let output = ipcs(&["-q", "-s"]);
+    assert!(ipcs_row(&output, "q", msqid).is_none());
+    assert!(ipcs_row(&output, "s", semid).is_none());
+}
@@ -6,39 +6,43 @@
 // file in the root directory of this project.
 // SPDX-License-Identifier: MIT
 //
-// TODO:
-// - implement -h, -H, -L, -P
-//
 
 extern crate clap;
 extern crate libc;
 extern crate plib;
 
+mod common;
+
+use self::common::{walk_tree, Dereference};
 use clap::Parser;
 use gettextrs::{bind_textdomain_codeset, textdomain};
 use plib::PROJECT_NAME;
 use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
 use std::path::Path;
 use std::{fs, io};
 
 /// chgrp - change file group ownership
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about)]
+#[command(author, version, about, long_about, disable_help_flag = true)]
 struct Args {
+    #[arg(long, action = clap::ArgAction::HelpLong)]
+    help: Option<bool>,
+
     /// Change symbolic links, rather than the files they point to
     #[arg(short = 'h', long)]
     no_derereference: bool,
 
     /// Follow command line symlinks during -R recursion
-    #[arg(short = 'H', long)]
+    #[arg(short = 'H', long, overrides_with_all = ["follow_cli", "dereference", "no_dereference2"])]
     follow_cli: bool,
 
     /// Follow symlinks during -R recursion
-    #[arg(short = 'L', group = "deref")]
+    #[arg(short = 'L', overrides_with_all = ["follow_cli", "dereference", "no_dereference2"])]
     dereference: bool,
 
     /// Never follow symlinks during -R recursion
-    #[arg(short = 'P', group = "deref")]
+    #[arg(short = 'P', overrides_with_all = ["follow_cli", "dereference", "no_dereference2"])]
     no_dereference2: bool,
 
     /// Recursively change groups of directories and their contents
@@ -52,26 +56,39 @@ struct Args {
     files: Vec<String>,
 }
 
-fn chgrp_file(filename: &str, gid: u32, recurse: bool) -> Result<(), io::Error> {
-    let path = Path::new(filename);
-    let metadata = fs::metadata(path)?;
-
-    // recurse into directories
-    if metadata.is_dir() && recurse {
-        for entry in fs::read_dir(path)? {
-            let entry = entry?;
-            let entry_path = entry.path();
-            let entry_filename = entry_path.to_str().unwrap();
-            chgrp_file(entry_filename, gid, recurse)?;
+impl Args {
+    // symlink handling implied by the command line options
+    fn dereference(&self) -> Dereference {
+        if self.recurse {
+            if self.dereference {
+                Dereference::Always
+            } else if self.follow_cli {
+                Dereference::CommandLine
+            } else {
+                Dereference::Never
+            }
+        } else if self.no_derereference {
+            Dereference::Never
+        } else {
+            Dereference::CommandLine
         }
     }
+}
 
-    // change the group
-    let pathstr = CString::new(filename).unwrap();
-    unsafe {
-        if libc::chown(pathstr.as_ptr(), libc::geteuid(), gid) != 0 {
-            return Err(io::Error::last_os_error());
+// change the group of path, or of the symlink itself if it was not followed
+fn chgrp_path(path: &Path, metadata: &fs::Metadata, gid: u32) -> Result<(), io::Error> {
+    let pathstr = CString::new(path.as_os_str().as_bytes())?;
+
+    // an owner ID of -1 leaves the owner unchanged
+    let ret = unsafe {
+        if metadata.file_type().is_symlink() {
+            libc::lchown(pathstr.as_ptr(), libc::uid_t::MAX, gid)
+        } else {
+            libc::chown(pathstr.as_ptr(), libc::uid_t::MAX, gid)
         }
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
     }
 
     Ok(())
@@ -79,20 +96,15 @@ fn chgrp_file(filename: &str, gid: u32, recurse: bool) -> Result<(), io::Error>
 
 // lookup string group by name, or parse numeric group ID
 fn parse_group(group: &str) -> Result<u32, &'static str> {
-    match group.parse::<u32>() {
-        Ok(gid) => Ok(gid),
-        Err(_) => {
-            // lookup group by name
-            let group_cstr = CString::new(group).unwrap();
-            let group = unsafe { libc::getgrnam(group_cstr.as_ptr()) };
-            if group.is_null() {
-                return Err("group not found");
-            }
-
-            let gid = unsafe { (*group).gr_gid };
-            Ok(gid)
+    // a name in the group database takes precedence over a numeric ID
+    if let Ok(group_cstr) = CString::new(group) {
+        let grp = unsafe { libc::getgrnam(group_cstr.as_ptr()) };
+        if !grp.is_null() {
+            return Ok(unsafe { (*grp).gr_gid });
         }
     }
+
+    group.parse::<u32>().map_err(|_| "group not found")
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -108,11 +120,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // lookup string group by name, or parse numeric group ID
     let gid = parse_group(&args.group)?;
 
+    let deref = args.dereference();
+
     // apply the group to each file
     for filename in &args.files {
-        if let Err(e) = chgrp_file(filename, gid, args.recurse) {
+        let success = walk_tree(
+            Path::new(filename),
+            deref,
+            args.recurse,
+            &mut |path, metadata| chgrp_path(path, metadata, gid),
+        );
+        if !success {
             exit_code = 1;
-            eprintln!("{}: {}", filename, e);
         }
     }
 
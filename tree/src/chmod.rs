@@ -10,6 +10,9 @@
 extern crate clap;
 extern crate plib;
 
+mod common;
+
+use self::common::{walk_tree, Dereference};
 use clap::Parser;
 use gettextrs::{bind_textdomain_codeset, textdomain};
 use modestr::{ChmodMode, ChmodSymbolic};
@@ -34,13 +37,15 @@ struct Args {
 }
 
 // apply symbolic mutations to the given file at path
-fn set_permissions_symbolic(path: &Path, symbolic: &ChmodSymbolic) -> Result<(), io::Error> {
-    // query the current mode bits
-    let metadata = fs::metadata(path)?;
+fn set_permissions_symbolic(
+    path: &Path,
+    metadata: &fs::Metadata,
+    symbolic: &ChmodSymbolic,
+) -> Result<(), io::Error> {
     let mut perms = metadata.permissions();
 
     // perform mutations on the mode bits
-    let new_mode = modestr::mutate(perms.mode(), symbolic);
+    let new_mode = modestr::mutate(perms.mode(), metadata.is_dir(), symbolic);
 
     // update path in filesystem
     perms.set_mode(new_mode);
@@ -49,19 +54,7 @@ fn set_permissions_symbolic(path: &Path, symbolic: &ChmodSymbolic) -> Result<(),
     Ok(())
 }
 
-fn chmod_file(filename: &str, mode: &ChmodMode, recurse: bool) -> Result<(), io::Error> {
-    let path = Path::new(filename);
-    let metadata = fs::metadata(path)?;
-
-    if metadata.is_dir() && recurse {
-        for entry in fs::read_dir(path)? {
-            let entry = entry?;
-            let entry_path = entry.path();
-            let entry_filename = entry_path.to_str().unwrap();
-            chmod_file(entry_filename, mode, recurse)?;
-        }
-    }
-
+fn chmod_path(path: &Path, metadata: &fs::Metadata, mode: &ChmodMode) -> Result<(), io::Error> {
     match mode {
         // set the mode bits to the given value
         ChmodMode::Absolute(m) => {
@@ -70,13 +63,29 @@ fn chmod_file(filename: &str, mode: &ChmodMode, recurse: bool) -> Result<(), io:
 
         // apply symbolic mutations to the mode bits
         ChmodMode::Symbolic(s) => {
-            set_permissions_symbolic(path, s)?;
+            set_permissions_symbolic(path, metadata, s)?;
         }
     }
 
     Ok(())
 }
 
+fn chmod_file(filename: &str, mode: &ChmodMode, recurse: bool) -> bool {
+    // Symbolic links named on the command line are followed, those found
+    // during the traversal are neither changed nor followed
+    walk_tree(
+        Path::new(filename),
+        Dereference::CommandLine,
+        recurse,
+        &mut |path, metadata| {
+            if metadata.file_type().is_symlink() {
+                return Ok(());
+            }
+            chmod_path(path, metadata, mode)
+        },
+    )
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // parse command line arguments
     let args = Args::parse();
@@ -92,9 +101,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // apply the mode to each file
     for filename in &args.files {
-        if let Err(e) = chmod_file(filename, &mode, args.recurse) {
+        if !chmod_file(filename, &mode, args.recurse) {
             exit_code = 1;
-            eprintln!("{}: {}", filename, e);
         }
     }
 
@@ -6,40 +6,43 @@
 // file in the root directory of this project.
 // SPDX-License-Identifier: MIT
 //
-// TODO:
-// - implement -h, -H, -L, -P
-//
 
 extern crate clap;
 extern crate libc;
 extern crate plib;
 
+mod common;
+
+use self::common::{walk_tree, Dereference};
 use clap::Parser;
 use gettextrs::{bind_textdomain_codeset, textdomain};
 use plib::PROJECT_NAME;
 use std::ffi::CString;
-use std::os::unix::fs::MetadataExt;
+use std::os::unix::ffi::OsStrExt;
 use std::path::Path;
 use std::{fs, io};
 
 /// chown - change the file ownership
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about)]
+#[command(author, version, about, long_about, disable_help_flag = true)]
 struct Args {
+    #[arg(long, action = clap::ArgAction::HelpLong)]
+    help: Option<bool>,
+
     /// Change symbolic links, rather than the files they point to
     #[arg(short = 'h', long)]
     no_derereference: bool,
 
     /// Follow command line symlinks during -R recursion
-    #[arg(short = 'H', long)]
+    #[arg(short = 'H', long, overrides_with_all = ["follow_cli", "dereference", "no_dereference2"])]
     follow_cli: bool,
 
     /// Follow symlinks during -R recursion
-    #[arg(short = 'L', group = "deref")]
+    #[arg(short = 'L', overrides_with_all = ["follow_cli", "dereference", "no_dereference2"])]
     dereference: bool,
 
     /// Never follow symlinks during -R recursion
-    #[arg(short = 'P', group = "deref")]
+    #[arg(short = 'P', overrides_with_all = ["follow_cli", "dereference", "no_dereference2"])]
     no_dereference2: bool,
 
     /// Recursively change groups of directories and their contents
@@ -53,33 +56,48 @@ struct Args {
     files: Vec<String>,
 }
 
-fn chown_file(filename: &str, uid: u32, gid: Option<u32>, recurse: bool) -> Result<(), io::Error> {
-    let path = Path::new(filename);
-    let metadata = fs::metadata(path)?;
-
-    // recurse into directories
-    if metadata.is_dir() && recurse {
-        for entry in fs::read_dir(path)? {
-            let entry = entry?;
-            let entry_path = entry.path();
-            let entry_filename = entry_path.to_str().unwrap();
-            chown_file(entry_filename, uid, gid, recurse)?;
+impl Args {
+    // symlink handling implied by the command line options
+    fn dereference(&self) -> Dereference {
+        if self.recurse {
+            if self.dereference {
+                Dereference::Always
+            } else if self.follow_cli {
+                Dereference::CommandLine
+            } else {
+                Dereference::Never
+            }
+        } else if self.no_derereference {
+            Dereference::Never
+        } else {
+            Dereference::CommandLine
         }
     }
+}
 
-    // change the user, and optionally, the group
-    let pathstr = CString::new(filename).unwrap();
-    let gid = {
-        if let Some(gid) = gid {
-            gid
+// change the owner and/or group of path, or of the symlink itself if
+// it was not followed
+fn chown_path(
+    path: &Path,
+    metadata: &fs::Metadata,
+    uid: Option<u32>,
+    gid: Option<u32>,
+) -> Result<(), io::Error> {
+    let pathstr = CString::new(path.as_os_str().as_bytes())?;
+
+    // an ID of -1 leaves the corresponding ID unchanged
+    let uid = uid.unwrap_or(libc::uid_t::MAX);
+    let gid = gid.unwrap_or(libc::gid_t::MAX);
+
+    let ret = unsafe {
+        if metadata.file_type().is_symlink() {
+            libc::lchown(pathstr.as_ptr(), uid, gid)
         } else {
-            metadata.gid()
+            libc::chown(pathstr.as_ptr(), uid, gid)
         }
     };
-    unsafe {
-        if libc::chown(pathstr.as_ptr(), uid, gid) != 0 {
-            return Err(io::Error::last_os_error());
-        }
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
     }
 
     Ok(())
@@ -87,50 +105,50 @@ fn chown_file(filename: &str, uid: u32, gid: Option<u32>, recurse: bool) -> Resu
 
 // lookup string group by name, or parse numeric group ID
 fn parse_group(group: &str) -> Result<u32, &'static str> {
-    match group.parse::<u32>() {
-        Ok(gid) => Ok(gid),
-        Err(_) => {
-            // lookup group by name
-            let group_cstr = CString::new(group).unwrap();
-            let group = unsafe { libc::getgrnam(group_cstr.as_ptr()) };
-            if group.is_null() {
-                return Err("group not found");
-            }
-
-            let gid = unsafe { (*group).gr_gid };
-            Ok(gid)
+    // a name in the group database takes precedence over a numeric ID
+    if let Ok(group_cstr) = CString::new(group) {
+        let grp = unsafe { libc::getgrnam(group_cstr.as_ptr()) };
+        if !grp.is_null() {
+            return Ok(unsafe { (*grp).gr_gid });
         }
     }
+
+    group.parse::<u32>().map_err(|_| "group not found")
 }
 
 // lookup string user by name, or parse numeric user ID
 fn parse_user(user: &str) -> Result<u32, &'static str> {
-    match user.parse::<u32>() {
-        Ok(uid) => Ok(uid),
-        Err(_) => {
-            // lookup user by name
-            let user_cstr = CString::new(user).unwrap();
-            let user = unsafe { libc::getpwnam(user_cstr.as_ptr()) };
-            if user.is_null() {
-                return Err("user not found");
-            }
-
-            let uid = unsafe { (*user).pw_uid };
-            Ok(uid)
+    // a name in the user database takes precedence over a numeric ID
+    if let Ok(user_cstr) = CString::new(user) {
+        let pwd = unsafe { libc::getpwnam(user_cstr.as_ptr()) };
+        if !pwd.is_null() {
+            return Ok(unsafe { (*pwd).pw_uid });
         }
     }
+
+    user.parse::<u32>().map_err(|_| "user not found")
 }
 
-fn parse_owner_group(owner_group: &str) -> Result<(u32, Option<u32>), &'static str> {
+// parse OWNER[:GROUP] or :GROUP
+fn parse_owner_group(owner_group: &str) -> Result<(Option<u32>, Option<u32>), &'static str> {
     match owner_group.split_once(':') {
         None => {
             let uid = parse_user(owner_group)?;
-            Ok((uid, None))
+            Ok((Some(uid), None))
         }
+        Some(("", "")) => Err("invalid owner"),
         Some((owner, group)) => {
-            let uid = parse_user(owner)?;
-            let gid = parse_group(group)?;
-            Ok((uid, Some(gid)))
+            let uid = if owner.is_empty() {
+                None
+            } else {
+                Some(parse_user(owner)?)
+            };
+            let gid = if group.is_empty() {
+                None
+            } else {
+                Some(parse_group(group)?)
+            };
+            Ok((uid, gid))
         }
     }
 }
@@ -148,11 +166,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // lookup the owner and group
     let (uid, gid) = parse_owner_group(&args.owner_group)?;
 
-    // apply the group to each file
+    let deref = args.dereference();
+
+    // apply the owner and group to each file
     for filename in &args.files {
-        if let Err(e) = chown_file(filename, uid, gid, args.recurse) {
+        let success = walk_tree(
+            Path::new(filename),
+            deref,
+            args.recurse,
+            &mut |path, metadata| chown_path(path, metadata, uid, gid),
+        );
+        if !success {
             exit_code = 1;
-            eprintln!("{}: {}", filename, e);
         }
     }
 
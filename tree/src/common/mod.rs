@@ -1,7 +1,7 @@
-// This module is shared between `cp`, `mv` and `rm` but is considered as three
-// separated modules due to the project structure. The `#![allow(unused)]` is
-// to remove warnings when, say, `rm` doesn't use all the the functions in this
-// module (but is used in `cp` or `mv`).
+// This module is shared between `chgrp`, `chmod`, `chown`, `cp`, `mv` and
+// `rm` but is considered as separate modules due to the project structure.
+// The `#![allow(unused)]` is to remove warnings when, say, `rm` doesn't use
+// all the the functions in this module (but is used in `cp` or `mv`).
 #![allow(unused)]
 
 use gettextrs::gettext;
@@ -112,3 +112,105 @@ pub fn is_file_writable(md: Option<&fs::Metadata>) -> bool {
         None => false,
     }
 }
+
+/// How symbolic links are handled by [`walk_tree`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dereference {
+    /// Never follow symbolic links (`-P`).
+    Never,
+    /// Follow symbolic links named on the command line only (`-H`).
+    CommandLine,
+    /// Follow all symbolic links (`-L`).
+    Always,
+}
+
+/// Visit `path` and, if `recurse` is set and it is a directory, everything
+/// below it.
+///
+/// `visit` is called for each file before descending into it. It receives
+/// the metadata of the file a symbolic link points to when the link is
+/// followed according to `deref`, and the metadata of the link itself
+/// otherwise. Errors are reported on stderr and the walk continues with the
+/// next file. Returns `false` if any error was encountered.
+pub fn walk_tree<F>(path: &Path, deref: Dereference, recurse: bool, visit: &mut F) -> bool
+where
+    F: FnMut(&Path, &fs::Metadata) -> io::Result<()>,
+{
+    let follow = deref != Dereference::Never;
+    let mut ancestors = Vec::new();
+    walk_tree_inner(path, deref, follow, recurse, &mut ancestors, visit)
+}
+
+fn walk_tree_inner<F>(
+    path: &Path,
+    deref: Dereference,
+    follow: bool,
+    recurse: bool,
+    ancestors: &mut Vec<(u64, u64)>,
+    visit: &mut F,
+) -> bool
+where
+    F: FnMut(&Path, &fs::Metadata) -> io::Result<()>,
+{
+    let metadata = if follow {
+        fs::metadata(path)
+    } else {
+        fs::symlink_metadata(path)
+    };
+    let metadata = match metadata {
+        Ok(md) => md,
+        Err(e) => {
+            eprintln!("{}: {}", path.display(), error_string(&e));
+            return false;
+        }
+    };
+
+    let mut success = true;
+    if let Err(e) = visit(path, &metadata) {
+        eprintln!("{}: {}", path.display(), error_string(&e));
+        success = false;
+    }
+
+    if !recurse || !metadata.is_dir() {
+        return success;
+    }
+
+    // Following symbolic links can lead back to a directory that is
+    // currently being traversed
+    let id = (metadata.dev(), metadata.ino());
+    if ancestors.contains(&id) {
+        eprintln!(
+            "{}: {}",
+            path.display(),
+            gettext("recursive directory loop")
+        );
+        return false;
+    }
+
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("{}: {}", path.display(), error_string(&e));
+            return false;
+        }
+    };
+
+    ancestors.push(id);
+    for entry in entries {
+        match entry {
+            Ok(entry) => {
+                let follow = deref == Dereference::Always;
+                if !walk_tree_inner(&entry.path(), deref, follow, recurse, ancestors, visit) {
+                    success = false;
+                }
+            }
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), error_string(&e));
+                success = false;
+            }
+        }
+    }
+    ancestors.pop();
+
+    success
+}
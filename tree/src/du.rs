@@ -6,34 +6,35 @@
 // file in the root directory of this project.
 // SPDX-License-Identifier: MIT
 //
-// TODO:
-// - implement -H, -L, -x
-//
 
 extern crate clap;
 extern crate plib;
 
+mod common;
+
+use self::common::{error_string, Dereference};
 use clap::Parser;
 use gettextrs::{bind_textdomain_codeset, textdomain};
 use plib::PROJECT_NAME;
+use std::collections::HashSet;
+use std::fs;
 use std::os::unix::fs::MetadataExt;
 use std::path::Path;
-use std::{fs, io};
 
 /// du - estimate file space usage
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about)]
 struct Args {
     /// Write counts for all files, not just directories
-    #[arg(short, long)]
+    #[arg(short, long, conflicts_with = "sum")]
     all: bool,
 
     /// Follow command line symlinks
-    #[arg(short = 'H', long)]
+    #[arg(short = 'H', long, overrides_with = "dereference")]
     follow_cli: bool,
 
     /// Dereference all symlinks
-    #[arg(short = 'L', long)]
+    #[arg(short = 'L', long, overrides_with = "follow_cli")]
     dereference: bool,
 
     /// Write the files sizes in units of 1024 bytes, rather than the default 512-byte units.
@@ -52,58 +53,101 @@ struct Args {
     files: Vec<String>,
 }
 
-fn calc_size(kilo: bool, size: u64) -> u64 {
+// convert a count of 512-byte blocks to the requested units
+fn calc_size(kilo: bool, blocks: u64) -> u64 {
     if kilo {
-        size / 2
+        blocks.div_ceil(2)
     } else {
-        size
+        blocks
     }
 }
 
-fn print_pathinfo(args: &Args, filename: &str, size: u64, toplevel: bool) {
-    if args.sum && !toplevel {
-        return;
-    }
-
+fn print_pathinfo(args: &Args, filename: &str, blocks: u64) {
     // print the file size
-    println!("{}\t{}", size, filename);
+    println!("{}\t{}", calc_size(args.kilo, blocks), filename);
 }
 
-fn du_cli_arg(
-    args: &Args,
-    filename: &str,
-    total: &mut u64,
-    toplevel: bool,
-) -> Result<(), io::Error> {
-    let path = Path::new(filename);
-    let metadata = fs::metadata(path)?;
+struct DuState {
+    deref: Dereference,
 
-    // recursively process directories
-    if metadata.is_dir() {
-        let mut sub_total = 0;
-        for entry in fs::read_dir(path)? {
-            let entry = entry?;
-            let path = entry.path();
-            let filename = path.to_str().unwrap();
-            if let Err(e) = du_cli_arg(args, filename, &mut sub_total, false) {
-                eprintln!("{}: {}", filename, e);
-            }
+    // device of the file operand being processed, for -x
+    root_dev: u64,
+
+    // files already counted, so multiply-linked files are counted once
+    seen: HashSet<(u64, u64)>,
+
+    exit_code: i32,
+}
+
+// return the number of 512-byte blocks used by path and, for directories,
+// everything below it
+fn du_path(args: &Args, state: &mut DuState, path: &Path, toplevel: bool) -> u64 {
+    let follow = match state.deref {
+        Dereference::Always => true,
+        Dereference::CommandLine => toplevel,
+        Dereference::Never => false,
+    };
+    let metadata = if follow {
+        fs::metadata(path)
+    } else {
+        fs::symlink_metadata(path)
+    };
+    let metadata = match metadata {
+        Ok(md) => md,
+        Err(e) => {
+            eprintln!("{}: {}", path.display(), error_string(&e));
+            state.exit_code = 1;
+            return 0;
         }
-        print_pathinfo(args, filename, sub_total, toplevel);
+    };
 
-        *total += sub_total;
-        return Ok(());
+    if toplevel {
+        state.root_dev = metadata.dev();
+    } else if args.one_fs && metadata.dev() != state.root_dev {
+        return 0;
     }
 
-    // print the file size
-    let size = calc_size(args.kilo, metadata.blocks());
-    *total += size;
+    // directories are tracked too, which prevents loops with -L
+    if (metadata.is_dir() || metadata.nlink() > 1)
+        && !state.seen.insert((metadata.dev(), metadata.ino()))
+    {
+        return 0;
+    }
 
-    if args.all {
-        print_pathinfo(args, filename, size, toplevel);
+    let mut blocks = metadata.blocks();
+    let filename = path.to_string_lossy();
+
+    if !metadata.is_dir() {
+        if toplevel || args.all {
+            print_pathinfo(args, &filename, blocks);
+        }
+        return blocks;
     }
 
-    Ok(())
+    // recursively process directories
+    match fs::read_dir(path) {
+        Ok(entries) => {
+            for entry in entries {
+                match entry {
+                    Ok(entry) => blocks += du_path(args, state, &entry.path(), false),
+                    Err(e) => {
+                        eprintln!("{}: {}", filename, error_string(&e));
+                        state.exit_code = 1;
+                    }
+                }
+            }
+        }
+        Err(e) => {
+            eprintln!("{}: {}", filename, error_string(&e));
+            state.exit_code = 1;
+        }
+    }
+
+    if toplevel || !args.sum {
+        print_pathinfo(args, &filename, blocks);
+    }
+
+    blocks
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -119,16 +163,24 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     textdomain(PROJECT_NAME)?;
     bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
 
-    let mut exit_code = 0;
-    let mut total = 0;
-
-    // apply the group to each file
+    let deref = if args.dereference {
+        Dereference::Always
+    } else if args.follow_cli {
+        Dereference::CommandLine
+    } else {
+        Dereference::Never
+    };
+    let mut state = DuState {
+        deref,
+        root_dev: 0,
+        seen: HashSet::new(),
+        exit_code: 0,
+    };
+
+    // report the usage of each file operand
     for filename in &args.files {
-        if let Err(e) = du_cli_arg(&args, filename, &mut total, true) {
-            exit_code = 1;
-            eprintln!("{}: {}", filename, e);
-        }
+        du_path(&args, &mut state, Path::new(filename), true);
     }
 
-    std::process::exit(exit_code)
+    std::process::exit(state.exit_code)
 }
@@ -0,0 +1,137 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+extern crate libc;
+extern crate plib;
+
+mod common;
+mod find_util;
+
+use self::common::Dereference;
+use self::find_util::{parse_expression, walk, walk_parallel, Context, WalkEntry, WalkOptions};
+use gettextrs::{bind_textdomain_codeset, textdomain};
+use plib::PROJECT_NAME;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Command line of find: `find [-H|-L] [-j threads] path... [expression]`
+///
+/// `-j` is an extension: with more than one thread, directories are read
+/// in parallel, and the order of the output is no longer deterministic.
+struct Args {
+    deref: Dereference,
+    threads: usize,
+    paths: Vec<String>,
+    expression: Vec<String>,
+}
+
+// the expression starts at the first argument that begins with '-', or is
+// '!' or '('
+fn is_expression_start(arg: &str) -> bool {
+    (arg.starts_with('-') && arg.len() > 1) || arg == "!" || arg == "("
+}
+
+fn parse_threads(arg: Option<&str>) -> Result<usize, String> {
+    match arg.map(|s| s.parse::<usize>()) {
+        Some(Ok(n)) if n > 0 => Ok(n),
+        Some(_) => Err(format!("-j: invalid thread count: {}", arg.unwrap())),
+        None => Err(String::from("-j: missing argument")),
+    }
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = std::env::args().skip(1).peekable();
+
+    // -H and -L may be repeated; the last one specified wins
+    let mut deref = Dereference::Never;
+    let mut threads = 1;
+    while let Some(arg) = args.peek() {
+        match arg.as_str() {
+            "-H" => deref = Dereference::CommandLine,
+            "-L" => deref = Dereference::Always,
+            "-j" => {
+                args.next();
+                threads = parse_threads(args.peek().map(|s| s.as_str()))?;
+            }
+            s if s.starts_with("-j") => threads = parse_threads(Some(&s[2..]))?,
+            "--" => {
+                args.next();
+                break;
+            }
+            _ => break,
+        }
+        args.next();
+    }
+
+    let mut paths = Vec::new();
+    while let Some(arg) = args.peek() {
+        if is_expression_start(arg) {
+            break;
+        }
+        paths.push(args.next().unwrap());
+    }
+    if paths.is_empty() {
+        paths.push(String::from("."));
+    }
+
+    Ok(Args {
+        deref,
+        threads,
+        paths,
+        expression: args.collect(),
+    })
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // initialize translations
+    textdomain(PROJECT_NAME)?;
+    bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
+
+    let args = parse_args()?;
+    let expression = parse_expression(&args.expression, args.deref != Dereference::Never)?;
+    let opts = WalkOptions {
+        deref: args.deref,
+        depth_first: expression.depth_first,
+        xdev: expression.xdev,
+    };
+
+    let ctx = Mutex::new(Context::new());
+    let mut exit_code = 0;
+
+    // the expression is evaluated for one file at a time, also when
+    // directories are read by several threads
+    let visit = |entry: &WalkEntry| {
+        let mut ctx = ctx.lock().unwrap();
+        ctx.prune = false;
+        expression.expr.eval(entry, &mut ctx);
+        !ctx.prune
+    };
+
+    // post-order traversal is always sequential
+    let parallel = args.threads > 1 && !expression.depth_first;
+
+    for path in &args.paths {
+        let success = if parallel {
+            walk_parallel(Path::new(path), opts, args.threads, &visit)
+        } else {
+            walk(Path::new(path), opts, &mut |entry| visit(entry))
+        };
+        if !success {
+            exit_code = 1;
+        }
+    }
+
+    let mut ctx = ctx.into_inner().unwrap();
+    ctx.finish();
+    if ctx.failed {
+        exit_code = 1;
+    }
+
+    std::process::exit(exit_code)
+}
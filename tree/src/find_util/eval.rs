@@ -0,0 +1,177 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use super::exec::{exec_single, Batch};
+use super::expr::{Expr, FileType, Primary, TimeField};
+use super::walk::WalkEntry;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::Path;
+
+const SECONDS_PER_DAY: i64 = 86400;
+
+/// State shared by all evaluations of the expression.
+pub struct Context {
+    /// Time find was started, as seconds since the epoch.
+    pub now: i64,
+
+    /// Set when a utility could not be executed, or an `-exec ... +`
+    /// invocation exited with a non-zero status.
+    pub failed: bool,
+
+    /// Set by `-prune` to skip the entries of the current directory.
+    pub prune: bool,
+
+    // pending -exec ... {} + invocations, by primary
+    batches: BTreeMap<usize, Batch>,
+}
+
+impl Context {
+    pub fn new() -> Context {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        Context {
+            now,
+            failed: false,
+            prune: false,
+            batches: BTreeMap::new(),
+        }
+    }
+
+    /// Run the utilities of `-exec ... +` primaries for the pathnames that
+    /// are still pending.
+    pub fn finish(&mut self) {
+        for batch in self.batches.values_mut() {
+            if !batch.flush() {
+                self.failed = true;
+            }
+        }
+    }
+}
+
+// the last component of path, ignoring trailing slashes.  The root
+// directory is its own basename.
+fn basename(path: &Path) -> &OsStr {
+    let bytes = path.as_os_str().as_bytes();
+    let trimmed = {
+        let mut end = bytes.len();
+        while end > 1 && bytes[end - 1] == b'/' {
+            end -= 1;
+        }
+        &bytes[..end]
+    };
+    if trimmed == b"/" {
+        return OsStr::from_bytes(trimmed);
+    }
+    match trimmed.iter().rposition(|&b| b == b'/') {
+        Some(pos) => OsStr::from_bytes(&trimmed[pos + 1..]),
+        None => OsStr::from_bytes(trimmed),
+    }
+}
+
+fn file_type_matches(file_type: FileType, md: &std::fs::Metadata) -> bool {
+    let ft = md.file_type();
+    match file_type {
+        FileType::BlockDevice => ft.is_block_device(),
+        FileType::CharDevice => ft.is_char_device(),
+        FileType::Directory => ft.is_dir(),
+        FileType::Symlink => ft.is_symlink(),
+        FileType::Fifo => ft.is_fifo(),
+        FileType::Regular => ft.is_file(),
+        FileType::Socket => ft.is_socket(),
+    }
+}
+
+fn user_exists(uid: u32) -> bool {
+    !unsafe { libc::getpwuid(uid) }.is_null()
+}
+
+fn group_exists(gid: u32) -> bool {
+    !unsafe { libc::getgrgid(gid) }.is_null()
+}
+
+fn print_path(path: &Path) -> bool {
+    let mut stdout = io::stdout().lock();
+    let _ = stdout.write_all(path.as_os_str().as_bytes());
+    let _ = stdout.write_all(b"\n");
+    true
+}
+
+fn eval_primary(primary: &Primary, entry: &WalkEntry, ctx: &mut Context) -> bool {
+    let md = entry.metadata;
+
+    match primary {
+        Primary::Name(pattern) => pattern.matches(&basename(entry.path).to_string_lossy()),
+        Primary::Path(pattern) => pattern.matches(&entry.path.to_string_lossy()),
+        Primary::Type(file_type) => file_type_matches(*file_type, md),
+        Primary::Time(field, cmp) => {
+            let time = match field {
+                TimeField::Access => md.atime(),
+                TimeField::Modification => md.mtime(),
+                TimeField::StatusChange => md.ctime(),
+            };
+            // elapsed time in days, with any fraction discarded
+            let days = (ctx.now - time).div_euclid(SECONDS_PER_DAY);
+            days >= 0 && cmp.matches(days as u64)
+        }
+        Primary::Size { size, bytes } => {
+            let value = if *bytes {
+                md.size()
+            } else {
+                md.size().div_ceil(512)
+            };
+            size.matches(value)
+        }
+        Primary::User(uid) => md.uid() == *uid,
+        Primary::Group(gid) => md.gid() == *gid,
+        Primary::NoUser => !user_exists(md.uid()),
+        Primary::NoGroup => !group_exists(md.gid()),
+        Primary::Perm { mode, at_least } => {
+            let file_mode = md.mode() & 0o7777;
+            if *at_least {
+                file_mode & mode == *mode
+            } else {
+                file_mode == *mode
+            }
+        }
+        Primary::Links(cmp) => cmp.matches(md.nlink()),
+        Primary::Newer { sec, nsec } => (md.mtime(), md.mtime_nsec()) > (*sec, *nsec),
+        Primary::Print => print_path(entry.path),
+        Primary::Prune => {
+            ctx.prune = true;
+            true
+        }
+        Primary::Depth | Primary::Xdev => true,
+        Primary::Exec { argv, prompt } => exec_single(argv, entry.path, *prompt, &mut ctx.failed),
+        Primary::ExecBatch { id, argv } => {
+            let batch = ctx.batches.entry(*id).or_insert_with(|| Batch::new(argv));
+            if !batch.push(entry.path) {
+                ctx.failed = true;
+            }
+            true
+        }
+    }
+}
+
+impl Expr {
+    /// Evaluate the expression for one file.
+    pub fn eval(&self, entry: &WalkEntry, ctx: &mut Context) -> bool {
+        match self {
+            Expr::Primary(primary) => eval_primary(primary, entry, ctx),
+            Expr::Not(expr) => !expr.eval(entry, ctx),
+            Expr::And(lhs, rhs) => lhs.eval(entry, ctx) && rhs.eval(entry, ctx),
+            Expr::Or(lhs, rhs) => lhs.eval(entry, ctx) || rhs.eval(entry, ctx),
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Write};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::Path;
+use std::process::Command;
+
+// headroom left below ARG_MAX, as recommended by POSIX for xargs
+const ARG_MAX_HEADROOM: usize = 2048;
+
+// bytes taken up by one argument or environment string in the new process
+fn arg_size(arg: &OsStr) -> usize {
+    arg.len() + 1 + std::mem::size_of::<*const libc::c_char>()
+}
+
+/// Number of bytes available for the arguments of an executed utility.
+fn args_limit() -> usize {
+    let arg_max = match unsafe { libc::sysconf(libc::_SC_ARG_MAX) } {
+        n if n > 0 => n as usize,
+        _ => 4096,
+    };
+    let env_size: usize = std::env::vars_os()
+        .map(|(k, v)| arg_size(&k) + arg_size(&v))
+        .sum();
+
+    arg_max
+        .saturating_sub(env_size)
+        .saturating_sub(ARG_MAX_HEADROOM)
+        .max(ARG_MAX_HEADROOM)
+}
+
+// replace each occurrence of "{}" in arg with path
+fn substitute(arg: &str, path: &Path) -> OsString {
+    if !arg.contains("{}") {
+        return OsString::from(arg);
+    }
+
+    let path = path.as_os_str().as_bytes();
+    let mut result = Vec::new();
+    let mut pieces = arg.split("{}").peekable();
+    while let Some(piece) = pieces.next() {
+        result.extend_from_slice(piece.as_bytes());
+        if pieces.peek().is_some() {
+            result.extend_from_slice(path);
+        }
+    }
+    OsString::from_vec(result)
+}
+
+/// Run `argv` and return true if it exited with status zero.
+///
+/// Standard output is flushed first, so output of find and of the utility
+/// is not reordered.
+fn run(argv: &[OsString]) -> io::Result<bool> {
+    let _ = io::stdout().flush();
+
+    let status = Command::new(&argv[0]).args(&argv[1..]).status()?;
+    Ok(status.success())
+}
+
+fn report_exec_error(utility: &OsStr, e: &io::Error) {
+    eprintln!("{}: {}", utility.to_string_lossy(), e);
+}
+
+// write the -ok prompt and read the answer from standard input
+fn confirm(argv: &[OsString]) -> bool {
+    let words: Vec<_> = argv.iter().map(|a| a.to_string_lossy()).collect();
+    eprint!("< {} > ? ", words.join(" "));
+    let _ = io::stderr().flush();
+
+    let mut response = String::new();
+    match io::stdin().read_line(&mut response) {
+        Ok(_) => response.to_lowercase().starts_with('y'),
+        Err(_) => false,
+    }
+}
+
+/// Execute the utility of `-exec ... ;` or `-ok ... ;` for path.
+///
+/// Returns the value of the primary: true if the utility was run and
+/// exited with status zero. If the utility could not be run, an error is
+/// reported and `failed` is set.
+pub fn exec_single(template: &[String], path: &Path, prompt: bool, failed: &mut bool) -> bool {
+    let argv: Vec<OsString> = template.iter().map(|a| substitute(a, path)).collect();
+
+    if prompt && !confirm(&argv) {
+        return false;
+    }
+
+    match run(&argv) {
+        Ok(success) => success,
+        Err(e) => {
+            report_exec_error(&argv[0], &e);
+            *failed = true;
+            false
+        }
+    }
+}
+
+/// Pending invocation of the utility of an `-exec ... {} +` primary.
+pub struct Batch {
+    template: Vec<String>,
+    paths: Vec<OsString>,
+    template_size: usize,
+    size: usize,
+    limit: usize,
+}
+
+impl Batch {
+    pub fn new(template: &[String]) -> Batch {
+        let template_size = template.iter().map(|a| arg_size(OsStr::new(a))).sum();
+        Batch {
+            template: template.to_vec(),
+            paths: Vec::new(),
+            template_size,
+            size: template_size,
+            limit: args_limit(),
+        }
+    }
+
+    /// Add path to the batch, first running the utility for the paths
+    /// collected so far if the new one would exceed the argument limit.
+    /// Returns false if an invocation failed.
+    pub fn push(&mut self, path: &Path) -> bool {
+        let size = arg_size(path.as_os_str());
+
+        let mut success = true;
+        if !self.paths.is_empty() && self.size + size > self.limit {
+            success = self.flush();
+        }
+
+        self.paths.push(path.as_os_str().to_os_string());
+        self.size += size;
+        success
+    }
+
+    /// Run the utility for all pending paths. Returns false if it could
+    /// not be run or exited with a non-zero status.
+    pub fn flush(&mut self) -> bool {
+        if self.paths.is_empty() {
+            return true;
+        }
+
+        let mut argv: Vec<OsString> = self.template.iter().map(OsString::from).collect();
+        argv.append(&mut self.paths);
+        self.size = self.template_size;
+
+        match run(&argv) {
+            Ok(success) => success,
+            Err(e) => {
+                report_exec_error(&argv[0], &e);
+                false
+            }
+        }
+    }
+}
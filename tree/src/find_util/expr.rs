@@ -0,0 +1,445 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use plib::fnmatch::{MatchOptions, Pattern};
+use plib::modestr::{self, ChmodMode};
+use std::ffi::CString;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+
+/// File type tested by `-type`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FileType {
+    BlockDevice,
+    CharDevice,
+    Directory,
+    Symlink,
+    Fifo,
+    Regular,
+    Socket,
+}
+
+/// Numeric argument of a primary: `+n`, `-n` or `n`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    Greater(u64),
+    Less(u64),
+    Equal(u64),
+}
+
+impl Comparison {
+    fn parse(primary: &str, arg: &str) -> Result<Comparison, String> {
+        let (ctor, digits): (fn(u64) -> Comparison, &str) = match arg.as_bytes().first() {
+            Some(b'+') => (Comparison::Greater, &arg[1..]),
+            Some(b'-') => (Comparison::Less, &arg[1..]),
+            _ => (Comparison::Equal, arg),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(format!("{}: invalid argument: {}", primary, arg));
+        }
+        let n = digits
+            .parse::<u64>()
+            .map_err(|_| format!("{}: invalid argument: {}", primary, arg))?;
+        Ok(ctor(n))
+    }
+
+    pub fn matches(&self, value: u64) -> bool {
+        match *self {
+            Comparison::Greater(n) => value > n,
+            Comparison::Less(n) => value < n,
+            Comparison::Equal(n) => value == n,
+        }
+    }
+}
+
+/// Which timestamp a time primary examines.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeField {
+    Access,
+    Modification,
+    StatusChange,
+}
+
+#[derive(Debug)]
+pub enum Primary {
+    Name(Pattern),
+    Path(Pattern),
+    Type(FileType),
+    Time(TimeField, Comparison),
+    Size {
+        size: Comparison,
+        bytes: bool,
+    },
+    User(u32),
+    Group(u32),
+    NoUser,
+    NoGroup,
+    Perm {
+        mode: u32,
+        at_least: bool,
+    },
+    Links(Comparison),
+    Newer {
+        sec: i64,
+        nsec: i64,
+    },
+    Print,
+    Prune,
+
+    /// `-depth` and `-xdev` always evaluate as true; they change how the
+    /// hierarchy is traversed
+    Depth,
+    Xdev,
+
+    /// `-exec utility [argument...] ;` and `-ok utility [argument...] ;`
+    Exec {
+        argv: Vec<String>,
+        prompt: bool,
+    },
+
+    /// `-exec utility [argument...] {} +`, with `{}` removed from `argv`
+    ExecBatch {
+        id: usize,
+        argv: Vec<String>,
+    },
+}
+
+/// A parsed find expression.
+#[derive(Debug)]
+pub enum Expr {
+    Primary(Primary),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    // does the expression contain an action that writes or executes
+    // something?  If not, -print is implied.
+    fn has_action(&self) -> bool {
+        match self {
+            Expr::Primary(p) => matches!(
+                p,
+                Primary::Print | Primary::Exec { .. } | Primary::ExecBatch { .. }
+            ),
+            Expr::Not(e) => e.has_action(),
+            Expr::And(l, r) | Expr::Or(l, r) => l.has_action() || r.has_action(),
+        }
+    }
+}
+
+/// A parsed find expression, with the traversal options requested by its
+/// primaries.
+#[derive(Debug)]
+pub struct Expression {
+    pub expr: Expr,
+
+    /// `-depth` was given
+    pub depth_first: bool,
+
+    /// `-xdev` was given
+    pub xdev: bool,
+}
+
+// lookup string user by name, or parse numeric user ID
+fn parse_user(user: &str) -> Result<u32, String> {
+    if let Ok(user_cstr) = CString::new(user) {
+        let pwd = unsafe { libc::getpwnam(user_cstr.as_ptr()) };
+        if !pwd.is_null() {
+            return Ok(unsafe { (*pwd).pw_uid });
+        }
+    }
+
+    user.parse::<u32>()
+        .map_err(|_| format!("-user: user not found: {}", user))
+}
+
+// lookup string group by name, or parse numeric group ID
+fn parse_group(group: &str) -> Result<u32, String> {
+    if let Ok(group_cstr) = CString::new(group) {
+        let grp = unsafe { libc::getgrnam(group_cstr.as_ptr()) };
+        if !grp.is_null() {
+            return Ok(unsafe { (*grp).gr_gid });
+        }
+    }
+
+    group
+        .parse::<u32>()
+        .map_err(|_| format!("-group: group not found: {}", group))
+}
+
+fn parse_type(arg: &str) -> Result<FileType, String> {
+    let file_type = match arg {
+        "b" => FileType::BlockDevice,
+        "c" => FileType::CharDevice,
+        "d" => FileType::Directory,
+        "l" => FileType::Symlink,
+        "p" => FileType::Fifo,
+        "f" => FileType::Regular,
+        "s" => FileType::Socket,
+        _ => return Err(format!("-type: unknown type: {}", arg)),
+    };
+    Ok(file_type)
+}
+
+fn parse_perm(arg: &str) -> Result<Primary, String> {
+    let (at_least, mode_str) = match arg.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, arg),
+    };
+
+    // symbolic modes are applied to an initial mode of zero, as if the
+    // umask were zero
+    let mode = match modestr::parse(mode_str).map_err(|e| format!("-perm: {}", e))? {
+        ChmodMode::Absolute(mode) => mode,
+        ChmodMode::Symbolic(sym) => modestr::mutate_unmasked(0, false, &sym),
+    };
+
+    Ok(Primary::Perm { mode, at_least })
+}
+
+fn parse_size(arg: &str) -> Result<Primary, String> {
+    let (digits, bytes) = match arg.strip_suffix('c') {
+        Some(rest) => (rest, true),
+        None => (arg, false),
+    };
+    let size = Comparison::parse("-size", digits)?;
+    Ok(Primary::Size { size, bytes })
+}
+
+/// Parser state for the expression operands of the command line.
+struct Parser<'a> {
+    args: &'a [String],
+    pos: usize,
+
+    // follow symbolic links when examining the -newer reference file
+    follow: bool,
+
+    // number of -exec ... {} + primaries seen so far
+    batches: usize,
+
+    depth_first: bool,
+    xdev: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.args.get(self.pos).map(|s| s.as_str())
+    }
+
+    fn next_arg(&mut self, primary: &str) -> Result<&'a str, String> {
+        match self.args.get(self.pos) {
+            Some(arg) => {
+                self.pos += 1;
+                Ok(arg)
+            }
+            None => Err(format!("{}: missing argument", primary)),
+        }
+    }
+
+    // parse the utility and arguments of -exec or -ok, up to the
+    // terminating ';', or '+' following "{}" for -exec
+    fn parse_exec(&mut self, primary: &str) -> Result<Primary, String> {
+        let prompt = primary == "-ok";
+        let mut argv: Vec<String> = Vec::new();
+
+        loop {
+            let arg = self
+                .next_arg(primary)
+                .map_err(|_| format!("{}: missing terminating ';'", primary))?;
+            match arg {
+                ";" => break,
+                "+" if !prompt && argv.last().is_some_and(|a| a == "{}") => {
+                    argv.pop();
+                    if argv.is_empty() {
+                        return Err(format!("{}: missing utility", primary));
+                    }
+                    let id = self.batches;
+                    self.batches += 1;
+                    return Ok(Primary::ExecBatch { id, argv });
+                }
+                _ => argv.push(arg.to_string()),
+            }
+        }
+
+        if argv.is_empty() {
+            return Err(format!("{}: missing utility", primary));
+        }
+        Ok(Primary::Exec { argv, prompt })
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, String> {
+        let name = self.next_arg("expression")?;
+
+        let primary = match name {
+            "-name" => {
+                let pattern = self.next_arg(name)?;
+                Primary::Name(Pattern::new(pattern, MatchOptions::default()))
+            }
+            "-path" => {
+                let pattern = self.next_arg(name)?;
+                Primary::Path(Pattern::new(pattern, MatchOptions::default()))
+            }
+            "-type" => Primary::Type(parse_type(self.next_arg(name)?)?),
+            "-atime" => Primary::Time(
+                TimeField::Access,
+                Comparison::parse(name, self.next_arg(name)?)?,
+            ),
+            "-ctime" => Primary::Time(
+                TimeField::StatusChange,
+                Comparison::parse(name, self.next_arg(name)?)?,
+            ),
+            "-mtime" => Primary::Time(
+                TimeField::Modification,
+                Comparison::parse(name, self.next_arg(name)?)?,
+            ),
+            "-size" => parse_size(self.next_arg(name)?)?,
+            "-user" => Primary::User(parse_user(self.next_arg(name)?)?),
+            "-group" => Primary::Group(parse_group(self.next_arg(name)?)?),
+            "-nouser" => Primary::NoUser,
+            "-nogroup" => Primary::NoGroup,
+            "-perm" => parse_perm(self.next_arg(name)?)?,
+            "-links" => Primary::Links(Comparison::parse(name, self.next_arg(name)?)?),
+            "-newer" => {
+                let file = self.next_arg(name)?;
+                let md = if self.follow {
+                    fs::metadata(file)
+                } else {
+                    fs::symlink_metadata(file)
+                }
+                .map_err(|e| format!("{}: {}", file, e))?;
+                Primary::Newer {
+                    sec: md.mtime(),
+                    nsec: md.mtime_nsec(),
+                }
+            }
+            "-print" => Primary::Print,
+            "-prune" => Primary::Prune,
+            "-depth" => {
+                self.depth_first = true;
+                Primary::Depth
+            }
+            "-xdev" => {
+                self.xdev = true;
+                Primary::Xdev
+            }
+            "-exec" | "-ok" => self.parse_exec(name)?,
+            _ => return Err(format!("unknown primary: {}", name)),
+        };
+
+        Ok(Expr::Primary(primary))
+    }
+
+    // ( expression ), or a primary
+    fn parse_operand(&mut self) -> Result<Expr, String> {
+        match self.peek() {
+            Some("(") => {
+                self.pos += 1;
+                if self.peek() == Some(")") {
+                    return Err(String::from("empty parentheses"));
+                }
+                let expr = self.parse_or()?;
+                match self.peek() {
+                    Some(")") => {
+                        self.pos += 1;
+                        Ok(expr)
+                    }
+                    _ => Err(String::from("missing ')'")),
+                }
+            }
+            Some(")") => Err(String::from("unexpected ')'")),
+            Some(op @ ("-a" | "-o")) => Err(format!("{}: missing expression", op)),
+            _ => self.parse_primary(),
+        }
+    }
+
+    // ! expression
+    fn parse_not(&mut self) -> Result<Expr, String> {
+        if self.peek() == Some("!") {
+            self.pos += 1;
+            let expr = self.parse_not()?;
+            return Ok(Expr::Not(Box::new(expr)));
+        }
+        self.parse_operand()
+    }
+
+    // expression [-a] expression; juxtaposed expressions are joined with
+    // an implicit -a
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_not()?;
+        loop {
+            match self.peek() {
+                None | Some("-o") | Some(")") => break,
+                Some("-a") => {
+                    self.pos += 1;
+                    if matches!(self.peek(), None | Some("-o") | Some(")")) {
+                        return Err(String::from("-a: missing expression"));
+                    }
+                }
+                _ => {}
+            }
+            let rhs = self.parse_not()?;
+            expr = Expr::And(Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    // expression -o expression, which binds less tightly than -a
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_and()?;
+        while self.peek() == Some("-o") {
+            self.pos += 1;
+            if matches!(self.peek(), None | Some(")")) {
+                return Err(String::from("-o: missing expression"));
+            }
+            let rhs = self.parse_and()?;
+            expr = Expr::Or(Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+}
+
+/// Parse the expression operands of find.
+///
+/// `follow` tells whether symbolic links named as primary arguments are
+/// followed (-H or -L).  If the expression contains no action, it is
+/// treated as `( expression ) -a -print`; an empty expression is
+/// equivalent to `-print`.
+pub fn parse_expression(args: &[String], follow: bool) -> Result<Expression, String> {
+    if args.is_empty() {
+        return Ok(Expression {
+            expr: Expr::Primary(Primary::Print),
+            depth_first: false,
+            xdev: false,
+        });
+    }
+
+    let mut parser = Parser {
+        args,
+        pos: 0,
+        follow,
+        batches: 0,
+        depth_first: false,
+        xdev: false,
+    };
+    let mut expr = parser.parse_or()?;
+    if let Some(arg) = parser.peek() {
+        return Err(format!("unexpected argument: {}", arg));
+    }
+
+    if !expr.has_action() {
+        expr = Expr::And(Box::new(expr), Box::new(Expr::Primary(Primary::Print)));
+    }
+
+    Ok(Expression {
+        expr,
+        depth_first: parser.depth_first,
+        xdev: parser.xdev,
+    })
+}
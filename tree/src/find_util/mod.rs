@@ -0,0 +1,19 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+mod eval;
+mod exec;
+mod expr;
+mod parallel;
+mod walk;
+
+pub use eval::Context;
+pub use expr::parse_expression;
+pub use parallel::walk_parallel;
+pub use walk::{walk, WalkEntry, WalkOptions};
@@ -0,0 +1,213 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+//! Multithreaded traversal of a file hierarchy.
+//!
+//! Each worker thread owns a queue of directories still to be read.  It
+//! takes work from the back of its own queue, and when that is empty,
+//! steals from the front of the queue of another worker.  Entries within
+//! one directory are visited in the order they are read, but the order in
+//! which directories are processed is not deterministic.
+
+use super::walk::{entry_metadata, WalkEntry, WalkOptions};
+use crate::common::error_string;
+use gettextrs::gettext;
+use std::collections::VecDeque;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+// (dev, ino) of a directory being traversed, linked to its parent, to
+// detect loops
+struct Ancestor {
+    id: (u64, u64),
+    parent: Option<Arc<Ancestor>>,
+}
+
+impl Ancestor {
+    fn contains(node: &Option<Arc<Ancestor>>, id: (u64, u64)) -> bool {
+        let mut node = node.as_ref();
+        while let Some(ancestor) = node {
+            if ancestor.id == id {
+                return true;
+            }
+            node = ancestor.parent.as_ref();
+        }
+        false
+    }
+}
+
+/// A directory whose entries are still to be visited.
+struct Job {
+    path: PathBuf,
+    depth: usize,
+    root_dev: u64,
+    ancestors: Option<Arc<Ancestor>>,
+}
+
+struct Shared<'a, F> {
+    opts: WalkOptions,
+    visit: &'a F,
+    queues: Vec<Mutex<VecDeque<Job>>>,
+
+    // jobs queued or being processed; the walk is over when it drops to 0.
+    // Jobs are only queued with this locked, and idle workers wait on
+    // `wakeup` for new jobs or for the end of the walk
+    pending: Mutex<usize>,
+    wakeup: Condvar,
+    success: AtomicBool,
+}
+
+impl<F> Shared<'_, F>
+where
+    F: Fn(&WalkEntry) -> bool + Sync,
+{
+    fn report(&self, path: &Path, msg: &str) {
+        eprintln!("{}: {}", path.display(), msg);
+        self.success.store(false, Ordering::Relaxed);
+    }
+
+    fn push(&self, worker: usize, job: Job) {
+        let mut pending = self.pending.lock().unwrap();
+        *pending += 1;
+        self.queues[worker].lock().unwrap().push_back(job);
+        self.wakeup.notify_one();
+    }
+
+    // take a job from our own queue, or steal one from another worker
+    fn take(&self, worker: usize) -> Option<Job> {
+        if let Some(job) = self.queues[worker].lock().unwrap().pop_back() {
+            return Some(job);
+        }
+        let n = self.queues.len();
+        (1..n).find_map(|i| self.queues[(worker + i) % n].lock().unwrap().pop_front())
+    }
+
+    // visit one file, and queue it if it is a directory to descend into
+    fn visit(&self, worker: usize, path: PathBuf, depth: usize, parent: Option<&Job>) {
+        let metadata = match entry_metadata(&path, self.opts.deref, depth) {
+            Ok(md) => md,
+            Err(e) => {
+                self.report(&path, &error_string(&e));
+                return;
+            }
+        };
+
+        let descend = (self.visit)(&WalkEntry {
+            path: &path,
+            metadata: &metadata,
+        });
+
+        let root_dev = parent.map_or(metadata.dev(), |job| job.root_dev);
+        let same_dev = !self.opts.xdev || metadata.dev() == root_dev;
+        if !metadata.is_dir() || !descend || !same_dev {
+            return;
+        }
+
+        let ancestors = parent.and_then(|job| job.ancestors.clone());
+        let id = (metadata.dev(), metadata.ino());
+        if Ancestor::contains(&ancestors, id) {
+            self.report(&path, &gettext("file system loop detected"));
+            return;
+        }
+
+        self.push(
+            worker,
+            Job {
+                path,
+                depth,
+                root_dev,
+                ancestors: Some(Arc::new(Ancestor {
+                    id,
+                    parent: ancestors,
+                })),
+            },
+        );
+    }
+
+    fn read_dir(&self, worker: usize, job: &Job) {
+        let entries = match fs::read_dir(&job.path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                self.report(&job.path, &error_string(&e));
+                return;
+            }
+        };
+
+        for entry in entries {
+            match entry {
+                Ok(entry) => self.visit(
+                    worker,
+                    job.path.join(entry.file_name()),
+                    job.depth + 1,
+                    Some(job),
+                ),
+                Err(e) => self.report(&job.path, &error_string(&e)),
+            }
+        }
+    }
+
+    fn run(&self, worker: usize) {
+        loop {
+            if let Some(job) = self.take(worker) {
+                self.read_dir(worker, &job);
+                let mut pending = self.pending.lock().unwrap();
+                *pending -= 1;
+                if *pending == 0 {
+                    self.wakeup.notify_all();
+                }
+                continue;
+            }
+
+            // with the lock held, no job can be queued between the check
+            // of the queues and the wait
+            let mut pending = self.pending.lock().unwrap();
+            while *pending > 0 && self.queues.iter().all(|q| q.lock().unwrap().is_empty()) {
+                pending = self.wakeup.wait(pending).unwrap();
+            }
+            if *pending == 0 {
+                return;
+            }
+        }
+    }
+}
+
+/// Visit `root` and every file below it using `threads` worker threads.
+///
+/// A directory is visited before its entries, which are skipped if
+/// `visit` returns `false` for it; `opts.depth_first` is not supported.
+/// Errors are reported on stderr and the traversal continues.  Returns
+/// `false` if any error was encountered.
+pub fn walk_parallel<F>(root: &Path, opts: WalkOptions, threads: usize, visit: &F) -> bool
+where
+    F: Fn(&WalkEntry) -> bool + Sync,
+{
+    let shared = Shared {
+        opts,
+        visit,
+        queues: (0..threads).map(|_| Mutex::new(VecDeque::new())).collect(),
+        pending: Mutex::new(0),
+        wakeup: Condvar::new(),
+        success: AtomicBool::new(true),
+    };
+
+    shared.visit(0, root.to_path_buf(), 0, None);
+
+    thread::scope(|scope| {
+        for worker in 0..threads {
+            let shared = &shared;
+            scope.spawn(move || shared.run(worker));
+        }
+    });
+
+    shared.success.load(Ordering::Relaxed)
+}
@@ -0,0 +1,167 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use crate::common::{error_string, Dereference};
+use gettextrs::gettext;
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+/// A file found during the traversal.
+pub struct WalkEntry<'a> {
+    pub path: &'a Path,
+    pub metadata: &'a fs::Metadata,
+}
+
+/// How the file hierarchy is traversed.
+#[derive(Clone, Copy, Debug)]
+pub struct WalkOptions {
+    pub deref: Dereference,
+
+    /// Visit the entries of a directory before the directory itself
+    /// (`-depth`).
+    pub depth_first: bool,
+
+    /// Do not descend into directories on a different device than the
+    /// starting point (`-xdev`).
+    pub xdev: bool,
+}
+
+/// Metadata of a file found at `depth` below the starting point, following
+/// symbolic links as requested by `deref`.
+pub(super) fn entry_metadata(
+    path: &Path,
+    deref: Dereference,
+    depth: usize,
+) -> io::Result<fs::Metadata> {
+    let follow = match deref {
+        Dereference::Always => true,
+        Dereference::CommandLine => depth == 0,
+        Dereference::Never => false,
+    };
+
+    if follow {
+        // a dangling symlink is reported as the link itself
+        fs::metadata(path).or_else(|_| fs::symlink_metadata(path))
+    } else {
+        fs::symlink_metadata(path)
+    }
+}
+
+struct Walker<'a, F> {
+    opts: WalkOptions,
+    visit: &'a mut F,
+    success: bool,
+
+    // device of the starting point, for -xdev
+    root_dev: u64,
+
+    // (dev, ino) of the directories being traversed, to detect loops
+    ancestors: Vec<(u64, u64)>,
+}
+
+impl<'a, F> Walker<'a, F>
+where
+    F: FnMut(&WalkEntry) -> bool,
+{
+    fn report(&mut self, path: &Path, msg: &str) {
+        eprintln!("{}: {}", path.display(), msg);
+        self.success = false;
+    }
+
+    fn metadata(&mut self, path: &Path, depth: usize) -> Option<fs::Metadata> {
+        match entry_metadata(path, self.opts.deref, depth) {
+            Ok(md) => Some(md),
+            Err(e) => {
+                self.report(path, &error_string(&e));
+                None
+            }
+        }
+    }
+
+    fn walk(&mut self, path: &Path, depth: usize) {
+        let Some(metadata) = self.metadata(path, depth) else {
+            return;
+        };
+        let entry = WalkEntry {
+            path,
+            metadata: &metadata,
+        };
+
+        if depth == 0 {
+            self.root_dev = metadata.dev();
+        }
+
+        // in pre-order, the visitor decides whether to descend
+        let descend = if self.opts.depth_first {
+            true
+        } else {
+            (self.visit)(&entry)
+        };
+
+        let same_dev = !self.opts.xdev || metadata.dev() == self.root_dev;
+        if metadata.is_dir() && descend && same_dev {
+            self.walk_dir(path, &metadata, depth);
+        }
+
+        if self.opts.depth_first {
+            (self.visit)(&entry);
+        }
+    }
+
+    fn walk_dir(&mut self, path: &Path, metadata: &fs::Metadata, depth: usize) {
+        let id = (metadata.dev(), metadata.ino());
+        if self.ancestors.contains(&id) {
+            self.report(path, &gettext("file system loop detected"));
+            return;
+        }
+
+        // entries are read and visited one at a time, so memory use does
+        // not depend on the size of the directory
+        let entries = match fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                self.report(path, &error_string(&e));
+                return;
+            }
+        };
+
+        self.ancestors.push(id);
+        for entry in entries {
+            match entry {
+                Ok(entry) => self.walk(&path.join(entry.file_name()), depth + 1),
+                Err(e) => self.report(path, &error_string(&e)),
+            }
+        }
+        self.ancestors.pop();
+    }
+}
+
+/// Visit `root` and every file below it, in pre-order, or in post-order
+/// if `opts.depth_first` is set.
+///
+/// In pre-order, the entries of a directory are skipped if `visit`
+/// returns `false` for it; the return value is ignored in post-order.
+/// Errors are reported on stderr and the traversal continues.  Returns
+/// `false` if any error was encountered.
+pub fn walk<F>(root: &Path, opts: WalkOptions, visit: &mut F) -> bool
+where
+    F: FnMut(&WalkEntry) -> bool,
+{
+    let mut walker = Walker {
+        opts,
+        visit,
+        success: true,
+        root_dev: 0,
+        ancestors: Vec::new(),
+    };
+    walker.walk(root, 0);
+    walker.success
+}
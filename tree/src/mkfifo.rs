@@ -15,6 +15,7 @@ use clap::Parser;
 use gettextrs::{bind_textdomain_codeset, textdomain};
 use modestr::ChmodMode;
 use plib::{modestr, PROJECT_NAME};
+use std::ffi::CString;
 use std::io;
 
 /// mkfifo - make FIFO special files
@@ -26,20 +27,35 @@ struct Args {
     mode: Option<String>,
 
     /// A pathname of the FIFO special file to be created.
+    #[arg(required = true)]
     files: Vec<String>,
 }
 
-fn do_mkfifo(filename: &str, mode: &ChmodMode) -> io::Result<()> {
-    let mode_val = match mode {
-        ChmodMode::Absolute(mode) => *mode,
-        ChmodMode::Symbolic(sym) => modestr::mutate(0o666, sym),
-    };
+// The FIFO is created with the default mode of a=rw, subject to the umask.
+// A mode given with -m is then applied with chmod, so it does not depend on
+// the umask.
+fn do_mkfifo(filename: &str, mode: Option<&ChmodMode>) -> io::Result<()> {
+    let filename =
+        CString::new(filename).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
 
-    let res = unsafe { libc::mkfifo(filename.as_ptr() as *const i8, mode_val as libc::mode_t) };
+    let res = unsafe { libc::mkfifo(filename.as_ptr(), 0o666) };
     if res < 0 {
         return Err(io::Error::last_os_error());
     }
 
+    if let Some(mode) = mode {
+        // '+' and '-' are relative to an assumed initial mode of a=rw
+        let mode_val = match mode {
+            ChmodMode::Absolute(mode) => *mode,
+            ChmodMode::Symbolic(sym) => modestr::mutate(0o666, false, sym),
+        };
+
+        let res = unsafe { libc::chmod(filename.as_ptr(), mode_val as libc::mode_t) };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
     Ok(())
 }
 
@@ -54,13 +70,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // parse the mode string
     let mode = match args.mode {
-        Some(mode) => modestr::parse(&mode)?,
-        None => ChmodMode::Absolute(0o666),
+        Some(mode) => Some(modestr::parse(&mode)?),
+        None => None,
     };
 
-    // apply the mode to each file
+    // create each FIFO
     for filename in &args.files {
-        if let Err(e) = do_mkfifo(filename, &mode) {
+        if let Err(e) = do_mkfifo(filename, mode.as_ref()) {
             exit_code = 1;
             eprintln!("{}: {}", filename, e);
         }
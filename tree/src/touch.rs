@@ -11,10 +11,13 @@ extern crate clap;
 extern crate libc;
 extern crate plib;
 
-use chrono::{DateTime, Datelike, LocalResult, TimeZone, Utc};
+use chrono::{DateTime, Datelike, Duration, Local, LocalResult, NaiveDateTime, TimeZone, Utc};
 use clap::Parser;
 use gettextrs::{bind_textdomain_codeset, textdomain};
 use plib::PROJECT_NAME;
+use std::ffi::CString;
+use std::os::unix::fs::MetadataExt;
+use std::{fs, io};
 
 /// touch - change file access and modification times
 #[derive(Parser, Debug)]
@@ -49,208 +52,162 @@ struct Args {
 }
 
 fn parse_tm_iso(time: &str) -> Result<DateTime<Utc>, Box<dyn std::error::Error>> {
-    let dt = DateTime::parse_from_rfc3339(time)?;
-    Ok(dt.into())
+    // the date and time may be separated by a space instead of 'T'
+    let time = time.replacen(' ', "T", 1);
+
+    // a time zone designator is optional; without one, local time is used
+    if let Ok(dt) = DateTime::parse_from_rfc3339(&time) {
+        return Ok(dt.into());
+    }
+
+    // POSIX allows ',' as the fractional seconds separator
+    let time = time.replacen(',', ".", 1);
+    let naive = NaiveDateTime::parse_from_str(&time, "%Y-%m-%dT%H:%M:%S%.f")?;
+    match Local.from_local_datetime(&naive) {
+        LocalResult::Single(dt) | LocalResult::Ambiguous(dt, _) => Ok(dt.into()),
+        LocalResult::None => Err("Invalid time".into()),
+    }
 }
 
 fn parse_tm_posix(time: &str) -> Result<DateTime<Utc>, Box<dyn std::error::Error>> {
-    let mut time = String::from(time);
-    let mut seconds = String::from("0");
-
-    // split into YYYYMMDDhhmm and [.SS] components
-    let mut tmp_time = String::new();
-    match time.split_once('.') {
-        Some((t, secs)) => {
-            tmp_time = t.to_string();
-            seconds = secs.to_string();
-        }
-        None => {}
-    }
-    if !tmp_time.is_empty() {
-        time = tmp_time;
+    // split into [[CC]YY]MMDDhhmm and [.SS] components
+    let (time, seconds) = match time.split_once('.') {
+        Some((t, secs)) if secs.len() == 2 => (t, secs),
+        Some(_) => return Err("Invalid time format".into()),
+        None => (time, "00"),
+    };
+    if !time
+        .chars()
+        .chain(seconds.chars())
+        .all(|c| c.is_ascii_digit())
+    {
+        return Err("Invalid time format".into());
     }
 
     // extract date and time elements, with length implying format
-    let tmp_year;
-    let (year_str, month_str, day_str, hour_str, minute_str) = match time.len() {
+    let (year, rest) = match time.len() {
         // format: MMDDhhmm[.SS]
-        8 => {
-            tmp_year = Utc::now().year().to_string();
-            (
-                tmp_year.as_str(),
-                &time[0..2],
-                &time[2..4],
-                &time[4..6],
-                &time[6..8],
-            )
-        }
+        8 => (Local::now().year(), time),
 
         // format: YYMMDDhhmm[.SS]
         10 => {
-            let mut yearling = *&time[0..2].parse::<u32>()?;
+            let yearling = time[0..2].parse::<i32>()?;
             if yearling <= 68 {
-                yearling += 2000;
+                (2000 + yearling, &time[2..])
             } else {
-                yearling += 1900;
+                (1900 + yearling, &time[2..])
             }
-            tmp_year = yearling.to_string();
-            (
-                tmp_year.as_str(),
-                &time[2..4],
-                &time[4..6],
-                &time[6..8],
-                &time[8..10],
-            )
         }
 
-        // format: YYYYMMDDhhmm[.SS]
-        12 => (
-            &time[0..4],
-            &time[4..6],
-            &time[6..8],
-            &time[8..10],
-            &time[10..12],
-        ),
+        // format: CCYYMMDDhhmm[.SS]
+        12 => (time[0..4].parse::<i32>()?, &time[4..]),
+
         _ => {
             return Err("Invalid time format".into());
         }
     };
 
     // convert strings to integers
-    let year = year_str.parse::<i32>()?;
-    let month = month_str.parse::<u32>()?;
-    let day = day_str.parse::<u32>()?;
-    let hour = hour_str.parse::<u32>()?;
-    let minute = minute_str.parse::<u32>()?;
-    let secs = seconds.parse::<u32>()?;
-
-    // convert to DateTime and validate input
-    let res = Utc.with_ymd_and_hms(year, month, day, hour, minute, secs);
-    if res == LocalResult::None {
-        return Err("Invalid time".into());
+    let month = rest[0..2].parse::<u32>()?;
+    let day = rest[2..4].parse::<u32>()?;
+    let hour = rest[4..6].parse::<u32>()?;
+    let minute = rest[6..8].parse::<u32>()?;
+    let mut secs = seconds.parse::<u32>()?;
+
+    // a leap second is accepted, and refers to the next minute
+    let leap = secs == 60;
+    if leap {
+        secs = 59;
     }
 
-    // return parsed date
-    let dt = res.unwrap();
-    Ok(dt)
-}
+    // the time is interpreted in the local time zone; validate input
+    let dt = match Local.with_ymd_and_hms(year, month, day, hour, minute, secs) {
+        LocalResult::Single(dt) | LocalResult::Ambiguous(dt, _) => dt,
+        LocalResult::None => return Err("Invalid time".into()),
+    };
 
-fn parse_tm_ref_file(filename: &str) -> Result<DateTime<Utc>, Box<dyn std::error::Error>> {
-    let metadata = std::fs::metadata(filename)?;
-    let timespec = metadata.modified()?;
-    Ok(DateTime::from(timespec))
+    let mut dt: DateTime<Utc> = dt.into();
+    if leap {
+        dt += Duration::seconds(1);
+    }
+    Ok(dt)
 }
 
-fn touch_file_new(time: libc::time_t, filename: &str) -> Result<(), Box<dyn std::error::Error>> {
-    // open file for writing, creating if necessary
-    let flags = libc::O_CREAT | libc::O_WRONLY | libc::O_TRUNC;
-    let fd = unsafe { libc::open(filename.as_ptr() as *const i8, flags, 0o666) };
-    if fd < 0 {
-        return Err("Failed to open file".into());
+fn timespec_from(dt: &DateTime<Utc>) -> libc::timespec {
+    libc::timespec {
+        tv_sec: dt.timestamp() as libc::time_t,
+        tv_nsec: dt.timestamp_subsec_nanos() as _,
     }
+}
 
-    // configure file times array
-    let times = [
-        libc::timeval {
-            tv_sec: time,
-            tv_usec: 0,
-        },
-        libc::timeval {
-            tv_sec: time,
-            tv_usec: 0,
-        },
-    ];
+// build the [access, modification] times to apply to each file
+fn requested_times(args: &Args) -> Result<[libc::timespec; 2], Box<dyn std::error::Error>> {
+    let mut times = if let Some(datetime) = &args.datetime {
+        let ts = timespec_from(&parse_tm_iso(datetime)?);
+        [ts, ts]
+    } else if let Some(time) = &args.time {
+        let ts = timespec_from(&parse_tm_posix(time)?);
+        [ts, ts]
+    } else if let Some(ref_file) = &args.ref_file {
+        // use the corresponding times of the reference file
+        let md = fs::metadata(ref_file)?;
+        [
+            libc::timespec {
+                tv_sec: md.atime() as libc::time_t,
+                tv_nsec: md.atime_nsec() as _,
+            },
+            libc::timespec {
+                tv_sec: md.mtime() as libc::time_t,
+                tv_nsec: md.mtime_nsec() as _,
+            },
+        ]
+    } else {
+        let now = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: libc::UTIME_NOW,
+        };
+        [now, now]
+    };
 
-    // set file times
-    if unsafe { libc::futimes(fd, times.as_ptr()) } < 0 {
-        return Err("Failed to change file times".into());
+    // leave the times that were not selected unchanged
+    if !args.access {
+        times[0].tv_nsec = libc::UTIME_OMIT;
     }
-
-    // close file
-    if unsafe { libc::close(fd) } < 0 {
-        return Err("Failed to close file".into());
+    if !args.mtime {
+        times[1].tv_nsec = libc::UTIME_OMIT;
     }
 
-    Ok(())
+    Ok(times)
 }
 
-fn touch_file_existing(
-    args: &Args,
-    time: libc::time_t,
-    filename: &str,
-    md: std::fs::Metadata,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // configure access and modification times
-    let atime = if args.access {
-        libc::timeval {
-            tv_sec: time,
-            tv_usec: 0,
-        }
-    } else {
-        libc::timeval {
-            tv_sec: md
-                .accessed()?
-                .duration_since(std::time::UNIX_EPOCH)?
-                .as_secs() as libc::time_t,
-            tv_usec: 0,
+fn touch_file(args: &Args, times: &[libc::timespec; 2], filename: &str) -> io::Result<()> {
+    let path = CString::new(filename)?;
+
+    // create the file if it does not exist
+    if fs::metadata(filename).is_err() {
+        // silently skip missing files with -c
+        if args.no_create {
+            return Ok(());
         }
-    };
 
-    let mtime = if args.mtime {
-        libc::timeval {
-            tv_sec: time,
-            tv_usec: 0,
+        let flags = libc::O_CREAT | libc::O_WRONLY;
+        let fd = unsafe { libc::open(path.as_ptr(), flags, 0o666 as libc::c_uint) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
         }
-    } else {
-        libc::timeval {
-            tv_sec: md
-                .modified()?
-                .duration_since(std::time::UNIX_EPOCH)?
-                .as_secs() as libc::time_t,
-            tv_usec: 0,
+        if unsafe { libc::close(fd) } < 0 {
+            return Err(io::Error::last_os_error());
         }
-    };
-
-    // configure file times array
-    let times = [
-        libc::timeval {
-            tv_sec: atime.tv_sec,
-            tv_usec: atime.tv_usec,
-        },
-        libc::timeval {
-            tv_sec: mtime.tv_sec,
-            tv_usec: mtime.tv_usec,
-        },
-    ];
+    }
 
     // set file times
-    if unsafe { libc::utimes(filename.as_ptr() as *const i8, times.as_ptr()) } < 0 {
-        return Err("Failed to change file times".into());
+    if unsafe { libc::utimensat(libc::AT_FDCWD, path.as_ptr(), times.as_ptr(), 0) } < 0 {
+        return Err(io::Error::last_os_error());
     }
 
     Ok(())
 }
 
-fn touch_file(
-    args: &Args,
-    timespec: &DateTime<Utc>,
-    filename: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // convert timespec to time_t
-    let time = timespec.timestamp() as libc::time_t;
-
-    // check if file exists, and dispatch based on that
-    match std::fs::metadata(filename) {
-        Ok(md) => touch_file_existing(args, time, filename, md),
-        Err(_) => {
-            if args.no_create {
-                return Err("File does not exist".into());
-            }
-            touch_file_new(time, filename)
-        }
-    }
-}
-
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // parse command line arguments
     let mut args = Args::parse();
@@ -266,23 +223,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // parse time format, or default to current time
-    let timespec: DateTime<Utc> = {
-        if let Some(datetime) = &args.datetime {
-            parse_tm_iso(datetime)?
-        } else if let Some(time) = &args.time {
-            parse_tm_posix(time)?
-        } else if let Some(ref_file) = &args.ref_file {
-            parse_tm_ref_file(ref_file)?
-        } else {
-            Utc::now()
-        }
-    };
+    let times = requested_times(&args)?;
 
     let mut exit_code = 0;
 
     // touch each file
     for filename in &args.files {
-        if let Err(e) = touch_file(&args, &timespec, filename) {
+        if let Err(e) = touch_file(&args, &times, filename) {
             exit_code = 1;
             eprintln!("{}: {}", filename, e);
         }
@@ -0,0 +1,84 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use plib::{run_test, TestPlan};
+use std::fs;
+use std::os::unix::{self, fs::PermissionsExt};
+
+fn chmod_test(args: &[&str], expected_output: &str, expected_error: &str, expected_exit_code: i32) {
+    let str_args: Vec<String> = args.iter().map(|s| String::from(*s)).collect();
+
+    run_test(TestPlan {
+        cmd: String::from("chmod"),
+        args: str_args,
+        stdin_data: String::new(),
+        expected_out: String::from(expected_output),
+        expected_err: String::from(expected_error),
+        expected_exit_code,
+    });
+}
+
+fn mode_of(path: &str) -> u32 {
+    fs::symlink_metadata(path).unwrap().permissions().mode() & 0o7777
+}
+
+#[test]
+fn test_chmod_symbolic_clauses() {
+    let test_dir = &format!(
+        "{}/test_chmod_symbolic_clauses",
+        env!("CARGO_TARGET_TMPDIR")
+    );
+    let file = &format!("{test_dir}/file");
+
+    fs::create_dir(test_dir).unwrap();
+    fs::File::create(file).unwrap();
+    fs::set_permissions(file, fs::Permissions::from_mode(0o640)).unwrap();
+
+    chmod_test(&["u+x,g=u,o=g-x", file], "", "", 0);
+    assert_eq!(mode_of(file), 0o776);
+
+    chmod_test(&["a-x,a+X", file], "", "", 0);
+    assert_eq!(mode_of(file), 0o666);
+
+    chmod_test(&["go=,u+s", file], "", "", 0);
+    assert_eq!(mode_of(file), 0o4600);
+
+    fs::remove_dir_all(test_dir).unwrap();
+}
+
+#[test]
+fn test_chmod_recursive() {
+    let test_dir = &format!("{}/test_chmod_recursive", env!("CARGO_TARGET_TMPDIR"));
+    let sub = &format!("{test_dir}/sub");
+    let file = &format!("{test_dir}/sub/file");
+    let outside = &format!("{test_dir}/outside");
+    let link = &format!("{test_dir}/sub/link");
+
+    fs::create_dir_all(sub).unwrap();
+    fs::File::create(file).unwrap();
+    fs::File::create(outside).unwrap();
+    fs::set_permissions(file, fs::Permissions::from_mode(0o600)).unwrap();
+    fs::set_permissions(outside, fs::Permissions::from_mode(0o600)).unwrap();
+    unix::fs::symlink(outside, link).unwrap();
+
+    chmod_test(&["-R", "go+rX", sub], "", "", 0);
+
+    assert_eq!(mode_of(sub) & 0o055, 0o055);
+    assert_eq!(mode_of(file), 0o644);
+
+    // symlinks encountered during the traversal are not followed
+    assert_eq!(mode_of(outside), 0o600);
+
+    fs::remove_dir_all(test_dir).unwrap();
+}
+
+#[test]
+fn test_chmod_invalid_mode() {
+    chmod_test(&["u+z", "."], "", "Error: \"unexpected character: z\"\n", 1);
+}
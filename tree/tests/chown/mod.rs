@@ -0,0 +1,117 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use plib::{run_test, TestPlan};
+use std::fs;
+use std::os::unix::{self, fs::MetadataExt};
+
+fn tree_test(
+    cmd: &str,
+    args: &[&str],
+    expected_output: &str,
+    expected_error: &str,
+    expected_exit_code: i32,
+) {
+    let str_args: Vec<String> = args.iter().map(|s| String::from(*s)).collect();
+
+    run_test(TestPlan {
+        cmd: String::from(cmd),
+        args: str_args,
+        stdin_data: String::new(),
+        expected_out: String::from(expected_output),
+        expected_err: String::from(expected_error),
+        expected_exit_code,
+    });
+}
+
+#[test]
+fn test_chgrp_recursive_own_group() {
+    let test_dir = &format!(
+        "{}/test_chgrp_recursive_own_group",
+        env!("CARGO_TARGET_TMPDIR")
+    );
+    let sub = &format!("{test_dir}/sub");
+    let file = &format!("{test_dir}/sub/file");
+
+    fs::create_dir_all(sub).unwrap();
+    fs::File::create(file).unwrap();
+
+    let gid = unsafe { libc::getegid() }.to_string();
+    tree_test("chgrp", &["-R", &gid, test_dir], "", "", 0);
+
+    assert_eq!(fs::metadata(file).unwrap().gid().to_string(), gid);
+
+    fs::remove_dir_all(test_dir).unwrap();
+}
+
+#[test]
+fn test_chown_missing_file() {
+    let test_dir = &format!("{}/test_chown_missing_file", env!("CARGO_TARGET_TMPDIR"));
+    let missing = &format!("{test_dir}/missing");
+
+    fs::create_dir(test_dir).unwrap();
+
+    let uid = unsafe { libc::geteuid() }.to_string();
+    tree_test(
+        "chown",
+        &[&uid, missing],
+        "",
+        &format!("{missing}: No such file or directory\n"),
+        1,
+    );
+
+    fs::remove_dir_all(test_dir).unwrap();
+}
+
+// This test needs root access to give files away.
+#[test]
+#[cfg_attr(
+    not(all(
+        target_os = "linux",
+        feature = "posixutils_test_all",
+        feature = "requires_root"
+    )),
+    ignore
+)]
+fn test_chown_recursive_symlinks() {
+    let test_dir = &format!(
+        "{}/test_chown_recursive_symlinks",
+        env!("CARGO_TARGET_TMPDIR")
+    );
+    let top = &format!("{test_dir}/top");
+    let file = &format!("{test_dir}/top/file");
+    let link = &format!("{test_dir}/top/link");
+    let target = &format!("{test_dir}/target");
+    let target_file = &format!("{test_dir}/target/file");
+
+    fs::create_dir_all(top).unwrap();
+    fs::create_dir_all(target).unwrap();
+    fs::File::create(file).unwrap();
+    fs::File::create(target_file).unwrap();
+    unix::fs::symlink(target, link).unwrap();
+
+    // -P changes the link itself and does not follow it
+    tree_test("chown", &["-R", "-P", "1234:5678", top], "", "", 0);
+    assert_eq!(fs::metadata(file).unwrap().uid(), 1234);
+    assert_eq!(fs::metadata(file).unwrap().gid(), 5678);
+    assert_eq!(fs::symlink_metadata(link).unwrap().uid(), 1234);
+    assert_eq!(fs::metadata(target_file).unwrap().uid(), 0);
+
+    // -L follows links found during the traversal
+    tree_test("chown", &["-R", "-L", "2345", top], "", "", 0);
+    assert_eq!(fs::metadata(target_file).unwrap().uid(), 2345);
+    assert_eq!(fs::metadata(target_file).unwrap().gid(), 0);
+
+    // -h without -R changes the link named on the command line
+    tree_test("chgrp", &["-h", "4321", link], "", "", 0);
+    assert_eq!(fs::symlink_metadata(link).unwrap().gid(), 4321);
+    assert_eq!(fs::metadata(target).unwrap().gid(), 0);
+
+    fs::remove_dir_all(test_dir).unwrap();
+}
@@ -0,0 +1,92 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use plib::{run_test, TestPlan};
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::MetadataExt;
+
+fn du_test(args: &[&str], expected_output: &str, expected_error: &str, expected_exit_code: i32) {
+    let str_args: Vec<String> = args.iter().map(|s| String::from(*s)).collect();
+
+    run_test(TestPlan {
+        cmd: String::from("du"),
+        args: str_args,
+        stdin_data: String::new(),
+        expected_out: String::from(expected_output),
+        expected_err: String::from(expected_error),
+        expected_exit_code,
+    });
+}
+
+fn blocks(path: &str) -> u64 {
+    fs::symlink_metadata(path).unwrap().blocks()
+}
+
+#[test]
+fn test_du_hard_links_counted_once() {
+    let test_dir = &format!(
+        "{}/test_du_hard_links_counted_once",
+        env!("CARGO_TARGET_TMPDIR")
+    );
+    let file = &format!("{test_dir}/file");
+    let link = &format!("{test_dir}/link");
+
+    fs::create_dir(test_dir).unwrap();
+    let mut f = fs::File::create(file).unwrap();
+    f.write_all(&[b'x'; 20000]).unwrap();
+    f.sync_all().unwrap();
+    fs::hard_link(file, link).unwrap();
+
+    let total = blocks(test_dir) + blocks(file);
+
+    du_test(&["-s", test_dir], &format!("{total}\t{test_dir}\n"), "", 0);
+    du_test(
+        &["-s", "-k", test_dir],
+        &format!("{}\t{test_dir}\n", total.div_ceil(2)),
+        "",
+        0,
+    );
+
+    // the second operand naming the same file is not counted again
+    du_test(&[file, link], &format!("{}\t{file}\n", blocks(file)), "", 0);
+
+    fs::remove_dir_all(test_dir).unwrap();
+}
+
+#[test]
+fn test_du_all_files() {
+    let test_dir = &format!("{}/test_du_all_files", env!("CARGO_TARGET_TMPDIR"));
+    let sub = &format!("{test_dir}/sub");
+    let file = &format!("{test_dir}/sub/file");
+
+    fs::create_dir_all(sub).unwrap();
+    fs::File::create(file).unwrap();
+
+    let sub_total = blocks(sub) + blocks(file);
+    let total = blocks(test_dir) + sub_total;
+
+    du_test(
+        &[test_dir],
+        &format!("{sub_total}\t{sub}\n{total}\t{test_dir}\n"),
+        "",
+        0,
+    );
+    du_test(
+        &["-a", test_dir],
+        &format!(
+            "{}\t{file}\n{sub_total}\t{sub}\n{total}\t{test_dir}\n",
+            blocks(file)
+        ),
+        "",
+        0,
+    );
+
+    fs::remove_dir_all(test_dir).unwrap();
+}
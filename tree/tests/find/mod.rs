@@ -0,0 +1,398 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use plib::{run_test_with_checker, TestPlan};
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::process::Output;
+
+// Run find and compare its output lines, in any order, to `expected`
+fn find_test(args: &[&str], expected: &[&str], expected_exit_code: i32) {
+    find_test_with_stdin(args, "", expected, expected_exit_code);
+}
+
+fn find_test_with_stdin(args: &[&str], stdin: &str, expected: &[&str], expected_exit_code: i32) {
+    let str_args: Vec<String> = args.iter().map(|s| String::from(*s)).collect();
+
+    let test_plan = TestPlan {
+        cmd: String::from("find"),
+        args: str_args,
+        stdin_data: String::from(stdin),
+        expected_out: String::new(),
+        expected_err: String::new(),
+        expected_exit_code,
+    };
+
+    run_test_with_checker(test_plan, |plan: &TestPlan, output: &Output| {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut lines: Vec<&str> = stdout.lines().collect();
+        lines.sort();
+        let mut expected = expected.to_vec();
+        expected.sort();
+        assert_eq!(lines, expected);
+        assert_eq!(output.status.code(), Some(plan.expected_exit_code));
+    });
+}
+
+// Create this hierarchy under test_dir:
+//   a/           directory
+//   a/x.c        0755, 1000 bytes
+//   a/b/         directory
+//   a/b/y.c      0644, empty
+//   .hidden      0600, empty
+fn create_tree(test_dir: &str) {
+    fs::create_dir_all(format!("{test_dir}/a/b")).unwrap();
+
+    let x = format!("{test_dir}/a/x.c");
+    let mut f = fs::File::create(&x).unwrap();
+    f.write_all(&[b'x'; 1000]).unwrap();
+    fs::set_permissions(&x, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let y = format!("{test_dir}/a/b/y.c");
+    fs::File::create(&y).unwrap();
+    fs::set_permissions(&y, fs::Permissions::from_mode(0o644)).unwrap();
+
+    let hidden = format!("{test_dir}/.hidden");
+    fs::File::create(&hidden).unwrap();
+    fs::set_permissions(&hidden, fs::Permissions::from_mode(0o600)).unwrap();
+}
+
+#[test]
+fn test_find_name_and_path() {
+    let test_dir = &format!("{}/test_find_name_and_path", env!("CARGO_TARGET_TMPDIR"));
+    create_tree(test_dir);
+
+    let x = &format!("{test_dir}/a/x.c");
+    let y = &format!("{test_dir}/a/b/y.c");
+    let b = &format!("{test_dir}/a/b");
+    let hidden = &format!("{test_dir}/.hidden");
+
+    find_test(&[test_dir, "-name", "*.c"], &[x, y], 0);
+    find_test(
+        &[test_dir, "-name", "*"],
+        &[test_dir, &format!("{test_dir}/a"), b, x, y, hidden],
+        0,
+    );
+    find_test(&[test_dir, "-path", "*/a/b*"], &[b, y], 0);
+    find_test(&[test_dir, "-type", "d", "-name", "b"], &[b], 0);
+
+    fs::remove_dir_all(test_dir).unwrap();
+}
+
+#[test]
+fn test_find_size_perm_links() {
+    let test_dir = &format!("{}/test_find_size_perm_links", env!("CARGO_TARGET_TMPDIR"));
+    create_tree(test_dir);
+
+    let x = &format!("{test_dir}/a/x.c");
+    let y = &format!("{test_dir}/a/b/y.c");
+    let hidden = &format!("{test_dir}/.hidden");
+
+    find_test(&[test_dir, "-size", "1000c"], &[x], 0);
+    find_test(&[test_dir, "-type", "f", "-size", "+1"], &[x], 0);
+    find_test(&[test_dir, "-type", "f", "-size", "-1"], &[y, hidden], 0);
+    find_test(&[test_dir, "-perm", "644"], &[y], 0);
+    find_test(&[test_dir, "-type", "f", "-perm", "-u+x"], &[x], 0);
+    find_test(&[test_dir, "-type", "f", "-perm", "-044"], &[x, y], 0);
+    // symbolic templates are applied as if the umask were zero
+    find_test(&[test_dir, "-type", "f", "-perm", "-+w"], &[], 0);
+    find_test(&[test_dir, "-type", "f", "-perm", "u=rwx,+rx"], &[x], 0);
+    find_test(
+        &[test_dir, "-type", "f", "-links", "1", "-name", "y.c"],
+        &[y],
+        0,
+    );
+
+    fs::remove_dir_all(test_dir).unwrap();
+}
+
+#[test]
+fn test_find_times_and_newer() {
+    let test_dir = &format!("{}/test_find_times_and_newer", env!("CARGO_TARGET_TMPDIR"));
+    create_tree(test_dir);
+
+    let x = &format!("{test_dir}/a/x.c");
+    let y = &format!("{test_dir}/a/b/y.c");
+
+    // make x.c 10 days old
+    let old = std::time::SystemTime::now() - std::time::Duration::from_secs(10 * 86400);
+    fs::File::options()
+        .write(true)
+        .open(x)
+        .unwrap()
+        .set_modified(old)
+        .unwrap();
+
+    find_test(&[test_dir, "-type", "f", "-mtime", "+5"], &[x], 0);
+    find_test(&[test_dir, "-name", "*.c", "-mtime", "-1"], &[y], 0);
+    find_test(&[test_dir, "-name", "*.c", "-mtime", "10"], &[x], 0);
+    find_test(&[test_dir, "-name", "*.c", "-newer", x], &[y], 0);
+
+    fs::remove_dir_all(test_dir).unwrap();
+}
+
+#[test]
+fn test_find_user_group() {
+    let test_dir = &format!("{}/test_find_user_group", env!("CARGO_TARGET_TMPDIR"));
+    create_tree(test_dir);
+
+    let y = &format!("{test_dir}/a/b/y.c");
+    let uid = unsafe { libc::geteuid() }.to_string();
+    let gid = unsafe { libc::getegid() }.to_string();
+
+    find_test(&[y, "-user", &uid, "-group", &gid, "-print"], &[y], 0);
+    find_test(&[y, "-user", "4294967294"], &[], 0);
+
+    fs::remove_dir_all(test_dir).unwrap();
+}
+
+#[test]
+fn test_find_missing_path() {
+    let test_dir = &format!("{}/test_find_missing_path", env!("CARGO_TARGET_TMPDIR"));
+    fs::create_dir(test_dir).unwrap();
+
+    find_test(&[&format!("{test_dir}/missing"), test_dir], &[test_dir], 1);
+
+    fs::remove_dir_all(test_dir).unwrap();
+}
+
+#[test]
+fn test_find_exec() {
+    let test_dir = &format!("{}/test_find_exec", env!("CARGO_TARGET_TMPDIR"));
+    create_tree(test_dir);
+
+    let x = &format!("{test_dir}/a/x.c");
+    let y = &format!("{test_dir}/a/b/y.c");
+
+    // one invocation per file, with every {} replaced
+    find_test(
+        &[test_dir, "-name", "*.c", "-exec", "echo", "<{}>", "{}", ";"],
+        &[&format!("<{x}> {x}"), &format!("<{y}> {y}")],
+        0,
+    );
+
+    // the value of -exec is the exit status of the utility
+    find_test(
+        &[test_dir, "-name", "x.c", "-exec", "true", ";", "-print"],
+        &[x],
+        0,
+    );
+    find_test(
+        &[test_dir, "-name", "x.c", "-exec", "false", ";", "-print"],
+        &[],
+        0,
+    );
+}
+
+#[test]
+fn test_find_exec_batch() {
+    let test_dir = &format!("{}/test_find_exec_batch", env!("CARGO_TARGET_TMPDIR"));
+    create_tree(test_dir);
+
+    let x = &format!("{test_dir}/a/x.c");
+    let y = &format!("{test_dir}/a/b/y.c");
+
+    // all pathnames are passed to a single invocation
+    let run = std::process::Command::new(env!("CARGO_BIN_EXE_find"))
+        .args([
+            test_dir, "-name", "*.c", "-exec", "echo", "files:", "{}", "+",
+        ])
+        .output()
+        .unwrap();
+    assert_eq!(run.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&run.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 1);
+    let mut words: Vec<&str> = lines[0].split(' ').collect();
+    words.sort();
+    let mut expected = vec!["files:", x.as_str(), y.as_str()];
+    expected.sort();
+    assert_eq!(words, expected);
+
+    // a failing invocation is reflected in the exit status of find
+    find_test(&[test_dir, "-exec", "false", "{}", "+"], &[], 1);
+}
+
+#[test]
+fn test_find_ok() {
+    let test_dir = &format!("{}/test_find_ok", env!("CARGO_TARGET_TMPDIR"));
+    create_tree(test_dir);
+
+    let x = &format!("{test_dir}/a/x.c");
+
+    find_test_with_stdin(
+        &[test_dir, "-name", "x.c", "-ok", "echo", "ran", "{}", ";"],
+        "y\n",
+        &[&format!("ran {x}")],
+        0,
+    );
+    find_test_with_stdin(
+        &[test_dir, "-name", "x.c", "-ok", "echo", "ran", "{}", ";"],
+        "n\n",
+        &[],
+        0,
+    );
+}
+
+#[test]
+fn test_find_exec_errors() {
+    let test_dir = &format!("{}/test_find_exec_errors", env!("CARGO_TARGET_TMPDIR"));
+    create_tree(test_dir);
+
+    // unterminated command
+    find_test(&[test_dir, "-exec", "echo", "{}"], &[], 1);
+
+    // utility that cannot be executed
+    find_test(
+        &[
+            test_dir,
+            "-name",
+            "x.c",
+            "-exec",
+            "/nonexistent/utility",
+            ";",
+        ],
+        &[],
+        1,
+    );
+}
+
+#[test]
+fn test_find_operators() {
+    let test_dir = &format!("{}/test_find_operators", env!("CARGO_TARGET_TMPDIR"));
+    create_tree(test_dir);
+
+    let a = &format!("{test_dir}/a");
+    let b = &format!("{test_dir}/a/b");
+    let x = &format!("{test_dir}/a/x.c");
+    let y = &format!("{test_dir}/a/b/y.c");
+    let hidden = &format!("{test_dir}/.hidden");
+
+    find_test(&[test_dir, "!", "-type", "d"], &[x, y, hidden], 0);
+    find_test(&[test_dir, "-type", "f", "-a", "-name", "x.c"], &[x], 0);
+    find_test(
+        &[test_dir, "-name", "x.c", "-o", "-name", "y.c"],
+        &[x, y],
+        0,
+    );
+
+    // -a binds more tightly than -o
+    find_test(
+        &[test_dir, "-name", "b", "-o", "-type", "f", "-name", "*.c"],
+        &[b, x, y],
+        0,
+    );
+    find_test(
+        &[
+            test_dir, "(", "-name", "b", "-o", "-type", "f", ")", "-name", "*.c",
+        ],
+        &[x, y],
+        0,
+    );
+
+    // -o short-circuits, so -print is only evaluated for non-directories
+    find_test(
+        &[test_dir, "-type", "d", "-o", "-print"],
+        &[x, y, hidden],
+        0,
+    );
+    find_test(
+        &[test_dir, "!", "(", "-type", "f", ")", "-print"],
+        &[test_dir, a, b],
+        0,
+    );
+
+    find_test(&[test_dir, "(", "-name", "x.c"], &[], 1);
+    find_test(&[test_dir, "-name", "x.c", "-o"], &[], 1);
+    find_test(&[test_dir, "(", ")"], &[], 1);
+}
+
+#[test]
+fn test_find_prune() {
+    let test_dir = &format!("{}/test_find_prune", env!("CARGO_TARGET_TMPDIR"));
+    create_tree(test_dir);
+
+    let a = &format!("{test_dir}/a");
+    let b = &format!("{test_dir}/a/b");
+    let x = &format!("{test_dir}/a/x.c");
+    let hidden = &format!("{test_dir}/.hidden");
+
+    find_test(
+        &[test_dir, "-name", "b", "-prune", "-o", "-print"],
+        &[test_dir, a, x, hidden],
+        0,
+    );
+    find_test(&[test_dir, "-name", "b", "-prune"], &[b], 0);
+}
+
+#[test]
+fn test_find_depth() {
+    let test_dir = &format!("{}/test_find_depth", env!("CARGO_TARGET_TMPDIR"));
+    create_tree(test_dir);
+
+    let test_plan = TestPlan {
+        cmd: String::from("find"),
+        args: vec![format!("{test_dir}/a"), String::from("-depth")],
+        stdin_data: String::new(),
+        expected_out: String::new(),
+        expected_err: String::new(),
+        expected_exit_code: 0,
+    };
+
+    // every directory is listed after its entries
+    run_test_with_checker(test_plan, |_: &TestPlan, output: &Output| {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let lines: Vec<&str> = stdout.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[3], format!("{test_dir}/a"));
+        let pos = |p: &str| lines.iter().position(|l| *l == p).unwrap();
+        assert!(pos(&format!("{test_dir}/a/b/y.c")) < pos(&format!("{test_dir}/a/b")));
+    });
+}
+
+#[test]
+fn test_find_xdev() {
+    let test_dir = &format!("{}/test_find_xdev", env!("CARGO_TARGET_TMPDIR"));
+    create_tree(test_dir);
+
+    let x = &format!("{test_dir}/a/x.c");
+
+    // everything is on the same device
+    find_test(&[test_dir, "-xdev", "-name", "x.c"], &[x], 0);
+}
+
+#[test]
+fn test_find_parallel() {
+    let test_dir = &format!("{}/test_find_parallel", env!("CARGO_TARGET_TMPDIR"));
+    create_tree(test_dir);
+    for i in 0..20 {
+        fs::create_dir_all(format!("{test_dir}/d{i}/e")).unwrap();
+        fs::File::create(format!("{test_dir}/d{i}/e/f.c")).unwrap();
+    }
+
+    let mut expected: Vec<String> = (0..20).map(|i| format!("{test_dir}/d{i}/e/f.c")).collect();
+    expected.push(format!("{test_dir}/a/x.c"));
+    expected.push(format!("{test_dir}/a/b/y.c"));
+    let expected: Vec<&str> = expected.iter().map(|s| s.as_str()).collect();
+
+    find_test(&["-j", "4", test_dir, "-name", "*.c"], &expected, 0);
+    find_test(&["-j4", test_dir, "-name", "*.c"], &expected, 0);
+
+    // pruned directories are not read
+    find_test(
+        &[
+            "-j", "4", test_dir, "-name", "e", "-prune", "-o", "-name", "*.c", "-print",
+        ],
+        &[&format!("{test_dir}/a/x.c"), &format!("{test_dir}/a/b/y.c")],
+        0,
+    );
+
+    find_test(&["-j", "0", test_dir], &[], 1);
+}
@@ -7,7 +7,12 @@
 // SPDX-License-Identifier: MIT
 //
 
+mod chmod;
+mod chown;
 mod cp;
+mod du;
+mod find;
 mod ls;
 mod mv;
 mod rm;
+mod touch;
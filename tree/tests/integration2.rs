@@ -60,6 +60,19 @@ fn rm_test(args: &[&str], expected_output: &str, expected_error: &str, expected_
     });
 }
 
+fn mkfifo_test(args: &[&str], expected_error: &str, expected_exit_code: i32) {
+    let str_args: Vec<String> = args.iter().map(|s| String::from(*s)).collect();
+
+    run_test(TestPlan {
+        cmd: String::from("mkfifo"),
+        args: str_args,
+        stdin_data: String::new(),
+        expected_out: String::new(),
+        expected_err: String::from(expected_error),
+        expected_exit_code,
+    });
+}
+
 // Port of coreutils/tests/cp/existing-perm-dir.sh
 #[test]
 fn test_cp_existing_perm_dir() {
@@ -195,3 +208,41 @@ fn test_rm_deep_1() {
     umask_setter.umask(original_umask);
     fs::remove_dir_all(test_dir).unwrap();
 }
+
+#[test]
+fn test_mkfifo_mode() {
+    let test_dir = &format!("{}/test_mkfifo_mode", env!("CARGO_TARGET_TMPDIR"));
+    let f1 = &format!("{test_dir}/f1");
+    let f2 = &format!("{test_dir}/f2");
+    let f3 = &format!("{test_dir}/f3");
+    let f4 = &format!("{test_dir}/f4");
+
+    fs::create_dir(test_dir).unwrap();
+
+    let umask_setter = UMASK_SETTER.lock().unwrap();
+    let original_umask = umask_setter.umask(0o077);
+
+    let mode = |path: &str| fs::metadata(path).unwrap().mode();
+
+    // without -m, the umask applies
+    mkfifo_test(&[f1, f2], "", 0);
+    assert_eq!(mode(f1) & libc::S_IFMT, libc::S_IFIFO);
+    assert_eq!(mode(f1) & 0o7777, 0o600);
+    assert_eq!(mode(f2) & 0o7777, 0o600);
+
+    // an explicit mode is independent of the umask
+    mkfifo_test(&["-m", "644", f3], "", 0);
+    assert_eq!(mode(f3) & 0o7777, 0o644);
+
+    // symbolic modes start from a=rw
+    mkfifo_test(&["-m", "g-w,o=", f4], "", 0);
+    assert_eq!(mode(f4) & 0o7777, 0o640);
+
+    // existing files are reported, and the remaining operands created
+    let f5 = &format!("{test_dir}/f5");
+    mkfifo_test(&[f1, f5], &format!("{f1}: File exists (os error 17)\n"), 1);
+    assert!(Path::new(f5).exists());
+
+    umask_setter.umask(original_umask);
+    fs::remove_dir_all(test_dir).unwrap();
+}
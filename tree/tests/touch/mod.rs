@@ -0,0 +1,116 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use chrono::{Local, TimeZone};
+use plib::{run_test, TestPlan};
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+fn touch_test(args: &[&str], expected_output: &str, expected_error: &str, expected_exit_code: i32) {
+    let str_args: Vec<String> = args.iter().map(|s| String::from(*s)).collect();
+
+    run_test(TestPlan {
+        cmd: String::from("touch"),
+        args: str_args,
+        stdin_data: String::new(),
+        expected_out: String::from(expected_output),
+        expected_err: String::from(expected_error),
+        expected_exit_code,
+    });
+}
+
+#[test]
+fn test_touch_datetime_nanoseconds() {
+    let test_dir = &format!(
+        "{}/test_touch_datetime_nanoseconds",
+        env!("CARGO_TARGET_TMPDIR")
+    );
+    let file = &format!("{test_dir}/file");
+
+    fs::create_dir(test_dir).unwrap();
+
+    touch_test(&["-d", "2000-01-02T03:04:05.123456789Z", file], "", "", 0);
+
+    let md = fs::metadata(file).unwrap();
+    assert_eq!(md.mtime(), 946782245);
+    assert_eq!(md.mtime_nsec(), 123456789);
+    assert_eq!(md.atime(), 946782245);
+    assert_eq!(md.atime_nsec(), 123456789);
+
+    fs::remove_dir_all(test_dir).unwrap();
+}
+
+#[test]
+fn test_touch_posix_time_local() {
+    let test_dir = &format!(
+        "{}/test_touch_posix_time_local",
+        env!("CARGO_TARGET_TMPDIR")
+    );
+    let file = &format!("{test_dir}/file");
+
+    fs::create_dir(test_dir).unwrap();
+    fs::File::create(file).unwrap();
+
+    touch_test(&["-m", "-t", "199912312359.30", file], "", "", 0);
+
+    let expected = Local
+        .with_ymd_and_hms(1999, 12, 31, 23, 59, 30)
+        .unwrap()
+        .timestamp();
+    let md = fs::metadata(file).unwrap();
+    assert_eq!(md.mtime(), expected);
+    assert_ne!(md.atime(), expected);
+
+    touch_test(
+        &["-t", "12312359.5", file],
+        "",
+        "Error: \"Invalid time format\"\n",
+        1,
+    );
+
+    fs::remove_dir_all(test_dir).unwrap();
+}
+
+#[test]
+fn test_touch_reference_access_only() {
+    let test_dir = &format!(
+        "{}/test_touch_reference_access_only",
+        env!("CARGO_TARGET_TMPDIR")
+    );
+    let reference = &format!("{test_dir}/reference");
+    let file = &format!("{test_dir}/file");
+
+    fs::create_dir(test_dir).unwrap();
+
+    touch_test(&["-d", "2001-02-03T04:05:06Z", reference], "", "", 0);
+    touch_test(&["-m", "-d", "2010-01-01T00:00:00Z", reference], "", "", 0);
+    touch_test(&["-d", "2020-01-01T00:00:00Z", file], "", "", 0);
+
+    touch_test(&["-a", "-r", reference, file], "", "", 0);
+
+    let md = fs::metadata(file).unwrap();
+    assert_eq!(md.atime(), 981173106);
+    assert_eq!(md.mtime(), 1577836800);
+
+    fs::remove_dir_all(test_dir).unwrap();
+}
+
+#[test]
+fn test_touch_no_create() {
+    let test_dir = &format!("{}/test_touch_no_create", env!("CARGO_TARGET_TMPDIR"));
+    let file = &format!("{test_dir}/file");
+
+    fs::create_dir(test_dir).unwrap();
+
+    touch_test(&["-c", file], "", "", 0);
+    assert!(!Path::new(file).exists());
+
+    fs::remove_dir_all(test_dir).unwrap();
+}
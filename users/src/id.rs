@@ -6,19 +6,16 @@
 // file in the root directory of this project.
 // SPDX-License-Identifier: MIT
 //
-// TODO:
-// - bug: only one group is returned, in group list (MacOS-only?)
-//
 
 extern crate clap;
 extern crate libc;
 extern crate plib;
 
 use clap::Parser;
-use gettextrs::{bind_textdomain_codeset, textdomain};
+use gettextrs::{bind_textdomain_codeset, gettext, textdomain};
 use plib::PROJECT_NAME;
-use std::collections::HashMap;
-use std::io::Error;
+use std::ffi::{CStr, CString};
+use std::io;
 
 /// id - return user identity
 #[derive(Parser, Debug)]
@@ -37,11 +34,11 @@ struct Args {
     e_user: bool,
 
     /// Output the real ID instead of the effective ID.
-    #[arg(short, long)]
+    #[arg(short, long, requires = "output")]
     real: bool,
 
     /// Output the name in string format, instead of the numeric
-    #[arg(short, long)]
+    #[arg(short, long, requires = "output")]
     name: bool,
 
     /// The login name for which information is to be written.
@@ -53,147 +50,196 @@ struct UserInfo {
     gid: libc::gid_t,
     euid: libc::uid_t,
     egid: libc::gid_t,
-    groups: Vec<libc::gid_t>,
 
-    username: String,
-    group_names: HashMap<libc::gid_t, String>,
+    /// Supplementary group IDs.
+    groups: Vec<libc::gid_t>,
 }
 
-fn userinfo_process(userinfo: &mut UserInfo) -> Result<(), Box<dyn std::error::Error>> {
-    userinfo.uid = unsafe { libc::getuid() };
-    userinfo.gid = unsafe { libc::getgid() };
-    userinfo.euid = unsafe { libc::geteuid() };
-    userinfo.egid = unsafe { libc::getegid() };
-
-    let passwd = unsafe { libc::getpwuid(userinfo.uid) };
+fn user_name(uid: libc::uid_t) -> Option<String> {
+    let passwd = unsafe { libc::getpwuid(uid) };
     if passwd.is_null() {
-        let err = Error::last_os_error();
-        eprintln!("getpwuid: {}", err);
-        return Err(Box::new(err));
+        return None;
     }
-
-    userinfo.username = unsafe {
-        std::ffi::CStr::from_ptr((*passwd).pw_name)
-            .to_string_lossy()
-            .to_string()
-    };
-
-    Ok(())
+    let name = unsafe { CStr::from_ptr((*passwd).pw_name) };
+    Some(name.to_string_lossy().into_owned())
 }
 
-fn userinfo_name(userinfo: &mut UserInfo, user: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let user_str = std::ffi::CString::new(user).unwrap();
-    let passwd = unsafe { libc::getpwnam(user_str.as_ptr()) };
-    if passwd.is_null() {
-        let err = Error::last_os_error();
-        eprintln!("getpwnam: {}", err);
-        return Err(Box::new(err));
+fn group_name(gid: libc::gid_t) -> Option<String> {
+    let group = unsafe { libc::getgrgid(gid) };
+    if group.is_null() {
+        return None;
     }
+    let name = unsafe { CStr::from_ptr((*group).gr_name) };
+    Some(name.to_string_lossy().into_owned())
+}
 
-    unsafe {
-        userinfo.uid = (*passwd).pw_uid;
-        userinfo.gid = (*passwd).pw_gid;
+// the identity of the invoking process
+fn process_info() -> io::Result<UserInfo> {
+    let count = unsafe { libc::getgroups(0, std::ptr::null_mut()) };
+    if count < 0 {
+        return Err(io::Error::last_os_error());
     }
-    userinfo.euid = userinfo.uid;
-    userinfo.egid = userinfo.gid;
-    userinfo.username = unsafe {
-        std::ffi::CStr::from_ptr((*passwd).pw_name)
-            .to_string_lossy()
-            .to_string()
-    };
-
-    Ok(())
+    let mut groups: Vec<libc::gid_t> = vec![0; count as usize];
+    let count = unsafe { libc::getgroups(count, groups.as_mut_ptr()) };
+    if count < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    groups.truncate(count as usize);
+
+    Ok(UserInfo {
+        uid: unsafe { libc::getuid() },
+        gid: unsafe { libc::getgid() },
+        euid: unsafe { libc::geteuid() },
+        egid: unsafe { libc::getegid() },
+        groups,
+    })
 }
 
-fn get_user_info(args: &Args) -> Result<UserInfo, Box<dyn std::error::Error>> {
-    let mut userinfo = UserInfo {
-        uid: 0,
-        gid: 0,
-        euid: 0,
-        egid: 0,
-        groups: Vec::new(),
-        username: String::new(),
-        group_names: HashMap::new(),
-    };
-
-    // fill in uid, gid
-    match args.user {
-        None => userinfo_process(&mut userinfo)?,
-        Some(ref user) => userinfo_name(&mut userinfo, user)?,
+// the groups of the user `name`, whose group database entry is `gid`
+#[cfg(not(target_os = "macos"))]
+fn user_groups(name: &CStr, gid: libc::gid_t) -> Vec<libc::gid_t> {
+    let mut count: libc::c_int = 32;
+    loop {
+        let mut groups: Vec<libc::gid_t> = vec![0; count as usize];
+        let res =
+            unsafe { libc::getgrouplist(name.as_ptr(), gid, groups.as_mut_ptr(), &mut count) };
+        if res >= 0 {
+            groups.truncate(count as usize);
+            return groups;
+        }
+        count *= 2;
     }
-
-    Ok(userinfo)
 }
 
-fn get_group_info(userinfo: &mut UserInfo) -> Result<(), Box<dyn std::error::Error>> {
-    let groups = plib::group::load();
-
-    for group in &groups {
-        // skip groups that the user is not a member of
-        let mut found = false;
-        for member in &group.members {
-            if *member == userinfo.username {
-                found = true;
-                break;
-            }
+#[cfg(target_os = "macos")]
+fn user_groups(name: &CStr, gid: libc::gid_t) -> Vec<libc::gid_t> {
+    let mut count: libc::c_int = 32;
+    loop {
+        let mut groups: Vec<libc::c_int> = vec![0; count as usize];
+        let res = unsafe {
+            libc::getgrouplist(
+                name.as_ptr(),
+                gid as libc::c_int,
+                groups.as_mut_ptr(),
+                &mut count,
+            )
+        };
+        if res >= 0 {
+            groups.truncate(count as usize);
+            return groups.into_iter().map(|g| g as libc::gid_t).collect();
         }
-        if !found && group.gid != userinfo.gid {
-            continue;
+        count *= 2;
+    }
+}
+
+// the identity of the user `user`, named by login name or user ID
+fn named_info(user: &str) -> Option<UserInfo> {
+    let c_user = CString::new(user).ok()?;
+    let mut passwd = unsafe { libc::getpwnam(c_user.as_ptr()) };
+    if passwd.is_null() {
+        if let Ok(uid) = user.parse::<libc::uid_t>() {
+            passwd = unsafe { libc::getpwuid(uid) };
         }
+    }
+    if passwd.is_null() {
+        return None;
+    }
 
-        // add group to user's group list
-        userinfo.groups.push(group.gid);
-        userinfo.group_names.insert(group.gid, group.name.clone());
+    let (uid, gid) = unsafe { ((*passwd).pw_uid, (*passwd).pw_gid) };
+    let name = unsafe { CStr::from_ptr((*passwd).pw_name) }.to_owned();
+    Some(UserInfo {
+        uid,
+        gid,
+        euid: uid,
+        egid: gid,
+        groups: user_groups(&name, gid),
+    })
+}
+
+// an ID, or its name if it has one and names were requested
+fn id_str(id: u32, name: Option<String>, want_name: bool) -> String {
+    match name {
+        Some(name) if want_name => name,
+        _ => id.to_string(),
     }
+}
 
-    Ok(())
+// an ID followed by its name in parentheses, if it has one
+fn id_with_name(id: u32, name: Option<String>) -> String {
+    match name {
+        Some(name) => format!("{}({})", id, name),
+        None => id.to_string(),
+    }
 }
 
 fn display_user_info(args: &Args, userinfo: &UserInfo) {
     if args.e_user {
-        println!("{}", userinfo.euid);
+        let uid = if args.real {
+            userinfo.uid
+        } else {
+            userinfo.euid
+        };
+        println!("{}", id_str(uid, user_name(uid), args.name));
         return;
     }
 
     if args.group {
-        println!("{}", userinfo.egid);
+        let gid = if args.real {
+            userinfo.gid
+        } else {
+            userinfo.egid
+        };
+        println!("{}", id_str(gid, group_name(gid), args.name));
         return;
     }
 
     if args.groups {
-        for gid in &userinfo.groups {
-            print!("{} ", gid);
-        }
-        println!();
-        return;
-    }
-
-    if args.name {
-        let group_name = {
-            match userinfo.group_names.get(&userinfo.egid) {
-                None => "unknown",
-                Some(name) => name,
+        // the effective, real and supplementary group IDs, each once
+        let mut gids = vec![userinfo.egid];
+        for gid in std::iter::once(&userinfo.gid).chain(&userinfo.groups) {
+            if !gids.contains(gid) {
+                gids.push(*gid);
             }
-        };
-        println!(
-            "uid={}({}) gid={}({}) groups={}",
-            userinfo.uid, userinfo.username, userinfo.gid, group_name, userinfo.egid
-        );
-        for gid in &userinfo.groups {
-            print!("{}({}),", gid, userinfo.group_names[gid]);
         }
-        println!();
+        let gids: Vec<String> = gids
+            .iter()
+            .map(|gid| id_str(*gid, group_name(*gid), args.name))
+            .collect();
+        println!("{}", gids.join(" "));
         return;
     }
 
-    println!(
-        "uid={} gid={} groups={}",
-        userinfo.uid, userinfo.gid, userinfo.egid
+    let mut out = format!(
+        "uid={} gid={}",
+        id_with_name(userinfo.uid, user_name(userinfo.uid)),
+        id_with_name(userinfo.gid, group_name(userinfo.gid))
     );
-    for gid in &userinfo.groups {
-        print!("{},", gid);
+    if userinfo.euid != userinfo.uid {
+        out.push_str(&format!(
+            " euid={}",
+            id_with_name(userinfo.euid, user_name(userinfo.euid))
+        ));
+    }
+    if userinfo.egid != userinfo.gid {
+        out.push_str(&format!(
+            " egid={}",
+            id_with_name(userinfo.egid, group_name(userinfo.egid))
+        ));
+    }
+    if !userinfo.groups.is_empty() {
+        let mut gids: Vec<libc::gid_t> = Vec::new();
+        for gid in &userinfo.groups {
+            if !gids.contains(gid) {
+                gids.push(*gid);
+            }
+        }
+        let gids: Vec<String> = gids
+            .iter()
+            .map(|gid| id_with_name(*gid, group_name(*gid)))
+            .collect();
+        out.push_str(&format!(" groups={}", gids.join(",")));
     }
-    println!();
+    println!("{}", out);
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -203,8 +249,22 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     textdomain(PROJECT_NAME)?;
     bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
 
-    let mut userinfo = get_user_info(&args)?;
-    get_group_info(&mut userinfo)?;
+    let userinfo = match args.user {
+        None => match process_info() {
+            Ok(userinfo) => userinfo,
+            Err(e) => {
+                eprintln!("id: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Some(ref user) => match named_info(user) {
+            Some(userinfo) => userinfo,
+            None => {
+                eprintln!("id: '{}': {}", user, gettext("no such user"));
+                std::process::exit(1);
+            }
+        },
+    };
 
     display_user_info(&args, &userinfo);
 
@@ -7,32 +7,255 @@
 // SPDX-License-Identifier: MIT
 //
 
+extern crate clap;
+extern crate libc;
 extern crate plib;
 
-use gettextrs::{bind_textdomain_codeset, textdomain};
+use clap::Parser;
+use gettextrs::{bind_textdomain_codeset, gettext, textdomain};
 use plib::PROJECT_NAME;
-use syslog::{Facility, Formatter3164};
+use std::ffi::CStr;
+use std::io::{self, BufRead, Write};
+use std::os::unix::net::{UnixDatagram, UnixStream};
+use std::path::{Path, PathBuf};
+
+// the socket of the system log
+#[cfg(target_os = "macos")]
+const LOG_SOCKET: &str = "/var/run/syslog";
+#[cfg(not(target_os = "macos"))]
+const LOG_SOCKET: &str = "/dev/log";
+
+// the longest message sent, as syslog daemons truncate longer ones
+const MAX_MESSAGE: usize = 8192;
+
+const FACILITIES: &[(&str, libc::c_int)] = &[
+    ("kern", libc::LOG_KERN),
+    ("user", libc::LOG_USER),
+    ("mail", libc::LOG_MAIL),
+    ("daemon", libc::LOG_DAEMON),
+    ("auth", libc::LOG_AUTH),
+    ("security", libc::LOG_AUTH),
+    ("syslog", libc::LOG_SYSLOG),
+    ("lpr", libc::LOG_LPR),
+    ("news", libc::LOG_NEWS),
+    ("uucp", libc::LOG_UUCP),
+    ("cron", libc::LOG_CRON),
+    ("authpriv", libc::LOG_AUTHPRIV),
+    ("ftp", libc::LOG_FTP),
+    ("local0", libc::LOG_LOCAL0),
+    ("local1", libc::LOG_LOCAL1),
+    ("local2", libc::LOG_LOCAL2),
+    ("local3", libc::LOG_LOCAL3),
+    ("local4", libc::LOG_LOCAL4),
+    ("local5", libc::LOG_LOCAL5),
+    ("local6", libc::LOG_LOCAL6),
+    ("local7", libc::LOG_LOCAL7),
+];
+
+const LEVELS: &[(&str, libc::c_int)] = &[
+    ("emerg", libc::LOG_EMERG),
+    ("panic", libc::LOG_EMERG),
+    ("alert", libc::LOG_ALERT),
+    ("crit", libc::LOG_CRIT),
+    ("err", libc::LOG_ERR),
+    ("error", libc::LOG_ERR),
+    ("warning", libc::LOG_WARNING),
+    ("warn", libc::LOG_WARNING),
+    ("notice", libc::LOG_NOTICE),
+    ("info", libc::LOG_INFO),
+    ("debug", libc::LOG_DEBUG),
+];
+
+/// logger - log messages
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about)]
+struct Args {
+    /// Log the message with the given priority, as facility.level or a
+    /// level alone (in the user facility), or a number.
+    #[arg(short, long, default_value = "user.notice", value_parser = parse_priority)]
+    priority: libc::c_int,
+
+    /// Mark each message with TAG, instead of the login name.
+    #[arg(short, long)]
+    tag: Option<String>,
+
+    /// Include the process ID of logger in each message.
+    #[arg(short = 'i', long = "id")]
+    pid: bool,
+
+    /// Write to the datagram socket SOCKET, instead of the system log.
+    #[arg(short = 'u', long, value_name = "SOCKET")]
+    socket: Option<PathBuf>,
+
+    /// The message to log; without it, each line of standard input is logged.
+    message: Vec<String>,
+}
+
+fn lookup(table: &[(&str, libc::c_int)], name: &str) -> Option<libc::c_int> {
+    table
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, value)| *value)
+}
+
+fn parse_priority(s: &str) -> Result<libc::c_int, String> {
+    if let Ok(n) = s.parse::<libc::c_int>() {
+        return Ok(n);
+    }
+    let (facility, level) = match s.split_once('.') {
+        Some((facility, level)) => (
+            lookup(FACILITIES, facility)
+                .ok_or_else(|| gettext!("unknown facility name: {}", facility))?,
+            level,
+        ),
+        None => (libc::LOG_USER, s),
+    };
+    let level =
+        lookup(LEVELS, level).ok_or_else(|| gettext!("unknown priority name: {}", level))?;
+    Ok(facility | level)
+}
+
+// the login name, for the default tag
+fn login_name() -> String {
+    let login = unsafe { libc::getlogin() };
+    if !login.is_null() {
+        return unsafe { CStr::from_ptr(login) }
+            .to_string_lossy()
+            .into_owned();
+    }
+    let passwd = unsafe { libc::getpwuid(libc::getuid()) };
+    if !passwd.is_null() {
+        return unsafe { CStr::from_ptr((*passwd).pw_name) }
+            .to_string_lossy()
+            .into_owned();
+    }
+    String::from("logger")
+}
+
+// the local time as "Mmm dd hh:mm:ss", the timestamp of the BSD syslog
+// protocol (RFC 3164)
+fn timestamp() -> String {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let now = unsafe { libc::time(std::ptr::null_mut()) };
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe { libc::localtime_r(&now, &mut tm) };
+    format!(
+        "{} {:>2} {:02}:{:02}:{:02}",
+        MONTHS[tm.tm_mon as usize % 12],
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
+
+/// A connection to the system log.
+enum Connection {
+    Datagram(UnixDatagram),
+    Stream(UnixStream),
+}
+
+impl Connection {
+    fn open(path: &Path) -> io::Result<Connection> {
+        let socket = UnixDatagram::unbound()?;
+        match socket.connect(path) {
+            Ok(()) => Ok(Connection::Datagram(socket)),
+
+            // some systems listen on a stream socket instead
+            Err(e) if e.raw_os_error() == Some(libc::EPROTOTYPE) => {
+                Ok(Connection::Stream(UnixStream::connect(path)?))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn send(&mut self, packet: &[u8]) -> io::Result<()> {
+        match self {
+            Connection::Datagram(socket) => socket.send(packet).map(|_| ()),
+
+            // stream messages are delimited by a null byte
+            Connection::Stream(stream) => {
+                stream.write_all(packet)?;
+                stream.write_all(b"\0")
+            }
+        }
+    }
+}
+
+struct Logger {
+    connection: Connection,
+    priority: libc::c_int,
+    tag: String,
+}
+
+impl Logger {
+    /// Send `message` as "<priority>timestamp tag[pid]: message".
+    fn log(&mut self, message: &str) -> io::Result<()> {
+        let mut packet = format!(
+            "<{}>{} {}: {}",
+            self.priority,
+            timestamp(),
+            self.tag,
+            message
+        );
+        if packet.len() > MAX_MESSAGE {
+            let mut end = MAX_MESSAGE;
+            while !packet.is_char_boundary(end) {
+                end -= 1;
+            }
+            packet.truncate(end);
+        }
+        self.connection.send(packet.as_bytes())
+    }
+}
+
+fn log_input(logger: &mut Logger) -> io::Result<()> {
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if !line.is_empty() {
+            logger.log(&line)?;
+        }
+    }
+    Ok(())
+}
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // parse command line arguments
+    let args = Args::parse();
+
     textdomain(PROJECT_NAME)?;
     bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
 
-    let mut args: Vec<String> = std::env::args().collect();
-    args.remove(0);
-    let log_str = args.join(" ");
+    let path = args.socket.as_deref().unwrap_or(Path::new(LOG_SOCKET));
+    let connection = match Connection::open(path) {
+        Ok(connection) => connection,
+        Err(e) => {
+            eprintln!("logger: {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut tag = args.tag.clone().unwrap_or_else(login_name);
+    if args.pid {
+        tag = format!("{}[{}]", tag, std::process::id());
+    }
+    let mut logger = Logger {
+        connection,
+        priority: args.priority,
+        tag,
+    };
 
-    let formatter = Formatter3164 {
-        facility: Facility::LOG_USER,
-        hostname: None,
-        process: "logger".into(),
-        pid: 0,
+    let result = if args.message.is_empty() {
+        log_input(&mut logger)
+    } else {
+        logger.log(&args.message.join(" "))
     };
 
-    match syslog::unix(formatter) {
-        Err(e) => eprintln!("Unable to connect to syslog: {:?}", e),
-        Ok(mut writer) => {
-            writer.err(&log_str).expect("could not write error message");
-        }
+    if let Err(e) = result {
+        eprintln!("logger: {}", e);
+        std::process::exit(1);
     }
 
     Ok(())
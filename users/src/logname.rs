@@ -7,28 +7,37 @@
 // SPDX-License-Identifier: MIT
 //
 
+extern crate clap;
 extern crate libc;
+extern crate plib;
+
+use clap::Parser;
+use gettextrs::{bind_textdomain_codeset, gettext, textdomain};
+use plib::PROJECT_NAME;
 use std::ffi::CStr;
 
-fn main() {
-    let username = unsafe {
-        // Call getlogin to get the username as a *mut c_char
-        let c_str = libc::getlogin();
+/// logname - return the user's login name
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about)]
+struct Args {}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // parse command line arguments
+    let _args = Args::parse();
 
-        // Check if the pointer is not null
-        if c_str.is_null() {
-            panic!("Failed to get login name");
-        }
+    textdomain(PROJECT_NAME)?;
+    bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
 
-        // Convert the *mut c_char to a &CStr
-        let c_str = CStr::from_ptr(c_str);
+    // getlogin() finds the user logged in on the controlling terminal,
+    // rather than trusting the environment
+    let login = unsafe { libc::getlogin() };
+    if login.is_null() {
+        eprintln!("logname: {}", gettext("no login name"));
+        std::process::exit(1);
+    }
 
-        // Convert the &CStr to a Rust String
-        match c_str.to_str() {
-            Ok(s) => s.to_owned(), // Successfully converted CStr to Rust String
-            Err(e) => panic!("Failed to convert login name to a Rust String: {}", e),
-        }
-    };
+    let login = unsafe { CStr::from_ptr(login) };
+    println!("{}", login.to_string_lossy());
 
-    println!("{}", username);
+    Ok(())
 }
@@ -0,0 +1,151 @@
+//
+// Copyright (c) 2024 Hemi Labs, Inc.
+//
+// This file is part of the posixutils-rs project covered under
+// the MIT License.  For the full license text, please see the LICENSE
+// file in the root directory of this project.
+// SPDX-License-Identifier: MIT
+//
+
+use std::process::{Command, Output};
+
+fn id(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_id"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn id_out(args: &[&str]) -> String {
+    let output = id(args);
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_id_numeric() {
+    let euid = unsafe { libc::geteuid() };
+    let egid = unsafe { libc::getegid() };
+    let uid = unsafe { libc::getuid() };
+    assert_eq!(id_out(&["-u"]), format!("{}\n", euid));
+    assert_eq!(id_out(&["-g"]), format!("{}\n", egid));
+    assert_eq!(id_out(&["-ur"]), format!("{}\n", uid));
+
+    // the effective group ID comes first
+    let groups = id_out(&["-G"]);
+    assert_eq!(groups.split_whitespace().next(), Some(&*egid.to_string()));
+}
+
+#[test]
+fn test_id_default_format() {
+    let out = id_out(&[]);
+    let uid = unsafe { libc::getuid() };
+    assert!(out.starts_with(&format!("uid={}", uid)));
+    assert!(out.contains(" gid="));
+}
+
+#[test]
+fn test_id_root() {
+    assert_eq!(id_out(&["-un", "root"]), "root\n");
+    assert_eq!(id_out(&["-u", "root"]), "0\n");
+    assert!(id_out(&["root"]).starts_with("uid=0(root) gid=0("));
+}
+
+#[test]
+fn test_id_errors() {
+    let output = id(&["no-such-user-here"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "id: 'no-such-user-here': no such user\n"
+    );
+
+    // -n and -r select a form of -u, -g or -G
+    assert_eq!(id(&["-n"]).status.code(), Some(2));
+}
+
+// run logger writing to a datagram socket of its own, returning the
+// messages received
+fn logger(name: &str, args: &[&str], stdin: &str) -> Vec<String> {
+    use std::io::Write;
+    use std::os::unix::net::UnixDatagram;
+    use std::process::Stdio;
+
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let path = dir.join(format!("{}.sock", name));
+    let _ = std::fs::remove_file(&path);
+    let socket = UnixDatagram::bind(&path).unwrap();
+    socket.set_nonblocking(true).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_logger"))
+        .arg("-u")
+        .arg(&path)
+        .args(args)
+        .stdin(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    assert!(child.wait().unwrap().success());
+
+    let mut messages = Vec::new();
+    let mut buf = [0u8; 1024];
+    while let Ok(n) = socket.recv(&mut buf) {
+        messages.push(String::from_utf8_lossy(&buf[..n]).into_owned());
+    }
+    let _ = std::fs::remove_file(&path);
+    messages
+}
+
+#[test]
+fn test_logger_message() {
+    let messages = logger("logger_message", &["-t", "test", "hello", "world"], "");
+    assert_eq!(messages.len(), 1);
+
+    // "<13>Mmm dd hh:mm:ss test: hello world", user.notice by default
+    let message = &messages[0];
+    assert!(message.starts_with("<13>"));
+    assert!(message.ends_with(" test: hello world"));
+    assert_eq!(
+        message.len(),
+        "<13>".len() + 15 + " test: hello world".len()
+    );
+}
+
+#[test]
+fn test_logger_priority_pid() {
+    let messages = logger(
+        "logger_priority",
+        &["-p", "local3.err", "-t", "tag", "-i", "msg"],
+        "",
+    );
+    assert_eq!(messages.len(), 1);
+    assert!(messages[0].starts_with("<155>"));
+    assert!(messages[0].contains(" tag["));
+    assert!(messages[0].ends_with("]: msg"));
+}
+
+#[test]
+fn test_logger_stdin() {
+    let messages = logger(
+        "logger_stdin",
+        &["-p", "daemon.info", "-t", "t"],
+        "one\n\ntwo\n",
+    );
+    assert_eq!(messages.len(), 2);
+    assert!(messages[0].starts_with("<30>") && messages[0].ends_with(" t: one"));
+    assert!(messages[1].starts_with("<30>") && messages[1].ends_with(" t: two"));
+}
+
+#[test]
+fn test_logger_bad_priority() {
+    let output = Command::new(env!("CARGO_BIN_EXE_logger"))
+        .args(["-p", "nosuch.err", "msg"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
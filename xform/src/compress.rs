@@ -7,12 +7,17 @@
 // SPDX-License-Identifier: MIT
 //
 
+extern crate atty;
+
 use clap::Parser;
 use gettextrs::{bind_textdomain_codeset, gettext, textdomain};
-use plib::lzw::UnixLZWWriter;
+use plib::io::prompt_user;
+use plib::lzw::{UnixLZWWriter, DEFAULT_BITS, MAX_BITS};
 use plib::PROJECT_NAME;
-use std::fs::{self, File};
-use std::io::{self, Write};
+use std::ffi::OsString;
+use std::fs::{self, File, FileTimes};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
 
 const NAME_MAX: usize = 255;
@@ -21,15 +26,15 @@ const NAME_MAX: usize = 255;
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about)]
 struct Args {
-    /// Specify the maximum number of bits to use in a code. 9 <= bits <= 14
-    #[arg(short = 'b')]
-    bits: Option<u32>,
+    /// Specify the maximum number of bits to use in a code. 9 <= bits <= 16
+    #[arg(short = 'b', value_parser = clap::value_parser!(u32).range(9..=MAX_BITS as i64), default_value_t = DEFAULT_BITS)]
+    bits: u32,
 
     /// Write to the standard output; the input file is not changed, and no .Z files are created.
     #[arg(short = 'c', long)]
     stdout: bool,
 
-    /// Do not prompt for overwriting files
+    /// Do not prompt for overwriting files, and compress files even if they do not shrink.
     #[arg(short = 'f', long)]
     force: bool,
 
@@ -37,99 +42,162 @@ struct Args {
     #[arg(short = 'v', long)]
     verbose: bool,
 
-    /// Files to read as input.  Use "-" or no-args for stdin.
+    /// Files to compress.  Without files, standard input is compressed to standard output.
     files: Vec<PathBuf>,
 }
 
-fn prompt_user(prompt: &str) -> bool {
-    eprint!("compress: {} ", prompt);
-    let mut response = String::new();
-    io::stdin().read_line(&mut response).unwrap();
-    response.to_lowercase().starts_with('y')
+// percentage of the input saved by compression
+fn savings(bytes_in: u64, bytes_out: u64) -> f64 {
+    if bytes_in == 0 {
+        return 0.0;
+    }
+    100.0 * (1.0 - bytes_out as f64 / bytes_in as f64)
 }
 
-fn compress_file(args: &Args, pathname: &PathBuf) -> io::Result<i32> {
-    let mut file = plib::io::input_stream(pathname, false)?;
-
-    let mut encoder = UnixLZWWriter::new(args.bits);
-
-    let mut inp_buf = Vec::new();
-    file.read_to_end(&mut inp_buf)?;
-    let inp_buf_size = inp_buf.len();
-
-    let mut out_buf = encoder.write(&inp_buf)?;
-    out_buf.extend_from_slice(&encoder.close()?);
-    let out_buf_size = out_buf.len();
-
-    // check if the output buffer size is greater than input buffer size
-    // this can indicate that the compressed file is greater than the input file
-    //
-    // in such case using '-f' works, i.e "force", which means we don't care if size
-    // isn't reduced, just compress it
-    if (out_buf_size < inp_buf_size) || args.force {
-        if args.stdout {
-            io::stdout().write_all(&out_buf)?;
-        } else {
-            let fname = format!("{}.Z", pathname.file_name().unwrap().to_str().unwrap());
-            // If "on adding .Z" the name exceeds the limit of NAME_MAX, then we output on the stdout
-            if fname.len() > NAME_MAX {
-                io::stdout().write_all(&out_buf)?;
-            } else {
-                let path = Path::new(&fname);
-                if path.exists() && !args.force {
-                    let is_affirm = prompt_user(&gettext!(
-                        "Do you want to overwrite {} (y)es or (n)o?",
-                        fname
-                    ));
-
-                    if !is_affirm {
-                        println!("{fname} not overwritten");
-                        return Ok(1);
-                    }
-                }
+/// Compress `input` to `output`, returning the number of bytes read and
+/// written.
+fn compress_stream(
+    args: &Args,
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+) -> io::Result<(u64, u64)> {
+    let mut encoder = UnixLZWWriter::new(BufWriter::new(output), args.bits)?;
+    io::copy(input, &mut encoder)?;
+    encoder.finish()?;
+    Ok((encoder.bytes_in(), encoder.bytes_out()))
+}
 
-                let mut new_file = pathname.clone();
-                new_file.set_file_name(format!("{fname}"));
-                let mut f = File::create(&new_file)?;
+// the compressed file keeps the mode, owner and times of the original
+fn copy_attributes(md: &fs::Metadata, file: &File) -> io::Result<()> {
+    // the owner may not be changed by ordinary users
+    let _ = std::os::unix::fs::fchown(file, Some(md.uid()), Some(md.gid()));
+    file.set_permissions(md.permissions())?;
+    let times = FileTimes::new()
+        .set_accessed(md.accessed()?)
+        .set_modified(md.modified()?);
+    file.set_times(times)
+}
+
+/// Compress the file at `path` to `path.Z`, replacing it.  Returns false
+/// if the file was left unchanged because it would not shrink.
+fn compress_file(args: &Args, path: &Path) -> io::Result<bool> {
+    let md = fs::metadata(path)?;
+    if !md.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            gettext("not a regular file"),
+        ));
+    }
+    let mut input = BufReader::new(File::open(path)?);
+
+    if args.stdout {
+        let (bytes_in, bytes_out) = compress_stream(args, &mut input, &mut io::stdout().lock())?;
+        if args.verbose {
+            eprintln!(
+                "{}: {} {:.2}%",
+                path.display(),
+                gettext("Compression:"),
+                savings(bytes_in, bytes_out)
+            );
+        }
+        return Ok(true);
+    }
 
-                fs::remove_file(&pathname)?;
-                f.write_all(&out_buf)?;
+    let mut target = OsString::from(path.as_os_str());
+    target.push(".Z");
+    let target = PathBuf::from(target);
+    let name_len = target.file_name().map_or(0, |name| name.len());
+    if name_len > NAME_MAX {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            gettext("file name too long"),
+        ));
+    }
 
-                if args.verbose {
-                    println!(
-                        "{}: -- replaced with {} Compression: {:.1}%",
-                        pathname.display(),
-                        new_file.display(),
-                        100_f32 - (out_buf_size as f32 / inp_buf_size as f32) * 100_f32
-                    );
-                }
-            }
+    if target.exists() && !args.force {
+        let overwrite = atty::is(atty::Stream::Stdin)
+            && prompt_user("compress", &gettext!("overwrite {}?", target.display()));
+        if !overwrite {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                gettext!("{} already exists", target.display()),
+            ));
+        }
+    }
+
+    let mut file = File::create(&target)?;
+    let result = compress_stream(args, &mut input, &mut file);
+    let (bytes_in, bytes_out) = match result {
+        Ok(counts) => counts,
+        Err(e) => {
+            let _ = fs::remove_file(&target);
+            return Err(e);
+        }
+    };
+
+    if bytes_out >= bytes_in && !args.force {
+        fs::remove_file(&target)?;
+        if args.verbose {
+            eprintln!(
+                "{}: {}",
+                path.display(),
+                gettext("No compression -- file unchanged")
+            );
         }
-    } else {
-        // error status code when file was not compressed as they would have increased in size and (-f
-        // was not provided)
-        return Ok(2);
+        return Ok(false);
     }
 
-    Ok(0)
+    copy_attributes(&md, &file)?;
+    fs::remove_file(path)?;
+
+    if args.verbose {
+        eprintln!(
+            "{}: {} {:.2}% -- {} {}",
+            path.display(),
+            gettext("Compression:"),
+            savings(bytes_in, bytes_out),
+            gettext("replaced with"),
+            target.display()
+        );
+    }
+    Ok(true)
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let mut args = Args::parse();
+    let args = Args::parse();
 
     textdomain(PROJECT_NAME)?;
     bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
 
+    let mut exit_code = 0;
+
     if args.files.is_empty() {
-        args.files.push(PathBuf::new());
+        match compress_stream(&args, &mut io::stdin().lock(), &mut io::stdout().lock()) {
+            Ok((bytes_in, bytes_out)) => {
+                if args.verbose {
+                    eprintln!(
+                        "{} {:.2}%",
+                        gettext("Compression:"),
+                        savings(bytes_in, bytes_out)
+                    );
+                }
+            }
+            Err(e) => {
+                exit_code = 1;
+                eprintln!("compress: {}", e);
+            }
+        }
     }
 
-    let mut exit_code = 0;
-
+    // files that were not compressed because they would not shrink give
+    // exit status 2, unless there were errors
     for filename in &args.files {
         match compress_file(&args, filename) {
-            Ok(v) => {
-                exit_code = v;
+            Ok(true) => {}
+            Ok(false) => {
+                if exit_code == 0 {
+                    exit_code = 2;
+                }
             }
             Err(e) => {
                 exit_code = 1;
@@ -6,20 +6,22 @@
 // file in the root directory of this project.
 // SPDX-License-Identifier: MIT
 //
-// TODO:
-// - support NOT writing to stdout (but to file.Z, with .Z suffix removed)
-// - support options -f, -v
-//
 
+extern crate atty;
 extern crate clap;
 extern crate plib;
 
 use clap::Parser;
-use gettextrs::{bind_textdomain_codeset, textdomain};
+use gettextrs::{bind_textdomain_codeset, gettext, textdomain};
+use plib::io::prompt_user;
 use plib::lzw::UnixLZWReader;
 use plib::PROJECT_NAME;
-use std::io::{self, Write};
-use std::path::PathBuf;
+use std::ffi::OsString;
+use std::fs::{self, File, FileTimes};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
 
 /// uncompress - expand compressed data
 #[derive(Parser, Debug)]
@@ -37,23 +39,83 @@ struct Args {
     #[arg(short, long)]
     verbose: bool,
 
-    /// Files to read as input.  Use "-" or no-args for stdin.
+    /// Files to expand; the .Z suffix may be omitted.  Without files,
+    /// standard input is expanded to standard output.
     files: Vec<PathBuf>,
 }
 
-fn uncompress_file(pathname: &PathBuf) -> io::Result<()> {
-    let file = plib::io::input_stream(pathname, false)?;
-    let mut decoder = UnixLZWReader::new(file);
+fn uncompress_stream(input: &mut dyn Read, output: &mut dyn Write) -> io::Result<()> {
+    let mut decoder = UnixLZWReader::new(BufReader::new(input));
+    let mut output = BufWriter::new(output);
+    io::copy(&mut decoder, &mut output)?;
+    output.flush()
+}
 
-    loop {
-        let buf = decoder.read()?;
-        if buf.is_empty() {
-            break;
+// the expanded file keeps the mode, owner and times of the compressed one
+fn copy_attributes(md: &fs::Metadata, file: &File) -> io::Result<()> {
+    // the owner may not be changed by ordinary users
+    let _ = std::os::unix::fs::fchown(file, Some(md.uid()), Some(md.gid()));
+    file.set_permissions(md.permissions())?;
+    let times = FileTimes::new()
+        .set_accessed(md.accessed()?)
+        .set_modified(md.modified()?);
+    file.set_times(times)
+}
+
+// the names of the compressed file and of the expanded file
+fn file_names(path: &Path) -> (PathBuf, PathBuf) {
+    let bytes = path.as_os_str().as_bytes();
+    match bytes.strip_suffix(b".Z") {
+        Some(stem) => (
+            path.to_path_buf(),
+            PathBuf::from(std::ffi::OsStr::from_bytes(stem)),
+        ),
+        None => {
+            let mut compressed = OsString::from(path.as_os_str());
+            compressed.push(".Z");
+            (PathBuf::from(compressed), path.to_path_buf())
         }
+    }
+}
+
+/// Expand the file at `path` (or `path.Z`), replacing it by the file
+/// without the .Z suffix.
+fn uncompress_file(args: &Args, path: &Path) -> io::Result<()> {
+    let (compressed, target) = file_names(path);
+    let md = fs::metadata(&compressed)?;
+    let mut input = File::open(&compressed)?;
 
-        io::stdout().write_all(&buf)?;
+    if args.stdout {
+        return uncompress_stream(&mut input, &mut io::stdout().lock());
+    }
+
+    if target.exists() && !args.force {
+        let overwrite = atty::is(atty::Stream::Stdin)
+            && prompt_user("uncompress", &gettext!("overwrite {}?", target.display()));
+        if !overwrite {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                gettext!("{} already exists", target.display()),
+            ));
+        }
     }
 
+    let mut file = File::create(&target)?;
+    if let Err(e) = uncompress_stream(&mut input, &mut file) {
+        let _ = fs::remove_file(&target);
+        return Err(e);
+    }
+    copy_attributes(&md, &file)?;
+    fs::remove_file(&compressed)?;
+
+    if args.verbose {
+        eprintln!(
+            "{}: -- {} {}",
+            compressed.display(),
+            gettext("replaced with"),
+            target.display()
+        );
+    }
     Ok(())
 }
 
@@ -69,11 +131,6 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     textdomain(PROJECT_NAME)?;
     bind_textdomain_codeset(PROJECT_NAME, "UTF-8")?;
 
-    // if no file args, read from stdin
-    if args.files.is_empty() {
-        args.files.push(PathBuf::new());
-    }
-
     // zcat is a special case:  always write to stdout
     if prog_is_zcat() {
         args.stdout = true;
@@ -81,8 +138,15 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let mut exit_code = 0;
 
+    if args.files.is_empty() {
+        if let Err(e) = uncompress_stream(&mut io::stdin().lock(), &mut io::stdout().lock()) {
+            exit_code = 1;
+            eprintln!("uncompress: {}", e);
+        }
+    }
+
     for filename in &args.files {
-        if let Err(e) = uncompress_file(filename) {
+        if let Err(e) = uncompress_file(&args, filename) {
             exit_code = 1;
             eprintln!("{}: {}", filename.display(), e);
         }
@@ -10,8 +10,9 @@
 use plib::{run_test, TestPlan};
 use std::{
     fs::{remove_file, File, Permissions},
-    io::Read,
+    io::{Read, Write},
     os::unix::fs::PermissionsExt,
+    process::{Command, Output, Stdio},
 };
 
 const RWX: u32 = 0o7;
@@ -168,7 +169,7 @@ fn test_compression_compress_file() {
 
     compress_test(&[file.to_str().unwrap()], "", "");
 
-    uncompress_test(&[compressed_file_path.to_str().unwrap()], &buf, "");
+    uncompress_test(&["-c", compressed_file_path.to_str().unwrap()], &buf, "");
 
     // Delete the compressed file(if test is successful)
     if compressed_file_path.exists() {
@@ -176,6 +177,126 @@ fn test_compression_compress_file() {
     }
 }
 
+// Run `cmd` directly, for binary input and output
+fn run_binary(cmd: &str, args: &[&str], stdin: &[u8]) -> Output {
+    let path = match cmd {
+        "compress" => env!("CARGO_BIN_EXE_compress"),
+        _ => env!("CARGO_BIN_EXE_uncompress"),
+    };
+    let mut child = Command::new(path)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_compress_stdin_roundtrip() {
+    let data: Vec<u8> = (0..200_000u32)
+        .map(|i| b"posixutils compress "[((i % 20) * (i % 7) % 20) as usize])
+        .collect();
+
+    for bits in ["9", "12", "16"] {
+        let output = run_binary("compress", &["-b", bits], &data);
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(output.stdout[..2], [0x1f, 0x9d]);
+        assert_eq!(output.stdout[2], 0x80 | bits.parse::<u8>().unwrap());
+        assert!(output.stdout.len() < data.len() / 2);
+
+        let expanded = run_binary("uncompress", &[], &output.stdout);
+        assert_eq!(expanded.status.code(), Some(0));
+        assert_eq!(expanded.stdout, data);
+    }
+
+    let output = run_binary("compress", &["-b", "8"], b"");
+    assert_eq!(output.status.code(), Some(2));
+
+    let output = run_binary("uncompress", &[], b"not compressed");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "uncompress: not in compressed format\n"
+    );
+}
+
+#[test]
+fn test_compress_replace_file() {
+    let dir = &format!("{}/test_compress_replace_file", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::create_dir(dir).unwrap();
+    let file = format!("{dir}/data.txt");
+    let data = "the quick brown fox jumps over the lazy dog\n".repeat(100);
+    std::fs::write(&file, &data).unwrap();
+    std::fs::set_permissions(&file, Permissions::from_mode(0o640)).unwrap();
+
+    let output = run_binary("compress", &["-v", &file], b"");
+    assert_eq!(output.status.code(), Some(0));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with(&format!("{file}: Compression: ")));
+    assert!(stderr.ends_with(&format!("% -- replaced with {file}.Z\n")));
+    assert!(!std::path::Path::new(&file).exists());
+    let md = std::fs::metadata(format!("{file}.Z")).unwrap();
+    assert_eq!(md.permissions().mode() & 0o777, 0o640);
+
+    // the .Z suffix may be omitted
+    let output = run_binary("uncompress", &["-v", &file], b"");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!("{file}.Z: -- replaced with {file}\n")
+    );
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), data);
+    assert!(!std::path::Path::new(&format!("{file}.Z")).exists());
+
+    // -c leaves the file alone
+    let output = run_binary("compress", &["-c", &file], b"");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(std::path::Path::new(&file).exists());
+    let expanded = run_binary("uncompress", &["-c"], &output.stdout);
+    assert_eq!(expanded.stdout, data.as_bytes());
+
+    // an existing .Z file is not overwritten without -f
+    std::fs::write(format!("{file}.Z"), b"").unwrap();
+    let output = run_binary("compress", &[&file], b"");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!("{file}: {file}.Z already exists\n")
+    );
+    let output = run_binary("compress", &["-f", &file], b"");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(!std::path::Path::new(&file).exists());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_compress_no_shrink() {
+    let dir = &format!("{}/test_compress_no_shrink", env!("CARGO_TARGET_TMPDIR"));
+    std::fs::create_dir(dir).unwrap();
+    let file = format!("{dir}/small");
+    std::fs::write(&file, b"a").unwrap();
+
+    // files that would grow are left alone, unless -f is given
+    let output = run_binary("compress", &["-v", &file], b"");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!("{file}: No compression -- file unchanged\n")
+    );
+    assert!(std::path::Path::new(&file).exists());
+    assert!(!std::path::Path::new(&format!("{file}.Z")).exists());
+
+    let output = run_binary("compress", &["-f", &file], b"");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(std::path::Path::new(&format!("{file}.Z")).exists());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
 #[test]
 fn test_uuencode_uudecode_with_historical_encoding_text_file() {
     use std::env;